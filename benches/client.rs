@@ -0,0 +1,229 @@
+extern crate criterion;
+extern crate http_api_service;
+
+use std::convert::TryFrom;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use formdata::FormData;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, HeaderMap, Method, Request, Response, Server};
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Runtime;
+
+use http_api_service::bind_hyper::{self, DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM};
+use http_api_service::bind_ureq;
+use http_api_service::simple_api::{
+    AsyncBodySerializer, BodySerializer, Headers, MakeApi, DEFAULT_MULTIPART_SERIALIZER,
+    DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
+};
+use http_api_service::{path_param, query_param};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct Product {
+    name: String,
+    age: String,
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+// Start the stub server echoing the request bodies (or a fixed product), on a random port
+fn start_stub_server(runtime: &Runtime) -> SocketAddr {
+    runtime.block_on(async {
+        let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|req: Request<Body>| async move {
+                let bytes = hyper::body::to_bytes(req.into_body()).await?;
+                let body = if bytes.is_empty() {
+                    Body::from("{\"name\":\"Baxter\",\"age\":\"1 month\"}")
+                } else {
+                    Body::from(bytes)
+                };
+                Ok::<_, hyper::Error>(Response::new(body))
+            }))
+        }));
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    })
+}
+
+fn bench_request_build(c: &mut Criterion) {
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse("http://localhost:3000").unwrap());
+    let header_map =
+        bind_hyper::add_header_authentication_bearer(Headers::new(), "MY_TOKEN").unwrap();
+    base_service_setter.set_default_header(Some(header_map));
+
+    c.bench_function("request_build", |b| {
+        b.iter(|| {
+            common_api
+                .simple_api
+                .lock()
+                .unwrap()
+                .make_request(
+                    Method::GET,
+                    "/products/{id}",
+                    "application/json",
+                    Some(path_param!["id" => "3"]),
+                    Some(query_param!["soft" => "true"]),
+                    Body::empty(),
+                )
+                .unwrap()
+        })
+    });
+}
+
+fn bench_header_merge(c: &mut Criterion) {
+    let mut group = c.benchmark_group("header_merge");
+    for count in [4, 32].iter() {
+        let mut default_header = Headers::new();
+        for i in 0..*count {
+            default_header.append(format!("x-default-{}", i), format!("value {}", i));
+        }
+
+        group.bench_with_input(
+            BenchmarkId::new("headers_to_header_map", count),
+            &default_header,
+            |b, default_header| {
+                b.iter(|| {
+                    let header = bind_hyper::add_header_authentication_bearer(
+                        default_header.clone(),
+                        "MY_TOKEN",
+                    )
+                    .unwrap();
+                    HeaderMap::try_from(&header).unwrap()
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_multipart(c: &mut Criterion) {
+    let runtime = runtime();
+    let mut group = c.benchmark_group("multipart");
+    for size in [1_024, 1_024 * 1_024].iter() {
+        let form_data = FormData {
+            fields: vec![
+                ("name".to_owned(), "Baxter".to_owned()),
+                ("content".to_owned(), "x".repeat(*size)),
+            ],
+            files: vec![],
+        };
+        group.throughput(Throughput::Bytes(*size as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("buffered", size),
+            &form_data,
+            |b, form_data| {
+                b.iter(|| {
+                    let (_, body): (String, Bytes) = DEFAULT_MULTIPART_SERIALIZER
+                        .encode(form_data.clone())
+                        .unwrap();
+                    body
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("streaming", size),
+            &form_data,
+            |b, form_data| {
+                b.iter(|| {
+                    runtime.block_on(async {
+                        let (_, body) = DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM
+                            .serialize(form_data.clone())
+                            .await
+                            .unwrap();
+                        hyper::body::to_bytes(body).await.unwrap()
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_end_to_end(c: &mut Criterion) {
+    let runtime = runtime();
+    let addr = start_stub_server(&runtime);
+    let base_url = url::Url::parse(&format!("http://{}", addr)).unwrap();
+    let json_serializer = Arc::new(DEFAULT_SERDE_JSON_SERIALIZER);
+    let json_deserializer = Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER);
+    let return_type_marker = &Product::default();
+
+    let mut group = c.benchmark_group("end_to_end");
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    common_api
+        .as_base_service_setter()
+        .set_base_url(base_url.clone());
+    let base_service_shared = common_api.as_base_service_shared();
+    let api_get_product = base_service_shared.api_no_body(
+        Method::GET,
+        "/products/{id}",
+        json_deserializer.clone(),
+        return_type_marker,
+    );
+    let api_post_product = base_service_shared.api_has_body(
+        Method::POST,
+        "/products/{id}",
+        "application/json",
+        json_serializer.clone(),
+        json_deserializer.clone(),
+        return_type_marker,
+    );
+    group.bench_function("hyper_get", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(api_get_product.call(Some(path_param!["id" => "3"])))
+                .unwrap()
+        })
+    });
+    group.bench_function("hyper_post", |b| {
+        b.iter(|| {
+            let sent_body = Product {
+                name: "Alien".to_string(),
+                age: "5 month".to_string(),
+            };
+            runtime
+                .block_on(api_post_product.call(Some(path_param!["id" => "5"]), sent_body))
+                .unwrap()
+        })
+    });
+
+    let common_api = bind_ureq::CommonAPI::new_for_ureq();
+    common_api.as_base_service_setter().set_base_url(base_url);
+    let base_service_shared = common_api.as_base_service_shared();
+    let api_get_product = base_service_shared.api_no_body(
+        Method::GET,
+        "/products/{id}",
+        json_deserializer,
+        return_type_marker,
+    );
+    group.bench_function("ureq_get", |b| {
+        b.iter(|| {
+            runtime
+                .block_on(api_get_product.call(Some(path_param!["id" => "3"])))
+                .unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_request_build,
+    bench_header_merge,
+    bench_multipart,
+    bench_end_to_end
+);
+criterion_main!(benches);
@@ -0,0 +1,84 @@
+extern crate criterion;
+extern crate http_api_service;
+
+use bytes::Bytes;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde::{Deserialize, Serialize};
+
+use http_api_service::simple_api::{
+    BodyDeserializer, BodySerializer, DEFAULT_SERDE_JSON_DESERIALIZER,
+    DEFAULT_SERDE_JSON_SERIALIZER, DEFAULT_SIMD_JSON_DESERIALIZER, DEFAULT_SIMD_JSON_SERIALIZER,
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Product {
+    id: u64,
+    name: String,
+    price: f64,
+    tags: Vec<String>,
+    meta: Option<String>,
+}
+
+fn products(count: u64) -> Vec<Product> {
+    (0..count)
+        .map(|id| Product {
+            id,
+            name: format!("Product {}", id),
+            price: id as f64 * 1.5,
+            tags: vec!["new".to_string(), "sale".to_string()],
+            meta: if id % 2 == 0 {
+                Some("{\"escaped\": \"json\"}".to_string())
+            } else {
+                None
+            },
+        })
+        .collect()
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for count in [10, 1_000, 10_000].iter() {
+        let body = Bytes::from(serde_json::to_vec(&products(*count)).unwrap());
+        group.throughput(Throughput::Bytes(body.len() as u64));
+
+        group.bench_with_input(BenchmarkId::new("serde_json", count), &body, |b, body| {
+            b.iter(|| {
+                let decoded: Box<Vec<Product>> =
+                    DEFAULT_SERDE_JSON_DESERIALIZER.decode(body).unwrap();
+                decoded
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("simd_json", count), &body, |b, body| {
+            b.iter(|| {
+                let decoded: Box<Vec<Product>> =
+                    DEFAULT_SIMD_JSON_DESERIALIZER.decode(body).unwrap();
+                decoded
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    for count in [10, 1_000, 10_000].iter() {
+        let list = products(*count);
+
+        group.bench_with_input(BenchmarkId::new("serde_json", count), &list, |b, list| {
+            b.iter(|| {
+                let encoded: Bytes = DEFAULT_SERDE_JSON_SERIALIZER.encode(list).unwrap();
+                encoded
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("simd_json", count), &list, |b, list| {
+            b.iter(|| {
+                let encoded: Bytes = DEFAULT_SIMD_JSON_SERIALIZER.encode(list).unwrap();
+                encoded
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode, bench_encode);
+criterion_main!(benches);
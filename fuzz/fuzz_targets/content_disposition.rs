@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use http_api_service::common::{
+    parse_content_disposition_filename, sanitize_filename, MAX_FILENAME_LENGTH,
+};
+
+fuzz_target!(|data: &[u8]| {
+    // Content dispositions from the untrusted responses
+    if let Ok(content_disposition) = std::str::from_utf8(data) {
+        if let Some(filename) = parse_content_disposition_filename(content_disposition) {
+            assert!(!filename.is_empty() && filename.len() <= MAX_FILENAME_LENGTH);
+            assert!(!filename.contains(['/', '\\']));
+            assert!(!filename.starts_with('.'));
+
+            // The sanitized ones are kept as they are
+            assert_eq!(Some(filename.clone()), sanitize_filename(&filename));
+        }
+    }
+});
@@ -0,0 +1,16 @@
+#![no_main]
+
+use bytes::Bytes;
+use futures::executor::block_on;
+use futures::stream;
+use libfuzzer_sys::fuzz_target;
+use multer::Multipart;
+
+use http_api_service::simple_http::multer_multipart_to_hash_map;
+
+// The bodies from the untrusted requests/responses
+fuzz_target!(|data: &[u8]| {
+    let body = stream::iter(vec![Ok::<_, std::io::Error>(Bytes::copy_from_slice(data))]);
+    let mut multipart = Multipart::new(body, "X-BOUNDARY");
+    let _ = block_on(multer_multipart_to_hash_map(&mut multipart));
+});
@@ -0,0 +1,23 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use http_api_service::simple_http::{
+    get_content_type_from_multipart_boundary, parse_multipart_boundary,
+    validate_multipart_boundary,
+};
+
+fuzz_target!(|data: &[u8]| {
+    // Content types from the untrusted headers
+    if let Ok(content_type) = std::str::from_utf8(data) {
+        if let Ok(boundary) = parse_multipart_boundary(content_type) {
+            assert!(validate_multipart_boundary(boundary.as_bytes()).is_ok());
+        }
+    }
+
+    // The valid boundaries survive the round trip
+    if let Ok(boundary) = validate_multipart_boundary(data) {
+        let content_type = get_content_type_from_multipart_boundary(data.to_vec()).unwrap();
+        assert_eq!(boundary, parse_multipart_boundary(&content_type).unwrap());
+    }
+});
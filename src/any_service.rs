@@ -0,0 +1,782 @@
+/*!
+In this module there're implementations of `AnyService`,
+the uniform API across bindings (dispatching to whichever binding feature is enabled).
+
+The methods are `http::Method` and the headers are `Headers`,
+and the request/response bodies are `Bytes` for all bindings.
+*/
+
+#[cfg(feature = "for_hyper")]
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::method::Method;
+use url::Url;
+
+use super::audit::AuditLog;
+use super::common::{
+    catch_hook_panic_async, parse_content_disposition_filename, DeprecationMonitor, DrainGate,
+    Headers, KeepAliveOptions, KeepAliveStats, MaintenancePolicy, OfflineError, OfflineSwitch,
+    PathParam, QueryParam, ReadOnlyGuard, ServiceStatus, TemplateVariables, UrlJoinStrategy,
+    UrlNormalization, UsageQuota, Versioning,
+};
+#[cfg(feature = "queue")]
+use super::queue::QueueStore;
+use super::simple_api::{BaseService, BodyDeserializer, BodySerializer};
+
+#[cfg(feature = "for_hyper")]
+use hyper::{
+    client::HttpConnector, Body, Client, HeaderMap, Request as HyperRequest,
+    Response as HyperResponse, Result as HyperResult,
+};
+
+#[cfg(feature = "for_ureq")]
+use ureq::{Agent, Header, Response as UreqResponse};
+
+#[cfg(feature = "for_ureq")]
+use super::bind_ureq::UreqRequest;
+
+#[cfg(feature = "for_hyper")]
+pub type HyperService = dyn BaseService<
+    Client<HttpConnector, Body>,
+    HyperRequest<Body>,
+    HyperResult<HyperResponse<Body>>,
+    Method,
+    HeaderMap,
+    Body,
+>;
+#[cfg(feature = "for_ureq")]
+pub type UreqService = dyn BaseService<
+    Agent,
+    UreqRequest,
+    StdResult<UreqResponse, Box<dyn StdError>>,
+    Method,
+    Vec<Header>,
+    Bytes,
+>;
+
+/*
+`AnyService` The uniform service dispatching to the binding
+*/
+#[derive(Clone)]
+pub enum AnyService {
+    #[cfg(feature = "for_hyper")]
+    Hyper(Arc<HyperService>),
+    #[cfg(feature = "for_ureq")]
+    Ureq(Arc<UreqService>),
+}
+
+impl AnyService {
+    #[cfg(feature = "for_hyper")]
+    pub fn new_for_hyper() -> Self {
+        AnyService::Hyper(super::bind_hyper::CommonAPI::new_for_hyper().as_base_service_shared())
+    }
+    #[cfg(feature = "for_ureq")]
+    pub fn new_for_ureq() -> Self {
+        AnyService::Ureq(super::bind_ureq::CommonAPI::new_for_ureq().as_base_service_shared())
+    }
+
+    pub fn set_base_url(&self, url: Url) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_base_url(url),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_base_url(url),
+        }
+    }
+    pub fn get_base_url(&self) -> Url {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_base_url(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_base_url(),
+        }
+    }
+    pub fn set_url_join_strategy(&self, strategy: UrlJoinStrategy) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_url_join_strategy(strategy),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_url_join_strategy(strategy),
+        }
+    }
+    pub fn get_url_join_strategy(&self) -> UrlJoinStrategy {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_url_join_strategy(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_url_join_strategy(),
+        }
+    }
+    pub fn set_url_normalization(&self, normalization: UrlNormalization) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_url_normalization(normalization),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_url_normalization(normalization),
+        }
+    }
+    pub fn get_url_normalization(&self) -> UrlNormalization {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_url_normalization(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_url_normalization(),
+        }
+    }
+    pub fn set_versioning(&self, versioning: Versioning) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_versioning(versioning),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_versioning(versioning),
+        }
+    }
+    pub fn get_versioning(&self) -> Versioning {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_versioning(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_versioning(),
+        }
+    }
+    pub fn set_template_variables(&self, variables: TemplateVariables) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_template_variables(variables),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_template_variables(variables),
+        }
+    }
+    pub fn get_template_variables(&self) -> TemplateVariables {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_template_variables(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_template_variables(),
+        }
+    }
+    pub fn set_offline(&self, offline: bool) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_offline(offline),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_offline(offline),
+        }
+    }
+    pub fn is_offline(&self) -> bool {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.is_offline(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.is_offline(),
+        }
+    }
+    pub fn set_offline_switch(&self, offline: OfflineSwitch) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_offline_switch(offline),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_offline_switch(offline),
+        }
+    }
+    pub fn get_offline_switch(&self) -> OfflineSwitch {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_offline_switch(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_offline_switch(),
+        }
+    }
+    pub fn set_drain_gate(&self, gate: DrainGate) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_drain_gate(gate),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_drain_gate(gate),
+        }
+    }
+    pub fn get_drain_gate(&self) -> DrainGate {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_drain_gate(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_drain_gate(),
+        }
+    }
+    pub fn set_usage_quota(&self, quota: Option<Arc<UsageQuota>>) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_usage_quota(quota),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_usage_quota(quota),
+        }
+    }
+    pub fn get_usage_quota(&self) -> Option<Arc<UsageQuota>> {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_usage_quota(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_usage_quota(),
+        }
+    }
+    pub fn set_maintenance_policy(&self, policy: Option<Arc<MaintenancePolicy>>) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_maintenance_policy(policy),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_maintenance_policy(policy),
+        }
+    }
+    pub fn get_maintenance_policy(&self) -> Option<Arc<MaintenancePolicy>> {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_maintenance_policy(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_maintenance_policy(),
+        }
+    }
+    pub fn set_read_only(&self, guard: Option<ReadOnlyGuard>) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_read_only(guard),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_read_only(guard),
+        }
+    }
+    pub fn get_read_only(&self) -> Option<ReadOnlyGuard> {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_read_only(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_read_only(),
+        }
+    }
+    pub fn start_keep_alive(&self, options: KeepAliveOptions) -> StdResult<(), Box<dyn StdError>> {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.start_keep_alive(options),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.start_keep_alive(options),
+        }
+    }
+    pub fn stop_keep_alive(&self) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.stop_keep_alive(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.stop_keep_alive(),
+        }
+    }
+    pub fn get_keep_alive_stats(&self) -> Option<KeepAliveStats> {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_keep_alive_stats(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_keep_alive_stats(),
+        }
+    }
+    // The snapshot of the health of the service (`BaseService::status()`)
+    pub fn status(&self) -> ServiceStatus {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.status(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.status(),
+        }
+    }
+    // Shut down the service gracefully (`BaseService::shutdown()`)
+    pub async fn shutdown(&self, grace: Duration) -> StdResult<(), Box<dyn StdError>> {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.shutdown(grace).await,
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.shutdown(grace).await,
+        }
+    }
+    #[cfg(feature = "queue")]
+    pub fn set_offline_queue(&self, store: Option<Arc<dyn QueueStore>>) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_offline_queue(store),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_offline_queue(store),
+        }
+    }
+    pub fn set_default_header(&self, header: Option<Headers>) -> StdResult<(), Box<dyn StdError>> {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => {
+                base.set_default_header(header);
+            }
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => {
+                base.set_default_header(header);
+            }
+        };
+        Ok(())
+    }
+    pub fn set_audit_log(&self, audit_log: Option<Arc<AuditLog>>) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_audit_log(audit_log),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_audit_log(audit_log),
+        }
+    }
+    pub fn set_deprecation_monitor(&self, deprecation_monitor: Option<DeprecationMonitor>) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_deprecation_monitor(deprecation_monitor),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_deprecation_monitor(deprecation_monitor),
+        }
+    }
+    pub fn set_timeout(&self, timeout: Duration) {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.set_timeout(timeout),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.set_timeout(timeout),
+        }
+    }
+    pub fn get_timeout(&self) -> Duration {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_timeout(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_timeout(),
+        }
+    }
+    #[deprecated(note = "Use `set_timeout()` instead")]
+    pub fn set_timeout_millisecond(&self, timeout_millisecond: u64) {
+        self.set_timeout(Duration::from_millis(timeout_millisecond));
+    }
+    #[deprecated(note = "Use `get_timeout()` instead")]
+    #[allow(deprecated)]
+    pub fn get_timeout_millisecond(&self) -> u64 {
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => base.get_timeout_millisecond(),
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => base.get_timeout_millisecond(),
+        }
+    }
+
+    /**
+    Send a request by the binding and read the whole response body.
+
+    # Arguments
+
+    * `relative_url` - The relative url to the base url, `{name}` would be replaced by the `path_param`.
+
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub async fn do_request(
+        &self,
+        method: Method,
+        header: Option<Headers>,
+        relative_url: impl Into<String>,
+        content_type: impl Into<String>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        body: Bytes,
+    ) -> StdResult<Bytes, Box<dyn StdError>> {
+        let relative_url = relative_url.into();
+        let content_type = content_type.into();
+        let path_param = path_param.map(|v| v.into());
+        let query_param = query_param.map(|v| v.into());
+
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => {
+                let header = match header {
+                    Some(header) => Some(HeaderMap::try_from(&header)?),
+                    None => None,
+                };
+                let body = base
+                    ._call_common(
+                        method,
+                        header,
+                        relative_url,
+                        content_type,
+                        path_param,
+                        query_param,
+                        Body::from(body),
+                    )
+                    .await?;
+                match base.body_to_bytes(*body).await {
+                    Ok(v) => Ok(v),
+                    Err(e) => Err(e as Box<dyn StdError>),
+                }
+            }
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => {
+                let body = base
+                    ._call_common(
+                        method,
+                        header.as_ref().map(Vec::<Header>::from),
+                        relative_url,
+                        content_type,
+                        path_param,
+                        query_param,
+                        body,
+                    )
+                    .await?;
+                Ok(*body)
+            }
+        }
+    }
+
+    /**
+    Send a request by the binding and read the whole response with its status and headers
+    (e.g. for the conditional requests).
+
+    # Remarks
+
+    * The error statuses (`4xx`/`5xx`) are returned as the responses too.
+    * It fails with `OfflineError` while it's offline (the writes are not queued).
+
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub async fn do_request_response(
+        &self,
+        method: Method,
+        header: Option<Headers>,
+        relative_url: impl Into<String>,
+        content_type: impl Into<String>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        body: Bytes,
+    ) -> StdResult<AnyResponse, Box<dyn StdError>> {
+        let relative_url = relative_url.into();
+        let content_type = content_type.into();
+        let path_param = path_param.map(|v| v.into());
+        let query_param = query_param.map(|v| v.into());
+        if self.is_offline() {
+            return Err(Box::new(OfflineError::new(format!(
+                "{} {} isn't sent",
+                method, relative_url
+            ))));
+        }
+
+        match self {
+            #[cfg(feature = "for_hyper")]
+            AnyService::Hyper(base) => {
+                let (mut req, simple_http) = {
+                    let mut simple_api = base.get_simple_api().lock().unwrap();
+                    let req = simple_api.make_request(
+                        method,
+                        relative_url,
+                        content_type,
+                        path_param,
+                        query_param,
+                        Body::from(body),
+                    )?;
+                    (req, simple_api.get_simple_http().clone())
+                };
+                if let Some(header) = header {
+                    let header_existing = req.headers_mut();
+                    for (k, v) in HeaderMap::try_from(&header)?.iter() {
+                        header_existing.insert(k, v.clone());
+                    }
+                }
+
+                let res = simple_http.request(req).await??;
+                let status = res.status().as_u16();
+                let headers = Headers::from(res.headers());
+                let body = hyper::body::to_bytes(res.into_body()).await?;
+                Ok(AnyResponse {
+                    status,
+                    headers,
+                    body,
+                })
+            }
+            #[cfg(feature = "for_ureq")]
+            AnyService::Ureq(base) => {
+                let (mut req, simple_http) = {
+                    let mut simple_api = base.get_simple_api().lock().unwrap();
+                    let req = simple_api.make_request(
+                        method,
+                        relative_url,
+                        content_type,
+                        path_param,
+                        query_param,
+                        body,
+                    )?;
+                    (req, simple_api.get_simple_http().clone())
+                };
+                if let Some(header) = header {
+                    for (k, v) in header.iter() {
+                        req.headers.insert(k, v);
+                    }
+                }
+
+                let (status, headers, body) = simple_http.request_response(req).await?;
+                Ok(AnyResponse {
+                    status,
+                    headers,
+                    body,
+                })
+            }
+        }
+    }
+
+    pub fn make_api<T, R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        content_type: impl Into<String>,
+        request_serializer: Arc<dyn BodySerializer<T, Bytes>>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> AnyAPI<T, R> {
+        AnyAPI {
+            service: self.clone(),
+            method,
+            relative_url: relative_url.into(),
+            content_type: content_type.into(),
+            request_serializer,
+            response_deserializer,
+        }
+    }
+    pub fn make_api_no_body<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> AnyAPI<(), R> {
+        self.make_api(
+            method,
+            relative_url,
+            "",
+            Arc::new(NoBodySerializer {}),
+            response_deserializer,
+            _return_type,
+        )
+    }
+}
+
+/*
+`AnyResponse` The whole response of `AnyService::do_request_response()`
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnyResponse {
+    pub status: u16,
+    pub headers: Headers,
+    pub body: Bytes,
+}
+impl AnyResponse {
+    // The sanitized file name of the `Content-Disposition` header (e.g. of the downloads)
+    pub fn file_name(&self) -> Option<String> {
+        parse_content_disposition_filename(self.headers.get("Content-Disposition")?)
+    }
+}
+
+// AnyAPI API of `AnyService`
+// T: Request body Type
+// R: Response body Type
+pub struct AnyAPI<T, R> {
+    pub service: AnyService,
+    pub method: Method,
+    pub relative_url: String,
+    pub content_type: String,
+
+    pub request_serializer: Arc<dyn BodySerializer<T, Bytes>>,
+    pub response_deserializer: Arc<dyn BodyDeserializer<R>>,
+}
+impl<T, R> AnyAPI<T, R> {
+    pub async fn call(
+        &self,
+        path_param: Option<impl Into<PathParam>>,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.call_with_options(None, path_param, None::<QueryParam>, sent_body)
+            .await
+    }
+
+    pub async fn call_with_options(
+        &self,
+        header: Option<Headers>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        let bytes = self
+            .service
+            .do_request(
+                self.method.clone(),
+                header,
+                self.relative_url.clone(),
+                self.content_type.clone(),
+                path_param,
+                query_param,
+                catch_hook_panic_async(
+                    "request_serializer",
+                    self.request_serializer.encode_async(sent_body),
+                )
+                .await??,
+            )
+            .await?;
+        catch_hook_panic_async(
+            "response_deserializer",
+            self.response_deserializer.decode_async(bytes),
+        )
+        .await?
+    }
+
+    // Send the request and read the whole response (the error statuses are returned too)
+    async fn call_response(
+        &self,
+        header: Option<Headers>,
+        path_param: Option<PathParam>,
+        sent_body: T,
+    ) -> StdResult<AnyResponse, Box<dyn StdError>> {
+        self.service
+            .do_request_response(
+                self.method.clone(),
+                header,
+                self.relative_url.clone(),
+                self.content_type.clone(),
+                path_param,
+                None::<QueryParam>,
+                catch_hook_panic_async(
+                    "request_serializer",
+                    self.request_serializer.encode_async(sent_body),
+                )
+                .await??,
+            )
+            .await
+    }
+}
+
+// The default max attempts of `get_then_update()`
+pub const DEFAULT_OPTIMISTIC_UPDATE_ATTEMPTS: usize = 3;
+
+/*
+`OptimisticUpdateError` The update of `get_then_update()` isn't done:
+the `ETag` is missing, a response isn't successful, or all attempts are rejected by `412 Precondition Failed`
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptimisticUpdateError {
+    details: String,
+    pub status: Option<u16>,
+    pub attempts: usize,
+}
+impl StdError for OptimisticUpdateError {}
+impl OptimisticUpdateError {
+    pub fn new(msg: impl Into<String>, status: Option<u16>, attempts: usize) -> Self {
+        OptimisticUpdateError {
+            details: msg.into(),
+            status,
+            attempts,
+        }
+    }
+}
+impl std::fmt::Display for OptimisticUpdateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "OptimisticUpdateError: {}", self.details)
+    }
+}
+
+/**
+Update a resource optimistically: fetch it with its `ETag`, mutate it,
+and send the update with `If-Match` so that it's rejected if the resource is changed in between.
+
+# Arguments
+
+* `api_get` - Fetch the resource (e.g. `GET /products/{id}`).
+* `api_put` - Send the mutated resource (e.g. `PUT /products/{id}`).
+* `path_param` - The path params of both APIs.
+* `max_attempts` - The max number of the fetch & update rounds (e.g. `DEFAULT_OPTIMISTIC_UPDATE_ATTEMPTS`).
+* `mutate_fn` - Make the update from the fetched resource, it's called again for each round
+  (its errors stop the update).
+
+# Remarks
+
+* `412 Precondition Failed` starts the next round with the resource fetched again,
+  and `OptimisticUpdateError` (`status` is `412`) is returned when all rounds are rejected.
+* The resources without `ETag` aren't updated (no unconditional updates), and the other non-`2xx`
+  responses are returned as `OptimisticUpdateError` with their statuses.
+* The weak `ETag`s (`W/"..."`) are sent as they are, while the servers comparing `If-Match` strongly reject them.
+
+*/
+pub async fn get_then_update<G, T, R>(
+    api_get: &AnyAPI<(), G>,
+    api_put: &AnyAPI<T, R>,
+    path_param: Option<PathParam>,
+    max_attempts: usize,
+    mut mutate_fn: impl FnMut(G) -> StdResult<T, Box<dyn StdError>>,
+) -> StdResult<Box<R>, Box<dyn StdError>> {
+    for attempt in 1..=max_attempts {
+        let res = api_get.call_response(None, path_param.clone(), ()).await?;
+        if !(200..300).contains(&res.status) {
+            return Err(Box::new(OptimisticUpdateError::new(
+                format!("{} isn't fetched", api_get.relative_url),
+                Some(res.status),
+                attempt,
+            )));
+        }
+        let etag = res
+            .headers
+            .get("ETag")
+            .map(|v| v.to_string())
+            .ok_or_else(|| {
+                OptimisticUpdateError::new(
+                    format!("{} has no ETag", api_get.relative_url),
+                    Some(res.status),
+                    attempt,
+                )
+            })?;
+        let fetched = catch_hook_panic_async(
+            "response_deserializer",
+            api_get.response_deserializer.decode_async(res.body),
+        )
+        .await??;
+
+        let mut header = Headers::new();
+        header.insert("If-Match", etag);
+        let res = api_put
+            .call_response(Some(header), path_param.clone(), mutate_fn(*fetched)?)
+            .await?;
+        match res.status {
+            412 => continue,
+            status if (200..300).contains(&status) => {
+                return catch_hook_panic_async(
+                    "response_deserializer",
+                    api_put.response_deserializer.decode_async(res.body),
+                )
+                .await?
+            }
+            status => {
+                return Err(Box::new(OptimisticUpdateError::new(
+                    format!("{} isn't updated", api_put.relative_url),
+                    Some(status),
+                    attempt,
+                )))
+            }
+        }
+    }
+    Err(Box::new(OptimisticUpdateError::new(
+        format!(
+            "{} is changed by others in all {} attempts",
+            api_put.relative_url, max_attempts
+        ),
+        Some(412),
+        max_attempts,
+    )))
+}
+
+// NoBodySerializer Serialize nothing for APIs without request bodies
+#[derive(Debug, Clone, Copy)]
+pub struct NoBodySerializer {}
+impl BodySerializer<(), Bytes> for NoBodySerializer {
+    fn encode(&self, _origin: ()) -> StdResult<Bytes, Box<dyn StdError>> {
+        Ok(Bytes::new())
+    }
+}
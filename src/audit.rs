@@ -0,0 +1,247 @@
+/*!
+In this module there're implementations of the outbound request audit log,
+each call's method, URL template, tags, status and latency are recorded to a pluggable `AuditSink`.
+
+It's made for compliance-sensitive integrations.
+
+# Remarks
+
+* The audit log is set by `SimpleHTTP::set_audit_log()` (or `set_audit_log()` of the services).
+* The URL template is the relative url before the path params are applied (e.g. `users/{id}`),
+  it's carried by `RequestOptions.url_template` (set by `make_request()` of the bindings),
+  otherwise the path of the URL is recorded.
+* The caller-provided tags are `AuditLog.tags` (per log) and `RequestOptions.tags` (per request,
+  e.g. set by interceptors).
+* The tags and the error texts are scrubbed by `AuditLog.scrubber` before they're sent to the sink.
+
+*/
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use super::common::{catch_hook_panic, system_clock, Clock, Headers, RequestOptions};
+use super::scrub::Scrubber;
+
+/*
+`AuditRecord` The audit record of an outbound call
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    // The time the call started
+    pub timestamp: SystemTime,
+    pub method: String,
+    pub url_template: String,
+    pub tags: Vec<(String, String)>,
+    // `None` if there's no response (e.g. connection failures or timeouts)
+    pub status: Option<u16>,
+    pub latency: Duration,
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    /**
+    Format the record as a line of tab separated fields:
+    epoch millis, method, URL template, status (`-` if none), latency millis, tags (`k=v` joined by `,`), error.
+    */
+    pub fn to_line(&self) -> String {
+        let timestamp = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_millis())
+            .unwrap_or_default();
+        let status = self
+            .status
+            .map(|v| v.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let tags = self
+            .tags
+            .iter()
+            .map(|(k, v)| k.clone() + "=" + v)
+            .collect::<Vec<_>>()
+            .join(",");
+
+        [
+            timestamp.to_string(),
+            self.method.clone(),
+            self.url_template.clone(),
+            status,
+            self.latency.as_millis().to_string(),
+            tags,
+            self.error.clone().unwrap_or_default(),
+        ]
+        .iter()
+        .map(|v| v.replace(['\t', '\n', '\r'], " "))
+        .collect::<Vec<_>>()
+        .join("\t")
+    }
+}
+
+/*
+`AuditSink` The destination of `AuditRecord`s
+*/
+pub trait AuditSink: Send + Sync {
+    fn record(&self, record: &AuditRecord);
+    // Persist the buffered records (e.g. by `shutdown()`), nothing by default
+    fn flush(&self) {}
+}
+
+/*
+`FileAuditSink` The `AuditSink` appending each record as a line (`AuditRecord::to_line()`) to the file
+*/
+pub struct FileAuditSink {
+    file: Mutex<File>,
+}
+impl FileAuditSink {
+    // Open the file for appending (it's created if it doesn't exist)
+    pub fn new(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileAuditSink {
+            file: Mutex::new(file),
+        })
+    }
+}
+impl AuditSink for FileAuditSink {
+    fn record(&self, record: &AuditRecord) {
+        let _ = writeln!(self.file.lock().unwrap(), "{}", record.to_line());
+    }
+    fn flush(&self) {
+        let _ = self.file.lock().unwrap().sync_data();
+    }
+}
+
+/*
+`ChannelAuditSink` The `AuditSink` sending the records to a channel (e.g. consumed by a shipping thread)
+*/
+pub struct ChannelAuditSink {
+    sender: Mutex<Sender<AuditRecord>>,
+}
+impl ChannelAuditSink {
+    pub fn channel() -> (Self, Receiver<AuditRecord>) {
+        let (sender, receiver) = mpsc::channel();
+        (
+            ChannelAuditSink {
+                sender: Mutex::new(sender),
+            },
+            receiver,
+        )
+    }
+}
+impl AuditSink for ChannelAuditSink {
+    fn record(&self, record: &AuditRecord) {
+        // The records are discarded after the receiver is dropped
+        let _ = self.sender.lock().unwrap().send(record.clone());
+    }
+}
+
+/**
+`AuditLog` records the sampled outbound calls to the `sink`.
+
+# Arguments
+
+* `sample_rate` - The ratio of the recorded calls (`1.0` for all calls),
+  the sampling is deterministic (evenly spaced) rather than random.
+* `tags` - The tags recorded with every call (before the per-request tags).
+
+*/
+pub struct AuditLog {
+    pub sink: Arc<dyn AuditSink>,
+    pub sample_rate: f64,
+    pub tags: Vec<(String, String)>,
+    pub scrubber: Option<Arc<dyn Scrubber>>,
+    // The time source of the timestamps and latencies
+    pub clock: Arc<dyn Clock>,
+    counter: AtomicU64,
+}
+
+impl AuditLog {
+    pub fn new(sink: Arc<dyn AuditSink>) -> Self {
+        Self::new_with_options(sink, 1.0, Vec::new())
+    }
+    pub fn new_with_options(
+        sink: Arc<dyn AuditSink>,
+        sample_rate: f64,
+        tags: Vec<(String, String)>,
+    ) -> Self {
+        AuditLog {
+            sink,
+            sample_rate,
+            tags,
+            scrubber: None,
+            clock: system_clock(),
+            counter: AtomicU64::new(0),
+        }
+    }
+
+    fn should_sample(&self) -> bool {
+        if self.sample_rate >= 1.0 {
+            return true;
+        }
+        if self.sample_rate <= 0.0 {
+            return false;
+        }
+
+        let n = self.counter.fetch_add(1, Ordering::SeqCst) as f64;
+        ((n + 1.0) * self.sample_rate).floor() > (n * self.sample_rate).floor()
+    }
+
+    /**
+    Start auditing a call, it's `None` if the call is not sampled.
+
+    # Arguments
+
+    * `path` - The path of the URL, recorded if there's no `url_template` in the `options`.
+
+    */
+    pub fn begin(&self, method: &str, path: &str, options: &RequestOptions) -> Option<AuditCall> {
+        if !self.should_sample() {
+            return None;
+        }
+
+        let mut tags = self.tags.clone();
+        tags.extend(options.tags.iter().cloned());
+        Some(AuditCall {
+            record: AuditRecord {
+                timestamp: self.clock.system_now(),
+                method: method.to_string(),
+                url_template: options
+                    .url_template
+                    .clone()
+                    .unwrap_or_else(|| path.to_string()),
+                tags,
+                status: None,
+                latency: Duration::default(),
+                error: None,
+            },
+            start: self.clock.now(),
+        })
+    }
+
+    // Record the finished call with its status (or error)
+    pub fn finish(&self, call: AuditCall, status: Option<u16>, error: Option<String>) {
+        let mut record = call.record;
+        record.latency = self.clock.now().duration_since(call.start);
+        record.status = status;
+        record.error = error;
+        if let Some(scrubber) = &self.scrubber {
+            let mut tags = Headers::from(record.tags);
+            scrubber.scrub_headers(&mut tags);
+            record.tags = tags.into_iter().collect();
+            record.error = record.error.map(|error| scrubber.scrub_text(&error));
+        }
+        let _ = catch_hook_panic("audit_sink", || self.sink.record(&record));
+    }
+}
+
+/*
+`AuditCall` The audited call in progress, made by `AuditLog::begin()`
+*/
+#[derive(Debug)]
+pub struct AuditCall {
+    pub record: AuditRecord,
+    start: Instant,
+}
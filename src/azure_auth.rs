@@ -0,0 +1,387 @@
+/*!
+In this module there's `AzureAuth`, minting the access tokens of the Azure AD (Microsoft Entra ID)
+applications by the client-credentials flow (with a client secret or a certificate).
+
+# Remarks
+
+* The token endpoint is called by `AnyService`, so it works with whichever binding is enabled.
+* The tokens are sent by the `AzureTokenCache` (`AzureAuth::auth_provider()`)
+  of `Session::set_auth_provider()` (hyper) or `InterceptorFunc::new_for_auth_provider()`,
+  after they're fetched by `AzureAuth::authorized()` (or `AzureAuth::token()`).
+
+*/
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::method::Method;
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use ring::rand::SystemRandom;
+use ring::signature::{RsaKeyPair, RSA_PKCS1_SHA256};
+use serde::Deserialize;
+use url::Url;
+
+use super::any_service::AnyService;
+use super::common::{
+    add_header_authentication_bearer, system_clock, with_extensions, AbsoluteUrl, AuthProvider,
+    Clock, Extensions, Headers, PathParam, QueryParam,
+};
+
+pub const AUTHORITY_URL: &str = "https://login.microsoftonline.com";
+pub const CLIENT_ASSERTION_TYPE_JWT_BEARER: &str =
+    "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+// The scope of the Microsoft Graph (the client-credentials scopes are `<resource>/.default`)
+pub const SCOPE_GRAPH_DEFAULT: &str = "https://graph.microsoft.com/.default";
+
+// The lifetime of the client assertions
+const ASSERTION_TTL: Duration = Duration::from_secs(600);
+
+/*
+`AzureAuthError` The token isn't available (e.g. an illegal certificate, or rejected by Azure AD)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct AzureAuthError {
+    details: String,
+    pub status: Option<u16>,
+}
+impl StdError for AzureAuthError {}
+impl AzureAuthError {
+    pub fn new(msg: impl Into<String>, status: Option<u16>) -> AzureAuthError {
+        AzureAuthError {
+            details: msg.into(),
+            status,
+        }
+    }
+}
+impl std::fmt::Display for AzureAuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "AzureAuthError: {}", self.details)
+    }
+}
+
+/*
+`AzureCredential` The credential of the application:
+the client secret, or the certificate (the PEMs of the PKCS#8 RSA key and the X.509 certificate) signing the client assertions.
+*/
+#[derive(Clone)]
+pub enum AzureCredential {
+    ClientSecret(String),
+    Certificate {
+        private_key: String,
+        certificate: String,
+    },
+}
+impl std::fmt::Debug for AzureCredential {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AzureCredential::ClientSecret(_) => write!(f, "ClientSecret(***)"),
+            AzureCredential::Certificate { .. } => write!(f, "Certificate {{ .. }}"),
+        }
+    }
+}
+
+enum AzureCredentialSource {
+    ClientSecret(String),
+    Certificate {
+        key_pair: Box<RsaKeyPair>,
+        // The base64url SHA-1 thumbprint of the certificate (`x5t`)
+        thumbprint: String,
+    },
+}
+
+/**
+`AzureTokenCache` The `AuthProvider` sending the cached token of `AzureAuth` as `Authorization: Bearer <token>`.
+
+# Remarks
+
+* It doesn't fetch the tokens (it fails with `AzureAuthError` without a valid token),
+  so the calls should be wrapped by `AzureAuth::authorized()` (or preceded by `AzureAuth::token()`).
+* The token is dropped when the responses are `401`.
+
+*/
+pub struct AzureTokenCache {
+    pub clock: Arc<dyn Clock>,
+    // (expires at, token)
+    token: Mutex<Option<(Instant, String)>>,
+}
+impl AzureTokenCache {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        AzureTokenCache {
+            clock,
+            token: Mutex::new(None),
+        }
+    }
+    // The token if it's not expired
+    pub fn get(&self) -> Option<String> {
+        match &*self.token.lock().unwrap() {
+            Some((expires_at, token)) if *expires_at > self.clock.now() => Some(token.clone()),
+            _ => None,
+        }
+    }
+    pub fn set(&self, token: impl Into<String>, ttl: Duration) {
+        *self.token.lock().unwrap() = Some((self.clock.now() + ttl, token.into()));
+    }
+    pub fn clear(&self) {
+        *self.token.lock().unwrap() = None;
+    }
+}
+impl AuthProvider for AzureTokenCache {
+    fn apply(&self, headers: &mut Headers) -> StdResult<(), Box<dyn StdError>> {
+        let token = self.get().ok_or_else(|| {
+            AzureAuthError::new("no valid token (fetched by `authorized()`/`token()`)", None)
+        })?;
+        *headers = add_header_authentication_bearer(std::mem::take(headers), token)?;
+        Ok(())
+    }
+    fn on_response(&self, status: u16, _headers: &Headers) {
+        if status == 401 {
+            self.clear();
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ErrorResponse {
+    error: String,
+    error_description: Option<String>,
+}
+
+/**
+`AzureAuth` Mint the access tokens of an Azure AD application by the client-credentials flow,
+cached by the `AzureTokenCache` (the `AuthProvider`).
+
+# Arguments
+
+* `service` - The service calling the token endpoint (by the absolute urls).
+* `tenant_id` - The tenant (the directory ID, or the domain like `contoso.onmicrosoft.com`).
+* `client_id` - The application (client) ID.
+* `credential` - The client secret or the certificate.
+* `scopes` - The scopes of the tokens (e.g. `SCOPE_GRAPH_DEFAULT`).
+
+# Remarks
+
+* The token endpoint is `{authority_url}/{tenant_id}/oauth2/v2.0/token`,
+  the `authority_url` (`AUTHORITY_URL` by default) could be changed for the sovereign clouds.
+* The certificates sign the client assertions (RS256, with the `x5t` thumbprint).
+* The token is cached until it expires (by `expires_in`, minus `expiry_margin`).
+* The expiration is by the `clock` of the `cache` (`SystemClock` by default).
+* The concurrent refreshes are not coalesced.
+
+*/
+pub struct AzureAuth {
+    pub service: AnyService,
+    pub tenant_id: String,
+    pub client_id: String,
+    pub scopes: Vec<String>,
+    pub authority_url: String,
+    pub expiry_margin: Duration,
+    // The ttl of the tokens without `expires_in`
+    pub default_ttl: Duration,
+    pub cache: Arc<AzureTokenCache>,
+    source: AzureCredentialSource,
+}
+impl AzureAuth {
+    pub fn new(
+        service: AnyService,
+        tenant_id: impl Into<String>,
+        client_id: impl Into<String>,
+        credential: AzureCredential,
+        scopes: Vec<String>,
+    ) -> StdResult<Self, AzureAuthError> {
+        let source = match credential {
+            AzureCredential::ClientSecret(secret) => AzureCredentialSource::ClientSecret(secret),
+            AzureCredential::Certificate {
+                private_key,
+                certificate,
+            } => {
+                let key_pair = RsaKeyPair::from_pkcs8(&decode_pem(&private_key, "private key")?)
+                    .map_err(|e| {
+                        AzureAuthError::new(format!("illegal private key: {}", e), None)
+                    })?;
+                let thumbprint = digest(
+                    &SHA1_FOR_LEGACY_USE_ONLY,
+                    &decode_pem(&certificate, "certificate")?,
+                );
+                AzureCredentialSource::Certificate {
+                    key_pair: Box::new(key_pair),
+                    thumbprint: base64::encode_config(thumbprint, base64::URL_SAFE_NO_PAD),
+                }
+            }
+        };
+        Ok(AzureAuth {
+            service,
+            tenant_id: tenant_id.into(),
+            client_id: client_id.into(),
+            scopes,
+            authority_url: AUTHORITY_URL.to_string(),
+            expiry_margin: Duration::from_secs(60),
+            default_ttl: Duration::from_secs(300),
+            cache: Arc::new(AzureTokenCache::new(system_clock())),
+            source,
+        })
+    }
+
+    // The token endpoint of the tenant
+    pub fn token_url(&self) -> String {
+        format!(
+            "{}/{}/oauth2/v2.0/token",
+            self.authority_url.trim_end_matches('/'),
+            self.tenant_id
+        )
+    }
+
+    // The `AuthProvider` of the tokens (e.g. for `InterceptorFunc::new_for_auth_provider()`)
+    pub fn auth_provider(&self) -> Arc<dyn AuthProvider> {
+        self.cache.clone()
+    }
+
+    // Get the token (the cached one, or a new one)
+    pub async fn token(&self) -> StdResult<String, Box<dyn StdError>> {
+        if let Some(token) = self.cache.get() {
+            return Ok(token);
+        }
+
+        let token_url = self.token_url();
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", "client_credentials");
+        form.append_pair("client_id", &self.client_id);
+        form.append_pair("scope", &self.scopes.join(" "));
+        match &self.source {
+            AzureCredentialSource::ClientSecret(secret) => {
+                form.append_pair("client_secret", secret);
+            }
+            AzureCredentialSource::Certificate {
+                key_pair,
+                thumbprint,
+            } => {
+                let assertion = self.client_assertion(&token_url, key_pair, thumbprint)?;
+                form.append_pair("client_assertion_type", CLIENT_ASSERTION_TYPE_JWT_BEARER);
+                form.append_pair("client_assertion", &assertion);
+            }
+        }
+        let body = self.call(&token_url, Bytes::from(form.finish())).await?;
+
+        let res: TokenResponse = serde_json::from_slice(&body)
+            .map_err(|e| AzureAuthError::new(format!("illegal response: {}", e), None))?;
+        let token = res
+            .access_token
+            .ok_or_else(|| AzureAuthError::new("no token in the response", None))?;
+        let ttl = res
+            .expires_in
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_ttl)
+            .saturating_sub(self.expiry_margin);
+        self.cache.set(token.clone(), ttl);
+        Ok(token)
+    }
+
+    // Run the calls of the `future` after the token is ready (the failures are returned without polling it)
+    pub async fn authorized<F: Future>(
+        &self,
+        future: F,
+    ) -> StdResult<F::Output, Box<dyn StdError>> {
+        self.token().await?;
+        Ok(future.await)
+    }
+
+    // Drop the cached token (it's fetched again by the next `token()`)
+    pub fn invalidate(&self) {
+        self.cache.clear();
+    }
+
+    // The client assertion signed by the certificate
+    fn client_assertion(
+        &self,
+        token_url: &str,
+        key_pair: &RsaKeyPair,
+        thumbprint: &str,
+    ) -> StdResult<String, AzureAuthError> {
+        let issued_at = self
+            .cache
+            .clock
+            .system_now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+            .as_secs();
+        let header = serde_json::json!({ "alg": "RS256", "typ": "JWT", "x5t": thumbprint });
+        let claims = serde_json::json!({
+            "aud": token_url,
+            "iss": self.client_id,
+            "sub": self.client_id,
+            "jti": uuid::Uuid::now_v7().to_string(),
+            "nbf": issued_at,
+            "exp": issued_at + ASSERTION_TTL.as_secs(),
+        });
+
+        let encode = |data: &[u8]| base64::encode_config(data, base64::URL_SAFE_NO_PAD);
+        let message =
+            encode(header.to_string().as_bytes()) + "." + &encode(claims.to_string().as_bytes());
+        let mut signature = vec![0; key_pair.public().modulus_len()];
+        key_pair
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|e| AzureAuthError::new(format!("failed to sign: {}", e), None))?;
+        Ok(message + "." + &encode(&signature))
+    }
+
+    // Post the form to the token endpoint without the credentials, the non-2xx responses are errors
+    async fn call(&self, url: &str, body: Bytes) -> StdResult<Bytes, Box<dyn StdError>> {
+        let mut extensions = Extensions::new();
+        extensions.insert(AbsoluteUrl {
+            url: Url::parse(url)?,
+            skip_auth: true,
+        });
+        let res = with_extensions(
+            extensions,
+            self.service.do_request_response(
+                Method::POST,
+                None,
+                "",
+                "application/x-www-form-urlencoded",
+                None::<PathParam>,
+                None::<QueryParam>,
+                body,
+            ),
+        )
+        .await?;
+        if !(200..300).contains(&res.status) {
+            // e.g. `invalid_client: AADSTS7000215: Invalid client secret provided.`
+            let reason = match serde_json::from_slice::<ErrorResponse>(&res.body) {
+                Ok(e) => match e.error_description {
+                    Some(description) => format!(": {}: {}", e.error, description),
+                    None => format!(": {}", e.error),
+                },
+                Err(_) => String::new(),
+            };
+            return Err(Box::new(AzureAuthError::new(
+                format!("{} responded with the status {}{}", url, res.status, reason),
+                Some(res.status),
+            )));
+        }
+        Ok(res.body)
+    }
+}
+// The DER of the PEM (e.g. `-----BEGIN CERTIFICATE-----`)
+fn decode_pem(pem: &str, name: &str) -> StdResult<Vec<u8>, AzureAuthError> {
+    let base64_text = pem
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+        .collect::<String>();
+    base64::decode(base64_text)
+        .map_err(|e| AzureAuthError::new(format!("illegal {}: {}", name, e), None))
+}
@@ -3,41 +3,75 @@ In this module there're implementations & tests of `SimpleHTTP`.
 */
 
 use std::collections::VecDeque;
+use std::convert::TryFrom;
 use std::error::Error as StdError;
 use std::future::Future;
-use std::io::{self, Write};
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
 use std::pin::Pin;
 use std::result::Result as StdResult;
 use std::str::FromStr;
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
-};
-use std::task::{Context, Poll, Waker};
-use std::thread;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
 use http::method::Method;
+use http::uri::Authority;
 // use futures::TryStreamExt;
 // use hyper::body::HttpBody;
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 // use futures::executor::block_on;
 use futures::executor::ThreadPool;
 use futures::prelude::*;
-use futures::Stream;
 // use futures::task::SpawnExt;
 use hyper::body::HttpBody;
-use hyper::client::{connect::Connect, HttpConnector};
-use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::client::connect::dns::{GaiResolver, Name};
+use hyper::client::connect::{Connect, Connected, Connection};
+use hyper::client::HttpConnector;
+use hyper::header::{
+    HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_ENCODING, CONTENT_LANGUAGE,
+    CONTENT_LENGTH, CONTENT_TYPE, DATE, EXPECT, HOST, LOCATION, SET_COOKIE, WARNING,
+};
+use hyper::service::Service;
 use hyper::{Body, Client, HeaderMap, Request, Response, Result, Uri};
+use tokio::fs::File;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
 use url::Url;
 
-use super::common::{make_stream, PathParam, QueryParam, WriteForStream};
-use super::simple_api::{
-    APIMultipart, BaseAPI, BaseService, BodyDeserializer, BodySerializer, SimpleAPI,
+#[cfg(feature = "for_serde")]
+use super::codec::CodecRegistry;
+#[cfg(feature = "queue")]
+use super::common::is_safe_method;
+#[cfg(feature = "multipart")]
+use super::common::DEFAULT_STREAMING_BODY_CAPACITY;
+pub use super::common::{add_header_authentication, add_header_authentication_bearer};
+use super::common::{
+    append_query_param, catch_hook_panic, catch_hook_panic_async, join_url, scoped_extensions,
+    system_clock, validate_header_name, AbsoluteUrl, AcceptMediaType, AuthProvider, BodyFactory,
+    BodyProducerError, Clock, ConnectTo, CookieJar, CredentialRotation, DefaultRedirectPolicy,
+    DeprecationNotice, DrainGate, HasRequestOptions, Headers, InvalidHeaderError, KeepAliveMethod,
+    KeepAliveProbe, KeepAliveWorker, MaintenanceAction, MaintenancePolicy, MetricsSink,
+    OfflineError, OfflineSwitch, PathParam, QueryParam, ReadOnlyGuard, Redirect, RedirectAction,
+    RedirectPolicy, RequestHeaders, RequestOptions, RequestSummary, RequestTiming, ResilienceEvent,
+    ResponseLanguage, ResponseSize, ResponseSizeInfo, ResponseStatus, ResponseTiming,
+    ResponseTlsInfo, RetryAttempt, RetryBudget, SniHostname, StatusTracker, StreamingBodyWriter,
+    TemplateVariables, TlsInfo, TransportErrorKind, UrlJoinStrategy, UrlNormalization, UsageQuota,
+    Versioning, MAX_REDIRECTS,
 };
-use super::simple_http::{
-    BaseClient, FormDataParseError, SimpleHTTP, SimpleHTTPResponse, DEFAULT_TIMEOUT_MILLISECOND,
+#[cfg(feature = "queue")]
+use super::queue::{enqueue_offline, QueueStore, QueuedRequest};
+#[cfg(feature = "multipart")]
+use super::simple_api::{APIMultipart, AsyncBodySerializer, BodySerializer, SerializeError};
+use super::simple_api::{
+    BaseAPI, BaseService, BodyDeserializer, BodyStream, ResponseFuture, SimpleAPI,
 };
+#[cfg(feature = "multipart")]
+use super::simple_http::FormDataParseError;
+use super::simple_http::{BaseClient, LazyClient, SimpleHTTP, SimpleHTTPResponse, DEFAULT_TIMEOUT};
+
+pub const DEFAULT_UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
 
 #[cfg(feature = "for_serde")]
 pub use super::simple_api::DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES;
@@ -47,6 +81,7 @@ pub use super::simple_api::{DEFAULT_MULTIPART_SERIALIZER, DEFAULT_MULTIPART_SERI
 #[cfg(feature = "multipart")]
 pub use super::simple_http::{
     data_and_boundary_from_multipart, get_content_type_from_multipart_boundary,
+    parse_multipart_boundary, Constraints, SizeLimit,
 };
 #[cfg(feature = "multipart")]
 use formdata::FormData;
@@ -55,202 +90,1585 @@ use multer;
 #[cfg(feature = "multipart")]
 use multer::Multipart;
 
-#[derive(Clone)]
-pub struct WriteForBody {
-    // pub Box<Sender>
-    pub cached: Arc<Mutex<VecDeque<Bytes>>>,
-    pub waker: Arc<Mutex<Option<Waker>>>,
-    pub alive: Arc<Mutex<AtomicBool>>,
+#[deprecated(note = "Use `common::StreamingBodyWriter` instead")]
+pub type WriteForBody = StreamingBodyWriter;
+
+// The shared `BaseService` of the hyper binding (e.g. by `CommonAPI::as_base_service_shared()`)
+pub type HyperBaseService<C, B = Body> =
+    Arc<dyn BaseService<Client<C, B>, Request<B>, Result<Response<B>>, Method, HeaderMap, B>>;
+
+#[cfg(feature = "multipart")]
+#[derive(Debug, Clone)]
+/**
+MultipartSerializerForStream Serialize the multipart body as a stream (for put/post/patch etc)
+
+# Remarks
+
+* The files are checked before the request is sent (missing files fail with `SerializeError`).
+* The body is written on the `thread_pool` (or the blocking pool of tokio if it's `None`),
+  while it's sent.
+*/
+pub struct MultipartSerializerForStream {
+    // NOTE: It can't be Copy because of this one:
+    thread_pool: Option<Arc<ThreadPool>>,
 }
+#[cfg(feature = "multipart")]
+impl MultipartSerializerForStream {
+    pub fn new(thread_pool: Option<Arc<ThreadPool>>) -> Self {
+        MultipartSerializerForStream { thread_pool }
+    }
 
-impl WriteForBody {
-    pub fn close(&self) {
-        self.alive.lock().unwrap().store(false, Ordering::SeqCst);
+    // The `BodyFactory` serializing a fresh stream of the `form` per attempt (e.g. retried by `RetryClient`)
+    pub fn body_factory(
+        &self,
+        form: impl Fn() -> FormData + Send + Sync + 'static,
+    ) -> BodyFactory<Body> {
+        let serializer = self.clone();
+        BodyFactory::new(move || {
+            let serializer = serializer.clone();
+            let form = form();
+            async move {
+                serializer
+                    .serialize(form)
+                    .await
+                    .map_err(|e| Box::new(e) as Box<dyn StdError>)
+            }
+        })
+    }
+}
+#[cfg(feature = "multipart")]
+impl AsyncBodySerializer<FormData, (String, Body)> for MultipartSerializerForStream {
+    fn serialize<'a>(
+        &'a self,
+        origin: FormData,
+    ) -> Pin<Box<dyn Future<Output = StdResult<(String, Body), SerializeError>> + 'a>>
+    where
+        FormData: 'a,
+        (String, Body): 'a,
+    {
+        Box::pin(async move {
+            for (name, file) in origin.files.iter() {
+                if let Err(e) = tokio::fs::metadata(&file.path).await {
+                    return Err(SerializeError::new_with_source(
+                        format!("the file of {} ({:?}) is unreadable", name, file.path),
+                        e,
+                    ));
+                }
+            }
 
-        {
-            if let Some(waker) = self.waker.lock().unwrap().take() {
-                waker.wake()
+            let boundary = formdata::generate_boundary();
+            let content_type = get_content_type_from_multipart_boundary(boundary.clone())
+                .map_err(|e| SerializeError::new_with_source(e.to_string(), e))?;
+
+            let (data, body) = StreamingBodyWriter::channel(DEFAULT_STREAMING_BODY_CAPACITY);
+            let write = move || {
+                let mut data = data;
+                match formdata::write_formdata(&mut data, &boundary, &origin) {
+                    Ok(_) => data.close(),
+                    Err(e) => data.abort_with_source("write_formdata", e),
+                };
+            };
+            match &self.thread_pool {
+                Some(thread_pool) => thread_pool.spawn_ok(async move { write() }),
+                None => {
+                    tokio::task::spawn_blocking(write);
+                }
+            };
+
+            Ok((content_type, Body::wrap_stream(body)))
+        })
+    }
+}
+#[cfg(feature = "multipart")]
+pub const DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM: MultipartSerializerForStream =
+    MultipartSerializerForStream { thread_pool: None };
+
+/*
+`HyperClientOptions` The connection pool & keepalive options to build hyper clients.
+
+The default values are the same as hyper's.
+*/
+#[derive(Debug, Clone)]
+pub struct HyperClientOptions {
+    // `None` to disable the timeout of idle sockets
+    pub pool_idle_timeout: Option<Duration>,
+    pub pool_max_idle_per_host: usize,
+    pub http2_only: bool,
+    // `None` to disable HTTP2 keepalive pings
+    pub http2_keep_alive_interval: Option<Duration>,
+    pub http2_keep_alive_timeout: Duration,
+    // Send HTTP2 keepalive pings even when there're no open streams
+    pub http2_keep_alive_while_idle: bool,
+}
+impl Default for HyperClientOptions {
+    fn default() -> Self {
+        HyperClientOptions {
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            pool_max_idle_per_host: usize::MAX,
+            http2_only: false,
+            http2_keep_alive_interval: None,
+            http2_keep_alive_timeout: Duration::from_secs(20),
+            http2_keep_alive_while_idle: false,
+        }
+    }
+}
+impl HyperClientOptions {
+    pub fn build<C, B>(&self, connector: C) -> Client<C, B>
+    where
+        C: Connect + Clone,
+        B: HttpBody + Send,
+        B::Data: Send,
+    {
+        Client::builder()
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .http2_only(self.http2_only)
+            .http2_keep_alive_interval(self.http2_keep_alive_interval)
+            .http2_keep_alive_timeout(self.http2_keep_alive_timeout)
+            .http2_keep_alive_while_idle(self.http2_keep_alive_while_idle)
+            .build(connector)
+    }
+}
+
+pub struct HyperClient<C, B> {
+    pub client: Client<C, B>,
+    pub thread_pool: Option<ThreadPool>,
+    // The connector & options to rebuild the client, for `close_idle()`
+    pub connector: Option<C>,
+    pub options: HyperClientOptions,
+}
+impl<C, B> HyperClient<C, B>
+where
+    C: Connect + Clone,
+    B: HttpBody + Send,
+    B::Data: Send,
+{
+    pub fn new_with_options(connector: C, options: HyperClientOptions) -> Self {
+        HyperClient {
+            client: options.build(connector.clone()),
+            thread_pool: None,
+            connector: Some(connector),
+            options,
+        }
+    }
+}
+impl HyperClient<HttpConnector, Body> {
+    pub fn new_for_hyper() -> Self {
+        Self::new_with_options(HttpConnector::new(), HyperClientOptions::default())
+    }
+}
+tokio::task_local! {
+    // The authority connected by `ConnectToConnector` (the `connect_to` address, or the host of the url)
+    // & whether it's connected by `ConnectToConnector`, in the scope of the connecting
+    static CONNECT_TO: (Authority, Arc<AtomicBool>);
+}
+
+// The uri of the `authority`
+fn uri_with_authority(uri: &Uri, authority: Authority) -> Uri {
+    let mut parts = uri.clone().into_parts();
+    parts.authority = Some(authority);
+    Uri::from_parts(parts).unwrap_or_else(|_| uri.clone())
+}
+
+// The uri of the `host` (keeping the port)
+fn uri_with_host(uri: &Uri, host: &str) -> Uri {
+    let authority = match uri.port_u16() {
+        Some(port) => format!("{}:{}", host, port).parse(),
+        None => host.parse(),
+    };
+    match authority {
+        Ok(authority) => uri_with_authority(uri, authority),
+        Err(_) => uri.clone(),
+    }
+}
+
+fn authority_of(addr: SocketAddr) -> Option<Authority> {
+    addr.to_string().parse().ok()
+}
+
+/**
+`ConnectToConnector` wraps the TCP connector (e.g. `HttpConnector`) of the TLS connectors,
+connecting to the `RequestOptions::connect_to` addresses of `HyperClient` instead of the hosts of the urls,
+so that the SNI (& the certificate validation) of the TLS connectors are still by the hosts of the urls
+(or by the `RequestOptions::sni_hostname`, connecting to the hosts of the urls).
+
+# Remarks
+
+* The `https` urls of `connect_to`/`sni_hostname` are sent over the dedicated connections
+  made by the connector of `HyperClient` (e.g. `HttpsConnector<ConnectToConnector>` of `hyper-tls`/`hyper-rustls`).
+* Without it in the connector, the connections made to the hosts are dropped,
+  and they're connected by the addresses as the hosts (the SNI is the IP), the `sni_hostname` is ignored.
+* The `http` urls are always connected that way (pooled by the addresses), with the `Host` of the urls.
+
+*/
+#[derive(Debug, Clone)]
+pub struct ConnectToConnector<C = HttpConnector>(pub C);
+impl ConnectToConnector<HttpConnector> {
+    // The `HttpConnector` connecting the `https` urls as well (for the TLS connectors wrapping it)
+    pub fn new_for_hyper() -> Self {
+        let mut connector = HttpConnector::new();
+        connector.enforce_http(false);
+        ConnectToConnector(connector)
+    }
+}
+impl<C> Service<Uri> for ConnectToConnector<C>
+where
+    C: Service<Uri>,
+    C::Future: Send + 'static,
+{
+    type Response = C::Response;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = StdResult<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<StdResult<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connect_to = CONNECT_TO.try_with(|(authority, connected)| {
+            connected.store(true, Ordering::SeqCst);
+            authority.clone()
+        });
+        match connect_to {
+            Ok(authority) => Box::pin(self.0.call(uri_with_authority(&uri, authority))),
+            Err(_) => Box::pin(self.0.call(uri)),
+        }
+    }
+}
+
+// Send the request to the `connect_to` address (or the host of the url) keeping the `Host`,
+// and the SNI of the url (or the `sni_hostname`) by `ConnectToConnector`
+async fn request_connect_to<C, B>(
+    client: Client<C, B>,
+    connector: Option<C>,
+    http2_only: bool,
+    connect_to: Option<SocketAddr>,
+    sni_hostname: Option<String>,
+    req: Request<B>,
+) -> Result<Response<Body>>
+where
+    C: Connect + Service<Uri> + Clone + Send + Sync + 'static,
+    C::Response: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    C::Future: Send,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    let (mut parts, body) = req.into_parts();
+    if let Some(authority) = parts.uri.authority() {
+        if !parts.headers.contains_key(HOST) {
+            if let Ok(host) = HeaderValue::from_str(authority.as_str()) {
+                parts.headers.insert(HOST, host);
+            }
+        }
+    }
+
+    let target = match connect_to {
+        Some(addr) => authority_of(addr),
+        None => parts.uri.authority().cloned(),
+    };
+    if let (Some(mut connector), Some("https"), Some(target)) =
+        (connector, parts.uri.scheme_str(), target)
+    {
+        let connected = Arc::new(AtomicBool::new(false));
+        let uri = match &sni_hostname {
+            Some(sni_hostname) => uri_with_host(&parts.uri, sni_hostname),
+            None => parts.uri.clone(),
+        };
+        let connecting = CONNECT_TO.scope((target, connected.clone()), async move {
+            match future::poll_fn(|cx| connector.poll_ready(cx)).await {
+                Ok(_) => connector.call(uri).await.ok(),
+                Err(_) => None,
+            }
+        });
+        // The connections to the hosts (without `ConnectToConnector`) are dropped
+        if let (Some(io), true) = (connecting.await, connected.load(Ordering::SeqCst)) {
+            let (mut sender, connection) = hyper::client::conn::Builder::new()
+                .http2_only(http2_only)
+                .handshake::<_, B>(io)
+                .await?;
+            tokio::spawn(connection);
+            if !http2_only {
+                // The origin-form over the dedicated connection
+                parts.uri = parts
+                    .uri
+                    .path_and_query()
+                    .map_or("/", |v| v.as_str())
+                    .parse()
+                    .unwrap_or_default();
             }
+            return sender.send_request(Request::from_parts(parts, body)).await;
+        }
+    }
+
+    if let Some(authority) = connect_to.and_then(authority_of) {
+        parts.uri = uri_with_authority(&parts.uri, authority);
+    }
+    client.request(Request::from_parts(parts, body)).await
+}
+
+impl<C, B> BaseClient<Client<C, B>, Request<B>, Result<Response<Body>>, Method, HeaderMap, B>
+    for HyperClient<C, B>
+where
+    C: Connect + Service<Uri> + Clone + Send + Sync + 'static,
+    C::Response: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    C::Future: Send,
+    B: HttpBody + Default + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn request(&self, req: Request<B>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
+        let options = req.get_request_options();
+        match (options.connect_to, options.sni_hostname) {
+            (None, None) => Box::pin(self.client.request(req)),
+            (connect_to, sni_hostname) => Box::pin(request_connect_to(
+                self.client.clone(),
+                self.connector.clone(),
+                self.options.http2_only,
+                connect_to,
+                sni_hostname,
+                req,
+            )),
+        }
+    }
+    fn get_client(&mut self) -> &mut Client<C, B> {
+        return &mut self.client;
+    }
+
+    // Rebuild the client, the idle connections are closed with the old pool.
+    // (The in-flight requests keep the old pool until they're done)
+    fn close_idle(&mut self) {
+        if let Some(connector) = &self.connector {
+            self.client = self.options.build(connector.clone());
+        }
+    }
+    // Send the probes by the clone of the client (sharing the pool of the client)
+    fn keep_alive_probe(&mut self) -> Option<KeepAliveProbe> {
+        let client = self.client.clone();
+        Some(Arc::new(move |method, url, _timeout| {
+            let client = client.clone();
+            Box::pin(async move {
+                let req = Request::builder()
+                    .method(method.as_str())
+                    .uri(url.as_str())
+                    .body(B::default())?;
+                let res = client.request(req).await?;
+                let status = res.status().as_u16();
+                // Drain the body, for the connection to be back to the pool
+                hyper::body::to_bytes(res.into_body()).await?;
+                Ok(status)
+            })
+        }))
+    }
+}
+
+/*
+`ServedBy` The response extension annotating which base URL served the response,
+set by `MirrorFailoverClient`.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServedBy(pub Url);
+
+/**
+`MirrorFailoverClient` implements a mirror-failover middleware as a `BaseClient`.
+
+If the request fails at the connection level, the same request is retried against
+the configured mirror base URLs in order.
+
+# Remarks
+
+* The request URL prefixed by `primary_base_url` (the same origin & the whole path segments)
+  is rebased onto each mirror base URL, otherwise only the scheme/host/port are replaced.
+* The request body is buffered (when there're mirrors) so that it could be sent again.
+* The `RequestOptions` are kept by the failed over requests.
+* The response is annotated by the `ServedBy` extension.
+
+*/
+pub struct MirrorFailoverClient<C> {
+    pub client: Client<C, Body>,
+    pub primary_base_url: Url,
+    pub mirror_base_urls: Vec<Url>,
+}
+impl<C> MirrorFailoverClient<C> {
+    pub fn new_with_options(
+        client: Client<C, Body>,
+        primary_base_url: Url,
+        mirror_base_urls: Vec<Url>,
+    ) -> Self {
+        MirrorFailoverClient {
+            client,
+            primary_base_url,
+            mirror_base_urls,
         }
     }
 }
+impl MirrorFailoverClient<HttpConnector> {
+    pub fn new_for_hyper(primary_base_url: Url, mirror_base_urls: Vec<Url>) -> Self {
+        Self::new_with_options(Client::new(), primary_base_url, mirror_base_urls)
+    }
+}
 
-impl Stream for WriteForBody {
-    type Item = StdResult<Bytes, Box<dyn StdError + Send + Sync>>;
+// The path segments of the url (the trailing empty one of the directory urls is skipped)
+fn path_segments_of(url: &Url) -> Vec<&str> {
+    let mut segments: Vec<&str> = url.path_segments().map_or(vec![], |v| v.collect());
+    if segments.last() == Some(&"") {
+        segments.pop();
+    }
+    segments
+}
 
-    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+// Rebase the given url from the primary base url onto the mirror base url
+fn rebase_url(url: &Url, primary: &Url, mirror: &Url) -> Url {
+    // The same origin & the whole path segments of the primary one (`/api` doesn't prefix `/apiv2`)
+    let base_segments = path_segments_of(primary);
+    if url.origin() == primary.origin() && !mirror.cannot_be_a_base() {
+        if let Some(rest) = url
+            .path_segments()
+            .map(|v| v.collect::<Vec<_>>())
+            .filter(|v| v.starts_with(&base_segments))
         {
-            {
-                let mut cached = self.cached.lock().unwrap();
-                if !cached.is_empty() {
-                    self.waker.lock().unwrap().replace(cx.waker().clone());
+            let rest = &rest[base_segments.len()..];
+            let mirror_path = mirror.path().trim_end_matches('/');
+            let mut rebased = mirror.clone();
+            if rest.is_empty() {
+                rebased.set_path(mirror_path);
+            } else {
+                rebased.set_path(&format!("{}/{}", mirror_path, rest.join("/")));
+            }
+            rebased.set_query(url.query());
+            rebased.set_fragment(url.fragment());
+            return rebased;
+        }
+    }
+
+    let mut rebased = url.clone();
+    let _ = rebased.set_scheme(mirror.scheme());
+    let _ = rebased.set_host(mirror.host_str());
+    let _ = rebased.set_port(mirror.port());
+    rebased
+}
 
-                    let d = cached.pop_front();
-                    println!("WriteForBody stream read content: {:?}", d.clone());
-                    return Poll::Ready(Some(Ok(d.unwrap())));
+// Rebuild the request of an attempt from the buffered parts (by the clients sending it again).
+// `http::Extensions` can't be cloned, so the original extensions (taken from `extensions`)
+// go with the first attempt, and the `RequestOptions` (with the per-call data of its `extensions`)
+// go with every attempt.
+fn rebuild_request(
+    parts: &http::request::Parts,
+    extensions: &mut http::Extensions,
+    options: &RequestOptions,
+    body: Body,
+) -> Request<Body> {
+    let mut req = Request::new(body);
+    *req.method_mut() = parts.method.clone();
+    *req.uri_mut() = parts.uri.clone();
+    *req.version_mut() = parts.version;
+    *req.headers_mut() = parts.headers.clone();
+    *req.extensions_mut() = std::mem::take(extensions);
+    req.set_request_options(options.clone());
+    req
+}
+
+impl<C> BaseClient<Client<C, Body>, Request<Body>, Result<Response<Body>>, Method, HeaderMap, Body>
+    for MirrorFailoverClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn request(&self, req: Request<Body>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
+        let client = self.client.clone();
+        let primary = self.primary_base_url.clone();
+        let mirrors = self.mirror_base_urls.clone();
+
+        Box::pin(async move {
+            let origin = match Url::parse(&req.uri().to_string()) {
+                Ok(v) => v,
+                Err(_) => return client.request(req).await,
+            };
+            if mirrors.is_empty() {
+                let mut res = client.request(req).await?;
+                res.extensions_mut().insert(ServedBy(primary));
+                return Ok(res);
+            }
+
+            let options = req.get_request_options();
+            let (mut parts, body) = req.into_parts();
+            let mut extensions = std::mem::take(&mut parts.extensions);
+            let body = hyper::body::to_bytes(body).await?;
+
+            let mut last_error = None;
+            for base in std::iter::once(&primary).chain(mirrors.iter()) {
+                let url = if base == &primary {
+                    origin.clone()
+                } else {
+                    rebase_url(&origin, &primary, base)
+                };
+                let uri = match Uri::from_str(url.as_str()) {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                };
+
+                let mut req =
+                    rebuild_request(&parts, &mut extensions, &options, Body::from(body.clone()));
+                *req.uri_mut() = uri;
+
+                match client.request(req).await {
+                    Ok(mut res) => {
+                        res.extensions_mut().insert(ServedBy(base.clone()));
+                        return Ok(res);
+                    }
+                    // Only the connection level failures are failed over
+                    Err(e) if e.is_connect() => last_error = Some(e),
+                    Err(e) => return Err(e),
                 }
             }
-            {
-                if !self.alive.lock().unwrap().load(Ordering::SeqCst) {
-                    println!("WriteForBody stream end");
-                    return Poll::Ready(None);
+
+            match last_error {
+                Some(e) => Err(e),
+                // All of the mirror URLs are invalid, send it as it is.
+                None => {
+                    parts.extensions = extensions;
+                    client
+                        .request(Request::from_parts(parts, Body::from(body)))
+                        .await
                 }
             }
+        })
+    }
+    fn get_client(&mut self) -> &mut Client<C, Body> {
+        &mut self.client
+    }
+}
+
+/*
+`ConnectTiming` The connection level timing, attached to connections by `TimingConnector`
+(or by custom connectors via `Connected::extra()`).
+
+It's consumed by the first response served by the connection,
+so responses served by reused connections don't count the connecting time again.
+*/
+#[derive(Debug, Clone)]
+pub struct ConnectTiming {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+    consumed: Arc<AtomicBool>,
+}
+impl ConnectTiming {
+    pub fn new(dns: Option<Duration>, connect: Option<Duration>, tls: Option<Duration>) -> Self {
+        ConnectTiming {
+            dns,
+            connect,
+            tls,
+            consumed: Arc::new(AtomicBool::new(false)),
         }
+    }
 
-        {
-            self.waker.lock().unwrap().replace(cx.waker().clone());
-            println!("WriteForBody stream pending");
-            Poll::Pending
+    // Take the timing if it hasn't been taken by another response
+    pub fn take(&self) -> Option<(Option<Duration>, Option<Duration>, Option<Duration>)> {
+        if self.consumed.swap(true, Ordering::SeqCst) {
+            None
+        } else {
+            Some((self.dns, self.connect, self.tls))
+        }
+    }
+}
+
+tokio::task_local! {
+    // The connecting phases recorded by `TimingResolver` & the nested `TimingConnector`,
+    // in the scope of the connecting of the outermost `TimingConnector`
+    static CONNECT_PHASES: Arc<Mutex<ConnectPhases>>;
+}
+
+#[derive(Debug, Default)]
+struct ConnectPhases {
+    dns: Option<Duration>,
+    // When the nested `TimingConnector` (e.g. the TCP one wrapped by a TLS connector) connected
+    nested_connected: Option<Instant>,
+}
+
+/**
+`TimingResolver` wraps a resolver of `HttpConnector` and measures how long the DNS lookups take,
+for the `dns` of the `ConnectTiming` of `TimingConnector`.
+
+# Remarks
+
+* The lookups out of the scope of the connecting of `TimingConnector` aren't recorded.
+* The IP literal hosts aren't looked up by `HttpConnector`, so their `dns` is `None`.
+
+*/
+#[derive(Debug, Clone, Default)]
+pub struct TimingResolver<R = GaiResolver>(pub R);
+impl TimingResolver<GaiResolver> {
+    pub fn new_for_hyper() -> Self {
+        TimingResolver(GaiResolver::new())
+    }
+}
+impl<R> Service<Name> for TimingResolver<R>
+where
+    R: Service<Name>,
+    R::Future: Send + 'static,
+{
+    type Response = R::Response;
+    type Error = R::Error;
+    type Future = Pin<Box<dyn Future<Output = StdResult<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<StdResult<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let phases = CONNECT_PHASES.try_with(|v| v.clone()).ok();
+        let start = Instant::now();
+        let resolving = self.0.call(name);
+        Box::pin(async move {
+            let result = resolving.await;
+            if let Some(phases) = phases {
+                phases.lock().unwrap().dns = Some(start.elapsed());
+            }
+            result
+        })
+    }
+}
+
+/**
+`TimingConnector` wraps a connector and measures how long it takes to connect.
+
+# Remarks
+
+* `dns` is measured if the DNS lookups are made by `TimingResolver` (e.g. by `new_for_hyper()`),
+  and it's excluded from `connect`.
+* For the TLS connectors, wrap the TCP connector by a `TimingConnector` as well
+  (e.g. `TimingConnector(HttpsConnector::from((TimingConnector::new_for_hyper(), config)))`):
+  the nested one marks when the TCP connection is made, so `tls` is measured after that.
+  Otherwise the whole connecting time (TCP + TLS) is reported as `connect`, and `tls` is `None`.
+* Only the outermost `TimingConnector` attaches the `ConnectTiming`.
+
+*/
+#[derive(Debug, Clone)]
+pub struct TimingConnector<C>(pub C);
+impl TimingConnector<HttpConnector<TimingResolver>> {
+    pub fn new_for_hyper() -> Self {
+        let mut connector = HttpConnector::new_with_resolver(TimingResolver::new_for_hyper());
+        // The `https` urls are connected by the TLS connectors wrapping it
+        connector.enforce_http(false);
+        TimingConnector(connector)
+    }
+}
+impl<C> Service<Uri> for TimingConnector<C>
+where
+    C: Service<Uri>,
+    C::Future: Send + 'static,
+{
+    type Response = TimedConnection<C::Response>;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = StdResult<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<StdResult<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        // Nested in the connecting of another `TimingConnector`: mark when it's connected only
+        if let Ok(phases) = CONNECT_PHASES.try_with(|v| v.clone()) {
+            let connecting = self.0.call(uri);
+            return Box::pin(async move {
+                let inner = connecting.await?;
+                phases.lock().unwrap().nested_connected = Some(Instant::now());
+                Ok(TimedConnection {
+                    inner,
+                    timing: None,
+                })
+            });
         }
+
+        let phases = Arc::new(Mutex::new(ConnectPhases::default()));
+        let start = Instant::now();
+        let connecting = CONNECT_PHASES.sync_scope(phases.clone(), || self.0.call(uri));
+        Box::pin(CONNECT_PHASES.scope(phases.clone(), async move {
+            let inner = connecting.await?;
+            let end = Instant::now();
+            let phases = phases.lock().unwrap();
+            let dns = phases.dns;
+            let connected = phases.nested_connected.unwrap_or(end);
+            let connect = connected
+                .saturating_duration_since(start)
+                .saturating_sub(dns.unwrap_or_default());
+            let tls = phases
+                .nested_connected
+                .map(|nested_connected| end.saturating_duration_since(nested_connected));
+            Ok(TimedConnection {
+                inner,
+                timing: Some(ConnectTiming::new(dns, Some(connect), tls)),
+            })
+        }))
     }
 }
 
-impl io::Write for WriteForBody {
-    fn write(&mut self, d: &[u8]) -> io::Result<usize> {
-        let len = d.len();
-        println!("WriteForBody write len: {:?}", len);
-        if len <= 0 {
-            return Ok(len);
+// TimedConnection The connection made by `TimingConnector`
+pub struct TimedConnection<T> {
+    inner: T,
+    timing: Option<ConnectTiming>,
+}
+impl<T: Connection> Connection for TimedConnection<T> {
+    fn connected(&self) -> Connected {
+        match &self.timing {
+            Some(timing) => self.inner.connected().extra(timing.clone()),
+            None => self.inner.connected(),
         }
-        let d = Bytes::from(d.to_vec());
-        println!("WriteForBody write content: {:?}", d.clone());
+    }
+}
+impl<T: AsyncRead + Unpin> AsyncRead for TimedConnection<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+impl<T: AsyncWrite + Unpin> AsyncWrite for TimedConnection<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
 
-        {
-            let mut cached = self.cached.lock().unwrap();
-            cached.push_back(d);
-            cached.reserve_exact(10);
+/**
+`TimingClient` implements the request timing breakdown as a `BaseClient`.
+
+# Remarks
+
+* The `RequestTiming` is inserted into the response extensions (`total` is `None` there).
+* `dns`/`connect`/`tls` are obtainable when the connector attaches `ConnectTiming` (e.g. `TimingConnector`),
+  and `tls_info` when it attaches `TlsInfo`.
+* If there's a `metrics_sink`, the timing with `total` is recorded when the response body is fully read
+  (or when the call failed, with its `TransportErrorKind`), and the response body is wrapped as a stream for that.
+* `new_for_hyper()` uses `TimingConnector`, so it's used by `SimpleHTTP::new_with_timeout()`
+  instead of `set_client()` of the default `SimpleHTTP` (their client types differ).
+
+*/
+pub struct TimingClient<C> {
+    pub client: Client<C, Body>,
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+}
+impl<C> TimingClient<C> {
+    pub fn new_with_options(
+        client: Client<C, Body>,
+        metrics_sink: Option<Arc<dyn MetricsSink>>,
+    ) -> Self {
+        TimingClient {
+            client,
+            metrics_sink,
         }
-        {
-            if let Some(waker) = self.waker.lock().unwrap().take() {
-                waker.wake();
+    }
+}
+impl TimingClient<TimingConnector<HttpConnector<TimingResolver>>> {
+    pub fn new_for_hyper(metrics_sink: Option<Arc<dyn MetricsSink>>) -> Self {
+        Self::new_with_options(
+            Client::builder().build(TimingConnector::new_for_hyper()),
+            metrics_sink,
+        )
+    }
+}
+
+impl<C> BaseClient<Client<C, Body>, Request<Body>, Result<Response<Body>>, Method, HeaderMap, Body>
+    for TimingClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn request(&self, req: Request<Body>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
+        let client = self.client.clone();
+        let metrics_sink = self.metrics_sink.clone();
+        let method = req.method().to_string();
+        let url = req.uri().to_string();
+
+        Box::pin(async move {
+            let start = Instant::now();
+            let mut res = match client.request(req).await {
+                Ok(res) => res,
+                Err(e) => {
+                    if let Some(metrics_sink) = metrics_sink {
+                        let timing = RequestTiming {
+                            total: Some(start.elapsed()),
+                            ..Default::default()
+                        };
+                        let _ = catch_hook_panic("metrics_sink", || {
+                            metrics_sink.record_timing(&method, &url, &timing);
+                            if let Some(kind) = TransportErrorKind::of(&e) {
+                                metrics_sink.record_transport_error(&method, &url, kind);
+                            }
+                        });
+                    }
+                    return Err(e);
+                }
+            };
+
+            let mut timing = RequestTiming {
+                ttfb: Some(start.elapsed()),
+                ..Default::default()
+            };
+            if let Some((dns, connect, tls)) = res
+                .extensions()
+                .get::<ConnectTiming>()
+                .and_then(|connect_timing| connect_timing.take())
+            {
+                timing.dns = dns;
+                timing.connect = connect;
+                timing.tls = tls;
             }
+            timing.tls_info = res.extensions().get::<TlsInfo>().cloned();
+            res.extensions_mut().insert(timing.clone());
+
+            let metrics_sink = match metrics_sink {
+                Some(v) => v,
+                None => return Ok(res),
+            };
+            let (parts, body) = res.into_parts();
+            let on_end = move || {
+                let mut timing = timing;
+                timing.total = Some(start.elapsed());
+                let _ = catch_hook_panic("metrics_sink", || {
+                    metrics_sink.record_timing(&method, &url, &timing)
+                });
+            };
+            let body = stream::unfold((body, Some(on_end)), |(mut body, mut on_end)| async move {
+                let item = body.next().await;
+                if !matches!(item, Some(Ok(_))) {
+                    if let Some(on_end) = on_end.take() {
+                        on_end();
+                    }
+                }
+                item.map(|item| (item, (body, on_end)))
+            });
+            Ok(Response::from_parts(parts, Body::wrap_stream(body)))
+        })
+    }
+    fn get_client(&mut self) -> &mut Client<C, Body> {
+        &mut self.client
+    }
+}
+
+/**
+`RetryClient` implements the retries limited by a `RetryBudget` as a `BaseClient`.
+
+# Remarks
+
+* The connection level failures of the `retryable_errors` kinds (`TransportErrorKind`) are retried:
+  the ones failed to connect (not sent) for all methods, the others (e.g. reset) only for the idempotent methods.
+* `502`/`503`/`504` responses are retried only for the idempotent methods.
+* The `retry_budget` is shared (`Arc`) per service, so retry storms can't amplify outages:
+  when it's exhausted the last result is returned as it is.
+* The budget consumption is recorded by `MetricsSink::record_retry()` if there's a `metrics_sink`,
+  and so are the transport errors of the attempts by `MetricsSink::record_transport_error()`
+  and the attempts by `MetricsSink::record_event()` (`ResilienceEvent::RetryAttempt`).
+* The request body is buffered (when `max_retries > 0`) so that it could be sent again,
+  unless there's a `BodyFactory<Body>` extension (e.g. the streaming uploads):
+  the retries send the bodies produced by it instead (the last result is returned if it fails).
+* The backoff sleeps by the `clock` (`SystemClock` by default).
+* The `RequestOptions` are kept by the retried requests, with the `RetryAttempt` in the `extensions`,
+  and the responses are annotated by the `RetryAttempt` extension as well.
+  The other request extensions go with the first attempt (`http::Extensions` can't be cloned).
+
+*/
+pub struct RetryClient<C> {
+    pub client: Client<C, Body>,
+    pub max_retries: u32,
+    // The delay before the first retry, it's doubled after each retry
+    pub retry_backoff: Duration,
+    pub retry_budget: Arc<RetryBudget>,
+    // The kinds of the transport errors worth retrying (`DEFAULT_RETRYABLE_TRANSPORT_ERRORS` by default)
+    pub retryable_errors: Vec<TransportErrorKind>,
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+    pub clock: Arc<dyn Clock>,
+}
+impl<C> RetryClient<C> {
+    pub fn new_with_options(
+        client: Client<C, Body>,
+        max_retries: u32,
+        retry_backoff: Duration,
+        retry_budget: Arc<RetryBudget>,
+        metrics_sink: Option<Arc<dyn MetricsSink>>,
+    ) -> Self {
+        RetryClient {
+            client,
+            max_retries,
+            retry_backoff,
+            retry_budget,
+            retryable_errors: DEFAULT_RETRYABLE_TRANSPORT_ERRORS.to_vec(),
+            metrics_sink,
+            clock: system_clock(),
         }
+    }
+}
+impl RetryClient<HttpConnector> {
+    pub fn new_for_hyper(retry_budget: Arc<RetryBudget>) -> Self {
+        Self::new_with_options(
+            Client::new(),
+            3,
+            Duration::from_millis(100),
+            retry_budget,
+            None,
+        )
+    }
+}
 
-        /*
-        match self.0.try_send_data(d) {
-            Ok(_) => {
-                println!("WriteForBody write ok");
+// The transport errors retried by `RetryClient` (the TLS failures won't be fixed by retrying)
+pub const DEFAULT_RETRYABLE_TRANSPORT_ERRORS: [TransportErrorKind; 6] = [
+    TransportErrorKind::Dns,
+    TransportErrorKind::ConnectionRefused,
+    TransportErrorKind::ConnectionReset,
+    TransportErrorKind::BrokenPipe,
+    TransportErrorKind::TimedOut,
+    TransportErrorKind::Other,
+];
+
+// Whether the result is worth retrying for the method
+fn is_retryable(
+    method: &Method,
+    result: &Result<Response<Body>>,
+    retryable_errors: &[TransportErrorKind],
+) -> bool {
+    match result {
+        Ok(res) => method.is_idempotent() && matches!(res.status().as_u16(), 502..=504),
+        Err(e) => {
+            TransportErrorKind::of(e).is_some_and(|kind| retryable_errors.contains(&kind))
+                && (e.is_connect() || method.is_idempotent())
+        }
+    }
+}
+
+impl<C> BaseClient<Client<C, Body>, Request<Body>, Result<Response<Body>>, Method, HeaderMap, Body>
+    for RetryClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn request(&self, req: Request<Body>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
+        let client = self.client.clone();
+        let max_retries = self.max_retries;
+        let mut backoff = self.retry_backoff;
+        let retry_budget = self.retry_budget.clone();
+        let retryable_errors = self.retryable_errors.clone();
+        let metrics_sink = self.metrics_sink.clone();
+        let clock = self.clock.clone();
+
+        Box::pin(async move {
+            retry_budget.deposit();
+            if max_retries == 0 {
+                return client.request(req).await;
             }
-            Err(_) => {
-                println!("WriteForBody write error");
+
+            let method = req.method().to_string();
+            let url = req.uri().to_string();
+            let options = req.get_request_options();
+            let body_factory = options.extensions.get::<BodyFactory<Body>>().cloned();
+            let (mut parts, body) = req.into_parts();
+            let mut extensions = std::mem::take(&mut parts.extensions);
+            let (mut first_body, body) = match body_factory {
+                Some(_) => (Some(body), Bytes::new()),
+                None => (None, hyper::body::to_bytes(body).await?),
+            };
+
+            let mut retries = 0;
+            let mut content_type = None;
+            let mut last_result = None;
+            loop {
+                let body = match (first_body.take(), &body_factory) {
+                    (Some(first_body), _) => first_body,
+                    (None, Some(body_factory)) => match body_factory.produce().await {
+                        Ok((produced_content_type, produced_body)) => {
+                            content_type = Some(produced_content_type);
+                            produced_body
+                        }
+                        // The attempts can't be made without the bodies
+                        Err(_) => return last_result.unwrap(),
+                    },
+                    (None, None) => Body::from(body.clone()),
+                };
+                let mut options = options.clone();
+                options.extensions.insert(RetryAttempt(retries));
+                let mut req = rebuild_request(&parts, &mut extensions, &options, body);
+                if let Some(Ok(content_type)) = content_type.as_deref().map(HeaderValue::from_str) {
+                    req.headers_mut().insert(CONTENT_TYPE, content_type);
+                }
+
+                let mut result = client.request(req).await;
+                match &mut result {
+                    Ok(res) => {
+                        res.extensions_mut().insert(RetryAttempt(retries));
+                    }
+                    Err(e) => {
+                        if let (Some(metrics_sink), Some(kind)) =
+                            (&metrics_sink, TransportErrorKind::of(e))
+                        {
+                            let _ = catch_hook_panic("metrics_sink", || {
+                                metrics_sink.record_transport_error(&method, &url, kind)
+                            });
+                        }
+                    }
+                }
+                if retries >= max_retries
+                    || !is_retryable(&parts.method, &result, &retryable_errors)
+                {
+                    return result;
+                }
+
+                let allowed = retry_budget.try_withdraw();
+                if let Some(metrics_sink) = &metrics_sink {
+                    let _ = catch_hook_panic("metrics_sink", || {
+                        metrics_sink.record_retry(&method, &url, &retry_budget.usage(), allowed);
+                        metrics_sink.record_event(&ResilienceEvent::RetryAttempt {
+                            method: method.clone(),
+                            url: url.clone(),
+                            attempt: retries + 1,
+                            backoff,
+                            allowed,
+                        });
+                    });
+                }
+                if !allowed {
+                    return result;
+                }
+                last_result = Some(result);
+
+                retries += 1;
+                clock.sleep(backoff).await;
+                backoff *= 2;
             }
+        })
+    }
+    fn get_client(&mut self) -> &mut Client<C, Body> {
+        &mut self.client
+    }
+}
+
+/*
+`UsedCredential` The response extension annotating the index of the credential (of `CredentialRotation`)
+sent with the request, set by `CredentialRotationClient`.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsedCredential(pub usize);
+
+/**
+`CredentialRotationClient` implements the credential rotation (by `CredentialRotation`) as a `BaseClient`.
+
+# Remarks
+
+* The credential header is set to the next credential, and the responses are reported back
+  to the `rotation` so that the rejected/rate limited credentials are skipped.
+* The rejected (`429`/`403`) requests are sent again with the other credentials
+  (at most once per credential) if `retry_rejected` is set,
+  and the request body is buffered so that it could be sent again.
+* The response is annotated by the `UsedCredential` extension.
+
+*/
+pub struct CredentialRotationClient<C> {
+    pub client: Client<C, Body>,
+    pub rotation: Arc<CredentialRotation>,
+    pub retry_rejected: bool,
+}
+impl<C> CredentialRotationClient<C> {
+    pub fn new_with_options(
+        client: Client<C, Body>,
+        rotation: Arc<CredentialRotation>,
+        retry_rejected: bool,
+    ) -> Self {
+        CredentialRotationClient {
+            client,
+            rotation,
+            retry_rejected,
         }
-        */
-        Ok(len)
     }
+}
+impl CredentialRotationClient<HttpConnector> {
+    pub fn new_for_hyper(rotation: Arc<CredentialRotation>) -> Self {
+        Self::new_with_options(Client::new(), rotation, true)
+    }
+}
 
-    fn flush(&mut self) -> io::Result<()> {
-        {
-            if let Some(waker) = self.waker.lock().unwrap().take() {
-                waker.wake();
+// Send the request with the next credential, and report the response back
+async fn request_with_credential<C>(
+    client: &Client<C, Body>,
+    rotation: &CredentialRotation,
+    mut req: Request<Body>,
+) -> Result<Response<Body>>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    // The credentials are skipped by `RequestOptions::skip_auth`
+    let credential = if req.get_request_options().skip_auth {
+        None
+    } else {
+        rotation.next()
+    };
+    let index = match credential {
+        Some((index, value)) => {
+            // The header name & values are validated by `CredentialRotation::new()`
+            if let (Ok(name), Ok(mut value)) = (
+                HeaderName::from_bytes(rotation.header_name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                value.set_sensitive(true);
+                req.headers_mut().insert(name, value);
             }
+            Some(index)
         }
+        None => None,
+    };
 
-        println!("flush ok");
-        Ok(())
+    let mut result = client.request(req).await;
+    if let (Some(index), Ok(res)) = (index, &mut result) {
+        rotation.report(index, res.status().as_u16(), &Headers::from(res.headers()));
+        res.extensions_mut().insert(UsedCredential(index));
     }
+    result
 }
 
-#[cfg(feature = "multipart")]
-#[derive(Debug, Clone)]
-/// MultipartSerializerForStream Serialize the multipart body (for put/post/patch etc)
-pub struct MultipartSerializerForStream {
-    // NOTE: It can't be Copy because of this one:
-    thread_pool: Option<Arc<ThreadPool>>,
+impl<C> BaseClient<Client<C, Body>, Request<Body>, Result<Response<Body>>, Method, HeaderMap, Body>
+    for CredentialRotationClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn request(&self, req: Request<Body>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
+        let client = self.client.clone();
+        let rotation = self.rotation.clone();
+        let retry_rejected = self.retry_rejected;
+
+        Box::pin(async move {
+            if !retry_rejected || rotation.len() < 2 {
+                return request_with_credential(&client, &rotation, req).await;
+            }
+
+            let options = req.get_request_options();
+            let (mut parts, body) = req.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+            // The extensions go with the first attempt (they're not `Clone`)
+            let mut extensions = std::mem::take(&mut parts.extensions);
+
+            let mut attempts = 0;
+            loop {
+                let req =
+                    rebuild_request(&parts, &mut extensions, &options, Body::from(body.clone()));
+
+                let result = request_with_credential(&client, &rotation, req).await;
+                attempts += 1;
+                let rejected = match &result {
+                    Ok(res) => matches!(res.status().as_u16(), 403 | 429),
+                    Err(_) => false,
+                };
+                if !rejected || attempts >= rotation.len() {
+                    return result;
+                }
+            }
+        })
+    }
+    fn get_client(&mut self) -> &mut Client<C, Body> {
+        &mut self.client
+    }
 }
-#[cfg(feature = "multipart")]
-impl BodySerializer<FormData, (String, Body)> for MultipartSerializerForStream
+
+/**
+`RedirectClient` implements the redirect following (by the `RedirectPolicy`) as a `BaseClient`.
+
+# Remarks
+
+* The `policy` decides whether each redirect is followed, and adjusts the headers of the redirected request
+  (`DefaultRedirectPolicy` strips the credentials of the cross-origin redirects).
+* The request bodies of the known sizes are buffered so that they could be sent again (for `307`/`308`),
+  and the bodies are dropped when the redirects change the methods to `GET`.
+* The streaming bodies (e.g. the uploads) are forwarded as they are: the redirects keeping them are sent
+  with the bodies produced by the `BodyFactory<Body>` extension, or returned as they are without it.
+* The redirects not followed (e.g. over `max_redirects`, or over `MAX_REDIRECTS` whatever the `policy` decides)
+  are returned as they are.
+* The `RequestOptions` are kept by the redirected requests.
+
+*/
+pub struct RedirectClient<C> {
+    pub client: Client<C, Body>,
+    pub policy: Arc<dyn RedirectPolicy>,
+}
+impl<C> RedirectClient<C> {
+    pub fn new_with_options(client: Client<C, Body>, policy: Arc<dyn RedirectPolicy>) -> Self {
+        RedirectClient { client, policy }
+    }
+}
+impl RedirectClient<HttpConnector> {
+    pub fn new_for_hyper() -> Self {
+        Self::new_with_options(Client::new(), Arc::new(DefaultRedirectPolicy::default()))
+    }
+}
+
+impl<C> BaseClient<Client<C, Body>, Request<Body>, Result<Response<Body>>, Method, HeaderMap, Body>
+    for RedirectClient<C>
 where
-// B: HttpBody + Send + 'static,
-// B: From<Body>,
-// B::Data: Send,
-// B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    C: Connect + Clone + Send + Sync + 'static,
 {
-    fn encode(&self, origin: FormData) -> StdResult<(String, Body), Box<dyn StdError>> {
-        // let mut data = Vec::<u8>::new();
-
-        let (tx, rx) = make_stream::<Bytes>();
-        let mut data = WriteForStream(tx);
-
-        /*
-        let (tx, body) = Body::channel();
-        let mut data = WriteForBody(Box::new(tx));
-        // */
-
-        /*
-        let mut data = WriteForBody {
-            cached: Arc::new(Mutex::new(VecDeque::with_capacity(10))),
-            waker: Arc::new(Mutex::new(None)),
-            alive: Arc::new(Mutex::new(AtomicBool::new(true))),
-        };
-        let body = data.clone();
-        */
-
-        let boundary = formdata::generate_boundary();
-        let boundary_thread = boundary.clone();
-        //*
-        // println!("Enter encode");
-        let _ = thread::spawn(move || {
-            // let _ = tokio::spawn(async move {
-            // let _ = tokio::spawn(async move {
-            // tokio::time::sleep(std::time::Duration::from_millis(200)).await;
-            // thread::sleep_ms(2000);
-            // data.0.send_data(Bytes::new()).await;
-
-            // println!("spawn: Some");
-            // println!("write_formdata begin");
-            match formdata::write_formdata(&mut data, &boundary_thread, &origin) {
-                Err(e) => println!("Error -> write_formdata {:?}", e),
-                _ => {}
-            };
-            // println!("write_formdata done");
+    fn request(&self, req: Request<Body>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
+        let client = self.client.clone();
+        let policy = self.policy.clone();
 
-            match data.flush() {
-                Err(e) => println!("Error -> flush {:?}", e),
-                _ => {}
+        Box::pin(async move {
+            let options = req.get_request_options();
+            let body_factory = options.extensions.get::<BodyFactory<Body>>().cloned();
+            let (parts, body) = req.into_parts();
+            // Only the bodies of the known sizes are buffered, the streaming ones are forwarded as they are
+            let (mut streaming_body, mut body) = match HttpBody::size_hint(&body).exact() {
+                Some(_) => (None, hyper::body::to_bytes(body).await?),
+                None => (Some(body), Bytes::new()),
             };
-            // println!("flush ok");
+            let mut streaming = streaming_body.is_some();
+            let mut method = parts.method;
+            let mut uri = parts.uri;
+            let mut headers = parts.headers;
+
+            let mut count = 0;
+            loop {
+                let mut req = Request::new(match streaming_body.take() {
+                    Some(streaming_body) => streaming_body,
+                    None => Body::from(body.clone()),
+                });
+                *req.method_mut() = method.clone();
+                *req.uri_mut() = uri.clone();
+                *req.version_mut() = parts.version;
+                *req.headers_mut() = headers.clone();
+                req.set_request_options(options.clone());
+
+                let result = client.request(req).await;
+                let res = match &result {
+                    Ok(res) => res,
+                    Err(_) => return result,
+                };
+                count += 1;
+                let redirect = match Url::parse(&uri.to_string()).ok().and_then(|from| {
+                    let location = res.headers().get(LOCATION).and_then(|v| v.to_str().ok());
+                    Redirect::new(
+                        res.status().as_u16(),
+                        method.as_str(),
+                        &from,
+                        location,
+                        count,
+                    )
+                }) {
+                    Some(redirect) => redirect,
+                    None => return result,
+                };
+                if redirect.count > MAX_REDIRECTS {
+                    return result;
+                }
 
-            let mut tx = data.0;
-            tx.close_channel();
-            drop(tx);
-            // println!("Close!!");
-        });
-        // */
-        let content_type = get_content_type_from_multipart_boundary(boundary)?;
+                let mut redirected_headers = Headers::from(&headers);
+                if policy.redirect(&redirect, &mut redirected_headers) == RedirectAction::Stop {
+                    return result;
+                }
+                if !redirect.keeps_body() {
+                    body = Bytes::new();
+                    streaming = false;
+                    redirected_headers.remove(CONTENT_TYPE.as_str());
+                    redirected_headers.remove(CONTENT_LENGTH.as_str());
+                } else if streaming {
+                    // The streaming bodies can't be sent again without the `BodyFactory<Body>`
+                    match &body_factory {
+                        Some(body_factory) => match body_factory.produce().await {
+                            Ok((content_type, produced_body)) => {
+                                redirected_headers.insert(CONTENT_TYPE.as_str(), content_type);
+                                streaming_body = Some(produced_body);
+                            }
+                            Err(_) => return result,
+                        },
+                        None => return result,
+                    }
+                }
+                // The illegal ones (e.g. set by the policy) aren't followed
+                match (
+                    HeaderMap::try_from(&redirected_headers),
+                    Method::from_str(&redirect.method),
+                    Uri::from_str(redirect.to.as_str()),
+                ) {
+                    (Ok(redirected_headers), Ok(redirected_method), Ok(redirected_uri)) => {
+                        headers = redirected_headers;
+                        method = redirected_method;
+                        uri = redirected_uri;
+                    }
+                    _ => return result,
+                }
+            }
+        })
+    }
+    fn get_client(&mut self) -> &mut Client<C, Body> {
+        &mut self.client
+    }
+}
+
+pub const DEFAULT_EXPECT_CONTINUE_THRESHOLD: u64 = 1024 * 1024;
 
-        let body = rx
-            .map(|y| Ok::<Bytes, Box<dyn StdError + Send + Sync>>(y))
-            .into_stream();
+/*
+`ExpectContinueOutcome` The response extension annotating how the body of `ExpectContinueClient` went:
+sent after `100 Continue` or the timeout, or not sent at all (rejected by the early final response).
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExpectContinueOutcome {
+    Continued,
+    TimedOut,
+    Rejected,
+}
 
-        // Ok((content_type, B::from(body)))
-        // Ok((content_type, body))
-        Ok((content_type, Body::wrap_stream(body)))
+// ContinueSniffer The connection of `ExpectContinueClient`, telling whether the first response is `100 Continue`
+struct ContinueSniffer<T> {
+    inner: T,
+    prefix: Vec<u8>,
+    signal: Option<futures::channel::oneshot::Sender<()>>,
+}
+impl<T: Connection> Connection for ContinueSniffer<T> {
+    fn connected(&self) -> Connected {
+        self.inner.connected()
+    }
+}
+impl<T: AsyncRead + Unpin> AsyncRead for ContinueSniffer<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let (Poll::Ready(Ok(())), Some(_)) = (&result, &self.signal) {
+            let read = &buf.filled()[filled..];
+            // `HTTP/1.1 100` (the status line could be split)
+            let wanted = 12 - self.prefix.len();
+            let read = &read[..read.len().min(wanted)];
+            let eof = read.is_empty();
+            self.prefix.extend_from_slice(read);
+            if self.prefix.len() >= 12 || eof {
+                let signal = self.signal.take();
+                if self.prefix.starts_with(b"HTTP/1.") && self.prefix.get(8..12) == Some(b" 100") {
+                    if let Some(signal) = signal {
+                        let _ = signal.send(());
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+impl<T: AsyncWrite + Unpin> AsyncWrite for ContinueSniffer<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
     }
 }
-#[cfg(feature = "multipart")]
-#[allow(dead_code)]
-pub(crate) const DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM: MultipartSerializerForStream =
-    MultipartSerializerForStream { thread_pool: None };
 
-pub struct HyperClient<C, B> {
-    pub client: Client<C, B>,
-    pub thread_pool: Option<ThreadPool>,
+// GatedBody The body held until the gate is passed (it's held forever if the gate is closed)
+struct GatedBody {
+    inner: Body,
+    gate: Option<Pin<Box<dyn Future<Output = bool> + Send>>>,
+    closed: bool,
 }
-impl<C, B> BaseClient<Client<C, B>, Request<B>, Result<Response<Body>>, Method, HeaderMap, B>
-    for HyperClient<C, B>
+impl GatedBody {
+    fn poll_gate(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.closed {
+            return Poll::Pending;
+        }
+        if let Some(gate) = &mut self.gate {
+            match gate.as_mut().poll(cx) {
+                Poll::Ready(true) => self.gate = None,
+                Poll::Ready(false) => {
+                    self.closed = true;
+                    return Poll::Pending;
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        Poll::Ready(())
+    }
+}
+impl HttpBody for GatedBody {
+    type Data = Bytes;
+    type Error = hyper::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<StdResult<Self::Data, Self::Error>>> {
+        futures::ready!(self.poll_gate(cx));
+        Pin::new(&mut self.inner).poll_data(cx)
+    }
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<StdResult<Option<HeaderMap>, Self::Error>> {
+        futures::ready!(self.poll_gate(cx));
+        Pin::new(&mut self.inner).poll_trailers(cx)
+    }
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+    fn size_hint(&self) -> hyper::body::SizeHint {
+        HttpBody::size_hint(&self.inner)
+    }
+}
+
+/**
+`ExpectContinueClient` sends the large bodies with `Expect: 100-continue` as a `BaseClient`,
+so that the bodies rejected by the servers (e.g. `401`/`413`) aren't uploaded.
+
+# Remarks
+
+* The bodies of at least `threshold` bytes (by `Content-Length` or the size hint)
+  and the ones of the unknown sizes (e.g. the streams) are held until `100 Continue`,
+  or until `timeout` passes without it (some servers never send it).
+* The final responses before that are the rejections, they're returned (with the buffered bodies)
+  without sending the bodies, and their connections are closed.
+* The interim responses aren't exposed by the pooled connections of hyper,
+  so they're sent over the dedicated connections made by the `connector`;
+  the other requests (and the failed connections) are sent by `client`.
+* The response is annotated by the `ExpectContinueOutcome` extension.
+
+*/
+pub struct ExpectContinueClient<C> {
+    pub client: Client<C, Body>,
+    pub connector: C,
+    pub threshold: u64,
+    pub timeout: Duration,
+    pub clock: Arc<dyn Clock>,
+}
+impl<C: Connect + Clone> ExpectContinueClient<C> {
+    pub fn new_with_options(connector: C, threshold: u64, timeout: Duration) -> Self {
+        ExpectContinueClient {
+            client: Client::builder().build(connector.clone()),
+            connector,
+            threshold,
+            timeout,
+            clock: system_clock(),
+        }
+    }
+}
+impl ExpectContinueClient<HttpConnector> {
+    pub fn new_for_hyper() -> Self {
+        Self::new_with_options(
+            HttpConnector::new(),
+            DEFAULT_EXPECT_CONTINUE_THRESHOLD,
+            Duration::from_secs(1),
+        )
+    }
+}
+
+impl<C> BaseClient<Client<C, Body>, Request<Body>, Result<Response<Body>>, Method, HeaderMap, Body>
+    for ExpectContinueClient<C>
 where
-    C: Connect + Clone + Send + Sync + 'static,
-    B: HttpBody + Send + 'static,
-    B::Data: Send,
-    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+    C: Connect + Service<Uri> + Clone + Send + Sync + 'static,
+    C::Response: AsyncRead + AsyncWrite + Connection + Unpin + Send + 'static,
+    C::Future: Send + 'static,
 {
-    fn request(&self, req: Request<B>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
-        Box::pin(self.client.request(req))
+    fn request(&self, req: Request<Body>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
+        let client = self.client.clone();
+        let mut connector = self.connector.clone();
+        let threshold = self.threshold;
+        let sleep = self.clock.sleep(self.timeout);
+
+        Box::pin(async move {
+            let size = req
+                .headers()
+                .get(CONTENT_LENGTH)
+                .and_then(|v| v.to_str().ok()?.parse::<u64>().ok())
+                .or_else(|| HttpBody::size_hint(req.body()).exact());
+            if matches!(size, Some(size) if size < threshold) || req.uri().authority().is_none() {
+                return client.request(req).await;
+            }
+
+            let connected = match future::poll_fn(|cx| connector.poll_ready(cx)).await {
+                Ok(_) => connector.call(req.uri().clone()).await,
+                Err(e) => Err(e),
+            };
+            let io = match connected {
+                Ok(io) => io,
+                Err(_) => return client.request(req).await,
+            };
+            let (continue_sender, continue_receiver) = futures::channel::oneshot::channel();
+            let (mut sender, connection) = hyper::client::conn::Builder::new()
+                .handshake::<_, GatedBody>(ContinueSniffer {
+                    inner: io,
+                    prefix: Vec::new(),
+                    signal: Some(continue_sender),
+                })
+                .await?;
+            let connection = tokio::spawn(connection);
+
+            // The outcome is decided once, by the gate of the body or by the early final response
+            let outcome = Arc::new(Mutex::new(None));
+            let outcome_for_gate = outcome.clone();
+            let gate = Box::pin(async move {
+                let decided = match future::select(continue_receiver, sleep).await {
+                    future::Either::Left((Ok(()), _)) => Some(ExpectContinueOutcome::Continued),
+                    // The first response isn't `100 Continue`
+                    future::Either::Left((Err(_), _)) => None,
+                    future::Either::Right(_) => Some(ExpectContinueOutcome::TimedOut),
+                };
+                let mut outcome = outcome_for_gate.lock().unwrap();
+                if outcome.is_none() {
+                    *outcome = decided;
+                }
+                *outcome != Some(ExpectContinueOutcome::Rejected) && outcome.is_some()
+            });
+
+            let (mut parts, body) = req.into_parts();
+            if let Some(authority) = parts.uri.authority() {
+                if !parts.headers.contains_key(HOST) {
+                    if let Ok(host) = HeaderValue::from_str(authority.as_str()) {
+                        parts.headers.insert(HOST, host);
+                    }
+                }
+            }
+            parts
+                .headers
+                .insert(EXPECT, HeaderValue::from_static("100-continue"));
+            // The origin-form over the dedicated connection
+            parts.uri = parts
+                .uri
+                .path_and_query()
+                .map_or("/", |v| v.as_str())
+                .parse()
+                .unwrap_or_default();
+            let body = GatedBody {
+                inner: body,
+                gate: Some(gate),
+                closed: false,
+            };
+            let mut res = sender
+                .send_request(Request::from_parts(parts, body))
+                .await?;
+
+            let decided = {
+                let mut outcome = outcome.lock().unwrap();
+                *outcome.get_or_insert(ExpectContinueOutcome::Rejected)
+            };
+            if decided == ExpectContinueOutcome::Rejected {
+                let (parts, body) = res.into_parts();
+                let body = hyper::body::to_bytes(body).await;
+                connection.abort();
+                res = Response::from_parts(parts, Body::from(body?));
+            }
+            res.extensions_mut().insert(decided);
+            Ok(res)
+        })
     }
-    fn get_client(&mut self) -> &mut Client<C, B> {
-        return &mut self.client;
+    fn get_client(&mut self) -> &mut Client<C, Body> {
+        &mut self.client
     }
 }
 
@@ -266,12 +1684,91 @@ impl<Client, Req, Res, B> BaseAPI<Client, Req, Res, Method, HeaderMap, B>
     fn get_base_url(&self) -> Url {
         self.0.base_url.clone()
     }
-    fn set_default_header(&mut self, header: Option<HeaderMap>) {
+    fn set_url_join_strategy(&mut self, strategy: UrlJoinStrategy) {
+        self.0.url_join_strategy = strategy;
+    }
+    fn get_url_join_strategy(&self) -> UrlJoinStrategy {
+        self.0.url_join_strategy
+    }
+    fn set_url_normalization(&mut self, normalization: UrlNormalization) {
+        self.0.url_normalization = normalization;
+    }
+    fn get_url_normalization(&self) -> UrlNormalization {
+        self.0.url_normalization
+    }
+    fn set_versioning(&mut self, versioning: Versioning) {
+        self.0.versioning = versioning;
+    }
+    fn get_versioning(&self) -> Versioning {
+        self.0.versioning.clone()
+    }
+    fn set_template_variables(&mut self, variables: TemplateVariables) {
+        self.0.template_variables = variables;
+    }
+    fn get_template_variables(&self) -> TemplateVariables {
+        self.0.template_variables.clone()
+    }
+    fn set_default_header(&mut self, header: Option<Headers>) {
         self.0.default_header = header;
     }
-    fn get_default_header(&self) -> Option<HeaderMap> {
+    fn get_default_header(&self) -> Option<Headers> {
         self.0.default_header.clone()
     }
+    fn set_offline_switch(&mut self, offline: OfflineSwitch) {
+        self.0.offline = offline;
+    }
+    fn get_offline_switch(&self) -> OfflineSwitch {
+        self.0.offline.clone()
+    }
+    fn set_drain_gate(&mut self, gate: DrainGate) {
+        self.0.drain_gate = gate;
+    }
+    fn get_drain_gate(&self) -> DrainGate {
+        self.0.drain_gate.clone()
+    }
+    fn set_usage_quota(&mut self, quota: Option<Arc<UsageQuota>>) {
+        self.0.usage_quota = quota;
+    }
+    fn get_usage_quota(&self) -> Option<Arc<UsageQuota>> {
+        self.0.usage_quota.clone()
+    }
+    fn set_maintenance_policy(&mut self, policy: Option<Arc<MaintenancePolicy>>) {
+        self.0.maintenance_policy = policy;
+    }
+    fn get_maintenance_policy(&self) -> Option<Arc<MaintenancePolicy>> {
+        self.0.maintenance_policy.clone()
+    }
+    fn set_read_only(&mut self, guard: Option<ReadOnlyGuard>) {
+        self.0.read_only = guard;
+    }
+    fn get_read_only(&self) -> Option<ReadOnlyGuard> {
+        self.0.read_only.clone()
+    }
+    fn set_keep_alive(&mut self, worker: Option<KeepAliveWorker>) -> Option<KeepAliveWorker> {
+        std::mem::replace(&mut self.0.keep_alive, worker)
+    }
+    fn get_keep_alive(&self) -> Option<&KeepAliveWorker> {
+        self.0.keep_alive.as_ref()
+    }
+    fn get_status_tracker(&self) -> StatusTracker {
+        self.0.status_tracker.clone()
+    }
+    #[cfg(feature = "queue")]
+    fn set_offline_queue(&mut self, store: Option<Arc<dyn QueueStore>>) {
+        self.0.offline_queue = store;
+    }
+    #[cfg(feature = "queue")]
+    fn get_offline_queue(&self) -> Option<Arc<dyn QueueStore>> {
+        self.0.offline_queue.clone()
+    }
+    #[cfg(feature = "for_serde")]
+    fn set_codec_registry(&mut self, registry: Arc<CodecRegistry>) {
+        self.0.codec_registry = registry;
+    }
+    #[cfg(feature = "for_serde")]
+    fn get_codec_registry(&self) -> Arc<CodecRegistry> {
+        self.0.codec_registry.clone()
+    }
 
     fn get_simple_http(&mut self) -> &mut SimpleHTTP<Client, Req, Res, Method, HeaderMap, B> {
         &mut self.0.simple_http
@@ -304,14 +1801,13 @@ impl
         HeaderMap,
         Body,
     > {
-        return SimpleHTTP::new_with_options(
-            Arc::new(Mutex::new(HyperClient::<HttpConnector, Body> {
-                client: Client::new(),
-                thread_pool: None,
-            })),
+        SimpleHTTP::new_with_timeout(
+            Arc::new(Mutex::new(LazyClient::new(|| {
+                Box::new(HyperClient::new_for_hyper())
+            }))),
             VecDeque::new(),
-            DEFAULT_TIMEOUT_MILLISECOND,
-        );
+            DEFAULT_TIMEOUT,
+        )
     }
 }
 impl Default
@@ -466,6 +1962,31 @@ impl
     }
 }
 
+impl<C> CommonAPI<Client<C, Body>, Request<Body>, Result<Response<Body>>, HeaderMap, Body>
+where
+    C: Connect + Service<Uri> + Clone + Send + Sync + 'static,
+    C::Response: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    C::Future: Send,
+{
+    /**
+    Create a new CommonAPI with a Client of the given connector
+    (e.g. a TLS connector, or the `HttpConnector` of a custom resolver), with the default options.
+    */
+    pub fn new_with_connector(connector: C) -> Self {
+        let simple_http = SimpleHTTP::new_with_timeout(
+            Arc::new(Mutex::new(HyperClient::new_with_options(
+                connector,
+                HyperClientOptions::default(),
+            ))),
+            VecDeque::new(),
+            DEFAULT_TIMEOUT,
+        );
+        CommonAPI::new_with_options(Arc::new(Mutex::new(HyperSimpleAPI(
+            SimpleAPI::new_with_options(simple_http, Url::parse("http://localhost").ok().unwrap()),
+        ))))
+    }
+}
+
 impl Default
     for CommonAPI<
         Client<HttpConnector, Body>,
@@ -523,6 +2044,52 @@ where
         .await
     }
 
+    /**
+    Open a connection to the base URL ahead of time (including the TLS handshake for https connectors),
+    so that the first real request could reuse the pooled connection.
+
+    # Remarks
+
+    * It's done by the keep-alive probe of the client (`BaseClient::keep_alive_probe()`):
+      a bare `HEAD` of the base URL, and any HTTP status is regarded as connected.
+    * It's not an application request: the interceptors (the default headers & the credentials),
+      the audit log, the quotas and the metrics are skipped; only the request line & `Host` are sent.
+    * It's a no-op for the clients without the probes.
+
+    */
+    pub async fn preconnect(&self) -> StdResult<(), Box<dyn StdError>> {
+        let (probe, url, timeout, clock) = {
+            let mut simple_api = self.get_simple_api().lock().unwrap();
+            let url = simple_api.get_base_url();
+            let simple_http = simple_api.get_simple_http();
+            (
+                simple_http.keep_alive_probe(),
+                url,
+                simple_http.get_timeout(),
+                simple_http.clock.clone(),
+            )
+        };
+        let probe = match probe {
+            Some(probe) => probe,
+            None => return Ok(()),
+        };
+        match future::select(
+            probe(KeepAliveMethod::Head, url, timeout),
+            clock.sleep(timeout),
+        )
+        .await
+        {
+            future::Either::Left((result, _)) => match result {
+                Ok(_) => Ok(()),
+                Err(e) => Err(e),
+            },
+            future::Either::Right(_) => Err(Box::new(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "preconnect timed out",
+            ))),
+        }
+    }
+
     /*
     pub async fn do_request_multipart(
         &self,
@@ -564,9 +2131,7 @@ impl<C>
     dyn BaseService<Client<C, Body>, Request<Body>, Result<Response<Body>>, Method, HeaderMap, Body>
 {
     #[cfg(feature = "multipart")]
-    // NOTE: Experimental
-    #[allow(dead_code)]
-    pub(crate) fn make_api_multipart_for_stream<R>(
+    pub fn make_api_multipart_for_stream<R>(
         &self,
         base: Arc<
             dyn BaseService<
@@ -602,9 +2167,30 @@ impl<C>
             relative_url: relative_url.into(),
             request_serializer: Arc::new(DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM.clone()),
             response_deserializer,
+            request_info_hook: None,
         }
     }
 
+    pub fn make_api_upload<R>(
+        &self,
+        base: HyperBaseService<C>,
+        method: Method,
+        relative_url: impl Into<String>,
+        content_type: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIUpload<R, C> {
+        APIUpload {
+            base,
+            method,
+            relative_url: relative_url.into(),
+            content_type: content_type.into(),
+            chunk_size: DEFAULT_UPLOAD_CHUNK_SIZE,
+            response_deserializer,
+        }
+    }
+
+    #[cfg(feature = "multipart")]
     pub async fn do_request_multipart(
         &self,
         method: Method,
@@ -636,7 +2222,66 @@ impl<C>
             },
             body,
         )
-        .await
+        .await
+    }
+}
+
+// APIUpload API with the file-backed request body (streamed by chunks, with Content-Length)
+// R: Response body Type
+pub struct APIUpload<R, C> {
+    pub base: HyperBaseService<C>,
+    pub method: Method,
+    pub relative_url: String,
+    pub content_type: String,
+    pub chunk_size: usize,
+
+    pub response_deserializer: Arc<dyn BodyDeserializer<R>>,
+}
+impl<R, C> APIUpload<R, C> {
+    pub async fn call(
+        &self,
+        path_param: Option<impl Into<PathParam>>,
+        file_path: impl AsRef<Path>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        let file = File::open(file_path).await?;
+        self.call_with_options(None, path_param, None::<QueryParam>, file)
+            .await
+    }
+
+    pub async fn call_with_options(
+        &self,
+        header: Option<HeaderMap>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        file: File,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        let (body, content_length) = body_from_file(file, self.chunk_size).await?;
+        let mut header = header.unwrap_or_default();
+        if !header.contains_key(CONTENT_LENGTH) {
+            header.insert(CONTENT_LENGTH, HeaderValue::from(content_length));
+        }
+
+        let (response_content_type, body) = self
+            .base
+            ._call_common_with_content_type(
+                self.method.clone(),
+                Some(header),
+                self.relative_url.clone(),
+                self.content_type.clone(),
+                path_param.map(|v| v.into()),
+                query_param.map(|v| v.into()),
+                body,
+            )
+            .await?;
+
+        let target = catch_hook_panic_async(
+            "response_deserializer",
+            self.response_deserializer
+                .decode_response(response_content_type, self.base.body_to_stream(*body)),
+        )
+        .await??;
+
+        Ok(target)
     }
 }
 
@@ -680,6 +2325,33 @@ where
             }
         })
     }
+    fn body_size(&self, body: &B) -> Option<u64> {
+        HttpBody::size_hint(body).exact()
+    }
+    // The chunks are yielded as they're received (counted by the scoped `ResponseSizeInfo`)
+    fn body_to_stream(&self, body: B) -> BodyStream<'static> {
+        let response_size_info = scoped_extensions().get::<ResponseSizeInfo>().cloned();
+        Box::pin(stream::unfold(Box::pin(body), move |mut body| {
+            let response_size_info = response_size_info.clone();
+            async move {
+                let data = body.data().await;
+                if let Some(response_size_info) = &response_size_info {
+                    let length = match &data {
+                        Some(Ok(data)) => data.remaining() as u64,
+                        _ => 0,
+                    };
+                    response_size_info.add_decoded_length(length);
+                }
+                match data? {
+                    Ok(mut data) => {
+                        let chunk = data.copy_to_bytes(data.remaining());
+                        Some((Ok(chunk), body))
+                    }
+                    Err(e) => Some((Err(e.into() as Box<dyn StdError>), body)),
+                }
+            }
+        }))
+    }
 
     fn get_simple_api(
         &self,
@@ -698,10 +2370,138 @@ where
         query_param: Option<QueryParam>,
         body: B,
     ) -> Pin<Box<dyn Future<Output = StdResult<Box<B>, Box<dyn StdError>>>>> {
-        let simple_api = self.simple_api.clone();
+        let future = self._call_common_with_content_type(
+            method,
+            header,
+            relative_url,
+            content_type,
+            path_param,
+            query_param,
+            body,
+        );
+        Box::pin(async move { Ok(future.await?.1) })
+    }
+    fn _call_common_with_content_type(
+        &self,
+        method: Method,
+        header: Option<HeaderMap>,
+        relative_url: String,
+        content_type: String,
+        path_param: Option<PathParam>,
+        query_param: Option<QueryParam>,
+        body: B,
+    ) -> ResponseFuture<B> {
+        call_common(
+            self.simple_api.clone(),
+            None,
+            method,
+            header,
+            relative_url,
+            content_type,
+            path_param,
+            query_param,
+            body,
+        )
+    }
+}
 
-        Box::pin(async move {
+// The `SimpleAPI` shared by `CommonAPI` and its `Session`s
+type HyperBaseAPI<C, B> =
+    Arc<Mutex<dyn BaseAPI<Client<C, B>, Request<B>, Result<Response<B>>, Method, HeaderMap, B>>>;
+
+// The calls of `CommonAPI` (and `Session`, with its layer)
+#[allow(clippy::too_many_arguments)]
+fn call_common<C, B>(
+    simple_api: HyperBaseAPI<C, B>,
+    session: Option<SessionLayer>,
+    method: Method,
+    header: Option<HeaderMap>,
+    relative_url: String,
+    content_type: String,
+    path_param: Option<PathParam>,
+    query_param: Option<QueryParam>,
+    body: B,
+) -> ResponseFuture<B>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    let (status_tracker, clock) = {
+        let mut simple_api = simple_api.lock().unwrap();
+        (
+            simple_api.get_status_tracker(),
+            simple_api.get_simple_http().clock.clone(),
+        )
+    };
+    let clock_for_maintenance = clock.clone();
+    let future = Box::pin(async move {
+        // Rejected in the read-only mode (not deferred or queued)
+        if let Some(read_only) = simple_api.lock().unwrap().get_read_only() {
+            read_only.check(method.as_str(), &relative_url)?;
+        }
+        // Deferred/rejected in the maintenance windows of the upstream (without holding the lock)
+        let maintenance_policy = simple_api.lock().unwrap().get_maintenance_policy();
+        let maintenance = match maintenance_policy {
+            Some(policy) => policy
+                .enter(clock_for_maintenance.as_ref())
+                .await
+                .err()
+                .map(|e| (e, policy.action == MaintenanceAction::Queue)),
+            None => None,
+        };
+
+        // The call is in flight until the response (head) is received
+        let (_permit, offline) = {
+            let simple_api = simple_api.lock().unwrap();
+            (
+                simple_api.get_drain_gate().enter()?,
+                simple_api.get_offline_switch().is_offline(),
+            )
+        };
+        // The writes are queued while it's offline or in the maintenance window (if there's the offline queue)
+        if offline || maintenance.as_ref().is_some_and(|(_, queue)| *queue) {
+            #[cfg(feature = "queue")]
+            {
+                let offline_queue = simple_api.lock().unwrap().get_offline_queue();
+                if let (Some(store), false) = (offline_queue, is_safe_method(method.as_str())) {
+                    let body = hyper::body::to_bytes(body).await.map_err(|e| {
+                        let e: Box<dyn StdError + Send + Sync> = e.into();
+                        e as Box<dyn StdError>
+                    })?;
+                    let header = header.as_ref().map(Headers::from);
+                    let item = QueuedRequest::new_with_params(
+                        method,
+                        relative_url,
+                        path_param,
+                        query_param,
+                        header,
+                        content_type,
+                        body,
+                    );
+                    return Err(enqueue_offline(store.as_ref(), item));
+                }
+            }
+        }
+        if let Some((e, _)) = maintenance {
+            return Err(Box::new(e) as Box<dyn StdError>);
+        }
+        if offline {
+            return Err(Box::new(OfflineError::new(format!(
+                "{} {} isn't sent",
+                method, relative_url
+            ))) as Box<dyn StdError>);
+        }
+        // NOTE: Don't hold the lock of the `SimpleAPI` while awaiting (the concurrent calls would block)
+        let (req, simple_http, usage_quota, request_size) = {
             let mut simple_api = simple_api.lock().unwrap();
+            let usage_quota = simple_api.get_usage_quota();
+            if let Some(usage_quota) = &usage_quota {
+                usage_quota.try_acquire()?;
+            }
+            let request_size = HttpBody::size_hint(&body).exact().unwrap_or(0);
+
             let mut req = simple_api.make_request(
                 method,
                 relative_url,
@@ -711,6 +2511,9 @@ where
                 body,
             )?;
 
+            if let Some(session) = &session {
+                session.apply(&mut req, &simple_api.get_base_url())?;
+            }
             if let Some(header) = header {
                 let header_existing = req.headers_mut();
                 for (k, v) in header.iter() {
@@ -718,14 +2521,286 @@ where
                 }
             }
 
-            let body = simple_api
-                .get_simple_http()
-                .request(req)
-                .await??
-                .into_body();
+            let simple_http = simple_api.get_simple_http().clone();
+            (req, simple_http, usage_quota, request_size)
+        };
 
-            Ok(Box::new(body))
-        })
+        let url = req.uri().to_string();
+        let options = req.get_request_options();
+        let (response_content_type, body) = match simple_http.request(req).await? {
+            Ok(res) => {
+                if let Some(session) = &session {
+                    session.on_response(&url, &res);
+                }
+                ResponseStatus::record(&options, res.status().as_u16());
+                ResponseLanguage::record(
+                    &options,
+                    res.headers()
+                        .get(CONTENT_LANGUAGE)
+                        .and_then(|v| v.to_str().ok()),
+                );
+                ResponseTlsInfo::record(&options, res.extensions().get::<TlsInfo>());
+                ResponseTiming::record(&options, res.extensions().get::<RequestTiming>());
+                ResponseSizeInfo::record(
+                    &options,
+                    ResponseSize {
+                        content_length: res
+                            .headers()
+                            .get(CONTENT_LENGTH)
+                            .and_then(|v| v.to_str().ok()?.parse().ok()),
+                        content_encoding: res
+                            .headers()
+                            .get(CONTENT_ENCODING)
+                            .and_then(|v| v.to_str().ok())
+                            .map(|v| v.to_string()),
+                        decoded_length: None,
+                        decompressed: Some(false),
+                    },
+                );
+                if let Some(usage_quota) = &usage_quota {
+                    let response_size = res
+                        .headers()
+                        .get(CONTENT_LENGTH)
+                        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+                    usage_quota.record_bytes(request_size + response_size.unwrap_or(0));
+                }
+                let response_content_type = res
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_string());
+                (response_content_type, res.into_body())
+            }
+            Err(e) => {
+                // The streaming body has been aborted by its producer
+                if let Some(producer_error) = BodyProducerError::find_in(&e) {
+                    return Err(Box::new(producer_error.clone()) as Box<dyn StdError>);
+                }
+                return Err(Box::new(e));
+            }
+        };
+
+        Ok((response_content_type, Box::new(body)))
+    });
+    // The last error is reported by `status()`
+    Box::pin(async move {
+        let result = future.await;
+        if let Err(e) = &result {
+            status_tracker.record_error(e.as_ref(), clock.system_now());
+        }
+        result
+    })
+}
+
+struct SessionState {
+    base_url: Option<Url>,
+    default_header: Option<Headers>,
+    auth_provider: Option<Arc<dyn AuthProvider>>,
+}
+
+// The per-session layer of the calls over the shared `CommonAPI`
+#[derive(Clone)]
+struct SessionLayer {
+    state: Arc<RwLock<SessionState>>,
+    cookie_jar: Arc<CookieJar>,
+}
+impl SessionLayer {
+    // Rebase the url, then set the default headers, the credentials and the cookies
+    fn apply<B>(
+        &self,
+        req: &mut Request<B>,
+        shared_base_url: &Url,
+    ) -> StdResult<(), Box<dyn StdError>> {
+        let state = self.state.read().unwrap();
+        let options = req.get_request_options();
+        let mut url = Url::parse(&req.uri().to_string())?;
+        // The absolute urls are called verbatim
+        if let (Some(base_url), None) = (&state.base_url, &options.absolute_url) {
+            url = rebase_url(&url, shared_base_url, base_url);
+            *req.uri_mut() = Uri::from_str(url.as_str())?;
+        }
+
+        let mut headers = state.default_header.clone().unwrap_or_default();
+        if options.skip_auth {
+            headers.remove(AUTHORIZATION.as_str());
+        } else if let Some(auth_provider) = &state.auth_provider {
+            auth_provider.apply(&mut headers)?;
+        }
+        if let Some(cookie) = self.cookie_jar.cookie_header(&url) {
+            headers.insert("Cookie", cookie);
+        }
+        let header_existing = req.headers_mut();
+        for (k, v) in HeaderMap::try_from(&headers)?.iter() {
+            header_existing.insert(k, v.clone());
+        }
+        Ok(())
+    }
+
+    // Store the cookies, and report the response to the `AuthProvider`
+    fn on_response<B>(&self, url: &str, res: &Response<B>) {
+        if let Ok(url) = Url::parse(url) {
+            let set_cookies = res.headers().get_all(SET_COOKIE);
+            self.cookie_jar
+                .store(&url, set_cookies.iter().filter_map(|v| v.to_str().ok()));
+        }
+        if let Some(auth_provider) = &self.state.read().unwrap().auth_provider {
+            auth_provider.on_response(res.status().as_u16(), &Headers::from(res.headers()));
+        }
+    }
+}
+
+/**
+`Session` An identity (cookies, credentials, default headers and base URL) layered over a shared `CommonAPI`,
+so that multiple identities could call the same service concurrently (sharing the connections/interceptors).
+
+# Remarks
+
+* The session base URL (if it's set) replaces the shared one (by rebasing the urls),
+  and the session default headers are added to the shared ones.
+* The credentials (by the `AuthProvider`) and the cookies (by the `CookieJar`) are set after the default headers,
+  and the per-call headers take precedence over all of them.
+* The `Set-Cookie` of the responses are stored into the `CookieJar`.
+* The setters of the `BaseService` (e.g. `as_base_service_shared().set_base_url()`) change the shared `CommonAPI`,
+  the ones of the `Session` change the session only.
+
+*/
+pub struct Session<Client, Req, Res, Header, B> {
+    pub common_api: CommonAPI<Client, Req, Res, Header, B>,
+    layer: SessionLayer,
+}
+impl<Client, Req, Res, Header, B> Clone for Session<Client, Req, Res, Header, B> {
+    fn clone(&self) -> Self {
+        Session {
+            common_api: self.common_api.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+impl<Client, Req, Res, Header, B> Session<Client, Req, Res, Header, B> {
+    pub fn new(common_api: &CommonAPI<Client, Req, Res, Header, B>) -> Self {
+        Self::new_with_cookie_jar(common_api, Arc::new(CookieJar::new()))
+    }
+    pub fn new_with_cookie_jar(
+        common_api: &CommonAPI<Client, Req, Res, Header, B>,
+        cookie_jar: Arc<CookieJar>,
+    ) -> Self {
+        Session {
+            common_api: common_api.clone(),
+            layer: SessionLayer {
+                state: Arc::new(RwLock::new(SessionState {
+                    base_url: None,
+                    default_header: None,
+                    auth_provider: None,
+                })),
+                cookie_jar,
+            },
+        }
+    }
+
+    // Override the base URL of the shared `CommonAPI` (`None` to use the shared one)
+    pub fn set_base_url(&self, url: Option<Url>) {
+        self.layer.state.write().unwrap().base_url = url;
+    }
+    pub fn get_base_url(&self) -> Option<Url> {
+        self.layer.state.read().unwrap().base_url.clone()
+    }
+    // The default headers of the session (added to the shared ones)
+    pub fn set_default_header(&self, header: Option<Headers>) {
+        self.layer.state.write().unwrap().default_header = header;
+    }
+    pub fn get_default_header(&self) -> Option<Headers> {
+        self.layer.state.read().unwrap().default_header.clone()
+    }
+    pub fn set_auth_provider(&self, auth_provider: Option<Arc<dyn AuthProvider>>) {
+        self.layer.state.write().unwrap().auth_provider = auth_provider;
+    }
+    pub fn get_cookie_jar(&self) -> Arc<CookieJar> {
+        self.layer.cookie_jar.clone()
+    }
+}
+
+impl<C, B> Session<Client<C, B>, Request<B>, Result<Response<B>>, HeaderMap, B>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    pub fn as_base_service_shared(&self) -> HyperBaseService<C, B> {
+        Arc::new(self.clone())
+    }
+}
+
+impl<C, B> BaseService<Client<C, B>, Request<B>, Result<Response<B>>, Method, HeaderMap, B>
+    for Session<Client<C, B>, Request<B>, Result<Response<B>>, HeaderMap, B>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+    B: HttpBody + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn body_to_bytes(
+        &self,
+        body: B,
+    ) -> Pin<Box<dyn Future<Output = StdResult<Bytes, Box<dyn StdError + Send + Sync>>>>> {
+        self.common_api.body_to_bytes(body)
+    }
+    fn body_size(&self, body: &B) -> Option<u64> {
+        self.common_api.body_size(body)
+    }
+    fn body_to_stream(&self, body: B) -> BodyStream<'static> {
+        self.common_api.body_to_stream(body)
+    }
+
+    fn get_simple_api(
+        &self,
+    ) -> &Arc<Mutex<dyn BaseAPI<Client<C, B>, Request<B>, Result<Response<B>>, Method, HeaderMap, B>>>
+    {
+        &self.common_api.simple_api
+    }
+
+    fn _call_common(
+        &self,
+        method: Method,
+        header: Option<HeaderMap>,
+        relative_url: String,
+        content_type: String,
+        path_param: Option<PathParam>,
+        query_param: Option<QueryParam>,
+        body: B,
+    ) -> Pin<Box<dyn Future<Output = StdResult<Box<B>, Box<dyn StdError>>>>> {
+        let future = self._call_common_with_content_type(
+            method,
+            header,
+            relative_url,
+            content_type,
+            path_param,
+            query_param,
+            body,
+        );
+        Box::pin(async move { Ok(future.await?.1) })
+    }
+    fn _call_common_with_content_type(
+        &self,
+        method: Method,
+        header: Option<HeaderMap>,
+        relative_url: String,
+        content_type: String,
+        path_param: Option<PathParam>,
+        query_param: Option<QueryParam>,
+        body: B,
+    ) -> ResponseFuture<B> {
+        call_common(
+            self.common_api.simple_api.clone(),
+            Some(self.layer.clone()),
+            method,
+            header,
+            relative_url,
+            content_type,
+            path_param,
+            query_param,
+            body,
+        )
     }
 }
 
@@ -745,7 +2820,28 @@ where
         query_param: Option<impl Into<QueryParam>>,
         body: B,
     ) -> StdResult<Request<B>, Box<dyn StdError>> {
-        let mut relative_url = relative_url.into();
+        let extensions = scoped_extensions();
+        // The versioning of the API (or the one of the service)
+        let versioning = extensions
+            .get::<Versioning>()
+            .cloned()
+            .unwrap_or_else(|| self.get_versioning());
+        let accept = extensions
+            .get::<AcceptMediaType>()
+            .map(|v| versioning.apply_to_media_type(&v.0));
+        let absolute_url = extensions.get::<AbsoluteUrl>().cloned();
+        let connect_to = extensions.get::<ConnectTo>().copied();
+        let sni_hostname = extensions.get::<SniHostname>().map(|v| v.0.clone());
+
+        // The variables of the scope take precedence over the ones of the service
+        let template_variables = match extensions.get::<TemplateVariables>() {
+            Some(variables) => self.get_template_variables().merge(variables),
+            None => self.get_template_variables(),
+        };
+
+        let relative_url = versioning.apply_to_url(&relative_url.into());
+        let url_template = relative_url.clone();
+        let mut relative_url = template_variables.apply_to_url(&relative_url)?;
         if let Some(path_param) = path_param {
             for (k, v) in path_param.into().into_iter() {
                 relative_url = relative_url.replace(&("{".to_string() + &k + "}"), &v);
@@ -753,29 +2849,60 @@ where
         }
 
         let mut req = Request::new(body);
-        // Url
-        match self.get_base_url().join(&relative_url) {
+        req.set_request_options(RequestOptions {
+            url_template: Some(url_template),
+            extensions,
+            absolute_url: absolute_url.as_ref().map(|v| v.url.clone()),
+            skip_auth: absolute_url.as_ref().is_some_and(|v| v.skip_auth),
+            connect_to: connect_to.map(|v| v.0),
+            sni_hostname,
+            ..Default::default()
+        });
+        // Url (the absolute urls are called verbatim)
+        let joined_url = match &absolute_url {
+            Some(absolute_url) => Ok(absolute_url.url.clone()),
+            None => join_url(
+                &self.get_base_url(),
+                &relative_url,
+                self.get_url_join_strategy(),
+            ),
+        };
+        match joined_url {
             Ok(mut url) => {
                 if let Some(query_param) = query_param {
                     for (k, v) in query_param.into().into_iter() {
-                        url.set_query(Some(&(k + "=" + &v)));
+                        append_query_param(&mut url, &k, &v);
                     }
                 }
+                if absolute_url.is_none() {
+                    self.get_url_normalization().normalize(&mut url);
+                }
                 *req.uri_mut() = Uri::from_str(url.as_str())?;
             }
-            Err(e) => return Err(Box::new(e)),
+            Err(e) => return Err(e),
         };
         // Method
         *req.method_mut() = method;
         // Header
         if let Some(header) = self.get_default_header() {
-            *req.headers_mut() = header.clone();
+            *req.headers_mut() = HeaderMap::try_from(&header)?;
         }
-        let content_type = content_type.into();
+        if req.get_request_options().skip_auth {
+            req.headers_mut().remove(AUTHORIZATION);
+        }
+        let content_type = versioning.apply_to_media_type(&content_type.into());
         if !content_type.is_empty() {
             req.headers_mut()
                 .insert(CONTENT_TYPE, HeaderValue::from_str(&content_type)?);
         }
+        if let Some(accept) = accept {
+            req.headers_mut()
+                .insert(ACCEPT, HeaderValue::from_str(&accept)?);
+        }
+        if let Some((name, value)) = versioning.get_header() {
+            req.headers_mut()
+                .insert(HeaderName::from_str(name)?, HeaderValue::from_str(value)?);
+        }
 
         Ok(req)
     }
@@ -809,21 +2936,115 @@ where
     }
 }
 
-pub fn add_header_authentication(
-    mut header_map: HeaderMap,
-    token: impl Into<String>,
-) -> StdResult<HeaderMap, Box<dyn StdError>> {
-    let str = token.into();
-    header_map.insert("Authorization", HeaderValue::from_str(&str)?);
+impl TryFrom<&Headers> for HeaderMap {
+    type Error = Box<dyn StdError>;
+
+    // The illegal names/values are reported by `InvalidHeaderError`
+    fn try_from(headers: &Headers) -> StdResult<Self, Self::Error> {
+        let mut header_map = HeaderMap::new();
+        for (k, v) in headers.iter() {
+            validate_header_name(k)?;
+            let mut value = HeaderValue::from_bytes(headers.value_bytes(k, v)).map_err(|_| {
+                InvalidHeaderError::new(format!("invalid character in the header value of {:?}", k))
+            })?;
+            value.set_sensitive(headers.is_sensitive(k));
+            header_map.append(HeaderName::from_bytes(k.as_bytes())?, value);
+        }
+        Ok(header_map)
+    }
+}
+impl From<&HeaderMap> for Headers {
+    fn from(header_map: &HeaderMap) -> Self {
+        // The values which aren't UTF-8 (obs-text) are kept by their raw bytes
+        let mut headers = Headers::new();
+        for (k, v) in header_map.iter() {
+            headers.append_bytes(k.as_str(), v.as_bytes());
+            if v.is_sensitive() {
+                headers.set_sensitive(k.as_str(), true);
+            }
+        }
+        headers
+    }
+}
+
+impl<B> HasRequestOptions for Request<B> {
+    fn get_request_options(&self) -> RequestOptions {
+        self.extensions()
+            .get::<RequestOptions>()
+            .cloned()
+            .unwrap_or_default()
+    }
+    fn set_request_options(&mut self, options: RequestOptions) {
+        self.extensions_mut().insert(options);
+    }
+}
+
+impl<B> RequestHeaders for Request<B> {
+    fn get_headers(&self) -> Headers {
+        Headers::from(self.headers())
+    }
+    fn set_headers(&mut self, headers: Headers) -> StdResult<(), Box<dyn StdError>> {
+        *self.headers_mut() = HeaderMap::try_from(&headers)?;
+        Ok(())
+    }
+}
+// The hyper bodies are streams, so they're not summarized
+impl<B> RequestSummary for Request<B> {
+    fn get_method(&self) -> String {
+        self.method().to_string()
+    }
+    fn get_url(&self) -> String {
+        self.uri().to_string()
+    }
+}
+
+/**
+Make a `Body` streaming the given file by chunks.
+
+# Arguments
+
+* `file` - The given `tokio::fs::File`.
+* `chunk_size` - The max size of each chunk.
 
-    Ok(header_map)
+# Returns
+
+The `Body` and the file length (for `Content-Length`).
+
+*/
+pub async fn body_from_file(file: File, chunk_size: usize) -> io::Result<(Body, u64)> {
+    let content_length = file.metadata().await?.len();
+    let chunk_size = if chunk_size > 0 {
+        chunk_size
+    } else {
+        DEFAULT_UPLOAD_CHUNK_SIZE
+    };
+
+    let body = stream::unfold(Some(file), move |file| async move {
+        let mut file = file?;
+        let mut buf = bytes::BytesMut::with_capacity(chunk_size);
+        match file.read_buf(&mut buf).await {
+            Ok(0) => None,
+            Ok(_) => Some((Ok(buf.freeze()), Some(file))),
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+
+    Ok((Body::wrap_stream(body), content_length))
 }
+/**
+Make a `Body` streaming the file of the given path by chunks.
+
+# Arguments
+
+* `path` - The given file path.
 
-pub fn add_header_authentication_bearer(
-    header_map: HeaderMap,
-    token: impl Into<String>,
-) -> StdResult<HeaderMap, Box<dyn StdError>> {
-    return add_header_authentication(header_map, "Bearer ".to_string() + &token.into());
+# Returns
+
+The `Body` and the file length (for `Content-Length`).
+
+*/
+pub async fn body_from_path(path: impl AsRef<Path>) -> io::Result<(Body, u64)> {
+    body_from_file(File::open(path).await?, DEFAULT_UPLOAD_CHUNK_SIZE).await
 }
 
 #[cfg(feature = "multipart")]
@@ -837,17 +3058,34 @@ pub async fn body_to_multipart(
     headers: &HeaderMap,
     body: Body,
 ) -> StdResult<Multipart<'_>, Box<dyn StdError>> {
-    let boundary: String;
-    match headers.get(CONTENT_TYPE) {
-        Some(content_type) => boundary = multer::parse_boundary(content_type.to_str()?)?,
+    body_to_multipart_with_constraints(headers, body, Constraints::default()).await
+}
+/**
+Parse the multipart `body` with the `constraints` (e.g. the upload limits of the servers).
+
+# Remarks
+
+* The violations (e.g. `multer::Error::FieldSizeExceeded`/`StreamSizeExceeded`/`UnknownField`)
+  are returned by reading the fields (e.g. `next_field()`).
+* The file fields are limited by `SizeLimit::per_field()`/`for_field()` as well.
+
+*/
+#[cfg(feature = "multipart")]
+pub async fn body_to_multipart_with_constraints(
+    headers: &HeaderMap,
+    body: Body,
+    constraints: Constraints,
+) -> StdResult<Multipart<'_>, Box<dyn StdError>> {
+    let boundary = match headers.get(CONTENT_TYPE) {
+        Some(content_type) => parse_multipart_boundary(content_type.to_str()?)?,
         None => {
             return Err(Box::new(FormDataParseError::new(
                 "{}: None".to_string() + CONTENT_TYPE.as_str(),
             )));
         }
-    }
+    };
 
-    Ok(Multipart::new(body, boundary))
+    Ok(Multipart::with_constraints(body, boundary, constraints))
 }
 
 impl<C, B> SimpleHTTP<Client<C, B>, Request<B>, Result<Response<B>>, Method, HeaderMap, B>
@@ -862,16 +3100,54 @@ where
         mut request: Request<B>,
     ) -> SimpleHTTPResponse<Result<Response<B>>> {
         for interceptor in &mut self.interceptors.iter() {
-            interceptor.intercept(&mut request)?;
+            catch_hook_panic("interceptor", || interceptor.intercept(&mut request))??;
         }
 
-        // Implement timeout
-        match tokio::time::timeout(
-            self.get_timeout_duration(),
-            { self.client.lock().unwrap() }.request(request),
-        )
-        .await
-        {
+        let audit_call = self.audit_log.as_ref().and_then(|audit_log| {
+            audit_log.begin(
+                request.method().as_str(),
+                request.uri().path(),
+                &request.get_request_options(),
+            )
+        });
+
+        let url = request.uri().to_string();
+        let options = request.get_request_options();
+        let sent_at = self.clock.system_now();
+        // NOTE: Don't hold the lock of the client while awaiting (the concurrent calls would block)
+        let future = self.client.lock().unwrap().request(request);
+
+        // Implement timeout (by the clock)
+        let timeout = self.clock.sleep(self.get_timeout());
+        let result = match future::select(future, timeout).await {
+            future::Either::Left((result, _)) => Ok(result),
+            future::Either::Right(_) => {
+                Err(io::Error::new(io::ErrorKind::TimedOut, "request timed out"))
+            }
+        };
+        if let Ok(Ok(res)) = &result {
+            let headers = res.headers();
+            let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+            self.observe_date(&url, header(DATE.as_str()), sent_at);
+            let warnings: Vec<&str> = headers
+                .get_all(WARNING)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .collect();
+            self.observe_deprecation(
+                &options,
+                DeprecationNotice::parse(&url, header("deprecation"), header("sunset"), &warnings),
+            );
+        }
+        if let (Some(audit_log), Some(audit_call)) = (&self.audit_log, audit_call) {
+            let (status, error) = match &result {
+                Ok(Ok(res)) => (Some(res.status().as_u16()), None),
+                Ok(Err(e)) => (None, Some(e.to_string())),
+                Err(e) => (None, Some(e.to_string())),
+            };
+            audit_log.finish(audit_call, status, error);
+        }
+        match result {
             Ok(result) => Ok(result),
             Err(e) => Err(Box::new(e)),
         }
@@ -5,27 +5,60 @@ In this module there're implementations & tests of `SimpleHTTP`.
 use std::collections::VecDeque;
 use std::error::Error as StdError;
 use std::future::Future;
-use std::io::{self, Read, Write};
+use std::io::{self, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
 use std::pin::Pin;
 use std::result::Result as StdResult;
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Arc, Mutex,
-};
+#[cfg(feature = "dev_tls")]
+use std::sync::OnceLock;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "multipart")]
 use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 // use futures::TryStreamExt;
-use bytes::{Buf, Bytes};
+use bytes::Bytes;
 use futures::executor::ThreadPool;
+use futures::future;
+#[cfg(feature = "multipart")]
 use futures::prelude::*;
+#[cfg(feature = "multipart")]
 use futures::stream;
-use futures::task::SpawnExt;
-use ureq::{Agent, Header, Request, Response};
+use futures::task::{SpawnError, SpawnExt};
+use http::method::Method;
+#[cfg(feature = "dev_tls")]
+use rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore};
+use ureq::{Agent, AgentBuilder, Header, Request, Response};
+#[cfg(feature = "dev_tls")]
+use ureq::{ReadWrite, TlsConnector};
 use url::Url;
 
-use super::common::{PathParam, QueryParam};
-use super::simple_api::{BaseAPI, BaseService, BodySerializer, SimpleAPI};
-use super::simple_http::{BaseClient, SimpleHTTP, SimpleHTTPResponse, DEFAULT_TIMEOUT_MILLISECOND};
+use super::audit::AuditCall;
+#[cfg(feature = "for_serde")]
+use super::codec::CodecRegistry;
+#[cfg(feature = "queue")]
+use super::common::is_safe_method;
+#[cfg(feature = "multipart")]
+use super::common::DEFAULT_STREAMING_BODY_CAPACITY;
+pub use super::common::{add_header_authentication, add_header_authentication_bearer};
+use super::common::{
+    append_query_param, catch_hook_panic, catch_hook_panic_async, join_url, scoped_extensions,
+    with_extensions, AbsoluteUrl, AcceptMediaType, BodyFactory, Clock, ConnectTo,
+    DefaultRedirectPolicy, DeprecationNotice, DrainGate, Extensions, HasRequestOptions, Headers,
+    KeepAliveProbe, KeepAliveWorker, MaintenanceAction, MaintenancePolicy, OfflineError,
+    OfflineSwitch, PathParam, QueryParam, ReadOnlyGuard, Redirect, RedirectAction, RedirectPolicy,
+    RequestHeaders, RequestOptions, RequestSummary, ResponseLanguage, ResponseSize,
+    ResponseSizeInfo, ResponseStatus, SniHostname, StatusTracker, StreamingBodyWriter,
+    TemplateVariables, UrlJoinStrategy, UrlNormalization, UsageQuota, Versioning, MAX_REDIRECTS,
+};
+#[cfg(feature = "queue")]
+use super::queue::{enqueue_offline, QueueStore, QueuedRequest};
+use super::simple_api::{
+    AsyncBodySerializer, BaseAPI, BaseService, BodyDeserializer, ResponseFuture, SimpleAPI,
+};
+#[cfg(feature = "multipart")]
+use super::simple_api::{BodySerializer, SerializeError};
+use super::simple_http::{BaseClient, LazyClient, SimpleHTTP, SimpleHTTPResponse, DEFAULT_TIMEOUT};
 use fp_rust::common::shared_thread_pool;
 
 #[cfg(feature = "for_serde")]
@@ -35,7 +68,8 @@ pub use super::simple_api::DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES;
 pub use super::simple_api::{DEFAULT_MULTIPART_SERIALIZER, DEFAULT_MULTIPART_SERIALIZER_FOR_BYTES};
 #[cfg(feature = "multipart")]
 pub use super::simple_http::{
-    data_and_boundary_from_multipart, get_content_type_from_multipart_boundary, FormDataParseError,
+    data_and_boundary_from_multipart, get_content_type_from_multipart_boundary,
+    parse_multipart_boundary, Constraints, FormDataParseError, SizeLimit,
 };
 #[cfg(feature = "multipart")]
 use formdata::FormData;
@@ -44,172 +78,426 @@ use multer;
 #[cfg(feature = "multipart")]
 use multer::Multipart;
 
-pub const CONTENT_TYPE: &'static str = "content-type";
+pub const CONTENT_TYPE: &str = "content-type";
+pub const ACCEPT: &str = "accept";
+pub const AUTHORIZATION: &str = "authorization";
 
-#[derive(Clone)]
-pub struct WriteForBody {
-    // pub Box<Sender>
-    pub cached: Arc<Mutex<VecDeque<Bytes>>>,
-    pub alive: Arc<Mutex<AtomicBool>>,
+#[deprecated(note = "Use `common::StreamingBodyWriter` instead")]
+pub type WriteForBody = StreamingBodyWriter;
+
+// The shared `BaseService` of the ureq binding (e.g. by `CommonAPI::as_base_service_shared()`)
+pub type UreqBaseService = Arc<
+    dyn BaseService<
+        Agent,
+        UreqRequest,
+        Result<Response, Box<dyn StdError>>,
+        Method,
+        Vec<Header>,
+        Bytes,
+    >,
+>;
+
+#[cfg(feature = "multipart")]
+#[derive(Debug, Clone)]
+/**
+MultipartSerializerForStream Serialize the multipart body as a stream (for put/post/patch etc)
+
+# Remarks
+
+* The files are checked before the request is sent (missing files fail with `SerializeError`).
+* The body is written on the `thread_pool` (or the shared thread pool if it's `None`),
+  while it's sent.
+*/
+pub struct MultipartSerializerForStream {
+    // NOTE: It can't be Copy because of this one:
+    thread_pool: Option<Arc<ThreadPool>>,
 }
+#[cfg(feature = "multipart")]
+impl MultipartSerializerForStream {
+    pub fn new(thread_pool: Option<Arc<ThreadPool>>) -> Self {
+        MultipartSerializerForStream { thread_pool }
+    }
 
-impl WriteForBody {
-    pub fn close(&self) {
-        self.alive.lock().unwrap().store(false, Ordering::SeqCst);
+    // The `BodyFactory` serializing a fresh stream of the `form` per attempt (e.g. sent again by `RedirectClient`)
+    pub fn body_factory(
+        &self,
+        form: impl Fn() -> FormData + Send + Sync + 'static,
+    ) -> BodyFactory<Box<dyn Read + Send + Sync>> {
+        let serializer = self.clone();
+        BodyFactory::new(move || {
+            let serializer = serializer.clone();
+            let form = form();
+            async move {
+                serializer
+                    .serialize(form)
+                    .await
+                    .map_err(|e| Box::new(e) as Box<dyn StdError>)
+            }
+        })
     }
 }
-
-impl Read for WriteForBody {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        {
-            if !self.alive.lock().unwrap().load(Ordering::SeqCst) {
-                return Ok(0);
+#[cfg(feature = "multipart")]
+impl AsyncBodySerializer<FormData, (String, Box<dyn Read + Send + Sync>)>
+    for MultipartSerializerForStream
+{
+    fn serialize<'a>(
+        &'a self,
+        origin: FormData,
+    ) -> Pin<
+        Box<
+            dyn Future<Output = StdResult<(String, Box<dyn Read + Send + Sync>), SerializeError>>
+                + 'a,
+        >,
+    >
+    where
+        FormData: 'a,
+        (String, Box<dyn Read + Send + Sync>): 'a,
+    {
+        Box::pin(async move {
+            for (name, file) in origin.files.iter() {
+                if let Err(e) = std::fs::metadata(&file.path) {
+                    return Err(SerializeError::new_with_source(
+                        format!("the file of {} ({:?}) is unreadable", name, file.path),
+                        e,
+                    ));
+                }
             }
-        }
 
-        {
-            let mut cached = self.cached.lock().unwrap();
-            if cached.is_empty() {
-                return Ok(0);
-            }
+            let boundary = formdata::generate_boundary();
+            let content_type = get_content_type_from_multipart_boundary(boundary.clone())
+                .map_err(|e| SerializeError::new_with_source(e.to_string(), e))?;
 
-            let item = cached.pop_front();
-            if let Some(item) = item {
-                // let len = item.len();
-                match item.reader().read(buf) {
-                    Ok(len) => return Ok(len),
-                    Err(e) => return Err(e),
+            let (data, body) = StreamingBodyWriter::channel(DEFAULT_STREAMING_BODY_CAPACITY);
+            let write = move || {
+                let mut data = data;
+                match formdata::write_formdata(&mut data, &boundary, &origin) {
+                    Ok(_) => data.close(),
+                    Err(e) => data.abort_with_source("write_formdata", e),
+                };
+            };
+            match &self.thread_pool {
+                Some(thread_pool) => thread_pool.spawn_ok(async move { write() }),
+                // A dedicated thread: the writer blocks until the body is read,
+                // and the shared pool could be busy with the call waiting for it
+                None => {
+                    thread::Builder::new()
+                        .name("multipart-writer".to_string())
+                        .spawn(write)
+                        .map_err(|e| {
+                            SerializeError::new_with_source("the writer isn't spawned", e)
+                        })?;
                 }
-            }
+            };
 
-            return Ok(0);
-        }
+            Ok((content_type, Box::new(body) as Box<dyn Read + Send + Sync>))
+        })
     }
 }
-impl Write for WriteForBody {
-    fn write(&mut self, d: &[u8]) -> io::Result<usize> {
-        let len = d.len();
-        println!("WriteForBody write len: {:?}", len);
-        if len <= 0 {
-            return Ok(len);
-        }
-        let d = Bytes::from(d.to_vec());
-        println!("WriteForBody write content: {:?}", d.clone());
+#[cfg(feature = "multipart")]
+pub(crate) const DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM: MultipartSerializerForStream =
+    MultipartSerializerForStream { thread_pool: None };
 
-        {
-            let mut cached = self.cached.lock().unwrap();
-            cached.push_back(d);
-            cached.reserve_exact(10);
+/*
+`UreqRequest` The request of the ureq binding,
+it's built into `ureq::Request` right before sending,
+so interceptors could inspect/modify the method/url/headers/body uniformly.
+*/
+#[derive(Debug, Clone)]
+pub struct UreqRequest {
+    pub method: Method,
+    pub url: Url,
+    pub headers: Headers,
+    pub body: Option<Bytes>,
+    // The streaming body sent instead of the `body` (chunked, without `Content-Length`)
+    pub streaming_body: Option<StreamingBody>,
+    pub timeout: Option<Duration>,
+    pub options: RequestOptions,
+}
+
+impl UreqRequest {
+    pub fn new(method: Method, url: Url) -> Self {
+        UreqRequest {
+            method,
+            url,
+            headers: Headers::new(),
+            body: None,
+            streaming_body: None,
+            timeout: None,
+            options: RequestOptions::default(),
         }
+    }
 
-        /*
-        match self.0.try_send_data(d) {
-            Ok(_) => {
-                println!("WriteForBody write ok");
-            }
-            Err(_) => {
-                println!("WriteForBody write error");
+    // Build the `ureq::Request` by the given `Agent`
+    pub fn to_request(&self, agent: &Agent) -> Request {
+        let mut request = agent.request_url(self.method.as_str(), &self.url);
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        // `set()` keeps the last value of the name only, so the values of the same name are combined
+        // (RFC 7230 3.2.2, by `; ` for `Cookie`)
+        let mut names: Vec<&str> = Vec::new();
+        for (k, _) in self.headers.iter() {
+            if !names.iter().any(|v| v.eq_ignore_ascii_case(k)) {
+                names.push(k);
             }
         }
-        */
-        Ok(len)
+        for name in names {
+            let separator = if name.eq_ignore_ascii_case("cookie") {
+                "; "
+            } else {
+                ", "
+            };
+            request = request.set(name, &self.headers.get_all(name).join(separator));
+        }
+        request
     }
+}
 
-    fn flush(&mut self) -> io::Result<()> {
-        println!("flush ok");
-        Ok(())
+/**
+`StreamingBody` The streaming request body of unknown length (e.g. the `Read` of `MultipartSerializerForStream`),
+sent by `ureq::Request::send()` with the chunked transfer encoding.
+
+# Remarks
+
+* It's read only once: the clones of the request share it, so the requests sent again
+  (e.g. by the retries/redirects) fail with `StreamingBodyConsumedError`,
+  unless there's a `BodyFactory<Box<dyn Read + Send + Sync>>` extension producing the fresh ones
+  (e.g. `APIStreamingBody::call_with_body_factory()`).
+* It's used by the APIs/`make_request()` when it's in the scoped extensions (by `with_extensions()`),
+  e.g. `APIStreamingBody`/`do_request_streaming()`.
+* The requests with it aren't queued while it's offline (the readers can't be persisted).
+
+*/
+#[derive(Clone)]
+pub struct StreamingBody(Arc<Mutex<Option<Box<dyn Read + Send + Sync>>>>);
+impl StreamingBody {
+    pub fn new(reader: impl Read + Send + Sync + 'static) -> Self {
+        StreamingBody(Arc::new(Mutex::new(Some(Box::new(reader)))))
+    }
+
+    // Take the reader (`None` if it has been taken)
+    pub fn take(&self) -> Option<Box<dyn Read + Send + Sync>> {
+        self.0.lock().unwrap().take()
+    }
+    pub fn is_consumed(&self) -> bool {
+        self.0.lock().unwrap().is_none()
+    }
+}
+impl std::fmt::Debug for StreamingBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("StreamingBody")
+            .field("consumed", &self.is_consumed())
+            .finish()
     }
 }
 
-#[cfg(feature = "multipart")]
-#[derive(Debug, Clone)]
-/// MultipartSerializerForStream Serialize the multipart body (for put/post/patch etc)
-pub struct MultipartSerializerForStream {
-    // NOTE: It can't be Copy because of this one:
-    thread_pool: Option<Arc<ThreadPool>>,
+/*
+`StreamingBodyConsumedError` The `StreamingBody` has been sent (it can't be sent again)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamingBodyConsumedError {
+    details: String,
 }
-#[cfg(feature = "multipart")]
-impl BodySerializer<FormData, (String, Box<dyn Read + Send + Sync>)>
-    for MultipartSerializerForStream
-where
-// B: HttpBody + Send + 'static,
-// B: From<Body>,
-// B::Data: Send,
-// B::Error: Into<Box<dyn StdError + Send + Sync>>,
-{
-    fn encode(
+impl StdError for StreamingBodyConsumedError {}
+impl StreamingBodyConsumedError {
+    pub fn new(msg: impl Into<String>) -> StreamingBodyConsumedError {
+        StreamingBodyConsumedError {
+            details: msg.into(),
+        }
+    }
+}
+impl std::fmt::Display for StreamingBodyConsumedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "StreamingBodyConsumedError: {}", self.details)
+    }
+}
+
+// The default TLS config of ureq (the webpki roots)
+#[cfg(feature = "dev_tls")]
+fn default_tls_config() -> Arc<ClientConfig> {
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    CONFIG
+        .get_or_init(|| {
+            let mut roots = RootCertStore::empty();
+            roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+            Arc::new(
+                ClientConfig::builder()
+                    .with_safe_defaults()
+                    .with_root_certificates(roots)
+                    .with_no_client_auth(),
+            )
+        })
+        .clone()
+}
+
+// The TLS connector of the `sni_hostname` instead of the host of the url
+// (by the `TlsConnector` of the rustls 0.20 `ClientConfig`, so it's bound to the ureq releases on rustls 0.20, see `Cargo.toml`)
+#[cfg(feature = "dev_tls")]
+struct SniTlsConnector(String);
+#[cfg(feature = "dev_tls")]
+impl TlsConnector for SniTlsConnector {
+    fn connect(
         &self,
-        origin: FormData,
-    ) -> StdResult<(String, Box<dyn Read + Send + Sync>), Box<dyn StdError>> {
-        let boundary = formdata::generate_boundary();
-        let boundary_thread = boundary.clone();
+        _dns_name: &str,
+        io: Box<dyn ReadWrite>,
+    ) -> StdResult<Box<dyn ReadWrite>, ureq::Error> {
+        default_tls_config().connect(&self.0, io)
+    }
+}
 
-        let data = WriteForBody {
-            cached: Arc::new(Mutex::new(VecDeque::new())),
-            alive: Arc::new(Mutex::new(AtomicBool::new(true))),
-        };
-        let mut data_thread = data.clone();
+/**
+The `Agent` of the `connect_to`/`sni_hostname` of the request.
 
-        let _ = thread::spawn(move || {
-            match formdata::write_formdata(&mut data_thread, &boundary_thread, &origin) {
-                Err(e) => println!("Error -> write_formdata {:?}", e),
-                _ => {}
-            };
-        });
-        let content_type = get_content_type_from_multipart_boundary(boundary)?;
+# Remarks
 
-        Ok((content_type, Box::new(data)))
+* The host of the `url` is connected to the `connect_to` address (the `Host` & the SNI are by the `url`).
+* The TLS connections are by the `sni_hostname` with the default TLS config
+  (the custom one of the agent, e.g. `DevTlsOptions`, isn't applied).
+* The `sni_hostname` needs the `dev_tls` feature (the rustls of ureq),
+  the requests with it fail without the feature (rather than being verified by the host of the url).
+
+*/
+fn request_agent(
+    url: &Url,
+    connect_to: Option<SocketAddr>,
+    sni_hostname: Option<String>,
+) -> StdResult<Agent, Box<dyn StdError>> {
+    let mut builder = AgentBuilder::new();
+    if let Some(addr) = connect_to {
+        let netloc = format!(
+            "{}:{}",
+            url.host_str().unwrap_or_default(),
+            url.port_or_known_default().unwrap_or_default()
+        );
+        builder = builder.resolver(move |v: &str| {
+            if v == netloc {
+                Ok(vec![addr])
+            } else {
+                v.to_socket_addrs().map(|v| v.collect())
+            }
+        });
     }
+    if let Some(sni_hostname) = sni_hostname {
+        #[cfg(feature = "dev_tls")]
+        {
+            builder = builder.tls_connector(Arc::new(SniTlsConnector(sni_hostname)));
+        }
+        #[cfg(not(feature = "dev_tls"))]
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("sni_hostname ({}) needs the dev_tls feature", sni_hostname),
+        )));
+    }
+    Ok(builder.build())
 }
-#[cfg(feature = "multipart")]
-#[allow(dead_code)]
-pub(crate) const DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM: MultipartSerializerForStream =
-    MultipartSerializerForStream { thread_pool: None };
 
+#[derive(Clone)]
 pub struct UreqClient {
     pub agent: Agent,
     pub thread_pool: Option<ThreadPool>,
 }
 
-impl
-    BaseClient<
-        Agent,
-        (Request, Option<Bytes>),
-        Result<Response, Box<dyn StdError>>,
-        String,
-        Vec<Header>,
-        Bytes,
-    > for UreqClient
+impl BaseClient<Agent, UreqRequest, Result<Response, Box<dyn StdError>>, Method, Vec<Header>, Bytes>
+    for UreqClient
 {
     fn get_client(&mut self) -> &mut Agent {
         return &mut self.agent;
     }
 
+    // Spawn the shared thread pool ahead of the first request
+    fn warm(&mut self) {
+        if self.thread_pool.is_none() {
+            shared_thread_pool();
+        }
+    }
+    // Send the probes by the clone of the agent (sharing the pool of the agent),
+    // they're blocking calls on the thread of the keep-alive worker (bounded by the `timeout`)
+    fn keep_alive_probe(&mut self) -> Option<KeepAliveProbe> {
+        let agent = self.agent.clone();
+        Some(Arc::new(move |method, url, timeout| {
+            let agent = agent.clone();
+            Box::pin(async move {
+                let res = match agent
+                    .request(method.as_str(), url.as_str())
+                    .timeout(timeout)
+                    .call()
+                {
+                    Ok(res) => res,
+                    // Any status is alive
+                    Err(ureq::Error::Status(_, res)) => res,
+                    Err(e) => return Err(e.into()),
+                };
+                let status = res.status();
+                // Drain the body, for the connection to be back to the pool
+                io::copy(&mut res.into_reader(), &mut io::sink())?;
+                Ok(status)
+            })
+        }))
+    }
+
     fn request(
         &self,
-        req: (Request, Option<Bytes>),
+        req: UreqRequest,
     ) -> Pin<Box<dyn Future<Output = Result<Response, Box<dyn StdError>>>>> {
-        // let req = self.0.get("path");
-        let spawn_future_result = match &self.thread_pool {
-            Some(thread_pool) => thread_pool.spawn_with_handle(async {
-                match req.1 {
-                    Some(body) => req.0.send_bytes(&body),
-                    None => req.0.call(),
+        let request = match (req.options.connect_to, &req.options.sni_hostname) {
+            (None, None) => req.to_request(&self.agent),
+            (connect_to, sni_hostname) => {
+                match request_agent(&req.url, connect_to, sni_hostname.clone()) {
+                    Ok(agent) => req.to_request(&agent),
+                    Err(e) => return Box::pin(future::ready(Err(e))),
                 }
-            }),
-            None => shared_thread_pool()
-                .inner
-                .lock()
-                .unwrap()
-                .spawn_with_handle(async {
-                    match req.1 {
-                        Some(body) => req.0.send_bytes(&body),
-                        None => req.0.call(),
-                    }
-                }),
+            }
         };
+        let body = req.body;
+        let streaming_body = req.streaming_body;
+        let body_factory = req
+            .options
+            .extensions
+            .get::<BodyFactory<Box<dyn Read + Send + Sync>>>()
+            .cloned();
+        let thread_pool = self.thread_pool.clone();
+
+        // Send it when the future is polled (so it could be scheduled, e.g. by `PriorityClient`)
+        Box::pin(async move {
+            let mut request = request;
+            let reader = match (streaming_body.map(|v| v.take()), body_factory) {
+                (Some(None), Some(body_factory)) => {
+                    let (content_type, reader) = body_factory.produce().await?;
+                    request = request.set("Content-Type", &content_type);
+                    Some(reader)
+                }
+                (Some(None), None) => {
+                    return Err(Box::new(StreamingBodyConsumedError::new(format!(
+                        "the streaming body of {} {} has been sent",
+                        request.method(),
+                        request.url()
+                    ))) as Box<dyn StdError>)
+                }
+                (Some(reader), _) => reader,
+                (None, _) => None,
+            };
+            let send = async move {
+                match (reader, body) {
+                    (Some(reader), _) => request.send(reader),
+                    (None, Some(body)) => request.send_bytes(&body),
+                    (None, None) => request.call(),
+                }
+            };
+            let spawn_future_result = match thread_pool {
+                Some(thread_pool) => thread_pool.spawn_with_handle(send),
+                None => shared_thread_pool()
+                    .inner
+                    .lock()
+                    .unwrap()
+                    .spawn_with_handle(send),
+            };
 
-        Box::pin(async {
             match spawn_future_result {
                 Ok(future) => match future.await {
                     Ok(v) => Ok(v),
@@ -221,11 +509,109 @@ impl
     }
 }
 
+/**
+`RedirectClient` implements the redirect following (by the `RedirectPolicy`) as a `BaseClient`.
+
+# Remarks
+
+* The `Agent` of the `client` should not follow the redirects itself (e.g. `new_for_ureq()` sets `redirects(0)`).
+* The `policy` decides whether each redirect is followed, and adjusts the headers of the redirected request
+  (`DefaultRedirectPolicy` strips the credentials of the cross-origin redirects).
+* The bodies are dropped when the redirects change the methods to `GET`.
+* The redirects not followed (e.g. over `max_redirects`, or over `MAX_REDIRECTS` whatever the `policy` decides)
+  are returned as they are.
+
+*/
+pub struct RedirectClient {
+    pub client: UreqClient,
+    pub policy: Arc<dyn RedirectPolicy>,
+}
+impl RedirectClient {
+    pub fn new_with_options(client: UreqClient, policy: Arc<dyn RedirectPolicy>) -> Self {
+        RedirectClient { client, policy }
+    }
+    pub fn new_for_ureq() -> Self {
+        Self::new_with_options(
+            UreqClient {
+                agent: AgentBuilder::new().redirects(0).build(),
+                thread_pool: None,
+            },
+            Arc::new(DefaultRedirectPolicy::default()),
+        )
+    }
+}
+
+impl BaseClient<Agent, UreqRequest, Result<Response, Box<dyn StdError>>, Method, Vec<Header>, Bytes>
+    for RedirectClient
+{
+    fn get_client(&mut self) -> &mut Agent {
+        self.client.get_client()
+    }
+    fn warm(&mut self) {
+        self.client.warm()
+    }
+    fn keep_alive_probe(&mut self) -> Option<KeepAliveProbe> {
+        self.client.keep_alive_probe()
+    }
+
+    fn request(
+        &self,
+        req: UreqRequest,
+    ) -> Pin<Box<dyn Future<Output = Result<Response, Box<dyn StdError>>>>> {
+        let client = self.client.clone();
+        let policy = self.policy.clone();
+
+        Box::pin(async move {
+            let mut req = req;
+            let mut count = 0;
+            loop {
+                let result = client.request(req.clone()).await;
+                let res = match &result {
+                    Ok(res) => res,
+                    Err(_) => return result,
+                };
+                count += 1;
+                let redirect = match Redirect::new(
+                    res.status(),
+                    req.method.as_str(),
+                    &req.url,
+                    res.header("location"),
+                    count,
+                ) {
+                    Some(redirect) => redirect,
+                    None => return result,
+                };
+                if redirect.count > MAX_REDIRECTS {
+                    return result;
+                }
+
+                let mut headers = req.headers.clone();
+                if policy.redirect(&redirect, &mut headers) == RedirectAction::Stop {
+                    return result;
+                }
+                if !redirect.keeps_body() {
+                    req.body = None;
+                    req.streaming_body = None;
+                    headers.remove("Content-Type");
+                    headers.remove("Content-Length");
+                }
+                // The illegal methods (e.g. set by the policy) aren't followed
+                req.method = match Method::from_bytes(redirect.method.as_bytes()) {
+                    Ok(method) => method,
+                    Err(_) => return result,
+                };
+                req.url = redirect.to;
+                req.headers = headers;
+            }
+        })
+    }
+}
+
 pub struct UreqSimpleAPI<Client, Req, Res, Method, Header, Bytes>(
     SimpleAPI<Client, Req, Res, Method, Header, Bytes>,
 );
-impl<Client, Req, Res, Bytes> BaseAPI<Client, Req, Res, String, Vec<Header>, Bytes>
-    for UreqSimpleAPI<Client, Req, Res, String, Vec<Header>, Bytes>
+impl<Client, Req, Res, Bytes> BaseAPI<Client, Req, Res, Method, Vec<Header>, Bytes>
+    for UreqSimpleAPI<Client, Req, Res, Method, Vec<Header>, Bytes>
 {
     fn set_base_url(&mut self, url: Url) {
         self.0.base_url = url;
@@ -233,63 +619,137 @@ impl<Client, Req, Res, Bytes> BaseAPI<Client, Req, Res, String, Vec<Header>, Byt
     fn get_base_url(&self) -> Url {
         self.0.base_url.clone()
     }
-    fn set_default_header(&mut self, header: Option<Vec<Header>>) {
+    fn set_url_join_strategy(&mut self, strategy: UrlJoinStrategy) {
+        self.0.url_join_strategy = strategy;
+    }
+    fn get_url_join_strategy(&self) -> UrlJoinStrategy {
+        self.0.url_join_strategy
+    }
+    fn set_url_normalization(&mut self, normalization: UrlNormalization) {
+        self.0.url_normalization = normalization;
+    }
+    fn get_url_normalization(&self) -> UrlNormalization {
+        self.0.url_normalization
+    }
+    fn set_versioning(&mut self, versioning: Versioning) {
+        self.0.versioning = versioning;
+    }
+    fn get_versioning(&self) -> Versioning {
+        self.0.versioning.clone()
+    }
+    fn set_template_variables(&mut self, variables: TemplateVariables) {
+        self.0.template_variables = variables;
+    }
+    fn get_template_variables(&self) -> TemplateVariables {
+        self.0.template_variables.clone()
+    }
+    fn set_default_header(&mut self, header: Option<Headers>) {
         self.0.default_header = header;
     }
-    fn get_default_header(&self) -> Option<Vec<Header>> {
+    fn get_default_header(&self) -> Option<Headers> {
         self.0.default_header.clone()
     }
+    fn set_offline_switch(&mut self, offline: OfflineSwitch) {
+        self.0.offline = offline;
+    }
+    fn get_offline_switch(&self) -> OfflineSwitch {
+        self.0.offline.clone()
+    }
+    fn set_drain_gate(&mut self, gate: DrainGate) {
+        self.0.drain_gate = gate;
+    }
+    fn get_drain_gate(&self) -> DrainGate {
+        self.0.drain_gate.clone()
+    }
+    fn set_usage_quota(&mut self, quota: Option<Arc<UsageQuota>>) {
+        self.0.usage_quota = quota;
+    }
+    fn get_usage_quota(&self) -> Option<Arc<UsageQuota>> {
+        self.0.usage_quota.clone()
+    }
+    fn set_maintenance_policy(&mut self, policy: Option<Arc<MaintenancePolicy>>) {
+        self.0.maintenance_policy = policy;
+    }
+    fn get_maintenance_policy(&self) -> Option<Arc<MaintenancePolicy>> {
+        self.0.maintenance_policy.clone()
+    }
+    fn set_read_only(&mut self, guard: Option<ReadOnlyGuard>) {
+        self.0.read_only = guard;
+    }
+    fn get_read_only(&self) -> Option<ReadOnlyGuard> {
+        self.0.read_only.clone()
+    }
+    fn set_keep_alive(&mut self, worker: Option<KeepAliveWorker>) -> Option<KeepAliveWorker> {
+        std::mem::replace(&mut self.0.keep_alive, worker)
+    }
+    fn get_keep_alive(&self) -> Option<&KeepAliveWorker> {
+        self.0.keep_alive.as_ref()
+    }
+    fn get_status_tracker(&self) -> StatusTracker {
+        self.0.status_tracker.clone()
+    }
+    #[cfg(feature = "queue")]
+    fn set_offline_queue(&mut self, store: Option<Arc<dyn QueueStore>>) {
+        self.0.offline_queue = store;
+    }
+    #[cfg(feature = "queue")]
+    fn get_offline_queue(&self) -> Option<Arc<dyn QueueStore>> {
+        self.0.offline_queue.clone()
+    }
+    #[cfg(feature = "for_serde")]
+    fn set_codec_registry(&mut self, registry: Arc<CodecRegistry>) {
+        self.0.codec_registry = registry;
+    }
+    #[cfg(feature = "for_serde")]
+    fn get_codec_registry(&self) -> Arc<CodecRegistry> {
+        self.0.codec_registry.clone()
+    }
 
-    fn get_simple_http(&mut self) -> &mut SimpleHTTP<Client, Req, Res, String, Vec<Header>, Bytes> {
+    fn get_simple_http(&mut self) -> &mut SimpleHTTP<Client, Req, Res, Method, Vec<Header>, Bytes> {
         &mut self.0.simple_http
     }
 }
 
 impl
-    SimpleHTTP<
-        Agent,
-        (Request, Option<Bytes>),
-        Result<Response, Box<dyn StdError>>,
-        String,
-        Vec<Header>,
-        Bytes,
-    >
+    SimpleHTTP<Agent, UreqRequest, Result<Response, Box<dyn StdError>>, Method, Vec<Header>, Bytes>
 {
     /// Create a new SimpleHTTP with a Client with the default [config](Builder).
     #[inline]
     pub fn new_for_ureq() -> SimpleHTTP<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     > {
-        return SimpleHTTP::new_with_options(
-            Arc::new(Mutex::new(UreqClient {
-                agent: Agent::new(),
-                thread_pool: None,
-            })),
+        SimpleHTTP::new_with_timeout(
+            Arc::new(Mutex::new(LazyClient::new(|| {
+                Box::new(UreqClient {
+                    agent: Agent::new(),
+                    thread_pool: None,
+                })
+            }))),
             VecDeque::new(),
-            DEFAULT_TIMEOUT_MILLISECOND,
-        );
+            DEFAULT_TIMEOUT,
+        )
     }
 }
 impl Default
     for SimpleHTTP<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     >
 {
     fn default() -> SimpleHTTP<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     > {
@@ -298,22 +758,15 @@ impl Default
 }
 
 impl
-    SimpleAPI<
-        Agent,
-        (Request, Option<Bytes>),
-        Result<Response, Box<dyn StdError>>,
-        String,
-        Vec<Header>,
-        Bytes,
-    >
+    SimpleAPI<Agent, UreqRequest, Result<Response, Box<dyn StdError>>, Method, Vec<Header>, Bytes>
 {
     /// Create a new SimpleAPI with a Client with the default [config](Builder).
     #[inline]
     pub fn new_for_ureq() -> SimpleAPI<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     > {
@@ -327,26 +780,26 @@ impl
 impl Default
     for SimpleAPI<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     >
 {
     fn default() -> SimpleAPI<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     > {
         SimpleAPI::<
             Agent,
-            (Request, Option<Bytes>),
+            UreqRequest,
             Result<Response, Box<dyn StdError>>,
-            String,
+            Method,
             Vec<Header>,
             Bytes,
         >::new_for_ureq()
@@ -399,22 +852,15 @@ impl<Client, Req, Res, Method, Header, Bytes> CommonAPI<Client, Req, Res, Method
 }
 
 impl
-    CommonAPI<
-        Agent,
-        (Request, Option<Bytes>),
-        Result<Response, Box<dyn StdError>>,
-        String,
-        Vec<Header>,
-        Bytes,
-    >
+    CommonAPI<Agent, UreqRequest, Result<Response, Box<dyn StdError>>, Method, Vec<Header>, Bytes>
 {
     /// Create a new CommonAPI with a Client with the default [config](Builder).
     #[inline]
     pub fn new_for_ureq() -> CommonAPI<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     > {
@@ -422,23 +868,41 @@ impl
             SimpleAPI::new_for_ureq(),
         ))));
     }
+
+    /**
+    Create a new CommonAPI with a Client of the given agent
+    (e.g. the one of custom TLS options, `DevTlsOptions::ureq_agent()`), with the default options.
+    */
+    pub fn new_with_agent(agent: Agent) -> Self {
+        let common_api = Self::new_for_ureq();
+        common_api
+            .simple_api
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .set_client(Arc::new(Mutex::new(UreqClient {
+                agent,
+                thread_pool: None,
+            })));
+        common_api
+    }
 }
 
 impl Default
     for CommonAPI<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     >
 {
     fn default() -> CommonAPI<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     > {
@@ -449,16 +913,17 @@ impl Default
 impl
     dyn BaseService<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     >
 {
+    #[allow(clippy::too_many_arguments)]
     pub async fn do_request(
         &self,
-        method: String,
+        method: Method,
         header: Option<Vec<Header>>,
         relative_url: impl Into<String>,
         content_type: impl Into<String>,
@@ -486,9 +951,10 @@ impl
         .await
     }
 
+    #[cfg(feature = "multipart")]
     pub async fn do_request_multipart(
         &self,
-        method: String,
+        method: Method,
         header: Option<Vec<Header>>,
         relative_url: impl Into<String>,
         // content_type: impl Into<String>,
@@ -516,26 +982,170 @@ impl
         )
         .await
     }
+
+    /**
+    Send the streaming body of unknown length (chunked, e.g. a large export or a pipe)
+    instead of buffering it to `Bytes`.
+
+    # Remarks
+
+    * The body is sent once (see `StreamingBody`).
+
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub async fn do_request_streaming(
+        &self,
+        method: Method,
+        header: Option<Vec<Header>>,
+        relative_url: impl Into<String>,
+        content_type: impl Into<String>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        body: impl Read + Send + Sync + 'static,
+    ) -> StdResult<Box<Bytes>, Box<dyn StdError>> {
+        let mut extensions = Extensions::new();
+        extensions.insert(StreamingBody::new(body));
+        with_extensions(
+            extensions,
+            self._call_common(
+                method,
+                header,
+                relative_url.into(),
+                content_type.into(),
+                path_param.map(|v| v.into()),
+                query_param.map(|v| v.into()),
+                Bytes::new(),
+            ),
+        )
+        .await
+    }
+
+    // The multipart API streaming the body as it's written (by `MultipartSerializerForStream`)
+    #[cfg(feature = "multipart")]
+    pub fn make_api_multipart_for_stream<R>(
+        &self,
+        base: UreqBaseService,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIStreamingBody<FormData, R> {
+        APIStreamingBody {
+            base,
+            method,
+            relative_url: relative_url.into(),
+            request_serializer: Arc::new(DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM.clone()),
+            response_deserializer,
+        }
+    }
+}
+
+// APIStreamingBody API with the streaming request body (sent by `StreamingBody`, without buffering)
+// T: Request body Type
+// R: Response body Type
+pub struct APIStreamingBody<T, R> {
+    pub base: UreqBaseService,
+    pub method: Method,
+    pub relative_url: String,
+    pub request_serializer: Arc<dyn AsyncBodySerializer<T, (String, Box<dyn Read + Send + Sync>)>>,
+    pub response_deserializer: Arc<dyn BodyDeserializer<R>>,
+}
+impl<T, R> APIStreamingBody<T, R> {
+    pub async fn call(
+        &self,
+        path_param: Option<impl Into<PathParam>>,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.call_with_options(None, path_param, None::<QueryParam>, sent_body)
+            .await
+    }
+
+    pub async fn call_with_options(
+        &self,
+        header: Option<Vec<Header>>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        let (content_type, reader) = catch_hook_panic_async(
+            "request_serializer",
+            self.request_serializer.serialize(sent_body),
+        )
+        .await??;
+        let mut extensions = Extensions::new();
+        extensions.insert(StreamingBody(Arc::new(Mutex::new(Some(reader)))));
+        let (response_content_type, body) = with_extensions(
+            extensions,
+            self.base._call_common_with_content_type(
+                self.method.clone(),
+                header,
+                self.relative_url.clone(),
+                content_type,
+                path_param.map(|v| v.into()),
+                query_param.map(|v| v.into()),
+                Bytes::new(),
+            ),
+        )
+        .await?;
+
+        let target = catch_hook_panic_async(
+            "response_deserializer",
+            self.response_deserializer
+                .decode_response(response_content_type, self.base.body_to_stream(*body)),
+        )
+        .await??;
+
+        Ok(target)
+    }
+
+    // Call with the readers produced by the `factory` (per attempt, e.g. by `MultipartSerializerForStream::body_factory()`)
+    pub async fn call_with_body_factory(
+        &self,
+        header: Option<Vec<Header>>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        factory: BodyFactory<Box<dyn Read + Send + Sync>>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        let (content_type, reader) = factory.produce().await?;
+        let mut extensions = Extensions::new();
+        extensions.insert(StreamingBody(Arc::new(Mutex::new(Some(reader)))));
+        extensions.insert(factory);
+        let (response_content_type, body) = with_extensions(
+            extensions,
+            self.base._call_common_with_content_type(
+                self.method.clone(),
+                header,
+                self.relative_url.clone(),
+                content_type,
+                path_param.map(|v| v.into()),
+                query_param.map(|v| v.into()),
+                Bytes::new(),
+            ),
+        )
+        .await?;
+
+        let target = catch_hook_panic_async(
+            "response_deserializer",
+            self.response_deserializer
+                .decode_response(response_content_type, self.base.body_to_stream(*body)),
+        )
+        .await??;
+
+        Ok(target)
+    }
 }
 
 impl
-    CommonAPI<
-        Agent,
-        (Request, Option<Bytes>),
-        Result<Response, Box<dyn StdError>>,
-        String,
-        Vec<Header>,
-        Bytes,
-    >
+    CommonAPI<Agent, UreqRequest, Result<Response, Box<dyn StdError>>, Method, Vec<Header>, Bytes>
 {
     pub fn as_base_service_shared(
         &self,
     ) -> Arc<
         dyn BaseService<
             Agent,
-            (Request, Option<Bytes>),
+            UreqRequest,
             Result<Response, Box<dyn StdError>>,
-            String,
+            Method,
             Vec<Header>,
             Bytes,
         >,
@@ -547,9 +1157,9 @@ impl
     ) -> Box<
         dyn BaseService<
             Agent,
-            (Request, Option<Bytes>),
+            UreqRequest,
             Result<Response, Box<dyn StdError>>,
-            String,
+            Method,
             Vec<Header>,
             Bytes,
         >,
@@ -559,19 +1169,12 @@ impl
 }
 
 impl
-    BaseService<
-        Agent,
-        (Request, Option<Bytes>),
-        Result<Response, Box<dyn StdError>>,
-        String,
-        Vec<Header>,
-        Bytes,
-    >
+    BaseService<Agent, UreqRequest, Result<Response, Box<dyn StdError>>, Method, Vec<Header>, Bytes>
     for CommonAPI<
         Agent,
-        (Request, Option<Bytes>),
+        UreqRequest,
         Result<Response, Box<dyn StdError>>,
-        String,
+        Method,
         Vec<Header>,
         Bytes,
     >
@@ -582,6 +1185,9 @@ impl
     ) -> Pin<Box<dyn Future<Output = StdResult<Bytes, Box<dyn StdError + Send + Sync>>>>> {
         Box::pin(async { Ok(body) })
     }
+    fn body_size(&self, body: &Bytes) -> Option<u64> {
+        Some(body.len() as u64)
+    }
 
     fn get_simple_api(
         &self,
@@ -589,9 +1195,9 @@ impl
         Mutex<
             dyn BaseAPI<
                 Agent,
-                (Request, Option<Bytes>),
+                UreqRequest,
                 Result<Response, Box<dyn StdError>>,
-                String,
+                Method,
                 Vec<Header>,
                 Bytes,
             >,
@@ -602,7 +1208,7 @@ impl
 
     fn _call_common(
         &self,
-        method: String,
+        method: Method,
         header: Option<Vec<Header>>,
         relative_url: String,
         content_type: String,
@@ -610,11 +1216,102 @@ impl
         query_param: Option<QueryParam>,
         body: Bytes,
     ) -> Pin<Box<dyn Future<Output = StdResult<Box<Bytes>, Box<dyn StdError>>>>> {
+        let future = self._call_common_with_content_type(
+            method,
+            header,
+            relative_url,
+            content_type,
+            path_param,
+            query_param,
+            body,
+        );
+        Box::pin(async move { Ok(future.await?.1) })
+    }
+    fn _call_common_with_content_type(
+        &self,
+        method: Method,
+        header: Option<Vec<Header>>,
+        relative_url: String,
+        content_type: String,
+        path_param: Option<PathParam>,
+        query_param: Option<QueryParam>,
+        body: Bytes,
+    ) -> ResponseFuture<Bytes> {
         let simple_api = self.simple_api.clone();
+        let (status_tracker, clock) = {
+            let mut simple_api = simple_api.lock().unwrap();
+            (
+                simple_api.get_status_tracker(),
+                simple_api.get_simple_http().clock.clone(),
+            )
+        };
+
+        let clock_for_maintenance = clock.clone();
+        let future = Box::pin(async move {
+            // Rejected in the read-only mode (not deferred or queued)
+            if let Some(read_only) = simple_api.lock().unwrap().get_read_only() {
+                read_only.check(method.as_str(), &relative_url)?;
+            }
+            // Deferred/rejected in the maintenance windows of the upstream (without holding the lock)
+            let maintenance_policy = simple_api.lock().unwrap().get_maintenance_policy();
+            let maintenance = match maintenance_policy {
+                Some(policy) => policy
+                    .enter(clock_for_maintenance.as_ref())
+                    .await
+                    .err()
+                    .map(|e| (e, policy.action == MaintenanceAction::Queue)),
+                None => None,
+            };
 
-        Box::pin(async move {
             let mut simple_api = simple_api.lock().unwrap();
-            let (mut req, body) = simple_api.make_request(
+            // The call is in flight until the response is received
+            let _permit = simple_api.get_drain_gate().enter()?;
+            // The writes are queued while it's offline or in the maintenance window
+            // (if there's the offline queue), except the streaming ones
+            let offline = simple_api.get_offline_switch().is_offline();
+            if offline || maintenance.as_ref().is_some_and(|(_, queue)| *queue) {
+                #[cfg(feature = "queue")]
+                {
+                    if let (Some(store), false, false) = (
+                        simple_api.get_offline_queue(),
+                        is_safe_method(method.as_str()),
+                        scoped_extensions().contains::<StreamingBody>(),
+                    ) {
+                        let header = header.as_ref().map(|header| {
+                            header
+                                .iter()
+                                .filter_map(|item| item.value().map(|v| (item.name(), v)))
+                                .collect::<Headers>()
+                        });
+                        let item = QueuedRequest::new_with_params(
+                            method,
+                            relative_url,
+                            path_param,
+                            query_param,
+                            header,
+                            content_type,
+                            body,
+                        );
+                        return Err(enqueue_offline(store.as_ref(), item));
+                    }
+                }
+            }
+            if let Some((e, _)) = maintenance {
+                return Err(Box::new(e) as Box<dyn StdError>);
+            }
+            if offline {
+                return Err(Box::new(OfflineError::new(format!(
+                    "{} {} isn't sent",
+                    method, relative_url
+                ))) as Box<dyn StdError>);
+            }
+            let usage_quota = simple_api.get_usage_quota();
+            if let Some(usage_quota) = &usage_quota {
+                usage_quota.try_acquire()?;
+            }
+            let request_size = body.len() as u64;
+
+            let mut req = simple_api.make_request(
                 method,
                 relative_url,
                 content_type,
@@ -626,92 +1323,136 @@ impl
             if let Some(header) = header {
                 for item in header.into_iter() {
                     if let Some(v) = item.value() {
-                        req = req.set(item.name(), v);
+                        req.headers.insert(item.name(), v);
                     }
                 }
             }
 
-            let res = simple_api.get_simple_http().request((req, body)).await??;
-            let mut bytes: Vec<u8> = Vec::with_capacity(1_000);
-            res.into_reader().take(10_000_000).read_to_end(&mut bytes)?;
+            // NOTE: Don't hold the lock of the `SimpleAPI` while awaiting (e.g. `shutdown()` would block)
+            let simple_http = simple_api.get_simple_http().clone();
+            drop(simple_api);
+            let (response_content_type, bytes) =
+                simple_http.request_bytes_with_content_type(req).await?;
+            if let Some(usage_quota) = &usage_quota {
+                usage_quota.record_bytes(request_size + bytes.len() as u64);
+            }
 
-            Ok(Box::new(Bytes::from(bytes)))
+            Ok((response_content_type, Box::new(bytes)))
+        });
+        // The last error is reported by `status()`
+        Box::pin(async move {
+            let result = future.await;
+            if let Err(e) = &result {
+                status_tracker.record_error(e.as_ref(), clock.system_now());
+            }
+            result
         })
     }
 }
 
 impl
-    dyn BaseAPI<
-        Agent,
-        (Request, Option<Bytes>),
-        Result<Response, Box<dyn StdError>>,
-        String,
-        Vec<Header>,
-        Bytes,
-    >
+    dyn BaseAPI<Agent, UreqRequest, Result<Response, Box<dyn StdError>>, Method, Vec<Header>, Bytes>
 {
     pub fn make_request(
         &mut self,
-        method: String,
+        method: Method,
         relative_url: impl Into<String>,
         content_type: impl Into<String>,
         path_param: Option<impl Into<PathParam>>,
         query_param: Option<impl Into<QueryParam>>,
         body: Bytes,
-    ) -> StdResult<(Request, Option<Bytes>), Box<dyn StdError>> {
-        let mut relative_url = relative_url.into();
+    ) -> StdResult<UreqRequest, Box<dyn StdError>> {
+        let extensions = scoped_extensions();
+        // The versioning of the API (or the one of the service)
+        let versioning = extensions
+            .get::<Versioning>()
+            .cloned()
+            .unwrap_or_else(|| self.get_versioning());
+
+        // The variables of the scope take precedence over the ones of the service
+        let template_variables = match extensions.get::<TemplateVariables>() {
+            Some(variables) => self.get_template_variables().merge(variables),
+            None => self.get_template_variables(),
+        };
+
+        let relative_url = versioning.apply_to_url(&relative_url.into());
+        let url_template = relative_url.clone();
+        let mut relative_url = template_variables.apply_to_url(&relative_url)?;
         if let Some(path_param) = path_param {
             for (k, v) in path_param.into().into_iter() {
                 relative_url = relative_url.replace(&("{".to_string() + &k + "}"), &v);
             }
         }
 
-        // Url
-        let uri: String;
-        match self.get_base_url().join(&relative_url) {
-            Ok(mut url) => {
-                if let Some(query_param) = query_param {
-                    for (k, v) in query_param.into().into_iter() {
-                        url.set_query(Some(&(k + "=" + &v)));
-                    }
-                }
-                uri = url.into();
-            }
-            Err(e) => return Err(Box::new(e)),
+        // Url (the absolute urls are called verbatim)
+        let absolute_url = extensions.get::<AbsoluteUrl>().cloned();
+        let mut url = match &absolute_url {
+            Some(absolute_url) => absolute_url.url.clone(),
+            None => join_url(
+                &self.get_base_url(),
+                &relative_url,
+                self.get_url_join_strategy(),
+            )?,
         };
-        // Method
+        if let Some(query_param) = query_param {
+            for (k, v) in query_param.into().into_iter() {
+                append_query_param(&mut url, &k, &v);
+            }
+        }
+        if absolute_url.is_none() {
+            self.get_url_normalization().normalize(&mut url);
+        }
 
-        let mut req = { self.get_simple_http().client.lock().unwrap() }
-            .get_client()
-            .request(&method, &uri);
-        req = req.timeout(self.get_simple_http().get_timeout_duration());
+        let mut req = UreqRequest::new(method, url);
+        req.timeout = Some(self.get_simple_http().get_timeout());
+        req.options.url_template = Some(url_template);
+        req.options.extensions = extensions;
+        req.options.absolute_url = absolute_url.as_ref().map(|v| v.url.clone());
+        req.options.skip_auth = absolute_url.as_ref().is_some_and(|v| v.skip_auth);
+        req.options.connect_to = req.options.extensions.get::<ConnectTo>().map(|v| v.0);
+        req.options.sni_hostname = req
+            .options
+            .extensions
+            .get::<SniHostname>()
+            .map(|v| v.0.clone());
 
         // Header
         if let Some(header) = self.get_default_header() {
-            for item in header.into_iter() {
-                if let Some(v) = item.value() {
-                    req = req.set(item.name(), v);
-                }
-            }
+            req.headers = header;
+        }
+        if req.options.skip_auth {
+            req.headers.remove(AUTHORIZATION);
         }
-        let content_type = content_type.into();
+        let content_type = versioning.apply_to_media_type(&content_type.into());
         if !content_type.is_empty() {
-            req = req.set(CONTENT_TYPE, &content_type);
+            req.headers.insert(CONTENT_TYPE, content_type);
+        }
+        if let Some(accept) = req.options.extensions.get::<AcceptMediaType>() {
+            let accept = versioning.apply_to_media_type(&accept.0);
+            req.headers.insert(ACCEPT, accept);
+        }
+        if let Some((name, value)) = versioning.get_header() {
+            req.headers.insert(name, value);
+        }
+        // The scoped streaming body is sent instead of the body
+        req.streaming_body = req.options.extensions.get::<StreamingBody>().cloned();
+        if req.streaming_body.is_none() {
+            req.body = Some(body);
         }
 
-        Ok((req, Some(body)))
+        Ok(req)
     }
 
     #[cfg(feature = "multipart")]
     pub fn make_request_multipart(
         &mut self,
-        method: String,
+        method: Method,
         relative_url: impl Into<String>,
         // content_type: String,
         path_param: Option<impl Into<PathParam>>,
         query_param: Option<impl Into<QueryParam>>,
         body: FormData,
-    ) -> StdResult<(Request, Option<Bytes>), Box<dyn StdError>> {
+    ) -> StdResult<UreqRequest, Box<dyn StdError>> {
         let (content_type, body) = DEFAULT_MULTIPART_SERIALIZER.encode(body)?;
         self.make_request(
             method,
@@ -728,21 +1469,100 @@ impl
     }
 }
 
-pub fn add_header_authentication(
-    mut header_map: Vec<Header>,
-    token: impl Into<String>,
-) -> StdResult<Vec<Header>, Box<dyn StdError>> {
-    let str = token.into();
-    header_map.push(Header::new("Authorization", &str));
+// DeadlineReader Fail the reading with `TimedOut` once the deadline has passed (checked before each read)
+struct DeadlineReader<R> {
+    inner: R,
+    deadline: Option<Instant>,
+}
+impl<R: Read> Read for DeadlineReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(deadline_error());
+            }
+        }
+        self.inner.read(buf)
+    }
+}
+fn deadline_error() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "timed out reading response body")
+}
+
+/*
+Read the whole body (limited to 10MB) on the shared thread pool,
+failed with `TimedOut` at the deadline even if a read is blocked (e.g. the server stalls mid-body):
+the blocked read itself is bounded by the timeout of the ureq call (`UreqRequest.timeout`), releasing the thread.
+*/
+async fn read_body(
+    res: Response,
+    deadline: Option<Instant>,
+    clock: Arc<dyn Clock>,
+) -> StdResult<io::Result<Bytes>, SpawnError> {
+    let handle = shared_thread_pool()
+        .inner
+        .lock()
+        .unwrap()
+        .spawn_with_handle(async move {
+            let mut reader = DeadlineReader {
+                inner: res.into_reader().take(10_000_000),
+                deadline,
+            };
+            let mut bytes: Vec<u8> = Vec::with_capacity(1_000);
+            reader.read_to_end(&mut bytes).map(|_| Bytes::from(bytes))
+        })?;
+    let deadline = match deadline {
+        Some(deadline) => deadline,
+        None => return Ok(handle.await),
+    };
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    match future::select(handle, clock.sleep(remaining)).await {
+        future::Either::Left((result, _)) => Ok(result),
+        future::Either::Right(_) => Ok(Err(deadline_error())),
+    }
+}
+
+impl From<&Headers> for Vec<Header> {
+    fn from(headers: &Headers) -> Self {
+        headers.iter().map(|(k, v)| Header::new(k, v)).collect()
+    }
+}
+impl From<&Vec<Header>> for Headers {
+    fn from(header_list: &Vec<Header>) -> Self {
+        header_list
+            .iter()
+            .filter_map(|item| item.value().map(|v| (item.name(), v)))
+            .collect()
+    }
+}
 
-    Ok(header_map)
+impl HasRequestOptions for UreqRequest {
+    fn get_request_options(&self) -> RequestOptions {
+        self.options.clone()
+    }
+    fn set_request_options(&mut self, options: RequestOptions) {
+        self.options = options;
+    }
 }
 
-pub fn add_header_authentication_bearer(
-    header_map: Vec<Header>,
-    token: impl Into<String>,
-) -> StdResult<Vec<Header>, Box<dyn StdError>> {
-    return add_header_authentication(header_map, "Bearer ".to_string() + &token.into());
+impl RequestHeaders for UreqRequest {
+    fn get_headers(&self) -> Headers {
+        self.headers.clone()
+    }
+    fn set_headers(&mut self, headers: Headers) -> StdResult<(), Box<dyn StdError>> {
+        self.headers = headers;
+        Ok(())
+    }
+}
+impl RequestSummary for UreqRequest {
+    fn get_method(&self) -> String {
+        self.method.to_string()
+    }
+    fn get_url(&self) -> String {
+        self.url.to_string()
+    }
+    fn get_body(&self) -> Option<Bytes> {
+        self.body.clone()
+    }
 }
 
 #[cfg(feature = "multipart")]
@@ -755,6 +1575,24 @@ pub fn body_from_multipart(form_data: &FormData) -> StdResult<(Bytes, Vec<u8>),
 pub async fn body_to_multipart(
     headers: &Vec<Header>,
     body: Bytes,
+) -> StdResult<Multipart<'_>, Box<dyn StdError>> {
+    body_to_multipart_with_constraints(headers, body, Constraints::default()).await
+}
+/**
+Parse the multipart `body` with the `constraints` (e.g. the upload limits of the servers).
+
+# Remarks
+
+* The violations (e.g. `multer::Error::FieldSizeExceeded`/`StreamSizeExceeded`/`UnknownField`)
+  are returned by reading the fields (e.g. `next_field()`).
+* The file fields are limited by `SizeLimit::per_field()`/`for_field()` as well.
+
+*/
+#[cfg(feature = "multipart")]
+pub async fn body_to_multipart_with_constraints(
+    headers: &Vec<Header>,
+    body: Bytes,
+    constraints: Constraints,
 ) -> StdResult<Multipart<'_>, Box<dyn StdError>> {
     let boundary: String;
 
@@ -764,8 +1602,8 @@ pub async fn body_to_multipart(
     for item in headers.into_iter() {
         if item.name() == CONTENT_TYPE {
             if let Some(content_type) = item.value() {
-                boundary = multer::parse_boundary(&content_type)?;
-                return Ok(Multipart::new(body, boundary));
+                boundary = parse_multipart_boundary(content_type)?;
+                return Ok(Multipart::with_constraints(body, boundary, constraints));
             }
         }
     }
@@ -776,61 +1614,255 @@ pub async fn body_to_multipart(
 }
 
 impl
-    SimpleHTTP<
-        Agent,
-        (Request, Option<Bytes>),
-        Result<Response, Box<dyn StdError>>,
-        String,
-        Vec<Header>,
-        Bytes,
-    >
+    SimpleHTTP<Agent, UreqRequest, Result<Response, Box<dyn StdError>>, Method, Vec<Header>, Bytes>
 {
     pub async fn request(
         &self,
-        mut request: (Request, Option<Bytes>),
+        mut request: UreqRequest,
     ) -> SimpleHTTPResponse<Result<Response, Box<dyn StdError>>> {
         for interceptor in &mut self.interceptors.iter() {
-            interceptor.intercept(&mut request)?;
+            catch_hook_panic("interceptor", || interceptor.intercept(&mut request))??;
         }
 
+        let audit_call = self.begin_audit(&request);
+
+        let url = request.url.to_string();
+        let options = request.get_request_options();
+        let sent_at = self.clock.system_now();
+        // NOTE: Don't hold the lock of the client while awaiting (the concurrent calls would block)
+        let future = self.client.lock().unwrap().request(request);
+
         // Implement timeout
-        match { self.client.lock().unwrap().request(request) }.await {
+        let result = future.await;
+        let observed = result.as_ref().map_err(|e| e.as_ref());
+        self.observe_response(&url, &options, observed, sent_at);
+        self.finish_audit(
+            audit_call,
+            match &result {
+                Ok(res) => Ok(res.status()),
+                Err(e) => Err(e.as_ref()),
+            },
+        );
+        match result {
             Ok(result) => Ok(Ok(result)),
             Err(e) => Err(e),
         }
     }
 
+    /**
+    Send the request and read the whole response body.
+
+    # Remarks
+
+    * The timeout (`UreqRequest.timeout` after interceptors) covers reading the body too,
+      it's counted from sending the request (so it's enforced even if the server stalls mid-body).
+    * The body is read on the shared thread pool rather than the async caller.
+
+    */
+    pub async fn request_bytes(&self, request: UreqRequest) -> StdResult<Bytes, Box<dyn StdError>> {
+        Ok(self.request_bytes_with_content_type(request).await?.1)
+    }
+
+    // Send the request and read the whole response body with its `Content-Type` (like `request_bytes()`)
+    pub async fn request_bytes_with_content_type(
+        &self,
+        mut request: UreqRequest,
+    ) -> StdResult<(Option<String>, Bytes), Box<dyn StdError>> {
+        for interceptor in &mut self.interceptors.iter() {
+            catch_hook_panic("interceptor", || interceptor.intercept(&mut request))??;
+        }
+
+        let audit_call = self.begin_audit(&request);
+
+        let deadline = request.timeout.map(|timeout| Instant::now() + timeout);
+        let options = request.get_request_options();
+        let url = request.url.to_string();
+        let sent_at = self.clock.system_now();
+        let future = self.client.lock().unwrap().request(request);
+        let res = match future.await {
+            Ok(res) => res,
+            Err(e) => {
+                if let Some(ureq::Error::Status(status, _)) = e.downcast_ref::<ureq::Error>() {
+                    ResponseStatus::record(&options, *status);
+                }
+                self.observe_response(&url, &options, Err(e.as_ref()), sent_at);
+                self.finish_audit(audit_call, Err(e.as_ref()));
+                return Err(e);
+            }
+        };
+        self.observe_response(&url, &options, Ok(&res), sent_at);
+        let status = res.status();
+        ResponseStatus::record(&options, status);
+        let content_type = res.header("Content-Type").map(|v| v.to_string());
+        ResponseLanguage::record(&options, res.header("Content-Language"));
+        let content_length = res
+            .header("Content-Length")
+            .and_then(|v| v.trim().parse::<u64>().ok());
+        let content_encoding = res.header("Content-Encoding").map(|v| v.to_string());
+        // ureq drops both of them after decompressing the body
+        let decompressed =
+            (content_length.is_some() || content_encoding.is_some()).then_some(false);
+
+        let result = read_body(res, deadline, self.clock.clone()).await?;
+        if let Ok(body) = &result {
+            ResponseSizeInfo::record(
+                &options,
+                ResponseSize {
+                    content_length,
+                    content_encoding,
+                    decoded_length: Some(body.len() as u64),
+                    decompressed,
+                },
+            );
+        }
+        self.finish_audit(
+            audit_call,
+            match &result {
+                Ok(_) => Ok(status),
+                Err(e) => Err(e),
+            },
+        );
+        Ok((content_type, result?))
+    }
+
+    /**
+    Send the request and read the whole response with its status and headers,
+    the error statuses (`4xx`/`5xx`) are returned as the responses too (rather than `ureq::Error::Status`).
+    */
+    pub async fn request_response(
+        &self,
+        mut request: UreqRequest,
+    ) -> StdResult<(u16, Headers, Bytes), Box<dyn StdError>> {
+        for interceptor in &mut self.interceptors.iter() {
+            catch_hook_panic("interceptor", || interceptor.intercept(&mut request))??;
+        }
+
+        let audit_call = self.begin_audit(&request);
+
+        let deadline = request.timeout.map(|timeout| Instant::now() + timeout);
+        let url = request.url.to_string();
+        let options = request.get_request_options();
+        let sent_at = self.clock.system_now();
+        let future = self.client.lock().unwrap().request(request);
+        let res = match future.await {
+            Ok(res) => res,
+            Err(e) => match e.downcast::<ureq::Error>() {
+                Ok(e) => match *e {
+                    ureq::Error::Status(_, res) => res,
+                    e => {
+                        let e = Box::new(e) as Box<dyn StdError>;
+                        self.finish_audit(audit_call, Err(e.as_ref()));
+                        return Err(e);
+                    }
+                },
+                Err(e) => {
+                    self.finish_audit(audit_call, Err(e.as_ref()));
+                    return Err(e);
+                }
+            },
+        };
+        self.observe_response(&url, &options, Ok(&res), sent_at);
+        let status = res.status();
+        let headers = res
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| res.header(&name).map(|v| (name.clone(), v.to_string())))
+            .collect::<Headers>();
+
+        let result = read_body(res, deadline, self.clock.clone()).await?;
+        self.finish_audit(
+            audit_call,
+            match &result {
+                Ok(_) => Ok(status),
+                Err(e) => Err(e),
+            },
+        );
+        Ok((status, headers, result?))
+    }
+
+    // Observe the `Date` (for the `ClockSkew`) and the deprecation signals of the response
+    // (the error statuses of `ureq::Error::Status` too)
+    fn observe_response(
+        &self,
+        url: &str,
+        options: &RequestOptions,
+        result: StdResult<&Response, &(dyn StdError + 'static)>,
+        sent_at: SystemTime,
+    ) {
+        let res = match result {
+            Ok(res) => res,
+            Err(e) => match e.downcast_ref::<ureq::Error>() {
+                Some(ureq::Error::Status(_, res)) => res,
+                _ => return,
+            },
+        };
+        self.observe_date(url, res.header("Date"), sent_at);
+        self.observe_deprecation(
+            options,
+            DeprecationNotice::parse(
+                url,
+                res.header("Deprecation"),
+                res.header("Sunset"),
+                &res.all("Warning"),
+            ),
+        );
+    }
+
+    fn begin_audit(&self, request: &UreqRequest) -> Option<AuditCall> {
+        self.audit_log.as_ref().and_then(|audit_log| {
+            audit_log.begin(
+                request.method.as_str(),
+                request.url.path(),
+                &request.options,
+            )
+        })
+    }
+
+    // The status of the error responses is taken from `ureq::Error::Status`
+    fn finish_audit(
+        &self,
+        audit_call: Option<AuditCall>,
+        result: StdResult<u16, &(dyn StdError + 'static)>,
+    ) {
+        if let (Some(audit_log), Some(audit_call)) = (&self.audit_log, audit_call) {
+            let (status, error) = match result {
+                Ok(status) => (Some(status), None),
+                Err(e) => match e.downcast_ref::<ureq::Error>() {
+                    Some(ureq::Error::Status(status, _)) => (Some(*status), Some(e.to_string())),
+                    _ => (None, Some(e.to_string())),
+                },
+            };
+            audit_log.finish(audit_call, status, error);
+        }
+    }
+
     pub async fn get(
         &self,
         uri: impl Into<String>,
     ) -> SimpleHTTPResponse<Result<Response, Box<dyn StdError>>> {
-        let req = { self.client.lock().unwrap().get_client().get(&uri.into()) };
-        self.request((req, None)).await
+        let req = UreqRequest::new(Method::GET, Url::parse(&uri.into())?);
+        self.request(req).await
     }
     pub async fn head(
         &self,
         uri: impl Into<String>,
     ) -> SimpleHTTPResponse<Result<Response, Box<dyn StdError>>> {
-        let req = { self.client.lock().unwrap().get_client().head(&uri.into()) };
-        self.request((req, None)).await
+        let req = UreqRequest::new(Method::HEAD, Url::parse(&uri.into())?);
+        self.request(req).await
     }
     pub async fn option(
         &self,
         uri: impl Into<String>,
     ) -> SimpleHTTPResponse<Result<Response, Box<dyn StdError>>> {
-        let req = {
-            { self.client.lock().unwrap() }
-                .get_client()
-                .request("OPTIONS", &uri.into())
-        };
-        self.request((req, None)).await
+        let req = UreqRequest::new(Method::OPTIONS, Url::parse(&uri.into())?);
+        self.request(req).await
     }
     pub async fn delete(
         &self,
         uri: impl Into<String>,
     ) -> SimpleHTTPResponse<Result<Response, Box<dyn StdError>>> {
-        let req = { self.client.lock().unwrap().get_client().delete(&uri.into()) };
-        self.request((req, None)).await
+        let req = UreqRequest::new(Method::DELETE, Url::parse(&uri.into())?);
+        self.request(req).await
     }
 
     pub async fn post(
@@ -838,27 +1870,26 @@ impl
         uri: impl Into<String>,
         body: Bytes,
     ) -> SimpleHTTPResponse<Result<Response, Box<dyn StdError>>> {
-        let req = { self.client.lock().unwrap().get_client().post(&uri.into()) };
-        self.request((req, Some(body))).await
+        let mut req = UreqRequest::new(Method::POST, Url::parse(&uri.into())?);
+        req.body = Some(body);
+        self.request(req).await
     }
     pub async fn put(
         &self,
         uri: impl Into<String>,
         body: Bytes,
     ) -> SimpleHTTPResponse<Result<Response, Box<dyn StdError>>> {
-        let req = { self.client.lock().unwrap().get_client().put(&uri.into()) };
-        self.request((req, Some(body))).await
+        let mut req = UreqRequest::new(Method::PUT, Url::parse(&uri.into())?);
+        req.body = Some(body);
+        self.request(req).await
     }
     pub async fn patch(
         &self,
         uri: impl Into<String>,
         body: Bytes,
     ) -> SimpleHTTPResponse<Result<Response, Box<dyn StdError>>> {
-        let req = {
-            { self.client.lock().unwrap() }
-                .get_client()
-                .request("PATCH", &uri.into())
-        };
-        self.request((req, Some(body))).await
+        let mut req = UreqRequest::new(Method::PATCH, Url::parse(&uri.into())?);
+        req.body = Some(body);
+        self.request(req).await
     }
 }
@@ -0,0 +1,268 @@
+/*!
+In this module there're implementations of the blocking facade over the hyper binding.
+
+The async calls are driven by an internal (current thread) tokio runtime,
+so non-async codebases could still use the hyper binding.
+
+# Remarks
+
+* Like `reqwest::blocking`, don't call them inside an async context (it panics).
+* The pooled connections are driven only while there're blocking calls in progress.
+
+*/
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::io;
+use std::path::Path;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use http::method::Method;
+use hyper::client::{connect::Connect, HttpConnector};
+use hyper::{Body, Client, HeaderMap, Request, Response, Result};
+use tokio::runtime::{Builder, Runtime};
+
+use super::bind_hyper::{self, APIUpload};
+use super::common::{PathParam, QueryParam};
+use super::simple_api::{APIHasBody, APIMultipart, APINoBody, APIResponseOnly, BaseService};
+
+/*
+`HyperBaseService` The `BaseService` of the hyper binding
+*/
+pub type HyperBaseService<C = HttpConnector> = dyn BaseService<
+    Client<C, Body>,
+    Request<Body>,
+    Result<Response<Body>>,
+    Method,
+    HeaderMap,
+    Body,
+>;
+
+/*
+`HyperCommonAPI` The async `CommonAPI` of the hyper binding
+*/
+pub type HyperCommonAPI<C = HttpConnector> =
+    bind_hyper::CommonAPI<Client<C, Body>, Request<Body>, Result<Response<Body>>, HeaderMap, Body>;
+
+/*
+`CommonAPI` The blocking facade of `bind_hyper::CommonAPI`
+*/
+pub struct CommonAPI<C = HttpConnector> {
+    pub inner: HyperCommonAPI<C>,
+    runtime: Arc<Runtime>,
+}
+
+impl<C> Clone for CommonAPI<C> {
+    fn clone(&self) -> Self {
+        CommonAPI {
+            inner: self.inner.clone(),
+            runtime: self.runtime.clone(),
+        }
+    }
+}
+
+impl CommonAPI<HttpConnector> {
+    pub fn new_for_hyper() -> io::Result<Self> {
+        Ok(Self::new_with_options(
+            bind_hyper::CommonAPI::new_for_hyper(),
+            Arc::new(new_runtime()?),
+        ))
+    }
+}
+
+impl<C> CommonAPI<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    pub fn new_with_options(inner: HyperCommonAPI<C>, runtime: Arc<Runtime>) -> Self {
+        CommonAPI { inner, runtime }
+    }
+
+    pub fn as_base_service_shared(&self) -> Arc<HyperBaseService<C>> {
+        self.inner.as_base_service_shared()
+    }
+    pub fn as_base_service_setter(&self) -> Box<HyperBaseService<C>> {
+        self.inner.as_base_service_setter()
+    }
+
+    // Run the given future to completion on the internal runtime
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    // Wrap the async API (made by `make_api_*()`) as a blocking one
+    pub fn wrap<A>(&self, api: A) -> BlockingAPI<A> {
+        BlockingAPI {
+            api,
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    pub fn preconnect(&self) -> StdResult<(), Box<dyn StdError>> {
+        let base_service = self.as_base_service_setter();
+        self.block_on(base_service.preconnect())
+    }
+
+    /**
+    Send a request and read the whole response body (blocking).
+
+    # Arguments
+
+    * `relative_url` - The relative url to the base url, `{name}` would be replaced by the `path_param`.
+
+    */
+    #[allow(clippy::too_many_arguments)]
+    pub fn do_request(
+        &self,
+        method: Method,
+        header: Option<HeaderMap>,
+        relative_url: impl Into<String>,
+        content_type: impl Into<String>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        body: Body,
+    ) -> StdResult<Bytes, Box<dyn StdError>> {
+        let base_service = self.as_base_service_setter();
+        self.block_on(async {
+            let body = base_service
+                .do_request(
+                    method,
+                    header,
+                    relative_url,
+                    content_type,
+                    path_param,
+                    query_param,
+                    body,
+                )
+                .await?;
+            match base_service.body_to_bytes(*body).await {
+                Ok(v) => Ok(v),
+                Err(e) => Err(e as Box<dyn StdError>),
+            }
+        })
+    }
+}
+
+/*
+`BlockingAPI` The blocking facade of the APIs made by `make_api_*()`
+*/
+pub struct BlockingAPI<A> {
+    pub api: A,
+    runtime: Arc<Runtime>,
+}
+
+impl<R, Client, Req, Res, Method, Header, B>
+    BlockingAPI<APIResponseOnly<R, Client, Req, Res, Method, Header, B>>
+where
+    B: Default,
+    Method: Clone,
+{
+    pub fn call(&self) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.runtime.block_on(self.api.call())
+    }
+    pub fn call_with_options(
+        &self,
+        header: Option<Header>,
+        query_param: Option<impl Into<QueryParam>>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.runtime
+            .block_on(self.api.call_with_options(header, query_param))
+    }
+}
+
+impl<R, Client, Req, Res, Method, Header, B>
+    BlockingAPI<APINoBody<R, Client, Req, Res, Method, Header, B>>
+where
+    B: Default,
+    Method: Clone,
+{
+    pub fn call(&self, path_param: Option<PathParam>) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.runtime.block_on(self.api.call(path_param))
+    }
+    pub fn call_with_options(
+        &self,
+        header: Option<Header>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.runtime
+            .block_on(self.api.call_with_options(header, path_param, query_param))
+    }
+}
+
+impl<T, R, Client, Req, Res, Method, Header, B>
+    BlockingAPI<APIHasBody<T, R, Client, Req, Res, Method, Header, B>>
+where
+    B: Default,
+    Method: Clone,
+{
+    pub fn call(
+        &self,
+        path_param: Option<impl Into<PathParam>>,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.runtime.block_on(self.api.call(path_param, sent_body))
+    }
+    pub fn call_with_options(
+        &self,
+        header: Option<Header>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.runtime.block_on(self.api.call_with_options(
+            header,
+            path_param,
+            query_param,
+            sent_body,
+        ))
+    }
+}
+
+impl<T, R, Client, Req, Res, Method, Header, B>
+    BlockingAPI<APIMultipart<T, R, Client, Req, Res, Method, Header, B>>
+where
+    B: Default,
+    Method: Clone,
+{
+    pub fn call(
+        &self,
+        path_param: Option<impl Into<PathParam>>,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.runtime.block_on(self.api.call(path_param, sent_body))
+    }
+    pub fn call_with_options(
+        &self,
+        header: Option<Header>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.runtime.block_on(self.api.call_with_options(
+            header,
+            path_param,
+            query_param,
+            sent_body,
+        ))
+    }
+}
+
+impl<R, C> BlockingAPI<APIUpload<R, C>>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    pub fn call(
+        &self,
+        path_param: Option<impl Into<PathParam>>,
+        file_path: impl AsRef<Path>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.runtime.block_on(self.api.call(path_param, file_path))
+    }
+}
+
+fn new_runtime() -> io::Result<Runtime> {
+    Builder::new_current_thread().enable_all().build()
+}
@@ -0,0 +1,398 @@
+/*!
+In this module there're implementations of `CodecRegistry`,
+mapping the media types to the codecs, so the serializers/deserializers are looked up by the content types.
+
+# Remarks
+
+* The serde codecs (`SerdeCodec`, JSON/plain text) encode/decode any serde type directly (without an intermediate value),
+  and the typed codecs (`Codec<T>`, e.g. the protobuf codec of a message) are registered per type.
+* The parameters of the content types are ignored (e.g. `; charset=utf-8`),
+  and the structured syntax suffixes fall back to their bases (e.g. `application/problem+json` to `application/json`).
+* The responses are decoded by the codecs of their `Content-Type` (by `CodecDeserializer`).
+
+*/
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::marker::PhantomData;
+use std::result::Result as StdResult;
+use std::sync::{Arc, RwLock};
+
+use bytes::Bytes;
+use serde::de::value::{Error as ValueError, StrDeserializer};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::simple_api::{
+    collect_body_stream, BodyDeserializer, BodySerializer, BodyStream, DecodeFuture,
+};
+
+pub const MEDIA_TYPE_JSON: &str = "application/json";
+pub const MEDIA_TYPE_TEXT: &str = "text/plain";
+
+/*
+`Codec` Encode/decode the values of `T` in a media type (registered by `CodecRegistry::register()`)
+*/
+pub trait Codec<T>: Send + Sync {
+    fn encode(&self, value: &T) -> StdResult<Bytes, Box<dyn StdError>>;
+    fn decode(&self, bytes: &Bytes) -> StdResult<T, Box<dyn StdError>>;
+}
+
+/*
+`SerdeCodec` Encode/decode any serde type (registered by `CodecRegistry::register_serde()`)
+
+* `Json`: As JSON (`application/json`).
+* `Text`: The strings as they are (`text/plain`), the other values are encoded as JSON.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerdeCodec {
+    Json,
+    Text,
+}
+impl SerdeCodec {
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> StdResult<Bytes, Box<dyn StdError>> {
+        let json = serde_json::to_vec(value)?;
+        match self {
+            SerdeCodec::Text if json.first() == Some(&b'"') => {
+                Ok(Bytes::from(serde_json::from_slice::<String>(&json)?))
+            }
+            _ => Ok(Bytes::from(json)),
+        }
+    }
+    pub fn decode<R: DeserializeOwned>(&self, bytes: &Bytes) -> StdResult<R, Box<dyn StdError>> {
+        match self {
+            SerdeCodec::Json => Ok(serde_json::from_slice(bytes)?),
+            SerdeCodec::Text => Ok(R::deserialize(StrDeserializer::<ValueError>::new(
+                std::str::from_utf8(bytes)?,
+            ))?),
+        }
+    }
+}
+impl<T: Serialize + DeserializeOwned> Codec<T> for SerdeCodec {
+    fn encode(&self, value: &T) -> StdResult<Bytes, Box<dyn StdError>> {
+        SerdeCodec::encode(self, value)
+    }
+    fn decode(&self, bytes: &Bytes) -> StdResult<T, Box<dyn StdError>> {
+        SerdeCodec::decode(self, bytes)
+    }
+}
+pub const DEFAULT_JSON_CODEC: SerdeCodec = SerdeCodec::Json;
+pub const DEFAULT_TEXT_CODEC: SerdeCodec = SerdeCodec::Text;
+
+// The codec of a media type resolved for `T`
+enum ResolvedCodec<T> {
+    Serde(SerdeCodec),
+    Typed(Arc<dyn Codec<T>>),
+}
+impl<T> Clone for ResolvedCodec<T> {
+    fn clone(&self) -> Self {
+        match self {
+            ResolvedCodec::Serde(codec) => ResolvedCodec::Serde(*codec),
+            ResolvedCodec::Typed(codec) => ResolvedCodec::Typed(codec.clone()),
+        }
+    }
+}
+impl<T> ResolvedCodec<T> {
+    fn encode(&self, value: &T) -> StdResult<Bytes, Box<dyn StdError>>
+    where
+        T: Serialize,
+    {
+        match self {
+            ResolvedCodec::Serde(codec) => codec.encode(value),
+            ResolvedCodec::Typed(codec) => codec.encode(value),
+        }
+    }
+    fn decode(&self, bytes: &Bytes) -> StdResult<T, Box<dyn StdError>>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            ResolvedCodec::Serde(codec) => codec.decode(bytes),
+            ResolvedCodec::Typed(codec) => codec.decode(bytes),
+        }
+    }
+}
+
+// The codecs of a media type
+#[derive(Default)]
+struct CodecEntry {
+    serde: Option<SerdeCodec>,
+    // The `TypeId` of `T` -> `Arc<dyn Codec<T>>`
+    typed: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+impl CodecEntry {
+    // The typed codec of `T` takes precedence over the serde one
+    fn resolve<T: 'static>(&self) -> Option<ResolvedCodec<T>> {
+        let typed = self
+            .typed
+            .get(&TypeId::of::<T>())
+            .and_then(|codec| codec.downcast_ref::<Arc<dyn Codec<T>>>());
+        match typed {
+            Some(codec) => Some(ResolvedCodec::Typed(codec.clone())),
+            None => self.serde.map(ResolvedCodec::Serde),
+        }
+    }
+}
+
+/*
+`UnsupportedMediaTypeError` There's no codec registered for the media type
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsupportedMediaTypeError {
+    details: String,
+    pub media_type: String,
+}
+impl StdError for UnsupportedMediaTypeError {}
+impl UnsupportedMediaTypeError {
+    pub fn new(msg: impl Into<String>, media_type: impl Into<String>) -> UnsupportedMediaTypeError {
+        UnsupportedMediaTypeError {
+            details: msg.into(),
+            media_type: media_type.into(),
+        }
+    }
+}
+impl std::fmt::Display for UnsupportedMediaTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "UnsupportedMediaTypeError: {}", self.details)
+    }
+}
+
+// The result of the lookups by the media types (e.g. the APIs of the codecs)
+pub type CodecResult<T> = StdResult<T, UnsupportedMediaTypeError>;
+
+// The media type of the content type, without the parameters (lowercase)
+pub fn media_type_of(content_type: &str) -> String {
+    content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/**
+`CodecRegistry` The codecs by their media types.
+
+# Remarks
+
+* `CodecRegistry::default()` has `application/json` (`SerdeCodec::Json`) and `text/plain` (`SerdeCodec::Text`),
+  `CodecRegistry::new()` has none.
+* The typed codecs of a media type (`register()`) take precedence over its serde codec for their types.
+* It's shared (`Arc`) by the `CommonAPI` (`get_codec_registry()`), and the codecs could be registered at any time.
+
+*/
+pub struct CodecRegistry {
+    codecs: RwLock<HashMap<String, CodecEntry>>,
+}
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        let registry = Self::new();
+        registry.register_serde(MEDIA_TYPE_JSON, DEFAULT_JSON_CODEC);
+        registry.register_serde(MEDIA_TYPE_TEXT, DEFAULT_TEXT_CODEC);
+        registry
+    }
+}
+impl CodecRegistry {
+    pub fn new() -> Self {
+        CodecRegistry {
+            codecs: RwLock::new(HashMap::new()),
+        }
+    }
+
+    // Register (or replace) the serde codec of the media type
+    pub fn register_serde(&self, media_type: impl AsRef<str>, codec: SerdeCodec) {
+        self.codecs
+            .write()
+            .unwrap()
+            .entry(media_type_of(media_type.as_ref()))
+            .or_default()
+            .serde = Some(codec);
+    }
+    // Register (or replace) the codec of the values of `T` of the media type
+    pub fn register<T: 'static>(&self, media_type: impl AsRef<str>, codec: Arc<dyn Codec<T>>) {
+        self.codecs
+            .write()
+            .unwrap()
+            .entry(media_type_of(media_type.as_ref()))
+            .or_default()
+            .typed
+            .insert(TypeId::of::<T>(), Arc::new(codec));
+    }
+    // Unregister all codecs of the media type, returns whether there's any
+    pub fn unregister(&self, media_type: impl AsRef<str>) -> bool {
+        self.codecs
+            .write()
+            .unwrap()
+            .remove(&media_type_of(media_type.as_ref()))
+            .is_some()
+    }
+    // The registered media types (sorted)
+    pub fn media_types(&self) -> Vec<String> {
+        let mut media_types = self
+            .codecs
+            .read()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        media_types.sort();
+        media_types
+    }
+
+    // Whether there's any codec of the content type (or of its structured syntax suffix)
+    pub fn contains(&self, content_type: &str) -> bool {
+        self.resolve_by(content_type, |_| Some(())).is_some()
+    }
+    // Get the codec of `T` of the content type (or the one of its structured syntax suffix)
+    pub fn get<T>(&self, content_type: &str) -> Option<Arc<dyn Codec<T>>>
+    where
+        T: Serialize + DeserializeOwned + 'static,
+    {
+        match self.resolve::<T>(content_type)? {
+            ResolvedCodec::Serde(codec) => Some(Arc::new(codec)),
+            ResolvedCodec::Typed(codec) => Some(codec),
+        }
+    }
+
+    fn resolve_by<V>(
+        &self,
+        content_type: &str,
+        resolve: impl Fn(&CodecEntry) -> Option<V>,
+    ) -> Option<V> {
+        let media_type = media_type_of(content_type);
+        let codecs = self.codecs.read().unwrap();
+        if let Some(resolved) = codecs.get(&media_type).and_then(&resolve) {
+            return Some(resolved);
+        }
+
+        // e.g. application/problem+json -> application/json
+        let (top_level, sub_type) = media_type.split_once('/')?;
+        let (_, suffix) = sub_type.rsplit_once('+')?;
+        codecs
+            .get(&format!("{}/{}", top_level, suffix))
+            .and_then(resolve)
+    }
+    fn resolve<T: 'static>(&self, content_type: &str) -> Option<ResolvedCodec<T>> {
+        self.resolve_by(content_type, CodecEntry::resolve::<T>)
+    }
+    fn resolve_or_err<T: 'static>(
+        &self,
+        content_type: &str,
+    ) -> StdResult<ResolvedCodec<T>, UnsupportedMediaTypeError> {
+        self.resolve(content_type).ok_or_else(|| {
+            UnsupportedMediaTypeError::new(
+                format!("no codec for {}", content_type),
+                media_type_of(content_type),
+            )
+        })
+    }
+
+    // The serializer of the codec of the content type
+    pub fn serializer<T, B>(
+        &self,
+        content_type: &str,
+    ) -> StdResult<Arc<dyn BodySerializer<T, B>>, UnsupportedMediaTypeError>
+    where
+        T: Serialize + 'static,
+        B: From<Bytes> + 'static,
+    {
+        Ok(Arc::new(CodecSerializer {
+            codec: self.resolve_or_err(content_type)?,
+        }))
+    }
+
+    /**
+    The deserializer choosing the codecs by the `Content-Type` of the responses.
+
+    # Arguments
+
+    * `default_content_type` - For the responses without `Content-Type`.
+
+    */
+    pub fn deserializer<R>(
+        self: &Arc<Self>,
+        default_content_type: &str,
+    ) -> StdResult<Arc<dyn BodyDeserializer<R>>, UnsupportedMediaTypeError>
+    where
+        R: DeserializeOwned + 'static,
+    {
+        self.resolve_or_err::<R>(default_content_type)?;
+        Ok(Arc::new(CodecDeserializer::new(
+            self.clone(),
+            default_content_type,
+        )))
+    }
+}
+
+// CodecSerializer Serialize the body by the codec (for put/post/patch etc)
+pub struct CodecSerializer<T> {
+    codec: ResolvedCodec<T>,
+}
+impl<T> CodecSerializer<T> {
+    pub fn new(codec: Arc<dyn Codec<T>>) -> Self {
+        CodecSerializer {
+            codec: ResolvedCodec::Typed(codec),
+        }
+    }
+    pub fn new_serde(codec: SerdeCodec) -> Self {
+        CodecSerializer {
+            codec: ResolvedCodec::Serde(codec),
+        }
+    }
+}
+impl<T: Serialize, B: From<Bytes>> BodySerializer<T, B> for CodecSerializer<T> {
+    fn encode(&self, origin: T) -> StdResult<B, Box<dyn StdError>> {
+        Ok(B::from(self.codec.encode(&origin)?))
+    }
+}
+
+/**
+`CodecDeserializer` Deserialize the body by the codec of the `Content-Type` of the response (for response)
+
+# Remarks
+
+* The responses without `Content-Type` are decoded by the codec of the `default_content_type`.
+* The responses of the unregistered media types fail with `UnsupportedMediaTypeError`.
+
+*/
+pub struct CodecDeserializer<R> {
+    pub registry: Arc<CodecRegistry>,
+    pub default_content_type: String,
+    _type: PhantomData<fn() -> R>,
+}
+impl<R> CodecDeserializer<R> {
+    pub fn new(registry: Arc<CodecRegistry>, default_content_type: impl Into<String>) -> Self {
+        CodecDeserializer {
+            registry,
+            default_content_type: default_content_type.into(),
+            _type: PhantomData,
+        }
+    }
+
+    fn decode_by(&self, content_type: &str, bytes: &Bytes) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        R: DeserializeOwned + 'static,
+    {
+        let codec = self.registry.resolve_or_err::<R>(content_type)?;
+        Ok(Box::new(codec.decode(bytes)?))
+    }
+}
+impl<R: DeserializeOwned + 'static> BodyDeserializer<R> for CodecDeserializer<R> {
+    fn decode(&self, bytes: &Bytes) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.decode_by(&self.default_content_type, bytes)
+    }
+    fn decode_response<'a>(
+        &'a self,
+        content_type: Option<String>,
+        stream: BodyStream<'a>,
+    ) -> DecodeFuture<'a, R>
+    where
+        R: 'a,
+    {
+        Box::pin(async move {
+            let bytes = collect_body_stream(stream).await?;
+            let content_type = content_type.unwrap_or_else(|| self.default_content_type.clone());
+            self.decode_by(&content_type, &bytes)
+        })
+    }
+}
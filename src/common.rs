@@ -1,15 +1,35 @@
-use std::collections::HashMap;
-// use std::error::Error as StdError;
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+use std::error::Error as StdError;
+use std::future::Future;
 use std::io;
-// use std::result::Result as StdResult;
+use std::iter::FromIterator;
+use std::net::SocketAddr;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::pin::Pin;
+use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, RwLock, Weak};
 // use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
 use std::thread;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime};
 
-use bytes::Bytes;
+use bytes::{Buf, Bytes};
 use futures::executor::block_on;
 // use futures::task::SpawnExt;
-use futures::{channel::mpsc as futureMpsc, SinkExt, Stream};
+use futures::channel::oneshot;
+use futures::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use futures::{
+    channel::mpsc as futureMpsc, stream, Sink, SinkExt, Stream, StreamExt, TryStreamExt,
+};
+use url::Url;
+
+use super::scrub::REDACTED;
+
+pub const DEFAULT_STREAM_BUFFER_SIZE: usize = 8 * 1024;
+pub const DEFAULT_STREAMING_BODY_CAPACITY: usize = 64 * 1024;
 
 /*
 `PathParam` Path params for API usages
@@ -20,6 +40,883 @@ pub type PathParam = HashMap<String, String>;
 */
 pub type QueryParam = HashMap<String, String>;
 
+/*
+`Headers` The binding independent headers, an ordered multimap of (name, value).
+
+The names are compared case-insensitively.
+The values of the sensitive names (e.g. `Authorization`) are masked in the `Debug` output.
+The values which aren't UTF-8 (obs-text) are kept by their raw bytes as well (by `append_bytes()`).
+*/
+#[derive(Clone, Default)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+    // The lowercase names of the sensitive headers
+    sensitive: Vec<String>,
+    // The raw bytes of the values which aren't UTF-8, by (the lowercase names, the lossy values)
+    opaque: Vec<(String, String, Vec<u8>)>,
+}
+impl Headers {
+    pub fn new() -> Self {
+        Headers::default()
+    }
+
+    // Set the value of the name (replacing all existing values of the name)
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        self.remove(&name);
+        self.entries.push((name, value.into()));
+    }
+    // Set the value of the name like `insert()`, and mark the name as sensitive
+    pub fn insert_sensitive(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        self.set_sensitive(&name, true);
+        self.insert(name, value);
+    }
+    // Add the value of the name (keeping the existing values of the name)
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+    // Add the value of the name by its raw bytes: the values which aren't UTF-8 (obs-text)
+    // are kept as the lossy `String`s, and their bytes are sent as they are by the bindings
+    pub fn append_bytes(&mut self, name: impl Into<String>, value: &[u8]) {
+        let name = name.into();
+        match std::str::from_utf8(value) {
+            Ok(v) => self.append(name, v),
+            Err(_) => {
+                let lossy = String::from_utf8_lossy(value).to_string();
+                self.opaque
+                    .push((name.to_ascii_lowercase(), lossy.clone(), value.to_vec()));
+                self.entries.push((name, lossy));
+            }
+        }
+    }
+    // Remove all values of the name, returns true if there were any
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+        self.opaque
+            .retain(|(k, _, _)| !k.eq_ignore_ascii_case(name));
+        len != self.entries.len()
+    }
+
+    // Get the first value of the name
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+    pub fn get_all(&self, name: &str) -> Vec<&str> {
+        self.iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+            .collect()
+    }
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+    // Get the raw bytes of the first value of the name
+    pub fn get_bytes(&self, name: &str) -> Option<&[u8]> {
+        self.get(name).map(|v| self.value_bytes(name, v))
+    }
+    // The raw bytes of the value of the name (the original ones of the values by `append_bytes()`)
+    pub fn value_bytes<'a>(&'a self, name: &str, value: &'a str) -> &'a [u8] {
+        self.opaque
+            .iter()
+            .find(|(k, v, _)| k.eq_ignore_ascii_case(name) && v == value)
+            .map_or(value.as_bytes(), |(_, _, bytes)| bytes.as_slice())
+    }
+
+    // Mark (or unmark) the name as sensitive, the mark is kept even if the values are removed
+    pub fn set_sensitive(&mut self, name: &str, sensitive: bool) {
+        let name = name.to_ascii_lowercase();
+        self.sensitive.retain(|k| k != &name);
+        if sensitive {
+            self.sensitive.push(name);
+        }
+    }
+    pub fn is_sensitive(&self, name: &str) -> bool {
+        self.sensitive.iter().any(|k| k.eq_ignore_ascii_case(name))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+impl std::fmt::Debug for Headers {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let entries = self
+            .iter()
+            .map(|(k, v)| (k, if self.is_sensitive(k) { REDACTED } else { v }))
+            .collect::<Vec<_>>();
+        f.debug_tuple("Headers").field(&entries).finish()
+    }
+}
+// The sensitive marks are not compared
+impl PartialEq for Headers {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries && self.opaque == other.opaque
+    }
+}
+impl From<Vec<(String, String)>> for Headers {
+    fn from(headers: Vec<(String, String)>) -> Self {
+        Headers {
+            entries: headers,
+            sensitive: Vec::new(),
+            opaque: Vec::new(),
+        }
+    }
+}
+impl<K: Into<String>, V: Into<String>> FromIterator<(K, V)> for Headers {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Headers::from(
+            iter.into_iter()
+                .map(|(k, v)| (k.into(), v.into()))
+                .collect::<Vec<_>>(),
+        )
+    }
+}
+impl IntoIterator for Headers {
+    type Item = (String, String);
+    type IntoIter = std::vec::IntoIter<(String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+/*
+`InvalidHeaderError` The header name/value has illegal characters
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct InvalidHeaderError {
+    details: String,
+}
+impl StdError for InvalidHeaderError {}
+impl InvalidHeaderError {
+    pub fn new(msg: impl Into<String>) -> InvalidHeaderError {
+        InvalidHeaderError {
+            details: msg.into(),
+        }
+    }
+}
+impl std::fmt::Display for InvalidHeaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "InvalidHeaderError: {}", self.details)
+    }
+}
+
+// The header names are tokens (RFC 7230)
+pub fn validate_header_name(name: &str) -> StdResult<(), InvalidHeaderError> {
+    if name.is_empty() {
+        return Err(InvalidHeaderError::new("empty header name"));
+    }
+    match name
+        .char_indices()
+        .find(|(_, c)| !(c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(*c)))
+    {
+        Some((i, c)) => Err(InvalidHeaderError::new(format!(
+            "invalid character {:?} at {} of the header name {:?}",
+            c, i, name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/**
+`HeaderValueBuilder` Build the header values with validation (visible ASCII, spaces and tabs only).
+
+# Arguments
+
+* `percent_encode` - Percent-encode the illegal characters (and `%`) as their UTF-8 bytes
+  instead of rejecting them (e.g. non-ASCII tokens).
+* `sensitive` - Mark the header as sensitive (masked in the `Debug` output and logs) when it's inserted.
+
+# Remarks
+
+* The errors tell the illegal characters and their positions (not the values, they might be secrets).
+
+*/
+#[derive(Clone, Default)]
+pub struct HeaderValueBuilder {
+    value: String,
+    percent_encode: bool,
+    sensitive: bool,
+}
+impl HeaderValueBuilder {
+    pub fn new(value: impl Into<String>) -> Self {
+        HeaderValueBuilder {
+            value: value.into(),
+            ..Default::default()
+        }
+    }
+    pub fn percent_encode(mut self, percent_encode: bool) -> Self {
+        self.percent_encode = percent_encode;
+        self
+    }
+    pub fn sensitive(mut self, sensitive: bool) -> Self {
+        self.sensitive = sensitive;
+        self
+    }
+
+    fn is_legal(c: char) -> bool {
+        c == '\t' || (' '..='~').contains(&c)
+    }
+
+    // Validate (or percent-encode) the value
+    pub fn build(self) -> StdResult<String, InvalidHeaderError> {
+        if self.percent_encode {
+            let mut encoded = String::with_capacity(self.value.len());
+            for c in self.value.chars() {
+                if Self::is_legal(c) && c != '%' {
+                    encoded.push(c);
+                } else {
+                    let mut buffer = [0; 4];
+                    for b in c.encode_utf8(&mut buffer).as_bytes() {
+                        encoded.push_str(&format!("%{:02X}", b));
+                    }
+                }
+            }
+            return Ok(encoded);
+        }
+
+        match self.value.char_indices().find(|(_, c)| !Self::is_legal(*c)) {
+            Some((i, c)) => Err(InvalidHeaderError::new(format!(
+                "invalid character {:?} at {} of the header value (percent_encode() could encode it)",
+                c, i
+            ))),
+            None => Ok(self.value),
+        }
+    }
+    // Validate the name & the value, then insert it into the `headers` (replacing the existing values)
+    pub fn insert_into(
+        self,
+        headers: &mut Headers,
+        name: impl Into<String>,
+    ) -> StdResult<(), InvalidHeaderError> {
+        let name = name.into();
+        validate_header_name(&name)?;
+        let sensitive = self.sensitive;
+        let value = self.build()?;
+        if sensitive {
+            headers.insert_sensitive(name, value);
+        } else {
+            headers.insert(name, value);
+        }
+        Ok(())
+    }
+}
+
+/**
+`RequestHeaders` The binding independent access to the headers of requests.
+
+It's implemented by the request types of bindings,
+so that interceptors for headers could be written once (`InterceptorFunc::new_for_headers()`).
+*/
+pub trait RequestHeaders {
+    fn get_headers(&self) -> Headers;
+    fn set_headers(&mut self, headers: Headers) -> StdResult<(), Box<dyn StdError>>;
+}
+
+/**
+`RequestSummary` The binding independent summary of requests,
+used to format the requests for logging (`RequestFormatter`).
+*/
+pub trait RequestSummary: RequestHeaders {
+    fn get_method(&self) -> String;
+    fn get_url(&self) -> String;
+    // The buffered body (`None` if the body is streamed or absent)
+    fn get_body(&self) -> Option<Bytes> {
+        None
+    }
+}
+
+// Set the `Authorization` header (validated, and marked as sensitive)
+pub fn add_header_authentication(
+    mut headers: Headers,
+    token: impl Into<String>,
+) -> StdResult<Headers, Box<dyn StdError>> {
+    HeaderValueBuilder::new(token)
+        .sensitive(true)
+        .insert_into(&mut headers, "Authorization")?;
+
+    Ok(headers)
+}
+
+pub fn add_header_authentication_bearer(
+    headers: Headers,
+    token: impl Into<String>,
+) -> StdResult<Headers, Box<dyn StdError>> {
+    add_header_authentication(headers, "Bearer ".to_string() + &token.into())
+}
+
+/**
+`UrlJoinStrategy` How the relative urls of the APIs are joined with the base url.
+
+e.g. base `https://host/api/v2` with the relative url `/products`:
+
+* `Standard` - `Url::join()` (RFC 3986, the default): `https://host/products` (the base path is dropped).
+* `StrictAppend` - Always appended to the base path: `https://host/api/v2/products`,
+  the absolute urls (`https://...`, `//host/...`) and the `..` segments are rejected.
+* `RootRelative` - Resolved from the root of the base url: `https://host/products`,
+  the relative urls must start with `/` (`products` would silently replace the last base segment by `Standard`),
+  the absolute urls are rejected.
+* `AbsoluteAllowed` - The same as `StrictAppend`, but the absolute urls (with schemes) are used as they are.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UrlJoinStrategy {
+    #[default]
+    Standard,
+    StrictAppend,
+    RootRelative,
+    AbsoluteAllowed,
+}
+
+/*
+`UrlJoinError` The relative url is rejected by the `UrlJoinStrategy`
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrlJoinError {
+    details: String,
+}
+impl StdError for UrlJoinError {}
+impl UrlJoinError {
+    pub fn new(msg: impl Into<String>) -> UrlJoinError {
+        UrlJoinError {
+            details: msg.into(),
+        }
+    }
+}
+impl std::fmt::Display for UrlJoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "UrlJoinError: {}", self.details)
+    }
+}
+
+// Append the query param to the query of the url (keeping the existing params, e.g. of the relative url)
+pub(crate) fn append_query_param(url: &mut Url, key: &str, value: &str) {
+    let query = match url.query() {
+        Some(query) if !query.is_empty() => format!("{}&{}={}", query, key, value),
+        _ => format!("{}={}", key, value),
+    };
+    url.set_query(Some(&query));
+}
+
+/**
+Join the `relative_url` with the `base_url` by the `strategy`.
+
+# Arguments
+
+* `base_url` - The base url of the APIs.
+* `relative_url` - The relative url (path params applied, with the optional query/fragment).
+* `strategy` - The `UrlJoinStrategy`.
+
+*/
+pub fn join_url(
+    base_url: &Url,
+    relative_url: &str,
+    strategy: UrlJoinStrategy,
+) -> StdResult<Url, Box<dyn StdError>> {
+    let is_absolute = Url::parse(relative_url).is_ok();
+    let is_network_path = relative_url.starts_with("//");
+    let reject = |reason: &str| -> StdResult<Url, Box<dyn StdError>> {
+        Err(Box::new(UrlJoinError::new(format!(
+            "{:?} doesn't allow {}: {}",
+            strategy, reason, relative_url
+        ))))
+    };
+
+    match strategy {
+        UrlJoinStrategy::Standard => Ok(base_url.join(relative_url)?),
+        UrlJoinStrategy::RootRelative => {
+            if is_absolute || is_network_path {
+                return reject("absolute urls");
+            }
+            if !relative_url.starts_with('/') {
+                return reject("urls without the leading /");
+            }
+            Ok(base_url.join(relative_url)?)
+        }
+        UrlJoinStrategy::StrictAppend | UrlJoinStrategy::AbsoluteAllowed => {
+            if is_absolute && strategy == UrlJoinStrategy::AbsoluteAllowed {
+                return Ok(Url::parse(relative_url)?);
+            }
+            if is_absolute || is_network_path {
+                return reject("absolute urls");
+            }
+            let path = relative_url.split(['?', '#']).next().unwrap_or_default();
+            // `\` is a separator of the special schemes, and `Url::join()` resolves the encoded dots too
+            if path.split(['/', '\\']).any(is_double_dot_segment) {
+                return reject("the .. segments");
+            }
+
+            let relative_url = relative_url.trim_start_matches('/');
+            if relative_url.is_empty() {
+                return Ok(base_url.clone());
+            }
+            let mut base_url = base_url.clone();
+            if !base_url.path().ends_with('/') {
+                let path = base_url.path().to_string() + "/";
+                base_url.set_path(&path);
+            }
+            Ok(base_url.join(relative_url)?)
+        }
+    }
+}
+
+// Whether it's a `..` segment, with the dots percent-encoded (`%2e`, case-insensitively) or not
+fn is_double_dot_segment(segment: &str) -> bool {
+    segment.to_ascii_lowercase().replace("%2e", ".") == ".."
+}
+
+/**
+`UrlNormalization` How the final urls are normalized before they're sent (nothing by default).
+
+e.g. base `https://HOST:443/api/` with the relative url `/v2//products/` (`StrictAppend`), and the query `b=2&a=1`:
+
+* `collapse_slashes` - The duplicate slashes of the path are collapsed: `https://host/api/v2/products/`.
+* `sort_query` - The query parameters are sorted by names (stable): `?a=1&b=2`.
+
+# Remarks
+
+* The hosts are always lowercased, and the default ports are always removed by `Url`
+  (for the special schemes, e.g. `http`/`https`/`ws`/`wss`).
+* The absolute urls called verbatim (`AbsoluteUrl`) aren't normalized.
+
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UrlNormalization {
+    pub collapse_slashes: bool,
+    pub sort_query: bool,
+}
+impl UrlNormalization {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // All of the normalizations
+    pub fn all() -> Self {
+        UrlNormalization {
+            collapse_slashes: true,
+            sort_query: true,
+        }
+    }
+    pub fn with_collapsed_slashes(mut self) -> Self {
+        self.collapse_slashes = true;
+        self
+    }
+    pub fn with_sorted_query(mut self) -> Self {
+        self.sort_query = true;
+        self
+    }
+
+    pub fn normalize(&self, url: &mut Url) {
+        if self.collapse_slashes && url.path().contains("//") {
+            let mut path = String::with_capacity(url.path().len());
+            for c in url.path().chars() {
+                if !(c == '/' && path.ends_with('/')) {
+                    path.push(c);
+                }
+            }
+            url.set_path(&path);
+        }
+        if self.sort_query && url.query().is_some() {
+            let mut query_pairs: Vec<(String, String)> = url
+                .query_pairs()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect();
+            query_pairs.sort_by(|a, b| a.0.cmp(&b.0));
+            if query_pairs.is_empty() {
+                url.set_query(None);
+            } else {
+                url.query_pairs_mut().clear().extend_pairs(query_pairs);
+            }
+        }
+    }
+}
+
+/**
+`Versioning` How the API version is applied to the requests.
+
+e.g. the relative url `/products` with the `Content-Type` `application/json`:
+
+* `None` - Not applied (the default).
+* `PathPrefix` - The path prefix segment: `/v2/products` (the absolute urls are kept as they are).
+* `Header` - The header of the version: e.g. `Accept-Version: 2`.
+* `MediaTypeParameter` - The parameter of the `Content-Type` and the `Accept` (if they're sent):
+  `application/json; version=2` (the multipart ones are kept as they are).
+
+# Remarks
+
+* It's set for all calls of a service by `set_versioning()`,
+  and overridden per API by `ApiBuilder::versioning()` (e.g. `Versioning::None` for the unversioned ones).
+
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Versioning {
+    #[default]
+    None,
+    PathPrefix(String),
+    Header {
+        name: String,
+        value: String,
+    },
+    MediaTypeParameter {
+        name: String,
+        value: String,
+    },
+}
+impl Versioning {
+    pub fn path_prefix(segment: impl Into<String>) -> Self {
+        Versioning::PathPrefix(segment.into())
+    }
+    pub fn header(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Versioning::Header {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+    pub fn media_type_parameter(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Versioning::MediaTypeParameter {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+
+    // Prefix the relative url by the segment (for `PathPrefix`)
+    pub fn apply_to_url(&self, relative_url: &str) -> String {
+        let segment = match self {
+            Versioning::PathPrefix(segment) => segment.trim_matches('/'),
+            _ => return relative_url.to_string(),
+        };
+        if segment.is_empty() || Url::parse(relative_url).is_ok() || relative_url.starts_with("//")
+        {
+            return relative_url.to_string();
+        }
+        match relative_url.strip_prefix('/') {
+            Some(path) => format!("/{}/{}", segment, path),
+            None => format!("{}/{}", segment, relative_url),
+        }
+    }
+    // Add the parameter to the media type (for `MediaTypeParameter`)
+    pub fn apply_to_media_type(&self, media_type: &str) -> String {
+        match self {
+            Versioning::MediaTypeParameter { name, value }
+                if !media_type.is_empty() && !media_type.starts_with("multipart/") =>
+            {
+                format!("{}; {}={}", media_type, name, value)
+            }
+            _ => media_type.to_string(),
+        }
+    }
+    // The header of the version (for `Header`)
+    pub fn get_header(&self) -> Option<(&str, &str)> {
+        match self {
+            Versioning::Header { name, value } => Some((name, value)),
+            _ => None,
+        }
+    }
+}
+
+/*
+`TemplateVariableError` The placeholder of the relative url isn't resolved by the `TemplateVariables`
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateVariableError {
+    details: String,
+    pub name: String,
+}
+impl StdError for TemplateVariableError {}
+impl TemplateVariableError {
+    pub fn new(msg: impl Into<String>, name: impl Into<String>) -> TemplateVariableError {
+        TemplateVariableError {
+            details: msg.into(),
+            name: name.into(),
+        }
+    }
+}
+impl std::fmt::Display for TemplateVariableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "TemplateVariableError: {}", self.details)
+    }
+}
+
+/**
+`TemplateVariables` The variables of the `{namespace.name}` placeholders of the relative urls,
+resolved at call time (e.g. the region-scoped endpoints `/{env.REGION}/products/{id}`).
+
+A placeholder is resolved by:
+
+* The value set by `set()` of the same name (e.g. `set("env.REGION", "eu")`, or `set("ctx.tenant", ...)`).
+* Otherwise `{env.NAME}` by the environment variable `NAME` (read at each call).
+
+# Remarks
+
+* It's set for all calls of a service by `set_template_variables()`,
+  and the scoped ones (by `with_extensions()`) are merged over it for the calls of the scope.
+* The placeholders without `.` are the path params (applied after the variables).
+* The calls fail with `TemplateVariableError` if any placeholder isn't resolved (instead of sending it verbatim).
+
+*/
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TemplateVariables {
+    variables: HashMap<String, String>,
+}
+impl TemplateVariables {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(|v| v.as_str())
+    }
+    // The variables of `other` take precedence over the ones of this
+    pub fn merge(&self, other: &TemplateVariables) -> TemplateVariables {
+        let mut variables = self.variables.clone();
+        variables.extend(other.variables.clone());
+        TemplateVariables { variables }
+    }
+
+    // Resolve the variable by the values set, or the environment variables (`env.NAME`)
+    pub fn resolve(&self, name: &str) -> Option<String> {
+        match self.get(name) {
+            Some(value) => Some(value.to_string()),
+            None => std::env::var(name.strip_prefix("env.")?).ok(),
+        }
+    }
+    // Replace the `{namespace.name}` placeholders of the relative url (the other ones are kept)
+    pub fn apply_to_url(&self, relative_url: &str) -> StdResult<String, TemplateVariableError> {
+        let mut url = String::with_capacity(relative_url.len());
+        let mut rest = relative_url;
+        while let Some(start) = rest.find('{') {
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let name = &rest[start + 1..end];
+            url += &rest[..start];
+            if name.contains('.') {
+                url += &self.resolve(name).ok_or_else(|| {
+                    TemplateVariableError::new(
+                        format!("`{{{}}}` of `{}` isn't resolved", name, relative_url),
+                        name,
+                    )
+                })?;
+            } else {
+                url += &rest[start..=end];
+            }
+            rest = &rest[end + 1..];
+        }
+        url += rest;
+        Ok(url)
+    }
+}
+
+// The max length of the prefixes read by `sniff_content_type()`
+#[cfg(feature = "sniff")]
+pub const SNIFF_LENGTH: usize = 16;
+
+// The prefixes (and the magic bytes at the offsets) of the content types
+#[cfg(feature = "sniff")]
+type MagicBytes = (&'static [u8], Option<(usize, &'static [u8])>, &'static str);
+#[cfg(feature = "sniff")]
+const MAGIC_BYTES: &[MagicBytes] = &[
+    (b"\x89PNG\r\n\x1a\n", None, "image/png"),
+    (b"\xff\xd8\xff", None, "image/jpeg"),
+    (b"GIF87a", None, "image/gif"),
+    (b"GIF89a", None, "image/gif"),
+    (b"RIFF", Some((8, b"WEBP")), "image/webp"),
+    (b"BM", None, "image/bmp"),
+    (b"II*\x00", None, "image/tiff"),
+    (b"MM\x00*", None, "image/tiff"),
+    (b"%PDF-", None, "application/pdf"),
+    (b"PK\x03\x04", None, "application/zip"),
+    (b"\x1f\x8b", None, "application/gzip"),
+    (b"\x00asm", None, "application/wasm"),
+    (b"RIFF", Some((8, b"WAVE")), "audio/wav"),
+    (b"ID3", None, "audio/mpeg"),
+    (b"OggS", None, "audio/ogg"),
+    (b"", Some((4, b"ftyp")), "video/mp4"),
+];
+
+/**
+Detect the content type of the body (e.g. the uploaded files/bytes) by its magic bytes.
+
+# Remarks
+
+* Only the first `SNIFF_LENGTH` bytes are read.
+* The common binary formats are detected (images/PDF/archives/audio/video),
+  and `None` is returned for the others (e.g. the texts), for the callers' default (e.g. `application/octet-stream`).
+* It's a pure function, and it doesn't panic on any input.
+
+*/
+#[cfg(feature = "sniff")]
+pub fn sniff_content_type(bytes: &[u8]) -> Option<&'static str> {
+    MAGIC_BYTES
+        .iter()
+        .find(|(prefix, magic, _)| {
+            bytes.starts_with(prefix)
+                && magic.is_none_or(|(offset, magic)| {
+                    bytes.get(offset..offset + magic.len()) == Some(magic)
+                })
+        })
+        .map(|(_, _, content_type)| *content_type)
+}
+
+// The max length (bytes) of the file names by `sanitize_filename()` (of the common file systems)
+pub const MAX_FILENAME_LENGTH: usize = 255;
+
+/**
+Parse the file name of the `Content-Disposition` header (RFC 6266) of the responses (e.g. the downloads),
+sanitized by `sanitize_filename()` for saving it as a local file.
+
+e.g. `attachment; filename="report.pdf"`, or `attachment; filename*=UTF-8''na%C3%AFve%20r%C3%A9sum%C3%A9.pdf`.
+
+# Remarks
+
+* `filename*` (the RFC 5987 ext-value of the `UTF-8`/`ISO-8859-1` charsets) takes precedence over `filename`,
+  and the invalid one falls back to `filename`.
+* Return `None` if there's no file name (or nothing is left after sanitizing it).
+* It's a pure function (for fuzzing), and it doesn't panic on any input.
+
+*/
+pub fn parse_content_disposition_filename(content_disposition: &str) -> Option<String> {
+    let mut filename = None;
+    let mut filename_ext = None;
+    // Skip the disposition type
+    let mut rest = content_disposition.split_once(';')?.1;
+    loop {
+        let (name, after_name) = match rest.find([';', '=']) {
+            // The parameter without the value
+            Some(i) if rest[i..].starts_with(';') => {
+                rest = &rest[i + 1..];
+                continue;
+            }
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => break,
+        };
+        let name = name.trim().to_ascii_lowercase();
+        let after_name = after_name.trim_start();
+        let (value, after_value) = match after_name.strip_prefix('"') {
+            // The quoted-string with the escapes
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((i, c)) = chars.next() {
+                    match c {
+                        '\\' => value.extend(chars.next().map(|(_, c)| c)),
+                        '"' => {
+                            end = i + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                let after_value = &quoted[end..];
+                let after_value = after_value.split_once(';').map_or("", |v| v.1);
+                (value, after_value)
+            }
+            None => match after_name.split_once(';') {
+                Some((value, after_value)) => (value.trim().to_string(), after_value),
+                None => (after_name.trim().to_string(), ""),
+            },
+        };
+        match name.as_str() {
+            "filename" => filename = Some(value),
+            "filename*" => filename_ext = decode_ext_value(&value),
+            _ => {}
+        }
+        rest = after_value;
+    }
+
+    filename_ext
+        .and_then(|v| sanitize_filename(&v))
+        .or_else(|| sanitize_filename(&filename?))
+}
+
+// Decode the RFC 5987 ext-value (`charset'language'percent-encoded`)
+fn decode_ext_value(ext_value: &str) -> Option<String> {
+    let mut parts = ext_value.splitn(3, '\'');
+    let charset = parts.next()?;
+    let _language = parts.next()?;
+    let encoded = parts.next()?.as_bytes();
+
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        if encoded[i] == b'%' {
+            let hex = std::str::from_utf8(encoded.get(i + 1..i + 3)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            bytes.push(encoded[i]);
+            i += 1;
+        }
+    }
+    if charset.eq_ignore_ascii_case("UTF-8") {
+        String::from_utf8(bytes).ok()
+    } else if charset.eq_ignore_ascii_case("ISO-8859-1") {
+        Some(bytes.into_iter().map(char::from).collect())
+    } else {
+        None
+    }
+}
+
+/**
+Sanitize the (untrusted) file name for saving it as a local file.
+
+# Remarks
+
+* Only the last segment of the paths is kept (by `/` and `\`), so it can't escape the directory.
+* The control characters and the reserved ones of Windows (`<>:"|?*`) are replaced by `_`,
+  and the leading dots/spaces and the trailing dots/spaces are trimmed (no hidden files, `.` or `..`).
+* It's truncated to `MAX_FILENAME_LENGTH` bytes (at the char boundaries, keeping the extension if possible).
+* Return `None` if nothing is left.
+* It's a pure function (for fuzzing), and it doesn't panic on any input.
+
+*/
+pub fn sanitize_filename(filename: &str) -> Option<String> {
+    let filename = filename.rsplit(['/', '\\']).next()?;
+    let filename: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_control() || "<>:\"|?*".contains(c) {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let filename = filename
+        .trim_start_matches(['.', ' '])
+        .trim_end_matches(['.', ' ']);
+    if filename.is_empty() {
+        return None;
+    }
+    if filename.len() <= MAX_FILENAME_LENGTH {
+        return Some(filename.to_string());
+    }
+
+    // Keep the (short) extension
+    let extension = match filename.rfind('.') {
+        Some(i) if filename.len() - i <= 16 => &filename[i..],
+        _ => "",
+    };
+    let mut end = MAX_FILENAME_LENGTH - extension.len();
+    while !filename.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(filename[..end].to_string() + extension)
+}
+
 #[macro_export]
 macro_rules! path_param {
     ($( $key: expr => $val: expr ),*) => {{
@@ -96,14 +993,4230 @@ where
     }
 }
 // */
-pub fn make_stream<T>() -> (futureMpsc::Sender<T>, impl Stream<Item = T>) {
-    futureMpsc::channel(10)
+/*
+`AsyncWriteForStream` The non-blocking version of `WriteForStream`,
+it waits(by the task waker) until the receiver side is ready, instead of blocking the thread.
+*/
+pub struct AsyncWriteForStream(pub futureMpsc::Sender<Bytes>);
+
+impl AsyncWrite for AsyncWriteForStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        match this.0.poll_ready(cx) {
+            Poll::Ready(Ok(())) => {}
+            Poll::Ready(Err(e)) => {
+                return Poll::Ready(Err(io::Error::new(io::ErrorKind::BrokenPipe, e)))
+            }
+            Poll::Pending => return Poll::Pending,
+        };
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        match this.0.start_send(Bytes::copy_from_slice(buf)) {
+            Ok(()) => Poll::Ready(Ok(buf.len())),
+            Err(e) => Poll::Ready(Err(io::Error::new(io::ErrorKind::BrokenPipe, e))),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().0)
+            .poll_flush(cx)
+            .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.get_mut().0.close_channel();
+        Poll::Ready(Ok(()))
+    }
 }
 
-pub fn generate_id() -> String {
-    let since_the_epoch = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
+/**
+Convert the given `Stream` of `Bytes` into an `AsyncRead`.
+
+# Arguments
+
+* `stream` - The given `Stream`, errors are converted into `io::Error`.
+
+*/
+pub fn stream_to_async_read<S, E>(stream: S) -> impl AsyncRead
+where
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    let stream: Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>> =
+        Box::pin(stream.map(|item| item.map_err(io::Error::other)));
+    stream.into_async_read()
+}
+
+/**
+Convert the given `AsyncRead` into a `Stream` of `Bytes`.
+
+# Arguments
+
+* `reader` - The given `AsyncRead`.
+* `buffer_size` - The max size of each `Bytes` (`DEFAULT_STREAM_BUFFER_SIZE` if it's 0).
+
+*/
+pub fn async_read_to_stream<R>(
+    reader: R,
+    buffer_size: usize,
+) -> impl Stream<Item = io::Result<Bytes>>
+where
+    R: AsyncRead + Unpin,
+{
+    let buffer_size = if buffer_size > 0 {
+        buffer_size
+    } else {
+        DEFAULT_STREAM_BUFFER_SIZE
+    };
+
+    stream::unfold(Some(reader), move |reader| async move {
+        let mut reader = reader?;
+        let mut buf = vec![0; buffer_size];
+        match reader.read(&mut buf).await {
+            Ok(0) => None,
+            Ok(len) => {
+                buf.truncate(len);
+                Some((Ok(Bytes::from(buf)), Some(reader)))
+            }
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+/*
+`BodyProducerError` The producer of a streaming body failed (e.g. `write_formdata` failed),
+the request is aborted instead of sending a truncated body.
+*/
+#[derive(Debug, Clone)]
+pub struct BodyProducerError {
+    details: String,
+    // The typed error of the producer (shared by the clones)
+    source: Option<Arc<dyn StdError + Send + Sync>>,
+}
+impl StdError for BodyProducerError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source
+            .as_deref()
+            .map(|e| e as &(dyn StdError + 'static))
+    }
+}
+impl BodyProducerError {
+    pub fn new(msg: impl Into<String>) -> BodyProducerError {
+        BodyProducerError {
+            details: msg.into(),
+            source: None,
+        }
+    }
+    pub fn new_with_source(
+        msg: impl Into<String>,
+        source: impl Into<Box<dyn StdError + Send + Sync>>,
+    ) -> BodyProducerError {
+        BodyProducerError {
+            details: msg.into(),
+            source: Some(Arc::from(source.into())),
+        }
+    }
+
+    /**
+    Find the `BodyProducerError` in the source chain of the given error
+    (including the inner errors of `io::Error`).
+
+    # Arguments
+
+    * `err` - The given error (e.g. the error of the http client).
+
+    */
+    pub fn find_in<'a>(err: &'a (dyn StdError + 'static)) -> Option<&'a BodyProducerError> {
+        let mut current = Some(err);
+        while let Some(err) = current {
+            if let Some(found) = err.downcast_ref::<BodyProducerError>() {
+                return Some(found);
+            }
+            if let Some(inner) = err.downcast_ref::<io::Error>().and_then(|e| e.get_ref()) {
+                if let Some(found) = inner.downcast_ref::<BodyProducerError>() {
+                    return Some(found);
+                }
+            }
+            current = err.source();
+        }
+        None
+    }
+}
+impl std::fmt::Display for BodyProducerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "BodyProducerError: {}", self.details)
+    }
+}
+
+struct StreamingBodyState {
+    queue: VecDeque<Bytes>,
+    queued_len: usize,
+    capacity: usize,
+    closed: bool,
+    aborted: Option<BodyProducerError>,
+    // The error has been yielded by the Stream side
+    finished: bool,
+    reader_dropped: bool,
+    waker: Option<Waker>,
+}
+
+struct StreamingBodyShared {
+    state: Mutex<StreamingBodyState>,
+    condvar: Condvar,
+}
+impl StreamingBodyShared {
+    fn notify(&self, state: &mut StreamingBodyState) {
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.condvar.notify_all();
+    }
+}
+
+/**
+`StreamingBodyWriter` The producer side of a streaming request body.
+
+It's made by `StreamingBodyWriter::channel()` with the consumer side `StreamingBodyReader`,
+the producer writes the body by `io::Write` (usually on another thread),
+and the consumer reads it by `Stream` (for hyper) or `io::Read` (for ureq).
+
+# Remarks
 
-    format!("{:?}{:?}", thread::current().id(), since_the_epoch)
+* Backpressure: `write()` blocks while the buffered data exceeds the capacity,
+  until the consumer reads them.
+* `close()`: The body is complete, the consumer gets the rest buffered data and then the end.
+  Dropping the writer closes it as well (or aborts it when the thread is panicking).
+* `abort()`: The body is broken, the buffered data are discarded and the consumer gets
+  the `BodyProducerError`, so the request fails instead of sending a truncated body.
+* Error propagation: `write()` fails with `BrokenPipe` after `close()`/`abort()`,
+  or when the consumer has been dropped(e.g. the request has been cancelled).
+
+*/
+pub struct StreamingBodyWriter {
+    shared: Arc<StreamingBodyShared>,
+}
+
+/**
+`StreamingBodyReader` The consumer side of a streaming request body,
+see `StreamingBodyWriter` for details.
+*/
+pub struct StreamingBodyReader {
+    shared: Arc<StreamingBodyShared>,
+}
+
+impl StreamingBodyWriter {
+    /**
+    Make a pair of `StreamingBodyWriter`/`StreamingBodyReader`.
+
+    # Arguments
+
+    * `capacity` - The max buffered bytes before `write()` blocks (`DEFAULT_STREAMING_BODY_CAPACITY` if it's 0).
+
+    */
+    pub fn channel(capacity: usize) -> (StreamingBodyWriter, StreamingBodyReader) {
+        let shared = Arc::new(StreamingBodyShared {
+            state: Mutex::new(StreamingBodyState {
+                queue: VecDeque::new(),
+                queued_len: 0,
+                capacity: if capacity > 0 {
+                    capacity
+                } else {
+                    DEFAULT_STREAMING_BODY_CAPACITY
+                },
+                closed: false,
+                aborted: None,
+                finished: false,
+                reader_dropped: false,
+                waker: None,
+            }),
+            condvar: Condvar::new(),
+        });
+
+        (
+            StreamingBodyWriter {
+                shared: shared.clone(),
+            },
+            StreamingBodyReader { shared },
+        )
+    }
+
+    /**
+    Mark the body as complete.
+    */
+    pub fn close(&self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        self.shared.notify(&mut state);
+    }
+
+    /**
+    Mark the body as broken, the consumer gets the error instead of the rest data.
+
+    # Arguments
+
+    * `reason` - The reason of the abort.
+
+    */
+    pub fn abort(&self, reason: impl Into<String>) {
+        self.abort_with_error(BodyProducerError::new(reason));
+    }
+
+    /**
+    Mark the body as broken by the typed error of the producer
+    (it's the `source()` of the `BodyProducerError` of the consumer).
+
+    # Arguments
+
+    * `reason` - The reason of the abort.
+    * `source` - The error of the producer (e.g. the one of `write_formdata`).
+
+    */
+    pub fn abort_with_source(
+        &self,
+        reason: impl Into<String>,
+        source: impl Into<Box<dyn StdError + Send + Sync>>,
+    ) {
+        self.abort_with_error(BodyProducerError::new_with_source(reason, source));
+    }
+
+    fn abort_with_error(&self, error: BodyProducerError) {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closed || state.aborted.is_some() {
+            // It has been completed (or aborted), nothing to abort
+            return;
+        }
+        state.queue.clear();
+        state.queued_len = 0;
+        state.aborted = Some(error);
+        self.shared.notify(&mut state);
+    }
+
+    pub fn is_closed(&self) -> bool {
+        let state = self.shared.state.lock().unwrap();
+        state.closed || state.aborted.is_some() || state.reader_dropped
+    }
+}
+
+impl io::Write for StreamingBodyWriter {
+    fn write(&mut self, d: &[u8]) -> io::Result<usize> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if state.reader_dropped {
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "StreamingBodyWriter: the reader has been dropped",
+                ));
+            }
+            if state.closed || state.aborted.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::BrokenPipe,
+                    "StreamingBodyWriter: the body has been closed",
+                ));
+            }
+            if d.is_empty() {
+                return Ok(0);
+            }
+            if state.queued_len == 0 || state.queued_len + d.len() <= state.capacity {
+                break;
+            }
+            state = self.shared.condvar.wait(state).unwrap();
+        }
+
+        state.queue.push_back(Bytes::copy_from_slice(d));
+        state.queued_len += d.len();
+        self.shared.notify(&mut state);
+        Ok(d.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for StreamingBodyWriter {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.abort("StreamingBodyWriter: the producer panicked");
+        } else {
+            self.close();
+        }
+    }
+}
+
+impl StreamingBodyReader {
+    // Take the next chunk (at most max_len bytes), or the end state
+    fn take_next(state: &mut StreamingBodyState, max_len: usize) -> Option<Bytes> {
+        let front = state.queue.front_mut()?;
+        let chunk = if front.len() > max_len {
+            front.split_to(max_len)
+        } else {
+            state.queue.pop_front().unwrap()
+        };
+        state.queued_len -= chunk.len();
+        Some(chunk)
+    }
+}
+
+impl Stream for StreamingBodyReader {
+    type Item = Result<Bytes, Box<dyn StdError + Send + Sync>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let shared = self.shared.clone();
+        let mut state = shared.state.lock().unwrap();
+        if state.finished {
+            return Poll::Ready(None);
+        }
+        if let Some(error) = state.aborted.clone() {
+            state.finished = true;
+            return Poll::Ready(Some(Err(Box::new(error))));
+        }
+        if let Some(chunk) = Self::take_next(&mut state, usize::MAX) {
+            shared.condvar.notify_all();
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+        if state.closed {
+            return Poll::Ready(None);
+        }
+
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl io::Read for StreamingBodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(error) = state.aborted.clone() {
+                return Err(io::Error::other(error));
+            }
+            if let Some(mut chunk) = Self::take_next(&mut state, buf.len()) {
+                self.shared.condvar.notify_all();
+                let len = chunk.len();
+                chunk.copy_to_slice(&mut buf[..len]);
+                return Ok(len);
+            }
+            if state.closed {
+                return Ok(0);
+            }
+            state = self.shared.condvar.wait(state).unwrap();
+        }
+    }
+}
+
+impl Drop for StreamingBodyReader {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.reader_dropped = true;
+        self.shared.notify(&mut state);
+    }
+}
+
+/*
+`RequestTiming` The timing breakdown of a call.
+
+Each phase is `None` if it's not obtainable
+(e.g. `dns`/`connect`/`tls` of a reused pooled connection, or `total` before the body is fully read).
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestTiming {
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub tls: Option<Duration>,
+    // Time to the first byte (the response head)
+    pub ttfb: Option<Duration>,
+    pub total: Option<Duration>,
+    // The TLS session of the connection (if the connector attaches `TlsInfo`)
+    pub tls_info: Option<TlsInfo>,
+}
+
+/**
+`TlsInfo` The metadata of the TLS session of a connection, attached to the connections by the TLS connectors
+(via `Connected::extra()` of hyper, e.g. by `tls_info_of_rustls()` of the `dev_tls` feature).
+
+# Remarks
+
+* It's in the extensions of all the responses served by the connection (unlike `ConnectTiming`).
+* `peer_certificates` are DER, the end-entity one first (as sent by the server).
+* The OCSP/CRL statuses aren't checked here, they're up to the consumers (by the certificates).
+
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TlsInfo {
+    // e.g. "TLSv1_3"
+    pub protocol: Option<String>,
+    // e.g. "TLS13_AES_128_GCM_SHA256"
+    pub cipher_suite: Option<String>,
+    // The negotiated ALPN protocol, e.g. "h2"
+    pub alpn_protocol: Option<String>,
+    pub peer_certificates: Vec<Vec<u8>>,
+}
+
+/*
+`ResponseTlsInfo` The extension receiving the `TlsInfo` of the responses (set by the bindings),
+scoped by `with_tls_info()`.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ResponseTlsInfo(Arc<Mutex<Option<TlsInfo>>>);
+impl ResponseTlsInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set(&self, tls_info: Option<TlsInfo>) {
+        *self.0.lock().unwrap() = tls_info;
+    }
+    pub fn get(&self) -> Option<TlsInfo> {
+        self.0.lock().unwrap().clone()
+    }
+
+    // Set the `TlsInfo` of the response to the `ResponseTlsInfo` of the request (if any)
+    pub fn record(options: &RequestOptions, tls_info: Option<&TlsInfo>) {
+        if let Some(response_tls_info) = options.extensions.get::<ResponseTlsInfo>() {
+            response_tls_info.set(tls_info.cloned());
+        }
+    }
+}
+
+/*
+`WithTlsInfo` The output of the calls by `with_tls_info()` with the `TlsInfo` of the response
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithTlsInfo<T> {
+    pub value: T,
+    pub tls_info: Option<TlsInfo>,
+}
+
+/**
+Run the calls of the `future` and get the `TlsInfo` of the response as well
+(e.g. to log or verify the peer certificates).
+
+# Remarks
+
+* The `tls_info` is the one of the last response in the scope
+  (`None` for the plain HTTP connections, or the connectors not attaching `TlsInfo`).
+
+*/
+pub async fn with_tls_info<F: Future>(future: F) -> WithTlsInfo<F::Output> {
+    let response_tls_info = ResponseTlsInfo::new();
+    let mut extensions = Extensions::new();
+    extensions.insert(response_tls_info.clone());
+    let value = with_extensions(extensions, future).await;
+    WithTlsInfo {
+        value,
+        tls_info: response_tls_info.get(),
+    }
+}
+
+/*
+`ResponseTiming` The extension receiving the `RequestTiming` of the responses (set by the bindings),
+scoped by `with_timing()`.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ResponseTiming(Arc<Mutex<Option<RequestTiming>>>);
+impl ResponseTiming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set(&self, timing: Option<RequestTiming>) {
+        *self.0.lock().unwrap() = timing;
+    }
+    pub fn get(&self) -> Option<RequestTiming> {
+        self.0.lock().unwrap().clone()
+    }
+
+    // Set the `RequestTiming` of the response to the `ResponseTiming` of the request (if any)
+    pub fn record(options: &RequestOptions, timing: Option<&RequestTiming>) {
+        if let Some(response_timing) = options.extensions.get::<ResponseTiming>() {
+            response_timing.set(timing.cloned());
+        }
+    }
+}
+
+/*
+`WithTiming` The output of the calls by `with_timing()` with the `RequestTiming` of the response
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithTiming<T> {
+    pub value: T,
+    pub timing: Option<RequestTiming>,
+}
+
+/**
+Run the calls of the `future` and get the `RequestTiming` of the response as well.
+
+# Remarks
+
+* The `timing` is the one of the last response in the scope,
+  it's `None` unless the client measures it (e.g. `TimingClient` of `bind_hyper`).
+* `total` is `None` there, as it's measured when the response body is fully read.
+
+*/
+pub async fn with_timing<F: Future>(future: F) -> WithTiming<F::Output> {
+    let response_timing = ResponseTiming::new();
+    let mut extensions = Extensions::new();
+    extensions.insert(response_timing.clone());
+    let value = with_extensions(extensions, future).await;
+    WithTiming {
+        value,
+        timing: response_timing.get(),
+    }
+}
+
+/**
+`ResponseSize` The sizes of a response body (e.g. to track the bandwidth versus the decoded payloads).
+
+# Remarks
+
+* `content_length` is the `Content-Length` of the response (`None` for the chunked ones).
+* `content_encoding` is the `Content-Encoding` of the response, if the body is still encoded.
+* `decoded_length` is the length of the body passed to the deserializers
+  (hyper counts it while the body is read, so it's `None` until then).
+* `decompressed` is `None` if it's unknown: hyper doesn't decompress the bodies,
+  but ureq decompresses the `gzip` ones transparently (dropping `Content-Encoding`/`Content-Length`).
+
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseSize {
+    pub content_length: Option<u64>,
+    pub content_encoding: Option<String>,
+    pub decoded_length: Option<u64>,
+    pub decompressed: Option<bool>,
+}
+
+/*
+`ResponseSizeInfo` The extension receiving the `ResponseSize` of the responses (set by the bindings),
+scoped by `with_response_size()`.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ResponseSizeInfo(Arc<Mutex<Option<ResponseSize>>>);
+impl ResponseSizeInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set(&self, response_size: Option<ResponseSize>) {
+        *self.0.lock().unwrap() = response_size;
+    }
+    pub fn get(&self) -> Option<ResponseSize> {
+        self.0.lock().unwrap().clone()
+    }
+    // Count the decoded bytes of the response body (as it's read)
+    pub fn add_decoded_length(&self, length: u64) {
+        if let Some(response_size) = self.0.lock().unwrap().as_mut() {
+            response_size.decoded_length = Some(response_size.decoded_length.unwrap_or(0) + length);
+        }
+    }
+
+    // Set the `ResponseSize` of the response to the `ResponseSizeInfo` of the request (if any)
+    pub fn record(options: &RequestOptions, response_size: ResponseSize) {
+        if let Some(response_size_info) = options.extensions.get::<ResponseSizeInfo>() {
+            response_size_info.set(Some(response_size));
+        }
+    }
+}
+
+/*
+`WithResponseSize` The output of the calls by `with_response_size()` with the `ResponseSize` of the response
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithResponseSize<T> {
+    pub value: T,
+    pub response_size: Option<ResponseSize>,
+}
+
+// Run the calls of the `future` and get the `ResponseSize` of the (last) response as well
+pub async fn with_response_size<F: Future>(future: F) -> WithResponseSize<F::Output> {
+    let response_size_info = ResponseSizeInfo::new();
+    let mut extensions = Extensions::new();
+    extensions.insert(response_size_info.clone());
+    let value = with_extensions(extensions, future).await;
+    WithResponseSize {
+        value,
+        response_size: response_size_info.get(),
+    }
+}
+
+/*
+`ResponseStatus` The extension receiving the status of the responses (set by the bindings),
+e.g. to tell the error statuses apart from the failures of the deserializers.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ResponseStatus(Arc<Mutex<Option<u16>>>);
+impl ResponseStatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set(&self, status: Option<u16>) {
+        *self.0.lock().unwrap() = status;
+    }
+    pub fn get(&self) -> Option<u16> {
+        *self.0.lock().unwrap()
+    }
+
+    // Set the status of the response to the `ResponseStatus` of the request (if any)
+    pub fn record(options: &RequestOptions, status: u16) {
+        if let Some(response_status) = options.extensions.get::<ResponseStatus>() {
+            response_status.set(Some(status));
+        }
+    }
+}
+
+/**
+`DeprecationNotice` The deprecation signals of a response (parsed by `DeprecationNotice::parse()`).
+
+# Remarks
+
+* `Deprecation` (RFC 9745) is `@<the seconds since the epoch>`,
+  or the `true`/HTTP-date of the earlier drafts (any other value is regarded as deprecated without the date).
+* `Sunset` (RFC 8594) is the HTTP-date after which the endpoint is expected to be unresponsive.
+* `warnings` are the warn-texts of the `Warning` headers with the code `299` (the persistent warnings),
+  e.g. `299 - "Deprecated API, use /v2"`.
+
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeprecationNotice {
+    pub url: String,
+    pub deprecated: bool,
+    pub deprecated_at: Option<SystemTime>,
+    pub sunset: Option<SystemTime>,
+    pub warnings: Vec<String>,
+}
+impl DeprecationNotice {
+    // The notice of the header values of a response, `None` if there's no deprecation signal
+    pub fn parse(
+        url: &str,
+        deprecation: Option<&str>,
+        sunset: Option<&str>,
+        warnings: &[&str],
+    ) -> Option<Self> {
+        let (deprecated, deprecated_at) = match deprecation.map(|v| v.trim()) {
+            None => (false, None),
+            Some(v) if v.eq_ignore_ascii_case("false") => (false, None),
+            Some(v) => match v.strip_prefix('@') {
+                Some(secs) => (
+                    true,
+                    // The timestamps beyond the range of `SystemTime` are unknown
+                    secs.parse::<u64>().ok().and_then(|secs| {
+                        SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(secs))
+                    }),
+                ),
+                None => (true, parse_http_date(v)),
+            },
+        };
+        let sunset = sunset.and_then(parse_http_date);
+        let warnings: Vec<String> = warnings
+            .iter()
+            .flat_map(|v| parse_warning(v))
+            .filter(|(code, _)| *code == 299)
+            .map(|(_, text)| text)
+            .collect();
+        if !deprecated && sunset.is_none() && warnings.is_empty() {
+            return None;
+        }
+
+        Some(DeprecationNotice {
+            url: url.to_string(),
+            deprecated,
+            deprecated_at,
+            sunset,
+            warnings,
+        })
+    }
+}
+
+// Parse the `Warning` (e.g. `299 - "text" "date", 110 host "text"`) as (code, warn-text)
+fn parse_warning(value: &str) -> Vec<(u16, String)> {
+    // Split by the commas out of the quoted-strings
+    let mut elements = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ',' if !quoted => {
+                elements.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(&value[start..]);
+
+    elements
+        .into_iter()
+        .filter_map(|element| {
+            let mut parts = element.trim().splitn(3, ' ');
+            let code = parts.next()?.parse::<u16>().ok()?;
+            let quoted = parts.nth(1)?.trim_start().strip_prefix('"')?;
+
+            // The warn-text (the first quoted-string, unescaped)
+            let mut text = String::new();
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                match c {
+                    '\\' => text.extend(chars.next()),
+                    '"' => break,
+                    c => text.push(c),
+                }
+            }
+            Some((code, text))
+        })
+        .collect()
+}
+
+/*
+`DeprecationInfo` The extension receiving the `DeprecationNotice` of the responses (set by the bindings),
+scoped by `with_deprecation()`.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct DeprecationInfo(Arc<Mutex<Option<DeprecationNotice>>>);
+impl DeprecationInfo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set(&self, notice: Option<DeprecationNotice>) {
+        *self.0.lock().unwrap() = notice;
+    }
+    pub fn get(&self) -> Option<DeprecationNotice> {
+        self.0.lock().unwrap().clone()
+    }
+
+    // Set the `DeprecationNotice` of the response to the `DeprecationInfo` of the request (if any)
+    pub fn record(options: &RequestOptions, notice: Option<DeprecationNotice>) {
+        if let Some(deprecation_info) = options.extensions.get::<DeprecationInfo>() {
+            deprecation_info.set(notice);
+        }
+    }
+}
+
+/*
+`WithDeprecation` The output of the calls by `with_deprecation()` with the `DeprecationNotice` of the response
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithDeprecation<T> {
+    pub value: T,
+    pub deprecation: Option<DeprecationNotice>,
+}
+
+// Run the calls of the `future` and get the `DeprecationNotice` of the (last) response as well
+pub async fn with_deprecation<F: Future>(future: F) -> WithDeprecation<F::Output> {
+    let deprecation_info = DeprecationInfo::new();
+    let mut extensions = Extensions::new();
+    extensions.insert(deprecation_info.clone());
+    let value = with_extensions(extensions, future).await;
+    WithDeprecation {
+        value,
+        deprecation: deprecation_info.get(),
+    }
+}
+
+// The hook receiving the `DeprecationNotice`s (by `DeprecationMonitor`)
+pub type DeprecationHook = Arc<dyn Fn(&DeprecationNotice) + Send + Sync>;
+
+/**
+`DeprecationMonitor` Observe the deprecation signals of the responses of a service (`set_deprecation_monitor()`),
+so the upstream deprecations are learned from the telemetry instead of the outages.
+
+# Remarks
+
+* Each deprecated response is reported to the `hook` and the `metrics_sink` (`ResilienceEvent::Deprecation`).
+* `notices()` are the last notices of the deprecated endpoints (the URLs without the queries).
+* The clones share the notices.
+
+*/
+#[derive(Clone, Default)]
+pub struct DeprecationMonitor {
+    notices: Arc<Mutex<HashMap<String, DeprecationNotice>>>,
+    pub hook: Option<DeprecationHook>,
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+}
+impl DeprecationMonitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn with_hook(mut self, hook: impl Fn(&DeprecationNotice) + Send + Sync + 'static) -> Self {
+        self.hook = Some(Arc::new(hook));
+        self
+    }
+    pub fn with_metrics_sink(mut self, metrics_sink: Arc<dyn MetricsSink>) -> Self {
+        self.metrics_sink = Some(metrics_sink);
+        self
+    }
+
+    pub fn observe(&self, notice: &DeprecationNotice) {
+        let endpoint = notice.url.split('?').next().unwrap_or_default().to_string();
+        self.notices
+            .lock()
+            .unwrap()
+            .insert(endpoint, notice.clone());
+
+        if let Some(metrics_sink) = &self.metrics_sink {
+            let _ = catch_hook_panic("metrics_sink", || {
+                metrics_sink.record_event(&ResilienceEvent::Deprecation {
+                    url: notice.url.clone(),
+                    deprecated_at: notice.deprecated_at,
+                    sunset: notice.sunset,
+                })
+            });
+        }
+        if let Some(hook) = &self.hook {
+            let _ = catch_hook_panic("deprecation_hook", || hook(notice));
+        }
+    }
+
+    // The last notices of the deprecated endpoints (sorted by the URLs)
+    pub fn notices(&self) -> Vec<DeprecationNotice> {
+        let mut notices: Vec<DeprecationNotice> =
+            self.notices.lock().unwrap().values().cloned().collect();
+        notices.sort_by(|a, b| a.url.cmp(&b.url));
+        notices
+    }
+}
+
+/*
+`MetricsSink` The hooks to receive metrics of calls.
+
+All methods have empty default implementations, implement the ones you're interested in.
+*/
+pub trait MetricsSink: Send + Sync {
+    fn record_timing(&self, _method: &str, _url: &str, _timing: &RequestTiming) {}
+    // A retry is attempted (`allowed`) or rejected by the `RetryBudget`
+    fn record_retry(&self, _method: &str, _url: &str, _usage: &RetryBudgetUsage, _allowed: bool) {}
+    // A call failed at the transport level (classified by `TransportErrorKind::of()`)
+    fn record_transport_error(&self, _method: &str, _url: &str, _kind: TransportErrorKind) {}
+    // An event of the resilience features (retries/caches/limiters)
+    fn record_event(&self, _event: &ResilienceEvent) {}
+}
+
+/**
+`ResilienceEvent` The structured events of the resilience features (by `MetricsSink::record_event()`),
+e.g. for building the dashboards.
+
+The schema is stable: the `name()` and the field names of `fields()` of each event are kept
+(new events/fields could be added).
+
+* `retry.attempt` - A retry of `RetryClient` (`attempt` is from 1, after `backoff_ms`),
+  `allowed` is false if it's rejected by the `RetryBudget`.
+* `cache.hit`/`cache.stale`/`cache.miss` - A lookup of `MemoCache` (the fresh/stale/absent values).
+* `limiter.wait` - A call waited for the permit of `PriorityClient`/`AdaptiveClient`
+  (`limiter` is `priority`/`adaptive`).
+* `clock.skew` - The `Date` of a response is off the local clock over the threshold of `ClockSkew`
+  (`skew_ms` is positive if the local clock is behind).
+
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResilienceEvent {
+    RetryAttempt {
+        method: String,
+        url: String,
+        attempt: u32,
+        backoff: Duration,
+        allowed: bool,
+    },
+    CacheHit {
+        key: String,
+    },
+    CacheStale {
+        key: String,
+    },
+    CacheMiss {
+        key: String,
+    },
+    LimiterWait {
+        limiter: &'static str,
+        method: String,
+        url: String,
+        waited: Duration,
+    },
+    // The clock skew over the threshold (by `ClockSkew`)
+    ClockSkew {
+        url: String,
+        skew_millis: i64,
+    },
+    // The deprecation signals of a response (by `DeprecationMonitor`)
+    Deprecation {
+        url: String,
+        deprecated_at: Option<SystemTime>,
+        sunset: Option<SystemTime>,
+    },
+}
+impl ResilienceEvent {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResilienceEvent::RetryAttempt { .. } => "retry.attempt",
+            ResilienceEvent::CacheHit { .. } => "cache.hit",
+            ResilienceEvent::CacheStale { .. } => "cache.stale",
+            ResilienceEvent::CacheMiss { .. } => "cache.miss",
+            ResilienceEvent::LimiterWait { .. } => "limiter.wait",
+            ResilienceEvent::ClockSkew { .. } => "clock.skew",
+            ResilienceEvent::Deprecation { .. } => "api.deprecation",
+        }
+    }
+    // The fields of the event as (name, value) (e.g. for the structured logs), the durations are in milliseconds
+    pub fn fields(&self) -> Vec<(&'static str, String)> {
+        match self {
+            ResilienceEvent::RetryAttempt {
+                method,
+                url,
+                attempt,
+                backoff,
+                allowed,
+            } => vec![
+                ("method", method.clone()),
+                ("url", url.clone()),
+                ("attempt", attempt.to_string()),
+                ("backoff_ms", backoff.as_millis().to_string()),
+                ("allowed", allowed.to_string()),
+            ],
+            ResilienceEvent::CacheHit { key }
+            | ResilienceEvent::CacheStale { key }
+            | ResilienceEvent::CacheMiss { key } => vec![("key", key.clone())],
+            ResilienceEvent::LimiterWait {
+                limiter,
+                method,
+                url,
+                waited,
+            } => vec![
+                ("limiter", limiter.to_string()),
+                ("method", method.clone()),
+                ("url", url.clone()),
+                ("waited_ms", waited.as_millis().to_string()),
+            ],
+            ResilienceEvent::ClockSkew { url, skew_millis } => {
+                vec![("url", url.clone()), ("skew_ms", skew_millis.to_string())]
+            }
+            // The times are the seconds since the epoch
+            ResilienceEvent::Deprecation {
+                url,
+                deprecated_at,
+                sunset,
+            } => {
+                let mut fields = vec![("url", url.clone())];
+                let epoch_secs = |time: &SystemTime| {
+                    time.duration_since(SystemTime::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_secs()
+                        .to_string()
+                };
+                if let Some(deprecated_at) = deprecated_at {
+                    fields.push(("deprecated_at", epoch_secs(deprecated_at)));
+                }
+                if let Some(sunset) = sunset {
+                    fields.push(("sunset", epoch_secs(sunset)));
+                }
+                fields
+            }
+        }
+    }
+}
+// `name field=value ...` (the values are quoted if they have spaces)
+impl std::fmt::Display for ResilienceEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())?;
+        for (name, value) in self.fields() {
+            if value.contains(' ') {
+                write!(f, " {}={:?}", name, value)?;
+            } else {
+                write!(f, " {}={}", name, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+`TransportErrorKind` The classification of the transport (connection level) errors of both bindings,
+so the retry policies and the metrics could distinguish them.
+
+# Remarks
+
+* It's classified by `TransportErrorKind::of()` from the errors returned by the calls
+  (e.g. `hyper::Error`/`ureq::Error`, or the `io::Error` of their sources).
+* The transport errors which aren't any of the others are `Other`
+  (e.g. the connection failures of unknown causes).
+
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransportErrorKind {
+    // The host name isn't resolved
+    Dns,
+    ConnectionRefused,
+    // The TLS handshake failed (e.g. the invalid certificates)
+    Tls,
+    // The connection was reset/aborted/closed before the response
+    ConnectionReset,
+    BrokenPipe,
+    TimedOut,
+    Other,
+}
+impl TransportErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransportErrorKind::Dns => "dns",
+            TransportErrorKind::ConnectionRefused => "connection_refused",
+            TransportErrorKind::Tls => "tls",
+            TransportErrorKind::ConnectionReset => "connection_reset",
+            TransportErrorKind::BrokenPipe => "broken_pipe",
+            TransportErrorKind::TimedOut => "timed_out",
+            TransportErrorKind::Other => "other",
+        }
+    }
+
+    /**
+    Classify the error by itself and its sources (`None` if it's not a transport error).
+
+    # Remarks
+
+    * The `io::Error` kinds are used when they're specific, otherwise the messages of the transport errors are
+      (e.g. the DNS/TLS failures are wrapped as `io::Error` of other kinds).
+
+    */
+    pub fn of(error: &(dyn StdError + 'static)) -> Option<Self> {
+        let mut is_transport_error = false;
+        let mut next = Some(error);
+        while let Some(error) = next {
+            if let Some(kind) = Self::of_error(error, &mut is_transport_error) {
+                return Some(kind);
+            }
+            next = error.source();
+        }
+        if is_transport_error {
+            Some(TransportErrorKind::Other)
+        } else {
+            None
+        }
+    }
+    fn of_error(error: &(dyn StdError + 'static), is_transport_error: &mut bool) -> Option<Self> {
+        #[cfg(feature = "for_hyper")]
+        if let Some(error) = error.downcast_ref::<hyper::Error>() {
+            if error.is_incomplete_message() {
+                return Some(TransportErrorKind::ConnectionReset);
+            }
+            *is_transport_error |= error.is_connect() || error.is_closed();
+        }
+        #[cfg(feature = "for_ureq")]
+        if let Some(ureq::Error::Transport(transport)) = error.downcast_ref::<ureq::Error>() {
+            match transport.kind() {
+                ureq::ErrorKind::Dns => return Some(TransportErrorKind::Dns),
+                ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::Io => {
+                    *is_transport_error = true
+                }
+                _ => {}
+            }
+        }
+        if let Some(error) = error.downcast_ref::<io::Error>() {
+            *is_transport_error = true;
+            match error.kind() {
+                io::ErrorKind::ConnectionRefused => {
+                    return Some(TransportErrorKind::ConnectionRefused)
+                }
+                io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted => {
+                    return Some(TransportErrorKind::ConnectionReset)
+                }
+                io::ErrorKind::BrokenPipe => return Some(TransportErrorKind::BrokenPipe),
+                io::ErrorKind::TimedOut => return Some(TransportErrorKind::TimedOut),
+                _ => {}
+            }
+        }
+
+        // The messages of the other errors (e.g. the bodies) aren't classified
+        if !*is_transport_error {
+            return None;
+        }
+        let message = error.to_string().to_ascii_lowercase();
+        let contains_any = |patterns: &[&str]| patterns.iter().any(|v| message.contains(v));
+        if contains_any(&[
+            "dns error",
+            "failed to lookup address",
+            "name or service not known",
+            "temporary failure in name resolution",
+            "nodename nor servname",
+            "no such host",
+        ]) {
+            Some(TransportErrorKind::Dns)
+        } else if contains_any(&["tls", "ssl", "certificate", "handshake"]) {
+            Some(TransportErrorKind::Tls)
+        } else if contains_any(&["connection refused"]) {
+            Some(TransportErrorKind::ConnectionRefused)
+        } else if contains_any(&["connection reset", "connection aborted"]) {
+            Some(TransportErrorKind::ConnectionReset)
+        } else if contains_any(&["broken pipe"]) {
+            Some(TransportErrorKind::BrokenPipe)
+        } else {
+            None
+        }
+    }
+}
+impl std::fmt::Display for TransportErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/**
+`Clock` The injectable time source of the time-dependent behaviors
+(e.g. retry backoff, retry budget windows, timeouts and queue schedules).
+
+# Remarks
+
+* `SystemClock` is the real one, and `MockClock` makes the tests run instantly without real sleeps.
+
+*/
+pub trait Clock: Send + Sync {
+    // The monotonic time (for durations)
+    fn now(&self) -> Instant;
+    // The wall clock time (for timestamps)
+    fn system_now(&self) -> SystemTime;
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/**
+`SystemClock` The `Clock` of the real time.
+
+# Remarks
+
+* The sleeping is done by tokio if it's enabled (by `for_hyper`), so it should be polled in a tokio runtime;
+  otherwise it's done by a thread for each sleeping (runtime independent).
+
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+    #[cfg(feature = "tokio")]
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+    #[cfg(not(feature = "tokio"))]
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        let (sender, receiver) = oneshot::channel();
+        thread::spawn(move || {
+            thread::sleep(duration);
+            let _ = sender.send(());
+        });
+        Box::pin(async move {
+            let _ = receiver.await;
+        })
+    }
+}
+
+// The default `Clock` (`SystemClock`)
+pub fn system_clock() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
+
+pub const DEFAULT_CLOCK_SKEW_THRESHOLD: Duration = Duration::from_secs(60);
+
+/**
+`ClockSkew` The skew of the local clock versus the servers, estimated by the `Date` of the responses
+(e.g. for the signatures of HMAC/SigV4 rejected by the drifted clocks).
+
+# Remarks
+
+* The skew is the time of the server minus the local one (positive if the local clock is behind),
+  against the local time of the middle of the call (so the latency is halved).
+* The `Date` is in seconds, so the skews under a second (or the latency) aren't meaningful.
+* The skews over the `threshold` are reported to the `metrics_sink` (`ResilienceEvent::ClockSkew`).
+* The clones share the estimation (e.g. with `SkewCorrectedClock`).
+
+*/
+#[derive(Clone)]
+pub struct ClockSkew {
+    skew_millis: Arc<Mutex<Option<i64>>>,
+    pub threshold: Duration,
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+}
+impl Default for ClockSkew {
+    fn default() -> Self {
+        Self::new(DEFAULT_CLOCK_SKEW_THRESHOLD)
+    }
+}
+impl ClockSkew {
+    pub fn new(threshold: Duration) -> Self {
+        ClockSkew {
+            skew_millis: Arc::new(Mutex::new(None)),
+            threshold,
+            metrics_sink: None,
+        }
+    }
+    pub fn with_metrics_sink(mut self, metrics_sink: Arc<dyn MetricsSink>) -> Self {
+        self.metrics_sink = Some(metrics_sink);
+        self
+    }
+
+    /**
+    Estimate the skew by the `Date` of a response, the call is sent at `sent_at` and received at `received_at`
+    (the local times). It returns the skew in milliseconds, or `None` if the `date` isn't valid.
+    */
+    pub fn observe(
+        &self,
+        url: &str,
+        date: &str,
+        sent_at: SystemTime,
+        received_at: SystemTime,
+    ) -> Option<i64> {
+        let server_time = parse_http_date(date)?;
+        let latency = received_at.duration_since(sent_at).unwrap_or_default();
+        let local_time = sent_at + latency / 2;
+        let skew_millis = match server_time.duration_since(local_time) {
+            Ok(ahead) => ahead.as_millis() as i64,
+            Err(behind) => -(behind.duration().as_millis() as i64),
+        };
+        *self.skew_millis.lock().unwrap() = Some(skew_millis);
+
+        if let Some(metrics_sink) = &self.metrics_sink {
+            if skew_millis.unsigned_abs() as u128 >= self.threshold.as_millis() {
+                let _ = catch_hook_panic("metrics_sink", || {
+                    metrics_sink.record_event(&ResilienceEvent::ClockSkew {
+                        url: url.to_string(),
+                        skew_millis,
+                    })
+                });
+            }
+        }
+        Some(skew_millis)
+    }
+
+    // The last estimated skew in milliseconds (`None` before any `Date` is observed)
+    pub fn skew_millis(&self) -> Option<i64> {
+        *self.skew_millis.lock().unwrap()
+    }
+    // Whether the last estimated skew is over the `threshold`
+    pub fn is_skewed(&self) -> bool {
+        self.skew_millis()
+            .map(|v| v.unsigned_abs() as u128 >= self.threshold.as_millis())
+            .unwrap_or(false)
+    }
+    // The local time corrected by the skew (the estimated time of the servers)
+    pub fn correct(&self, local_time: SystemTime) -> SystemTime {
+        match self.skew_millis() {
+            Some(skew_millis) if skew_millis >= 0 => {
+                local_time + Duration::from_millis(skew_millis as u64)
+            }
+            Some(skew_millis) => local_time - Duration::from_millis(skew_millis.unsigned_abs()),
+            None => local_time,
+        }
+    }
+}
+
+/*
+`SkewCorrectedClock` The `Clock` of the wall clock time corrected by the `ClockSkew`
+(e.g. for the timestamps of the signatures), the monotonic time & the sleeping are the ones of the `inner`.
+*/
+#[derive(Clone)]
+pub struct SkewCorrectedClock {
+    pub inner: Arc<dyn Clock>,
+    pub skew: ClockSkew,
+}
+impl SkewCorrectedClock {
+    pub fn new(inner: Arc<dyn Clock>, skew: ClockSkew) -> Self {
+        SkewCorrectedClock { inner, skew }
+    }
+}
+impl Clock for SkewCorrectedClock {
+    fn now(&self) -> Instant {
+        self.inner.now()
+    }
+    fn system_now(&self) -> SystemTime {
+        self.skew.correct(self.inner.system_now())
+    }
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        self.inner.sleep(duration)
+    }
+}
+
+struct MockClockState {
+    elapsed: Duration,
+    wakers: Vec<Waker>,
+}
+
+/**
+`MockClock` The `Clock` advanced manually (by `advance()`) for tests.
+
+# Remarks
+
+* The sleepings complete when the clock is advanced past their deadlines.
+* With `new_auto_advance()`, each sleeping advances the clock by its duration and completes immediately,
+  so the code sleeping (e.g. retry backoff) runs instantly.
+* The clones share the same time.
+
+*/
+#[derive(Clone)]
+pub struct MockClock {
+    base: Instant,
+    base_system: SystemTime,
+    auto_advance: bool,
+    state: Arc<Mutex<MockClockState>>,
+}
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MockClock {
+    pub fn new() -> Self {
+        MockClock {
+            base: Instant::now(),
+            base_system: SystemTime::now(),
+            auto_advance: false,
+            state: Arc::new(Mutex::new(MockClockState {
+                elapsed: Duration::default(),
+                wakers: Vec::new(),
+            })),
+        }
+    }
+    pub fn new_auto_advance() -> Self {
+        MockClock {
+            auto_advance: true,
+            ..Self::new()
+        }
+    }
+
+    // The total advanced duration
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed
+    }
+    // Advance the clock, and wake the sleepings
+    pub fn advance(&self, duration: Duration) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.elapsed += duration;
+            std::mem::take(&mut state.wakers)
+        };
+        for waker in wakers.into_iter() {
+            waker.wake();
+        }
+    }
+}
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.base + self.elapsed()
+    }
+    fn system_now(&self) -> SystemTime {
+        self.base_system + self.elapsed()
+    }
+    fn sleep(&self, duration: Duration) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        if self.auto_advance {
+            self.advance(duration);
+            return Box::pin(futures::future::ready(()));
+        }
+
+        let deadline = self.elapsed() + duration;
+        let state = self.state.clone();
+        Box::pin(futures::future::poll_fn(move |cx| {
+            let mut state = state.lock().unwrap();
+            if state.elapsed >= deadline {
+                return Poll::Ready(());
+            }
+            state.wakers.push(cx.waker().clone());
+            Poll::Pending
+        }))
+    }
+}
+
+/*
+`RetryBudgetUsage` The consumption of a `RetryBudget` in the current window
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct RetryBudgetUsage {
+    pub requests: u64,
+    pub retries: u64,
+    // The max retries allowed in the current window
+    pub limit: u64,
+}
+
+const RETRY_BUDGET_BUCKETS: u32 = 10;
+
+/**
+`RetryBudget` limits the retries to a ratio of the requests over a sliding window,
+so that retry storms can't amplify outages.
+
+It's shared (`Arc`) per service, e.g. among the clients calling the same upstream.
+
+# Arguments
+
+* `ratio` - The max extra load by retries (e.g. `0.2` for 20%).
+* `min_retries` - The retries always allowed in a window (for low traffic).
+* `window` - The sliding window.
+
+*/
+pub struct RetryBudget {
+    pub ratio: f64,
+    pub min_retries: u64,
+    pub window: Duration,
+    pub clock: Arc<dyn Clock>,
+    // (start of the bucket, requests, retries)
+    buckets: Mutex<VecDeque<(Instant, u64, u64)>>,
+}
+impl std::fmt::Debug for RetryBudget {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("RetryBudget")
+            .field("ratio", &self.ratio)
+            .field("min_retries", &self.min_retries)
+            .field("window", &self.window)
+            .field("buckets", &self.buckets)
+            .finish()
+    }
+}
+impl Default for RetryBudget {
+    fn default() -> Self {
+        RetryBudget::new(0.2, 10, Duration::from_secs(10))
+    }
+}
+impl RetryBudget {
+    pub fn new(ratio: f64, min_retries: u64, window: Duration) -> Self {
+        Self::new_with_clock(ratio, min_retries, window, system_clock())
+    }
+    pub fn new_with_clock(
+        ratio: f64,
+        min_retries: u64,
+        window: Duration,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        RetryBudget {
+            ratio,
+            min_retries,
+            window,
+            clock,
+            buckets: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    // Count an original (non-retry) request
+    pub fn deposit(&self) {
+        self.update(|bucket| bucket.1 += 1);
+    }
+
+    // Count a retry if the budget allows it
+    pub fn try_withdraw(&self) -> bool {
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let usage = Self::usage_of(&mut buckets, now, self.window, self.ratio, self.min_retries);
+        if usage.retries >= usage.limit {
+            return false;
+        }
+        Self::current_bucket(&mut buckets, now, self.window).2 += 1;
+        true
+    }
+
+    pub fn usage(&self) -> RetryBudgetUsage {
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap();
+        Self::usage_of(&mut buckets, now, self.window, self.ratio, self.min_retries)
+    }
+
+    fn update(&self, f: impl FnOnce(&mut (Instant, u64, u64))) {
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap();
+        f(Self::current_bucket(&mut buckets, now, self.window));
+    }
+
+    fn current_bucket(
+        buckets: &mut VecDeque<(Instant, u64, u64)>,
+        now: Instant,
+        window: Duration,
+    ) -> &mut (Instant, u64, u64) {
+        // Prune the expired buckets here as well, the budget may be only charged (never read)
+        while let Some(bucket) = buckets.front() {
+            if now.duration_since(bucket.0) < window {
+                break;
+            }
+            buckets.pop_front();
+        }
+
+        let bucket_width = window / RETRY_BUDGET_BUCKETS;
+        let is_current = match buckets.back() {
+            Some(bucket) => now.duration_since(bucket.0) < bucket_width,
+            None => false,
+        };
+        if !is_current {
+            buckets.push_back((now, 0, 0));
+        }
+        buckets.back_mut().unwrap()
+    }
+
+    fn usage_of(
+        buckets: &mut VecDeque<(Instant, u64, u64)>,
+        now: Instant,
+        window: Duration,
+        ratio: f64,
+        min_retries: u64,
+    ) -> RetryBudgetUsage {
+        while let Some(bucket) = buckets.front() {
+            if now.duration_since(bucket.0) < window {
+                break;
+            }
+            buckets.pop_front();
+        }
+
+        let (requests, retries) = buckets.iter().fold((0, 0), |(requests, retries), bucket| {
+            (requests + bucket.1, retries + bucket.2)
+        });
+        RetryBudgetUsage {
+            requests,
+            retries,
+            limit: min_retries + (requests as f64 * ratio) as u64,
+        }
+    }
+}
+
+/*
+`QuotaResource` The resources limited by `UsageQuota`
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuotaResource {
+    Calls,
+    // The known sizes of the request bodies and the response bodies (by `Content-Length` for hyper)
+    Bytes,
+}
+
+/*
+`QuotaUsage` The consumption of a `UsageQuota` in the current window
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QuotaUsage {
+    pub calls: u64,
+    pub bytes: u64,
+}
+
+/*
+`QuotaAlarm` A threshold of a `UsageQuota` is crossed (received by `UsageQuota::on_alarm()`)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaAlarm {
+    pub resource: QuotaResource,
+    // The crossed ratio of the limit (e.g. `0.8`)
+    pub threshold: f64,
+    pub used: u64,
+    pub limit: u64,
+}
+
+// Receive the `QuotaAlarm` of a `UsageQuota`
+pub type QuotaAlarmHook = Arc<dyn Fn(&QuotaAlarm) + Send + Sync>;
+
+/*
+`QuotaExceededError` The call is rejected by the hard stop of the `UsageQuota`
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuotaExceededError {
+    details: String,
+    pub resource: QuotaResource,
+}
+impl StdError for QuotaExceededError {}
+impl QuotaExceededError {
+    pub fn new(msg: impl Into<String>, resource: QuotaResource) -> QuotaExceededError {
+        QuotaExceededError {
+            details: msg.into(),
+            resource,
+        }
+    }
+}
+impl std::fmt::Display for QuotaExceededError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "QuotaExceededError: {}", self.details)
+    }
+}
+
+const USAGE_QUOTA_BUCKETS: u32 = 10;
+
+/**
+`UsageQuota` The budgets of the calls/bytes of a service over a sliding window
+(e.g. to stay under the quotas of the third-party APIs), set by `set_usage_quota()`.
+
+It's shared (`Arc`) per service, e.g. among the services calling the same third-party API.
+
+# Arguments
+
+* `window` - The sliding window.
+
+# Remarks
+
+* The alarms (`on_alarm()`) are raised once per crossing of the `thresholds` (the ratios of the limits),
+  i.e. they're raised again after the usage drops below the thresholds in the window.
+* With the `hard_stop`, the calls are rejected with `QuotaExceededError` once a limit is reached,
+  otherwise they're only alarmed.
+* The bytes are counted after the responses, so the calls in flight could exceed `max_bytes`.
+
+*/
+pub struct UsageQuota {
+    pub window: Duration,
+    pub max_calls: Option<u64>,
+    pub max_bytes: Option<u64>,
+    pub thresholds: Vec<f64>,
+    pub hard_stop: bool,
+    pub clock: Arc<dyn Clock>,
+    alarm_hook: Option<QuotaAlarmHook>,
+    // (start of the bucket, calls, bytes)
+    buckets: Mutex<VecDeque<(Instant, u64, u64)>>,
+}
+impl UsageQuota {
+    pub fn new(window: Duration) -> Self {
+        Self::new_with_clock(window, system_clock())
+    }
+    pub fn new_with_clock(window: Duration, clock: Arc<dyn Clock>) -> Self {
+        UsageQuota {
+            window,
+            max_calls: None,
+            max_bytes: None,
+            thresholds: vec![0.8, 1.0],
+            hard_stop: false,
+            clock,
+            alarm_hook: None,
+            buckets: Mutex::new(VecDeque::new()),
+        }
+    }
+    pub fn max_calls(mut self, max_calls: u64) -> Self {
+        self.max_calls = Some(max_calls);
+        self
+    }
+    pub fn max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+    // The ratios of the limits to alarm at (`[0.8, 1.0]` by default)
+    pub fn thresholds(mut self, thresholds: Vec<f64>) -> Self {
+        self.thresholds = thresholds;
+        self
+    }
+    pub fn hard_stop(mut self, hard_stop: bool) -> Self {
+        self.hard_stop = hard_stop;
+        self
+    }
+    pub fn on_alarm(mut self, hook: impl Fn(&QuotaAlarm) + Send + Sync + 'static) -> Self {
+        self.alarm_hook = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn usage(&self) -> QuotaUsage {
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap();
+        Self::usage_of(&mut buckets, now, self.window)
+    }
+
+    // Count a call before it's sent (rejected by the `hard_stop` if a limit has been reached)
+    pub fn try_acquire(&self) -> StdResult<(), QuotaExceededError> {
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let before = Self::usage_of(&mut buckets, now, self.window);
+        if self.hard_stop {
+            for (resource, used, limit) in [
+                (QuotaResource::Calls, before.calls, self.max_calls),
+                (QuotaResource::Bytes, before.bytes, self.max_bytes),
+            ] {
+                if let Some(limit) = limit.filter(|limit| used >= *limit) {
+                    return Err(QuotaExceededError::new(
+                        format!("{:?} {} of {} in {:?}", resource, used, limit, self.window),
+                        resource,
+                    ));
+                }
+            }
+        }
+        Self::current_bucket(&mut buckets, now, self.window).1 += 1;
+        drop(buckets);
+
+        self.alarm(QuotaResource::Calls, before.calls, before.calls + 1);
+        Ok(())
+    }
+
+    // Count the bytes of a call
+    pub fn record_bytes(&self, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let now = self.clock.now();
+        let mut buckets = self.buckets.lock().unwrap();
+        let before = Self::usage_of(&mut buckets, now, self.window);
+        Self::current_bucket(&mut buckets, now, self.window).2 += bytes;
+        drop(buckets);
+
+        self.alarm(QuotaResource::Bytes, before.bytes, before.bytes + bytes);
+    }
+
+    fn alarm(&self, resource: QuotaResource, before: u64, after: u64) {
+        let (hook, limit) = match (&self.alarm_hook, resource) {
+            (Some(hook), QuotaResource::Calls) => (hook, self.max_calls),
+            (Some(hook), QuotaResource::Bytes) => (hook, self.max_bytes),
+            (None, _) => return,
+        };
+        let limit = match limit {
+            Some(limit) => limit,
+            None => return,
+        };
+        for threshold in self.thresholds.iter() {
+            let at = (limit as f64 * threshold).ceil() as u64;
+            if before < at && at <= after {
+                let alarm = QuotaAlarm {
+                    resource,
+                    threshold: *threshold,
+                    used: after,
+                    limit,
+                };
+                let _ = catch_hook_panic("alarm_hook", || hook(&alarm));
+            }
+        }
+    }
+
+    fn current_bucket(
+        buckets: &mut VecDeque<(Instant, u64, u64)>,
+        now: Instant,
+        window: Duration,
+    ) -> &mut (Instant, u64, u64) {
+        let bucket_width = window / USAGE_QUOTA_BUCKETS;
+        let is_current = match buckets.back() {
+            Some(bucket) => now.duration_since(bucket.0) < bucket_width,
+            None => false,
+        };
+        if !is_current {
+            buckets.push_back((now, 0, 0));
+        }
+        buckets.back_mut().unwrap()
+    }
+
+    fn usage_of(
+        buckets: &mut VecDeque<(Instant, u64, u64)>,
+        now: Instant,
+        window: Duration,
+    ) -> QuotaUsage {
+        while let Some(bucket) = buckets.front() {
+            if now.duration_since(bucket.0) < window {
+                break;
+            }
+            buckets.pop_front();
+        }
+
+        let (calls, bytes) = buckets.iter().fold((0, 0), |(calls, bytes), bucket| {
+            (calls + bucket.1, bytes + bucket.2)
+        });
+        QuotaUsage { calls, bytes }
+    }
+}
+
+/*
+`RotationStrategy` How `CredentialRotation` picks the credentials
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RotationStrategy {
+    // Cycle through the credentials per request
+    #[default]
+    RoundRobin,
+    // Keep using the current one until it's rejected (`429`/`403`) or rate limited
+    OnRejection,
+}
+
+/*
+`CredentialState` The tracked state of a credential of `CredentialRotation`
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CredentialState {
+    pub uses: u64,
+    pub rejections: u64,
+    // The remaining requests reported by the `X-RateLimit-Remaining` header
+    pub remaining: Option<u64>,
+    // The credential is skipped until then (rejected or rate limited)
+    pub limited_until: Option<Instant>,
+}
+impl CredentialState {
+    pub fn is_available(&self, now: Instant) -> bool {
+        match self.limited_until {
+            Some(limited_until) => limited_until <= now,
+            None => true,
+        }
+    }
+}
+
+// The longest `Retry-After`/`X-RateLimit-Reset` honored by `CredentialRotation` (the larger ones are clamped)
+pub const MAX_RATE_LIMIT_RESET: Duration = Duration::from_secs(24 * 60 * 60);
+
+/**
+`CredentialRotation` Rotate the requests among the issued API keys/tokens,
+with the per-credential rate-limit state tracked.
+
+# Arguments
+
+* `header_name` - The header of the credentials (e.g. `Authorization` or `X-Api-Key`).
+* `credentials` - The whole header values (e.g. `Bearer <token>`), they're validated and marked as sensitive.
+
+# Remarks
+
+* The rejected (`429`/`403`) credentials are skipped until the `Retry-After`/`X-RateLimit-Reset`
+  (the delay seconds or the epoch seconds, up to `MAX_RATE_LIMIT_RESET`), or the `cooldown` if they're absent.
+* The credentials with `X-RateLimit-Remaining: 0` are skipped until the reset as well.
+* If all credentials are limited, the one available soonest is used.
+* It's shared (`Arc`) by the interceptor (`InterceptorFunc::new_for_credential_rotation()`)
+  or the client (`CredentialRotationClient`, which reports the responses back).
+
+*/
+pub struct CredentialRotation {
+    pub header_name: String,
+    pub strategy: RotationStrategy,
+    pub cooldown: Duration,
+    pub clock: Arc<dyn Clock>,
+    credentials: Vec<String>,
+    // (the next/current index, the states)
+    state: Mutex<(usize, Vec<CredentialState>)>,
+}
+impl CredentialRotation {
+    pub fn new(
+        header_name: impl Into<String>,
+        credentials: Vec<impl Into<String>>,
+        strategy: RotationStrategy,
+    ) -> StdResult<Self, InvalidHeaderError> {
+        let header_name = header_name.into();
+        validate_header_name(&header_name)?;
+        let credentials = credentials
+            .into_iter()
+            .map(|v| HeaderValueBuilder::new(v).build())
+            .collect::<StdResult<Vec<_>, _>>()?;
+        let states = vec![CredentialState::default(); credentials.len()];
+
+        Ok(CredentialRotation {
+            header_name,
+            strategy,
+            cooldown: Duration::from_secs(60),
+            clock: system_clock(),
+            credentials,
+            state: Mutex::new((0, states)),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.credentials.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.credentials.is_empty()
+    }
+
+    // Pick the credential for a request, returns (index, header value)
+    pub fn next(&self) -> Option<(usize, String)> {
+        if self.credentials.is_empty() {
+            return None;
+        }
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap();
+        let (current, states) = &mut *state;
+        let len = states.len();
+
+        let index = (0..len)
+            .map(|offset| (*current + offset) % len)
+            .find(|i| states[*i].is_available(now))
+            .unwrap_or_else(|| {
+                (0..len)
+                    .min_by_key(|i| states[*i].limited_until)
+                    .unwrap_or(0)
+            });
+        *current = match self.strategy {
+            RotationStrategy::RoundRobin => (index + 1) % len,
+            RotationStrategy::OnRejection => index,
+        };
+
+        let credential = &mut states[index];
+        if credential.is_available(now) && credential.limited_until.is_some() {
+            // The limit is over
+            credential.limited_until = None;
+            credential.remaining = None;
+        }
+        credential.uses += 1;
+        Some((index, self.credentials[index].clone()))
+    }
+
+    // Set the header to the next credential, returns its index
+    pub fn apply(&self, headers: &mut Headers) -> Option<usize> {
+        let (index, value) = self.next()?;
+        headers.insert_sensitive(self.header_name.clone(), value);
+        Some(index)
+    }
+
+    // Track the rate-limit state of the credential by the response
+    pub fn report(&self, index: usize, status: u16, headers: &Headers) {
+        let now = self.clock.now();
+        let remaining = headers
+            .get("X-RateLimit-Remaining")
+            .and_then(|v| v.trim().parse::<u64>().ok());
+        let reset_after = headers
+            .get("Retry-After")
+            .or_else(|| headers.get("X-RateLimit-Reset"))
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(|v| self.reset_after(v));
+
+        let mut state = self.state.lock().unwrap();
+        let (current, states) = &mut *state;
+        let len = states.len();
+        let credential = match states.get_mut(index) {
+            Some(v) => v,
+            None => return,
+        };
+        if remaining.is_some() {
+            credential.remaining = remaining;
+        }
+
+        let rejected = status == 429 || status == 403;
+        if rejected {
+            credential.rejections += 1;
+        }
+        if rejected || remaining == Some(0) {
+            let limited_for = reset_after.unwrap_or(self.cooldown);
+            credential.limited_until = Some(
+                now.checked_add(limited_for)
+                    .or_else(|| now.checked_add(MAX_RATE_LIMIT_RESET))
+                    .unwrap_or(now),
+            );
+            if self.strategy == RotationStrategy::OnRejection && *current == index {
+                *current = (index + 1) % len;
+            }
+        }
+    }
+
+    // The delay seconds, or the epoch seconds (the values after 2001-09-09), up to `MAX_RATE_LIMIT_RESET`
+    fn reset_after(&self, value: u64) -> Duration {
+        if value < 1_000_000_000 {
+            return Duration::from_secs(value).min(MAX_RATE_LIMIT_RESET);
+        }
+        match std::time::UNIX_EPOCH.checked_add(Duration::from_secs(value)) {
+            Some(reset_at) => reset_at
+                .duration_since(self.clock.system_now())
+                .unwrap_or(Duration::ZERO)
+                .min(MAX_RATE_LIMIT_RESET),
+            None => MAX_RATE_LIMIT_RESET,
+        }
+    }
+
+    pub fn states(&self) -> Vec<CredentialState> {
+        self.state.lock().unwrap().1.clone()
+    }
+}
+
+/**
+`AuthProvider` Provide the credentials of the requests (e.g. for `Session`).
+
+# Remarks
+
+* `on_response()` observes the responses (e.g. to refresh the expired tokens), it does nothing by default.
+
+*/
+pub trait AuthProvider: Send + Sync {
+    // Set the credential headers
+    fn apply(&self, headers: &mut Headers) -> StdResult<(), Box<dyn StdError>>;
+    fn on_response(&self, _status: u16, _headers: &Headers) {}
+}
+
+/*
+`HeaderAuth` The `AuthProvider` of a fixed credential header (validated, and marked as sensitive)
+*/
+#[derive(Clone, PartialEq)]
+pub struct HeaderAuth {
+    name: String,
+    value: String,
+}
+impl HeaderAuth {
+    pub fn new(
+        name: impl Into<String>,
+        value: impl Into<String>,
+    ) -> StdResult<Self, InvalidHeaderError> {
+        let name = name.into();
+        validate_header_name(&name)?;
+        let value = HeaderValueBuilder::new(value).build()?;
+        Ok(HeaderAuth { name, value })
+    }
+    // `Authorization: Bearer <token>`
+    pub fn bearer(token: impl Into<String>) -> StdResult<Self, InvalidHeaderError> {
+        Self::new("Authorization", "Bearer ".to_string() + &token.into())
+    }
+}
+impl std::fmt::Debug for HeaderAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HeaderAuth")
+            .field("name", &self.name)
+            .field("value", &REDACTED)
+            .finish()
+    }
+}
+impl AuthProvider for HeaderAuth {
+    fn apply(&self, headers: &mut Headers) -> StdResult<(), Box<dyn StdError>> {
+        headers.insert_sensitive(self.name.clone(), self.value.clone());
+        Ok(())
+    }
+}
+// The responses are not reported back (they're not bound to the credentials)
+impl AuthProvider for CredentialRotation {
+    fn apply(&self, headers: &mut Headers) -> StdResult<(), Box<dyn StdError>> {
+        CredentialRotation::apply(self, headers);
+        Ok(())
+    }
+}
+
+// The credential headers stripped from the cross-origin redirects (by `strip_credentials()`)
+pub const CREDENTIAL_HEADERS: [&str; 3] = ["Authorization", "Proxy-Authorization", "Cookie"];
+
+// Remove the credential headers and the ones marked as sensitive
+pub fn strip_credentials(headers: &mut Headers) {
+    let names = headers
+        .iter()
+        .map(|(k, _)| k.to_string())
+        .filter(|k| {
+            headers.is_sensitive(k) || CREDENTIAL_HEADERS.iter().any(|v| v.eq_ignore_ascii_case(k))
+        })
+        .collect::<Vec<_>>();
+    for name in names {
+        headers.remove(&name);
+    }
+}
+
+/**
+`Redirect` A redirect response to be followed (by the `RedirectClient` of the bindings).
+
+# Remarks
+
+* The `301`/`302`/`303`/`307`/`308` responses with the `Location` are redirects.
+* The `method` is the one of the redirected request:
+  `303` is redirected as `GET` (except `HEAD`), and so are the `POST` of `301`/`302`,
+  the others keep the method and the body.
+
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirect {
+    pub status: u16,
+    pub method: String,
+    pub from: Url,
+    pub to: Url,
+    // The number of the redirects followed by the request (including this one)
+    pub count: u32,
+}
+impl Redirect {
+    // The redirect of the response (`None` if it's not a redirect or its `Location` is illegal)
+    pub fn new(
+        status: u16,
+        method: &str,
+        from: &Url,
+        location: Option<&str>,
+        count: u32,
+    ) -> Option<Self> {
+        let method = match status {
+            303 if method != "HEAD" => "GET",
+            301 | 302 if method == "POST" => "GET",
+            301 | 302 | 303 | 307 | 308 => method,
+            _ => return None,
+        };
+        Some(Redirect {
+            status,
+            method: method.to_string(),
+            from: from.clone(),
+            to: from.join(location?).ok()?,
+            count,
+        })
+    }
+
+    // Whether the redirect keeps the origin (the scheme, the host and the port)
+    pub fn is_same_origin(&self) -> bool {
+        self.from.origin() == self.to.origin()
+    }
+    // Whether the body is sent again (when the method is kept)
+    pub fn keeps_body(&self) -> bool {
+        matches!(self.status, 307 | 308) || (self.method != "GET" && self.method != "HEAD")
+    }
+}
+
+/*
+`RedirectAction` Whether the redirect is followed (`Stop` returns the redirect response as it is)
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectAction {
+    Follow,
+    Stop,
+}
+
+/*
+`RedirectPolicy` Decide whether the redirects are followed, and adjust the headers of the redirected requests
+(e.g. stripping the credentials)
+*/
+pub trait RedirectPolicy: Send + Sync {
+    fn redirect(&self, redirect: &Redirect, headers: &mut Headers) -> RedirectAction;
+}
+
+// The hard limit of the redirects followed by the `RedirectClient` of the bindings (whatever the policy decides)
+pub const MAX_REDIRECTS: u32 = 50;
+
+/**
+`DefaultRedirectPolicy` Follow at most `max_redirects` redirects,
+and strip the credentials of the cross-origin ones (by `strip_credentials()`).
+
+# Remarks
+
+* The origins include the schemes, so the credentials are stripped from the `https` to `http` redirects as well.
+* Once the credentials are stripped they're not sent again, even if the later redirects are back to the origin.
+
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DefaultRedirectPolicy {
+    pub max_redirects: u32,
+}
+impl Default for DefaultRedirectPolicy {
+    fn default() -> Self {
+        DefaultRedirectPolicy { max_redirects: 10 }
+    }
+}
+impl RedirectPolicy for DefaultRedirectPolicy {
+    fn redirect(&self, redirect: &Redirect, headers: &mut Headers) -> RedirectAction {
+        if redirect.count > self.max_redirects {
+            return RedirectAction::Stop;
+        }
+        if !redirect.is_same_origin() {
+            strip_credentials(headers);
+        }
+        RedirectAction::Follow
+    }
+}
+
+/*
+`Cookie` A cookie stored in the `CookieJar`
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    // The lowercase domain without the leading dot
+    pub domain: String,
+    // Sent to the exact `domain` only (without the `Domain` attribute)
+    pub host_only: bool,
+    pub path: String,
+    pub secure: bool,
+    // `None` for the session cookies
+    pub expires: Option<SystemTime>,
+}
+impl Cookie {
+    /**
+    Parse the `Set-Cookie` header value received from the `url`.
+
+    # Remarks
+
+    * It returns `None` for the illegal values and the cookies of the other domains.
+    * `Max-Age` takes precedence over `Expires` (in the IMF-fixdate format).
+
+    */
+    pub fn parse(set_cookie: &str, url: &Url, now: SystemTime) -> Option<Cookie> {
+        let host = url.host_str()?.to_ascii_lowercase();
+        let mut parts = set_cookie.split(';');
+        let (name, value) = parts.next()?.split_once('=')?;
+        let name = name.trim();
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut cookie = Cookie {
+            name: name.to_string(),
+            value: value.trim().trim_matches('"').to_string(),
+            domain: host.clone(),
+            host_only: true,
+            path: default_cookie_path(url.path()),
+            secure: false,
+            expires: None,
+        };
+        let mut max_age = None;
+        for attribute in parts {
+            let (key, value) = match attribute.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => (attribute.trim(), ""),
+            };
+            match key.to_ascii_lowercase().as_str() {
+                "domain" if !value.is_empty() => {
+                    let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                    if !domain_matches(&host, &domain) {
+                        return None;
+                    }
+                    cookie.domain = domain;
+                    cookie.host_only = false;
+                }
+                "path" if value.starts_with('/') => cookie.path = value.to_string(),
+                "secure" => cookie.secure = true,
+                "max-age" => {
+                    // The overflowing ones never expire
+                    max_age = value.parse::<i64>().ok().map(|seconds| match seconds {
+                        seconds if seconds <= 0 => Some(std::time::UNIX_EPOCH),
+                        seconds => now.checked_add(Duration::from_secs(seconds as u64)),
+                    })
+                }
+                "expires" if cookie.expires.is_none() => cookie.expires = parse_http_date(value),
+                _ => {}
+            }
+        }
+        if let Some(max_age) = max_age {
+            cookie.expires = max_age;
+        }
+        Some(cookie)
+    }
+
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        match self.expires {
+            Some(expires) => expires <= now,
+            None => false,
+        }
+    }
+
+    // Whether it should be sent to the `url` (by the domain, the path and `Secure`)
+    pub fn matches(&self, url: &Url) -> bool {
+        let host = match url.host_str() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return false,
+        };
+        let domain_matched = if self.host_only {
+            host == self.domain
+        } else {
+            domain_matches(&host, &self.domain)
+        };
+        let path = url.path();
+        let path_matched = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+
+        domain_matched && path_matched && (!self.secure || url.scheme() == "https")
+    }
+}
+
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&(".".to_string() + domain))
+}
+
+// The directory of the request path (RFC 6265)
+fn default_cookie_path(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+// Parse the IMF-fixdate (e.g. `Wed, 21 Oct 2015 07:28:00 GMT`, or with dashes)
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let value = value.replace('-', " ");
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    if parts.len() < 5 {
+        return None;
+    }
+    let day = parts[1]
+        .parse::<i64>()
+        .ok()
+        .filter(|v| (1..=31).contains(v))?;
+    let month = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ]
+    .iter()
+    .position(|v| parts[2].eq_ignore_ascii_case(v))? as i64
+        + 1;
+    let year = match parts[3].parse::<i64>().ok()? {
+        year if (0..70).contains(&year) => year + 2000,
+        year if (0..100).contains(&year) => year + 1900,
+        year => year,
+    };
+    let time: Vec<i64> = parts[4]
+        .split(':')
+        .map(|v| v.parse::<i64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    if time.len() != 3
+        || !(0..24).contains(&time[0])
+        || !(0..60).contains(&time[1])
+        || !(0..=60).contains(&time[2])
+    {
+        return None;
+    }
+
+    // The days from 1970-01-01 (the civil calendar), the years out of range are invalid
+    let y = if month <= 2 {
+        year.checked_sub(1)?
+    } else {
+        year
+    };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era.checked_mul(146097)?.checked_add(doe - 719468)?;
+
+    let seconds = days
+        .checked_mul(86400)?
+        .checked_add(time[0] * 3600 + time[1] * 60 + time[2])?;
+    if seconds < 0 {
+        return Some(std::time::UNIX_EPOCH);
+    }
+    std::time::UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+}
+
+/**
+`CookieJar` Store the cookies (by `Set-Cookie`) and send them back (by `Cookie`), e.g. for `Session`.
+
+# Remarks
+
+* The cookies are replaced by the ones of the same (name, domain, path),
+  and the expired ones are removed (by the `clock`).
+* The `HttpOnly`/`SameSite` attributes are ignored (there's no script nor site for the clients).
+
+*/
+pub struct CookieJar {
+    pub clock: Arc<dyn Clock>,
+    cookies: Mutex<Vec<Cookie>>,
+}
+impl Default for CookieJar {
+    fn default() -> Self {
+        CookieJar::new()
+    }
+}
+impl CookieJar {
+    pub fn new() -> Self {
+        CookieJar {
+            clock: system_clock(),
+            cookies: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Store the cookies of the `Set-Cookie` header values received from the `url`
+    pub fn store<'a>(&self, url: &Url, set_cookies: impl IntoIterator<Item = &'a str>) {
+        let now = self.clock.system_now();
+        for set_cookie in set_cookies {
+            if let Some(cookie) = Cookie::parse(set_cookie, url, now) {
+                self.insert(cookie);
+            }
+        }
+    }
+
+    pub fn insert(&self, cookie: Cookie) {
+        let now = self.clock.system_now();
+        let mut cookies = self.cookies.lock().unwrap();
+        let replaced = |v: &Cookie| {
+            v.name == cookie.name && v.domain == cookie.domain && v.path == cookie.path
+        };
+        cookies.retain(|v| !v.is_expired(now) && !replaced(v));
+        if !cookie.is_expired(now) {
+            cookies.push(cookie);
+        }
+    }
+
+    // The `Cookie` header value for the `url` (the longer paths first)
+    pub fn cookie_header(&self, url: &Url) -> Option<String> {
+        let now = self.clock.system_now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|v| !v.is_expired(now));
+
+        let mut matched: Vec<&Cookie> = cookies.iter().filter(|v| v.matches(url)).collect();
+        if matched.is_empty() {
+            return None;
+        }
+        matched.sort_by_key(|v| std::cmp::Reverse(v.path.len()));
+        Some(
+            matched
+                .iter()
+                .map(|v| format!("{}={}", v.name, v.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    // The cookies not expired
+    pub fn cookies(&self) -> Vec<Cookie> {
+        let now = self.clock.system_now();
+        let cookies = self.cookies.lock().unwrap();
+        cookies
+            .iter()
+            .filter(|v| !v.is_expired(now))
+            .cloned()
+            .collect()
+    }
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+}
+
+/*
+`Priority` The priority of a call when the concurrency is limited (by `PriorityLimiter`)
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    // Bulk/background calls
+    Low,
+    #[default]
+    Normal,
+    // Interactive calls
+    High,
+}
+
+/**
+`Extensions` The typemap of the per-call data (at most one value per type).
+
+Middlewares could pass data to each other by it (e.g. `RetryAttempt` by the `RetryClient`),
+and callers could tag the calls (e.g. feature flags, tenants) without abusing the headers.
+
+# Remarks
+
+* The values are shared (`Arc`) by the clones, so cloning the `RequestOptions` is cheap.
+* The extensions are equal if they have the same types of the same (shared) values.
+
+*/
+#[derive(Clone, Default)]
+pub struct Extensions {
+    map: HashMap<TypeId, (&'static str, Arc<dyn Any + Send + Sync>)>,
+}
+impl Extensions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Insert the value, the previous one of the same type is returned
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<Arc<T>> {
+        self.map
+            .insert(
+                TypeId::of::<T>(),
+                (std::any::type_name::<T>(), Arc::new(value)),
+            )
+            .and_then(|(_, v)| v.downcast::<T>().ok())
+    }
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.map
+            .get(&TypeId::of::<T>())
+            .and_then(|(_, v)| v.downcast_ref::<T>())
+    }
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<Arc<T>> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|(_, v)| v.downcast::<T>().ok())
+    }
+    pub fn contains<T: Any + Send + Sync>(&self) -> bool {
+        self.map.contains_key(&TypeId::of::<T>())
+    }
+    // Insert the values of the other one (overriding the ones of the same types)
+    pub fn extend(&mut self, other: Extensions) {
+        self.map.extend(other.map);
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+    pub fn clear(&mut self) {
+        self.map.clear();
+    }
+}
+// The values are not required to be `Debug`, so only the type names are formatted
+impl std::fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_set()
+            .entries(self.map.values().map(|(name, _)| name))
+            .finish()
+    }
+}
+impl PartialEq for Extensions {
+    fn eq(&self, other: &Self) -> bool {
+        self.map.len() == other.map.len()
+            && self.map.iter().all(|(k, (_, v))| match other.map.get(k) {
+                Some((_, other)) => Arc::ptr_eq(v, other),
+                None => false,
+            })
+    }
+}
+
+thread_local! {
+    static SCOPED_EXTENSIONS: RefCell<Option<Extensions>> = const { RefCell::new(None) };
+}
+
+// Restore the outer scope even if the polling panics
+struct ScopedExtensionsGuard(Option<Extensions>);
+impl Drop for ScopedExtensionsGuard {
+    fn drop(&mut self) {
+        let outer = self.0.take();
+        SCOPED_EXTENSIONS.with(|v| *v.borrow_mut() = outer);
+    }
+}
+
+/*
+`WithExtensions` The future polled within the scope of the `Extensions` (by `with_extensions()`)
+*/
+pub struct WithExtensions<F> {
+    extensions: Extensions,
+    future: Pin<Box<F>>,
+}
+impl<F: Future> Future for WithExtensions<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let outer = SCOPED_EXTENSIONS.with(|v| v.borrow().clone());
+        let mut extensions = outer.clone().unwrap_or_default();
+        extensions.extend(this.extensions.clone());
+        SCOPED_EXTENSIONS.with(|v| *v.borrow_mut() = Some(extensions));
+
+        let _guard = ScopedExtensionsGuard(outer);
+        this.future.as_mut().poll(cx)
+    }
+}
+
+/**
+Run the calls of the `future` within the scope of the `extensions`,
+they're added to the `RequestOptions.extensions` of the requests made in the scope
+(by `make_request()` of the bindings), e.g. the per-call `DelegatedIdentity`.
+
+# Remarks
+
+* The nested scopes are merged (the inner values take precedence).
+* The scope is bound to the polling of the `future`,
+  so the calls spawned to the other tasks/threads are out of the scope.
+
+*/
+pub fn with_extensions<F: Future>(extensions: Extensions, future: F) -> WithExtensions<F> {
+    WithExtensions {
+        extensions,
+        future: Box::pin(future),
+    }
+}
+
+// The `Extensions` of the current scope (by `with_extensions()`)
+pub fn scoped_extensions() -> Extensions {
+    SCOPED_EXTENSIONS.with(|v| v.borrow().clone().unwrap_or_default())
+}
+
+/*
+`RequestOptions` The per-request options carried by the requests of bindings
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestOptions {
+    pub priority: Priority,
+    // The relative url before the path params are applied (set by `make_request()` of the bindings)
+    pub url_template: Option<String>,
+    // The caller-provided tags (e.g. recorded by the `AuditLog`)
+    pub tags: Vec<(String, String)>,
+    // The per-call data of middlewares/callers
+    pub extensions: Extensions,
+    // The absolute url called verbatim instead of the relative url (by the `AbsoluteUrl` extension)
+    pub absolute_url: Option<Url>,
+    // Don't send the credentials (by the `AbsoluteUrl` extension):
+    // the `Authorization` of the default headers, the `AuthProvider` of the `Session`,
+    // the `CredentialRotation` and the `ExchangedToken` are skipped
+    // (the custom interceptors should check it as well)
+    pub skip_auth: bool,
+    // Connect to the address instead of the resolved host of the url (by the `ConnectTo` extension),
+    // keeping the `Host` (and the SNI) of the url
+    pub connect_to: Option<SocketAddr>,
+    // The TLS SNI & the hostname verified by the certificate instead of the host of the url
+    // (by the `SniHostname` extension), the `Host` is still the one of the url
+    pub sni_hostname: Option<String>,
+}
+
+/*
+`RetryAttempt` The extension of the attempt number (`0` for the first attempt),
+set on the requests (`RequestOptions::extensions`) and the responses by the retry clients.
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryAttempt(pub u32);
+
+/*
+`AcceptMediaType` The extension of the media type accepted by the API (e.g. by `ApiBuilder::accept()`),
+sent as the `Accept` header of the requests.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AcceptMediaType(pub String);
+
+/*
+`AbsoluteUrl` The extension of the absolute url called verbatim instead of the relative url of the API
+(e.g. the signed urls), set by the `call_absolute()` of the APIs.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AbsoluteUrl {
+    pub url: Url,
+    // Don't send the credentials (e.g. the signed urls rejecting the extra ones)
+    pub skip_auth: bool,
+}
+
+/*
+`ConnectTo` The extension of the address connected instead of the host of the url
+(e.g. debugging a specific backend behind a VIP, like `curl --connect-to`), set as `RequestOptions::connect_to`
+(connected by `HyperClient`/`UreqClient`, the default clients).
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectTo(pub SocketAddr);
+
+/*
+`SniHostname` The extension of the TLS SNI (& the hostname verified by the certificate) instead of the host of the url
+(e.g. the fronted domains, or the CDNs serving the origins by other names), set as `RequestOptions::sni_hostname`
+(by `HyperClient` with `ConnectToConnector`, or `UreqClient`).
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SniHostname(pub String);
+
+// The future of the body (& the content type) produced by `BodyFactory`
+pub type BodyFactoryFuture<B> =
+    Pin<Box<dyn Future<Output = StdResult<(String, B), Box<dyn StdError>>>>>;
+
+/**
+`BodyFactory` The extension producing a fresh request body (& its content type) per attempt,
+so the streaming bodies (read only once) could be sent again by the retries/redirects.
+
+# Remarks
+
+* It's set by the `call_with_body_factory()` of the APIs, the first attempt is sent by the first body produced.
+* The bodies are produced again (e.g. by `RetryClient` of hyper, or `UreqClient` for the consumed `StreamingBody`)
+  instead of buffering them, and the `Content-Type` is replaced (e.g. the new boundary of the multipart bodies).
+* `B` is the body type of the client (e.g. `hyper::Body`, or `Box<dyn Read + Send + Sync>` for ureq).
+
+*/
+pub struct BodyFactory<B>(Arc<dyn Fn() -> BodyFactoryFuture<B> + Send + Sync>);
+impl<B> BodyFactory<B> {
+    pub fn new<F, Fut>(f: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = StdResult<(String, B), Box<dyn StdError>>> + 'static,
+    {
+        BodyFactory(Arc::new(move || Box::pin(f()) as BodyFactoryFuture<B>))
+    }
+
+    // Produce a fresh body (& its content type)
+    pub fn produce(&self) -> BodyFactoryFuture<B> {
+        (self.0)()
+    }
+}
+impl<B> Clone for BodyFactory<B> {
+    fn clone(&self) -> Self {
+        BodyFactory(self.0.clone())
+    }
+}
+impl<B> std::fmt::Debug for BodyFactory<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("BodyFactory").finish()
+    }
+}
+
+/*
+`HookPanicked` A user-provided hook (e.g. an interceptor, a serializer/deserializer or a callback) panicked,
+the panic is isolated to the call as this error (instead of poisoning the shared states).
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookPanicked {
+    hook: String,
+    details: String,
+}
+impl StdError for HookPanicked {}
+impl HookPanicked {
+    pub fn new(hook: impl Into<String>, msg: impl Into<String>) -> HookPanicked {
+        HookPanicked {
+            hook: hook.into(),
+            details: msg.into(),
+        }
+    }
+    // The kind of the hook (e.g. `interceptor`, `response_deserializer`)
+    pub fn hook(&self) -> &str {
+        &self.hook
+    }
+}
+impl std::fmt::Display for HookPanicked {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "HookPanicked: {} panicked: {}", self.hook, self.details)
+    }
+}
+
+// The message of the panic (`panic!()` with a `&str`/`String`)
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(message), _) => message.to_string(),
+        (None, Some(message)) => message.clone(),
+        (None, None) => "(unknown panic)".to_string(),
+    }
+}
+
+// Call the `hook`, its panic is caught as `HookPanicked`
+pub fn catch_hook_panic<T>(hook: &str, f: impl FnOnce() -> T) -> StdResult<T, HookPanicked> {
+    catch_unwind(AssertUnwindSafe(f))
+        .map_err(|payload| HookPanicked::new(hook, panic_message(&*payload)))
+}
+
+/*
+`CatchHookPanic` The future of a hook, its panic (while it's polled) is caught as `HookPanicked`,
+made by `catch_hook_panic_async()`.
+*/
+pub struct CatchHookPanic<F> {
+    hook: &'static str,
+    future: Pin<Box<F>>,
+}
+impl<F: Future> Future for CatchHookPanic<F> {
+    type Output = StdResult<F::Output, HookPanicked>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let hook = self.hook;
+        let future = self.future.as_mut();
+        match catch_hook_panic(hook, move || future.poll(cx)) {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+pub fn catch_hook_panic_async<F: Future>(hook: &'static str, future: F) -> CatchHookPanic<F> {
+    CatchHookPanic {
+        hook,
+        future: Box::pin(future),
+    }
+}
+
+/*
+`DelegatedIdentity` The extension of the identity a call is made on behalf of (e.g. the end user and the tenant),
+propagated as the headers by `InterceptorFunc::new_for_delegation()`.
+*/
+#[derive(Clone, Default, PartialEq)]
+pub struct DelegatedIdentity {
+    pub subject: String,
+    pub tenant: Option<String>,
+    // The token of the subject, exchanged for the token of the calls by `TokenExchanger` (**feature: token_exchange**)
+    pub subject_token: Option<String>,
+}
+impl DelegatedIdentity {
+    pub fn new(subject: impl Into<String>) -> Self {
+        DelegatedIdentity {
+            subject: subject.into(),
+            ..Default::default()
+        }
+    }
+    pub fn with_tenant(mut self, tenant: impl Into<String>) -> Self {
+        self.tenant = Some(tenant.into());
+        self
+    }
+    pub fn with_subject_token(mut self, subject_token: impl Into<String>) -> Self {
+        self.subject_token = Some(subject_token.into());
+        self
+    }
+}
+impl std::fmt::Debug for DelegatedIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("DelegatedIdentity")
+            .field("subject", &self.subject)
+            .field("tenant", &self.tenant)
+            .field(
+                "subject_token",
+                &self.subject_token.as_ref().map(|_| REDACTED),
+            )
+            .finish()
+    }
+}
+
+/*
+`ExchangedToken` The extension of the access token exchanged for the `DelegatedIdentity` (by `TokenExchanger`),
+sent as `Authorization: Bearer <token>` by `InterceptorFunc::new_for_delegation()`.
+*/
+#[derive(Clone, PartialEq)]
+pub struct ExchangedToken(pub String);
+impl std::fmt::Debug for ExchangedToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_tuple("ExchangedToken").field(&REDACTED).finish()
+    }
+}
+
+/*
+`DelegationHeaders` The header names of the `DelegatedIdentity` (`None` to skip the tenant)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelegationHeaders {
+    pub subject: String,
+    pub tenant: Option<String>,
+}
+impl Default for DelegationHeaders {
+    fn default() -> Self {
+        DelegationHeaders {
+            subject: "X-On-Behalf-Of".to_string(),
+            tenant: Some("X-Tenant-Id".to_string()),
+        }
+    }
+}
+impl DelegationHeaders {
+    // Set the headers of the identity (validated), the existing ones are replaced
+    pub fn apply(
+        &self,
+        identity: &DelegatedIdentity,
+        headers: &mut Headers,
+    ) -> StdResult<(), InvalidHeaderError> {
+        HeaderValueBuilder::new(identity.subject.clone())
+            .insert_into(headers, self.subject.clone())?;
+        if let (Some(name), Some(tenant)) = (&self.tenant, &identity.tenant) {
+            HeaderValueBuilder::new(tenant.clone()).insert_into(headers, name.clone())?;
+        }
+        Ok(())
+    }
+}
+
+/*
+`Locale` The extension of the locale of a call (or the default one of a service),
+sent as `Accept-Language` and the unit/timezone headers by `InterceptorFunc::new_for_locale()`.
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Locale {
+    // The value of `Accept-Language` (e.g. `fr-CA, fr;q=0.8`)
+    pub language: String,
+    // e.g. `metric` or `imperial`
+    pub units: Option<String>,
+    // e.g. `Asia/Taipei`
+    pub timezone: Option<String>,
+}
+impl Locale {
+    pub fn new(language: impl Into<String>) -> Self {
+        Locale {
+            language: language.into(),
+            ..Default::default()
+        }
+    }
+    pub fn with_units(mut self, units: impl Into<String>) -> Self {
+        self.units = Some(units.into());
+        self
+    }
+    pub fn with_timezone(mut self, timezone: impl Into<String>) -> Self {
+        self.timezone = Some(timezone.into());
+        self
+    }
+}
+
+/*
+`LocaleHeaders` The header names of the units & the timezone of the `Locale` (`None` to skip them)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocaleHeaders {
+    pub units: Option<String>,
+    pub timezone: Option<String>,
+}
+impl Default for LocaleHeaders {
+    fn default() -> Self {
+        LocaleHeaders {
+            units: Some("X-Units".to_string()),
+            timezone: Some("X-Timezone".to_string()),
+        }
+    }
+}
+impl LocaleHeaders {
+    // Set the headers of the locale (validated), the existing ones are replaced
+    pub fn apply(
+        &self,
+        locale: &Locale,
+        headers: &mut Headers,
+    ) -> StdResult<(), InvalidHeaderError> {
+        HeaderValueBuilder::new(locale.language.clone()).insert_into(headers, "Accept-Language")?;
+        if let (Some(name), Some(units)) = (&self.units, &locale.units) {
+            HeaderValueBuilder::new(units.clone()).insert_into(headers, name.clone())?;
+        }
+        if let (Some(name), Some(timezone)) = (&self.timezone, &locale.timezone) {
+            HeaderValueBuilder::new(timezone.clone()).insert_into(headers, name.clone())?;
+        }
+        Ok(())
+    }
+}
+
+/*
+`ResponseLanguage` The extension receiving the `Content-Language` of the responses (set by the bindings),
+scoped by `localized()`.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct ResponseLanguage(Arc<Mutex<Option<String>>>);
+impl ResponseLanguage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set(&self, content_language: Option<String>) {
+        *self.0.lock().unwrap() = content_language;
+    }
+    pub fn get(&self) -> Option<String> {
+        self.0.lock().unwrap().clone()
+    }
+
+    // Set the `Content-Language` to the `ResponseLanguage` of the request (if any)
+    pub fn record(options: &RequestOptions, content_language: Option<&str>) {
+        if let Some(response_language) = options.extensions.get::<ResponseLanguage>() {
+            response_language.set(content_language.map(|v| v.to_string()));
+        }
+    }
+}
+
+/*
+`Localized` The output of the calls by `localized()` with the `Content-Language` of the response
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct Localized<T> {
+    pub value: T,
+    pub content_language: Option<String>,
+}
+
+/**
+Run the calls of the `future` in the `locale` (overriding the default one of the service, if it's `Some`),
+and get the `Content-Language` of the response as well.
+
+# Remarks
+
+* The `locale` is sent by the interceptor of `add_interceptor_locale()` (`InterceptorFunc::new_for_locale()`).
+* The `content_language` is the one of the last response in the scope (`None` without `Content-Language`).
+
+*/
+pub async fn localized<F: Future>(locale: Option<Locale>, future: F) -> Localized<F::Output> {
+    let response_language = ResponseLanguage::new();
+    let mut extensions = Extensions::new();
+    extensions.insert(response_language.clone());
+    if let Some(locale) = locale {
+        extensions.insert(locale);
+    }
+    let value = with_extensions(extensions, future).await;
+    Localized {
+        value,
+        content_language: response_language.get(),
+    }
+}
+
+/*
+`ReplayNonce` The anti-replay parameters of a request as sent (set by `InterceptorFunc::new_for_replay_nonce()`),
+for the signers covering them in the canonical strings.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayNonce {
+    pub nonce: String,
+    pub timestamp: String,
+}
+
+/*
+`ReplayNonceHeaders` The header names of the `ReplayNonce`,
+the timestamps are the seconds since the epoch (or the milliseconds by `timestamp_millis`)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayNonceHeaders {
+    pub nonce: String,
+    pub timestamp: String,
+    pub timestamp_millis: bool,
+}
+impl Default for ReplayNonceHeaders {
+    fn default() -> Self {
+        ReplayNonceHeaders {
+            nonce: "X-Nonce".to_string(),
+            timestamp: "X-Timestamp".to_string(),
+            timestamp_millis: false,
+        }
+    }
+}
+impl ReplayNonceHeaders {
+    pub fn new(nonce: impl Into<String>, timestamp: impl Into<String>) -> Self {
+        ReplayNonceHeaders {
+            nonce: nonce.into(),
+            timestamp: timestamp.into(),
+            timestamp_millis: false,
+        }
+    }
+    pub fn with_timestamp_millis(mut self, timestamp_millis: bool) -> Self {
+        self.timestamp_millis = timestamp_millis;
+        self
+    }
+
+    /**
+    Set a new nonce (by `generate_id()`) & the timestamp of `now` as the headers,
+    the ones set already (e.g. by the call) are kept. It returns the `ReplayNonce` of the headers.
+    */
+    pub fn apply(
+        &self,
+        now: SystemTime,
+        headers: &mut Headers,
+    ) -> StdResult<ReplayNonce, InvalidHeaderError> {
+        let nonce = match headers.get(&self.nonce) {
+            Some(nonce) => nonce.to_string(),
+            None => {
+                let nonce = generate_id();
+                HeaderValueBuilder::new(nonce.clone()).insert_into(headers, self.nonce.clone())?;
+                nonce
+            }
+        };
+        let timestamp = match headers.get(&self.timestamp) {
+            Some(timestamp) => timestamp.to_string(),
+            None => {
+                let since_epoch = now
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default();
+                let timestamp = if self.timestamp_millis {
+                    since_epoch.as_millis().to_string()
+                } else {
+                    since_epoch.as_secs().to_string()
+                };
+                HeaderValueBuilder::new(timestamp.clone())
+                    .insert_into(headers, self.timestamp.clone())?;
+                timestamp
+            }
+        };
+        Ok(ReplayNonce { nonce, timestamp })
+    }
+}
+
+/*
+`OfflineSwitch` The shared switch of the offline mode,
+e.g. shared by the services and the `UploadQueue` flushing the writes queued while it's offline.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct OfflineSwitch(Arc<AtomicBool>);
+impl OfflineSwitch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set_offline(&self, offline: bool) {
+        self.0.store(offline, Ordering::SeqCst);
+    }
+    pub fn is_offline(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/*
+`OfflineError` The call isn't sent since it's offline,
+the `queued_id` is the id of the `QueuedRequest` if the call (a write) is queued to be sent later.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct OfflineError {
+    details: String,
+    pub queued_id: Option<String>,
+}
+impl StdError for OfflineError {}
+impl OfflineError {
+    pub fn new(msg: impl Into<String>) -> OfflineError {
+        OfflineError {
+            details: msg.into(),
+            queued_id: None,
+        }
+    }
+    pub fn new_queued(msg: impl Into<String>, queued_id: impl Into<String>) -> OfflineError {
+        OfflineError {
+            details: msg.into(),
+            queued_id: Some(queued_id.into()),
+        }
+    }
+}
+impl std::fmt::Display for OfflineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "OfflineError: {}", self.details)
+    }
+}
+
+/*
+`ReadOnlyMode` The call isn't sent since the service is read-only (`ReadOnlyGuard`),
+the `method` & the `relative_url` (the url template) are the ones of the rejected API.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadOnlyMode {
+    details: String,
+    pub method: String,
+    pub relative_url: String,
+}
+impl StdError for ReadOnlyMode {}
+impl ReadOnlyMode {
+    pub fn new(method: impl Into<String>, relative_url: impl Into<String>) -> ReadOnlyMode {
+        let (method, relative_url) = (method.into(), relative_url.into());
+        ReadOnlyMode {
+            details: format!(
+                "{} {} isn't sent in the read-only mode",
+                method, relative_url
+            ),
+            method,
+            relative_url,
+        }
+    }
+}
+impl std::fmt::Display for ReadOnlyMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ReadOnlyMode: {}", self.details)
+    }
+}
+
+/**
+`ReadOnlyGuard` The read-only (audit) mode of a service (`set_read_only()`):
+the calls other than `GET`/`HEAD` are rejected with `ReadOnlyMode`, unless their APIs are allowed explicitly.
+
+# Remarks
+
+* e.g. a safety net for running the integrations against the production data in the audit mode.
+* The APIs are allowed by the methods & the relative urls as they're made (the templates before the path params),
+  e.g. `allow("POST", "/products/search")`.
+* The rejected writes aren't queued by the offline queue (**feature: queue**) either.
+
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReadOnlyGuard {
+    allowed: Vec<(String, String)>,
+}
+impl ReadOnlyGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // Allow the API of the `method` & the `relative_url` in the read-only mode
+    pub fn allow(mut self, method: impl Into<String>, relative_url: impl Into<String>) -> Self {
+        self.allowed
+            .push((method.into().to_ascii_uppercase(), relative_url.into()));
+        self
+    }
+
+    pub fn is_allowed(&self, method: &str, relative_url: &str) -> bool {
+        matches!(method, "GET" | "HEAD")
+            || self
+                .allowed
+                .iter()
+                .any(|(m, url)| m.eq_ignore_ascii_case(method) && url == relative_url)
+    }
+    pub fn check(&self, method: &str, relative_url: &str) -> StdResult<(), ReadOnlyMode> {
+        if self.is_allowed(method, relative_url) {
+            Ok(())
+        } else {
+            Err(ReadOnlyMode::new(method, relative_url))
+        }
+    }
+}
+
+/*
+`ItemError` The error of an item of a batch/bulk operation,
+the `index` is the position of the item in the input (and the `key` identifies it, if any, e.g. its id).
+*/
+#[derive(Debug, Clone)]
+pub struct ItemError {
+    pub index: usize,
+    pub key: Option<String>,
+    // Shared by the items failed together (e.g. the ones of a batch)
+    pub error: Arc<dyn StdError>,
+}
+impl ItemError {
+    pub fn new(index: usize, error: impl Into<Arc<dyn StdError>>) -> Self {
+        ItemError {
+            index,
+            key: None,
+            error: error.into(),
+        }
+    }
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+}
+impl std::fmt::Display for ItemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "#{} ({}): {}", self.index, key, self.error),
+            None => write!(f, "#{}: {}", self.index, self.error),
+        }
+    }
+}
+
+// The max item errors listed by the summary of `AggregateError`
+const AGGREGATE_ERROR_SUMMARY_ITEMS: usize = 3;
+
+/**
+`AggregateError` The per-item errors of a batch/bulk operation (the partial failures) of the `total` items,
+sorted by the indices.
+
+# Remarks
+
+* The summary (`Display`) lists the first few item errors, e.g.
+  `AggregateError: 2 of 10 items failed: #3: ...; #7 (sku-7): ...`.
+* The `source()` is the error of the first failed item.
+
+*/
+#[derive(Debug, Clone)]
+pub struct AggregateError {
+    pub total: usize,
+    pub errors: Vec<ItemError>,
+}
+impl AggregateError {
+    pub fn new(total: usize, mut errors: Vec<ItemError>) -> Self {
+        errors.sort_by_key(|e| e.index);
+        AggregateError { total, errors }
+    }
+
+    // Collect the results of the items, the values if all of them are `Ok`, or the `AggregateError` of the `Err`s
+    pub fn collect<T, E: Into<Box<dyn StdError>>>(
+        results: impl IntoIterator<Item = StdResult<T, E>>,
+    ) -> StdResult<Vec<T>, AggregateError> {
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        let mut total = 0;
+        for (index, result) in results.into_iter().enumerate() {
+            total += 1;
+            match result {
+                Ok(value) => values.push(value),
+                Err(e) => errors.push(ItemError::new(index, Arc::from(e.into()))),
+            }
+        }
+        if errors.is_empty() {
+            Ok(values)
+        } else {
+            Err(AggregateError::new(total, errors))
+        }
+    }
+
+    // The indices of the failed items
+    pub fn indices(&self) -> Vec<usize> {
+        self.errors.iter().map(|e| e.index).collect()
+    }
+    pub fn get(&self, index: usize) -> Option<&ItemError> {
+        self.errors
+            .binary_search_by_key(&index, |e| e.index)
+            .ok()
+            .map(|i| &self.errors[i])
+    }
+    pub fn get_by_key(&self, key: &str) -> Option<&ItemError> {
+        self.errors.iter().find(|e| e.key.as_deref() == Some(key))
+    }
+}
+impl StdError for AggregateError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.errors.first().map(|e| e.error.as_ref())
+    }
+}
+impl std::fmt::Display for AggregateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "AggregateError: {} of {} items failed",
+            self.errors.len(),
+            self.total
+        )?;
+        for (i, e) in self
+            .errors
+            .iter()
+            .take(AGGREGATE_ERROR_SUMMARY_ITEMS)
+            .enumerate()
+        {
+            write!(f, "{}{}", if i == 0 { ": " } else { "; " }, e)?;
+        }
+        if self.errors.len() > AGGREGATE_ERROR_SUMMARY_ITEMS {
+            write!(
+                f,
+                "; and {} more",
+                self.errors.len() - AGGREGATE_ERROR_SUMMARY_ITEMS
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/*
+`ShuttingDownError` The call isn't accepted since the service is shutting down (`shutdown()`),
+or the in-flight calls aren't finished within the grace period (`in_flight` is the number of them).
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShuttingDownError {
+    details: String,
+    pub in_flight: usize,
+}
+impl StdError for ShuttingDownError {}
+impl ShuttingDownError {
+    pub fn new(msg: impl Into<String>) -> ShuttingDownError {
+        ShuttingDownError {
+            details: msg.into(),
+            in_flight: 0,
+        }
+    }
+    pub fn new_timed_out(msg: impl Into<String>, in_flight: usize) -> ShuttingDownError {
+        ShuttingDownError {
+            details: msg.into(),
+            in_flight,
+        }
+    }
+}
+impl std::fmt::Display for ShuttingDownError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ShuttingDownError: {}", self.details)
+    }
+}
+
+/**
+`DrainGate` The shared gate of the calls of the services, tracking the in-flight calls
+for the graceful shutdown.
+
+# Remarks
+
+* The calls are in flight from `enter()` until the returned `DrainPermit` is dropped.
+* After `close()` the new calls are rejected with `ShuttingDownError`,
+  and `drain()` waits for the in-flight ones.
+* It's runtime independent (the waiting is done by `futures::channel::oneshot`).
+
+*/
+#[derive(Debug, Clone, Default)]
+pub struct DrainGate(Arc<Mutex<DrainGateState>>);
+#[derive(Debug, Default)]
+struct DrainGateState {
+    closed: bool,
+    in_flight: usize,
+    waiters: Vec<oneshot::Sender<()>>,
+}
+impl DrainGate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn enter(&self) -> StdResult<DrainPermit, ShuttingDownError> {
+        let mut state = self.0.lock().unwrap();
+        if state.closed {
+            return Err(ShuttingDownError::new("the call isn't accepted"));
+        }
+        state.in_flight += 1;
+        Ok(DrainPermit { gate: self.clone() })
+    }
+    // Reject the new calls
+    pub fn close(&self) {
+        self.0.lock().unwrap().closed = true;
+    }
+    // Accept the new calls again (e.g. after a failed shutdown)
+    pub fn reopen(&self) {
+        self.0.lock().unwrap().closed = false;
+    }
+    pub fn is_closed(&self) -> bool {
+        self.0.lock().unwrap().closed
+    }
+    pub fn in_flight(&self) -> usize {
+        self.0.lock().unwrap().in_flight
+    }
+    // Wait for the in-flight calls within the `grace` (by the `clock`)
+    pub async fn drain(
+        &self,
+        grace: Duration,
+        clock: &dyn Clock,
+    ) -> StdResult<(), ShuttingDownError> {
+        let receiver = {
+            let mut state = self.0.lock().unwrap();
+            if state.in_flight == 0 {
+                return Ok(());
+            }
+            let (sender, receiver) = oneshot::channel();
+            state.waiters.push(sender);
+            receiver
+        };
+        match futures::future::select(receiver, clock.sleep(grace)).await {
+            futures::future::Either::Left(_) => Ok(()),
+            futures::future::Either::Right(_) => {
+                let in_flight = self.in_flight();
+                Err(ShuttingDownError::new_timed_out(
+                    format!("{} calls are in flight after {:?}", in_flight, grace),
+                    in_flight,
+                ))
+            }
+        }
+    }
+}
+
+// `DrainPermit` The call is in flight until it's dropped
+#[derive(Debug)]
+pub struct DrainPermit {
+    gate: DrainGate,
+}
+impl Drop for DrainPermit {
+    fn drop(&mut self) {
+        let mut state = self.gate.0.lock().unwrap();
+        state.in_flight -= 1;
+        if state.in_flight == 0 {
+            for waiter in state.waiters.drain(..) {
+                let _ = waiter.send(());
+            }
+        }
+    }
+}
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/*
+`MaintenanceWindow` A maintenance window of the upstream (by `MaintenancePolicy`)
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaintenanceWindow {
+    // Every day, from the time of day (since the midnight of UTC) for the duration
+    Daily { start: Duration, duration: Duration },
+    // Once, from the start until the end (e.g. the announced ones)
+    Once { start: SystemTime, end: SystemTime },
+}
+impl MaintenanceWindow {
+    pub fn daily(start: Duration, duration: Duration) -> Self {
+        MaintenanceWindow::Daily { start, duration }
+    }
+    pub fn once(start: SystemTime, end: SystemTime) -> Self {
+        MaintenanceWindow::Once { start, end }
+    }
+
+    // The end of the window if the time is in it
+    pub fn end_of(&self, now: SystemTime) -> Option<SystemTime> {
+        match *self {
+            MaintenanceWindow::Daily { start, duration } => {
+                let since_epoch = now.duration_since(std::time::UNIX_EPOCH).ok()?;
+                let day = Duration::from_secs(SECONDS_PER_DAY);
+                let time_of_day = Duration::from_secs(since_epoch.as_secs() % SECONDS_PER_DAY)
+                    + Duration::from_nanos(since_epoch.subsec_nanos() as u64);
+                let start = Duration::from_secs(start.as_secs() % SECONDS_PER_DAY);
+                // Since the start of the latest window (it could be started yesterday)
+                let elapsed = match time_of_day.checked_sub(start) {
+                    Some(elapsed) => elapsed,
+                    None => time_of_day + day - start,
+                };
+                (elapsed < duration).then(|| now + (duration - elapsed))
+            }
+            MaintenanceWindow::Once { start, end } => (start <= now && now < end).then_some(end),
+        }
+    }
+}
+
+/*
+`MaintenanceAction` What's done with the calls in the maintenance windows
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MaintenanceAction {
+    // Rejected with `MaintenanceWindowError`
+    #[default]
+    Reject,
+    // Sent after the window (waiting by the `Clock` of the service)
+    Defer,
+    // The writes are queued to the offline queue (**feature: queue**), the others are rejected
+    Queue,
+}
+
+/*
+`MaintenanceWindowError` The call is rejected (or queued) in the maintenance window of the upstream
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaintenanceWindowError {
+    details: String,
+    // The end of the window
+    pub until: SystemTime,
+}
+impl StdError for MaintenanceWindowError {}
+impl MaintenanceWindowError {
+    pub fn new(msg: impl Into<String>, until: SystemTime) -> MaintenanceWindowError {
+        MaintenanceWindowError {
+            details: msg.into(),
+            until,
+        }
+    }
+}
+impl std::fmt::Display for MaintenanceWindowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "MaintenanceWindowError: {}", self.details)
+    }
+}
+
+/**
+`MaintenancePolicy` The maintenance windows of the upstream of a service (set by `set_maintenance_policy()`),
+and what's done with the calls in them.
+
+# Remarks
+
+* The windows are checked by the `Clock` of the service (`system_now()`), the overlapping ones are merged
+  (the calls wait until none of them covers the time).
+* The queued writes are replayed by the `UploadQueue` of the offline queue,
+  and it doesn't send them in the windows.
+
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MaintenancePolicy {
+    pub windows: Vec<MaintenanceWindow>,
+    pub action: MaintenanceAction,
+}
+impl MaintenancePolicy {
+    pub fn new(windows: Vec<MaintenanceWindow>, action: MaintenanceAction) -> Self {
+        MaintenancePolicy { windows, action }
+    }
+
+    // The end of the (merged) windows if the time is in any of them
+    pub fn window_end(&self, now: SystemTime) -> Option<SystemTime> {
+        let mut end = None;
+        let mut at = now;
+        // Each of the chained windows extends the end at most once
+        for _ in 0..self.windows.len() {
+            match self.windows.iter().filter_map(|w| w.end_of(at)).max() {
+                Some(window_end) => {
+                    end = Some(window_end);
+                    at = window_end;
+                }
+                None => break,
+            }
+        }
+        end
+    }
+
+    /**
+    Check the call against the windows (by the `clock`).
+
+    # Remarks
+
+    * It's `Ok` if it's out of the windows (or after waiting for the end of the window by `Defer`),
+      otherwise it's the `MaintenanceWindowError` (to be queued by `Queue`).
+
+    */
+    pub async fn enter(&self, clock: &dyn Clock) -> StdResult<(), MaintenanceWindowError> {
+        let now = clock.system_now();
+        let until = match self.window_end(now) {
+            Some(until) => until,
+            None => return Ok(()),
+        };
+        match self.action {
+            MaintenanceAction::Defer => {
+                clock
+                    .sleep(until.duration_since(now).unwrap_or_default())
+                    .await;
+                Ok(())
+            }
+            MaintenanceAction::Reject | MaintenanceAction::Queue => Err(
+                MaintenanceWindowError::new(format!("in the window until {:?}", until), until),
+            ),
+        }
+    }
+}
+
+// The default interval between the keep-alive probes (shorter than the common NAT/LB idle timeouts)
+pub const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+// The default timeout of each keep-alive probe
+pub const DEFAULT_KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/*
+`KeepAliveMethod` The method of the keep-alive probes (the cheap ones without bodies)
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeepAliveMethod {
+    #[default]
+    Head,
+    Options,
+}
+impl KeepAliveMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeepAliveMethod::Head => "HEAD",
+            KeepAliveMethod::Options => "OPTIONS",
+        }
+    }
+}
+
+/**
+`KeepAliveProbe` Send a keep-alive probe (`method`, `url`, `timeout`) by the pooled connections of a client,
+resolved with the response status.
+
+# Remarks
+
+* It's made by `BaseClient::keep_alive_probe()`, sharing the connection pool of the client
+  (e.g. the clones of `hyper::Client`/`ureq::Agent`).
+* The interceptors of the service are not applied, and any status is regarded as alive.
+
+*/
+pub type KeepAliveProbe =
+    Arc<dyn Fn(KeepAliveMethod, Url, Duration) -> KeepAliveProbeFuture + Send + Sync>;
+pub type KeepAliveProbeFuture =
+    Pin<Box<dyn Future<Output = StdResult<u16, Box<dyn StdError + Send + Sync>>> + Send>>;
+
+/*
+`KeepAliveOptions` The options of the keep-alive pinger (`start_keep_alive()`)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeepAliveOptions {
+    pub interval: Duration,
+    pub method: KeepAliveMethod,
+    pub timeout: Duration,
+}
+impl Default for KeepAliveOptions {
+    fn default() -> Self {
+        KeepAliveOptions {
+            interval: DEFAULT_KEEP_ALIVE_INTERVAL,
+            method: KeepAliveMethod::default(),
+            timeout: DEFAULT_KEEP_ALIVE_TIMEOUT,
+        }
+    }
+}
+impl KeepAliveOptions {
+    pub fn new(interval: Duration) -> Self {
+        KeepAliveOptions {
+            interval,
+            ..Self::default()
+        }
+    }
+    pub fn method(mut self, method: KeepAliveMethod) -> Self {
+        self.method = method;
+        self
+    }
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+/*
+`KeepAliveStats` The probes sent by a `KeepAliveWorker`
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KeepAliveStats {
+    pub sent: u64,
+    // The connection failures & the timeouts
+    pub failed: u64,
+    pub last_status: Option<u16>,
+}
+
+/*
+`KeepAliveError` The keep-alive pinger isn't started
+(e.g. the client doesn't support the probes, or the worker thread isn't spawned)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeepAliveError {
+    details: String,
+}
+impl StdError for KeepAliveError {}
+impl KeepAliveError {
+    pub fn new(msg: impl Into<String>) -> KeepAliveError {
+        KeepAliveError {
+            details: msg.into(),
+        }
+    }
+}
+impl std::fmt::Display for KeepAliveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "KeepAliveError: {}", self.details)
+    }
+}
+
+/**
+`KeepAliveWorker` The background thread sending the keep-alive probes every `interval`,
+keeping the NAT mappings & the pooled connections of long-idle clients warm.
+
+# Remarks
+
+* The probes are scheduled by the `clock` (the first one is sent after an `interval`).
+* It stops promptly by `stop()` or dropping it (e.g. with the service), even if a probe is in flight
+  (the blocking probes, e.g. of ureq, are bounded by the `timeout`).
+* The HTTP/2 connections of hyper could be kept alive by PING frames as well
+  (`HyperClientOptions.http2_keep_alive_interval`).
+
+*/
+pub struct KeepAliveWorker {
+    stats: Arc<Mutex<KeepAliveStats>>,
+    stop_sender: Option<oneshot::Sender<()>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+impl KeepAliveWorker {
+    pub fn spawn(
+        probe: KeepAliveProbe,
+        url: Url,
+        options: KeepAliveOptions,
+        clock: Arc<dyn Clock>,
+    ) -> io::Result<Self> {
+        use futures::future::{select, Either};
+
+        let stats = Arc::new(Mutex::new(KeepAliveStats::default()));
+        let stats_for_thread = stats.clone();
+        let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+
+        let run = async move {
+            let mut stop_receiver = stop_receiver;
+            loop {
+                let sleep = clock.sleep(options.interval);
+                if let Either::Right(_) = select(sleep, &mut stop_receiver).await {
+                    break;
+                }
+
+                let sent = select(
+                    probe(options.method, url.clone(), options.timeout),
+                    clock.sleep(options.timeout),
+                );
+                let result = match select(sent, &mut stop_receiver).await {
+                    Either::Left((Either::Left((result, _)), _)) => result.ok(),
+                    // Timeout
+                    Either::Left((Either::Right(_), _)) => None,
+                    Either::Right(_) => break,
+                };
+                let mut stats = stats_for_thread.lock().unwrap();
+                stats.sent += 1;
+                match result {
+                    Some(status) => stats.last_status = Some(status),
+                    None => stats.failed += 1,
+                }
+            }
+        };
+        #[cfg(feature = "tokio")]
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let handle = thread::Builder::new()
+            .name("keep-alive".to_string())
+            .spawn(move || {
+                #[cfg(feature = "tokio")]
+                runtime.block_on(run);
+                #[cfg(not(feature = "tokio"))]
+                block_on(run);
+            })?;
+
+        Ok(KeepAliveWorker {
+            stats,
+            stop_sender: Some(stop_sender),
+            handle: Some(handle),
+        })
+    }
+
+    pub fn stats(&self) -> KeepAliveStats {
+        self.stats.lock().unwrap().clone()
+    }
+
+    // Stop the worker and wait for it
+    pub fn stop(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        if let Some(stop_sender) = self.stop_sender.take() {
+            let _ = stop_sender.send(());
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+impl Drop for KeepAliveWorker {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+/*
+`LastError` The last failure of the calls of a service (by `StatusTracker`)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct LastError {
+    pub at: SystemTime,
+    pub message: String,
+}
+
+// `CacheEntries` The caches counted by `StatusTracker` (e.g. `MemoCache`)
+pub trait CacheEntries {
+    fn entries(&self) -> usize;
+}
+
+/**
+`StatusTracker` The shared state reported by `status()` of the services:
+the last error of the calls and the caches of the APIs.
+
+# Remarks
+
+* The caches are held weakly, the dropped ones are not counted.
+
+*/
+#[derive(Clone, Default)]
+pub struct StatusTracker(Arc<Mutex<StatusTrackerState>>);
+#[derive(Default)]
+struct StatusTrackerState {
+    last_error: Option<LastError>,
+    caches: Vec<Weak<dyn CacheEntries>>,
+}
+impl StatusTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn record_error(&self, e: &dyn StdError, at: SystemTime) {
+        self.0.lock().unwrap().last_error = Some(LastError {
+            at,
+            message: e.to_string(),
+        });
+    }
+    pub fn last_error(&self) -> Option<LastError> {
+        self.0.lock().unwrap().last_error.clone()
+    }
+    pub fn track_cache(&self, cache: Weak<dyn CacheEntries>) {
+        let mut state = self.0.lock().unwrap();
+        state.caches.retain(|cache| cache.strong_count() > 0);
+        state.caches.push(cache);
+    }
+    // The entries of the tracked caches in total
+    pub fn cache_entries(&self) -> usize {
+        self.0
+            .lock()
+            .unwrap()
+            .caches
+            .iter()
+            .filter_map(|cache| cache.upgrade())
+            .map(|cache| cache.entries())
+            .sum()
+    }
+}
+
+/*
+`ServiceStatus` The snapshot of the health of a service (by `status()`),
+e.g. for the diagnostics endpoints of the apps.
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceStatus {
+    // The calls in flight (`DrainGate`)
+    pub in_flight: usize,
+    pub shutting_down: bool,
+    pub offline: bool,
+    // The writes in the offline queue (`None` without the queue or if it's unreadable)
+    pub queue_depth: Option<usize>,
+    // The entries of the `MemoCache`s of the APIs in total
+    pub cache_entries: usize,
+    pub last_error: Option<LastError>,
+}
+
+// The methods served as reads while it's offline, the others are writes
+pub fn is_safe_method(method: &str) -> bool {
+    matches!(method, "GET" | "HEAD" | "OPTIONS" | "TRACE")
+}
+
+/**
+`HasRequestOptions` The binding independent access to the `RequestOptions` of requests.
+
+It's implemented by the request types of bindings,
+so that interceptors could set the options per request (e.g. by the url).
+*/
+pub trait HasRequestOptions {
+    fn get_request_options(&self) -> RequestOptions;
+    fn set_request_options(&mut self, options: RequestOptions);
+}
+
+struct PriorityWaiter {
+    priority: Priority,
+    seq: u64,
+    sender: oneshot::Sender<()>,
+}
+impl PartialEq for PriorityWaiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+impl Eq for PriorityWaiter {}
+impl PartialOrd for PriorityWaiter {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for PriorityWaiter {
+    // Higher priorities first, then first come first served
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+struct PriorityLimiterState {
+    max_concurrency: usize,
+    running: usize,
+    next_seq: u64,
+    waiters: BinaryHeap<PriorityWaiter>,
+}
+
+/**
+`PriorityLimiter` limits the concurrency, and the waiting calls are admitted by their `Priority`
+(then first come first served), so low priority bulk calls don't starve interactive calls.
+
+# Remarks
+
+* It's runtime independent (the waiting is done by `futures::channel::oneshot`).
+
+*/
+pub struct PriorityLimiter {
+    state: Mutex<PriorityLimiterState>,
+}
+impl PriorityLimiter {
+    pub fn new(max_concurrency: usize) -> Self {
+        PriorityLimiter {
+            state: Mutex::new(PriorityLimiterState {
+                max_concurrency: max_concurrency.max(1),
+                running: 0,
+                next_seq: 0,
+                waiters: BinaryHeap::new(),
+            }),
+        }
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.state.lock().unwrap().max_concurrency
+    }
+    // Change the limit, the running calls over the new limit are not interrupted
+    pub fn set_max_concurrency(&self, max_concurrency: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.max_concurrency = max_concurrency.max(1);
+        while state.running < state.max_concurrency {
+            match state.waiters.pop() {
+                Some(waiter) => {
+                    if waiter.sender.send(()).is_ok() {
+                        state.running += 1;
+                    }
+                }
+                None => break,
+            }
+        }
+    }
+    pub fn running(&self) -> usize {
+        self.state.lock().unwrap().running
+    }
+    pub fn waiting(&self) -> usize {
+        self.state.lock().unwrap().waiters.len()
+    }
+
+    // Get a permit without waiting (`None` if it's at the limit or there're waiters)
+    pub fn try_acquire(self: &Arc<Self>) -> Option<PriorityPermit> {
+        let mut state = self.state.lock().unwrap();
+        if state.running < state.max_concurrency && state.waiters.is_empty() {
+            state.running += 1;
+            return Some(PriorityPermit {
+                limiter: self.clone(),
+            });
+        }
+        None
+    }
+    // Wait for a permit, the permit is released when it's dropped
+    pub async fn acquire(self: &Arc<Self>, priority: Priority) -> PriorityPermit {
+        let receiver = {
+            let mut state = self.state.lock().unwrap();
+            if state.running < state.max_concurrency && state.waiters.is_empty() {
+                state.running += 1;
+                return PriorityPermit {
+                    limiter: self.clone(),
+                };
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            let seq = state.next_seq;
+            state.next_seq += 1;
+            state.waiters.push(PriorityWaiter {
+                priority,
+                seq,
+                sender,
+            });
+            receiver
+        };
+
+        let mut waiting = PriorityWaiting {
+            limiter: self.clone(),
+            receiver: Some(receiver),
+        };
+        // The sender is never dropped without sending (the limiter outlives the waiting)
+        let _ = waiting.receiver.as_mut().unwrap().await;
+        waiting.receiver = None;
+
+        PriorityPermit {
+            limiter: self.clone(),
+        }
+    }
+
+    // Hand over the permit to the next waiter, or release it
+    fn release(&self) {
+        let mut state = self.state.lock().unwrap();
+        // The limit has been lowered
+        if state.running > state.max_concurrency {
+            state.running -= 1;
+            return;
+        }
+        while let Some(waiter) = state.waiters.pop() {
+            if waiter.sender.send(()).is_ok() {
+                return;
+            }
+        }
+        state.running -= 1;
+    }
+}
+
+// PriorityWaiting Release the handed over permit if the waiting is cancelled after being admitted
+struct PriorityWaiting {
+    limiter: Arc<PriorityLimiter>,
+    receiver: Option<oneshot::Receiver<()>>,
+}
+impl Drop for PriorityWaiting {
+    fn drop(&mut self) {
+        if let Some(mut receiver) = self.receiver.take() {
+            receiver.close();
+            if let Ok(Some(())) = receiver.try_recv() {
+                self.limiter.release();
+            }
+        }
+    }
+}
+
+/*
+`PriorityPermit` The permit of `PriorityLimiter`, it's released when it's dropped
+*/
+pub struct PriorityPermit {
+    limiter: Arc<PriorityLimiter>,
+}
+impl Drop for PriorityPermit {
+    fn drop(&mut self) {
+        self.limiter.release();
+    }
+}
+
+/*
+`AdaptiveConcurrencyOptions` The options of `AdaptiveLimiter`
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdaptiveConcurrencyOptions {
+    pub initial_limit: usize,
+    pub min_limit: usize,
+    pub max_limit: usize,
+    // The latency over `min latency * latency_tolerance` is regarded as overloaded
+    pub latency_tolerance: f64,
+    // The limit is multiplied by it when overloaded
+    pub backoff_ratio: f64,
+}
+impl Default for AdaptiveConcurrencyOptions {
+    fn default() -> Self {
+        AdaptiveConcurrencyOptions {
+            initial_limit: 10,
+            min_limit: 1,
+            max_limit: 200,
+            latency_tolerance: 2.0,
+            backoff_ratio: 0.9,
+        }
+    }
+}
+
+struct AdaptiveLimiterState {
+    limit: f64,
+    min_latency: Option<Duration>,
+}
+
+/**
+`AdaptiveLimiter` adjusts the concurrency limit of a `PriorityLimiter` by the observed latency (AIMD),
+so the upstreams are protected without hand tuning the static limit.
+
+# Remarks
+
+* The baseline is the minimum observed latency.
+* When a call is dropped (e.g. timed out) or its latency exceeds the tolerance,
+  the limit is decreased multiplicatively by `backoff_ratio`.
+* Otherwise the limit is increased by 1 if at least half of the limit is in use
+  (so idle traffic doesn't grow the limit).
+
+*/
+pub struct AdaptiveLimiter {
+    pub limiter: Arc<PriorityLimiter>,
+    pub options: AdaptiveConcurrencyOptions,
+    // The time source of the latency samples (by `AdaptiveClient`)
+    pub clock: Arc<dyn Clock>,
+    state: Mutex<AdaptiveLimiterState>,
+}
+impl AdaptiveLimiter {
+    pub fn new(options: AdaptiveConcurrencyOptions) -> Self {
+        let limit = options
+            .initial_limit
+            .clamp(options.min_limit.max(1), options.max_limit.max(1));
+        AdaptiveLimiter {
+            limiter: Arc::new(PriorityLimiter::new(limit)),
+            options,
+            clock: system_clock(),
+            state: Mutex::new(AdaptiveLimiterState {
+                limit: limit as f64,
+                min_latency: None,
+            }),
+        }
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limiter.max_concurrency()
+    }
+    pub fn min_latency(&self) -> Option<Duration> {
+        self.state.lock().unwrap().min_latency
+    }
+
+    pub async fn acquire(&self, priority: Priority) -> PriorityPermit {
+        self.limiter.acquire(priority).await
+    }
+
+    /**
+    Adjust the limit by the sample of a finished call.
+
+    # Arguments
+
+    * `latency` - The latency of the call.
+    * `dropped` - Whether the call was dropped (e.g. timed out) before its completion.
+
+    # Remarks
+
+    * It should be called while the permit of the call is held.
+
+    */
+    pub fn on_sample(&self, latency: Duration, dropped: bool) {
+        let mut state = self.state.lock().unwrap();
+        if !dropped {
+            state.min_latency = Some(state.min_latency.map_or(latency, |v| v.min(latency)));
+        }
+
+        let tolerance = self.options.latency_tolerance;
+        let overloaded = dropped
+            || match state.min_latency {
+                Some(min_latency) => latency.as_secs_f64() > min_latency.as_secs_f64() * tolerance,
+                None => false,
+            };
+        if overloaded {
+            state.limit *= self.options.backoff_ratio;
+        } else if (self.limiter.running() * 2) as f64 >= state.limit {
+            state.limit += 1.0;
+        }
+        state.limit = state.limit.clamp(
+            self.options.min_limit.max(1) as f64,
+            self.options.max_limit.max(1) as f64,
+        );
+
+        self.limiter.set_max_concurrency(state.limit as usize);
+    }
+}
+
+pub fn make_stream<T>() -> (futureMpsc::Sender<T>, impl Stream<Item = T>) {
+    futureMpsc::channel(10)
+}
+
+/*
+`IdGenerator` The injectable generator of ids (e.g. interceptor ids, request ids and idempotency keys)
+*/
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
+
+/*
+`UuidV7IdGenerator` The default `IdGenerator` of UUIDv7 (time ordered and random)
+*/
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidV7IdGenerator;
+impl IdGenerator for UuidV7IdGenerator {
+    fn generate(&self) -> String {
+        uuid::Uuid::now_v7().to_string()
+    }
+}
+
+/*
+`SequentialIdGenerator` The deterministic `IdGenerator` of `{prefix}{n}` (n from 1) for tests
+*/
+#[derive(Debug, Default)]
+pub struct SequentialIdGenerator {
+    pub prefix: String,
+    counter: AtomicU64,
+}
+impl SequentialIdGenerator {
+    pub fn new(prefix: impl Into<String>) -> Self {
+        SequentialIdGenerator {
+            prefix: prefix.into(),
+            counter: AtomicU64::new(0),
+        }
+    }
+}
+impl IdGenerator for SequentialIdGenerator {
+    fn generate(&self) -> String {
+        format!(
+            "{}{}",
+            self.prefix,
+            self.counter.fetch_add(1, Ordering::SeqCst) + 1
+        )
+    }
+}
+
+static ID_GENERATOR: RwLock<Option<Arc<dyn IdGenerator>>> = RwLock::new(None);
+
+// Replace the `IdGenerator` used by `generate_id()` (`None` for the default `UuidV7IdGenerator`)
+pub fn set_id_generator(id_generator: Option<Arc<dyn IdGenerator>>) {
+    *ID_GENERATOR.write().unwrap() = id_generator;
+}
+
+// Generate an id by the `IdGenerator` set by `set_id_generator()` (UUIDv7 by default)
+pub fn generate_id() -> String {
+    match ID_GENERATOR.read().unwrap().as_ref() {
+        Some(id_generator) => id_generator.generate(),
+        None => UuidV7IdGenerator.generate(),
+    }
 }
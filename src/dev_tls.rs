@@ -0,0 +1,213 @@
+/*!
+In this module there's `DevTlsOptions`, trusting the custom CAs (e.g. the one of a dev/test environment)
+or skipping the certificate verification of specific hosts, per service (the rustls `ClientConfig` of the ureq `Agent`).
+
+# Remarks
+
+* The options are for dev/test environments only, the names of the insecure ones start with `danger_`.
+* They're per service (e.g. `CommonAPI::new_with_agent(options.ureq_agent()?)`), instead of
+  the process-wide environment variables (e.g. `SSL_CERT_FILE`).
+* There's no TLS connector of hyper in this crate, the `client_config()` could be used by
+  a rustls based connector of the same rustls version (0.20),
+  and `tls_info_of_rustls()` makes the `TlsInfo` of its connections (for `Connected::extra()`).
+
+*/
+
+use std::error::Error as StdError;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, CommonState, OwnedTrustAnchor, RootCertStore, ServerName};
+use ureq::{Agent, AgentBuilder};
+
+use super::common::TlsInfo;
+
+const PEM_CERTIFICATE_BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const PEM_CERTIFICATE_END: &str = "-----END CERTIFICATE-----";
+
+/*
+`DevTlsError` The TLS options are invalid (e.g. an illegal PEM, or the certificate isn't a CA)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DevTlsError {
+    details: String,
+}
+impl StdError for DevTlsError {}
+impl DevTlsError {
+    pub fn new(msg: impl Into<String>) -> DevTlsError {
+        DevTlsError {
+            details: msg.into(),
+        }
+    }
+}
+impl std::fmt::Display for DevTlsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "DevTlsError: {}", self.details)
+    }
+}
+
+// The DER certificates of the PEM bundle (the other blocks, e.g. the keys, are ignored)
+pub fn pem_certificates(pem: &[u8]) -> StdResult<Vec<Vec<u8>>, DevTlsError> {
+    let pem = std::str::from_utf8(pem).map_err(|e| DevTlsError::new(e.to_string()))?;
+    let mut certificates = Vec::new();
+    let mut rest = pem;
+    while let Some(begin) = rest.find(PEM_CERTIFICATE_BEGIN) {
+        rest = &rest[begin + PEM_CERTIFICATE_BEGIN.len()..];
+        let end = rest
+            .find(PEM_CERTIFICATE_END)
+            .ok_or_else(|| DevTlsError::new("unterminated PEM certificate"))?;
+        let encoded: String = rest[..end].split_whitespace().collect();
+        certificates.push(base64::decode(encoded).map_err(|e| DevTlsError::new(e.to_string()))?);
+        rest = &rest[end + PEM_CERTIFICATE_END.len()..];
+    }
+    if certificates.is_empty() {
+        return Err(DevTlsError::new("no PEM certificate"));
+    }
+    Ok(certificates)
+}
+
+// The `TlsInfo` of the rustls connection (after the handshake)
+pub fn tls_info_of_rustls(state: &CommonState) -> TlsInfo {
+    TlsInfo {
+        protocol: state.protocol_version().map(|v| format!("{:?}", v)),
+        cipher_suite: state
+            .negotiated_cipher_suite()
+            .map(|v| format!("{:?}", v.suite())),
+        alpn_protocol: state
+            .alpn_protocol()
+            .map(|v| String::from_utf8_lossy(v).to_string()),
+        peer_certificates: state
+            .peer_certificates()
+            .map(|certificates| certificates.iter().map(|v| v.0.clone()).collect())
+            .unwrap_or_default(),
+    }
+}
+
+// Verify by the trust anchors, except the insecure hosts (any certificate of them is accepted)
+struct DevCertVerifier {
+    verifier: WebPkiVerifier,
+    insecure_hosts: Vec<String>,
+}
+impl ServerCertVerifier for DevCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> StdResult<ServerCertVerified, rustls::Error> {
+        let host = match server_name {
+            ServerName::DnsName(name) => name.as_ref().to_ascii_lowercase(),
+            ServerName::IpAddress(addr) => addr.to_string(),
+            _ => String::new(),
+        };
+        if self.insecure_hosts.contains(&host) {
+            return Ok(ServerCertVerified::assertion());
+        }
+        self.verifier.verify_server_cert(
+            end_entity,
+            intermediates,
+            server_name,
+            scts,
+            ocsp_response,
+            now,
+        )
+    }
+}
+
+/**
+`DevTlsOptions` The TLS options of the dev/test environments.
+
+# Remarks
+
+* `ca_certificates` (DER) are trusted in addition to the webpki roots (unless `without_webpki_roots()`).
+* The certificates of `danger_insecure_hosts` aren't verified at all (the hosts of the urls, or the IP addresses),
+  the other hosts are verified as usual. The handshake signatures are still verified by the certificates.
+
+*/
+#[derive(Debug, Clone)]
+pub struct DevTlsOptions {
+    pub ca_certificates: Vec<Vec<u8>>,
+    pub webpki_roots: bool,
+    pub danger_insecure_hosts: Vec<String>,
+}
+impl Default for DevTlsOptions {
+    fn default() -> Self {
+        DevTlsOptions {
+            ca_certificates: Vec::new(),
+            webpki_roots: true,
+            danger_insecure_hosts: Vec::new(),
+        }
+    }
+}
+impl DevTlsOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // Trust the CA certificates of the PEM bundle
+    pub fn with_ca_pem(mut self, pem: &[u8]) -> StdResult<Self, DevTlsError> {
+        self.ca_certificates.extend(pem_certificates(pem)?);
+        Ok(self)
+    }
+    pub fn with_ca_der(mut self, der: Vec<u8>) -> Self {
+        self.ca_certificates.push(der);
+        self
+    }
+    // Trust only the custom CAs
+    pub fn without_webpki_roots(mut self) -> Self {
+        self.webpki_roots = false;
+        self
+    }
+    // DANGER: Accept any certificate of the host (e.g. the self-signed one of a dev server)
+    pub fn danger_accept_invalid_certs_for_host(mut self, host: impl Into<String>) -> Self {
+        self.danger_insecure_hosts
+            .push(host.into().trim_end_matches('.').to_ascii_lowercase());
+        self
+    }
+
+    pub fn root_cert_store(&self) -> StdResult<RootCertStore, DevTlsError> {
+        let mut roots = RootCertStore::empty();
+        if self.webpki_roots {
+            roots.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(
+                    ta.subject,
+                    ta.spki,
+                    ta.name_constraints,
+                )
+            }));
+        }
+        for der in &self.ca_certificates {
+            roots
+                .add(&Certificate(der.clone()))
+                .map_err(|e| DevTlsError::new(format!("illegal CA certificate: {}", e)))?;
+        }
+        Ok(roots)
+    }
+
+    pub fn client_config(&self) -> StdResult<Arc<ClientConfig>, DevTlsError> {
+        let roots = self.root_cert_store()?;
+        let builder = ClientConfig::builder().with_safe_defaults();
+        let config = if self.danger_insecure_hosts.is_empty() {
+            builder.with_root_certificates(roots).with_no_client_auth()
+        } else {
+            builder
+                .with_custom_certificate_verifier(Arc::new(DevCertVerifier {
+                    verifier: WebPkiVerifier::new(roots, None),
+                    insecure_hosts: self.danger_insecure_hosts.clone(),
+                }))
+                .with_no_client_auth()
+        };
+        Ok(Arc::new(config))
+    }
+
+    // The ureq `Agent` of the options (for `CommonAPI::new_with_agent()`)
+    pub fn ureq_agent(&self) -> StdResult<Agent, DevTlsError> {
+        Ok(AgentBuilder::new()
+            .tls_config(self.client_config()?)
+            .build())
+    }
+}
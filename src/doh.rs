@@ -0,0 +1,333 @@
+/*!
+In this module there's `DohResolver`, resolving the hosts of the hyper connectors by DNS-over-HTTPS
+(RFC 8484, `application/dns-message`) instead of the system DNS.
+
+# Remarks
+
+* The DoH queries are sent by the `DohTransport`, e.g. a hyper `Client` with a TLS connector
+  (there's no TLS connector in this crate, see https://hyper.rs/guides/client/configuration).
+* The transport shouldn't resolve the DoH endpoint by the `DohResolver` itself,
+  the endpoints are usually IP addresses (e.g. `https://1.1.1.1/dns-query`) or resolved by the system DNS.
+* The resolvers are per connector, so each service could have its own
+  (e.g. by `CommonAPI::new_with_connector(resolver.connector())`).
+
+*/
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::error::Error as StdError;
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::pin::Pin;
+use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use http::method::Method;
+use hyper::client::connect::dns::Name;
+use hyper::client::connect::Connect;
+use hyper::client::HttpConnector;
+use hyper::header::{ACCEPT, CONTENT_TYPE};
+use hyper::service::Service;
+use hyper::{Body, Client, Request};
+use url::Url;
+
+use super::common::{system_clock, Clock};
+
+pub const MEDIA_TYPE_DNS_MESSAGE: &str = "application/dns-message";
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+// The cached addresses & when they expire
+type CachedAddrs = (Vec<IpAddr>, Instant);
+
+/*
+`DohError` The host isn't resolved (e.g. an illegal name, the failed query, or there's no address)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct DohError {
+    details: String,
+}
+impl StdError for DohError {}
+impl DohError {
+    pub fn new(msg: impl Into<String>) -> DohError {
+        DohError {
+            details: msg.into(),
+        }
+    }
+}
+impl std::fmt::Display for DohError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "DohError: {}", self.details)
+    }
+}
+
+/*
+`DohTransport` Send the DNS query message to the DoH endpoint (by POST), returning the response message
+*/
+pub trait DohTransport: Send + Sync {
+    fn query(
+        &self,
+        endpoint: &Url,
+        message: Vec<u8>,
+    ) -> Pin<Box<dyn Future<Output = StdResult<Vec<u8>, DohError>> + Send>>;
+}
+impl<C> DohTransport for Client<C, Body>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn query(
+        &self,
+        endpoint: &Url,
+        message: Vec<u8>,
+    ) -> Pin<Box<dyn Future<Output = StdResult<Vec<u8>, DohError>> + Send>> {
+        let client = self.clone();
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(endpoint.as_str())
+            .header(CONTENT_TYPE, MEDIA_TYPE_DNS_MESSAGE)
+            .header(ACCEPT, MEDIA_TYPE_DNS_MESSAGE)
+            .body(Body::from(message));
+        Box::pin(async move {
+            let request = request.map_err(|e| DohError::new(e.to_string()))?;
+            let response = client
+                .request(request)
+                .await
+                .map_err(|e| DohError::new(e.to_string()))?;
+            if !response.status().is_success() {
+                return Err(DohError::new(format!(
+                    "the DoH endpoint responded {}",
+                    response.status()
+                )));
+            }
+            let body = hyper::body::to_bytes(response.into_body())
+                .await
+                .map_err(|e| DohError::new(e.to_string()))?;
+            Ok(body.to_vec())
+        })
+    }
+}
+
+// The DNS query message of the host (the id is 0 for the HTTP caches, RFC 8484 4.1)
+pub fn dns_query_message(host: &str, record_type: u16) -> StdResult<Vec<u8>, DohError> {
+    let host = host.trim_end_matches('.');
+    if host.is_empty() || host.len() > 253 {
+        return Err(DohError::new(format!("illegal host: {}", host)));
+    }
+    // The header: id, flags (recursion desired), 1 question
+    let mut message = vec![0, 0, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in host.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(DohError::new(format!("illegal host: {}", host)));
+        }
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+    message.extend_from_slice(&record_type.to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(message)
+}
+
+fn read_u16(message: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([
+        *message.get(pos)?,
+        *message.get(pos + 1)?,
+    ]))
+}
+
+// The position after the (maybe compressed) name at `pos`
+fn skip_name(message: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(pos)? as usize;
+        if len == 0 {
+            return Some(pos + 1);
+        }
+        if len & 0xc0 == 0xc0 {
+            return Some(pos + 2);
+        }
+        pos += 1 + len;
+    }
+}
+
+fn parse_answers(message: &[u8]) -> Option<StdResult<(Vec<IpAddr>, u32), DohError>> {
+    let rcode = read_u16(message, 2)? & 0x000f;
+    if rcode != 0 {
+        return Some(Err(DohError::new(format!(
+            "the DNS query failed (rcode {})",
+            rcode
+        ))));
+    }
+    let questions = read_u16(message, 4)?;
+    let answers = read_u16(message, 6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos)? + 4;
+    }
+
+    let mut addrs = Vec::new();
+    let mut ttl = u32::MAX;
+    for _ in 0..answers {
+        pos = skip_name(message, pos)?;
+        let record_type = read_u16(message, pos)?;
+        let record_ttl = u32::from_be_bytes(message.get(pos + 4..pos + 8)?.try_into().ok()?);
+        let len = read_u16(message, pos + 8)? as usize;
+        let data = message.get(pos + 10..pos + 10 + len)?;
+        pos += 10 + len;
+
+        // The other records (e.g. CNAME) are followed by the ones of the canonical names
+        let addr = match (record_type, len) {
+            (TYPE_A, 4) => IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+            (TYPE_AAAA, 16) => {
+                let octets: [u8; 16] = data.try_into().ok()?;
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => continue,
+        };
+        addrs.push(addr);
+        ttl = ttl.min(record_ttl);
+    }
+    Some(Ok((addrs, ttl)))
+}
+
+/**
+The addresses (A/AAAA) & the min TTL (in seconds) of the DNS response message.
+
+# Remarks
+
+* The failed responses (the RCODE isn't `NOERROR`, e.g. `NXDOMAIN`) and the truncated ones are `DohError`.
+
+*/
+pub fn parse_dns_response(message: &[u8]) -> StdResult<(Vec<IpAddr>, u32), DohError> {
+    parse_answers(message).unwrap_or_else(|| Err(DohError::new("truncated DNS message")))
+}
+
+/**
+`DohResolver` resolves the hosts by the DoH `endpoint`, as the resolver of `HttpConnector`
+(`HttpConnector::new_with_resolver()`, or `connector()`).
+
+# Remarks
+
+* A & AAAA (if `ipv6`) are queried concurrently, the IPv4 addresses are tried first by the connector.
+* The addresses are cached by the min TTL of the records (by the `clock`), shared by the clones.
+* The `pinned` hosts are resolved to their addresses without querying (like `curl --resolve`).
+* The IP address hosts are connected directly by `HttpConnector` without resolving.
+
+*/
+#[derive(Clone)]
+pub struct DohResolver {
+    pub endpoint: Url,
+    pub transport: Arc<dyn DohTransport>,
+    pub pinned: HashMap<String, Vec<IpAddr>>,
+    pub ipv6: bool,
+    pub clock: Arc<dyn Clock>,
+    cache: Arc<Mutex<HashMap<String, CachedAddrs>>>,
+}
+impl DohResolver {
+    pub fn new(endpoint: Url, transport: Arc<dyn DohTransport>) -> Self {
+        DohResolver {
+            endpoint,
+            transport,
+            pinned: HashMap::new(),
+            ipv6: true,
+            clock: system_clock(),
+            cache: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+    pub fn with_pinned(mut self, host: impl Into<String>, addrs: Vec<IpAddr>) -> Self {
+        self.pinned.insert(host.into().to_ascii_lowercase(), addrs);
+        self
+    }
+    pub fn with_ipv6(mut self, ipv6: bool) -> Self {
+        self.ipv6 = ipv6;
+        self
+    }
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    // The `HttpConnector` resolving by this resolver
+    pub fn connector(self) -> HttpConnector<DohResolver> {
+        HttpConnector::new_with_resolver(self)
+    }
+
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    // Resolve the addresses of the host (by the pinned ones, the cached ones, or querying the endpoint)
+    pub fn resolve(
+        &self,
+        host: &str,
+    ) -> impl Future<Output = StdResult<Vec<IpAddr>, DohError>> + Send + 'static {
+        let resolver = self.clone();
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        async move {
+            if let Some(addrs) = resolver.pinned.get(&host) {
+                return Ok(addrs.clone());
+            }
+            if let Some((addrs, expires_at)) = resolver.cache.lock().unwrap().get(&host) {
+                if *expires_at > resolver.clock.now() {
+                    return Ok(addrs.clone());
+                }
+            }
+
+            let query = |record_type: u16| {
+                let resolver = resolver.clone();
+                let message = dns_query_message(&host, record_type);
+                async move {
+                    let response = resolver
+                        .transport
+                        .query(&resolver.endpoint, message?)
+                        .await?;
+                    parse_dns_response(&response)
+                }
+            };
+            let (addrs, ttl) = if resolver.ipv6 {
+                let ((mut addrs, ttl), (v6_addrs, v6_ttl)) =
+                    futures::future::try_join(query(TYPE_A), query(TYPE_AAAA)).await?;
+                addrs.extend(v6_addrs);
+                (addrs, ttl.min(v6_ttl))
+            } else {
+                query(TYPE_A).await?
+            };
+            if addrs.is_empty() {
+                return Err(DohError::new(format!("no address of host: {}", host)));
+            }
+
+            let expires_at = resolver.clock.now() + Duration::from_secs(ttl as u64);
+            resolver
+                .cache
+                .lock()
+                .unwrap()
+                .insert(host, (addrs.clone(), expires_at));
+            Ok(addrs)
+        }
+    }
+}
+impl Service<Name> for DohResolver {
+    type Response = std::vec::IntoIter<SocketAddr>;
+    type Error = DohError;
+    type Future = Pin<Box<dyn Future<Output = StdResult<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<StdResult<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, name: Name) -> Self::Future {
+        let resolving = self.resolve(name.as_str());
+        Box::pin(async move {
+            // The ports are set by the connector
+            let addrs = resolving.await?;
+            Ok(addrs
+                .into_iter()
+                .map(|addr| SocketAddr::new(addr, 0))
+                .collect::<Vec<_>>()
+                .into_iter())
+        })
+    }
+}
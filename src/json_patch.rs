@@ -0,0 +1,202 @@
+/*!
+In this module there're implementations of JSON Patch (RFC 6902, `application/json-patch+json`)
+and JSON Merge Patch (RFC 7386, `application/merge-patch+json`) for the PATCH APIs.
+
+# Remarks
+
+* The patches are sent by `JsonPatchSerializer`/`MergePatchSerializer`
+  with the content types of `make_api_json_patch()`/`make_api_merge_patch()`.
+* `json_patch_diff()`/`merge_patch_diff()` compute the patches between two serde values
+  (e.g. the fetched resource and the mutated one).
+* The `CodecRegistry` decodes both media types by the `application/json` codec (the `+json` suffix).
+
+*/
+
+use std::error::Error as StdError;
+use std::result::Result as StdResult;
+
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::simple_api::BodySerializer;
+
+pub const MEDIA_TYPE_JSON_PATCH: &str = "application/json-patch+json";
+pub const MEDIA_TYPE_MERGE_PATCH: &str = "application/merge-patch+json";
+
+/*
+`PatchOperation` An operation of JSON Patch, the paths are JSON Pointers (RFC 6901)
+*/
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+#[derive(Debug, Clone, Copy)]
+// JsonPatchSerializer Serialize the operations of JSON Patch (for patch, as `application/json-patch+json`)
+pub struct JsonPatchSerializer {}
+impl<B: From<Bytes>> BodySerializer<Vec<PatchOperation>, B> for JsonPatchSerializer {
+    fn encode(&self, origin: Vec<PatchOperation>) -> StdResult<B, Box<dyn StdError>> {
+        Ok(B::from(Bytes::from(serde_json::to_vec(&origin)?)))
+    }
+}
+pub const DEFAULT_JSON_PATCH_SERIALIZER: JsonPatchSerializer = JsonPatchSerializer {};
+
+#[derive(Debug, Clone, Copy)]
+// MergePatchSerializer Serialize the merge patch (for patch, as `application/merge-patch+json`, `null` removes the member)
+pub struct MergePatchSerializer {}
+impl<T: Serialize, B: From<Bytes>> BodySerializer<T, B> for MergePatchSerializer {
+    fn encode(&self, origin: T) -> StdResult<B, Box<dyn StdError>> {
+        Ok(B::from(Bytes::from(serde_json::to_vec(&origin)?)))
+    }
+}
+pub const DEFAULT_MERGE_PATCH_SERIALIZER: MergePatchSerializer = MergePatchSerializer {};
+
+// Escape the reference token of JSON Pointer ("~" -> "~0", "/" -> "~1")
+fn escape_pointer_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+fn diff_into(path: &str, before: &Value, after: &Value, operations: &mut Vec<PatchOperation>) {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            for key in before.keys().filter(|key| !after.contains_key(*key)) {
+                operations.push(PatchOperation::Remove {
+                    path: format!("{}/{}", path, escape_pointer_token(key)),
+                });
+            }
+            for (key, value) in after {
+                let path = format!("{}/{}", path, escape_pointer_token(key));
+                match before.get(key) {
+                    Some(before_value) => diff_into(&path, before_value, value, operations),
+                    None => operations.push(PatchOperation::Add {
+                        path,
+                        value: value.clone(),
+                    }),
+                }
+            }
+        }
+        (Value::Array(before), Value::Array(after)) => {
+            let common = before.len().min(after.len());
+            for index in 0..common {
+                diff_into(
+                    &format!("{}/{}", path, index),
+                    &before[index],
+                    &after[index],
+                    operations,
+                );
+            }
+            // From the last one, so the indices of the others aren't shifted
+            for index in (common..before.len()).rev() {
+                operations.push(PatchOperation::Remove {
+                    path: format!("{}/{}", path, index),
+                });
+            }
+            for (index, value) in after.iter().enumerate().skip(common) {
+                operations.push(PatchOperation::Add {
+                    path: format!("{}/{}", path, index),
+                    value: value.clone(),
+                });
+            }
+        }
+        (before, after) if before != after => operations.push(PatchOperation::Replace {
+            path: path.to_string(),
+            value: after.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/**
+The operations of JSON Patch turning `before` into `after`.
+
+# Remarks
+
+* The members of the objects are compared recursively, the removed ones are `remove`, the new ones are `add`.
+* The arrays are compared by the indices (there're no `move`/`copy` detected),
+  the extra items are removed from the end, or added to the end.
+* The different values of the others (e.g. the types are changed) are `replace`.
+
+*/
+pub fn json_patch_diff<T: Serialize>(
+    before: &T,
+    after: &T,
+) -> StdResult<Vec<PatchOperation>, serde_json::Error> {
+    let before = serde_json::to_value(before)?;
+    let after = serde_json::to_value(after)?;
+    let mut operations = Vec::new();
+    diff_into("", &before, &after, &mut operations);
+    Ok(operations)
+}
+
+fn merge_diff_value(before: &Value, after: &Value) -> Value {
+    match (before, after) {
+        (Value::Object(before), Value::Object(after)) => {
+            let mut patch = Map::new();
+            for key in before.keys().filter(|key| !after.contains_key(*key)) {
+                patch.insert(key.clone(), Value::Null);
+            }
+            for (key, value) in after {
+                match before.get(key) {
+                    Some(before_value) if before_value == value => {}
+                    Some(before_value) => {
+                        patch.insert(key.clone(), merge_diff_value(before_value, value));
+                    }
+                    None => {
+                        patch.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            Value::Object(patch)
+        }
+        (_, after) => after.clone(),
+    }
+}
+
+/**
+The JSON Merge Patch turning `before` into `after` (an empty object if they're the same).
+
+# Remarks
+
+* The removed members are `null`, the changed ones are merged recursively (the objects) or replaced.
+* The members changed into `null` can't be expressed (`null` removes them),
+  they're removed by applying the patch.
+
+*/
+pub fn merge_patch_diff<T: Serialize>(
+    before: &T,
+    after: &T,
+) -> StdResult<Value, serde_json::Error> {
+    let before = serde_json::to_value(before)?;
+    let after = serde_json::to_value(after)?;
+    Ok(merge_diff_value(&before, &after))
+}
+
+// Apply the JSON Merge Patch to the `target` (RFC 7386)
+pub fn apply_merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        patch => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                apply_merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
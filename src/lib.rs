@@ -20,14 +20,31 @@ extern crate mime;
 #[cfg(feature = "multipart")]
 extern crate multer;
 
+#[cfg(feature = "scrub")]
+extern crate regex;
+
 #[cfg(feature = "for_serde")]
 extern crate serde;
 #[cfg(feature = "for_serde")]
 extern crate serde_json;
+#[cfg(feature = "simd_json")]
+extern crate simd_json;
+
+// A binding is required (the other features are additive on top of either of them)
+#[cfg(not(any(feature = "for_hyper", feature = "for_ureq")))]
+compile_error!(
+    "http_api_service needs a binding: enable the feature `for_hyper` and/or `for_ureq`"
+);
 
 // MODs
 
+pub mod any_service;
+pub mod audit;
 pub mod common;
+pub mod matcher;
+pub mod prelude;
+pub mod scrub;
+pub mod service;
 pub mod simple_api;
 pub mod simple_http;
 
@@ -36,3 +53,41 @@ pub mod bind_hyper;
 
 #[cfg(feature = "for_ureq")]
 pub mod bind_ureq;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "dev_tls")]
+pub mod dev_tls;
+#[cfg(feature = "doh")]
+pub mod doh;
+
+#[cfg(feature = "for_serde")]
+pub mod codec;
+#[cfg(feature = "for_serde")]
+pub mod json_patch;
+#[cfg(feature = "for_serde")]
+pub mod serde_formats;
+
+#[cfg(all(
+    feature = "for_serde",
+    any(feature = "for_hyper", feature = "for_ureq")
+))]
+pub mod quick;
+
+#[cfg(feature = "queue")]
+pub mod queue;
+
+#[cfg(feature = "watch")]
+pub mod watcher;
+
+#[cfg(feature = "token_exchange")]
+pub mod token_exchange;
+
+#[cfg(feature = "azure_auth")]
+pub mod azure_auth;
+#[cfg(feature = "gcp_auth")]
+pub mod gcp_auth;
+#[cfg(feature = "negotiate")]
+pub mod negotiate;
+#[cfg(feature = "ntlm")]
+pub mod ntlm;
@@ -0,0 +1,152 @@
+/*!
+In this module there's `RequestMatcher`, matching the requests by their canonicalized forms
+(e.g. the recorded entries of the mocks/cassettes, looked up by `fingerprint()`).
+
+# Remarks
+
+* The canonical form is made of the method, the url (with the sorted query parameters),
+  the selected headers (sorted by names) and the digest of the body, so it's independent of the header ordering.
+* The volatile parts (e.g. the timestamps/nonces) are excluded by not selecting their headers
+  or ignoring their query parameters (`ignore_query_param()`).
+* The fingerprints are stable across the processes/builds (FNV-1a, not the `Hash` of std),
+  so they could be persisted as the keys of the recorded entries.
+* The streamed bodies (e.g. `hyper::Body`) aren't summarized, they're matched as absent bodies.
+
+*/
+
+use url::Url;
+
+use super::common::RequestSummary;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+// The 64-bit FNV-1a digest of the bytes (stable, unlike the `DefaultHasher`)
+pub fn fnv1a_digest(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, b| {
+        (hash ^ u64::from(*b)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/**
+`RequestMatcher` The parts of the requests to be matched (all of them except the headers by default).
+
+# Remarks
+
+* The `headers` are the lowercase names, their values are trimmed and joined (in the order they're sent).
+* The urls are normalized by `Url` (e.g. the default ports, the case of the schemes/hosts),
+  and the fragments are dropped.
+
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestMatcher {
+    pub method: bool,
+    pub url: bool,
+    pub ignored_query_params: Vec<String>,
+    pub headers: Vec<String>,
+    pub body: bool,
+}
+impl Default for RequestMatcher {
+    fn default() -> Self {
+        RequestMatcher {
+            method: true,
+            url: true,
+            ignored_query_params: Vec::new(),
+            headers: Vec::new(),
+            body: true,
+        }
+    }
+}
+impl RequestMatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // Match the values of the header as well (case insensitive names)
+    pub fn with_header(mut self, name: impl Into<String>) -> Self {
+        let name = name.into().to_ascii_lowercase();
+        if !self.headers.contains(&name) {
+            self.headers.push(name);
+            self.headers.sort();
+        }
+        self
+    }
+    // Ignore the query parameter (e.g. the timestamps/signatures)
+    pub fn ignore_query_param(mut self, name: impl Into<String>) -> Self {
+        self.ignored_query_params.push(name.into());
+        self
+    }
+    pub fn without_method(mut self) -> Self {
+        self.method = false;
+        self
+    }
+    pub fn without_url(mut self) -> Self {
+        self.url = false;
+        self
+    }
+    pub fn without_body(mut self) -> Self {
+        self.body = false;
+        self
+    }
+
+    // The canonical form of the matched parts (one per line)
+    pub fn canonicalize(&self, request: &impl RequestSummary) -> String {
+        let mut lines = Vec::new();
+        if self.method {
+            lines.push(request.get_method().to_ascii_uppercase());
+        }
+        if self.url {
+            lines.push(self.canonicalize_url(&request.get_url()));
+        }
+        if !self.headers.is_empty() {
+            let headers = request.get_headers();
+            for name in self.headers.iter() {
+                let values = headers.get_all(name);
+                if !values.is_empty() {
+                    let values: Vec<&str> = values.iter().map(|v| v.trim()).collect();
+                    lines.push(format!("{}: {}", name, values.join(", ")));
+                }
+            }
+        }
+        if self.body {
+            lines.push(match request.get_body() {
+                Some(body) if !body.is_empty() => format!("body: {:016x}", fnv1a_digest(&body)),
+                _ => "body: -".to_string(),
+            });
+        }
+        lines.join("\n")
+    }
+
+    // The url with the sorted query parameters (the ignored ones are removed)
+    pub fn canonicalize_url(&self, url: &str) -> String {
+        let mut url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(_) => return url.to_string(),
+        };
+        url.set_fragment(None);
+
+        let mut query_pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(name, _)| !self.ignored_query_params.iter().any(|v| v == name))
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        if query_pairs.is_empty() {
+            url.set_query(None);
+        } else {
+            query_pairs.sort();
+            url.query_pairs_mut().clear().extend_pairs(query_pairs);
+        }
+        url.to_string()
+    }
+
+    // The hex digest of the canonical form (the content address of the request)
+    pub fn fingerprint(&self, request: &impl RequestSummary) -> String {
+        format!(
+            "{:016x}",
+            fnv1a_digest(self.canonicalize(request).as_bytes())
+        )
+    }
+
+    pub fn matches(&self, request: &impl RequestSummary, other: &impl RequestSummary) -> bool {
+        self.canonicalize(request) == self.canonicalize(other)
+    }
+}
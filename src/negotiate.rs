@@ -0,0 +1,231 @@
+/*!
+In this module there's `NegotiateClient`, answering the `WWW-Authenticate: Negotiate` challenges
+(Kerberos/SPNEGO, RFC 4559) of the intranet services as a `BaseClient` of hyper.
+
+# Remarks
+
+* The GSSAPI/SSPI exchange is done by the `NegotiateProvider`/`NegotiateContext`
+  (e.g. backed by `libgssapi` on Unix or `sspi` on Windows), so there's no system library linked by this crate.
+* The tokens are base64 encoded in the `Authorization`/`WWW-Authenticate` headers by `NegotiateClient`.
+
+*/
+
+use std::error::Error as StdError;
+use std::future::Future;
+use std::pin::Pin;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+
+use http::method::Method;
+use hyper::client::connect::Connect;
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use hyper::{Body, Client, HeaderMap, Request, Response, Result, StatusCode};
+
+use super::common::HasRequestOptions;
+use super::simple_http::BaseClient;
+
+pub const NEGOTIATE_SCHEME: &str = "Negotiate";
+
+/**
+`NegotiateContext` The security context of a GSSAPI/SSPI exchange
+(e.g. `gss_init_sec_context()` or `InitializeSecurityContext()`).
+
+# Remarks
+
+* `step()` is called with `None` for the first token,
+  and then with the tokens of the server (of the `401` challenges, or the final one of the mutual authentication).
+* It returns the next token to send, or `None` when there's nothing to send (e.g. the context is complete).
+
+*/
+pub trait NegotiateContext {
+    fn step(
+        &mut self,
+        server_token: Option<&[u8]>,
+    ) -> StdResult<Option<Vec<u8>>, Box<dyn StdError>>;
+    fn is_complete(&self) -> bool;
+}
+
+/*
+`NegotiateProvider` Create the security contexts of the hosts
+(the service principals are usually `HTTP@<host>` for GSSAPI, or `HTTP/<host>` for SSPI).
+*/
+pub trait NegotiateProvider: Send + Sync {
+    fn new_context(&self, host: &str) -> StdResult<Box<dyn NegotiateContext>, Box<dyn StdError>>;
+}
+
+/*
+`NegotiateAuthenticated` The response extension set by `NegotiateClient` after the exchange is done,
+`mutual` is whether the server is authenticated too (by the final token of the response).
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NegotiateAuthenticated {
+    pub mutual: bool,
+}
+
+// The token of the `Negotiate` challenge (`Some(None)` without the token), or `None` if it's not challenged
+pub fn negotiate_challenge(headers: &HeaderMap) -> Option<Option<Vec<u8>>> {
+    headers
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .find_map(|challenge| {
+            let mut parts = challenge.splitn(2, ' ');
+            let scheme = parts.next()?;
+            if !scheme.eq_ignore_ascii_case(NEGOTIATE_SCHEME) {
+                return None;
+            }
+            Some(
+                parts
+                    .next()
+                    .map(str::trim)
+                    .filter(|token| !token.is_empty())
+                    .and_then(|token| base64::decode(token).ok()),
+            )
+        })
+}
+
+/**
+`NegotiateClient` answers the `Negotiate` challenges by the `NegotiateProvider` as a `BaseClient`.
+
+# Remarks
+
+* The `401` responses challenging `Negotiate` start the exchange with a new context of the host,
+  and the original request is sent again with `Authorization: Negotiate <token>`
+  (until it's not challenged again, or over `max_rounds`).
+* The request body is buffered so that it could be sent again.
+* The final token of the server (the mutual authentication) is passed to the context,
+  and the authenticated responses are annotated by the `NegotiateAuthenticated` extension.
+* The challenges not answered (e.g. the failures of the context) are returned as they are.
+* The requests of `RequestOptions::skip_auth` are sent as they are.
+* The connection-based authentication (e.g. NTLM over `Negotiate`) needs the exchange over the same connection,
+  which isn't guaranteed by the pooled connections of hyper, so Kerberos is expected.
+
+*/
+pub struct NegotiateClient<C> {
+    pub client: Client<C, Body>,
+    pub provider: Arc<dyn NegotiateProvider>,
+    pub max_rounds: usize,
+}
+impl<C> NegotiateClient<C> {
+    pub fn new_with_options(
+        client: Client<C, Body>,
+        provider: Arc<dyn NegotiateProvider>,
+        max_rounds: usize,
+    ) -> Self {
+        NegotiateClient {
+            client,
+            provider,
+            max_rounds,
+        }
+    }
+}
+impl NegotiateClient<HttpConnector> {
+    pub fn new_for_hyper(provider: Arc<dyn NegotiateProvider>) -> Self {
+        Self::new_with_options(Client::new(), provider, 4)
+    }
+}
+
+impl<C> BaseClient<Client<C, Body>, Request<Body>, Result<Response<Body>>, Method, HeaderMap, Body>
+    for NegotiateClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    fn request(&self, req: Request<Body>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
+        let client = self.client.clone();
+        let provider = self.provider.clone();
+        let max_rounds = self.max_rounds;
+
+        Box::pin(async move {
+            let options = req.get_request_options();
+            if options.skip_auth {
+                return client.request(req).await;
+            }
+            let (parts, body) = req.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+            let new_request = |token: Option<&[u8]>| {
+                let mut req = Request::new(Body::from(body.clone()));
+                *req.method_mut() = parts.method.clone();
+                *req.uri_mut() = parts.uri.clone();
+                *req.version_mut() = parts.version;
+                *req.headers_mut() = parts.headers.clone();
+                if let Some(Ok(mut value)) = token.map(|token| {
+                    HeaderValue::from_str(
+                        &(NEGOTIATE_SCHEME.to_string() + " " + &base64::encode(token)),
+                    )
+                }) {
+                    value.set_sensitive(true);
+                    req.headers_mut().insert(AUTHORIZATION, value);
+                }
+                req.set_request_options(options.clone());
+                req
+            };
+
+            let mut result = client.request(new_request(None)).await;
+            let mut context: Option<Box<dyn NegotiateContext>> = None;
+            let mut rounds = 0;
+            loop {
+                let res = match &mut result {
+                    Ok(res) => res,
+                    Err(_) => return result,
+                };
+                let server_token = match negotiate_challenge(res.headers()) {
+                    Some(server_token) => server_token,
+                    None if res.status() == StatusCode::UNAUTHORIZED => return result,
+                    // Authenticated (or not challenged at all)
+                    None => {
+                        if context.is_some() {
+                            res.extensions_mut()
+                                .insert(NegotiateAuthenticated { mutual: false });
+                        }
+                        return result;
+                    }
+                };
+
+                if res.status() != StatusCode::UNAUTHORIZED {
+                    // The final token of the mutual authentication
+                    if let Some(context) = &mut context {
+                        let mutual = server_token.is_some()
+                            && context.step(server_token.as_deref()).is_ok()
+                            && context.is_complete();
+                        res.extensions_mut()
+                            .insert(NegotiateAuthenticated { mutual });
+                    }
+                    return result;
+                }
+                if rounds >= max_rounds {
+                    return result;
+                }
+
+                let token = match &mut context {
+                    // Continue the exchange by the token of the server
+                    Some(context) => match server_token {
+                        Some(server_token) if !context.is_complete() => {
+                            context.step(Some(&server_token))
+                        }
+                        // Rejected
+                        _ => return result,
+                    },
+                    None => {
+                        let host = parts.uri.host().unwrap_or_default();
+                        match provider.new_context(host) {
+                            Ok(new_context) => context.insert(new_context).step(None),
+                            Err(_) => return result,
+                        }
+                    }
+                };
+                let token = match token {
+                    Ok(Some(token)) => token,
+                    _ => return result,
+                };
+                rounds += 1;
+                result = client.request(new_request(Some(&token))).await;
+            }
+        })
+    }
+    fn get_client(&mut self) -> &mut Client<C, Body> {
+        &mut self.client
+    }
+}
@@ -0,0 +1,476 @@
+/*!
+In this module there's `NtlmClient`, answering the `WWW-Authenticate: NTLM` challenges
+of the legacy intranet services (e.g. IIS) by the NTLMv2 handshake as a `BaseClient` of hyper.
+
+# Remarks
+
+* NTLM authenticates the connections (not the requests),
+  so the handshake is done over a pinned connection (`hyper::client::conn`) instead of the pooled ones of hyper,
+  and the authenticated connections are reused by the following requests.
+* The messages follow MS-NLMP (NTLMv2 responses, without the signing/sealing).
+
+*/
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use futures::future::poll_fn;
+use futures::task::noop_waker_ref;
+use futures::FutureExt;
+use hmac::{Hmac, Mac};
+use http::method::Method;
+use hyper::client::conn::{self, SendRequest};
+use hyper::client::connect::{Connect, Connection};
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderValue, AUTHORIZATION, WWW_AUTHENTICATE};
+use hyper::service::Service;
+use hyper::{Body, Client, HeaderMap, Request, Response, Result, StatusCode, Uri};
+use md4::{Digest, Md4};
+use md5::Md5;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::common::HasRequestOptions;
+use super::simple_http::BaseClient;
+
+pub const NTLM_SCHEME: &str = "NTLM";
+
+const SIGNATURE: &[u8; 8] = b"NTLMSSP\0";
+// UNICODE | OEM | REQUEST_TARGET | NTLM | ALWAYS_SIGN | EXTENDED_SESSIONSECURITY | 128 | 56
+const NEGOTIATE_FLAGS: u32 = 0xa008_8207;
+const NEGOTIATE_UNICODE: u32 = 0x0000_0001;
+// The `MsvAvTimestamp` of the target info
+const AV_ID_TIMESTAMP: u16 = 7;
+// The seconds between 1601-01-01 (FILETIME) and 1970-01-01
+const FILETIME_UNIX_EPOCH_SECS: u64 = 11_644_473_600;
+
+/*
+`NtlmCredentials` The Windows account of the NTLM handshake (the password is masked in the Debug output)
+*/
+#[derive(Clone)]
+pub struct NtlmCredentials {
+    pub domain: String,
+    pub username: String,
+    pub password: String,
+    pub workstation: String,
+}
+impl NtlmCredentials {
+    pub fn new(
+        domain: impl Into<String>,
+        username: impl Into<String>,
+        password: impl Into<String>,
+    ) -> Self {
+        NtlmCredentials {
+            domain: domain.into(),
+            username: username.into(),
+            password: password.into(),
+            workstation: String::new(),
+        }
+    }
+}
+impl std::fmt::Debug for NtlmCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("NtlmCredentials")
+            .field("domain", &self.domain)
+            .field("username", &self.username)
+            .field("password", &"***")
+            .field("workstation", &self.workstation)
+            .finish()
+    }
+}
+
+/*
+`NtlmChallenge` The CHALLENGE_MESSAGE (type 2) of the server
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NtlmChallenge {
+    pub flags: u32,
+    pub server_challenge: [u8; 8],
+    pub target_info: Vec<u8>,
+}
+impl NtlmChallenge {
+    pub fn parse(message: &[u8]) -> Option<Self> {
+        if message.len() < 48 || &message[0..8] != SIGNATURE || read_u32(message, 8)? != 2 {
+            return None;
+        }
+        let target_info_len = read_u16(message, 40)? as usize;
+        let target_info_offset = read_u32(message, 44)? as usize;
+        Some(NtlmChallenge {
+            flags: read_u32(message, 20)?,
+            server_challenge: message[24..32].try_into().ok()?,
+            target_info: message
+                .get(target_info_offset..target_info_offset.checked_add(target_info_len)?)?
+                .to_vec(),
+        })
+    }
+
+    // The `MsvAvTimestamp` (FILETIME) of the target info
+    pub fn timestamp(&self) -> Option<u64> {
+        let mut offset = 0;
+        while let (Some(id), Some(len)) = (
+            read_u16(&self.target_info, offset),
+            read_u16(&self.target_info, offset + 2),
+        ) {
+            let value = self
+                .target_info
+                .get(offset + 4..offset + 4 + len as usize)?;
+            match id {
+                // MsvAvEOL
+                0 => return None,
+                AV_ID_TIMESTAMP => return Some(u64::from_le_bytes(value.try_into().ok()?)),
+                _ => offset += 4 + len as usize,
+            }
+        }
+        None
+    }
+}
+
+// The NEGOTIATE_MESSAGE (type 1)
+pub fn negotiate_message() -> Vec<u8> {
+    let mut message = SIGNATURE.to_vec();
+    message.extend_from_slice(&1u32.to_le_bytes());
+    message.extend_from_slice(&NEGOTIATE_FLAGS.to_le_bytes());
+    // The empty domain & workstation
+    message.extend_from_slice(&[0; 16]);
+    message
+}
+
+// NTOWFv2: `HMAC_MD5(MD4(UNICODE(password)), UNICODE(UPPERCASE(username) + domain))`
+pub fn ntowf_v2(credentials: &NtlmCredentials) -> [u8; 16] {
+    let password_hash = Md4::digest(utf16le(&credentials.password));
+    hmac_md5(
+        &password_hash,
+        &[&utf16le(
+            &(credentials.username.to_uppercase() + &credentials.domain),
+        )],
+    )
+}
+
+// The AUTHENTICATE_MESSAGE (type 3) of the NTLMv2 responses
+pub fn authenticate_message(
+    credentials: &NtlmCredentials,
+    challenge: &NtlmChallenge,
+    client_challenge: [u8; 8],
+    timestamp: u64,
+) -> Vec<u8> {
+    let key = ntowf_v2(credentials);
+
+    // NTLMv2_CLIENT_CHALLENGE
+    let mut temp = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    temp.extend_from_slice(&timestamp.to_le_bytes());
+    temp.extend_from_slice(&client_challenge);
+    temp.extend_from_slice(&[0; 4]);
+    temp.extend_from_slice(&challenge.target_info);
+    temp.extend_from_slice(&[0; 4]);
+    let nt_proof = hmac_md5(&key, &[&challenge.server_challenge, &temp]);
+    let nt_response = [&nt_proof[..], &temp].concat();
+    let lm_response = [
+        &hmac_md5(&key, &[&challenge.server_challenge, &client_challenge])[..],
+        &client_challenge,
+    ]
+    .concat();
+
+    let unicode = challenge.flags & NEGOTIATE_UNICODE != 0;
+    let encode = |text: &str| {
+        if unicode {
+            utf16le(text)
+        } else {
+            text.as_bytes().to_vec()
+        }
+    };
+    let fields = [
+        lm_response,
+        nt_response,
+        encode(&credentials.domain),
+        encode(&credentials.username),
+        encode(&credentials.workstation),
+        // The empty EncryptedRandomSessionKey
+        Vec::new(),
+    ];
+
+    // The header is 64 bytes, then the payload of the fields
+    let mut message = SIGNATURE.to_vec();
+    message.extend_from_slice(&3u32.to_le_bytes());
+    let mut offset = 64u32;
+    for field in &fields {
+        message.extend_from_slice(&(field.len() as u16).to_le_bytes());
+        message.extend_from_slice(&(field.len() as u16).to_le_bytes());
+        message.extend_from_slice(&offset.to_le_bytes());
+        offset += field.len() as u32;
+    }
+    message.extend_from_slice(&(challenge.flags & NEGOTIATE_FLAGS).to_le_bytes());
+    for field in &fields {
+        message.extend_from_slice(field);
+    }
+    message
+}
+
+// The token of the `NTLM` challenge (`Some(None)` without the token), or `None` if it's not challenged
+pub fn ntlm_challenge(headers: &HeaderMap) -> Option<Option<Vec<u8>>> {
+    headers
+        .get_all(WWW_AUTHENTICATE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .find_map(|challenge| {
+            let mut parts = challenge.splitn(2, ' ');
+            if !parts.next()?.eq_ignore_ascii_case(NTLM_SCHEME) {
+                return None;
+            }
+            Some(
+                parts
+                    .next()
+                    .map(str::trim)
+                    .filter(|token| !token.is_empty())
+                    .and_then(|token| base64::decode(token).ok()),
+            )
+        })
+}
+
+fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+fn hmac_md5(key: &[u8], data: &[&[u8]]) -> [u8; 16] {
+    let mut mac = <Hmac<Md5> as Mac>::new_from_slice(key).expect("HMAC takes any key length");
+    for data in data {
+        mac.update(data);
+    }
+    mac.finalize().into_bytes().into()
+}
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/**
+`NtlmClient` answers the `NTLM` challenges by the NTLMv2 handshake as a `BaseClient`.
+
+# Remarks
+
+* The requests are sent over the pinned connections made by the `connector`:
+  the `401` responses challenging `NTLM` start the handshake over the same connection,
+  and the original request is sent again with the AUTHENTICATE_MESSAGE.
+* The authenticated connections are kept (by the scheme & the authority) for the following requests,
+  and they're dropped by `close_idle()`.
+* The request body is buffered so that it could be sent again.
+* The challenges not answered (e.g. the illegal messages or the rejected credentials) are returned as they are.
+* The requests of `RequestOptions::skip_auth` and the failed connections are sent by `client`
+  (the pooled connections of hyper, reporting the errors of the connections).
+
+*/
+pub struct NtlmClient<C> {
+    pub client: Client<C, Body>,
+    pub connector: C,
+    pub credentials: NtlmCredentials,
+    connections: Arc<Mutex<HashMap<String, Vec<SendRequest<Body>>>>>,
+}
+impl<C: Connect + Clone> NtlmClient<C> {
+    pub fn new_with_options(connector: C, credentials: NtlmCredentials) -> Self {
+        NtlmClient {
+            client: Client::builder().build(connector.clone()),
+            connector,
+            credentials,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+impl NtlmClient<HttpConnector> {
+    pub fn new_for_hyper(credentials: NtlmCredentials) -> Self {
+        Self::new_with_options(HttpConnector::new(), credentials)
+    }
+}
+
+// The key of the kept connections
+fn connection_key(uri: &Uri) -> Option<String> {
+    Some(format!(
+        "{}://{}",
+        uri.scheme_str()?,
+        uri.authority()?.as_str()
+    ))
+}
+
+// Send the request over the connection, reading the body of the challenges so that the connection could be reused
+async fn send_pinned(
+    sender: &mut SendRequest<Body>,
+    req: Request<Body>,
+) -> Result<(Response<Body>, Option<Option<Vec<u8>>>)> {
+    poll_fn(|cx| sender.poll_ready(cx)).await?;
+    let res = sender.send_request(req).await?;
+    let challenge = match res.status() {
+        StatusCode::UNAUTHORIZED => ntlm_challenge(res.headers()),
+        _ => None,
+    };
+    if challenge.is_none() {
+        return Ok((res, None));
+    }
+    let (parts, body) = res.into_parts();
+    let body = hyper::body::to_bytes(body).await?;
+    Ok((Response::from_parts(parts, Body::from(body)), challenge))
+}
+
+impl<C> BaseClient<Client<C, Body>, Request<Body>, Result<Response<Body>>, Method, HeaderMap, Body>
+    for NtlmClient<C>
+where
+    C: Connect + Service<Uri> + Clone + Send + Sync + 'static,
+    C::Response: AsyncRead + AsyncWrite + Connection + Unpin + Send + 'static,
+    C::Future: Send + 'static,
+{
+    fn request(&self, req: Request<Body>) -> Pin<Box<dyn Future<Output = Result<Response<Body>>>>> {
+        let client = self.client.clone();
+        let mut connector = self.connector.clone();
+        let credentials = self.credentials.clone();
+        let connections = self.connections.clone();
+
+        Box::pin(async move {
+            let options = req.get_request_options();
+            let key = match connection_key(req.uri()) {
+                Some(key) if !options.skip_auth => key,
+                _ => return client.request(req).await,
+            };
+            let (parts, body) = req.into_parts();
+            let body = hyper::body::to_bytes(body).await?;
+            let new_request = |message: Option<&[u8]>, body: Bytes| {
+                let mut req = Request::new(Body::from(body));
+                *req.method_mut() = parts.method.clone();
+                // The origin-form over the pinned connection
+                *req.uri_mut() = parts
+                    .uri
+                    .path_and_query()
+                    .map_or("/", |v| v.as_str())
+                    .parse()
+                    .unwrap_or_default();
+                *req.version_mut() = parts.version;
+                *req.headers_mut() = parts.headers.clone();
+                if let (Some(authority), false) = (
+                    parts.uri.authority(),
+                    req.headers().contains_key(hyper::header::HOST),
+                ) {
+                    if let Ok(host) = HeaderValue::from_str(authority.as_str()) {
+                        req.headers_mut().insert(hyper::header::HOST, host);
+                    }
+                }
+                if let Some(Ok(mut value)) = message.map(|message| {
+                    HeaderValue::from_str(
+                        &(NTLM_SCHEME.to_string() + " " + &base64::encode(message)),
+                    )
+                }) {
+                    value.set_sensitive(true);
+                    req.headers_mut().insert(AUTHORIZATION, value);
+                }
+                req.set_request_options(options.clone());
+                req
+            };
+
+            // A kept connection (ready now), or a new one
+            let kept = {
+                let mut connections = connections.lock().unwrap();
+                let kept = connections.entry(key.clone()).or_default();
+                let mut cx = Context::from_waker(noop_waker_ref());
+                let mut ready = None;
+                // The closed ones are dropped, and the busy ones are kept
+                for mut sender in std::mem::take(kept) {
+                    match sender.poll_ready(&mut cx) {
+                        Poll::Ready(Ok(())) if ready.is_none() => ready = Some(sender),
+                        Poll::Ready(Err(_)) => {}
+                        _ => kept.push(sender),
+                    }
+                }
+                ready
+            };
+            let mut sender = match kept {
+                Some(sender) => sender,
+                None => {
+                    let connected = match poll_fn(|cx| connector.poll_ready(cx)).await {
+                        Ok(_) => connector.call(parts.uri.clone()).await,
+                        Err(e) => Err(e),
+                    };
+                    let io = match connected {
+                        Ok(io) => io,
+                        Err(_) => return client.request(new_request(None, body)).await,
+                    };
+                    let (sender, connection) = conn::handshake(io).await?;
+                    tokio::spawn(connection.map(|_| ()));
+                    sender
+                }
+            };
+
+            let (res, challenge) =
+                send_pinned(&mut sender, new_request(None, body.clone())).await?;
+            let result = match challenge {
+                // Authenticated already (or not challenged at all)
+                None => Ok(res),
+                Some(_) => {
+                    // NEGOTIATE_MESSAGE -> CHALLENGE_MESSAGE
+                    let (res, challenge) = send_pinned(
+                        &mut sender,
+                        new_request(Some(&negotiate_message()), Bytes::new()),
+                    )
+                    .await?;
+                    match challenge
+                        .flatten()
+                        .as_deref()
+                        .and_then(NtlmChallenge::parse)
+                    {
+                        Some(challenge) => {
+                            let mut client_challenge = [0; 8];
+                            let timestamp = match (
+                                challenge.timestamp(),
+                                getrandom::getrandom(&mut client_challenge),
+                            ) {
+                                (_, Err(_)) => return Ok(res),
+                                (Some(timestamp), _) => timestamp,
+                                (None, _) => {
+                                    let now = SystemTime::now()
+                                        .duration_since(UNIX_EPOCH)
+                                        .unwrap_or(Duration::ZERO);
+                                    (now.as_secs() + FILETIME_UNIX_EPOCH_SECS) * 10_000_000
+                                        + u64::from(now.subsec_nanos() / 100)
+                                }
+                            };
+                            // AUTHENTICATE_MESSAGE with the original request
+                            let message = authenticate_message(
+                                &credentials,
+                                &challenge,
+                                client_challenge,
+                                timestamp,
+                            );
+                            send_pinned(&mut sender, new_request(Some(&message), body))
+                                .await
+                                .map(|(res, _)| res)
+                        }
+                        None => Ok(res),
+                    }
+                }
+            };
+
+            // Keep the authenticated connection
+            if matches!(&result, Ok(res) if res.status() != StatusCode::UNAUTHORIZED) {
+                connections
+                    .lock()
+                    .unwrap()
+                    .entry(key)
+                    .or_default()
+                    .push(sender);
+            }
+            result
+        })
+    }
+    fn get_client(&mut self) -> &mut Client<C, Body> {
+        &mut self.client
+    }
+    fn close_idle(&mut self) {
+        self.connections.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,33 @@
+/*!
+In this module there're the re-exports of the commonly needed items for calling the APIs,
+so that `use http_api_service::prelude::*;` is enough for the common cases
+(without the direct dependencies on `http`/`url`).
+
+# Remarks
+
+* `CommonAPI` is the one of `bind_hyper` (**feature: for_hyper**),
+  or the one of `bind_ureq` if only **feature: for_ureq** is enabled.
+* `path_param!`/`query_param!` are re-exported as well (they're also at the crate root).
+
+*/
+
+// The serializers/deserializers/services are shared by `Arc`
+pub use std::sync::Arc;
+
+pub use http::method::Method;
+pub use url::Url;
+
+pub use super::any_service::AnyService;
+pub use super::common::{Headers, PathParam, QueryParam};
+pub use super::simple_api::{
+    ApiBuilder, BodyDeserializer, BodySerializer, RequestFormatter, RequestLogHook,
+    DEFAULT_DUMMY_BYPASS_DESERIALIZER, DEFAULT_DUMMY_BYPASS_SERIALIZER_FOR_BYTES,
+};
+#[cfg(feature = "for_serde")]
+pub use super::simple_api::{DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER};
+pub use super::{path_param, query_param};
+
+#[cfg(feature = "for_hyper")]
+pub use super::bind_hyper::CommonAPI;
+#[cfg(all(feature = "for_ureq", not(feature = "for_hyper")))]
+pub use super::bind_ureq::CommonAPI;
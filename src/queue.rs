@@ -0,0 +1,487 @@
+/*!
+In this module there're implementations of `UploadQueue`,
+the background queue sending API calls with retry/backoff,
+and the queued requests are persisted by `QueueStore` (surviving process restarts).
+
+It's made for telemetry/analytics/file uploads, which could be sent later.
+
+# Remarks
+
+* The requests are sent by `AnyService`, so the queue works with whichever binding is enabled.
+* The failed sendings (e.g. connection failures) and the `5xx`/`429` responses are retried,
+  the other responses (including `4xx`) are treated as delivered.
+* `FileQueueStore` skips the corrupt item files and renames them to `{id}.json.corrupt`.
+* The schedules (backoff/polling) follow `UploadQueue.clock` (`SystemClock` by default).
+* The writes called while the services are offline are queued to their offline queues (`set_offline_queue()`),
+  they're flushed when it's back online if the `UploadQueue` shares the `OfflineSwitch`.
+
+*/
+
+use std::collections::BTreeMap;
+use std::error::Error as StdError;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::result::Result as StdResult;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::method::Method;
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Builder;
+
+use super::any_service::{AnyResponse, AnyService};
+use super::common::{
+    system_clock, Clock, Headers, OfflineError, OfflineSwitch, PathParam, QueryParam, SystemClock,
+};
+
+static QUEUED_REQUEST_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/*
+`QueuedRequest` The serialized API call to be sent by `UploadQueue`
+*/
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueuedRequest {
+    // The ids are ordered by the enqueueing time
+    pub id: String,
+    pub method: String,
+    pub relative_url: String,
+    pub headers: Vec<(String, String)>,
+    pub content_type: String,
+    pub body: Vec<u8>,
+
+    // The count of the failed sendings
+    pub attempts: u32,
+    // The epoch millis of the next sending
+    pub next_attempt_at: u64,
+}
+
+impl QueuedRequest {
+    pub fn new(
+        method: Method,
+        relative_url: impl Into<String>,
+        header: Option<Headers>,
+        content_type: impl Into<String>,
+        body: impl Into<Bytes>,
+    ) -> Self {
+        let now = epoch_millis(&SystemClock);
+        QueuedRequest {
+            id: format!(
+                "{:013}-{:06}",
+                now,
+                QUEUED_REQUEST_COUNTER.fetch_add(1, Ordering::SeqCst) % 1_000_000
+            ),
+            method: method.as_str().to_string(),
+            relative_url: relative_url.into(),
+            headers: header
+                .map(|header| header.into_iter().collect())
+                .unwrap_or_default(),
+            content_type: content_type.into(),
+            body: body.into().to_vec(),
+            attempts: 0,
+            next_attempt_at: now,
+        }
+    }
+
+    // Make it with the path params and the query params applied to the `relative_url`
+    pub fn new_with_params(
+        method: Method,
+        relative_url: impl Into<String>,
+        path_param: Option<PathParam>,
+        query_param: Option<QueryParam>,
+        header: Option<Headers>,
+        content_type: impl Into<String>,
+        body: impl Into<Bytes>,
+    ) -> Self {
+        let mut relative_url = relative_url.into();
+        if let Some(path_param) = path_param {
+            for (k, v) in path_param.into_iter() {
+                relative_url = relative_url.replace(&("{".to_string() + &k + "}"), &v);
+            }
+        }
+        if let Some(query_param) = query_param {
+            if !query_param.is_empty() {
+                let query = url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(query_param.iter())
+                    .finish();
+                relative_url += if relative_url.contains('?') { "&" } else { "?" };
+                relative_url += &query;
+            }
+        }
+        Self::new(method, relative_url, header, content_type, body)
+    }
+}
+
+/**
+Enqueue the write called while it's offline, returns the `OfflineError` telling the queued id.
+
+It's called by the bindings, and the reads (`is_safe_method()`) are not queued.
+*/
+pub(crate) fn enqueue_offline(store: &dyn QueueStore, item: QueuedRequest) -> Box<dyn StdError> {
+    match store.save(&item) {
+        Ok(_) => Box::new(OfflineError::new_queued(
+            format!("{} {} is queued", item.method, item.relative_url),
+            item.id,
+        )),
+        Err(e) => e,
+    }
+}
+
+/*
+`QueueStore` The durable store of `QueuedRequest`s
+*/
+pub trait QueueStore: Send + Sync {
+    // Insert or update the item by its id
+    fn save(&self, item: &QueuedRequest) -> StdResult<(), Box<dyn StdError>>;
+    fn remove(&self, id: &str) -> StdResult<(), Box<dyn StdError>>;
+    // Load all items ordered by their ids
+    fn load_all(&self) -> StdResult<Vec<QueuedRequest>, Box<dyn StdError>>;
+    // Persist the pending writes (e.g. by `shutdown()`), nothing by default
+    fn flush(&self) -> StdResult<(), Box<dyn StdError>> {
+        Ok(())
+    }
+}
+
+/*
+`MemoryQueueStore` The in-memory (non-durable) `QueueStore`
+*/
+#[derive(Debug, Default)]
+pub struct MemoryQueueStore {
+    items: Mutex<BTreeMap<String, QueuedRequest>>,
+}
+impl MemoryQueueStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+impl QueueStore for MemoryQueueStore {
+    fn save(&self, item: &QueuedRequest) -> StdResult<(), Box<dyn StdError>> {
+        self.items
+            .lock()
+            .unwrap()
+            .insert(item.id.clone(), item.clone());
+        Ok(())
+    }
+    fn remove(&self, id: &str) -> StdResult<(), Box<dyn StdError>> {
+        self.items.lock().unwrap().remove(id);
+        Ok(())
+    }
+    fn load_all(&self) -> StdResult<Vec<QueuedRequest>, Box<dyn StdError>> {
+        Ok(self.items.lock().unwrap().values().cloned().collect())
+    }
+}
+
+/*
+`FileQueueStore` The `QueueStore` keeping each item as a JSON file (`{id}.json`) in the directory
+*/
+#[derive(Debug, Clone)]
+pub struct FileQueueStore {
+    pub dir: PathBuf,
+}
+impl FileQueueStore {
+    // Create the store (and the directory if it doesn't exist)
+    pub fn new(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(FileQueueStore { dir })
+    }
+
+    fn path_of(&self, id: &str) -> PathBuf {
+        self.dir.join(id.to_string() + ".json")
+    }
+}
+impl QueueStore for FileQueueStore {
+    fn save(&self, item: &QueuedRequest) -> StdResult<(), Box<dyn StdError>> {
+        // Write to a temporary file then rename it, so a crash never leaves a partial item
+        let temp_path = self.dir.join(item.id.clone() + ".json.tmp");
+        fs::write(&temp_path, serde_json::to_vec(item)?)?;
+        fs::rename(&temp_path, self.path_of(&item.id))?;
+        Ok(())
+    }
+    fn remove(&self, id: &str) -> StdResult<(), Box<dyn StdError>> {
+        match fs::remove_file(self.path_of(id)) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+    fn load_all(&self) -> StdResult<Vec<QueuedRequest>, Box<dyn StdError>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&self.dir)?.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+
+        let mut items = Vec::with_capacity(paths.len());
+        for path in paths.into_iter() {
+            match fs::read(&path) {
+                Ok(bytes) => match serde_json::from_slice(&bytes) {
+                    Ok(item) => items.push(item),
+                    // Quarantine it, so the other items are still sent
+                    Err(_) => {
+                        let _ = fs::rename(&path, path.with_extension("json.corrupt"));
+                    }
+                },
+                // e.g. removed by another worker meanwhile
+                Err(_) => continue,
+            }
+        }
+        Ok(items)
+    }
+}
+
+/*
+`QueueOptions` The retry/backoff options of `UploadQueue`
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueueOptions {
+    // The items are dropped after `max_attempts` failed sendings (`0` to retry forever)
+    pub max_attempts: u32,
+    // The delay after the first failure, it's doubled after each failure
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // The interval of the background worker checking the due items
+    pub poll_interval: Duration,
+}
+impl Default for QueueOptions {
+    fn default() -> Self {
+        QueueOptions {
+            max_attempts: 10,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5 * 60),
+            poll_interval: Duration::from_secs(1),
+        }
+    }
+}
+impl QueueOptions {
+    // The delay before the next sending after `attempts` failed sendings
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(attempts.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.initial_backoff
+            .checked_mul(factor)
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+}
+
+/*
+`QueueRunReport` The result of `UploadQueue::run_once()`
+*/
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct QueueRunReport {
+    pub sent: usize,
+    pub failed: usize,
+    // The items failed `max_attempts` times and removed
+    pub dropped: usize,
+}
+
+/*
+`UploadQueue` The queue sending the persisted API calls with retry/backoff
+*/
+pub struct UploadQueue {
+    pub service: AnyService,
+    pub store: Arc<dyn QueueStore>,
+    pub options: QueueOptions,
+    pub clock: Arc<dyn Clock>,
+    // Nothing is sent while it's offline (e.g. shared with the `service`)
+    pub offline: Option<OfflineSwitch>,
+}
+
+impl UploadQueue {
+    pub fn new(service: AnyService, store: Arc<dyn QueueStore>) -> Self {
+        Self::new_with_options(service, store, QueueOptions::default())
+    }
+    pub fn new_with_options(
+        service: AnyService,
+        store: Arc<dyn QueueStore>,
+        options: QueueOptions,
+    ) -> Self {
+        UploadQueue {
+            service,
+            store,
+            options,
+            clock: system_clock(),
+            offline: None,
+        }
+    }
+
+    /**
+    Persist the API call, it will be sent by `run_once()` or the background worker.
+
+    # Arguments
+
+    * `relative_url` - The relative url to the base url of the `service`.
+
+    */
+    pub fn enqueue(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        header: Option<Headers>,
+        content_type: impl Into<String>,
+        body: impl Into<Bytes>,
+    ) -> StdResult<String, Box<dyn StdError>> {
+        let mut item = QueuedRequest::new(method, relative_url, header, content_type, body);
+        item.next_attempt_at = epoch_millis(self.clock.as_ref());
+        self.store.save(&item)?;
+        Ok(item.id)
+    }
+
+    pub fn pending(&self) -> StdResult<Vec<QueuedRequest>, Box<dyn StdError>> {
+        self.store.load_all()
+    }
+
+    // Send the due items once (in the enqueueing order), nothing is sent while it's offline
+    pub async fn run_once(&self) -> StdResult<QueueRunReport, Box<dyn StdError>> {
+        let mut report = QueueRunReport::default();
+        if let Some(offline) = &self.offline {
+            if offline.is_offline() {
+                return Ok(report);
+            }
+        }
+        // Nothing is sent in the maintenance windows of the upstream (it'd be queued again)
+        if let Some(policy) = self.service.get_maintenance_policy() {
+            if policy.window_end(self.clock.system_now()).is_some() {
+                return Ok(report);
+            }
+        }
+
+        for mut item in self.store.load_all()?.into_iter() {
+            if item.next_attempt_at > epoch_millis(self.clock.as_ref()) {
+                continue;
+            }
+
+            match self.send(&item).await {
+                Ok(res) if !is_retryable_status(res.status) => {
+                    self.store.remove(&item.id)?;
+                    report.sent += 1;
+                }
+                _ => {
+                    item.attempts += 1;
+                    if self.options.max_attempts > 0 && item.attempts >= self.options.max_attempts {
+                        self.store.remove(&item.id)?;
+                        report.dropped += 1;
+                    } else {
+                        item.next_attempt_at = epoch_millis(self.clock.as_ref())
+                            .saturating_add(self.options.backoff(item.attempts).as_millis() as u64);
+                        self.store.save(&item)?;
+                        report.failed += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn send(&self, item: &QueuedRequest) -> StdResult<AnyResponse, Box<dyn StdError>> {
+        self.service
+            .do_request_response(
+                Method::from_bytes(item.method.as_bytes())?,
+                Some(Headers::from(item.headers.clone())),
+                item.relative_url.clone(),
+                item.content_type.clone(),
+                None::<PathParam>,
+                None::<QueryParam>,
+                Bytes::from(item.body.clone()),
+            )
+            .await
+    }
+
+    /**
+    Spawn the background worker thread calling `run_once()` every `poll_interval`.
+
+    # Arguments
+
+    * `factory` - Create the `UploadQueue` on the worker thread
+      (the services are not `Send`).
+
+    # Remarks
+
+    * The worker runs on its own (current thread) tokio runtime.
+    * It stops within a `poll_interval` after `QueueWorker::stop()` or dropping the `QueueWorker`.
+    * The error of the last `run_once()` is kept by `QueueWorker::last_error()`.
+
+    */
+    pub fn spawn_worker(
+        factory: impl FnOnce() -> UploadQueue + Send + 'static,
+    ) -> io::Result<QueueWorker> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_for_thread = stopped.clone();
+        let last_error = Arc::new(Mutex::new(None));
+        let last_error_for_thread = last_error.clone();
+
+        let handle = thread::Builder::new()
+            .name("upload-queue".to_string())
+            .spawn(move || {
+                let queue = factory();
+                runtime.block_on(async move {
+                    while !stopped_for_thread.load(Ordering::SeqCst) {
+                        let result = queue.run_once().await;
+                        *last_error_for_thread.lock().unwrap() =
+                            result.err().map(|e| e.to_string());
+                        queue.clock.sleep(queue.options.poll_interval).await;
+                    }
+                });
+            })?;
+
+        Ok(QueueWorker {
+            stopped,
+            last_error,
+            handle: Some(handle),
+        })
+    }
+}
+
+/*
+`QueueWorker` The handle of the background worker of `UploadQueue`
+*/
+pub struct QueueWorker {
+    stopped: Arc<AtomicBool>,
+    last_error: Arc<Mutex<Option<String>>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+impl QueueWorker {
+    // The error of the last run (e.g. the store is unreadable), `None` if it succeeded
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    // Stop the worker and wait for it
+    pub fn stop(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+impl Drop for QueueWorker {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+// The server errors & the throttling are sent again, the other responses are delivered
+fn is_retryable_status(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+// The clocks before the epoch are taken as the epoch (the items are due at once)
+fn epoch_millis(clock: &dyn Clock) -> u64 {
+    clock
+        .system_now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
@@ -0,0 +1,161 @@
+/*!
+In this module there're the one-shot helpers (`get_json()`/`post_json()`),
+calling the absolute urls without building a service (e.g. for scripts and examples).
+
+# Remarks
+
+* The calls share a lazily initialized global client, so the repeated calls reuse the pooled connections
+  (hyper's `Client` if `for_hyper` is enabled, otherwise ureq's `Agent`).
+* Each call is sent by a default `AnyService` over the shared client
+  (the default timeout, without interceptors/default headers).
+* The non-`2xx` responses fail with `StatusError` (carrying the body) instead of being deserialized.
+* The pooled connections of hyper are driven by the runtime which made them,
+  so the calls are expected to be on one runtime.
+
+*/
+
+use std::error::Error as StdError;
+use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use bytes::Bytes;
+use http::method::Method;
+use serde::{de::DeserializeOwned, Serialize};
+use url::Url;
+
+use super::any_service::AnyService;
+use super::common::{with_extensions, AbsoluteUrl, Extensions, Headers, PathParam, QueryParam};
+use super::simple_api::{
+    BodyDeserializer, BodySerializer, DEFAULT_SERDE_JSON_DESERIALIZER,
+    DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES,
+};
+
+#[cfg(feature = "for_hyper")]
+use hyper::{client::HttpConnector, Body, Client};
+
+#[cfg(feature = "for_hyper")]
+use super::bind_hyper::{CommonAPI, HyperClient, HyperClientOptions};
+#[cfg(all(feature = "for_ureq", not(feature = "for_hyper")))]
+use super::bind_ureq::{CommonAPI, UreqClient};
+
+const MEDIA_TYPE_JSON: &str = "application/json";
+
+/*
+`StatusError` The response of the one-shot call is not successful (not `2xx`)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusError {
+    details: String,
+    pub status: u16,
+    pub body: Bytes,
+}
+impl StdError for StatusError {}
+impl StatusError {
+    pub fn new(msg: impl Into<String>, status: u16, body: Bytes) -> StatusError {
+        StatusError {
+            details: msg.into(),
+            status,
+            body,
+        }
+    }
+}
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "StatusError: {} (status {})", self.details, self.status)
+    }
+}
+
+// The default service over the shared client
+#[cfg(feature = "for_hyper")]
+fn shared_service() -> AnyService {
+    static CLIENT: OnceLock<Client<HttpConnector, Body>> = OnceLock::new();
+    let client = CLIENT.get_or_init(Client::new).clone();
+
+    let base = CommonAPI::new_for_hyper().as_base_service_shared();
+    base.set_client(Arc::new(Mutex::new(HyperClient {
+        client,
+        thread_pool: None,
+        connector: None,
+        options: HyperClientOptions::default(),
+    })));
+    AnyService::Hyper(base)
+}
+#[cfg(all(feature = "for_ureq", not(feature = "for_hyper")))]
+fn shared_service() -> AnyService {
+    static AGENT: OnceLock<ureq::Agent> = OnceLock::new();
+    let agent = AGENT.get_or_init(ureq::Agent::new).clone();
+
+    let base = CommonAPI::new_for_ureq().as_base_service_shared();
+    base.set_client(Arc::new(Mutex::new(UreqClient {
+        agent,
+        thread_pool: None,
+    })));
+    AnyService::Ureq(base)
+}
+
+// Call the absolute url by the shared service, the non-2xx responses are errors
+async fn call_absolute(
+    method: Method,
+    url: &str,
+    content_type: &str,
+    body: Bytes,
+) -> StdResult<Bytes, Box<dyn StdError>> {
+    let mut extensions = Extensions::new();
+    extensions.insert(AbsoluteUrl {
+        url: Url::parse(url)?,
+        skip_auth: false,
+    });
+    let mut header = Headers::new();
+    header.insert("Accept", MEDIA_TYPE_JSON);
+
+    let res = with_extensions(
+        extensions,
+        shared_service().do_request_response(
+            method.clone(),
+            Some(header),
+            "",
+            content_type,
+            None::<PathParam>,
+            None::<QueryParam>,
+            body,
+        ),
+    )
+    .await?;
+    if !(200..300).contains(&res.status) {
+        return Err(Box::new(StatusError::new(
+            format!("{} {}", method, url),
+            res.status,
+            res.body,
+        )));
+    }
+    Ok(res.body)
+}
+
+/**
+`GET` the absolute `url` and deserialize the JSON response.
+
+# Example
+
+```ignore
+let product = http_api_service::quick::get_json::<Product>("https://example.com/products/3").await?;
+```
+
+*/
+pub async fn get_json<R>(url: &str) -> StdResult<Box<R>, Box<dyn StdError>>
+where
+    R: DeserializeOwned + 'static,
+{
+    let body = call_absolute(Method::GET, url, "", Bytes::new()).await?;
+    DEFAULT_SERDE_JSON_DESERIALIZER.decode(&body)
+}
+
+// `POST` the body as JSON to the absolute `url` and deserialize the JSON response
+pub async fn post_json<T, R>(url: &str, body: T) -> StdResult<Box<R>, Box<dyn StdError>>
+where
+    T: Serialize,
+    R: DeserializeOwned + 'static,
+{
+    let body = DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES.encode(body)?;
+    let body = call_absolute(Method::POST, url, MEDIA_TYPE_JSON, body).await?;
+    DEFAULT_SERDE_JSON_DESERIALIZER.decode(&body)
+}
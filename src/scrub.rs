@@ -0,0 +1,399 @@
+/*!
+In this module there're implementations of `Scrubber`,
+the hooks scrubbing the secrets and personal data from the headers/bodies before they're persisted.
+
+# Remarks
+
+* The `AuditLog` applies its `scrubber` to the tags (as headers) and the error texts of the records.
+* The built-ins are `HeaderScrubber` (by names), `RegexScrubber` (**feature: scrub**)
+  and `JsonPathScrubber` (**feature: for_serde**), they could be combined by `Vec<Arc<dyn Scrubber>>`.
+* The requests sent by the `UploadQueue` are not scrubbed (they're sent as they are).
+* `RequestFormatter` formats the requests for logging, masking the headers by a `HeaderScrubber`
+  and the values of the query parameters.
+
+*/
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use url::Url;
+
+use super::common::{Headers, RequestSummary};
+
+#[cfg(feature = "scrub")]
+use regex::Regex;
+
+#[cfg(feature = "for_serde")]
+use serde_json::Value;
+
+// The replacement of the scrubbed values
+pub const REDACTED: &str = "[REDACTED]";
+
+/*
+`Scrubber` Scrub the headers/bodies before they're persisted (e.g. logged/recorded)
+*/
+pub trait Scrubber: Send + Sync {
+    fn scrub_headers(&self, _headers: &mut Headers) {}
+    // The body is returned as it is by default
+    fn scrub_body(&self, _content_type: &str, body: Bytes) -> Bytes {
+        body
+    }
+
+    // Scrub the text (e.g. error messages) as a `text/plain` body
+    fn scrub_text(&self, text: &str) -> String {
+        let body = self.scrub_body("text/plain", Bytes::from(text.to_string()));
+        String::from_utf8_lossy(&body).to_string()
+    }
+}
+
+impl Scrubber for Vec<Arc<dyn Scrubber>> {
+    fn scrub_headers(&self, headers: &mut Headers) {
+        for scrubber in self.iter() {
+            scrubber.scrub_headers(headers);
+        }
+    }
+    fn scrub_body(&self, content_type: &str, body: Bytes) -> Bytes {
+        self.iter().fold(body, |body, scrubber| {
+            scrubber.scrub_body(content_type, body)
+        })
+    }
+}
+
+/*
+`HeaderScrubber` Redact the values of the headers by their names (case-insensitively)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeaderScrubber {
+    pub names: Vec<String>,
+}
+impl Default for HeaderScrubber {
+    // The credentials headers
+    fn default() -> Self {
+        HeaderScrubber::new(vec![
+            "Authorization",
+            "Proxy-Authorization",
+            "Cookie",
+            "Set-Cookie",
+            "X-Api-Key",
+        ])
+    }
+}
+impl HeaderScrubber {
+    pub fn new(names: Vec<impl Into<String>>) -> Self {
+        HeaderScrubber {
+            names: names.into_iter().map(|v| v.into()).collect(),
+        }
+    }
+}
+impl Scrubber for HeaderScrubber {
+    // The headers marked as sensitive (`Headers::set_sensitive()`) are redacted as well
+    fn scrub_headers(&self, headers: &mut Headers) {
+        let sensitive = headers.clone();
+        *headers = headers
+            .clone()
+            .into_iter()
+            .map(|(k, v)| {
+                if sensitive.is_sensitive(&k)
+                    || self.names.iter().any(|name| name.eq_ignore_ascii_case(&k))
+                {
+                    (k, REDACTED.to_string())
+                } else {
+                    (k, v)
+                }
+            })
+            .collect();
+    }
+}
+
+// Receive the formatted requests (e.g. to write them to the logger of the app)
+pub type RequestLogHook = Arc<dyn Fn(&str) + Send + Sync>;
+
+// The default max length (in bytes) of the bodies formatted by `RequestFormatter`
+pub const DEFAULT_FORMATTED_BODY_LENGTH: usize = 1024;
+
+/**
+`RequestFormatter` Format the requests for logging/debugging,
+with the sensitive headers masked and the bodies truncated.
+
+# Remarks
+
+* The headers are masked by the `sensitive_headers` (the credentials headers by default),
+  and the headers marked as sensitive (`Headers::set_sensitive()`) are masked as well.
+* The values of the query parameters (e.g. the api keys/signatures) are masked if `mask_query` (by default).
+* The streamed bodies (e.g. `hyper::Body`) are not formatted,
+  and the non UTF-8 bodies are formatted as their lengths.
+
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestFormatter {
+    pub sensitive_headers: HeaderScrubber,
+    pub max_body_length: usize,
+    pub mask_query: bool,
+}
+impl Default for RequestFormatter {
+    fn default() -> Self {
+        RequestFormatter {
+            sensitive_headers: HeaderScrubber::default(),
+            max_body_length: DEFAULT_FORMATTED_BODY_LENGTH,
+            mask_query: true,
+        }
+    }
+}
+impl RequestFormatter {
+    pub fn new(sensitive_headers: Vec<impl Into<String>>, max_body_length: usize) -> Self {
+        RequestFormatter {
+            sensitive_headers: HeaderScrubber::new(sensitive_headers),
+            max_body_length,
+            mask_query: true,
+        }
+    }
+    pub fn mask_query(mut self, mask_query: bool) -> Self {
+        self.mask_query = mask_query;
+        self
+    }
+
+    // Format the request as `METHOD URL Headers([...])` and the body (if it's buffered)
+    pub fn format(&self, request: &impl RequestSummary) -> String {
+        let mut headers = request.get_headers();
+        self.sensitive_headers.scrub_headers(&mut headers);
+
+        let mut formatted = format!(
+            "{} {} {:?}",
+            request.get_method(),
+            self.format_url(&request.get_url()),
+            headers
+        );
+        if let Some(body) = request.get_body() {
+            formatted.push(' ');
+            formatted.push_str(&self.format_body(&body));
+        }
+        formatted
+    }
+
+    // Format the url with the values of the query parameters masked (if `mask_query`)
+    pub fn format_url(&self, url: &str) -> String {
+        if !self.mask_query {
+            return url.to_string();
+        }
+        let (without_query, query) = match url.split_once('?') {
+            Some(v) => v,
+            None => return url.to_string(),
+        };
+        match Url::parse(url) {
+            Ok(mut url) => {
+                let names = url
+                    .query_pairs()
+                    .map(|(k, _)| k.to_string())
+                    .collect::<Vec<_>>();
+                url.query_pairs_mut()
+                    .clear()
+                    .extend_pairs(names.iter().map(|k| (k, REDACTED)));
+                url.to_string()
+            }
+            // Mask the whole query if it's not parsed
+            Err(_) => match query.split_once('#') {
+                Some((_, fragment)) => format!("{}?{}#{}", without_query, REDACTED, fragment),
+                None => format!("{}?{}", without_query, REDACTED),
+            },
+        }
+    }
+
+    // Format the body truncated to `max_body_length` bytes (at the char boundary)
+    pub fn format_body(&self, body: &[u8]) -> String {
+        let text = match std::str::from_utf8(body) {
+            Ok(text) => text,
+            Err(_) => return format!("<{} bytes>", body.len()),
+        };
+        if text.len() <= self.max_body_length {
+            return format!("{:?}", text);
+        }
+
+        let mut end = self.max_body_length;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        format!("{:?}... <{} bytes>", &text[..end], body.len())
+    }
+}
+
+/**
+`RegexScrubber` Redact the matches of the patterns in the header values and the UTF-8 bodies.
+
+# Remarks
+
+* The non UTF-8 bodies are returned as they are.
+
+*/
+#[cfg(feature = "scrub")]
+#[derive(Debug, Clone)]
+pub struct RegexScrubber {
+    pub patterns: Vec<Regex>,
+    pub replacement: String,
+}
+#[cfg(feature = "scrub")]
+impl RegexScrubber {
+    pub fn new(patterns: Vec<Regex>) -> Self {
+        RegexScrubber {
+            patterns,
+            replacement: REDACTED.to_string(),
+        }
+    }
+
+    // Compile the patterns
+    pub fn from_patterns(patterns: &[&str]) -> Result<Self, regex::Error> {
+        let mut compiled = Vec::with_capacity(patterns.len());
+        for pattern in patterns.iter() {
+            compiled.push(Regex::new(pattern)?);
+        }
+        Ok(Self::new(compiled))
+    }
+
+    fn scrub_str(&self, text: &str) -> String {
+        self.patterns
+            .iter()
+            .fold(text.to_string(), |text, pattern| {
+                pattern
+                    .replace_all(&text, self.replacement.as_str())
+                    .to_string()
+            })
+    }
+}
+#[cfg(feature = "scrub")]
+impl Scrubber for RegexScrubber {
+    fn scrub_headers(&self, headers: &mut Headers) {
+        *headers = headers
+            .clone()
+            .into_iter()
+            .map(|(k, v)| {
+                let v = self.scrub_str(&v);
+                (k, v)
+            })
+            .collect();
+    }
+    fn scrub_body(&self, _content_type: &str, body: Bytes) -> Bytes {
+        match std::str::from_utf8(&body) {
+            Ok(text) => Bytes::from(self.scrub_str(text)),
+            Err(_) => body,
+        }
+    }
+}
+
+#[cfg(feature = "for_serde")]
+#[derive(Debug, Clone, PartialEq)]
+enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+    // `*` or `[*]`
+    Wildcard,
+}
+
+/**
+`JsonPathScrubber` Redact the values at the JSON paths of the JSON bodies.
+
+# Arguments
+
+* `paths` - The JSON paths like `$.user.email`, `$.items[*].card` or `$.tokens[0]`
+  (keys, indices and wildcards only).
+
+# Remarks
+
+* Only the bodies whose content types contain `json` are scrubbed,
+  and the bodies failed to parse are returned as they are.
+
+*/
+#[cfg(feature = "for_serde")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPathScrubber {
+    paths: Vec<Vec<JsonPathSegment>>,
+}
+#[cfg(feature = "for_serde")]
+impl JsonPathScrubber {
+    pub fn new(paths: Vec<impl AsRef<str>>) -> Self {
+        JsonPathScrubber {
+            paths: paths
+                .iter()
+                .map(|path| Self::parse_path(path.as_ref()))
+                .collect(),
+        }
+    }
+
+    fn parse_path(path: &str) -> Vec<JsonPathSegment> {
+        let path = path.strip_prefix('$').unwrap_or(path);
+        let mut segments = Vec::new();
+        for part in path.split('.').filter(|part| !part.is_empty()) {
+            let mut parts = part.split('[');
+            if let Some(key) = parts.next() {
+                match key {
+                    "" => {}
+                    "*" => segments.push(JsonPathSegment::Wildcard),
+                    _ => segments.push(JsonPathSegment::Key(key.to_string())),
+                }
+            }
+            for index in parts {
+                let index = index.trim_end_matches(']');
+                segments.push(match index.parse::<usize>() {
+                    Ok(index) => JsonPathSegment::Index(index),
+                    Err(_) if index == "*" => JsonPathSegment::Wildcard,
+                    Err(_) => JsonPathSegment::Key(
+                        index.trim_matches(|c| c == '\'' || c == '"').to_string(),
+                    ),
+                });
+            }
+        }
+        segments
+    }
+
+    fn redact(value: &mut Value, path: &[JsonPathSegment]) {
+        let (segment, rest) = match path.split_first() {
+            Some(v) => v,
+            None => {
+                *value = Value::String(REDACTED.to_string());
+                return;
+            }
+        };
+
+        match (segment, value) {
+            (JsonPathSegment::Key(key), Value::Object(map)) => {
+                if let Some(value) = map.get_mut(key) {
+                    Self::redact(value, rest);
+                }
+            }
+            (JsonPathSegment::Index(index), Value::Array(list)) => {
+                if let Some(value) = list.get_mut(*index) {
+                    Self::redact(value, rest);
+                }
+            }
+            (JsonPathSegment::Wildcard, Value::Object(map)) => {
+                for value in map.values_mut() {
+                    Self::redact(value, rest);
+                }
+            }
+            (JsonPathSegment::Wildcard, Value::Array(list)) => {
+                for value in list.iter_mut() {
+                    Self::redact(value, rest);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+#[cfg(feature = "for_serde")]
+impl Scrubber for JsonPathScrubber {
+    fn scrub_body(&self, content_type: &str, body: Bytes) -> Bytes {
+        if !content_type.contains("json") {
+            return body;
+        }
+        let mut value: Value = match serde_json::from_slice(&body) {
+            Ok(v) => v,
+            Err(_) => return body,
+        };
+
+        for path in self.paths.iter() {
+            Self::redact(&mut value, path);
+        }
+        match serde_json::to_vec(&value) {
+            Ok(v) => Bytes::from(v),
+            Err(_) => body,
+        }
+    }
+}
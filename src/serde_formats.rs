@@ -0,0 +1,411 @@
+/*!
+In this module there're implementations of common wire formats for `serde`.
+
+Each format is provided both as a `#[serde(with = "...")]` module and as a wrapper type,
+so consumers could pick them per field without hand-writing `serde(with=...)` modules.
+
+* `rfc3339` - `SystemTime` <-> `"2021-07-01T08:30:00Z"` (without fractional seconds)
+* `rfc3339_fractional` - `SystemTime` <-> `"2021-07-01T08:30:00.123Z"` (with milliseconds)
+* `epoch_millis` - `SystemTime` <-> `1625128200123`
+* `string_decimal` - `T: Display + FromStr` <-> `"12.50"` (also accepts JSON numbers)
+*/
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+// Days since 1970-01-01 for the given proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+// The proleptic Gregorian date for the given days since 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = if days >= 0 { days } else { days - 146_096 } / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        _ => {
+            if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 {
+                29
+            } else {
+                28
+            }
+        }
+    }
+}
+
+// Split the given time into (seconds, nanoseconds) relative to UNIX_EPOCH,
+// nanoseconds are always non-negative.
+fn to_unix_parts(time: &SystemTime) -> (i64, u32) {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => (d.as_secs() as i64, d.subsec_nanos()),
+        Err(e) => {
+            let d = e.duration();
+            let secs = -(d.as_secs() as i64);
+            if d.subsec_nanos() == 0 {
+                (secs, 0)
+            } else {
+                (secs - 1, 1_000_000_000 - d.subsec_nanos())
+            }
+        }
+    }
+}
+
+fn from_unix_parts(secs: i64, nanos: u32) -> SystemTime {
+    if secs >= 0 {
+        UNIX_EPOCH + Duration::new(secs as u64, nanos)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs()) + Duration::from_nanos(nanos as u64)
+    }
+}
+
+/**
+Format the given `SystemTime` as a RFC3339 string in UTC.
+
+# Arguments
+
+* `time` - The given `SystemTime`.
+* `fractional` - Append milliseconds (`.SSS`) or not.
+
+*/
+pub fn format_rfc3339(time: &SystemTime, fractional: bool) -> String {
+    let (secs, nanos) = to_unix_parts(time);
+    let days = secs.div_euclid(SECONDS_PER_DAY);
+    let seconds_of_day = secs.rem_euclid(SECONDS_PER_DAY);
+    let (year, month, day) = civil_from_days(days);
+
+    let mut result = format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60,
+    );
+    if fractional {
+        result += &format!(".{:03}", nanos / 1_000_000);
+    }
+    result + "Z"
+}
+
+/**
+Parse the given RFC3339 string (with or without fractional seconds, `Z` or `+hh:mm` offsets)
+into a `SystemTime`.
+
+# Arguments
+
+* `input` - The given RFC3339 string.
+
+*/
+pub fn parse_rfc3339(input: &str) -> Result<SystemTime, WireFormatError> {
+    let err = || WireFormatError::new(format!("invalid RFC3339 datetime: {:?}", input));
+    let bytes = input.as_bytes();
+    if bytes.len() < 20 || !input.is_ascii() {
+        return Err(err());
+    }
+
+    let number = |from: usize, to: usize| -> Result<i64, WireFormatError> {
+        input[from..to]
+            .parse::<u32>()
+            .map(|v| v as i64)
+            .map_err(|_| err())
+    };
+    if bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || !matches!(bytes[10], b'T' | b't' | b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return Err(err());
+    }
+    let year = number(0, 4)?;
+    let month = number(5, 7)? as u32;
+    let day = number(8, 10)? as u32;
+    let hour = number(11, 13)?;
+    let minute = number(14, 16)?;
+    let second = number(17, 19)?;
+    if !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+        || hour > 23
+        || minute > 59
+        // Leap seconds are clamped to 59
+        || second > 60
+    {
+        return Err(err());
+    }
+
+    let mut index = 19;
+    let mut nanos: u32 = 0;
+    if bytes[index] == b'.' {
+        index += 1;
+        let start = index;
+        while index < bytes.len() && bytes[index].is_ascii_digit() {
+            index += 1;
+        }
+        let digits = &input[start..index];
+        if digits.is_empty() {
+            return Err(err());
+        }
+        for (i, c) in digits.bytes().take(9).enumerate() {
+            nanos += (c - b'0') as u32 * 10u32.pow(8 - i as u32);
+        }
+    }
+
+    let offset_seconds = match &input[index..] {
+        "Z" | "z" => 0,
+        offset if offset.len() == 6 && offset.as_bytes()[3] == b':' => {
+            let sign = match offset.as_bytes()[0] {
+                b'+' => 1,
+                b'-' => -1,
+                _ => return Err(err()),
+            };
+            let hours = number(index + 1, index + 3)?;
+            let minutes = number(index + 4, index + 6)?;
+            if hours > 23 || minutes > 59 {
+                return Err(err());
+            }
+            sign * (hours * 3600 + minutes * 60)
+        }
+        _ => return Err(err()),
+    };
+
+    let secs = days_from_civil(year, month, day) * SECONDS_PER_DAY
+        + hour * 3600
+        + minute * 60
+        + second.min(59)
+        - offset_seconds;
+    Ok(from_unix_parts(secs, nanos))
+}
+
+/**
+Convert the given `SystemTime` into milliseconds since UNIX_EPOCH (negative before it).
+*/
+pub fn to_epoch_millis(time: &SystemTime) -> i64 {
+    let (secs, nanos) = to_unix_parts(time);
+    secs * 1000 + (nanos / 1_000_000) as i64
+}
+
+/**
+Convert the given milliseconds since UNIX_EPOCH into a `SystemTime`.
+*/
+pub fn from_epoch_millis(millis: i64) -> SystemTime {
+    from_unix_parts(
+        millis.div_euclid(1000),
+        millis.rem_euclid(1000) as u32 * 1_000_000,
+    )
+}
+
+/*
+`WireFormatError` The error for malformed wire format values
+*/
+#[derive(Debug)]
+pub struct WireFormatError {
+    details: String,
+}
+impl std::error::Error for WireFormatError {}
+impl WireFormatError {
+    pub fn new(msg: impl Into<String>) -> WireFormatError {
+        WireFormatError {
+            details: msg.into(),
+        }
+    }
+}
+impl fmt::Display for WireFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.details)
+    }
+}
+
+/*
+`rfc3339` Use it by `#[serde(with = "http_api_service::serde_formats::rfc3339")]`
+for `SystemTime` fields, serialized without fractional seconds.
+*/
+pub mod rfc3339 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_rfc3339(time, false))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse_rfc3339(&s).map_err(de::Error::custom)
+    }
+}
+
+/*
+`rfc3339_fractional` Use it by `#[serde(with = "http_api_service::serde_formats::rfc3339_fractional")]`
+for `SystemTime` fields, serialized with milliseconds.
+*/
+pub mod rfc3339_fractional {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format_rfc3339(time, true))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        super::rfc3339::deserialize(deserializer)
+    }
+}
+
+/*
+`epoch_millis` Use it by `#[serde(with = "http_api_service::serde_formats::epoch_millis")]`
+for `SystemTime` fields, serialized as milliseconds since UNIX_EPOCH.
+*/
+pub mod epoch_millis {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(to_epoch_millis(time))
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        Ok(from_epoch_millis(i64::deserialize(deserializer)?))
+    }
+}
+
+/*
+`string_decimal` Use it by `#[serde(with = "http_api_service::serde_formats::string_decimal")]`
+for numeric fields (or any `Display + FromStr` decimal types) transferred as strings.
+JSON numbers are accepted when deserializing as well.
+*/
+pub mod string_decimal {
+    use super::*;
+
+    pub fn serialize<T: fmt::Display, S: Serializer>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(value)
+    }
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(StringDecimalVisitor(PhantomData))
+    }
+
+    struct StringDecimalVisitor<T>(PhantomData<T>);
+    impl<'de, T> Visitor<'de> for StringDecimalVisitor<T>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        type Value = T;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a decimal string or number")
+        }
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+            v.trim().parse::<T>().map_err(E::custom)
+        }
+        fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+            self.visit_str(&v.to_string())
+        }
+        fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+            self.visit_str(&v.to_string())
+        }
+        fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+            self.visit_str(&v.to_string())
+        }
+    }
+}
+
+/*
+`Rfc3339` The wrapper version of `rfc3339`
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rfc3339(pub SystemTime);
+impl Serialize for Rfc3339 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        rfc3339::serialize(&self.0, serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Rfc3339 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        rfc3339::deserialize(deserializer).map(Rfc3339)
+    }
+}
+
+/*
+`Rfc3339Fractional` The wrapper version of `rfc3339_fractional`
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Rfc3339Fractional(pub SystemTime);
+impl Serialize for Rfc3339Fractional {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        rfc3339_fractional::serialize(&self.0, serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Rfc3339Fractional {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        rfc3339_fractional::deserialize(deserializer).map(Rfc3339Fractional)
+    }
+}
+
+/*
+`EpochMillis` The wrapper version of `epoch_millis`
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EpochMillis(pub SystemTime);
+impl Serialize for EpochMillis {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        epoch_millis::serialize(&self.0, serializer)
+    }
+}
+impl<'de> Deserialize<'de> for EpochMillis {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        epoch_millis::deserialize(deserializer).map(EpochMillis)
+    }
+}
+
+/*
+`StringDecimal` The wrapper version of `string_decimal`
+*/
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct StringDecimal<T>(pub T);
+impl<T: fmt::Display> Serialize for StringDecimal<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        string_decimal::serialize(&self.0, serializer)
+    }
+}
+impl<'de, T> Deserialize<'de> for StringDecimal<T>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        string_decimal::deserialize(deserializer).map(StringDecimal)
+    }
+}
@@ -0,0 +1,279 @@
+/*!
+In this module there're the helpers of the `service!` macro,
+declaring the APIs of a service as a struct (instead of the loose `make_api_*()` variables).
+
+# Remarks
+
+* The struct holds the `APINoBody`/`APIHasBody` of the APIs as its fields (e.g. for `call_with_options()`),
+  and has the typed methods of the same names calling them.
+* The APIs are made of the `BaseService` (e.g. `HyperService`/`UreqService` of `any_service`) by `new()`.
+* The configured services (e.g. `CommonAPI` or the `service!` structs) could be registered by names
+  (`register_service()`), and retrieved by the application modules with their types (`service()`).
+  The services aren't `Send`, so the registry is per thread
+  (e.g. register them on each worker thread, or use the `current_thread` runtime).
+
+*/
+
+use std::any::{type_name, Any};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::rc::Rc;
+
+use super::simple_api::{APIHasBody, APINoBody, BaseService};
+
+/*
+`ServiceTypes` The type parameters of the `BaseService` (for the fields of the `service!` structs)
+*/
+pub trait ServiceTypes {
+    type Client;
+    type Req;
+    type Res;
+    type Method;
+    type Header;
+    type B;
+}
+impl<Client, Req, Res, Method, Header, B> ServiceTypes
+    for dyn BaseService<Client, Req, Res, Method, Header, B>
+{
+    type Client = Client;
+    type Req = Req;
+    type Res = Res;
+    type Method = Method;
+    type Header = Header;
+    type B = B;
+}
+
+// The `APINoBody` of the `BaseService` S
+pub type NoBodyOf<S, R> = APINoBody<
+    R,
+    <S as ServiceTypes>::Client,
+    <S as ServiceTypes>::Req,
+    <S as ServiceTypes>::Res,
+    <S as ServiceTypes>::Method,
+    <S as ServiceTypes>::Header,
+    <S as ServiceTypes>::B,
+>;
+// The `APIHasBody` of the `BaseService` S
+pub type HasBodyOf<S, T, R> = APIHasBody<
+    T,
+    R,
+    <S as ServiceTypes>::Client,
+    <S as ServiceTypes>::Req,
+    <S as ServiceTypes>::Res,
+    <S as ServiceTypes>::Method,
+    <S as ServiceTypes>::Header,
+    <S as ServiceTypes>::B,
+>;
+
+/*
+`ServiceRegistryError` The service isn't registered, or it's registered with another type
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServiceRegistryError {
+    details: String,
+}
+impl StdError for ServiceRegistryError {}
+impl ServiceRegistryError {
+    pub fn new(msg: impl Into<String>) -> ServiceRegistryError {
+        ServiceRegistryError {
+            details: msg.into(),
+        }
+    }
+}
+impl std::fmt::Display for ServiceRegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ServiceRegistryError: {}", self.details)
+    }
+}
+
+// The registered service, with its type name for the errors
+struct RegisteredService {
+    service: Rc<dyn Any>,
+    type_name: &'static str,
+}
+
+thread_local! {
+    static SERVICE_REGISTRY: RefCell<HashMap<String, RegisteredService>> = RefCell::new(HashMap::new());
+}
+
+/**
+Register the service by the name (of the current thread), replacing the one of the same name.
+
+# Arguments
+
+* `name` - The name of the service (e.g. `"billing"`)
+* `service` - The configured service (e.g. `CommonAPI`, `Arc<HyperService>` or a `service!` struct)
+
+# Remarks
+
+* Return `true` if a service of the same name is replaced.
+
+*/
+pub fn register_service<S: 'static>(name: impl Into<String>, service: S) -> bool {
+    SERVICE_REGISTRY.with(|registry| {
+        registry
+            .borrow_mut()
+            .insert(
+                name.into(),
+                RegisteredService {
+                    service: Rc::new(service),
+                    type_name: type_name::<S>(),
+                },
+            )
+            .is_some()
+    })
+}
+
+// Retrieve the service registered by the name, it should be of the registered type `S`
+pub fn service<S: 'static>(name: &str) -> Result<Rc<S>, ServiceRegistryError> {
+    SERVICE_REGISTRY.with(|registry| match registry.borrow().get(name) {
+        Some(registered) => registered.service.clone().downcast::<S>().map_err(|_| {
+            ServiceRegistryError::new(format!(
+                "the service `{}` is `{}`, not `{}`",
+                name,
+                registered.type_name,
+                type_name::<S>()
+            ))
+        }),
+        None => Err(ServiceRegistryError::new(format!(
+            "the service `{}` isn't registered",
+            name
+        ))),
+    })
+}
+
+// Unregister the service by the name, return `true` if it's registered
+pub fn unregister_service(name: &str) -> bool {
+    SERVICE_REGISTRY.with(|registry| registry.borrow_mut().remove(name).is_some())
+}
+
+/**
+Declare the APIs of a service as a struct of the `BaseService` (a `dyn BaseService` type).
+
+# Arguments
+
+Each API is declared as `name(method, relative_url) -> R = deserializer;`,
+or `name(method, relative_url, T => content_type, serializer) -> R = deserializer;` with the request body `T`.
+
+# Remarks
+
+* The methods are `name(path_param)` (or `name(path_param, body)`), calling the fields of the same names.
+* The serializers/deserializers are evaluated by `new()`, for each instance.
+* The struct could be registered by `register_service()`, and retrieved with its type by `registered(name)`.
+
+# Example
+
+```ignore
+use std::sync::Arc;
+
+use http::Method;
+use http_api_service::any_service::HyperService;
+use http_api_service::service;
+use http_api_service::simple_api::{DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER};
+
+service! {
+    // The APIs of the products
+    pub struct ProductService: HyperService {
+        get_product(Method::GET, "/products/{id}") -> Product = Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER);
+        create_product(
+            Method::POST,
+            "/products",
+            Product => "application/json", Arc::new(DEFAULT_SERDE_JSON_SERIALIZER)
+        ) -> Product = Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER);
+    }
+}
+
+let service = ProductService::new(common_api.as_base_service_shared());
+let product = service.get_product(Some(path_param!["id" => "3"])).await?;
+
+register_service("products", service);
+let product = ProductService::registered("products")?.get_product(Some(path_param!["id" => "3"])).await?;
+```
+
+*/
+#[macro_export]
+macro_rules! service {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident: $service:ty {
+            $(
+                $(#[$api_meta:meta])*
+                $api:ident(
+                    $method:expr, $url:expr $(, $body:ty => $content_type:expr, $serializer:expr)? $(,)?
+                ) -> $ret:ty = $deserializer:expr;
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$api_meta])*
+                pub $api: $crate::service!(@api_type $service, $ret $(, $body)?),
+            )*
+        }
+        impl $name {
+            pub fn new(base: std::sync::Arc<$service>) -> Self {
+                $name {
+                    $(
+                        $api: $crate::service!(
+                            @build base, $method, $url, $ret, $deserializer
+                            $(, $body, $content_type, $serializer)?
+                        ),
+                    )*
+                }
+            }
+
+            // Retrieve the instance registered by `register_service()` with the name
+            pub fn registered(
+                name: &str,
+            ) -> std::result::Result<std::rc::Rc<Self>, $crate::service::ServiceRegistryError> {
+                $crate::service::service::<Self>(name)
+            }
+
+            $(
+                $crate::service!(@method $(#[$api_meta])* $api, $ret $(, $body)?);
+            )*
+        }
+    };
+
+    (@api_type $service:ty, $ret:ty) => {
+        $crate::service::NoBodyOf<$service, $ret>
+    };
+    (@api_type $service:ty, $ret:ty, $body:ty) => {
+        $crate::service::HasBodyOf<$service, $body, $ret>
+    };
+
+    (@build $base:ident, $method:expr, $url:expr, $ret:ty, $deserializer:expr) => {
+        $crate::simple_api::ApiBuilder::new($method, $url)
+            .deserializer::<$ret>($deserializer)
+            .build_no_body($base.clone())
+    };
+    (@build $base:ident, $method:expr, $url:expr, $ret:ty, $deserializer:expr, $body:ty, $content_type:expr, $serializer:expr) => {
+        $crate::simple_api::ApiBuilder::new($method, $url)
+            .content_type($content_type)
+            .serializer::<$body>($serializer)
+            .deserializer::<$ret>($deserializer)
+            .build($base.clone())
+    };
+
+    (@method $(#[$api_meta:meta])* $api:ident, $ret:ty) => {
+        $(#[$api_meta])*
+        pub async fn $api(
+            &self,
+            path_param: Option<$crate::simple_api::PathParam>,
+        ) -> std::result::Result<Box<$ret>, Box<dyn std::error::Error>> {
+            self.$api.call(path_param).await
+        }
+    };
+    (@method $(#[$api_meta:meta])* $api:ident, $ret:ty, $body:ty) => {
+        $(#[$api_meta])*
+        pub async fn $api(
+            &self,
+            path_param: Option<$crate::simple_api::PathParam>,
+            body: $body,
+        ) -> std::result::Result<Box<$ret>, Box<dyn std::error::Error>> {
+            self.$api.call(path_param, body).await
+        }
+    };
+}
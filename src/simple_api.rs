@@ -2,38 +2,453 @@
 In this module there're implementations & tests of `SimpleAPI`.
 */
 
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::error::Error as StdError;
+use std::fmt::Display;
 use std::future::Future;
+use std::io;
+use std::ops::Range;
+#[cfg(all(feature = "multipart", feature = "for_serde"))]
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::result::Result as StdResult;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use futures::channel::oneshot;
+use futures::executor::ThreadPool;
+use futures::future::{self, FutureExt};
+use futures::stream::{self, Stream, StreamExt};
 use url::Url;
 
-pub use super::common::{PathParam, QueryParam};
+use super::audit::AuditLog;
+#[cfg(feature = "for_serde")]
+use super::codec::{CodecRegistry, CodecResult};
+use super::common::{
+    catch_hook_panic, catch_hook_panic_async, join_url, system_clock, AbsoluteUrl, AcceptMediaType,
+    AuthProvider, CacheEntries, Clock, Extensions, HasRequestOptions, MetricsSink, ResilienceEvent,
+    TransportErrorKind,
+};
+pub use super::common::{localized, Locale, LocaleHeaders, Localized, ResponseLanguage};
+pub use super::common::{
+    with_deprecation, DeprecationHook, DeprecationInfo, DeprecationMonitor, DeprecationNotice,
+    WithDeprecation,
+};
+pub use super::common::{with_extensions, DelegatedIdentity, DelegationHeaders};
+pub use super::common::{
+    with_response_size, ResponseSize, ResponseSizeInfo, ResponseStatus, WithResponseSize,
+};
+pub use super::common::{with_timing, RequestTiming, ResponseTiming, WithTiming};
+pub use super::common::{with_tls_info, ResponseTlsInfo, TlsInfo, WithTlsInfo};
+pub use super::common::{AggregateError, ItemError};
+pub use super::common::{BodyFactory, BodyFactoryFuture, HookPanicked};
+pub use super::common::{ClockSkew, SkewCorrectedClock, DEFAULT_CLOCK_SKEW_THRESHOLD};
+pub use super::common::{
+    DrainGate, LastError, OfflineSwitch, ServiceStatus, ShuttingDownError, StatusTracker,
+};
+pub use super::common::{
+    Headers, PathParam, QueryParam, RequestHeaders, RequestSummary, TemplateVariables,
+    UrlJoinStrategy, UrlNormalization, Versioning,
+};
+pub use super::common::{
+    KeepAliveError, KeepAliveMethod, KeepAliveOptions, KeepAliveProbe, KeepAliveProbeFuture,
+    KeepAliveStats, KeepAliveWorker, DEFAULT_KEEP_ALIVE_INTERVAL, DEFAULT_KEEP_ALIVE_TIMEOUT,
+};
+pub use super::common::{
+    MaintenanceAction, MaintenancePolicy, MaintenanceWindow, MaintenanceWindowError,
+};
+pub use super::common::{
+    QuotaAlarm, QuotaAlarmHook, QuotaExceededError, QuotaResource, QuotaUsage, UsageQuota,
+};
+pub use super::common::{ReadOnlyGuard, ReadOnlyMode};
+pub use super::common::{ReplayNonce, ReplayNonceHeaders};
+#[cfg(feature = "for_serde")]
+use super::json_patch::{
+    PatchOperation, DEFAULT_JSON_PATCH_SERIALIZER, DEFAULT_MERGE_PATCH_SERIALIZER,
+    MEDIA_TYPE_JSON_PATCH, MEDIA_TYPE_MERGE_PATCH,
+};
+#[cfg(feature = "queue")]
+use super::queue::QueueStore;
+pub use super::scrub::{RequestFormatter, RequestLogHook};
+#[cfg(feature = "multipart")]
 use super::simple_http::{
-    data_and_boundary_from_multipart, get_content_type_from_multipart_boundary, BaseClient,
-    Interceptor, InterceptorFunc, SimpleHTTP,
+    data_and_boundary_from_multipart, get_content_type_from_multipart_boundary,
+    parse_multipart_boundary,
+};
+use super::simple_http::{
+    BaseClient, Interceptor, InterceptorFunc, InterceptorOrderError, InterceptorSlot, SimpleHTTP,
 };
 
+#[cfg(all(feature = "multipart", feature = "for_serde"))]
+use formdata::FilePart;
 #[cfg(feature = "multipart")]
 use formdata::FormData;
 
 #[cfg(feature = "for_serde")]
 use serde::{de::DeserializeOwned, Serialize};
 
+#[cfg(feature = "sniff")]
+use super::common::sniff_content_type;
+#[cfg(all(feature = "sniff", feature = "multipart"))]
+use super::common::SNIFF_LENGTH;
+
+pub const CONTENT_TYPE_OCTET_STREAM: &str = "application/octet-stream";
+
+// The future of the asynchronous encoding of `BodySerializer`
+pub type EncodeFuture<'a, B> = Pin<Box<dyn Future<Output = StdResult<B, Box<dyn StdError>>> + 'a>>;
+
 /*
 `BodySerializer  Serialize the body (for put/post/patch etc)
 */
 pub trait BodySerializer<T, B> {
     fn encode(&self, origin: T) -> StdResult<B, Box<dyn StdError>>;
+
+    // Encode it asynchronously (e.g. on a thread pool by `OffloadSerializer`), it's `encode()` by default
+    fn encode_async<'a>(&'a self, origin: T) -> EncodeFuture<'a, B>
+    where
+        B: 'a,
+    {
+        Box::pin(future::ready(self.encode(origin)))
+    }
+
+    // Encode it on another thread (by `OffloadSerializer`), the errors of `encode()` are kept as their messages by default
+    fn encode_send(&self, origin: T) -> StdResult<B, Box<dyn StdError + Send + Sync>> {
+        self.encode(origin).map_err(to_sendable_error)
+    }
+}
+
+/*
+`SerializeError` The body is failed to be serialized
+*/
+#[derive(Debug)]
+pub struct SerializeError {
+    details: String,
+    source: Option<Box<dyn StdError>>,
+}
+impl StdError for SerializeError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.source.as_deref()
+    }
+}
+impl SerializeError {
+    pub fn new(msg: impl Into<String>) -> SerializeError {
+        SerializeError {
+            details: msg.into(),
+            source: None,
+        }
+    }
+    pub fn new_with_source(
+        msg: impl Into<String>,
+        source: impl Into<Box<dyn StdError>>,
+    ) -> SerializeError {
+        SerializeError {
+            details: msg.into(),
+            source: Some(source.into()),
+        }
+    }
+}
+impl std::fmt::Display for SerializeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "SerializeError: {}", self.details)
+    }
+}
+impl From<Box<dyn StdError>> for SerializeError {
+    fn from(e: Box<dyn StdError>) -> Self {
+        match e.downcast::<SerializeError>() {
+            Ok(e) => *e,
+            Err(e) => SerializeError::new_with_source(e.to_string(), e),
+        }
+    }
+}
+
+/*
+`FieldViolation` A field failed the validation, `field` is the name of the field (or the query parameter)
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldViolation {
+    pub field: String,
+    pub message: String,
 }
+
 /*
+`ValidationError` The request is rejected locally by the `RequestValidator` before it's sent
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    details: String,
+    pub violations: Vec<FieldViolation>,
+}
+impl StdError for ValidationError {}
+impl ValidationError {
+    pub fn new(msg: impl Into<String>, violations: Vec<FieldViolation>) -> ValidationError {
+        ValidationError {
+            details: msg.into(),
+            violations,
+        }
+    }
+}
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ValidationError: {}", self.details)
+    }
+}
+
+/**
+`Violations` Collect the `FieldViolation` of the checks, for the implementations of `RequestValidator`.
+
+# Remarks
+
+* `into_result()` is `Ok` without violations, or `ValidationError` of all of them
+  (so all the invalid fields are reported at once).
+
+*/
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Violations(pub Vec<FieldViolation>);
+impl Violations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) -> &mut Self {
+        self.0.push(FieldViolation {
+            field: field.into(),
+            message: message.into(),
+        });
+        self
+    }
+    // Add the violation if the condition isn't met
+    pub fn check(
+        &mut self,
+        valid: bool,
+        field: impl Into<String>,
+        message: impl Into<String>,
+    ) -> &mut Self {
+        if !valid {
+            self.add(field, message);
+        }
+        self
+    }
+    // Check the length (of chars) is within `min..=max`
+    pub fn check_length(&mut self, field: &str, value: &str, min: usize, max: usize) -> &mut Self {
+        let len = value.chars().count();
+        self.check(
+            min <= len && len <= max,
+            field,
+            format!("length {} isn't within {}..={}", len, min, max),
+        )
+    }
+    // Check the value is one of the `allowed` ones (e.g. the enums of the server)
+    pub fn check_one_of(&mut self, field: &str, value: &str, allowed: &[&str]) -> &mut Self {
+        self.check(
+            allowed.contains(&value),
+            field,
+            format!("{:?} isn't one of {:?}", value, allowed),
+        )
+    }
+    // Check the query parameter is present
+    pub fn check_required(&mut self, query_param: &QueryParam, field: &str) -> &mut Self {
+        self.check(query_param.contains_key(field), field, "is required")
+    }
+
+    pub fn into_result(self) -> StdResult<(), ValidationError> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+        Err(ValidationError::new(
+            self.0
+                .iter()
+                .map(|v| format!("{}: {}", v.field, v.message))
+                .collect::<Vec<_>>()
+                .join(", "),
+            self.0,
+        ))
+    }
+}
+
+/**
+`RequestValidator` Validate the request bodies (or the query parameters) before the serialization,
+so the invalid requests fail locally with `ValidationError` instead of the `400` of the server.
+
+# Remarks
+
+* They're set per API by `APIHasBody::with_validator()` (the bodies)
+  or `with_query_validator()` (the query parameters, the empty ones if there's none).
+
+*/
+pub trait RequestValidator<T> {
+    fn validate(&self, value: &T) -> StdResult<(), ValidationError>;
+}
+
+type ValidateFunc<T> = Box<dyn Fn(&T) -> StdResult<(), ValidationError> + Send + Sync>;
+
+// RequestValidatorFunc The `RequestValidator` by the given `Fn`
+pub struct RequestValidatorFunc<T> {
+    func: ValidateFunc<T>,
+}
+impl<T> RequestValidatorFunc<T> {
+    pub fn new(
+        func: impl Fn(&T) -> StdResult<(), ValidationError> + Send + Sync + 'static,
+    ) -> Self {
+        RequestValidatorFunc {
+            func: Box::new(func),
+        }
+    }
+}
+impl<T> RequestValidator<T> for RequestValidatorFunc<T> {
+    fn validate(&self, value: &T) -> StdResult<(), ValidationError> {
+        (self.func)(value)
+    }
+}
+
+// Validate the query parameters (the empty ones if there's none)
+fn validate_query_param(
+    query_validator: &Option<Arc<dyn RequestValidator<QueryParam>>>,
+    query_param: &Option<QueryParam>,
+) -> StdResult<(), Box<dyn StdError>> {
+    if let Some(query_validator) = query_validator {
+        catch_hook_panic("query_validator", || match query_param {
+            Some(query_param) => query_validator.validate(query_param),
+            None => query_validator.validate(&QueryParam::new()),
+        })??;
+    }
+    Ok(())
+}
+
+/**
+`AsyncBodySerializer` Serialize the body asynchronously (for put/post/patch etc),
+for the serializers doing I/O (e.g. reading the files of the multipart bodies).
+
+# Remarks
+
+* All the `BodySerializer` are `AsyncBodySerializer` (by `encode_async()`),
+  and their errors are wrapped as the sources of `SerializeError`.
+
+*/
+pub trait AsyncBodySerializer<T, B> {
+    fn serialize<'a>(
+        &'a self,
+        origin: T,
+    ) -> Pin<Box<dyn Future<Output = StdResult<B, SerializeError>> + 'a>>
+    where
+        T: 'a,
+        B: 'a;
+}
+impl<T, B, S> AsyncBodySerializer<T, B> for S
+where
+    S: BodySerializer<T, B> + ?Sized,
+{
+    fn serialize<'a>(
+        &'a self,
+        origin: T,
+    ) -> Pin<Box<dyn Future<Output = StdResult<B, SerializeError>> + 'a>>
+    where
+        T: 'a,
+        B: 'a,
+    {
+        Box::pin(
+            self.encode_async(origin)
+                .map(|v| v.map_err(SerializeError::from)),
+        )
+    }
+}
+
+// The chunks of the response body (for `BodyDeserializer::decode_stream()`)
+pub type BodyStream<'a> = Pin<Box<dyn Stream<Item = StdResult<Bytes, Box<dyn StdError>>> + 'a>>;
+// The future of the asynchronous decoding of `BodyDeserializer`
+pub type DecodeFuture<'a, R> =
+    Pin<Box<dyn Future<Output = StdResult<Box<R>, Box<dyn StdError>>> + 'a>>;
+
+/**
 `BodyDeserializer` Deserialize the body (for response)
+
+# Remarks
+
+* Only `decode()` is required, the others are based on it by default.
+* Override `decode_owned()` to take the ownership of the body without copying it,
+  and `decode_stream()` to parse the chunks incrementally.
+
 */
 pub trait BodyDeserializer<R> {
     fn decode(&self, bytes: &Bytes) -> StdResult<Box<R>, Box<dyn StdError>>;
+
+    // Decode the owned body (e.g. zero-copy by `DummyBypassDeserializer`), it's `decode()` by default
+    fn decode_owned(&self, bytes: Bytes) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.decode(&bytes)
+    }
+
+    // Decode it asynchronously (e.g. on a thread pool by `OffloadDeserializer`), it's `decode_owned()` by default
+    fn decode_async<'a>(&'a self, bytes: Bytes) -> DecodeFuture<'a, R>
+    where
+        R: 'a,
+    {
+        Box::pin(future::ready(self.decode_owned(bytes)))
+    }
+
+    // Decode it on another thread (by `OffloadDeserializer`), the errors of `decode_owned()` are kept as their messages by default
+    fn decode_send(&self, bytes: Bytes) -> StdResult<Box<R>, Box<dyn StdError + Send + Sync>> {
+        self.decode_owned(bytes).map_err(to_sendable_error)
+    }
+
+    /**
+    Decode the body from its chunks (as they're received).
+
+    # Remarks
+
+    * By default the chunks are collected (a single chunk is kept without copying),
+      and then decoded by `decode_async()`.
+
+    */
+    fn decode_stream<'a>(&'a self, stream: BodyStream<'a>) -> DecodeFuture<'a, R>
+    where
+        R: 'a,
+    {
+        Box::pin(async move {
+            let bytes = collect_body_stream(stream).await?;
+            self.decode_async(bytes).await
+        })
+    }
+
+    // Decode the body by the `Content-Type` of the response (e.g. by `CodecDeserializer`), it's `decode_stream()` by default
+    fn decode_response<'a>(
+        &'a self,
+        _content_type: Option<String>,
+        stream: BodyStream<'a>,
+    ) -> DecodeFuture<'a, R>
+    where
+        R: 'a,
+    {
+        self.decode_stream(stream)
+    }
+}
+
+// The errors can't be sent across the threads as they are, so they're kept as their messages
+fn to_sendable_error(e: Box<dyn StdError>) -> Box<dyn StdError + Send + Sync> {
+    Box::new(io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+// Collect the chunks of the `stream` into one `Bytes` (a single chunk is returned as it is)
+pub async fn collect_body_stream(
+    mut stream: BodyStream<'_>,
+) -> StdResult<Bytes, Box<dyn StdError>> {
+    let first = match stream.next().await {
+        Some(chunk) => chunk?,
+        None => return Ok(Bytes::new()),
+    };
+    let second = match stream.next().await {
+        Some(chunk) => chunk?,
+        None => return Ok(first),
+    };
+
+    let mut buffer = BytesMut::with_capacity(first.len() + second.len());
+    buffer.extend_from_slice(&first);
+    buffer.extend_from_slice(&second);
+    while let Some(chunk) = stream.next().await {
+        buffer.extend_from_slice(&chunk?);
+    }
+    Ok(buffer.freeze())
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -81,6 +496,9 @@ impl BodyDeserializer<Bytes> for DummyBypassDeserializer {
     fn decode(&self, bytes: &Bytes) -> StdResult<Box<Bytes>, Box<dyn StdError>> {
         Ok(Box::new(bytes.clone()))
     }
+    fn decode_owned(&self, bytes: Bytes) -> StdResult<Box<Bytes>, Box<dyn StdError>> {
+        Ok(Box::new(bytes))
+    }
 }
 pub const DEFAULT_DUMMY_BYPASS_DESERIALIZER: DummyBypassDeserializer = DummyBypassDeserializer {};
 
@@ -119,6 +537,186 @@ where
 #[cfg(feature = "multipart")]
 pub const DEFAULT_MULTIPART_SERIALIZER: MultipartSerializer = MultipartSerializer {};
 
+#[cfg(feature = "multipart")]
+pub const MEDIA_TYPE_FORM_URLENCODED: &str = "application/x-www-form-urlencoded";
+
+#[cfg(feature = "multipart")]
+#[derive(Debug, Clone, Copy)]
+// FormSerializer Serialize the form like the browsers (`application/x-www-form-urlencoded` without files, multipart otherwise)
+pub struct FormSerializer {}
+#[cfg(feature = "multipart")]
+impl<B> BodySerializer<FormData, (String, B)> for FormSerializer
+where
+    B: From<Bytes>,
+{
+    fn encode(&self, origin: FormData) -> StdResult<(String, B), Box<dyn StdError>> {
+        if !origin.files.is_empty() {
+            return DEFAULT_MULTIPART_SERIALIZER.encode(origin);
+        }
+
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        for (name, value) in &origin.fields {
+            form.append_pair(name, value);
+        }
+        Ok((
+            MEDIA_TYPE_FORM_URLENCODED.to_string(),
+            B::from(Bytes::from(form.finish())),
+        ))
+    }
+}
+#[cfg(feature = "multipart")]
+pub const DEFAULT_FORM_SERIALIZER: FormSerializer = FormSerializer {};
+
+#[cfg(feature = "sniff")]
+#[derive(Debug, Clone, Copy)]
+// SniffingBytesSerializer Send the raw `Bytes` as the content type detected by `sniff_content_type()` (or `application/octet-stream`)
+pub struct SniffingBytesSerializer {}
+#[cfg(feature = "sniff")]
+impl<B> BodySerializer<Bytes, (String, B)> for SniffingBytesSerializer
+where
+    B: From<Bytes>,
+{
+    fn encode(&self, origin: Bytes) -> StdResult<(String, B), Box<dyn StdError>> {
+        let content_type = sniff_content_type(&origin).unwrap_or(CONTENT_TYPE_OCTET_STREAM);
+
+        Ok((content_type.to_string(), B::from(origin)))
+    }
+}
+#[cfg(feature = "sniff")]
+pub const DEFAULT_SNIFFING_BYTES_SERIALIZER: SniffingBytesSerializer = SniffingBytesSerializer {};
+
+// The serialized field name of the paths of `FormFile` (for telling them from the other nested structs)
+#[cfg(all(feature = "multipart", feature = "for_serde"))]
+const FORM_FILE_PATH_FIELD: &str = "__form_file_path__";
+
+/*
+`FormFile` The file part of the forms (e.g. as a field of the structs for `FormData::from_serialize()`)
+*/
+#[cfg(all(feature = "multipart", feature = "for_serde"))]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, serde::Deserialize)]
+pub struct FormFile {
+    #[serde(rename = "__form_file_path__")]
+    pub path: PathBuf,
+    // The `Content-Type` of the part (detected by the magic bytes of the file if it's `None`, **feature: sniff**)
+    pub content_type: Option<String>,
+}
+#[cfg(all(feature = "multipart", feature = "for_serde"))]
+impl FormFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        FormFile {
+            path: path.into(),
+            content_type: None,
+        }
+    }
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    // Detect the content type by the magic bytes of the file (`None` if it isn't readable)
+    #[cfg(feature = "sniff")]
+    fn sniff_content_type(&self) -> Option<String> {
+        let file = std::fs::File::open(&self.path).ok()?;
+        let mut prefix = Vec::with_capacity(SNIFF_LENGTH);
+        io::Read::read_to_end(&mut io::Read::take(file, SNIFF_LENGTH as u64), &mut prefix).ok()?;
+        sniff_content_type(&prefix).map(|v| v.to_string())
+    }
+    #[cfg(not(feature = "sniff"))]
+    fn sniff_content_type(&self) -> Option<String> {
+        None
+    }
+
+    fn to_file_part(&self) -> FilePart {
+        let mut headers = formdata_hyper::header::Headers::new();
+        if let Some(content_type) = self
+            .content_type
+            .clone()
+            .or_else(|| self.sniff_content_type())
+        {
+            headers.set_raw("Content-Type", vec![content_type.into_bytes()]);
+        }
+        FilePart::new(headers, &self.path)
+    }
+}
+
+/**
+`FormDataExt` Build the `FormData` of the serde structs (instead of the manual field vectors).
+
+# Remarks
+
+* The fields are the text parts of their serialized names (respecting `#[serde(rename)]`/`#[serde(skip)]`),
+  in the order of the names:
+  * The strings are as they are, the numbers/bools are their JSON texts, and `None` is skipped.
+  * The arrays are the repeated parts of the same name, and the nested structs are their JSON texts.
+  * The `FormFile` fields (or the arrays of them) are the file parts.
+
+# Example
+
+```ignore
+#[derive(Serialize)]
+struct Upload {
+    #[serde(rename = "title")]
+    name: String,
+    tags: Vec<String>,
+    photo: FormFile,
+}
+
+let form_data = FormData::from_serialize(&Upload {
+    name: "Baxter".to_string(),
+    tags: vec!["dog".to_string()],
+    photo: FormFile::new("/tmp/baxter.png").content_type("image/png"),
+})?;
+```
+
+*/
+#[cfg(all(feature = "multipart", feature = "for_serde"))]
+pub trait FormDataExt: Sized {
+    fn from_serialize<T: Serialize + ?Sized>(value: &T) -> StdResult<Self, SerializeError>;
+    fn with_field(self, name: impl Into<String>, value: impl Into<String>) -> Self;
+    fn with_file(self, name: impl Into<String>, file: FormFile) -> Self;
+}
+#[cfg(all(feature = "multipart", feature = "for_serde"))]
+impl FormDataExt for FormData {
+    fn from_serialize<T: Serialize + ?Sized>(value: &T) -> StdResult<Self, SerializeError> {
+        let fields = match serde_json::to_value(value) {
+            Ok(serde_json::Value::Object(fields)) => fields,
+            Ok(_) => return Err(SerializeError::new("form data: not a struct or a map")),
+            Err(e) => return Err(SerializeError::new_with_source("form data", e)),
+        };
+
+        let mut form_data = FormData::new();
+        for (name, value) in fields {
+            let values = match value {
+                serde_json::Value::Array(values) => values,
+                value => vec![value],
+            };
+            for value in values {
+                form_data = match value {
+                    serde_json::Value::Null => form_data,
+                    serde_json::Value::String(value) => form_data.with_field(name.clone(), value),
+                    serde_json::Value::Object(ref file)
+                        if file.contains_key(FORM_FILE_PATH_FIELD) =>
+                    {
+                        let file = serde_json::from_value::<FormFile>(value)
+                            .map_err(|e| SerializeError::new_with_source("form data: file", e))?;
+                        form_data.with_file(name.clone(), file)
+                    }
+                    value => form_data.with_field(name.clone(), value.to_string()),
+                };
+            }
+        }
+        Ok(form_data)
+    }
+    fn with_field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.fields.push((name.into(), value.into()));
+        self
+    }
+    fn with_file(mut self, name: impl Into<String>, file: FormFile) -> Self {
+        self.files.push((name.into(), file.to_file_part()));
+        self
+    }
+}
+
 #[cfg(feature = "for_serde")]
 #[derive(Debug, Clone, Copy)]
 // SerdeJsonSerializer Serialize the for_serde body (for put/post/patch etc)
@@ -133,6 +731,11 @@ impl<T: Serialize> BodySerializer<T, Bytes> for SerdeJsonSerializerForBytes {
     fn encode(&self, origin: T) -> StdResult<Bytes, Box<dyn StdError>> {
         let serialized = serde_json::to_vec(&origin)?;
 
+        Ok(Bytes::from(serialized))
+    }
+    fn encode_send(&self, origin: T) -> StdResult<Bytes, Box<dyn StdError + Send + Sync>> {
+        let serialized = serde_json::to_vec(&origin)?;
+
         Ok(Bytes::from(serialized))
     }
 }
@@ -148,6 +751,11 @@ where
     fn encode(&self, origin: T) -> StdResult<B, Box<dyn StdError>> {
         let serialized = DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES.encode(origin)?;
 
+        Ok(B::from(serialized))
+    }
+    fn encode_send(&self, origin: T) -> StdResult<B, Box<dyn StdError + Send + Sync>> {
+        let serialized = DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES.encode_send(origin)?;
+
         Ok(B::from(serialized))
     }
 }
@@ -161,7 +769,12 @@ pub struct SerdeJsonDeserializer {}
 #[cfg(feature = "for_serde")]
 impl<R: DeserializeOwned + 'static> BodyDeserializer<R> for SerdeJsonDeserializer {
     fn decode(&self, bytes: &Bytes) -> StdResult<Box<R>, Box<dyn StdError>> {
-        let target: R = serde_json::from_slice(bytes.to_vec().as_slice())?;
+        let target: R = serde_json::from_slice(bytes)?;
+
+        Ok(Box::new(target))
+    }
+    fn decode_send(&self, bytes: Bytes) -> StdResult<Box<R>, Box<dyn StdError + Send + Sync>> {
+        let target: R = serde_json::from_slice(&bytes)?;
 
         Ok(Box::new(target))
     }
@@ -169,53 +782,758 @@ impl<R: DeserializeOwned + 'static> BodyDeserializer<R> for SerdeJsonDeserialize
 #[cfg(feature = "for_serde")]
 pub const DEFAULT_SERDE_JSON_DESERIALIZER: SerdeJsonDeserializer = SerdeJsonDeserializer {};
 
-pub trait BaseAPI<Client, Req, Res, Method, Header, B> {
-    fn set_base_url(&mut self, url: Url);
-    fn get_base_url(&self) -> Url;
-    fn set_default_header(&mut self, header: Option<Header>);
-    fn get_default_header(&self) -> Option<Header>;
+#[cfg(feature = "simd_json")]
+#[derive(Debug, Clone, Copy)]
+// SimdJsonSerializer Serialize the for_serde body by simd-json (for put/post/patch etc)
+pub struct SimdJsonSerializer {}
+#[cfg(feature = "simd_json")]
+impl<T: Serialize, B> BodySerializer<T, B> for SimdJsonSerializer
+where
+    B: From<Bytes>,
+{
+    fn encode(&self, origin: T) -> StdResult<B, Box<dyn StdError>> {
+        let serialized = simd_json::serde::to_vec(&origin)?;
 
-    fn get_simple_http(&mut self) -> &mut SimpleHTTP<Client, Req, Res, Method, Header, B>;
+        Ok(B::from(Bytes::from(serialized)))
+    }
+    fn encode_send(&self, origin: T) -> StdResult<B, Box<dyn StdError + Send + Sync>> {
+        let serialized = simd_json::serde::to_vec(&origin)?;
+
+        Ok(B::from(Bytes::from(serialized)))
+    }
 }
+#[cfg(feature = "simd_json")]
+pub const DEFAULT_SIMD_JSON_SERIALIZER: SimdJsonSerializer = SimdJsonSerializer {};
 
-pub trait BaseService<Client, Req, Res, Method, Header, B> {
-    fn get_simple_api(&self) -> &Arc<Mutex<dyn BaseAPI<Client, Req, Res, Method, Header, B>>>;
-    fn _call_common(
-        &self,
-        method: Method,
-        header: Option<Header>,
-        relative_url: String,
-        content_type: String,
-        path_param: Option<PathParam>,
-        query_param: Option<QueryParam>,
-        body: B,
-    ) -> Pin<Box<dyn Future<Output = StdResult<Box<B>, Box<dyn StdError>>>>>;
+#[cfg(feature = "simd_json")]
+#[derive(Debug, Clone, Copy)]
+// SimdJsonDeserializer Deserialize the body by simd-json (for response, it's parsed on a copy of the body)
+pub struct SimdJsonDeserializer {}
+#[cfg(feature = "simd_json")]
+impl<R: DeserializeOwned + 'static> BodyDeserializer<R> for SimdJsonDeserializer {
+    fn decode(&self, bytes: &Bytes) -> StdResult<Box<R>, Box<dyn StdError>> {
+        let mut buffer = bytes.to_vec();
+        let target: R = simd_json::serde::from_slice(buffer.as_mut_slice())?;
 
-    fn body_to_bytes(
-        &self,
-        body: B,
-    ) -> Pin<Box<dyn Future<Output = StdResult<Bytes, Box<dyn StdError + Send + Sync>>>>>;
-}
+        Ok(Box::new(target))
+    }
+    fn decode_send(&self, bytes: Bytes) -> StdResult<Box<R>, Box<dyn StdError + Send + Sync>> {
+        let mut buffer = bytes.to_vec();
+        let target: R = simd_json::serde::from_slice(buffer.as_mut_slice())?;
 
-impl<Client, Req, Res, Method, Header, B> dyn BaseService<Client, Req, Res, Method, Header, B> {
-    pub fn set_base_url(&self, url: Url) {
-        self.get_simple_api().lock().unwrap().set_base_url(url);
+        Ok(Box::new(target))
     }
-    pub fn get_base_url(&self) -> Url {
-        self.get_simple_api().lock().unwrap().get_base_url()
+}
+#[cfg(feature = "simd_json")]
+pub const DEFAULT_SIMD_JSON_DESERIALIZER: SimdJsonDeserializer = SimdJsonDeserializer {};
+
+/*
+`MapIntoError` The error when converting the deserialized body into the domain type failed
+*/
+#[derive(Debug)]
+pub struct MapIntoError {
+    source: Box<dyn StdError>,
+}
+impl StdError for MapIntoError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(self.source.as_ref())
     }
-    pub fn set_default_header(&self, header: Option<Header>) {
-        self.get_simple_api()
-            .lock()
-            .unwrap()
-            .set_default_header(header);
+}
+impl MapIntoError {
+    pub fn new(source: impl Into<Box<dyn StdError>>) -> MapIntoError {
+        MapIntoError {
+            source: source.into(),
+        }
     }
-    pub fn get_default_header(&self) -> Option<Header> {
-        self.get_simple_api().lock().unwrap().get_default_header()
+}
+impl std::fmt::Display for MapIntoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "MapIntoError: {}", self.source)
     }
-    pub fn set_client(
-        &self,
-        client: Arc<Mutex<dyn BaseClient<Client, Req, Res, Method, Header, B>>>,
+}
+
+/*
+`UnexpectedContentTypeError` The `Content-Type` of the response isn't the expected one (of the strict APIs)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnexpectedContentTypeError {
+    details: String,
+    pub expected: String,
+    pub actual: Option<String>,
+}
+impl StdError for UnexpectedContentTypeError {}
+impl UnexpectedContentTypeError {
+    pub fn new(
+        msg: impl Into<String>,
+        expected: impl Into<String>,
+        actual: Option<String>,
+    ) -> UnexpectedContentTypeError {
+        UnexpectedContentTypeError {
+            details: msg.into(),
+            expected: expected.into(),
+            actual,
+        }
+    }
+}
+impl std::fmt::Display for UnexpectedContentTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "UnexpectedContentTypeError: {}", self.details)
+    }
+}
+
+// The lowercase media type and charset of the content type
+fn media_type_and_charset(content_type: &str) -> (String, Option<String>) {
+    let mut parts = content_type.split(';');
+    let media_type = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let charset = parts.find_map(|v| {
+        let (k, v) = v.split_once('=')?;
+        if k.trim().eq_ignore_ascii_case("charset") {
+            Some(v.trim().trim_matches('"').to_ascii_lowercase())
+        } else {
+            None
+        }
+    });
+    (media_type, charset)
+}
+
+/**
+Check the `Content-Type` of the response against the expected one, before the deserialization
+(e.g. so the HTML error pages of the proxies aren't fed to the deserializers).
+
+# Remarks
+
+* The media types are compared case-insensitively without the parameters,
+  and `type/*` (or `*/*`) of the `expected` matches any subtype.
+* The charsets are compared only when both of them have it (e.g. `application/json; charset=utf-8`).
+* The responses without `Content-Type` are unexpected.
+
+*/
+pub fn check_content_type(
+    expected: &str,
+    actual: Option<&str>,
+) -> StdResult<(), UnexpectedContentTypeError> {
+    let unexpected = |msg: String| {
+        Err(UnexpectedContentTypeError::new(
+            msg,
+            expected,
+            actual.map(|v| v.to_string()),
+        ))
+    };
+    let actual_content_type = match actual {
+        Some(v) => v,
+        None => {
+            return unexpected(format!(
+                "expected {}, but there's no Content-Type",
+                expected
+            ))
+        }
+    };
+
+    let (expected_media_type, expected_charset) = media_type_and_charset(expected);
+    let (actual_media_type, actual_charset) = media_type_and_charset(actual_content_type);
+    let media_type_matched = match expected_media_type.strip_suffix("/*") {
+        Some("*") => true,
+        Some(top_level) => actual_media_type
+            .split_once('/')
+            .is_some_and(|(v, _)| v == top_level),
+        None => expected_media_type == actual_media_type,
+    };
+    let charset_matched = match (expected_charset, actual_charset) {
+        (Some(expected_charset), Some(actual_charset)) => expected_charset == actual_charset,
+        _ => true,
+    };
+    if media_type_matched && charset_matched {
+        Ok(())
+    } else {
+        unexpected(format!(
+            "expected {}, got {}",
+            expected, actual_content_type
+        ))
+    }
+}
+
+// TryFromDeserializer Deserialize the body by the inner deserializer, then convert it by `TryFrom` (for response)
+pub struct TryFromDeserializer<R> {
+    pub inner: Arc<dyn BodyDeserializer<R>>,
+}
+impl<R, D> BodyDeserializer<D> for TryFromDeserializer<R>
+where
+    D: TryFrom<R>,
+    D::Error: Into<Box<dyn StdError>>,
+{
+    fn decode(&self, bytes: &Bytes) -> StdResult<Box<D>, Box<dyn StdError>> {
+        let origin = self.inner.decode(bytes)?;
+        match D::try_from(*origin) {
+            Ok(v) => Ok(Box::new(v)),
+            Err(e) => Err(Box::new(MapIntoError::new(e))),
+        }
+    }
+    fn decode_owned(&self, bytes: Bytes) -> StdResult<Box<D>, Box<dyn StdError>> {
+        let origin = self.inner.decode_owned(bytes)?;
+        match D::try_from(*origin) {
+            Ok(v) => Ok(Box::new(v)),
+            Err(e) => Err(Box::new(MapIntoError::new(e))),
+        }
+    }
+    // Keep the inner one's async decoding (e.g. offloaded)
+    fn decode_async<'a>(
+        &'a self,
+        bytes: Bytes,
+    ) -> Pin<Box<dyn Future<Output = StdResult<Box<D>, Box<dyn StdError>>> + 'a>>
+    where
+        D: 'a,
+    {
+        Box::pin(async move {
+            let origin = self.inner.decode_async(bytes).await?;
+            match D::try_from(*origin) {
+                Ok(v) => Ok(Box::new(v) as Box<D>),
+                Err(e) => Err(Box::new(MapIntoError::new(e)) as Box<dyn StdError>),
+            }
+        })
+    }
+    // Keep the inner one's choice by the `Content-Type` (e.g. `CodecDeserializer`)
+    fn decode_response<'a>(
+        &'a self,
+        content_type: Option<String>,
+        stream: BodyStream<'a>,
+    ) -> DecodeFuture<'a, D>
+    where
+        D: 'a,
+    {
+        Box::pin(async move {
+            let origin = self.inner.decode_response(content_type, stream).await?;
+            match D::try_from(*origin) {
+                Ok(v) => Ok(Box::new(v) as Box<D>),
+                Err(e) => Err(Box::new(MapIntoError::new(e)) as Box<dyn StdError>),
+            }
+        })
+    }
+}
+
+// Run the `task` on the `thread_pool` (or a new thread if it's `None`) and wait for its result
+fn run_offloaded<V: Send + 'static>(
+    thread_pool: &Option<Arc<ThreadPool>>,
+    task: impl FnOnce() -> StdResult<V, Box<dyn StdError + Send + Sync>> + Send + 'static,
+) -> impl Future<Output = StdResult<V, Box<dyn StdError>>> {
+    let (sender, receiver) = oneshot::channel();
+    let task = move || {
+        let _ = sender.send(task());
+    };
+    match thread_pool {
+        Some(thread_pool) => thread_pool.spawn_ok(async move { task() }),
+        None => {
+            thread::spawn(task);
+        }
+    };
+    async move {
+        match receiver.await {
+            Ok(Ok(v)) => Ok(v),
+            Ok(Err(e)) => Err(e as Box<dyn StdError>),
+            Err(_) => Err(Box::new(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "the offloaded encoding/decoding is canceled",
+            )) as Box<dyn StdError>),
+        }
+    }
+}
+
+/**
+`OffloadDeserializer` Decode the large bodies by the inner deserializer on a thread pool,
+so deserializing multi-MB payloads doesn't block the async runtime.
+
+# Arguments
+
+* `threshold` - The bodies larger than it (in bytes) are decoded on the thread pool,
+  the others are decoded in place.
+* `thread_pool` - The thread pool, a new thread is spawned per body if it's `None`.
+
+# Remarks
+
+* The errors of the offloaded decodings are the ones of `BodyDeserializer::decode_send()`:
+  they're kept as they are by the serde deserializers, and as `io::Error` (`InvalidData`)
+  with their messages by the ones not implementing it.
+
+*/
+pub struct OffloadDeserializer<R> {
+    pub inner: Arc<dyn BodyDeserializer<R> + Send + Sync>,
+    pub threshold: usize,
+    pub thread_pool: Option<Arc<ThreadPool>>,
+}
+impl<R> OffloadDeserializer<R> {
+    pub fn new(inner: Arc<dyn BodyDeserializer<R> + Send + Sync>, threshold: usize) -> Self {
+        Self::new_with_thread_pool(inner, threshold, None)
+    }
+    pub fn new_with_thread_pool(
+        inner: Arc<dyn BodyDeserializer<R> + Send + Sync>,
+        threshold: usize,
+        thread_pool: Option<Arc<ThreadPool>>,
+    ) -> Self {
+        OffloadDeserializer {
+            inner,
+            threshold,
+            thread_pool,
+        }
+    }
+}
+impl<R: Send + 'static> BodyDeserializer<R> for OffloadDeserializer<R> {
+    fn decode(&self, bytes: &Bytes) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.inner.decode(bytes)
+    }
+    fn decode_owned(&self, bytes: Bytes) -> StdResult<Box<R>, Box<dyn StdError>> {
+        self.inner.decode_owned(bytes)
+    }
+    fn decode_async<'a>(&'a self, bytes: Bytes) -> DecodeFuture<'a, R>
+    where
+        R: 'a,
+    {
+        if bytes.len() <= self.threshold {
+            return Box::pin(future::ready(self.decode_owned(bytes)));
+        }
+
+        let inner = self.inner.clone();
+        Box::pin(run_offloaded(&self.thread_pool, move || {
+            inner.decode_send(bytes)
+        }))
+    }
+    fn decode_send(&self, bytes: Bytes) -> StdResult<Box<R>, Box<dyn StdError + Send + Sync>> {
+        self.inner.decode_send(bytes)
+    }
+}
+
+// The estimated encoded size of the body (by `OffloadSerializer`)
+pub type SizeHint<T> = Arc<dyn Fn(&T) -> usize + Send + Sync>;
+
+/**
+`OffloadSerializer` Encode the large bodies by the inner serializer on a thread pool.
+
+# Arguments
+
+* `threshold` - The bodies estimated larger than it (in bytes, by the `size_hint`) are encoded on the thread pool,
+  the others are encoded in place.
+* `size_hint` - The estimated encoded size of the body,
+  all the bodies are encoded on the thread pool if it's `None` (the sizes are unknown before they're encoded).
+* `thread_pool` - The thread pool, a new thread is spawned per body if it's `None`.
+
+# Remarks
+
+* The errors of the offloaded encodings are the ones of `BodySerializer::encode_send()`:
+  they're kept as they are by the serde serializers, and as `io::Error` (`InvalidData`)
+  with their messages by the ones not implementing it.
+
+*/
+pub struct OffloadSerializer<T, B> {
+    pub inner: Arc<dyn BodySerializer<T, B> + Send + Sync>,
+    pub threshold: usize,
+    pub size_hint: Option<SizeHint<T>>,
+    pub thread_pool: Option<Arc<ThreadPool>>,
+}
+impl<T, B> OffloadSerializer<T, B> {
+    pub fn new(
+        inner: Arc<dyn BodySerializer<T, B> + Send + Sync>,
+        thread_pool: Option<Arc<ThreadPool>>,
+    ) -> Self {
+        OffloadSerializer {
+            inner,
+            threshold: 0,
+            size_hint: None,
+            thread_pool,
+        }
+    }
+    pub fn new_with_threshold(
+        inner: Arc<dyn BodySerializer<T, B> + Send + Sync>,
+        threshold: usize,
+        size_hint: SizeHint<T>,
+        thread_pool: Option<Arc<ThreadPool>>,
+    ) -> Self {
+        OffloadSerializer {
+            inner,
+            threshold,
+            size_hint: Some(size_hint),
+            thread_pool,
+        }
+    }
+}
+impl<T: Send + 'static, B: Send + 'static> BodySerializer<T, B> for OffloadSerializer<T, B> {
+    fn encode(&self, origin: T) -> StdResult<B, Box<dyn StdError>> {
+        self.inner.encode(origin)
+    }
+    fn encode_async<'a>(&'a self, origin: T) -> EncodeFuture<'a, B>
+    where
+        B: 'a,
+    {
+        if let Some(size_hint) = &self.size_hint {
+            if size_hint(&origin) <= self.threshold {
+                return Box::pin(future::ready(self.encode(origin)));
+            }
+        }
+
+        let inner = self.inner.clone();
+        Box::pin(run_offloaded(&self.thread_pool, move || {
+            inner.encode_send(origin)
+        }))
+    }
+    fn encode_send(&self, origin: T) -> StdResult<B, Box<dyn StdError + Send + Sync>> {
+        self.inner.encode_send(origin)
+    }
+}
+
+pub trait BaseAPI<Client, Req, Res, Method, Header, B> {
+    fn set_base_url(&mut self, url: Url);
+    fn get_base_url(&self) -> Url;
+    fn set_url_join_strategy(&mut self, strategy: UrlJoinStrategy);
+    fn get_url_join_strategy(&self) -> UrlJoinStrategy;
+    fn set_url_normalization(&mut self, normalization: UrlNormalization);
+    fn get_url_normalization(&self) -> UrlNormalization;
+    fn set_versioning(&mut self, versioning: Versioning);
+    fn get_versioning(&self) -> Versioning;
+    fn set_template_variables(&mut self, variables: TemplateVariables);
+    fn get_template_variables(&self) -> TemplateVariables;
+    fn set_default_header(&mut self, header: Option<Headers>);
+    fn get_default_header(&self) -> Option<Headers>;
+    fn set_offline_switch(&mut self, offline: OfflineSwitch);
+    fn get_offline_switch(&self) -> OfflineSwitch;
+    fn set_drain_gate(&mut self, gate: DrainGate);
+    fn get_drain_gate(&self) -> DrainGate;
+    fn set_usage_quota(&mut self, quota: Option<Arc<UsageQuota>>);
+    fn get_usage_quota(&self) -> Option<Arc<UsageQuota>>;
+    fn set_maintenance_policy(&mut self, policy: Option<Arc<MaintenancePolicy>>);
+    fn get_maintenance_policy(&self) -> Option<Arc<MaintenancePolicy>>;
+    fn set_read_only(&mut self, guard: Option<ReadOnlyGuard>);
+    fn get_read_only(&self) -> Option<ReadOnlyGuard>;
+    // Set the keep-alive worker, the replaced one is returned (to be stopped by the caller)
+    fn set_keep_alive(&mut self, worker: Option<KeepAliveWorker>) -> Option<KeepAliveWorker>;
+    fn get_keep_alive(&self) -> Option<&KeepAliveWorker>;
+    fn get_status_tracker(&self) -> StatusTracker;
+    #[cfg(feature = "queue")]
+    fn set_offline_queue(&mut self, store: Option<Arc<dyn QueueStore>>);
+    #[cfg(feature = "queue")]
+    fn get_offline_queue(&self) -> Option<Arc<dyn QueueStore>>;
+    #[cfg(feature = "for_serde")]
+    fn set_codec_registry(&mut self, registry: Arc<CodecRegistry>);
+    #[cfg(feature = "for_serde")]
+    fn get_codec_registry(&self) -> Arc<CodecRegistry>;
+
+    fn get_simple_http(&mut self) -> &mut SimpleHTTP<Client, Req, Res, Method, Header, B>;
+}
+
+// The body and the `Content-Type` of the response (by `BaseService::_call_common_with_content_type()`)
+pub type ResponseFuture<B> =
+    Pin<Box<dyn Future<Output = StdResult<(Option<String>, Box<B>), Box<dyn StdError>>>>>;
+
+pub trait BaseService<Client, Req, Res, Method, Header, B> {
+    fn get_simple_api(&self) -> &Arc<Mutex<dyn BaseAPI<Client, Req, Res, Method, Header, B>>>;
+    fn _call_common(
+        &self,
+        method: Method,
+        header: Option<Header>,
+        relative_url: String,
+        content_type: String,
+        path_param: Option<PathParam>,
+        query_param: Option<QueryParam>,
+        body: B,
+    ) -> Pin<Box<dyn Future<Output = StdResult<Box<B>, Box<dyn StdError>>>>>;
+
+    // Call it and get the `Content-Type` of the response as well (for choosing the codecs)
+    #[allow(clippy::too_many_arguments)]
+    fn _call_common_with_content_type(
+        &self,
+        method: Method,
+        header: Option<Header>,
+        relative_url: String,
+        content_type: String,
+        path_param: Option<PathParam>,
+        query_param: Option<QueryParam>,
+        body: B,
+    ) -> ResponseFuture<B>;
+
+    fn body_to_bytes(
+        &self,
+        body: B,
+    ) -> Pin<Box<dyn Future<Output = StdResult<Bytes, Box<dyn StdError + Send + Sync>>>>>;
+
+    // The size of the buffered request body (`None` if it's unknown, e.g. the streaming ones)
+    fn body_size(&self, _body: &B) -> Option<u64> {
+        None
+    }
+
+    // Read the body as a stream of chunks, it's a single chunk by `body_to_bytes()` by default
+    fn body_to_stream(&self, body: B) -> BodyStream<'static> {
+        let bytes = self.body_to_bytes(body);
+        Box::pin(stream::once(async move {
+            bytes.await.map_err(|e| e as Box<dyn StdError>)
+        }))
+    }
+}
+
+impl<Client, Req, Res, Method, Header, B> dyn BaseService<Client, Req, Res, Method, Header, B> {
+    pub fn set_base_url(&self, url: Url) {
+        self.get_simple_api().lock().unwrap().set_base_url(url);
+    }
+    pub fn get_base_url(&self) -> Url {
+        self.get_simple_api().lock().unwrap().get_base_url()
+    }
+    pub fn set_url_join_strategy(&self, strategy: UrlJoinStrategy) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .set_url_join_strategy(strategy);
+    }
+    pub fn get_url_join_strategy(&self) -> UrlJoinStrategy {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_url_join_strategy()
+    }
+    // Normalize the final urls of all calls (e.g. the duplicate slashes of the joined templates)
+    pub fn set_url_normalization(&self, normalization: UrlNormalization) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .set_url_normalization(normalization);
+    }
+    pub fn get_url_normalization(&self) -> UrlNormalization {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_url_normalization()
+    }
+    // Apply the versioning to all calls (unless it's overridden by the APIs)
+    pub fn set_versioning(&self, versioning: Versioning) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .set_versioning(versioning);
+    }
+    pub fn get_versioning(&self) -> Versioning {
+        self.get_simple_api().lock().unwrap().get_versioning()
+    }
+    // Resolve the `{namespace.name}` placeholders of the relative urls of all calls by the variables
+    pub fn set_template_variables(&self, variables: TemplateVariables) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .set_template_variables(variables);
+    }
+    pub fn get_template_variables(&self) -> TemplateVariables {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_template_variables()
+    }
+    pub fn set_default_header(&self, header: Option<Headers>) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .set_default_header(header);
+    }
+    pub fn get_default_header(&self) -> Option<Headers> {
+        self.get_simple_api().lock().unwrap().get_default_header()
+    }
+    /**
+    Switch the offline mode: the reads fail with `OfflineError`
+    (the `APIMemoized` serve the memoized values regardless of their ages),
+    and the writes are queued to the offline queue (`set_offline_queue()`, **feature: queue**) if any.
+    */
+    pub fn set_offline(&self, offline: bool) {
+        self.get_offline_switch().set_offline(offline);
+    }
+    pub fn is_offline(&self) -> bool {
+        self.get_offline_switch().is_offline()
+    }
+    // Share the `OfflineSwitch` (e.g. with the other services or the `UploadQueue`)
+    pub fn set_offline_switch(&self, offline: OfflineSwitch) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .set_offline_switch(offline);
+    }
+    pub fn get_offline_switch(&self) -> OfflineSwitch {
+        self.get_simple_api().lock().unwrap().get_offline_switch()
+    }
+    // Share the `DrainGate` (e.g. shutting down the services together)
+    pub fn set_drain_gate(&self, gate: DrainGate) {
+        self.get_simple_api().lock().unwrap().set_drain_gate(gate);
+    }
+    pub fn get_drain_gate(&self) -> DrainGate {
+        self.get_simple_api().lock().unwrap().get_drain_gate()
+    }
+    // Budget the calls/bytes of the service (e.g. under the quotas of the third-party APIs)
+    pub fn set_usage_quota(&self, quota: Option<Arc<UsageQuota>>) {
+        self.get_simple_api().lock().unwrap().set_usage_quota(quota);
+    }
+    pub fn get_usage_quota(&self) -> Option<Arc<UsageQuota>> {
+        self.get_simple_api().lock().unwrap().get_usage_quota()
+    }
+    // Reject/defer/queue the calls in the maintenance windows of the upstream
+    pub fn set_maintenance_policy(&self, policy: Option<Arc<MaintenancePolicy>>) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .set_maintenance_policy(policy);
+    }
+    pub fn get_maintenance_policy(&self) -> Option<Arc<MaintenancePolicy>> {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_maintenance_policy()
+    }
+    // Reject the calls other than `GET`/`HEAD` (except the allowed APIs) with `ReadOnlyMode`, `None` to disable it
+    pub fn set_read_only(&self, guard: Option<ReadOnlyGuard>) {
+        self.get_simple_api().lock().unwrap().set_read_only(guard);
+    }
+    pub fn get_read_only(&self) -> Option<ReadOnlyGuard> {
+        self.get_simple_api().lock().unwrap().get_read_only()
+    }
+    /**
+    Start the background keep-alive pinger, sending the cheap probes (`HEAD`/`OPTIONS`) to the base URL
+    every `interval` (e.g. to keep the NAT mappings & the pooled connections of long-idle services warm).
+
+    # Remarks
+
+    * It fails with `KeepAliveError` if the client doesn't support the probes (`BaseClient::keep_alive_probe()`).
+    * The running pinger (if any) is replaced, and it's stopped by `stop_keep_alive()`/`shutdown()`
+      or dropping the service.
+    * The probes are scheduled by the `clock` of the `SimpleHTTP`,
+      the interceptors aren't applied to them, and any status is regarded as alive.
+    * The probes share the pool of the client at the start, so restart the pinger after `close_idle()`.
+    * For HTTP/2 of hyper, the PING frames could be used instead (`HyperClientOptions.http2_keep_alive_interval`).
+    */
+    pub fn start_keep_alive(&self, options: KeepAliveOptions) -> StdResult<(), Box<dyn StdError>> {
+        let (probe, url, clock) = {
+            let mut simple_api = self.get_simple_api().lock().unwrap();
+            let url = simple_api.get_base_url();
+            let simple_http = simple_api.get_simple_http();
+            (
+                simple_http.keep_alive_probe(),
+                url,
+                simple_http.clock.clone(),
+            )
+        };
+        let probe = probe.ok_or_else(|| {
+            KeepAliveError::new("the client doesn't support the keep-alive probes")
+        })?;
+        let worker = KeepAliveWorker::spawn(probe, url, options, clock)
+            .map_err(|e| KeepAliveError::new(e.to_string()))?;
+
+        let replaced = self
+            .get_simple_api()
+            .lock()
+            .unwrap()
+            .set_keep_alive(Some(worker));
+        // Stop it out of the lock
+        if let Some(replaced) = replaced {
+            replaced.stop();
+        }
+        Ok(())
+    }
+    // Stop the keep-alive pinger (if any)
+    pub fn stop_keep_alive(&self) {
+        let worker = self.get_simple_api().lock().unwrap().set_keep_alive(None);
+        if let Some(worker) = worker {
+            worker.stop();
+        }
+    }
+    // The stats of the running keep-alive pinger (`None` if it's not started)
+    pub fn get_keep_alive_stats(&self) -> Option<KeepAliveStats> {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_keep_alive()
+            .map(|worker| worker.stats())
+    }
+    /**
+    Shut down the service gracefully (e.g. before restarting it):
+    the new calls are rejected with `ShuttingDownError`, the in-flight calls are waited within the `grace`,
+    then the offline queue (**feature: queue**) and the audit sink are flushed,
+    the keep-alive pinger is stopped, and the idle pooled connections are closed.
+
+    # Remarks
+
+    * It fails with `ShuttingDownError` (telling the number of the in-flight calls)
+      if they aren't finished within the `grace`, they're not cancelled but the rest is done anyway.
+    * The grace period is timed by the `clock` of the `SimpleHTTP`.
+    * The services sharing the `DrainGate` (`set_drain_gate()`) are shut down together.
+    * `get_drain_gate().reopen()` accepts the calls again.
+    */
+    pub async fn shutdown(&self, grace: Duration) -> StdResult<(), Box<dyn StdError>> {
+        let (gate, simple_http) = {
+            let mut simple_api = self.get_simple_api().lock().unwrap();
+            (
+                simple_api.get_drain_gate(),
+                simple_api.get_simple_http().clone(),
+            )
+        };
+        gate.close();
+        let drained = gate.drain(grace, simple_http.clock.as_ref()).await;
+
+        #[cfg(feature = "queue")]
+        if let Some(store) = self.get_offline_queue() {
+            store.flush()?;
+        }
+        if let Some(audit_log) = &simple_http.audit_log {
+            audit_log.sink.flush();
+        }
+        self.stop_keep_alive();
+        simple_http.client.lock().unwrap().close_idle();
+
+        drained.map_err(|e| Box::new(e) as Box<dyn StdError>)
+    }
+    /**
+    The snapshot of the health of the service (e.g. for the diagnostics endpoints).
+
+    # Remarks
+
+    * The `queue_depth` is read from the offline queue (**feature: queue**).
+    * The `cache_entries` are of the `MemoCache`s of the APIs (`memoized()`) of the service.
+    */
+    pub fn status(&self) -> ServiceStatus {
+        let simple_api = self.get_simple_api().lock().unwrap();
+        let gate = simple_api.get_drain_gate();
+        let status_tracker = simple_api.get_status_tracker();
+        #[cfg(feature = "queue")]
+        let queue_depth = simple_api
+            .get_offline_queue()
+            .and_then(|store| store.load_all().ok())
+            .map(|items| items.len());
+        #[cfg(not(feature = "queue"))]
+        let queue_depth = None;
+
+        ServiceStatus {
+            in_flight: gate.in_flight(),
+            shutting_down: gate.is_closed(),
+            offline: simple_api.get_offline_switch().is_offline(),
+            queue_depth,
+            cache_entries: status_tracker.cache_entries(),
+            last_error: status_tracker.last_error(),
+        }
+    }
+    // The store of the writes called while it's offline, flushed by an `UploadQueue` of the same store
+    #[cfg(feature = "queue")]
+    pub fn set_offline_queue(&self, store: Option<Arc<dyn QueueStore>>) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .set_offline_queue(store);
+    }
+    #[cfg(feature = "queue")]
+    pub fn get_offline_queue(&self) -> Option<Arc<dyn QueueStore>> {
+        self.get_simple_api().lock().unwrap().get_offline_queue()
+    }
+    // The codecs by the media types (for `make_api_*_with_codec()`)
+    #[cfg(feature = "for_serde")]
+    pub fn set_codec_registry(&self, registry: Arc<CodecRegistry>) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .set_codec_registry(registry);
+    }
+    #[cfg(feature = "for_serde")]
+    pub fn get_codec_registry(&self) -> Arc<CodecRegistry> {
+        self.get_simple_api().lock().unwrap().get_codec_registry()
+    }
+    pub fn set_client(
+        &self,
+        client: Arc<Mutex<dyn BaseClient<Client, Req, Res, Method, Header, B>>>,
     ) {
         self.get_simple_api()
             .lock()
@@ -223,57 +1541,255 @@ impl<Client, Req, Res, Method, Header, B> dyn BaseService<Client, Req, Res, Meth
             .get_simple_http()
             .set_client(client);
     }
-    pub fn set_timeout_millisecond(&self, timeout_millisecond: u64) {
+    /**
+    Close the idle pooled connections proactively,
+    e.g. before the process suspension (mobile/lambda) where the stale sockets cause first-call failures.
+
+    It does nothing if it's not supported by the client.
+    */
+    pub fn close_idle(&self) {
         self.get_simple_api()
             .lock()
             .unwrap()
             .get_simple_http()
-            .timeout_millisecond = timeout_millisecond;
+            .close_idle();
     }
-    pub fn get_timeout_millisecond(&self) -> u64 {
+    /**
+    Initialize the lazily constructed client (and its thread pools) ahead of the first request,
+    e.g. in the init phase of serverless functions.
+    */
+    pub fn warm(&self) {
         self.get_simple_api()
             .lock()
             .unwrap()
             .get_simple_http()
-            .timeout_millisecond
+            .warm();
     }
-
-    pub fn add_interceptor(&mut self, interceptor: Arc<dyn Interceptor<Req>>) {
+    pub fn set_audit_log(&self, audit_log: Option<Arc<AuditLog>>) {
         self.get_simple_api()
             .lock()
             .unwrap()
             .get_simple_http()
-            .add_interceptor(interceptor);
+            .set_audit_log(audit_log);
     }
-    pub fn add_interceptor_front(&mut self, interceptor: Arc<dyn Interceptor<Req>>) {
+    // The clock of the maintenance windows, the keep-alive probes and the timings (`SimpleHTTP::set_clock()`)
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
         self.get_simple_api()
             .lock()
             .unwrap()
             .get_simple_http()
-            .add_interceptor_front(interceptor);
+            .set_clock(clock);
     }
-    pub fn delete_interceptor(&mut self, interceptor: Arc<dyn Interceptor<Req>>) {
+    // Estimate the clock skew by the `Date` of the responses (`SimpleHTTP::set_clock_skew()`)
+    pub fn set_clock_skew(&self, clock_skew: Option<ClockSkew>) {
         self.get_simple_api()
             .lock()
             .unwrap()
             .get_simple_http()
-            .delete_interceptor(interceptor);
+            .set_clock_skew(clock_skew);
     }
-}
-
-impl<Client, Req, Res, Method, Header, B> dyn BaseService<Client, Req, Res, Method, Header, B>
-where
-    Req: 'static,
-{
-    pub fn add_interceptor_fn(
-        &mut self,
-        func: impl FnMut(&mut Req) -> StdResult<(), Box<dyn StdError>> + Send + Sync + 'static,
-    ) -> Arc<InterceptorFunc<Req>> {
+    // Observe the deprecation signals of the responses (`SimpleHTTP::set_deprecation_monitor()`)
+    pub fn set_deprecation_monitor(&self, deprecation_monitor: Option<DeprecationMonitor>) {
         self.get_simple_api()
             .lock()
             .unwrap()
             .get_simple_http()
-            .add_interceptor_fn(func)
+            .set_deprecation_monitor(deprecation_monitor);
+    }
+    // The timeout of the calls (zero for `DEFAULT_TIMEOUT`)
+    pub fn set_timeout(&self, timeout: Duration) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .set_timeout(timeout);
+    }
+    pub fn get_timeout(&self) -> Duration {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .get_timeout()
+    }
+    #[deprecated(note = "Use `set_timeout()` instead")]
+    pub fn set_timeout_millisecond(&self, timeout_millisecond: u64) {
+        self.set_timeout(Duration::from_millis(timeout_millisecond));
+    }
+    #[deprecated(note = "Use `get_timeout()` instead")]
+    pub fn get_timeout_millisecond(&self) -> u64 {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .timeout
+            .as_millis() as u64
+    }
+
+    pub fn add_interceptor(&mut self, interceptor: Arc<dyn Interceptor<Req>>) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor(interceptor);
+    }
+    pub fn add_interceptor_front(&mut self, interceptor: Arc<dyn Interceptor<Req>>) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_front(interceptor);
+    }
+    pub fn delete_interceptor(&mut self, interceptor: Arc<dyn Interceptor<Req>>) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .delete_interceptor(interceptor);
+    }
+    // Delete the interceptor of the id (from `list_interceptors()`), `false` if there's none
+    pub fn delete_interceptor_by_id(&mut self, id: &str) -> bool {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .delete_interceptor_by_id(id)
+    }
+    // The ids of the interceptors (in the order they're applied)
+    pub fn list_interceptors(&self) -> Vec<String> {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .list_interceptors()
+    }
+    pub fn clear_interceptors(&mut self) {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .clear_interceptors();
+    }
+}
+
+impl<Client, Req, Res, Method, Header, B> dyn BaseService<Client, Req, Res, Method, Header, B>
+where
+    Req: 'static,
+{
+    pub fn add_interceptor_fn(
+        &mut self,
+        func: impl FnMut(&mut Req) -> StdResult<(), Box<dyn StdError>> + Send + Sync + 'static,
+    ) -> Arc<InterceptorFunc<Req>> {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_fn(func)
+    }
+    // Add the interceptor in the named `slot`, ordered by its constraints (`InterceptorSlot`)
+    pub fn add_interceptor_slot(
+        &mut self,
+        slot: InterceptorSlot,
+        interceptor: Arc<dyn Interceptor<Req>>,
+    ) -> StdResult<(), InterceptorOrderError> {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_slot(slot, interceptor)
+    }
+    pub fn add_interceptor_headers_fn(
+        &mut self,
+        func: impl FnMut(&mut Headers) -> StdResult<(), Box<dyn StdError>> + Send + Sync + 'static,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders,
+    {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_headers_fn(func)
+    }
+    pub fn add_interceptor_id_header(
+        &mut self,
+        name: impl Into<String>,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders,
+    {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_id_header(name)
+    }
+    pub fn add_interceptor_logging(
+        &mut self,
+        formatter: RequestFormatter,
+        log_hook: RequestLogHook,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestSummary,
+    {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_logging(formatter, log_hook)
+    }
+    pub fn add_interceptor_delegation(
+        &mut self,
+        headers: DelegationHeaders,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders + HasRequestOptions,
+    {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_delegation(headers)
+    }
+    pub fn add_interceptor_locale(
+        &mut self,
+        default_locale: Option<Locale>,
+        headers: LocaleHeaders,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders + HasRequestOptions,
+    {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_locale(default_locale, headers)
+    }
+    pub fn add_interceptor_replay_nonce(
+        &mut self,
+        headers: ReplayNonceHeaders,
+        clock: Arc<dyn Clock>,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders + HasRequestOptions,
+    {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_replay_nonce(headers, clock)
+    }
+    pub fn add_interceptor_auth_provider(
+        &mut self,
+        provider: Arc<dyn AuthProvider>,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders + HasRequestOptions,
+    {
+        self.get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .add_interceptor_auth_provider(provider)
     }
 }
 
@@ -286,15 +1802,9 @@ impl<Client, Req, Res, Method, Header, B> dyn BaseService<Client, Req, Res, Meth
         response_deserializer: Arc<dyn BodyDeserializer<R>>,
         _return_type: &R,
     ) -> APIResponseOnly<R, Client, Req, Res, Method, Header, B> {
-        APIResponseOnly {
-            0: self.make_api_no_body(
-                base,
-                method,
-                relative_url,
-                response_deserializer,
-                _return_type,
-            ),
-        }
+        ApiBuilder::new(method, relative_url)
+            .deserializer(response_deserializer)
+            .build_response_only(base)
     }
     pub fn make_api_no_body<R>(
         &self,
@@ -304,13 +1814,9 @@ impl<Client, Req, Res, Method, Header, B> dyn BaseService<Client, Req, Res, Meth
         response_deserializer: Arc<dyn BodyDeserializer<R>>,
         _return_type: &R,
     ) -> APINoBody<R, Client, Req, Res, Method, Header, B> {
-        APINoBody {
-            base,
-            method,
-            relative_url: relative_url.into(),
-            response_deserializer,
-            content_type: "".to_string(),
-        }
+        ApiBuilder::new(method, relative_url)
+            .deserializer(response_deserializer)
+            .build_no_body(base)
     }
     pub fn make_api_has_body<T, R>(
         &self,
@@ -322,13 +1828,255 @@ impl<Client, Req, Res, Method, Header, B> dyn BaseService<Client, Req, Res, Meth
         response_deserializer: Arc<dyn BodyDeserializer<R>>,
         _return_type: &R,
     ) -> APIHasBody<T, R, Client, Req, Res, Method, Header, B> {
-        APIHasBody {
+        ApiBuilder::new(method, relative_url)
+            .content_type(content_type)
+            .serializer(request_serializer)
+            .deserializer(response_deserializer)
+            .build(base)
+    }
+
+    /**
+    Make an API sending the body by the codec of the `content_type`,
+    and receiving the responses by the codecs of their `Content-Type` (`CodecDeserializer`).
+
+    # Remarks
+
+    * The codecs are looked up in the `get_codec_registry()`,
+      it fails with `UnsupportedMediaTypeError` if there's no codec for the `content_type`.
+    * The responses without `Content-Type` are decoded by the codec of the `content_type`.
+
+    */
+    #[cfg(feature = "for_serde")]
+    pub fn make_api_has_body_with_codec<T, R>(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+        content_type: &str,
+        _return_type: &R,
+    ) -> APIHasBodyResult<T, R, Client, Req, Res, Method, Header, B>
+    where
+        T: Serialize + 'static,
+        R: DeserializeOwned + 'static,
+        B: From<Bytes> + 'static,
+    {
+        let registry = self.get_codec_registry();
+        Ok(self.make_api_has_body(
+            base,
+            method,
+            relative_url,
+            content_type,
+            registry.serializer(content_type)?,
+            registry.deserializer(content_type)?,
+            _return_type,
+        ))
+    }
+    /**
+    Make an API sending the body by the codec of the `content_type`,
+    and accepting the responses of the `accept` media type (e.g. sending protobuf and receiving JSON).
+
+    # Remarks
+
+    * The requests are sent with the `Content-Type` of the `content_type` and the `Accept` of the `accept`.
+    * The responses are decoded by the codecs of their `Content-Type` (`CodecDeserializer`),
+      the ones without `Content-Type` by the codec of the `accept`.
+    * It fails with `UnsupportedMediaTypeError` if there's no codec for the `content_type` or the `accept`.
+    * The typed codecs (`Codec<T>`) of `T`/`R` are chosen over the serde ones (e.g. the protobuf codec of the message).
+
+    */
+    #[cfg(feature = "for_serde")]
+    pub fn make_api_transcoding<T, R>(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+        content_type: &str,
+        accept: &str,
+        _return_type: &R,
+    ) -> APIHasBodyResult<T, R, Client, Req, Res, Method, Header, B>
+    where
+        T: Serialize + 'static,
+        R: DeserializeOwned + 'static,
+        B: From<Bytes> + 'static,
+    {
+        let registry = self.get_codec_registry();
+        Ok(ApiBuilder::new(method, relative_url)
+            .content_type(content_type)
+            .accept(accept)
+            .serializer(registry.serializer(content_type)?)
+            .deserializer(registry.deserializer(accept)?)
+            .build(base))
+    }
+    /**
+    Make an API receiving the responses by the codecs of their `Content-Type` (`CodecDeserializer`).
+
+    # Remarks
+
+    * The responses without `Content-Type` are decoded by the codec of the `default_content_type`.
+
+    */
+    #[cfg(feature = "for_serde")]
+    pub fn make_api_no_body_with_codec<R>(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+        default_content_type: &str,
+        _return_type: &R,
+    ) -> CodecResult<APINoBody<R, Client, Req, Res, Method, Header, B>>
+    where
+        R: DeserializeOwned + 'static,
+    {
+        let registry = self.get_codec_registry();
+        Ok(self.make_api_no_body(
+            base,
+            method,
+            relative_url,
+            registry.deserializer(default_content_type)?,
+            _return_type,
+        ))
+    }
+
+    /**
+    Make an API sending the operations of JSON Patch as `application/json-patch+json`
+    (e.g. the ones of `json_patch_diff()`).
+    */
+    #[cfg(feature = "for_serde")]
+    pub fn make_api_json_patch<R>(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIHasBody<Vec<PatchOperation>, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        self.make_api_has_body(
+            base,
+            method,
+            relative_url,
+            MEDIA_TYPE_JSON_PATCH,
+            Arc::new(DEFAULT_JSON_PATCH_SERIALIZER),
+            response_deserializer,
+            _return_type,
+        )
+    }
+    /**
+    Make an API sending the merge patch as `application/merge-patch+json`
+    (e.g. the one of `merge_patch_diff()`, or a struct of `Option` fields skipping the `None` ones).
+    */
+    #[cfg(feature = "for_serde")]
+    pub fn make_api_merge_patch<T, R>(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIHasBody<T, R, Client, Req, Res, Method, Header, B>
+    where
+        T: Serialize,
+        B: From<Bytes>,
+    {
+        self.make_api_has_body(
+            base,
+            method,
+            relative_url,
+            MEDIA_TYPE_MERGE_PATCH,
+            Arc::new(DEFAULT_MERGE_PATCH_SERIALIZER),
+            response_deserializer,
+            _return_type,
+        )
+    }
+
+    /**
+    Make an API sending the raw `Bytes` as `application/octet-stream`, skipping serde.
+    */
+    pub fn make_api_bytes_in<R>(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIHasBody<Bytes, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        self.make_api_has_body(
+            base,
+            method,
+            relative_url,
+            CONTENT_TYPE_OCTET_STREAM,
+            Arc::new(DEFAULT_DUMMY_BYPASS_SERIALIZER_FOR_BYTES_TO_BODY),
+            response_deserializer,
+            _return_type,
+        )
+    }
+    /**
+    Make an API receiving the raw `Bytes` of the response, skipping serde.
+    */
+    pub fn make_api_bytes_out(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+    ) -> APINoBody<Bytes, Client, Req, Res, Method, Header, B> {
+        self.make_api_no_body(
+            base,
+            method,
+            relative_url,
+            Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+            &Bytes::new(),
+        )
+    }
+    /**
+    Make an API sending the raw `Bytes` as `application/octet-stream`
+    and receiving the raw `Bytes` of the response, skipping serde.
+    */
+    pub fn make_api_bytes_in_out(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+    ) -> APIHasBody<Bytes, Bytes, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        self.make_api_bytes_in(
+            base,
+            method,
+            relative_url,
+            Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+            &Bytes::new(),
+        )
+    }
+
+    /**
+    Make an API sending the raw `Bytes` as the content type detected by their magic bytes
+    (or `application/octet-stream`), skipping serde.
+    */
+    #[cfg(feature = "sniff")]
+    pub fn make_api_bytes_in_sniffed<R>(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIMultipart<Bytes, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        APIMultipart {
             base,
             method,
             relative_url: relative_url.into(),
-            content_type: content_type.into(),
-            request_serializer,
+            request_serializer: Arc::new(DEFAULT_SNIFFING_BYTES_SERIALIZER),
             response_deserializer,
+            request_info_hook: None,
         }
     }
 
@@ -343,112 +2091,1092 @@ impl<Client, Req, Res, Method, Header, B> dyn BaseService<Client, Req, Res, Meth
         _return_type: &R,
     ) -> APIMultipart<FormData, R, Client, Req, Res, Method, Header, B>
     where
-        B: From<Bytes>,
+        B: From<Bytes>,
+    {
+        APIMultipart {
+            base,
+            method,
+            relative_url: relative_url.into(),
+            request_serializer: Arc::new(DEFAULT_MULTIPART_SERIALIZER),
+            response_deserializer,
+            request_info_hook: None,
+        }
+    }
+    /**
+    Make an API sending the form like the browsers: `application/x-www-form-urlencoded` if there're no files,
+    or `multipart/form-data` otherwise (e.g. the `FormData` of `FormData::from_serialize()`).
+    */
+    #[cfg(feature = "multipart")]
+    pub fn make_api_form<R>(
+        &self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIMultipart<FormData, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        APIMultipart {
+            base,
+            method,
+            relative_url: relative_url.into(),
+            request_serializer: Arc::new(DEFAULT_FORM_SERIALIZER),
+            response_deserializer,
+            request_info_hook: None,
+        }
+    }
+}
+
+/**
+`MakeApi` Make the APIs by the shared service itself (`Arc<dyn BaseService>`),
+the APIs capture the service, so it's not passed again like `make_api_*(base_service_shared.clone(), ...)`.
+
+# Remarks
+
+* The arguments are the same as `make_api_*()` without `base`.
+
+```ignore
+use http_api_service::simple_api::MakeApi;
+
+let api_get_product = base_service_shared.api_no_body(
+    Method::GET,
+    "/products/{id}",
+    json_deserializer.clone(),
+    return_type_marker,
+);
+```
+*/
+pub trait MakeApi<Client, Req, Res, Method, Header, B> {
+    fn api_response_only<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIResponseOnly<R, Client, Req, Res, Method, Header, B>;
+    fn api_no_body<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APINoBody<R, Client, Req, Res, Method, Header, B>;
+    fn api_has_body<T, R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        content_type: impl Into<String>,
+        request_serializer: Arc<dyn BodySerializer<T, B>>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIHasBody<T, R, Client, Req, Res, Method, Header, B>;
+    fn api_bytes_in<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIHasBody<Bytes, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>;
+    fn api_bytes_out(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+    ) -> APINoBody<Bytes, Client, Req, Res, Method, Header, B>;
+    fn api_bytes_in_out(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+    ) -> APIHasBody<Bytes, Bytes, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>;
+    #[cfg(feature = "sniff")]
+    fn api_bytes_in_sniffed<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIMultipart<Bytes, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>;
+    #[cfg(feature = "multipart")]
+    fn api_multipart<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIMultipart<FormData, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>;
+    #[cfg(feature = "multipart")]
+    fn api_form<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIMultipart<FormData, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>;
+}
+
+impl<Client, Req, Res, Method, Header, B> MakeApi<Client, Req, Res, Method, Header, B>
+    for Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>
+{
+    fn api_response_only<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIResponseOnly<R, Client, Req, Res, Method, Header, B> {
+        self.make_api_response_only(
+            self.clone(),
+            method,
+            relative_url,
+            response_deserializer,
+            _return_type,
+        )
+    }
+    fn api_no_body<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APINoBody<R, Client, Req, Res, Method, Header, B> {
+        self.make_api_no_body(
+            self.clone(),
+            method,
+            relative_url,
+            response_deserializer,
+            _return_type,
+        )
+    }
+    fn api_has_body<T, R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        content_type: impl Into<String>,
+        request_serializer: Arc<dyn BodySerializer<T, B>>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIHasBody<T, R, Client, Req, Res, Method, Header, B> {
+        self.make_api_has_body(
+            self.clone(),
+            method,
+            relative_url,
+            content_type,
+            request_serializer,
+            response_deserializer,
+            _return_type,
+        )
+    }
+    fn api_bytes_in<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIHasBody<Bytes, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        self.make_api_bytes_in(
+            self.clone(),
+            method,
+            relative_url,
+            response_deserializer,
+            _return_type,
+        )
+    }
+    fn api_bytes_out(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+    ) -> APINoBody<Bytes, Client, Req, Res, Method, Header, B> {
+        self.make_api_bytes_out(self.clone(), method, relative_url)
+    }
+    fn api_bytes_in_out(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+    ) -> APIHasBody<Bytes, Bytes, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        self.make_api_bytes_in_out(self.clone(), method, relative_url)
+    }
+    #[cfg(feature = "sniff")]
+    fn api_bytes_in_sniffed<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIMultipart<Bytes, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        self.make_api_bytes_in_sniffed(
+            self.clone(),
+            method,
+            relative_url,
+            response_deserializer,
+            _return_type,
+        )
+    }
+    #[cfg(feature = "multipart")]
+    fn api_multipart<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIMultipart<FormData, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        self.make_api_multipart(
+            self.clone(),
+            method,
+            relative_url,
+            response_deserializer,
+            _return_type,
+        )
+    }
+    #[cfg(feature = "multipart")]
+    fn api_form<R>(
+        &self,
+        method: Method,
+        relative_url: impl Into<String>,
+        response_deserializer: Arc<dyn BodyDeserializer<R>>,
+        _return_type: &R,
+    ) -> APIMultipart<FormData, R, Client, Req, Res, Method, Header, B>
+    where
+        B: From<Bytes>,
+    {
+        self.make_api_form(
+            self.clone(),
+            method,
+            relative_url,
+            response_deserializer,
+            _return_type,
+        )
+    }
+}
+
+/**
+`ApiBuilder` Build the APIs (`APINoBody`/`APIHasBody`/`APIResponseOnly`) with the optional parts,
+instead of the long positional argument lists of `make_api_*()`.
+
+# Arguments
+
+* `method` - The HTTP method.
+* `relative_url` - The relative url (with the path params like `{id}`).
+
+# Remarks
+
+* The request body is sent as it is (`DEFAULT_DUMMY_BYPASS_SERIALIZER_FOR_BODY`) by default,
+  and the response is returned as the raw `Bytes` (`DEFAULT_DUMMY_BYPASS_DESERIALIZER`) by default.
+* The content type is `application/octet-stream` for `build()` by default
+  (and empty for `build_no_body()`/`build_response_only()`).
+
+```ignore
+let api_post_product = ApiBuilder::new(Method::POST, "/products/{id}")
+    .content_type("application/json")
+    .serializer(json_serializer.clone())
+    .deserializer(json_deserializer.clone())
+    .build(base_service_shared.clone());
+```
+*/
+pub struct ApiBuilder<T, R, Method, B> {
+    pub method: Method,
+    pub relative_url: String,
+    pub content_type: Option<String>,
+    pub accept: Option<String>,
+    pub versioning: Option<Versioning>,
+    pub strict_content_type: Option<String>,
+
+    pub request_serializer: Arc<dyn BodySerializer<T, B>>,
+    pub response_deserializer: Arc<dyn BodyDeserializer<R>>,
+}
+impl<Method, B> ApiBuilder<B, Bytes, Method, B> {
+    pub fn new(method: Method, relative_url: impl Into<String>) -> Self {
+        ApiBuilder {
+            method,
+            relative_url: relative_url.into(),
+            content_type: None,
+            accept: None,
+            versioning: None,
+            strict_content_type: None,
+            request_serializer: Arc::new(DEFAULT_DUMMY_BYPASS_SERIALIZER_FOR_BODY),
+            response_deserializer: Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        }
+    }
+}
+impl<T, R, Method, B> ApiBuilder<T, R, Method, B> {
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+    // The media type accepted by the API (sent as the `Accept` header)
+    pub fn accept(mut self, accept: impl Into<String>) -> Self {
+        self.accept = Some(accept.into());
+        self
+    }
+    // Override the versioning of the service for the API
+    pub fn versioning(mut self, versioning: Versioning) -> Self {
+        self.versioning = Some(versioning);
+        self
+    }
+    // Verify the `Content-Type` of the responses before the deserialization (by `check_content_type()`)
+    pub fn strict_content_type(mut self, media_type: impl Into<String>) -> Self {
+        self.strict_content_type = Some(media_type.into());
+        self
+    }
+    pub fn serializer<T2>(
+        self,
+        request_serializer: Arc<dyn BodySerializer<T2, B>>,
+    ) -> ApiBuilder<T2, R, Method, B> {
+        ApiBuilder {
+            method: self.method,
+            relative_url: self.relative_url,
+            content_type: self.content_type,
+            accept: self.accept,
+            versioning: self.versioning,
+            strict_content_type: self.strict_content_type,
+            request_serializer,
+            response_deserializer: self.response_deserializer,
+        }
+    }
+    pub fn deserializer<R2>(
+        self,
+        response_deserializer: Arc<dyn BodyDeserializer<R2>>,
+    ) -> ApiBuilder<T, R2, Method, B> {
+        ApiBuilder {
+            method: self.method,
+            relative_url: self.relative_url,
+            content_type: self.content_type,
+            accept: self.accept,
+            versioning: self.versioning,
+            strict_content_type: self.strict_content_type,
+            request_serializer: self.request_serializer,
+            response_deserializer,
+        }
+    }
+
+    pub fn build<Client, Req, Res, Header>(
+        self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+    ) -> APIHasBody<T, R, Client, Req, Res, Method, Header, B> {
+        APIHasBody {
+            base,
+            method: self.method,
+            relative_url: self.relative_url,
+            content_type: self
+                .content_type
+                .unwrap_or_else(|| CONTENT_TYPE_OCTET_STREAM.to_string()),
+            accept: self.accept,
+            versioning: self.versioning,
+            strict_content_type: self.strict_content_type,
+            request_validator: None,
+            query_validator: None,
+            request_serializer: self.request_serializer,
+            response_deserializer: self.response_deserializer,
+        }
+    }
+    pub fn build_no_body<Client, Req, Res, Header>(
+        self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+    ) -> APINoBody<R, Client, Req, Res, Method, Header, B> {
+        APINoBody {
+            base,
+            method: self.method,
+            relative_url: self.relative_url,
+            content_type: self.content_type.unwrap_or_default(),
+            accept: self.accept,
+            versioning: self.versioning,
+            strict_content_type: self.strict_content_type,
+            query_validator: None,
+            response_deserializer: self.response_deserializer,
+        }
+    }
+    pub fn build_response_only<Client, Req, Res, Header>(
+        self,
+        base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+    ) -> APIResponseOnly<R, Client, Req, Res, Method, Header, B> {
+        APIResponseOnly(self.build_no_body(base))
+    }
+}
+
+// APIResponseOnly API with only response options
+// R: Response body Type
+pub struct APIResponseOnly<R, Client, Req, Res, Method, Header, B>(
+    APINoBody<R, Client, Req, Res, Method, Header, B>,
+);
+impl<R, Client, Req, Res, Method, Header, B>
+    APIResponseOnly<R, Client, Req, Res, Method, Header, B>
+{
+    /**
+    Convert the deserialized response into the domain type `D` by `TryFrom`,
+    conversion failures are returned as `MapIntoError`.
+    */
+    pub fn map_into<D>(self) -> APIResponseOnly<D, Client, Req, Res, Method, Header, B>
+    where
+        R: 'static,
+        D: TryFrom<R> + 'static,
+        D::Error: Into<Box<dyn StdError>>,
+    {
+        APIResponseOnly(self.0.map_into())
+    }
+
+    // Validate the query parameters by the `RequestValidator` before sending them
+    pub fn with_query_validator(
+        self,
+        query_validator: Arc<dyn RequestValidator<QueryParam>>,
+    ) -> Self {
+        APIResponseOnly(self.0.with_query_validator(query_validator))
+    }
+
+    // Memoize the deserialized responses by the `MemoCache`
+    pub fn memoized(
+        self,
+        cache: Arc<MemoCache<R>>,
+    ) -> APIMemoized<R, Client, Req, Res, Method, Header, B>
+    where
+        R: 'static,
+    {
+        self.0.memoized(cache)
+    }
+
+    pub async fn call(&self) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: Default,
+        Method: Clone,
+    {
+        self.call_with_options(None, None::<QueryParam>).await
+    }
+    pub async fn call_with_options(
+        &self,
+        header: Option<Header>,
+        query_param: Option<impl Into<QueryParam>>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: Default,
+        Method: Clone,
+    {
+        self.0
+            .call_with_options(header, None::<PathParam>, query_param)
+            .await
+    }
+}
+
+// APINoBody API without request body options
+// R: Response body Type
+pub struct APINoBody<R, Client, Req, Res, Method, Header, B> {
+    base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
+    pub method: Method,
+    pub relative_url: String,
+    pub content_type: String,
+    // The media type accepted by the API (sent as the `Accept` header)
+    pub accept: Option<String>,
+    // The versioning of the API (overriding the one of the service)
+    pub versioning: Option<Versioning>,
+    // The expected `Content-Type` of the responses (verified before the deserialization)
+    pub strict_content_type: Option<String>,
+    // Validate the query parameters before sending them
+    pub query_validator: Option<Arc<dyn RequestValidator<QueryParam>>>,
+
+    pub response_deserializer: Arc<dyn BodyDeserializer<R>>,
+}
+impl<R, Client, Req, Res, Method, Header, B> APINoBody<R, Client, Req, Res, Method, Header, B> {
+    /**
+    Convert the deserialized response into the domain type `D` by `TryFrom`,
+    conversion failures are returned as `MapIntoError`.
+    */
+    pub fn map_into<D>(self) -> APINoBody<D, Client, Req, Res, Method, Header, B>
+    where
+        R: 'static,
+        D: TryFrom<R> + 'static,
+        D::Error: Into<Box<dyn StdError>>,
+    {
+        APINoBody {
+            base: self.base,
+            method: self.method,
+            relative_url: self.relative_url,
+            content_type: self.content_type,
+            accept: self.accept,
+            versioning: self.versioning,
+            strict_content_type: self.strict_content_type,
+            query_validator: self.query_validator,
+            response_deserializer: Arc::new(TryFromDeserializer {
+                inner: self.response_deserializer,
+            }),
+        }
+    }
+
+    // Validate the query parameters by the `RequestValidator` before sending them
+    pub fn with_query_validator(
+        mut self,
+        query_validator: Arc<dyn RequestValidator<QueryParam>>,
+    ) -> Self {
+        self.query_validator = Some(query_validator);
+        self
+    }
+
+    // Memoize the deserialized responses by the `MemoCache`
+    pub fn memoized(
+        self,
+        cache: Arc<MemoCache<R>>,
+    ) -> APIMemoized<R, Client, Req, Res, Method, Header, B>
+    where
+        R: 'static,
+    {
+        // Counted by `status()`
+        let entries: Arc<dyn CacheEntries> = cache.clone();
+        self.base
+            .get_simple_api()
+            .lock()
+            .unwrap()
+            .get_status_tracker()
+            .track_cache(Arc::downgrade(&entries));
+        APIMemoized {
+            api: self,
+            cache,
+            vary_key: None,
+            refresh_spawner: None,
+        }
+    }
+
+    pub async fn call(&self, path_param: Option<PathParam>) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: Default,
+        Method: Clone,
+    {
+        self.call_with_options(None, path_param, None::<QueryParam>)
+            .await
+    }
+
+    // Call the absolute `url` verbatim instead of the relative url (e.g. the signed urls)
+    pub async fn call_absolute(&self, url: &str) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: Default,
+        Method: Clone,
+    {
+        self.call_absolute_with_options(url, false, None).await
+    }
+    /**
+    Call the absolute `url` verbatim instead of the relative url (e.g. the signed urls returned by the other APIs),
+    with the same interceptors (and the credentials unless they're skipped).
+
+    # Arguments
+
+    * `skip_auth` - Don't send the credentials (`RequestOptions::skip_auth`).
+
+    */
+    pub async fn call_absolute_with_options(
+        &self,
+        url: &str,
+        skip_auth: bool,
+        header: Option<Header>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: Default,
+        Method: Clone,
+    {
+        let mut extensions = Extensions::new();
+        extensions.insert(AbsoluteUrl {
+            url: Url::parse(url)?,
+            skip_auth,
+        });
+        with_extensions(
+            extensions,
+            self.call_with_options(header, None::<PathParam>, None::<QueryParam>),
+        )
+        .await
+    }
+
+    pub async fn call_with_options(
+        &self,
+        header: Option<Header>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: Default,
+        Method: Clone,
+    {
+        let query_param = query_param.map(|v| v.into());
+        validate_query_param(&self.query_validator, &query_param)?;
+        let (response_content_type, body) = with_api_extensions(
+            &self.accept,
+            &self.versioning,
+            self.base._call_common_with_content_type(
+                self.method.clone(),
+                header,
+                self.relative_url.clone(),
+                self.content_type.clone(),
+                if let Some(v) = path_param {
+                    Some(v.into())
+                } else {
+                    None
+                },
+                query_param,
+                B::default(),
+            ),
+        )
+        .await?;
+        if let Some(strict_content_type) = &self.strict_content_type {
+            check_content_type(strict_content_type, response_content_type.as_deref())?;
+        }
+        // let mut target = Box::new(target);
+        // let body = Box::new(body);
+        // let bytes = hyper::body::to_bytes(*body).await?;
+        let target = catch_hook_panic_async(
+            "response_deserializer",
+            self.response_deserializer
+                .decode_response(response_content_type, self.base.body_to_stream(*body)),
+        )
+        .await??;
+
+        Ok(target)
+    }
+}
+
+// Observe the refresh failures of `MemoCache` by (key, error)
+pub type RefreshErrorHook = Arc<dyn Fn(&str, &dyn StdError) + Send + Sync>;
+
+/**
+`MemoCache` The memoization of the deserialized responses (`Arc<R>`) with a TTL,
+keyed by the method, the resolved absolute url (with the path/query params)
+and the identity of the caller (`APIMemoized::vary_by()`).
+
+It's shared (`Arc`) by the `APIMemoized` of the hot reference-data endpoints,
+so the repeated calls skip the requests and the decoding.
+
+# Arguments
+
+* `ttl` - How long the values are fresh.
+* `max_entries` - The max number of the entries, the expired ones (then the oldest one)
+  are evicted when it's full.
+
+# Remarks
+
+* It's separate from HTTP caching: the responses are not revalidated, and the headers are not keys
+  (the values depending on the credentials should be varied by `APIMemoized::vary_by()`).
+* It could be shared by the services of the different base urls, the urls are parts of the keys.
+* The errors are not memoized, and the concurrent misses of the same key are not coalesced.
+* The expiration is by the `clock` (`SystemClock` by default).
+* The memoized values are served regardless of their ages while the service is offline (`set_offline()`).
+* The stale-while-revalidate mode is enabled by a non-zero `stale_while_revalidate`:
+  the expired values are kept for it, served by `APIMemoized::call_swr()` while they're refreshed,
+  and the refresh failures are observed by the `refresh_error_hook` (the stale values are kept).
+* The lookups are reported to `metrics_sink` (`ResilienceEvent::CacheHit`/`CacheStale`/`CacheMiss`).
+
+*/
+pub struct MemoCache<R> {
+    pub ttl: Duration,
+    pub max_entries: usize,
+    pub clock: Arc<dyn Clock>,
+    // How long the expired values could be served while they're refreshed (zero to disable)
+    pub stale_while_revalidate: Duration,
+    // Observe the refresh failures by (key, error)
+    pub refresh_error_hook: Option<RefreshErrorHook>,
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+    // key -> (expires at, value)
+    entries: Mutex<HashMap<String, (Instant, Arc<R>)>>,
+    // The keys being refreshed
+    refreshing: Mutex<HashSet<String>>,
+}
+impl<R> MemoCache<R> {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self::new_with_clock(ttl, max_entries, system_clock())
+    }
+    pub fn new_with_clock(ttl: Duration, max_entries: usize, clock: Arc<dyn Clock>) -> Self {
+        MemoCache {
+            ttl,
+            max_entries,
+            clock,
+            stale_while_revalidate: Duration::ZERO,
+            refresh_error_hook: None,
+            metrics_sink: None,
+            entries: Mutex::new(HashMap::new()),
+            refreshing: Mutex::new(HashSet::new()),
+        }
+    }
+
+    // The key of the call by the absolute url and the identity (`vary`), the query params are sorted so their orders don't matter
+    pub fn key(
+        method: &str,
+        url: &str,
+        query_param: Option<&QueryParam>,
+        vary: Option<&str>,
+    ) -> String {
+        let mut query_param = query_param
+            .map(|param| param.iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        query_param.sort();
+        format!("{} {} {:?} {:?}", method, url, query_param, vary)
+    }
+
+    // Get the value of the key regardless of its age (e.g. while it's offline)
+    pub fn peek(&self, key: &str) -> Option<Arc<R>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|(_, value)| value.clone())
+    }
+    // Get the fresh value of the key
+    pub fn get(&self, key: &str) -> Option<Arc<R>> {
+        match self.get_stale(key) {
+            Some((value, true)) => Some(value),
+            _ => None,
+        }
+    }
+    /**
+    Get the value of the key and whether it's fresh,
+    the stale values are returned within the `stale_while_revalidate` after they're expired.
+
+    The values expired beyond it are removed.
+    */
+    pub fn get_stale(&self, key: &str) -> Option<(Arc<R>, bool)> {
+        let now = self.clock.now();
+        let found = {
+            let mut entries = self.entries.lock().unwrap();
+            match entries.get(key) {
+                Some((expires_at, value)) if *expires_at > now => Some((value.clone(), true)),
+                Some((expires_at, value)) if *expires_at + self.stale_while_revalidate > now => {
+                    Some((value.clone(), false))
+                }
+                Some(_) => {
+                    entries.remove(key);
+                    None
+                }
+                None => None,
+            }
+        };
+        if let Some(metrics_sink) = &self.metrics_sink {
+            let key = key.to_string();
+            let event = match found {
+                Some((_, true)) => ResilienceEvent::CacheHit { key },
+                Some((_, false)) => ResilienceEvent::CacheStale { key },
+                None => ResilienceEvent::CacheMiss { key },
+            };
+            let _ = catch_hook_panic("metrics_sink", || metrics_sink.record_event(&event));
+        }
+        found
+    }
+    pub fn insert(&self, key: impl Into<String>, value: Arc<R>) {
+        if self.max_entries == 0 {
+            return;
+        }
+
+        let now = self.clock.now();
+        let key = key.into();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let stale_while_revalidate = self.stale_while_revalidate;
+            entries.retain(|_, (expires_at, _)| *expires_at + stale_while_revalidate > now);
+            if entries.len() >= self.max_entries {
+                let oldest = entries
+                    .iter()
+                    .min_by_key(|(_, (expires_at, _))| *expires_at)
+                    .map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+        entries.insert(key, (now + self.ttl, value));
+    }
+    pub fn invalidate(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+    // The number of the entries (including the expired ones not evicted yet)
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Mark the key as being refreshed, returns false if it's being refreshed already
+    fn begin_refresh(&self, key: &str) -> bool {
+        self.refreshing.lock().unwrap().insert(key.to_string())
+    }
+    fn end_refresh(&self, key: &str) {
+        self.refreshing.lock().unwrap().remove(key);
+    }
+}
+
+struct RefreshGuard<R> {
+    cache: Arc<MemoCache<R>>,
+    key: String,
+}
+impl<R> Drop for RefreshGuard<R> {
+    fn drop(&mut self) {
+        self.cache.end_refresh(&self.key);
+    }
+}
+
+impl<R> CacheEntries for MemoCache<R> {
+    fn entries(&self) -> usize {
+        self.len()
+    }
+}
+
+// The identity (e.g. the user/tenant) the memoized values are varied by (`APIMemoized::vary_by()`)
+pub type VaryKeyHook = Arc<dyn Fn() -> String + Send + Sync>;
+
+// APIMemoized API without request body options, the responses are memoized by the `MemoCache`
+// R: Response body Type
+pub struct APIMemoized<R, Client, Req, Res, Method, Header, B> {
+    pub api: APINoBody<R, Client, Req, Res, Method, Header, B>,
+    pub cache: Arc<MemoCache<R>>,
+    pub vary_key: Option<VaryKeyHook>,
+    pub refresh_spawner: Option<RefreshSpawner>,
+}
+impl<R, Client, Req, Res, Method, Header, B> APIMemoized<R, Client, Req, Res, Method, Header, B> {
+    // Vary the memoized values by the identity of the caller (e.g. the user of the session), it's a part of the keys
+    pub fn vary_by(mut self, vary_key: VaryKeyHook) -> Self {
+        self.vary_key = Some(vary_key);
+        self
+    }
+
+    // Spawn the background refreshes of `call_swr()` by the `refresh_spawner` (e.g. on the runtime of the service)
+    pub fn spawn_refresh_by(mut self, refresh_spawner: RefreshSpawner) -> Self {
+        self.refresh_spawner = Some(refresh_spawner);
+        self
+    }
+
+    // Spawn the refresh by the `refresh_spawner`, or `tokio::task::spawn_local()` (otherwise it's run in place)
+    async fn spawn_refresh(&self, refresh: RefreshFuture) {
+        match &self.refresh_spawner {
+            Some(refresh_spawner) => refresh_spawner(refresh),
+            #[cfg(feature = "tokio")]
+            None => {
+                tokio::task::spawn_local(refresh);
+            }
+            #[cfg(not(feature = "tokio"))]
+            None => refresh.await,
+        }
+    }
+
+    // The key of the call, by the resolved absolute url (the base url & the path params applied)
+    fn key(&self, path_param: Option<&PathParam>, query_param: Option<&QueryParam>) -> String
+    where
+        Method: Display,
     {
-        APIMultipart {
-            base,
-            method,
-            relative_url: relative_url.into(),
-            request_serializer: Arc::new(DEFAULT_MULTIPART_SERIALIZER),
-            response_deserializer,
+        let mut relative_url = self.api.relative_url.clone();
+        if let Some(path_param) = path_param {
+            for (k, v) in path_param.iter() {
+                relative_url = relative_url.replace(&("{".to_string() + k + "}"), v);
+            }
         }
+        let base_url = self.api.base.get_base_url();
+        let url = match join_url(
+            &base_url,
+            &relative_url,
+            self.api.base.get_url_join_strategy(),
+        ) {
+            Ok(url) => url.to_string(),
+            // The call fails as well, it's just kept distinct
+            Err(_) => base_url.to_string() + &relative_url,
+        };
+        let vary = self.vary_key.as_ref().map(|vary_key| vary_key());
+        MemoCache::<R>::key(
+            &self.api.method.to_string(),
+            &url,
+            query_param,
+            vary.as_deref(),
+        )
     }
-}
 
-// APIResponseOnly API with only response options
-// R: Response body Type
-pub struct APIResponseOnly<R, Client, Req, Res, Method, Header, B>(
-    APINoBody<R, Client, Req, Res, Method, Header, B>,
-);
-impl<R, Client, Req, Res, Method, Header, B>
-    APIResponseOnly<R, Client, Req, Res, Method, Header, B>
-{
-    pub async fn call(&self) -> StdResult<Box<R>, Box<dyn StdError>>
+    pub async fn call(&self, path_param: Option<PathParam>) -> StdResult<Arc<R>, Box<dyn StdError>>
     where
         B: Default,
-        Method: Clone,
+        Method: Clone + Display,
     {
-        self.call_with_options(None, None::<QueryParam>).await
+        self.call_with_options(None, path_param, None::<QueryParam>)
+            .await
     }
+
+    // The headers are not the part of the key
     pub async fn call_with_options(
         &self,
         header: Option<Header>,
+        path_param: Option<impl Into<PathParam>>,
         query_param: Option<impl Into<QueryParam>>,
-    ) -> StdResult<Box<R>, Box<dyn StdError>>
+    ) -> StdResult<Arc<R>, Box<dyn StdError>>
     where
         B: Default,
-        Method: Clone,
+        Method: Clone + Display,
     {
-        self.0
-            .call_with_options(header, None::<PathParam>, query_param)
-            .await
-    }
-}
+        let path_param = path_param.map(|v| v.into());
+        let query_param = query_param.map(|v| v.into());
+        let key = self.key(path_param.as_ref(), query_param.as_ref());
+        // The memoized value is served regardless of its age while it's offline
+        if self.api.base.is_offline() {
+            if let Some(value) = self.cache.peek(&key) {
+                return Ok(value);
+            }
+        }
+        if let Some(value) = self.cache.get(&key) {
+            return Ok(value);
+        }
 
-// APINoBody API without request body options
-// R: Response body Type
-pub struct APINoBody<R, Client, Req, Res, Method, Header, B> {
-    base: Arc<dyn BaseService<Client, Req, Res, Method, Header, B>>,
-    pub method: Method,
-    pub relative_url: String,
-    pub content_type: String,
+        let value: Arc<R> = Arc::from(
+            self.api
+                .call_with_options(header, path_param, query_param)
+                .await?,
+        );
+        self.cache.insert(key, value.clone());
+        Ok(value)
+    }
 
-    pub response_deserializer: Arc<dyn BodyDeserializer<R>>,
-}
-impl<R, Client, Req, Res, Method, Header, B> APINoBody<R, Client, Req, Res, Method, Header, B> {
-    pub async fn call(&self, path_param: Option<PathParam>) -> StdResult<Box<R>, Box<dyn StdError>>
+    pub async fn call_swr(
+        self: &Arc<Self>,
+        path_param: Option<PathParam>,
+    ) -> StdResult<(Arc<R>, bool), Box<dyn StdError>>
     where
-        B: Default,
-        Method: Clone,
+        R: 'static,
+        Client: 'static,
+        Req: 'static,
+        Res: 'static,
+        Method: Clone + Display + 'static,
+        Header: 'static,
+        B: Default + 'static,
     {
-        self.call_with_options(None, path_param, None::<QueryParam>)
+        self.call_swr_with_options(None, path_param, None::<QueryParam>)
             .await
     }
 
-    pub async fn call_with_options(
-        &self,
+    /**
+    Call it by the stale-while-revalidate semantics (`MemoCache::stale_while_revalidate`).
+
+    # Returns
+
+    * The fresh value, or the stale value with `true` if its refresh is spawned by this call.
+    * Otherwise (absent or expired beyond the `stale_while_revalidate`) it's called like `call_with_options()`.
+
+    # Remarks
+
+    * The requests of the bindings aren't `Send`, so the refresh (owning the `Arc` of the API) is spawned
+      by the `refresh_spawner`, or by `tokio::task::spawn_local()` by default (it must be called within a `LocalSet`).
+      Without both of them (no `tokio`), the refresh is run in place.
+    * The refresh is spawned once per key until it's done.
+    * The refresh failures are reported to the `refresh_error_hook`, and the stale value is kept.
+
+    */
+    pub async fn call_swr_with_options(
+        self: &Arc<Self>,
         header: Option<Header>,
         path_param: Option<impl Into<PathParam>>,
         query_param: Option<impl Into<QueryParam>>,
-    ) -> StdResult<Box<R>, Box<dyn StdError>>
+    ) -> StdResult<(Arc<R>, bool), Box<dyn StdError>>
     where
-        B: Default,
-        Method: Clone,
+        R: 'static,
+        Client: 'static,
+        Req: 'static,
+        Res: 'static,
+        Method: Clone + Display + 'static,
+        Header: 'static,
+        B: Default + 'static,
     {
-        let body = self
-            .base
-            ._call_common(
-                self.method.clone(),
-                header,
-                self.relative_url.clone(),
-                self.content_type.clone(),
-                if let Some(v) = path_param {
-                    Some(v.into())
-                } else {
-                    None
-                },
-                if let Some(v) = query_param {
-                    Some(v.into())
-                } else {
-                    None
-                },
-                B::default(),
-            )
-            .await?;
-        // let mut target = Box::new(target);
-        // let body = Box::new(body);
-        // let bytes = hyper::body::to_bytes(*body).await?;
-        let result = self.base.body_to_bytes(*body).await;
-        if result.is_err() {
-            return Err(result.err().unwrap());
+        let path_param = path_param.map(|v| v.into());
+        let query_param = query_param.map(|v| v.into());
+        let key = self.key(path_param.as_ref(), query_param.as_ref());
+        if self.api.base.is_offline() {
+            if let Some(value) = self.cache.peek(&key) {
+                return Ok((value, false));
+            }
         }
-        let bytes = result.ok().unwrap();
-        let target = self.response_deserializer.decode(&bytes)?;
+        match self.cache.get_stale(&key) {
+            Some((value, true)) => Ok((value, false)),
+            Some((value, false)) => {
+                if !self.cache.begin_refresh(&key) {
+                    return Ok((value, false));
+                }
 
-        Ok(target)
+                // The key is unmarked even if the refresh is dropped
+                let guard = RefreshGuard {
+                    cache: self.cache.clone(),
+                    key: key.clone(),
+                };
+                let this = self.clone();
+                let refresh = Box::pin(async move {
+                    let _guard = guard;
+                    match this
+                        .api
+                        .call_with_options(header, path_param, query_param)
+                        .await
+                    {
+                        Ok(value) => this.cache.insert(key.clone(), Arc::from(value)),
+                        Err(e) => {
+                            if let Some(hook) = &this.cache.refresh_error_hook {
+                                // The refresh goes on even if the hook panicked
+                                let _ = catch_hook_panic("refresh_error_hook", || {
+                                    hook(&key, e.as_ref())
+                                });
+                            }
+                        }
+                    }
+                });
+                self.spawn_refresh(refresh).await;
+                Ok((value, true))
+            }
+            None => {
+                let value: Arc<R> = Arc::from(
+                    self.api
+                        .call_with_options(header, path_param, query_param)
+                        .await?,
+                );
+                self.cache.insert(key, value.clone());
+                Ok((value, false))
+            }
+        }
+    }
+}
+
+// Run the call within the scope of the `AcceptMediaType`/`Versioning` of the API (if any)
+async fn with_api_extensions<F: Future>(
+    accept: &Option<String>,
+    versioning: &Option<Versioning>,
+    future: F,
+) -> F::Output {
+    let mut extensions = Extensions::new();
+    if let Some(accept) = accept {
+        extensions.insert(AcceptMediaType(accept.clone()));
+    }
+    if let Some(versioning) = versioning {
+        extensions.insert(versioning.clone());
     }
+    if extensions.is_empty() {
+        return future.await;
+    }
+    with_extensions(extensions, future).await
 }
 
+// The future refreshing the stale value of `APIMemoized::call_swr()`
+pub type RefreshFuture = Pin<Box<dyn Future<Output = ()>>>;
+// Spawn the `RefreshFuture` in the background (it isn't `Send`, e.g. by `tokio::task::spawn_local()`)
+pub type RefreshSpawner = Arc<dyn Fn(RefreshFuture) + Send + Sync>;
+
+// The `APIHasBody` made by the codecs of the media types (e.g. by `make_api_has_body_with_codec()`)
+#[cfg(feature = "for_serde")]
+pub type APIHasBodyResult<T, R, Client, Req, Res, Method, Header, B> =
+    CodecResult<APIHasBody<T, R, Client, Req, Res, Method, Header, B>>;
+
 // APIHasBody API with request body options
 // T: Request body Type
 // R: Response body Type
@@ -457,6 +3185,16 @@ pub struct APIHasBody<T, R, Client, Req, Res, Method, Header, B> {
     pub method: Method,
     pub relative_url: String,
     pub content_type: String,
+    // The media type accepted by the API (sent as the `Accept` header)
+    pub accept: Option<String>,
+    // The versioning of the API (overriding the one of the service)
+    pub versioning: Option<Versioning>,
+    // The expected `Content-Type` of the responses (verified before the deserialization)
+    pub strict_content_type: Option<String>,
+    // Validate the bodies before the serialization
+    pub request_validator: Option<Arc<dyn RequestValidator<T>>>,
+    // Validate the query parameters before sending them
+    pub query_validator: Option<Arc<dyn RequestValidator<QueryParam>>>,
 
     pub request_serializer: Arc<dyn BodySerializer<T, B>>,
     pub response_deserializer: Arc<dyn BodyDeserializer<R>>,
@@ -464,6 +3202,47 @@ pub struct APIHasBody<T, R, Client, Req, Res, Method, Header, B> {
 impl<T, R, Client, Req, Res, Method, Header, B>
     APIHasBody<T, R, Client, Req, Res, Method, Header, B>
 {
+    /**
+    Convert the deserialized response into the domain type `D` by `TryFrom`,
+    conversion failures are returned as `MapIntoError`.
+    */
+    pub fn map_into<D>(self) -> APIHasBody<T, D, Client, Req, Res, Method, Header, B>
+    where
+        R: 'static,
+        D: TryFrom<R> + 'static,
+        D::Error: Into<Box<dyn StdError>>,
+    {
+        APIHasBody {
+            base: self.base,
+            method: self.method,
+            relative_url: self.relative_url,
+            content_type: self.content_type,
+            accept: self.accept,
+            versioning: self.versioning,
+            strict_content_type: self.strict_content_type,
+            request_validator: self.request_validator,
+            query_validator: self.query_validator,
+            request_serializer: self.request_serializer,
+            response_deserializer: Arc::new(TryFromDeserializer {
+                inner: self.response_deserializer,
+            }),
+        }
+    }
+
+    // Validate the bodies by the `RequestValidator` before the serialization
+    pub fn with_validator(mut self, request_validator: Arc<dyn RequestValidator<T>>) -> Self {
+        self.request_validator = Some(request_validator);
+        self
+    }
+    // Validate the query parameters by the `RequestValidator` before sending them
+    pub fn with_query_validator(
+        mut self,
+        query_validator: Arc<dyn RequestValidator<QueryParam>>,
+    ) -> Self {
+        self.query_validator = Some(query_validator);
+        self
+    }
+
     pub async fn call(
         &self,
         path_param: Option<impl Into<PathParam>>,
@@ -477,6 +3256,51 @@ impl<T, R, Client, Req, Res, Method, Header, B>
             .await
     }
 
+    // Call the absolute `url` verbatim instead of the relative url (e.g. the signed urls)
+    pub async fn call_absolute(
+        &self,
+        url: &str,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: Default,
+        Method: Clone,
+    {
+        self.call_absolute_with_options(url, false, None, sent_body)
+            .await
+    }
+    /**
+    Call the absolute `url` verbatim instead of the relative url (e.g. the signed upload urls),
+    with the same interceptors (and the credentials unless they're skipped).
+
+    # Arguments
+
+    * `skip_auth` - Don't send the credentials (`RequestOptions::skip_auth`).
+
+    */
+    pub async fn call_absolute_with_options(
+        &self,
+        url: &str,
+        skip_auth: bool,
+        header: Option<Header>,
+        sent_body: T,
+    ) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: Default,
+        Method: Clone,
+    {
+        let mut extensions = Extensions::new();
+        extensions.insert(AbsoluteUrl {
+            url: Url::parse(url)?,
+            skip_auth,
+        });
+        with_extensions(
+            extensions,
+            self.call_with_options(header, None::<PathParam>, None::<QueryParam>, sent_body),
+        )
+        .await
+    }
+
     pub async fn call_with_options(
         &self,
         header: Option<Header>,
@@ -488,10 +3312,18 @@ impl<T, R, Client, Req, Res, Method, Header, B>
         B: Default,
         Method: Clone,
     {
+        let query_param = query_param.map(|v| v.into());
+        validate_query_param(&self.query_validator, &query_param)?;
+        if let Some(request_validator) = &self.request_validator {
+            catch_hook_panic("request_validator", || {
+                request_validator.validate(&sent_body)
+            })??;
+        }
         // let mut sent_body = Box::new(sent_body);
-        let body = self
-            .base
-            ._call_common(
+        let (response_content_type, body) = with_api_extensions(
+            &self.accept,
+            &self.versioning,
+            self.base._call_common_with_content_type(
                 self.method.clone(),
                 header,
                 self.relative_url.clone(),
@@ -501,29 +3333,343 @@ impl<T, R, Client, Req, Res, Method, Header, B>
                 } else {
                     None
                 },
-                if let Some(v) = query_param {
-                    Some(v.into())
-                } else {
-                    None
-                },
-                self.request_serializer.encode(sent_body)?,
-            )
-            .await?;
+                query_param,
+                catch_hook_panic_async(
+                    "request_serializer",
+                    self.request_serializer.encode_async(sent_body),
+                )
+                .await??,
+            ),
+        )
+        .await?;
 
+        if let Some(strict_content_type) = &self.strict_content_type {
+            check_content_type(strict_content_type, response_content_type.as_deref())?;
+        }
         // let mut target = Box::new(target);
         // let body = Box::new(body);
         // let bytes = hyper::body::to_bytes(*body).await?;
-        let result = self.base.body_to_bytes(*body).await;
-        if result.is_err() {
-            return Err(result.err().unwrap());
+        let target = catch_hook_panic_async(
+            "response_deserializer",
+            self.response_deserializer
+                .decode_response(response_content_type, self.base.body_to_stream(*body)),
+        )
+        .await??;
+
+        Ok(target)
+    }
+
+    /**
+    Call with the bodies produced by the `factory` (per attempt) instead of the serialized body,
+    so the streaming uploads could be sent again by the retries (the `BodyFactory` extension).
+
+    # Remarks
+
+    * The `request_serializer`/`request_validator` aren't used,
+      the content type is the one produced by the `factory`.
+
+    */
+    pub async fn call_with_body_factory(
+        &self,
+        header: Option<Header>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        factory: BodyFactory<B>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: 'static,
+        Method: Clone,
+    {
+        let query_param = query_param.map(|v| v.into());
+        validate_query_param(&self.query_validator, &query_param)?;
+        let (content_type, sent_body) = factory.produce().await?;
+        let mut extensions = Extensions::new();
+        extensions.insert(factory);
+        let (response_content_type, body) = with_extensions(
+            extensions,
+            with_api_extensions(
+                &self.accept,
+                &self.versioning,
+                self.base._call_common_with_content_type(
+                    self.method.clone(),
+                    header,
+                    self.relative_url.clone(),
+                    content_type,
+                    path_param.map(|v| v.into()),
+                    query_param,
+                    sent_body,
+                ),
+            ),
+        )
+        .await?;
+
+        if let Some(strict_content_type) = &self.strict_content_type {
+            check_content_type(strict_content_type, response_content_type.as_deref())?;
         }
-        let bytes = result.ok().unwrap();
-        let target = self.response_deserializer.decode(&bytes)?;
+        let target = catch_hook_panic_async(
+            "response_deserializer",
+            self.response_deserializer
+                .decode_response(response_content_type, self.base.body_to_stream(*body)),
+        )
+        .await??;
 
         Ok(target)
     }
 }
 
+// The default max items per batch of `call_bulk()`
+pub const DEFAULT_BULK_BATCH_SIZE: usize = 100;
+
+/*
+`BulkWriteOptions` The batching of the bulk writes by `APIHasBody::call_bulk()`
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct BulkWriteOptions {
+    // The max items per batch
+    pub batch_size: usize,
+    // The max batches in flight
+    pub concurrency: usize,
+    // The attempts per batch (including the first one)
+    pub max_attempts: u32,
+    // The delay before the first retry of a batch, it's doubled after each retry
+    pub retry_backoff: Duration,
+}
+impl Default for BulkWriteOptions {
+    fn default() -> Self {
+        BulkWriteOptions {
+            batch_size: DEFAULT_BULK_BATCH_SIZE,
+            concurrency: 1,
+            max_attempts: 1,
+            retry_backoff: Duration::from_millis(100),
+        }
+    }
+}
+impl BulkWriteOptions {
+    pub fn new(batch_size: usize) -> Self {
+        BulkWriteOptions {
+            batch_size,
+            ..Self::default()
+        }
+    }
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency;
+        self
+    }
+    pub fn retry(mut self, max_attempts: u32, retry_backoff: Duration) -> Self {
+        self.max_attempts = max_attempts;
+        self.retry_backoff = retry_backoff;
+        self
+    }
+}
+
+/*
+`BulkBatchResult` The result of a batch of `call_bulk()`, the `items` are the indices of its items (in the input order)
+*/
+#[derive(Debug)]
+pub struct BulkBatchResult<R> {
+    pub items: Range<usize>,
+    pub attempts: u32,
+    pub result: StdResult<Box<R>, Box<dyn StdError>>,
+}
+
+// The transport errors, the server errors & the throttling are sent again (not the other responses)
+fn is_retryable_bulk_error(e: &(dyn StdError + 'static), status: Option<u16>) -> bool {
+    match status {
+        Some(status) => status == 429 || (500..600).contains(&status),
+        None => TransportErrorKind::of(e).is_some(),
+    }
+}
+
+/*
+`BulkWriteReport` The results of the batches of `call_bulk()` (in the input order)
+*/
+#[derive(Debug)]
+pub struct BulkWriteReport<R> {
+    pub batches: Vec<BulkBatchResult<R>>,
+}
+impl<R> BulkWriteReport<R> {
+    pub fn item_count(&self) -> usize {
+        self.batches.last().map_or(0, |batch| batch.items.end)
+    }
+    pub fn is_ok(&self) -> bool {
+        self.batches.iter().all(|batch| batch.result.is_ok())
+    }
+    // The result of each item (the one of its batch), in the input order
+    pub fn item_results(&self) -> impl Iterator<Item = (usize, StdResult<&R, &dyn StdError>)> {
+        self.batches.iter().flat_map(|batch| {
+            batch.items.clone().map(move |index| {
+                let result = match &batch.result {
+                    Ok(v) => Ok(v.as_ref()),
+                    Err(e) => Err(e.as_ref()),
+                };
+                (index, result)
+            })
+        })
+    }
+    // The indices of the items of the failed batches (e.g. to be written again)
+    pub fn failed_items(&self) -> Vec<usize> {
+        self.batches
+            .iter()
+            .filter(|batch| batch.result.is_err())
+            .flat_map(|batch| batch.items.clone())
+            .collect()
+    }
+    /**
+    The responses of the batches if all of them succeeded,
+    or the `AggregateError` with the errors of the items of the failed batches (sharing the error of their batch).
+    */
+    pub fn into_result(self) -> StdResult<Vec<Box<R>>, AggregateError> {
+        let total = self.item_count();
+        let mut values = Vec::new();
+        let mut errors = Vec::new();
+        for batch in self.batches {
+            match batch.result {
+                Ok(value) => values.push(value),
+                Err(e) => {
+                    let e: Arc<dyn StdError> = Arc::from(e);
+                    errors.extend(batch.items.map(|index| ItemError::new(index, e.clone())));
+                }
+            }
+        }
+        if errors.is_empty() {
+            Ok(values)
+        } else {
+            Err(AggregateError::new(total, errors))
+        }
+    }
+}
+
+impl<I, R, Client, Req, Res, Method, Header, B>
+    APIHasBody<Vec<I>, R, Client, Req, Res, Method, Header, B>
+{
+    /**
+    Write the `items` in the batches (sent as the bodies of the API, `Vec<I>`),
+    with the results of the batches aggregated per item (`BulkWriteReport::item_results()`).
+
+    # Remarks
+
+    * The `items` are read lazily, up to `concurrency` batches are in flight.
+    * The failed batches are sent again up to `max_attempts` (after the backoff timed by the `clock` of the `SimpleHTTP`),
+      only for the transport errors and the `5xx`/`429` responses (the other failures are final),
+      so the writes should be idempotent (e.g. upserts) if they're retried.
+    * The failures of the batches don't stop the rest, check `is_ok()`/`failed_items()` of the report,
+      or get the `AggregateError` of the failed items by `into_result()`.
+
+    */
+    pub async fn call_bulk(
+        &self,
+        path_param: Option<impl Into<PathParam>>,
+        items: impl IntoIterator<Item = I>,
+        options: BulkWriteOptions,
+    ) -> BulkWriteReport<R>
+    where
+        I: Clone,
+        B: Default,
+        Method: Clone,
+    {
+        let path_param: Option<PathParam> = path_param.map(|v| v.into());
+        let clock = self
+            .base
+            .get_simple_api()
+            .lock()
+            .unwrap()
+            .get_simple_http()
+            .clock
+            .clone();
+        let batch_size = options.batch_size.max(1);
+        let max_attempts = options.max_attempts.max(1);
+
+        let mut items = items.into_iter();
+        let mut start = 0;
+        let batches = std::iter::from_fn(|| {
+            let batch: Vec<I> = items.by_ref().take(batch_size).collect();
+            if batch.is_empty() {
+                return None;
+            }
+            let range = start..start + batch.len();
+            start = range.end;
+            Some((range, batch))
+        });
+        let mut results: Vec<BulkBatchResult<R>> = stream::iter(batches)
+            .map(|(range, mut batch)| {
+                let path_param = path_param.clone();
+                let clock = clock.clone();
+                let mut backoff = options.retry_backoff;
+                async move {
+                    let mut attempts = 0;
+                    loop {
+                        attempts += 1;
+                        // Keep the items for the retries (if any)
+                        let body = if attempts < max_attempts {
+                            batch.clone()
+                        } else {
+                            std::mem::take(&mut batch)
+                        };
+                        let response_status = ResponseStatus::new();
+                        let mut extensions = Extensions::new();
+                        extensions.insert(response_status.clone());
+                        let result =
+                            with_extensions(extensions, self.call(path_param.clone(), body)).await;
+                        let retryable = match &result {
+                            Ok(_) => false,
+                            Err(e) => is_retryable_bulk_error(e.as_ref(), response_status.get()),
+                        };
+                        if !retryable || attempts >= max_attempts {
+                            return BulkBatchResult {
+                                items: range,
+                                attempts,
+                                result,
+                            };
+                        }
+                        clock.sleep(backoff).await;
+                        backoff = backoff.saturating_mul(2);
+                    }
+                }
+            })
+            .buffer_unordered(options.concurrency.max(1))
+            .collect()
+            .await;
+        results.sort_by_key(|batch| batch.items.start);
+
+        BulkWriteReport { batches: results }
+    }
+}
+
+/**
+`MultipartRequestInfo` The final metadata of the request body generated by `APIMultipart`
+(e.g. for logging, or the bookkeeping of the resumable uploads).
+
+# Remarks
+
+* `boundary` is `None` for the non-multipart bodies (e.g. the urlencoded forms of `make_api_form()`).
+* `body_size` is the size of the buffered bodies, and `None` for the streaming ones
+  (e.g. by `MultipartSerializerForStream`).
+
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartRequestInfo {
+    pub content_type: String,
+    pub boundary: Option<String>,
+    pub body_size: Option<u64>,
+}
+impl MultipartRequestInfo {
+    pub fn new(content_type: impl Into<String>, body_size: Option<u64>) -> Self {
+        let content_type = content_type.into();
+        #[cfg(feature = "multipart")]
+        let boundary = parse_multipart_boundary(&content_type).ok();
+        #[cfg(not(feature = "multipart"))]
+        let boundary = None;
+        MultipartRequestInfo {
+            content_type,
+            boundary,
+            body_size,
+        }
+    }
+}
+
+// Receive the `MultipartRequestInfo` of the requests of `APIMultipart` (before they're sent)
+pub type MultipartRequestInfoHook = Arc<dyn Fn(&MultipartRequestInfo) + Send + Sync>;
+
 // APIMultipart API with request body options
 // T: Request body Type(multipart)
 // R: Response body Type
@@ -532,12 +3678,63 @@ pub struct APIMultipart<T, R, Client, Req, Res, Method, Header, B> {
     pub method: Method,
     pub relative_url: String,
     // pub content_type: String,
-    pub request_serializer: Arc<dyn BodySerializer<T, (String, B)>>,
+    pub request_serializer: Arc<dyn AsyncBodySerializer<T, (String, B)>>,
     pub response_deserializer: Arc<dyn BodyDeserializer<R>>,
+    pub request_info_hook: Option<MultipartRequestInfoHook>,
 }
 impl<T, R, Client, Req, Res, Method, Header, B>
     APIMultipart<T, R, Client, Req, Res, Method, Header, B>
 {
+    /**
+    Convert the deserialized response into the domain type `D` by `TryFrom`,
+    conversion failures are returned as `MapIntoError`.
+    */
+    pub fn map_into<D>(self) -> APIMultipart<T, D, Client, Req, Res, Method, Header, B>
+    where
+        R: 'static,
+        D: TryFrom<R> + 'static,
+        D::Error: Into<Box<dyn StdError>>,
+    {
+        APIMultipart {
+            base: self.base,
+            method: self.method,
+            relative_url: self.relative_url,
+            request_serializer: self.request_serializer,
+            response_deserializer: Arc::new(TryFromDeserializer {
+                inner: self.response_deserializer,
+            }),
+            request_info_hook: self.request_info_hook,
+        }
+    }
+
+    /**
+    Receive the generated content type/boundary (and the size of the buffered body)
+    of each call before it's sent.
+
+    # Remarks
+
+    * For `call_with_body_factory()`, it's the body of the first attempt
+      (the boundaries of the retried ones are generated by the factory).
+
+    */
+    pub fn with_request_info_hook(
+        mut self,
+        hook: impl Fn(&MultipartRequestInfo) + Send + Sync + 'static,
+    ) -> Self {
+        self.request_info_hook = Some(Arc::new(hook));
+        self
+    }
+
+    fn report_request_info(&self, content_type: &str, body: &B) -> StdResult<(), HookPanicked> {
+        match &self.request_info_hook {
+            Some(hook) => {
+                let info = MultipartRequestInfo::new(content_type, self.base.body_size(body));
+                catch_hook_panic("request_info_hook", || hook(&info))
+            }
+            None => Ok(()),
+        }
+    }
+
     pub async fn call(
         &self,
         path_param: Option<impl Into<PathParam>>,
@@ -564,11 +3761,16 @@ impl<T, R, Client, Req, Res, Method, Header, B>
     {
         // let mut sent_body = Box::new(sent_body);
         println!("request_serializer encode before");
-        let (content_type_with_boundary, sent_body) = self.request_serializer.encode(sent_body)?;
+        let (content_type_with_boundary, sent_body) = catch_hook_panic_async(
+            "request_serializer",
+            self.request_serializer.serialize(sent_body),
+        )
+        .await??;
         println!("request_serializer encode after");
-        let body = self
+        self.report_request_info(&content_type_with_boundary, &sent_body)?;
+        let (response_content_type, body) = self
             .base
-            ._call_common(
+            ._call_common_with_content_type(
                 self.method.clone(),
                 header,
                 self.relative_url.clone(),
@@ -592,17 +3794,57 @@ impl<T, R, Client, Req, Res, Method, Header, B>
         // let mut target = Box::new(target);
         // let body = Box::new(body);
         // let bytes = hyper::body::to_bytes(*body).await?;
-        let result = self.base.body_to_bytes(*body).await;
-        if result.is_err() {
-            return Err(result.err().unwrap());
-        }
-        let bytes = result.ok().unwrap();
-        let target = self.response_deserializer.decode(&bytes)?;
+        let target = catch_hook_panic_async(
+            "response_deserializer",
+            self.response_deserializer
+                .decode_response(response_content_type, self.base.body_to_stream(*body)),
+        )
+        .await??;
 
         println!("response_deserializer.decode after");
 
         Ok(target)
     }
+
+    // Call with the bodies produced by the `factory` (per attempt, e.g. by `MultipartSerializerForStream::body_factory()`)
+    pub async fn call_with_body_factory(
+        &self,
+        header: Option<Header>,
+        path_param: Option<impl Into<PathParam>>,
+        query_param: Option<impl Into<QueryParam>>,
+        factory: BodyFactory<B>,
+    ) -> StdResult<Box<R>, Box<dyn StdError>>
+    where
+        B: 'static,
+        Method: Clone,
+    {
+        let (content_type_with_boundary, sent_body) = factory.produce().await?;
+        self.report_request_info(&content_type_with_boundary, &sent_body)?;
+        let mut extensions = Extensions::new();
+        extensions.insert(factory);
+        let (response_content_type, body) = with_extensions(
+            extensions,
+            self.base._call_common_with_content_type(
+                self.method.clone(),
+                header,
+                self.relative_url.clone(),
+                content_type_with_boundary,
+                path_param.map(|v| v.into()),
+                query_param.map(|v| v.into()),
+                sent_body,
+            ),
+        )
+        .await?;
+
+        let target = catch_hook_panic_async(
+            "response_deserializer",
+            self.response_deserializer
+                .decode_response(response_content_type, self.base.body_to_stream(*body)),
+        )
+        .await??;
+
+        Ok(target)
+    }
 }
 
 trait Outputting: Sized {
@@ -621,7 +3863,22 @@ impl<T: Future> Outputting for T {}
 pub struct SimpleAPI<Client, Req, Res, Method, Header, B> {
     pub simple_http: SimpleHTTP<Client, Req, Res, Method, Header, B>,
     pub base_url: Url,
-    pub default_header: Option<Header>,
+    pub url_join_strategy: UrlJoinStrategy,
+    pub url_normalization: UrlNormalization,
+    pub versioning: Versioning,
+    pub template_variables: TemplateVariables,
+    pub default_header: Option<Headers>,
+    pub offline: OfflineSwitch,
+    pub drain_gate: DrainGate,
+    pub usage_quota: Option<Arc<UsageQuota>>,
+    pub maintenance_policy: Option<Arc<MaintenancePolicy>>,
+    pub read_only: Option<ReadOnlyGuard>,
+    pub keep_alive: Option<KeepAliveWorker>,
+    pub status_tracker: StatusTracker,
+    #[cfg(feature = "queue")]
+    pub offline_queue: Option<Arc<dyn QueueStore>>,
+    #[cfg(feature = "for_serde")]
+    pub codec_registry: Arc<CodecRegistry>,
 }
 
 impl<Client, Req, Res, Method, Header: Default, B> SimpleAPI<Client, Req, Res, Method, Header, B> {
@@ -632,7 +3889,22 @@ impl<Client, Req, Res, Method, Header: Default, B> SimpleAPI<Client, Req, Res, M
         SimpleAPI {
             simple_http,
             base_url,
+            url_join_strategy: UrlJoinStrategy::default(),
+            url_normalization: UrlNormalization::default(),
+            versioning: Versioning::default(),
+            template_variables: TemplateVariables::default(),
             default_header: None,
+            offline: OfflineSwitch::default(),
+            drain_gate: DrainGate::default(),
+            usage_quota: None,
+            maintenance_policy: None,
+            read_only: None,
+            keep_alive: None,
+            status_tracker: StatusTracker::default(),
+            #[cfg(feature = "queue")]
+            offline_queue: None,
+            #[cfg(feature = "for_serde")]
+            codec_registry: Arc::new(CodecRegistry::default()),
         }
     }
 }
@@ -2,18 +2,37 @@
 In this module there're implementations & tests of `SimpleHTTP`.
 */
 
-use std::collections::{HashMap, VecDeque};
+use std::cell::OnceCell;
+#[cfg(feature = "multipart")]
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::error::Error as StdError;
+#[cfg(feature = "multipart")]
+use std::fs::{self, File};
 use std::future::Future;
+#[cfg(feature = "multipart")]
+use std::io::Write;
+#[cfg(feature = "multipart")]
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::result::Result as StdResult;
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
 
+#[cfg(feature = "multipart")]
 use bytes::Bytes;
 
-#[cfg(feature = "multipart")]
+use super::audit::AuditLog;
 pub use super::common::generate_id;
+use super::common::{
+    add_header_authentication_bearer, catch_hook_panic, system_clock, AdaptiveConcurrencyOptions,
+    AdaptiveLimiter, AuthProvider, Clock, ClockSkew, CredentialRotation, DelegatedIdentity,
+    DelegationHeaders, DeprecationInfo, DeprecationMonitor, DeprecationNotice, ExchangedToken,
+    HasRequestOptions, Headers, KeepAliveProbe, Locale, LocaleHeaders, MetricsSink, Priority,
+    PriorityLimiter, PriorityPermit, ReplayNonceHeaders, RequestHeaders, RequestOptions,
+    RequestSummary, ResilienceEvent,
+};
+use super::scrub::{RequestFormatter, RequestLogHook};
 #[cfg(feature = "multipart")]
 use formdata::FormData;
 #[cfg(feature = "multipart")]
@@ -22,7 +41,13 @@ use mime::MULTIPART_FORM_DATA;
 use multer;
 #[cfg(feature = "multipart")]
 use multer::Multipart;
+// The limits of the parsed multipart bodies (e.g. by `body_to_multipart_with_constraints()`)
+#[cfg(feature = "multipart")]
+pub use multer::{Constraints, SizeLimit};
 
+// The timeout of the calls if it's not set (zero)
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+#[deprecated(note = "Use `DEFAULT_TIMEOUT` instead")]
 pub const DEFAULT_TIMEOUT_MILLISECOND: u64 = 30 * 1000;
 
 /**
@@ -41,6 +66,10 @@ You could implement your own versions of interceptors
 pub trait Interceptor<R> {
     fn get_id(&self) -> String;
     fn intercept(&self, request: &mut R) -> StdResult<(), Box<dyn StdError>>;
+    // The named slot & the ordering constraints (`None` for the unnamed ones)
+    fn get_slot(&self) -> Option<InterceptorSlot> {
+        None
+    }
 }
 
 /**
@@ -84,59 +113,728 @@ impl<R> InterceptorFunc<R> {
         generate_id()
     }
 }
+impl<R: RequestHeaders> InterceptorFunc<R> {
+    /**
+    Generate a new `InterceptorFunc` intercepting the headers of Requests,
+    it could be shared by all bindings.
+
+    # Arguments
+
+    * `func` - The given `FnMut` for the headers.
+
+    */
+    pub fn new_for_headers<T>(mut func: T) -> InterceptorFunc<R>
+    where
+        T: FnMut(&mut Headers) -> StdResult<(), Box<dyn StdError>> + Send + Sync + 'static,
+    {
+        Self::new(move |request: &mut R| {
+            let mut headers = request.get_headers();
+            func(&mut headers)?;
+            request.set_headers(headers)
+        })
+    }
+
+    /**
+    Generate a new `InterceptorFunc` setting the header to a new id (by `generate_id()`)
+    if the request doesn't have it, e.g. `X-Request-Id` or `Idempotency-Key`.
+
+    # Remarks
+
+    * The id is kept by the retries of the wrapped clients (e.g. `RetryClient`),
+      since they're after the interceptors.
+
+    */
+    pub fn new_for_id_header(name: impl Into<String>) -> InterceptorFunc<R> {
+        let name = name.into();
+        Self::new_for_headers(move |headers| {
+            if !headers.contains_key(&name) {
+                headers.insert(name.clone(), generate_id());
+            }
+            Ok(())
+        })
+    }
+
+    /**
+    Generate a new `InterceptorFunc` setting the header to the next credential of the `CredentialRotation`.
+
+    # Remarks
+
+    * The responses are not reported back to the `rotation` by the interceptor
+      (`CredentialRotation::report()` could be called by the callers),
+      `CredentialRotationClient` does it for hyper.
+
+    */
+    pub fn new_for_credential_rotation(rotation: Arc<CredentialRotation>) -> InterceptorFunc<R> {
+        Self::new_for_headers(move |headers| {
+            rotation.apply(headers);
+            Ok(())
+        })
+    }
+}
+impl<R: RequestHeaders + HasRequestOptions> InterceptorFunc<R> {
+    /**
+    Generate a new `InterceptorFunc` setting the credentials of the `AuthProvider`,
+    unless it's skipped by `RequestOptions::skip_auth`.
+
+    # Remarks
+
+    * The responses are not reported back to the `provider` (`AuthProvider::on_response()`),
+      `Session` does it for hyper.
+
+    */
+    pub fn new_for_auth_provider(provider: Arc<dyn AuthProvider>) -> InterceptorFunc<R> {
+        Self::new(move |request: &mut R| {
+            if request.get_request_options().skip_auth {
+                return Ok(());
+            }
+            let mut headers = request.get_headers();
+            provider.apply(&mut headers)?;
+            request.set_headers(headers)
+        })
+    }
+
+    /**
+    Generate a new `InterceptorFunc` propagating the `DelegatedIdentity` of the request
+    (in the `RequestOptions.extensions`, e.g. scoped by `with_extensions()`) as the headers.
+
+    # Remarks
+
+    * The requests without the `DelegatedIdentity` are not changed.
+    * The `ExchangedToken` (if any) is sent as `Authorization: Bearer <token>` (marked as sensitive),
+      unless it's skipped by `RequestOptions::skip_auth`.
+    * The illegal values (e.g. with line breaks) fail the requests by `InvalidHeaderError`.
+
+    */
+    pub fn new_for_delegation(headers: DelegationHeaders) -> InterceptorFunc<R> {
+        Self::new(move |request: &mut R| {
+            let options = request.get_request_options();
+            let identity = match options.extensions.get::<DelegatedIdentity>() {
+                Some(identity) => identity,
+                None => return Ok(()),
+            };
+            let mut request_headers = request.get_headers();
+            headers.apply(identity, &mut request_headers)?;
+            let token = options.extensions.get::<ExchangedToken>();
+            if let (Some(token), false) = (token, options.skip_auth) {
+                request_headers =
+                    add_header_authentication_bearer(request_headers, token.0.clone())?;
+            }
+            request.set_headers(request_headers)
+        })
+    }
+
+    /**
+    Generate a new `InterceptorFunc` sending the `Locale` of the request
+    (in the `RequestOptions.extensions`, e.g. scoped by `localized()`) or the `default_locale`
+    as `Accept-Language` and the unit/timezone headers.
+
+    # Remarks
+
+    * The `Locale` of the request replaces the existing headers,
+      the `default_locale` is skipped if there's `Accept-Language` already (e.g. set by the call).
+    * The illegal values (e.g. with line breaks) fail the requests by `InvalidHeaderError`.
+
+    */
+    pub fn new_for_locale(
+        default_locale: Option<Locale>,
+        headers: LocaleHeaders,
+    ) -> InterceptorFunc<R> {
+        Self::new(move |request: &mut R| {
+            let options = request.get_request_options();
+            let mut request_headers = request.get_headers();
+            let locale = match (options.extensions.get::<Locale>(), &default_locale) {
+                (Some(locale), _) => locale,
+                (None, Some(locale)) if !request_headers.contains_key("Accept-Language") => locale,
+                _ => return Ok(()),
+            };
+            headers.apply(locale, &mut request_headers)?;
+            request.set_headers(request_headers)
+        })
+    }
+
+    /**
+    Generate a new `InterceptorFunc` sending a new nonce & the timestamp (of the `clock`) of each request
+    as the headers, for the APIs requiring the anti-replay parameters.
+
+    # Remarks
+
+    * The nonces are generated by `generate_id()`, the ones set already (e.g. by the call) are kept.
+    * The `ReplayNonce` is inserted into the `RequestOptions.extensions` of the request,
+      so the signing interceptors (e.g. HMAC) added after it could cover the nonce in the signatures.
+    * The `clock` could be the `SkewCorrectedClock`, for the servers rejecting the skewed timestamps.
+    * The retries of the wrapped clients (e.g. `RetryClient`) send the same nonce,
+      since they're after the interceptors.
+
+    */
+    pub fn new_for_replay_nonce(
+        headers: ReplayNonceHeaders,
+        clock: Arc<dyn Clock>,
+    ) -> InterceptorFunc<R> {
+        Self::new(move |request: &mut R| {
+            let mut request_headers = request.get_headers();
+            let nonce = headers.apply(clock.system_now(), &mut request_headers)?;
+            request.set_headers(request_headers)?;
+            let mut options = request.get_request_options();
+            options.extensions.insert(nonce);
+            request.set_request_options(options);
+            Ok(())
+        })
+    }
+}
+impl<R: RequestSummary> InterceptorFunc<R> {
+    /**
+    Generate a new `InterceptorFunc` logging the requests by the given `RequestFormatter` to the `log_hook`,
+    with the sensitive headers & the query values masked and the bodies truncated.
+    */
+    pub fn new_for_logging(
+        formatter: RequestFormatter,
+        log_hook: RequestLogHook,
+    ) -> InterceptorFunc<R> {
+        Self::new(move |request: &mut R| {
+            log_hook(&formatter.format(request));
+            Ok(())
+        })
+    }
+}
 impl<R> Interceptor<R> for InterceptorFunc<R> {
     fn get_id(&self) -> String {
         return self.id.clone();
     }
     fn intercept(&self, request: &mut R) -> StdResult<(), Box<dyn StdError>> {
-        let func = &mut *self.func.lock().unwrap();
+        // The lock is recovered if the func panicked (isolated as `HookPanicked` by the callers)
+        let func = &mut *self.func.lock().unwrap_or_else(PoisonError::into_inner);
         (func)(request)
     }
 }
 
+/**
+`InterceptorSlot` The name of an interceptor & its ordering constraints relative to the other named ones,
+e.g. `InterceptorSlot::new("auth").after("cache").before("logging")`.
+
+# Remarks
+
+* The name is the id of the interceptor (e.g. for `delete_interceptor_by_id()`),
+  and the interceptor of the same name is replaced.
+* The constraints of the names not registered (yet) are ignored until they're registered,
+  so the order is the same whatever order the slots are added in.
+* The interceptors are kept in the order they're added unless the constraints require otherwise,
+  the unnamed ones aren't moved relative to each other.
+
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterceptorSlot {
+    pub name: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+impl InterceptorSlot {
+    pub fn new(name: impl Into<String>) -> Self {
+        InterceptorSlot {
+            name: name.into(),
+            before: Vec::new(),
+            after: Vec::new(),
+        }
+    }
+    // Applied before the named interceptor
+    pub fn before(mut self, name: impl Into<String>) -> Self {
+        self.before.push(name.into());
+        self
+    }
+    // Applied after the named interceptor
+    pub fn after(mut self, name: impl Into<String>) -> Self {
+        self.after.push(name.into());
+        self
+    }
+}
+
+// The interceptor registered in the slot (the name is its id)
+struct SlottedInterceptor<R> {
+    slot: InterceptorSlot,
+    inner: Arc<dyn Interceptor<R>>,
+}
+impl<R> Interceptor<R> for SlottedInterceptor<R> {
+    fn get_id(&self) -> String {
+        self.slot.name.clone()
+    }
+    fn intercept(&self, request: &mut R) -> StdResult<(), Box<dyn StdError>> {
+        self.inner.intercept(request)
+    }
+    fn get_slot(&self) -> Option<InterceptorSlot> {
+        Some(self.slot.clone())
+    }
+}
+
+/*
+`InterceptorOrderError` The ordering constraints of the interceptor slots are contradictory (a cycle)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterceptorOrderError {
+    details: String,
+}
+impl StdError for InterceptorOrderError {}
+impl InterceptorOrderError {
+    pub fn new(msg: impl Into<String>) -> InterceptorOrderError {
+        InterceptorOrderError {
+            details: msg.into(),
+        }
+    }
+}
+impl std::fmt::Display for InterceptorOrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "InterceptorOrderError: {}", self.details)
+    }
+}
+
+// Sort the interceptors by the constraints of the slots (stable, the earliest ready one first)
+fn order_interceptors<R>(
+    interceptors: VecDeque<Arc<dyn Interceptor<R>>>,
+) -> StdResult<VecDeque<Arc<dyn Interceptor<R>>>, InterceptorOrderError> {
+    let ids: Vec<String> = interceptors.iter().map(|v| v.get_id()).collect();
+    let index_of = |name: &String| ids.iter().position(|id| id == name);
+
+    // The interceptors required to be applied before each one
+    let mut predecessors = vec![Vec::new(); interceptors.len()];
+    for (index, interceptor) in interceptors.iter().enumerate() {
+        if let Some(slot) = interceptor.get_slot() {
+            for other in slot.before.iter().filter_map(index_of) {
+                predecessors[other].push(index);
+            }
+            for other in slot.after.iter().filter_map(index_of) {
+                predecessors[index].push(other);
+            }
+        }
+    }
+
+    let mut ordered = Vec::with_capacity(interceptors.len());
+    let mut placed = vec![false; interceptors.len()];
+    while ordered.len() < interceptors.len() {
+        let ready = (0..interceptors.len())
+            .find(|&index| !placed[index] && predecessors[index].iter().all(|&v| placed[v]));
+        match ready {
+            Some(index) => {
+                placed[index] = true;
+                ordered.push(index);
+            }
+            None => {
+                let names: Vec<&str> = (0..interceptors.len())
+                    .filter(|&index| !placed[index])
+                    .map(|index| ids[index].as_str())
+                    .collect();
+                return Err(InterceptorOrderError::new(format!(
+                    "the ordering constraints of {:?} are contradictory",
+                    names
+                )));
+            }
+        }
+    }
+
+    Ok(ordered
+        .into_iter()
+        .map(|index| interceptors[index].clone())
+        .collect())
+}
+
 pub type SimpleHTTPResponse<R> = StdResult<R, Box<dyn StdError>>;
 
 pub trait BaseClient<Client, Req, Res, Method, Header, B> {
     fn request(&self, req: Req) -> Pin<Box<dyn Future<Output = Res>>>;
     fn get_client(&mut self) -> &mut Client;
+
+    // Close the idle pooled connections (it does nothing if it's not supported by the client)
+    fn close_idle(&mut self) {}
+    // Initialize the lazy parts (clients/thread pools) ahead of the first request
+    fn warm(&mut self) {}
+    // The keep-alive probe sharing the pooled connections (`None` if it's not supported by the client)
+    fn keep_alive_probe(&mut self) -> Option<KeepAliveProbe> {
+        None
+    }
+}
+
+// The client shared by the `SimpleHTTP` (and its copies)
+pub type SharedClient<Client, Req, Res, Method, Header, B> =
+    Arc<Mutex<dyn BaseClient<Client, Req, Res, Method, Header, B>>>;
+
+// The client wrapped by `LazyClient` (`Send`, so the `SimpleHTTP` sharing it could be moved across threads)
+pub type BoxedClient<Client, Req, Res, Method, Header, B> =
+    Box<dyn BaseClient<Client, Req, Res, Method, Header, B> + Send>;
+// The factory of the client of `LazyClient`
+pub type ClientFactory<Client, Req, Res, Method, Header, B> =
+    Box<dyn Fn() -> BoxedClient<Client, Req, Res, Method, Header, B> + Send>;
+
+/**
+`LazyClient` constructs the wrapped client on first use (or by `warm()`),
+so that building services costs nothing at startup (e.g. the cold start of serverless functions).
+*/
+pub struct LazyClient<Client, Req, Res, Method, Header, B> {
+    factory: ClientFactory<Client, Req, Res, Method, Header, B>,
+    inner: OnceCell<BoxedClient<Client, Req, Res, Method, Header, B>>,
+}
+impl<Client, Req, Res, Method, Header, B> LazyClient<Client, Req, Res, Method, Header, B> {
+    pub fn new(
+        factory: impl Fn() -> BoxedClient<Client, Req, Res, Method, Header, B> + Send + 'static,
+    ) -> Self {
+        LazyClient {
+            factory: Box::new(factory),
+            inner: OnceCell::new(),
+        }
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.inner.get().is_some()
+    }
+
+    fn get_inner(&self) -> &dyn BaseClient<Client, Req, Res, Method, Header, B> {
+        self.inner.get_or_init(|| (self.factory)()).as_ref()
+    }
+    fn get_inner_mut(&mut self) -> &mut BoxedClient<Client, Req, Res, Method, Header, B> {
+        self.get_inner();
+        self.inner.get_mut().unwrap()
+    }
+}
+impl<Client, Req, Res, Method, Header, B> BaseClient<Client, Req, Res, Method, Header, B>
+    for LazyClient<Client, Req, Res, Method, Header, B>
+{
+    fn request(&self, req: Req) -> Pin<Box<dyn Future<Output = Res>>> {
+        self.get_inner().request(req)
+    }
+    fn get_client(&mut self) -> &mut Client {
+        self.get_inner_mut().get_client()
+    }
+    fn close_idle(&mut self) {
+        // Nothing to close before the first use
+        if let Some(inner) = self.inner.get_mut() {
+            inner.close_idle();
+        }
+    }
+    fn warm(&mut self) {
+        self.get_inner_mut().warm();
+    }
+    fn keep_alive_probe(&mut self) -> Option<KeepAliveProbe> {
+        self.get_inner_mut().keep_alive_probe()
+    }
+}
+
+/**
+`PriorityClient` schedules the requests of the wrapped client by `PriorityLimiter`,
+the priority of each request is read from its `RequestOptions` (`HasRequestOptions`).
+
+# Remarks
+
+* The wrapped client should start the request lazily (when the returned future is polled),
+  the bindings' clients do.
+* The permit is held until the response (head) is received.
+* The waits for the permits are reported to `metrics_sink` (`ResilienceEvent::LimiterWait`).
+
+*/
+pub struct PriorityClient<Client, Req, Res, Method, Header, B> {
+    pub inner: Box<dyn BaseClient<Client, Req, Res, Method, Header, B>>,
+    pub limiter: Arc<PriorityLimiter>,
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+}
+impl<Client, Req, Res, Method, Header, B> PriorityClient<Client, Req, Res, Method, Header, B> {
+    pub fn new(
+        inner: Box<dyn BaseClient<Client, Req, Res, Method, Header, B>>,
+        max_concurrency: usize,
+    ) -> Self {
+        Self::new_with_limiter(inner, Arc::new(PriorityLimiter::new(max_concurrency)))
+    }
+    // Share the limiter among clients (e.g. for a global concurrency limit)
+    pub fn new_with_limiter(
+        inner: Box<dyn BaseClient<Client, Req, Res, Method, Header, B>>,
+        limiter: Arc<PriorityLimiter>,
+    ) -> Self {
+        PriorityClient {
+            inner,
+            limiter,
+            metrics_sink: None,
+        }
+    }
+}
+impl<Client, Req, Res, Method, Header, B> BaseClient<Client, Req, Res, Method, Header, B>
+    for PriorityClient<Client, Req, Res, Method, Header, B>
+where
+    Req: HasRequestOptions + RequestSummary,
+    Res: 'static,
+{
+    fn request(&self, req: Req) -> Pin<Box<dyn Future<Output = Res>>> {
+        let priority = req.get_request_options().priority;
+        let limiter = self.limiter.clone();
+        let wait = LimiterWait::new("priority", &req, self.metrics_sink.clone());
+        let future = self.inner.request(req);
+        Box::pin(async move {
+            let _permit = wait
+                .acquire(&limiter, priority, system_clock().as_ref())
+                .await;
+            future.await
+        })
+    }
+    fn get_client(&mut self) -> &mut Client {
+        self.inner.get_client()
+    }
+    fn close_idle(&mut self) {
+        self.inner.close_idle();
+    }
+    fn warm(&mut self) {
+        self.inner.warm();
+    }
+    fn keep_alive_probe(&mut self) -> Option<KeepAliveProbe> {
+        self.inner.keep_alive_probe()
+    }
+}
+
+/**
+`AdaptiveClient` limits the concurrency of the wrapped client by `AdaptiveLimiter`,
+the limit is adjusted by the observed latency of the requests.
+
+# Remarks
+
+* The priority of each request is read from its `RequestOptions` (`HasRequestOptions`).
+* The latency is measured until the response (head) is received,
+  and the requests dropped before that (e.g. timed out by `SimpleHTTP`) are regarded as overloaded.
+* The waits for the permits are reported to `metrics_sink` (`ResilienceEvent::LimiterWait`).
+
+*/
+pub struct AdaptiveClient<Client, Req, Res, Method, Header, B> {
+    pub inner: Box<dyn BaseClient<Client, Req, Res, Method, Header, B>>,
+    pub limiter: Arc<AdaptiveLimiter>,
+    pub metrics_sink: Option<Arc<dyn MetricsSink>>,
+}
+impl<Client, Req, Res, Method, Header, B> AdaptiveClient<Client, Req, Res, Method, Header, B> {
+    pub fn new(
+        inner: Box<dyn BaseClient<Client, Req, Res, Method, Header, B>>,
+        options: AdaptiveConcurrencyOptions,
+    ) -> Self {
+        Self::new_with_limiter(inner, Arc::new(AdaptiveLimiter::new(options)))
+    }
+    // Share the limiter among clients (e.g. calling the same upstream)
+    pub fn new_with_limiter(
+        inner: Box<dyn BaseClient<Client, Req, Res, Method, Header, B>>,
+        limiter: Arc<AdaptiveLimiter>,
+    ) -> Self {
+        AdaptiveClient {
+            inner,
+            limiter,
+            metrics_sink: None,
+        }
+    }
+}
+impl<Client, Req, Res, Method, Header, B> BaseClient<Client, Req, Res, Method, Header, B>
+    for AdaptiveClient<Client, Req, Res, Method, Header, B>
+where
+    Req: HasRequestOptions + RequestSummary,
+    Res: 'static,
+{
+    fn request(&self, req: Req) -> Pin<Box<dyn Future<Output = Res>>> {
+        let priority = req.get_request_options().priority;
+        let limiter = self.limiter.clone();
+        let wait = LimiterWait::new("adaptive", &req, self.metrics_sink.clone());
+        let future = self.inner.request(req);
+        Box::pin(async move {
+            let _permit = wait
+                .acquire(&limiter.limiter, priority, limiter.clock.as_ref())
+                .await;
+            let mut sample = AdaptiveSample {
+                limiter: limiter.clone(),
+                start: limiter.clock.now(),
+                completed: false,
+            };
+            let res = future.await;
+            sample.completed = true;
+            res
+        })
+    }
+    fn get_client(&mut self) -> &mut Client {
+        self.inner.get_client()
+    }
+    fn close_idle(&mut self) {
+        self.inner.close_idle();
+    }
+    fn warm(&mut self) {
+        self.inner.warm();
+    }
+    fn keep_alive_probe(&mut self) -> Option<KeepAliveProbe> {
+        self.inner.keep_alive_probe()
+    }
+}
+
+// LimiterWait Acquire the permit, and report the wait (if the request is queued) to the sink
+struct LimiterWait {
+    limiter: &'static str,
+    // (sink, method, url), `None` without the sink
+    target: Option<(Arc<dyn MetricsSink>, String, String)>,
+}
+impl LimiterWait {
+    fn new(
+        limiter: &'static str,
+        req: &impl RequestSummary,
+        metrics_sink: Option<Arc<dyn MetricsSink>>,
+    ) -> Self {
+        LimiterWait {
+            limiter,
+            target: metrics_sink.map(|sink| (sink, req.get_method(), req.get_url())),
+        }
+    }
+    async fn acquire(
+        self,
+        limiter: &Arc<PriorityLimiter>,
+        priority: Priority,
+        clock: &dyn Clock,
+    ) -> PriorityPermit {
+        let (sink, method, url) = match self.target {
+            Some(target) => target,
+            None => return limiter.acquire(priority).await,
+        };
+        if let Some(permit) = limiter.try_acquire() {
+            return permit;
+        }
+        let start = clock.now();
+        let permit = limiter.acquire(priority).await;
+        let event = ResilienceEvent::LimiterWait {
+            limiter: self.limiter,
+            method,
+            url,
+            waited: clock.now().duration_since(start),
+        };
+        let _ = catch_hook_panic("metrics_sink", || sink.record_event(&event));
+        permit
+    }
+}
+
+// AdaptiveSample Report the latency to the limiter when it's dropped (before the permit)
+struct AdaptiveSample {
+    limiter: Arc<AdaptiveLimiter>,
+    start: Instant,
+    completed: bool,
+}
+impl Drop for AdaptiveSample {
+    fn drop(&mut self) {
+        let latency = self.limiter.clock.now().duration_since(self.start);
+        self.limiter.on_sample(latency, !self.completed);
+    }
 }
 
 /* SimpleHTTP SimpleHTTP inspired by Retrofits
 */
 pub struct SimpleHTTP<Client, Req, Res, Method, Header, B> {
-    pub client: Arc<Mutex<dyn BaseClient<Client, Req, Res, Method, Header, B>>>,
+    pub client: SharedClient<Client, Req, Res, Method, Header, B>,
     pub interceptors: VecDeque<Arc<dyn Interceptor<Req>>>,
-    pub timeout_millisecond: u64,
+    // The timeout of the calls (zero for `DEFAULT_TIMEOUT`)
+    pub timeout: Duration,
+    pub audit_log: Option<Arc<AuditLog>>,
+    // The time source of the timeouts
+    pub clock: Arc<dyn Clock>,
+    // Estimate the clock skew by the `Date` of the responses
+    pub clock_skew: Option<ClockSkew>,
+    // Observe the deprecation signals of the responses
+    pub deprecation_monitor: Option<DeprecationMonitor>,
+}
+
+// The clones share the client, the interceptors and the audit log
+impl<Client, Req, Res, Method, Header, B> Clone
+    for SimpleHTTP<Client, Req, Res, Method, Header, B>
+{
+    fn clone(&self) -> Self {
+        SimpleHTTP {
+            client: self.client.clone(),
+            interceptors: self.interceptors.clone(),
+            timeout: self.timeout,
+            audit_log: self.audit_log.clone(),
+            clock: self.clock.clone(),
+            clock_skew: self.clock_skew.clone(),
+            deprecation_monitor: self.deprecation_monitor.clone(),
+        }
+    }
 }
 
 impl<Client, Req, Res, Method, Header, B> SimpleHTTP<Client, Req, Res, Method, Header, B> {
-    pub fn new_with_options(
-        client: Arc<Mutex<dyn BaseClient<Client, Req, Res, Method, Header, B>>>,
+    pub fn new_with_timeout(
+        client: SharedClient<Client, Req, Res, Method, Header, B>,
         interceptors: VecDeque<Arc<dyn Interceptor<Req>>>,
-        timeout_millisecond: u64,
+        timeout: Duration,
     ) -> Self {
         SimpleHTTP {
             client,
             interceptors,
-            timeout_millisecond,
+            timeout,
+            audit_log: None,
+            clock: system_clock(),
+            clock_skew: None,
+            deprecation_monitor: None,
         }
     }
+    #[deprecated(note = "Use `new_with_timeout()` instead")]
+    pub fn new_with_options(
+        client: SharedClient<Client, Req, Res, Method, Header, B>,
+        interceptors: VecDeque<Arc<dyn Interceptor<Req>>>,
+        timeout_millisecond: u64,
+    ) -> Self {
+        Self::new_with_timeout(
+            client,
+            interceptors,
+            Duration::from_millis(timeout_millisecond),
+        )
+    }
 
-    pub fn get_timeout_duration(&self) -> Duration {
-        Duration::from_millis(if self.timeout_millisecond > 0 {
-            self.timeout_millisecond
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+    // The timeout applied to the calls (`DEFAULT_TIMEOUT` if it's not set)
+    pub fn get_timeout(&self) -> Duration {
+        if self.timeout.is_zero() {
+            DEFAULT_TIMEOUT
         } else {
-            DEFAULT_TIMEOUT_MILLISECOND
-        })
+            self.timeout
+        }
+    }
+    #[deprecated(note = "Use `get_timeout()` instead")]
+    pub fn get_timeout_duration(&self) -> Duration {
+        self.get_timeout()
     }
 
-    pub fn set_client(
-        &mut self,
-        client: Arc<Mutex<dyn BaseClient<Client, Req, Res, Method, Header, B>>>,
-    ) {
+    pub fn close_idle(&self) {
+        self.client.lock().unwrap().close_idle();
+    }
+    pub fn warm(&self) {
+        self.client.lock().unwrap().warm();
+    }
+    pub fn keep_alive_probe(&self) -> Option<KeepAliveProbe> {
+        self.client.lock().unwrap().keep_alive_probe()
+    }
+
+    pub fn set_client(&mut self, client: SharedClient<Client, Req, Res, Method, Header, B>) {
         self.client = client;
     }
 
+    // Record the outbound calls (after the interceptors) by the audit log
+    pub fn set_audit_log(&mut self, audit_log: Option<Arc<AuditLog>>) {
+        self.audit_log = audit_log;
+    }
+
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+    // Estimate the clock skew by the `Date` of the responses (`ClockSkew`)
+    pub fn set_clock_skew(&mut self, clock_skew: Option<ClockSkew>) {
+        self.clock_skew = clock_skew;
+    }
+    // Observe the `Date` of the response (if there's the `ClockSkew`), the call is sent at `sent_at`
+    pub fn observe_date(&self, url: &str, date: Option<&str>, sent_at: SystemTime) {
+        if let (Some(clock_skew), Some(date)) = (&self.clock_skew, date) {
+            clock_skew.observe(url, date, sent_at, self.clock.system_now());
+        }
+    }
+    // Observe the deprecation signals of the responses (`DeprecationMonitor`)
+    pub fn set_deprecation_monitor(&mut self, deprecation_monitor: Option<DeprecationMonitor>) {
+        self.deprecation_monitor = deprecation_monitor;
+    }
+    // Observe the `DeprecationNotice` of the response (`None` if it's not deprecated) of the request of the `options`
+    pub fn observe_deprecation(&self, options: &RequestOptions, notice: Option<DeprecationNotice>) {
+        if let (Some(deprecation_monitor), Some(notice)) = (&self.deprecation_monitor, &notice) {
+            deprecation_monitor.observe(notice);
+        }
+        DeprecationInfo::record(options, notice);
+    }
+
     pub fn add_interceptor(&mut self, interceptor: Arc<dyn Interceptor<Req>>) {
         self.interceptors.push_back(interceptor);
     }
@@ -144,25 +842,54 @@ impl<Client, Req, Res, Method, Header, B> SimpleHTTP<Client, Req, Res, Method, H
         self.interceptors.push_front(interceptor);
     }
     pub fn delete_interceptor(&mut self, interceptor: Arc<dyn Interceptor<Req>>) {
-        let id;
-        {
-            id = interceptor.get_id();
-        }
-
-        for (index, obs) in self.interceptors.clone().iter().enumerate() {
-            if obs.get_id() == id {
-                // println!("delete_interceptor({});", interceptor);
+        self.delete_interceptor_by_id(&interceptor.get_id());
+    }
+    // Delete the interceptor of the id (from `list_interceptors()`), `false` if there's none
+    pub fn delete_interceptor_by_id(&mut self, id: &str) -> bool {
+        match self.interceptors.iter().position(|v| v.get_id() == id) {
+            Some(index) => {
                 self.interceptors.remove(index);
-                return;
+                true
             }
+            None => false,
         }
     }
+    // The ids of the interceptors (in the order they're applied)
+    pub fn list_interceptors(&self) -> Vec<String> {
+        self.interceptors.iter().map(|v| v.get_id()).collect()
+    }
+    pub fn clear_interceptors(&mut self) {
+        self.interceptors.clear();
+    }
 }
 
 impl<Client, Req, Res, Method, Header, B> SimpleHTTP<Client, Req, Res, Method, Header, B>
 where
     Req: 'static,
 {
+    /**
+    Add the interceptor in the named `slot`, ordered by its constraints (`InterceptorSlot`),
+    e.g. the built-in layers cache -> auth -> logging, with the user ones slotted in between.
+
+    The interceptors are unchanged if the constraints are contradictory (`InterceptorOrderError`).
+    */
+    pub fn add_interceptor_slot(
+        &mut self,
+        slot: InterceptorSlot,
+        interceptor: Arc<dyn Interceptor<Req>>,
+    ) -> StdResult<(), InterceptorOrderError> {
+        let mut interceptors = self.interceptors.clone();
+        if let Some(index) = interceptors.iter().position(|v| v.get_id() == slot.name) {
+            interceptors.remove(index);
+        }
+        interceptors.push_back(Arc::new(SlottedInterceptor {
+            slot,
+            inner: interceptor,
+        }));
+        self.interceptors = order_interceptors(interceptors)?;
+        Ok(())
+    }
+
     pub fn add_interceptor_fn(
         &mut self,
         func: impl FnMut(&mut Req) -> StdResult<(), Box<dyn StdError>> + Send + Sync + 'static,
@@ -170,15 +897,177 @@ where
         let interceptor = Arc::new(InterceptorFunc::new(func));
         self.add_interceptor(interceptor.clone());
 
+        interceptor
+    }
+    pub fn add_interceptor_headers_fn(
+        &mut self,
+        func: impl FnMut(&mut Headers) -> StdResult<(), Box<dyn StdError>> + Send + Sync + 'static,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders,
+    {
+        let interceptor = Arc::new(InterceptorFunc::new_for_headers(func));
+        self.add_interceptor(interceptor.clone());
+
+        interceptor
+    }
+    // Set the header to a new id if it's absent (`InterceptorFunc::new_for_id_header()`)
+    pub fn add_interceptor_id_header(
+        &mut self,
+        name: impl Into<String>,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders,
+    {
+        let interceptor = Arc::new(InterceptorFunc::new_for_id_header(name));
+        self.add_interceptor(interceptor.clone());
+
+        interceptor
+    }
+    // Log the requests by the `RequestFormatter` to the `log_hook` (`InterceptorFunc::new_for_logging()`)
+    pub fn add_interceptor_logging(
+        &mut self,
+        formatter: RequestFormatter,
+        log_hook: RequestLogHook,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestSummary,
+    {
+        let interceptor = Arc::new(InterceptorFunc::new_for_logging(formatter, log_hook));
+        self.add_interceptor(interceptor.clone());
+
+        interceptor
+    }
+    // Propagate the `DelegatedIdentity` as the headers (`InterceptorFunc::new_for_delegation()`)
+    pub fn add_interceptor_delegation(
+        &mut self,
+        headers: DelegationHeaders,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders + HasRequestOptions,
+    {
+        let interceptor = Arc::new(InterceptorFunc::new_for_delegation(headers));
+        self.add_interceptor(interceptor.clone());
+
+        interceptor
+    }
+    // Send the `Locale` as the headers (`InterceptorFunc::new_for_locale()`)
+    pub fn add_interceptor_locale(
+        &mut self,
+        default_locale: Option<Locale>,
+        headers: LocaleHeaders,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders + HasRequestOptions,
+    {
+        let interceptor = Arc::new(InterceptorFunc::new_for_locale(default_locale, headers));
+        self.add_interceptor(interceptor.clone());
+
+        interceptor
+    }
+    // Send a new nonce & the timestamp of each request (`InterceptorFunc::new_for_replay_nonce()`)
+    pub fn add_interceptor_replay_nonce(
+        &mut self,
+        headers: ReplayNonceHeaders,
+        clock: Arc<dyn Clock>,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders + HasRequestOptions,
+    {
+        let interceptor = Arc::new(InterceptorFunc::new_for_replay_nonce(headers, clock));
+        self.add_interceptor(interceptor.clone());
+
+        interceptor
+    }
+    // Set the credentials of the `AuthProvider` (`InterceptorFunc::new_for_auth_provider()`)
+    pub fn add_interceptor_auth_provider(
+        &mut self,
+        provider: Arc<dyn AuthProvider>,
+    ) -> Arc<InterceptorFunc<Req>>
+    where
+        Req: RequestHeaders + HasRequestOptions,
+    {
+        let interceptor = Arc::new(InterceptorFunc::new_for_auth_provider(provider));
+        self.add_interceptor(interceptor.clone());
+
         interceptor
     }
 }
 
+// The max length of the multipart boundaries (RFC 2046)
+#[cfg(feature = "multipart")]
+pub const MAX_MULTIPART_BOUNDARY_LENGTH: usize = 70;
+
+/**
+Validate the multipart boundary by RFC 2046: 1 to 70 `bchars` (alphanumerics and `'()+_,-./:=?` and spaces)
+not ending with a space.
+
+# Remarks
+
+* It's a pure function (for fuzzing), and it doesn't panic on any input.
+
+*/
+#[cfg(feature = "multipart")]
+pub fn validate_multipart_boundary(boundary: &[u8]) -> StdResult<&str, FormDataParseError> {
+    if boundary.is_empty() || boundary.len() > MAX_MULTIPART_BOUNDARY_LENGTH {
+        return Err(FormDataParseError::new(format!(
+            "boundary: invalid length {}",
+            boundary.len()
+        )));
+    }
+    if let Some(c) = boundary
+        .iter()
+        .find(|c| !(c.is_ascii_alphanumeric() || b"'()+_,-./:=? ".contains(c)))
+    {
+        return Err(FormDataParseError::new(format!(
+            "boundary: invalid character 0x{:02x}",
+            c
+        )));
+    }
+    if boundary.ends_with(b" ") {
+        return Err(FormDataParseError::new("boundary: ends with a space"));
+    }
+
+    // It's ASCII (validated above)
+    Ok(std::str::from_utf8(boundary).unwrap_or_default())
+}
+
+/**
+Parse the boundary of the `multipart/form-data` content type (e.g. from the `Content-Type` header).
+
+# Remarks
+
+* It's a pure function (for fuzzing), and it doesn't panic on any input.
+* The boundary is validated by `validate_multipart_boundary()`,
+  so the empty/oversized boundaries are rejected before parsing the bodies.
+
+*/
+#[cfg(feature = "multipart")]
+pub fn parse_multipart_boundary(content_type: &str) -> StdResult<String, FormDataParseError> {
+    let mime = content_type
+        .parse::<mime::Mime>()
+        .map_err(|e| FormDataParseError::new(format!("content type: {}", e)))?;
+    if mime.type_() != mime::MULTIPART || mime.subtype() != mime::FORM_DATA {
+        return Err(FormDataParseError::new(format!(
+            "content type: not {}",
+            MULTIPART_FORM_DATA
+        )));
+    }
+
+    match mime.get_param(mime::BOUNDARY) {
+        Some(boundary) => {
+            Ok(validate_multipart_boundary(boundary.as_str().as_bytes())?.to_string())
+        }
+        None => Err(FormDataParseError::new("boundary: None")),
+    }
+}
+
 #[cfg(feature = "multipart")]
 pub fn get_content_type_from_multipart_boundary(
     boundary: Vec<u8>,
 ) -> StdResult<String, Box<dyn StdError>> {
-    Ok(MULTIPART_FORM_DATA.to_string() + "; boundary=\"" + &String::from_utf8(boundary)? + "\"")
+    let boundary = validate_multipart_boundary(&boundary)?;
+    Ok(MULTIPART_FORM_DATA.to_string() + "; boundary=\"" + boundary + "\"")
 }
 #[cfg(feature = "multipart")]
 pub fn data_and_boundary_from_multipart(
@@ -244,5 +1133,107 @@ pub async fn multer_multipart_to_hash_map(
     Ok(result)
 }
 
+/**
+`MultipartSpooling` Spool the large fields of the received multipart bodies to the temporary files
+(by `multer_multipart_to_hash_map_spooled()`), to keep the memory of the servers bounded.
+
+# Remarks
+
+* The fields larger than `threshold` bytes are written to the new files in `dir`
+  (`std::env::temp_dir()` by default), the others are kept in memory.
+* The spooled files are owned by the callers (they're not removed automatically),
+  except the ones of the failed parsing.
+
+*/
+#[cfg(feature = "multipart")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MultipartSpooling {
+    pub threshold: usize,
+    pub dir: PathBuf,
+}
+#[cfg(feature = "multipart")]
+impl MultipartSpooling {
+    pub fn new(threshold: usize) -> Self {
+        MultipartSpooling {
+            threshold,
+            dir: std::env::temp_dir(),
+        }
+    }
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+}
+
+/*
+`MultipartFieldData` The data of a field parsed by `multer_multipart_to_hash_map_spooled()`
+*/
+#[cfg(feature = "multipart")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MultipartFieldData {
+    Memory(Bytes),
+    Spooled(PathBuf),
+}
+
+/**
+Parse the fields like `multer_multipart_to_hash_map()` (`name` -> `(name, file_name, data)`),
+but the data of the files are read as well, and the large fields are spooled by the `spooling`.
+*/
+#[cfg(feature = "multipart")]
+pub async fn multer_multipart_to_hash_map_spooled(
+    multipart: &mut Multipart<'_>,
+    spooling: &MultipartSpooling,
+) -> StdResult<HashMap<String, (String, String, MultipartFieldData)>, Box<dyn StdError>> {
+    let mut result = HashMap::new();
+    let mut spooled = Vec::new();
+
+    let parsed = async {
+        while let Some(mut field) = multipart.next_field().await? {
+            let name = field.name().unwrap_or_default().to_string();
+            let file_name = field.file_name().unwrap_or_default().to_string();
+
+            let mut buffer = Vec::new();
+            let mut file: Option<(File, PathBuf)> = None;
+            while let Some(chunk) = field.chunk().await? {
+                if let Some((file, _)) = file.as_mut() {
+                    file.write_all(&chunk)?;
+                    continue;
+                }
+                buffer.extend_from_slice(&chunk);
+                if buffer.len() > spooling.threshold {
+                    let file_name = format!("multipart-{}.tmp", generate_id());
+                    let path = spooling.dir.join(file_name);
+                    spooled.push(path.clone());
+                    let mut new_file = File::create(&path)?;
+                    new_file.write_all(&buffer)?;
+                    buffer = Vec::new();
+                    file = Some((new_file, path));
+                }
+            }
+            let data = match file {
+                Some((mut file, path)) => {
+                    file.flush()?;
+                    MultipartFieldData::Spooled(path)
+                }
+                None => MultipartFieldData::Memory(Bytes::from(buffer)),
+            };
+
+            result.insert(name.clone(), (name, file_name, data));
+        }
+        Ok::<(), Box<dyn StdError>>(())
+    }
+    .await;
+
+    match parsed {
+        Ok(_) => Ok(result),
+        Err(e) => {
+            for path in spooled.iter() {
+                let _ = fs::remove_file(path);
+            }
+            Err(e)
+        }
+    }
+}
+
 // #[inline]
 // #[derive(Debug, Clone)]
@@ -0,0 +1,202 @@
+/*!
+In this module there're implementations of `TokenExchanger`,
+exchanging the tokens of the delegated identities for the tokens of the backend-to-backend calls
+by the OAuth2 token exchange (RFC 8693).
+
+# Remarks
+
+* The token endpoint is called by `AnyService`, so the exchanger works with whichever binding is enabled.
+* The exchanged tokens are propagated by `InterceptorFunc::new_for_delegation()` (as `ExchangedToken`).
+
+*/
+
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::future::Future;
+use std::result::Result as StdResult;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use http::method::Method;
+use serde::Deserialize;
+
+use super::any_service::AnyService;
+use super::common::{
+    system_clock, with_extensions, Clock, DelegatedIdentity, ExchangedToken, Extensions, PathParam,
+    QueryParam,
+};
+
+pub const GRANT_TYPE_TOKEN_EXCHANGE: &str = "urn:ietf:params:oauth:grant-type:token-exchange";
+pub const TOKEN_TYPE_ACCESS_TOKEN: &str = "urn:ietf:params:oauth:token-type:access_token";
+
+/*
+`TokenExchangeError` The token exchange is failed (e.g. rejected by the token endpoint)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenExchangeError {
+    details: String,
+    pub status: Option<u16>,
+}
+impl StdError for TokenExchangeError {}
+impl TokenExchangeError {
+    pub fn new(msg: impl Into<String>, status: Option<u16>) -> TokenExchangeError {
+        TokenExchangeError {
+            details: msg.into(),
+            status,
+        }
+    }
+}
+impl std::fmt::Display for TokenExchangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "TokenExchangeError: {}", self.details)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenExchangeResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+/**
+`TokenExchanger` Exchange the `subject_token` of the `DelegatedIdentity` for an access token (RFC 8693).
+
+# Arguments
+
+* `service` - The service of the token endpoint.
+* `token_url` - The url of the token endpoint (relative to the base url of the `service`).
+
+# Remarks
+
+* The client credentials (`client_id`/`client_secret`) are sent in the form (`client_secret_post`).
+* The exchanged tokens are cached by the subject tokens until they expire
+  (by `expires_in` minus `expiry_margin`, or `default_ttl` without `expires_in`), up to `max_ttl`.
+
+*/
+pub struct TokenExchanger {
+    pub service: AnyService,
+    pub token_url: String,
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    pub audience: Option<String>,
+    pub scope: Option<String>,
+    pub subject_token_type: String,
+    pub expiry_margin: Duration,
+    pub default_ttl: Duration,
+    pub max_ttl: Duration,
+    pub clock: Arc<dyn Clock>,
+    // subject token -> (expires at, access token)
+    cache: Mutex<HashMap<String, (Instant, String)>>,
+}
+impl TokenExchanger {
+    pub fn new(service: AnyService, token_url: impl Into<String>) -> Self {
+        TokenExchanger {
+            service,
+            token_url: token_url.into(),
+            client_id: None,
+            client_secret: None,
+            audience: None,
+            scope: None,
+            subject_token_type: TOKEN_TYPE_ACCESS_TOKEN.to_string(),
+            expiry_margin: Duration::from_secs(30),
+            default_ttl: Duration::from_secs(300),
+            max_ttl: Duration::from_secs(24 * 60 * 60),
+            clock: system_clock(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Exchange the subject token (or get the cached one)
+    pub async fn exchange(&self, subject_token: &str) -> StdResult<String, Box<dyn StdError>> {
+        let now = self.clock.now();
+        if let Some((expires_at, token)) = self.cache.lock().unwrap().get(subject_token) {
+            if *expires_at > now {
+                return Ok(token.clone());
+            }
+        }
+
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", GRANT_TYPE_TOKEN_EXCHANGE);
+        form.append_pair("subject_token", subject_token);
+        form.append_pair("subject_token_type", &self.subject_token_type);
+        for (k, v) in [
+            ("audience", &self.audience),
+            ("scope", &self.scope),
+            ("client_id", &self.client_id),
+            ("client_secret", &self.client_secret),
+        ] {
+            if let Some(v) = v {
+                form.append_pair(k, v);
+            }
+        }
+
+        let res = self
+            .service
+            .do_request_response(
+                Method::POST,
+                None,
+                self.token_url.clone(),
+                "application/x-www-form-urlencoded",
+                None::<PathParam>,
+                None::<QueryParam>,
+                Bytes::from(form.finish()),
+            )
+            .await?;
+        if !(200..300).contains(&res.status) {
+            return Err(Box::new(TokenExchangeError::new(
+                format!(
+                    "{} responded with the status {}",
+                    self.token_url, res.status
+                ),
+                Some(res.status),
+            )));
+        }
+        let token: TokenExchangeResponse = serde_json::from_slice(&res.body).map_err(|e| {
+            TokenExchangeError::new(format!("illegal response: {}", e), Some(res.status))
+        })?;
+
+        let ttl = match token.expires_in {
+            Some(expires_in) => Duration::from_secs(expires_in).saturating_sub(self.expiry_margin),
+            None => self.default_ttl,
+        }
+        .min(self.max_ttl);
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (expires_at, _)| *expires_at > now);
+        // Not cached if it's beyond the range of `Instant`
+        if let Some(expires_at) = now.checked_add(ttl) {
+            cache.insert(
+                subject_token.to_string(),
+                (expires_at, token.access_token.clone()),
+            );
+        }
+        Ok(token.access_token)
+    }
+
+    /**
+    Run the calls of the `future` on behalf of the `identity`
+    (scoped by `with_extensions()` with the `DelegatedIdentity` and the `ExchangedToken`).
+
+    # Remarks
+
+    * The token is exchanged before the `future` is polled, and the failures are returned without polling it.
+    * The identities without the `subject_token` are scoped without the `ExchangedToken`.
+
+    */
+    pub async fn on_behalf_of<F: Future>(
+        &self,
+        identity: DelegatedIdentity,
+        future: F,
+    ) -> StdResult<F::Output, Box<dyn StdError>> {
+        let mut extensions = Extensions::new();
+        if let Some(subject_token) = &identity.subject_token {
+            extensions.insert(ExchangedToken(self.exchange(subject_token).await?));
+        }
+        extensions.insert(identity);
+        Ok(with_extensions(extensions, future).await)
+    }
+
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+}
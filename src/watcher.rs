@@ -0,0 +1,273 @@
+/*!
+In this module there're implementations of `Watcher`,
+polling an endpoint on an interval and emitting the deserialized values only when they're changed.
+
+# Remarks
+
+* The requests are sent by `AnyService`, so the watcher works with whichever binding is enabled.
+* The `ETag` of the last response is sent as `If-None-Match`, and `304 Not Modified` means no change.
+* The values are compared by `PartialEq` as well (for the servers without `ETag`).
+* The schedules follow `Watcher.clock` (`SystemClock` by default).
+
+*/
+
+use std::error::Error as StdError;
+use std::io;
+use std::result::Result as StdResult;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use futures::channel::oneshot;
+use futures::future::{self, select, Either, Future};
+use http::method::Method;
+use tokio::runtime::Builder;
+
+use super::any_service::AnyService;
+use super::common::{
+    catch_hook_panic, catch_hook_panic_async, system_clock, Clock, Headers, PathParam, QueryParam,
+};
+use super::simple_api::BodyDeserializer;
+
+// The default interval between the polls of `Watcher`
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+// Observe the failed polls of `Watcher`
+pub type WatchErrorHook = Arc<dyn Fn(&dyn StdError) + Send + Sync>;
+
+/*
+`WatchStatusError` The polled response of `Watcher` is not successful (neither `2xx` nor `304`)
+*/
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchStatusError {
+    details: String,
+    pub status: u16,
+}
+impl StdError for WatchStatusError {}
+impl WatchStatusError {
+    pub fn new(msg: impl Into<String>, status: u16) -> WatchStatusError {
+        WatchStatusError {
+            details: msg.into(),
+            status,
+        }
+    }
+}
+impl std::fmt::Display for WatchStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "WatchStatusError: {} (status {})",
+            self.details, self.status
+        )
+    }
+}
+
+/**
+`Watcher` Poll the endpoint (`GET`) and emit the deserialized value when it's changed.
+
+# Arguments
+
+* `R` - The deserialized value, compared with the last emitted one by `PartialEq`.
+
+# Remarks
+
+* The first successful poll always emits.
+* The failed polls (e.g. connection failures, error statuses, deserialization failures)
+  are reported to the `error_hook`, and the watching goes on.
+
+*/
+pub struct Watcher<R> {
+    pub service: AnyService,
+    pub relative_url: String,
+    pub header: Option<Headers>,
+    pub query_param: Option<QueryParam>,
+    pub deserializer: Arc<dyn BodyDeserializer<R>>,
+    pub interval: Duration,
+    pub clock: Arc<dyn Clock>,
+    pub error_hook: Option<WatchErrorHook>,
+
+    etag: Option<String>,
+    last: Option<Arc<R>>,
+}
+impl<R: PartialEq> Watcher<R> {
+    pub fn new(
+        service: AnyService,
+        relative_url: impl Into<String>,
+        deserializer: Arc<dyn BodyDeserializer<R>>,
+    ) -> Self {
+        Watcher {
+            service,
+            relative_url: relative_url.into(),
+            header: None,
+            query_param: None,
+            deserializer,
+            interval: DEFAULT_WATCH_INTERVAL,
+            clock: system_clock(),
+            error_hook: None,
+
+            etag: None,
+            last: None,
+        }
+    }
+
+    // The last emitted value
+    pub fn get_last(&self) -> Option<Arc<R>> {
+        self.last.clone()
+    }
+    // The `ETag` of the last successful response
+    pub fn get_etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /**
+    Poll the endpoint once.
+
+    # Remarks
+
+    * It returns `Some(value)` only if the value is changed (or it's the first one),
+      and `None` for `304 Not Modified` or the same value.
+    * The `ETag` is kept only after the body is deserialized successfully.
+
+    */
+    pub async fn poll_once(&mut self) -> StdResult<Option<Arc<R>>, Box<dyn StdError>> {
+        let mut header = self.header.clone().unwrap_or_default();
+        if let Some(etag) = &self.etag {
+            header.insert("If-None-Match", etag.clone());
+        }
+
+        let res = self
+            .service
+            .do_request_response(
+                Method::GET,
+                Some(header),
+                self.relative_url.clone(),
+                "",
+                None::<PathParam>,
+                self.query_param.clone(),
+                Bytes::new(),
+            )
+            .await?;
+        if res.status == 304 {
+            return Ok(None);
+        }
+        if !(200..300).contains(&res.status) {
+            return Err(Box::new(WatchStatusError::new(
+                format!("{} isn't watched", self.relative_url),
+                res.status,
+            )));
+        }
+
+        let value = Arc::new(
+            *catch_hook_panic_async("deserializer", self.deserializer.decode_async(res.body))
+                .await??,
+        );
+        self.etag = res.headers.get("ETag").map(|v| v.to_string());
+        if self.last.as_ref() == Some(&value) {
+            return Ok(None);
+        }
+        self.last = Some(value.clone());
+        Ok(Some(value))
+    }
+
+    // Poll every `interval` and send the changed values, until the receiver is dropped
+    pub async fn run(self, sender: UnboundedSender<Arc<R>>) {
+        self.run_until(sender, future::pending::<()>()).await
+    }
+
+    // The polls and the sleeps are interrupted as soon as the `stop` is resolved
+    async fn run_until(mut self, sender: UnboundedSender<Arc<R>>, mut stop: impl Future + Unpin) {
+        while !sender.is_closed() {
+            let result = match select(Box::pin(self.poll_once()), &mut stop).await {
+                Either::Left((result, _)) => result,
+                Either::Right(_) => break,
+            };
+            match result {
+                Ok(Some(value)) => {
+                    if sender.unbounded_send(value).is_err() {
+                        break;
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    if let Some(hook) = &self.error_hook {
+                        // The watching goes on even if the hook panicked
+                        let _ = catch_hook_panic("error_hook", || hook(e.as_ref()));
+                    }
+                }
+            }
+            if let Either::Right(_) = select(self.clock.sleep(self.interval), &mut stop).await {
+                break;
+            }
+        }
+    }
+}
+impl<R: PartialEq + Send + Sync + 'static> Watcher<R> {
+    /**
+    Spawn the background worker thread running the `Watcher`.
+
+    # Arguments
+
+    * `factory` - Create the `Watcher` on the worker thread
+      (the services are not `Send`).
+
+    # Remarks
+
+    * The worker runs on its own (current thread) tokio runtime.
+    * It stops right away after `WatcherWorker::stop()` or dropping the `WatcherWorker`
+      (the poll in flight is dropped), and within an `interval` after dropping the receiver.
+
+    */
+    pub fn spawn(
+        factory: impl FnOnce() -> Watcher<R> + Send + 'static,
+    ) -> io::Result<(WatcherWorker, UnboundedReceiver<Arc<R>>)> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let (stop_sender, stop_receiver) = oneshot::channel::<()>();
+        let (sender, receiver) = unbounded();
+
+        let handle = thread::Builder::new()
+            .name("watcher".to_string())
+            .spawn(move || {
+                let watcher = factory();
+                runtime.block_on(watcher.run_until(sender, stop_receiver));
+            })?;
+
+        Ok((
+            WatcherWorker {
+                stop_sender: Some(stop_sender),
+                handle: Some(handle),
+            },
+            receiver,
+        ))
+    }
+}
+
+/*
+`WatcherWorker` The handle of the background worker of `Watcher`
+*/
+pub struct WatcherWorker {
+    stop_sender: Option<oneshot::Sender<()>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+impl WatcherWorker {
+    // Stop the worker and wait for it
+    pub fn stop(mut self) {
+        self.stop_and_join();
+    }
+
+    fn stop_and_join(&mut self) {
+        // Wake the worker up (it's sleeping or polling)
+        if let Some(stop_sender) = self.stop_sender.take() {
+            let _ = stop_sender.send(());
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+impl Drop for WatcherWorker {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
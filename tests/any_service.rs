@@ -0,0 +1,303 @@
+extern crate http_api_service;
+
+#[tokio::test]
+async fn test_any_service() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+    use serde::{Deserialize, Serialize};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::any_service::AnyService;
+    use http_api_service::path_param;
+    use http_api_service::simple_api::{
+        DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, Default, PartialEq)]
+    struct Product {
+        name: String,
+        meta: Option<String>,
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3900).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let (parts, body_instance) = req.into_parts();
+            let bytes = body::to_bytes(body_instance).await?;
+            let mut product: Product = serde_json::from_slice(&bytes).unwrap_or_default();
+            product.meta = Some(format!(
+                "{} {} {}",
+                parts.method,
+                parts.uri,
+                parts
+                    .headers
+                    .get("x-binding")
+                    .map(|v| v.to_str().unwrap())
+                    .unwrap_or_default()
+            ));
+
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(
+                serde_json::to_string(&product).unwrap(),
+            )))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    // The same code for both bindings
+    for (binding, service) in [
+        ("hyper", AnyService::new_for_hyper()),
+        ("ureq", AnyService::new_for_ureq()),
+    ] {
+        service.set_base_url(
+            url::Url::parse(&("http://".to_string() + addr.to_string().as_str()))
+                .ok()
+                .unwrap(),
+        );
+        service
+            .set_default_header(Some(vec![("X-Binding", binding)].into_iter().collect()))
+            .ok()
+            .unwrap();
+
+        let api_put_product = service.make_api(
+            Method::PUT,
+            "/products/{id}",
+            "application/json",
+            Arc::new(DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES),
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &Product::default(),
+        );
+        let resp = api_put_product
+            .call(
+                Some(path_param!["id" => "5"]),
+                Product {
+                    name: "Alien".to_string(),
+                    meta: None,
+                },
+            )
+            .await;
+        assert_eq!(
+            Product {
+                name: "Alien".to_string(),
+                meta: Some(format!("PUT /products/5 {}", binding)),
+            },
+            *resp.ok().unwrap()
+        );
+
+        let api_get_product = service.make_api_no_body(
+            Method::GET,
+            "/products/{id}",
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &Product::default(),
+        );
+        let resp = api_get_product
+            .call(Some(path_param!["id" => "3"]), ())
+            .await;
+        assert_eq!(
+            Some(format!("GET /products/3 {}", binding)),
+            resp.ok().unwrap().meta
+        );
+    }
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_get_then_update() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server, StatusCode};
+    use serde::{Deserialize, Serialize};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::any_service::{get_then_update, AnyService, OptimisticUpdateError};
+    use http_api_service::path_param;
+    use http_api_service::simple_api::{
+        DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+    struct Counter {
+        count: i64,
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4068).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // (version, count) of "/counters/a", the ETag is the version,
+    // and the other writers change it before the next `conflicts` updates
+    let state = Arc::new(Mutex::new((1u64, 0i64)));
+    let conflicts = Arc::new(AtomicUsize::new(0));
+    let state_for_thread = state.clone();
+    let conflicts_for_thread = conflicts.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let state = state_for_thread.clone();
+        let conflicts = conflicts_for_thread.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let state = state.clone();
+                let conflicts = conflicts.clone();
+                async move {
+                    let (parts, body_instance) = req.into_parts();
+                    let bytes = body::to_bytes(body_instance).await?;
+                    if parts.uri.path() != "/counters/a" {
+                        return Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(
+                            "{\"count\":0}",
+                        )));
+                    }
+                    if parts.method == Method::PUT
+                        && conflicts
+                            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |v| v.checked_sub(1))
+                            .is_ok()
+                    {
+                        let mut state = state.lock().unwrap();
+                        *state = (state.0 + 1, state.1 + 100);
+                    }
+
+                    let mut state = state.lock().unwrap();
+                    let etag = format!("\"v{}\"", state.0);
+                    if parts.method == Method::PUT {
+                        let if_match = parts.headers.get("If-Match").map(|v| v.to_str().unwrap());
+                        if if_match != Some(etag.as_str()) {
+                            let mut resp = Response::new(Body::empty());
+                            *resp.status_mut() = StatusCode::PRECONDITION_FAILED;
+                            return Ok::<Response<Body>, hyper::Error>(resp);
+                        }
+                        let counter: Counter = serde_json::from_slice(&bytes).unwrap();
+                        *state = (state.0 + 1, counter.count);
+                    }
+                    let mut resp = Response::new(Body::from(
+                        serde_json::to_string(&Counter { count: state.1 }).unwrap(),
+                    ));
+                    resp.headers_mut()
+                        .insert("ETag", format!("\"v{}\"", state.0).parse().unwrap());
+                    Ok::<Response<Body>, hyper::Error>(resp)
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    for service in [AnyService::new_for_hyper(), AnyService::new_for_ureq()] {
+        *state.lock().unwrap() = (1, 0);
+        service.set_base_url(
+            url::Url::parse(&("http://".to_string() + addr.to_string().as_str()))
+                .ok()
+                .unwrap(),
+        );
+        let api_get_counter = service.make_api_no_body(
+            Method::GET,
+            "/counters/{id}",
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &Counter::default(),
+        );
+        let api_put_counter = service.make_api(
+            Method::PUT,
+            "/counters/{id}",
+            "application/json",
+            Arc::new(DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES),
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &Counter::default(),
+        );
+        let increment = |counter: Counter| {
+            Ok(Counter {
+                count: counter.count + 1,
+            })
+        };
+
+        // Updated at once
+        let counter = get_then_update(
+            &api_get_counter,
+            &api_put_counter,
+            Some(path_param!["id" => "a"]),
+            3,
+            increment,
+        )
+        .await
+        .ok()
+        .unwrap();
+        assert_eq!(1, counter.count);
+
+        // Fetched & mutated again after 412
+        conflicts.store(2, Ordering::SeqCst);
+        let mut mutated = 0;
+        let counter = get_then_update(
+            &api_get_counter,
+            &api_put_counter,
+            Some(path_param!["id" => "a"]),
+            3,
+            |counter| {
+                mutated += 1;
+                increment(counter)
+            },
+        )
+        .await
+        .ok()
+        .unwrap();
+        assert_eq!(3, mutated);
+        assert_eq!(202, counter.count);
+
+        // All attempts are rejected
+        conflicts.store(2, Ordering::SeqCst);
+        let err = get_then_update(
+            &api_get_counter,
+            &api_put_counter,
+            Some(path_param!["id" => "a"]),
+            2,
+            increment,
+        )
+        .await
+        .err()
+        .unwrap();
+        let err = err.downcast_ref::<OptimisticUpdateError>().unwrap();
+        assert_eq!((Some(412), 2), (err.status, err.attempts));
+        assert_eq!(402, state.lock().unwrap().1);
+
+        // The resources without ETag aren't updated
+        let err = get_then_update(
+            &api_get_counter,
+            &api_put_counter,
+            Some(path_param!["id" => "b"]),
+            3,
+            increment,
+        )
+        .await
+        .err()
+        .unwrap();
+        assert_eq!(
+            Some(200),
+            err.downcast_ref::<OptimisticUpdateError>().unwrap().status
+        );
+    }
+
+    hyper_latch.countdown();
+}
@@ -0,0 +1,276 @@
+extern crate http_api_service;
+
+#[tokio::test]
+async fn test_audit_log() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use bytes::Bytes;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::any_service::AnyService;
+    use http_api_service::audit::{AuditLog, ChannelAuditSink, FileAuditSink};
+    use http_api_service::bind_hyper::HyperClient;
+    use http_api_service::common::{HasRequestOptions, QueryParam};
+    use http_api_service::path_param;
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3920).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let mut resp = Response::new(Body::from("audited"));
+            if req.uri().path().starts_with("/missing") {
+                *resp.status_mut() = StatusCode::NOT_FOUND;
+            }
+            Ok::<Response<Body>, hyper::Error>(resp)
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = "http://".to_string() + addr.to_string().as_str();
+
+    // The same records for both bindings
+    for (binding, service) in [
+        ("hyper", AnyService::new_for_hyper()),
+        ("ureq", AnyService::new_for_ureq()),
+    ] {
+        let (sink, receiver) = ChannelAuditSink::channel();
+        service.set_base_url(url::Url::parse(&base_url).ok().unwrap());
+        service.set_audit_log(Some(Arc::new(AuditLog::new_with_options(
+            Arc::new(sink),
+            1.0,
+            vec![("binding".to_string(), binding.to_string())],
+        ))));
+
+        for relative_url in ["/products/{id}", "/missing/{id}"].iter() {
+            let _ = service
+                .do_request(
+                    Method::GET,
+                    None,
+                    *relative_url,
+                    "",
+                    Some(path_param!["id" => "5"]),
+                    None::<QueryParam>,
+                    Bytes::new(),
+                )
+                .await;
+        }
+
+        let records = receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(2, records.len());
+        assert_eq!("GET", records[0].method);
+        assert_eq!("/products/{id}", records[0].url_template);
+        assert_eq!(Some(200), records[0].status);
+        assert_eq!(
+            vec![("binding".to_string(), binding.to_string())],
+            records[0].tags
+        );
+        assert_eq!("/missing/{id}", records[1].url_template);
+        assert_eq!(Some(404), records[1].status);
+    }
+
+    // The sampling, the per-request tags and the file sink
+    let path = std::env::temp_dir().join(format!(
+        "http_api_service_test_audit_{}.log",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_file(&path);
+    let mut simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(HyperClient::new_for_hyper())),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+    simple_http.add_interceptor_fn(|req| {
+        let mut options = req.get_request_options();
+        options
+            .tags
+            .push(("caller".to_string(), "batch".to_string()));
+        req.set_request_options(options);
+        Ok(())
+    });
+    simple_http.set_audit_log(Some(Arc::new(AuditLog::new_with_options(
+        Arc::new(FileAuditSink::new(&path).ok().unwrap()),
+        0.5,
+        Vec::new(),
+    ))));
+    for i in 0..4 {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(format!("{}/events/{}", base_url, i))
+            .body(Body::empty())
+            .ok()
+            .unwrap();
+        assert!(simple_http.request(request).await.ok().unwrap().is_ok());
+    }
+
+    let content = std::fs::read_to_string(&path).ok().unwrap();
+    let lines = content.lines().collect::<Vec<_>>();
+    assert_eq!(2, lines.len());
+    let fields = lines[0].split('\t').collect::<Vec<_>>();
+    assert_eq!(vec!["POST", "/events/1", "200"], fields[1..4].to_vec());
+    assert_eq!("caller=batch", fields[5]);
+    let _ = std::fs::remove_file(&path);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_sink_panics() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::audit::{AuditLog, AuditRecord, AuditSink};
+    use http_api_service::bind_hyper::{HyperClient, RetryClient, TimingClient};
+    use http_api_service::common::{
+        MetricsSink, RequestTiming, ResilienceEvent, RetryBudget, RetryBudgetUsage,
+    };
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    // The sinks count the records before panicking
+    #[derive(Default)]
+    struct PanickingSink {
+        records: AtomicUsize,
+    }
+    impl PanickingSink {
+        fn panic(&self) {
+            self.records.fetch_add(1, Ordering::SeqCst);
+            panic!("the sink panicked");
+        }
+    }
+    impl AuditSink for PanickingSink {
+        fn record(&self, _record: &AuditRecord) {
+            self.panic();
+        }
+    }
+    impl MetricsSink for PanickingSink {
+        fn record_timing(&self, _method: &str, _url: &str, _timing: &RequestTiming) {
+            self.panic();
+        }
+        fn record_retry(
+            &self,
+            _method: &str,
+            _url: &str,
+            _usage: &RetryBudgetUsage,
+            _allowed: bool,
+        ) {
+            self.panic();
+        }
+        fn record_event(&self, _event: &ResilienceEvent) {
+            self.panic();
+        }
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4094).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let mut resp = Response::new(Body::from("recorded"));
+            if req.uri().path() == "/unavailable" {
+                *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            }
+            Ok::<Response<Body>, hyper::Error>(resp)
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let request = |path: &str| {
+        Request::builder()
+            .method(Method::GET)
+            .uri(format!("http://{}{}", addr, path))
+            .body(Body::empty())
+            .ok()
+            .unwrap()
+    };
+
+    // The panics of the audit sink don't fail the calls
+    let audit_sink = Arc::new(PanickingSink::default());
+    let mut simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(HyperClient::new_for_hyper())),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+    simple_http.set_audit_log(Some(Arc::new(AuditLog::new(audit_sink.clone()))));
+    for _ in 0..2 {
+        let resp = simple_http.request(request("/status")).await.ok().unwrap();
+        let body = hyper::body::to_bytes(resp.ok().unwrap().into_body())
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!("recorded", body);
+    }
+    assert_eq!(2, audit_sink.records.load(Ordering::SeqCst));
+
+    // Neither do the ones of the metrics sink (including the timing at the end of the body)
+    let metrics_sink = Arc::new(PanickingSink::default());
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(TimingClient::new_for_hyper(Some(
+            metrics_sink.clone(),
+        )))),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+    let resp = simple_http.request(request("/status")).await.ok().unwrap();
+    let body = hyper::body::to_bytes(resp.ok().unwrap().into_body())
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("recorded", body);
+    assert_eq!(1, metrics_sink.records.load(Ordering::SeqCst));
+
+    let metrics_sink = Arc::new(PanickingSink::default());
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(RetryClient::new_with_options(
+            hyper::Client::new(),
+            1,
+            Duration::from_millis(1),
+            Arc::new(RetryBudget::new(1.0, 10, Duration::from_secs(10))),
+            Some(metrics_sink.clone()),
+        ))),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+    let resp = simple_http
+        .request(request("/unavailable"))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, resp.ok().unwrap().status());
+    assert_eq!(1, metrics_sink.records.load(Ordering::SeqCst));
+
+    hyper_latch.countdown();
+}
@@ -0,0 +1,101 @@
+extern crate http_api_service;
+
+#[test]
+fn test_blocking_common_api() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::thread;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::blocking;
+    use http_api_service::common::{PathParam, QueryParam};
+    use http_api_service::path_param;
+    use http_api_service::simple_api;
+
+    let hyper_latch = CountDownLatch::new(1);
+    let started_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3800).into();
+
+    let hyper_latch_for_thread = hyper_latch.clone();
+    let started_latch_for_thread = started_latch.clone();
+    let server_thread = thread::spawn(move || {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async move {
+            let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+                Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+                    let (parts, body_instance) = req.into_parts();
+                    let bytes = body::to_bytes(body_instance).await?;
+                    let body_str = String::from_utf8(bytes.to_vec()).unwrap();
+
+                    Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                        "{} {} {}",
+                        parts.method, parts.uri, body_str
+                    ))))
+                }))
+            }));
+            started_latch_for_thread.countdown();
+            let _ = server
+                .with_graceful_shutdown(async move {
+                    hyper_latch_for_thread.await;
+                })
+                .await;
+        });
+    });
+    started_latch.wait();
+
+    let common_api = blocking::CommonAPI::new_for_hyper().ok().unwrap();
+    let base_service_setter = common_api.as_base_service_setter();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_setter.set_base_url(
+        url::Url::parse(&("http://".to_string() + addr.to_string().as_str()))
+            .ok()
+            .unwrap(),
+    );
+
+    assert!(common_api.preconnect().is_ok());
+
+    // do_request
+    let resp = common_api.do_request(
+        Method::POST,
+        None,
+        "/echo",
+        "text/plain",
+        None::<PathParam>,
+        None::<QueryParam>,
+        Body::from("hello"),
+    );
+    assert_eq!("POST /echo hello", resp.ok().unwrap());
+
+    // APINoBody
+    let api_get_product = common_api.wrap(base_service_setter.make_api_no_body(
+        base_service_shared.clone(),
+        Method::GET,
+        "/products/{id}",
+        Arc::new(simple_api::DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &bytes::Bytes::new(),
+    ));
+    let resp = api_get_product.call(Some(path_param!["id" => "3"]));
+    assert_eq!("GET /products/3 ", resp.ok().unwrap().as_ref());
+
+    // APIHasBody
+    let api_put_product = common_api.wrap(base_service_setter.make_api_bytes_in_out(
+        base_service_shared.clone(),
+        Method::PUT,
+        "/products/{id}",
+    ));
+    let resp = api_put_product.call(Some(path_param!["id" => "5"]), bytes::Bytes::from("5"));
+    assert_eq!("PUT /products/5 5", resp.ok().unwrap().as_ref());
+
+    hyper_latch.countdown();
+    server_thread.join().unwrap();
+}
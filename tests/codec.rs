@@ -0,0 +1,554 @@
+extern crate http_api_service;
+
+#[test]
+fn test_codec_registry() {
+    use std::error::Error as StdError;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use serde_json::json;
+
+    use http_api_service::codec::{
+        media_type_of, Codec, CodecRegistry, UnsupportedMediaTypeError, DEFAULT_JSON_CODEC,
+        MEDIA_TYPE_JSON, MEDIA_TYPE_TEXT,
+    };
+
+    assert_eq!(
+        "application/json",
+        media_type_of(" Application/JSON; charset=utf-8")
+    );
+
+    let registry = Arc::new(CodecRegistry::default());
+    assert_eq!(
+        vec![MEDIA_TYPE_JSON.to_string(), MEDIA_TYPE_TEXT.to_string()],
+        registry.media_types()
+    );
+    assert!(registry.contains("application/json; charset=utf-8"));
+    // The structured syntax suffixes
+    assert!(registry.contains("application/problem+json"));
+    assert!(!registry.contains("application/xml"));
+    assert!(!CodecRegistry::new().contains(MEDIA_TYPE_JSON));
+
+    // The serializers/deserializers
+    let serializer = registry
+        .serializer::<Vec<u32>, Bytes>(MEDIA_TYPE_JSON)
+        .ok()
+        .unwrap();
+    assert_eq!(
+        Bytes::from("[1,2,3]"),
+        serializer.encode(vec![1, 2, 3]).ok().unwrap()
+    );
+    let serializer = registry
+        .serializer::<String, Bytes>("text/plain")
+        .ok()
+        .unwrap();
+    assert_eq!(
+        Bytes::from("hello"),
+        serializer.encode("hello".to_string()).ok().unwrap()
+    );
+    let deserializer = registry
+        .deserializer::<Vec<u32>>(MEDIA_TYPE_JSON)
+        .ok()
+        .unwrap();
+    assert_eq!(
+        vec![1, 2, 3],
+        *deserializer.decode(&Bytes::from("[1,2,3]")).ok().unwrap()
+    );
+    // Without an intermediate value (e.g. the integers beyond `u64`)
+    let serializer = registry
+        .serializer::<u128, Bytes>(MEDIA_TYPE_JSON)
+        .ok()
+        .unwrap();
+    assert_eq!(
+        Bytes::from(u128::MAX.to_string()),
+        serializer.encode(u128::MAX).ok().unwrap()
+    );
+    // The plain text of the other values
+    let serializer = registry
+        .serializer::<u32, Bytes>(MEDIA_TYPE_TEXT)
+        .ok()
+        .unwrap();
+    assert_eq!(Bytes::from("7"), serializer.encode(7).ok().unwrap());
+    let deserializer = registry
+        .deserializer::<String>(MEDIA_TYPE_TEXT)
+        .ok()
+        .unwrap();
+    assert_eq!(
+        "\"quoted\"",
+        *deserializer
+            .decode(&Bytes::from("\"quoted\""))
+            .ok()
+            .unwrap()
+    );
+
+    // The typed codecs take precedence over the serde ones for their types
+    struct Reversed;
+    impl Codec<String> for Reversed {
+        fn encode(&self, value: &String) -> Result<Bytes, Box<dyn StdError>> {
+            Ok(Bytes::from(value.chars().rev().collect::<String>()))
+        }
+        fn decode(&self, bytes: &Bytes) -> Result<String, Box<dyn StdError>> {
+            Ok(std::str::from_utf8(bytes)?.chars().rev().collect())
+        }
+    }
+    let registry_typed = CodecRegistry::default();
+    registry_typed.register::<String>(MEDIA_TYPE_JSON, Arc::new(Reversed));
+    assert_eq!(
+        Bytes::from("cba"),
+        registry_typed
+            .serializer::<String, Bytes>("application/problem+json")
+            .ok()
+            .unwrap()
+            .encode("abc".to_string())
+            .ok()
+            .unwrap()
+    );
+    assert_eq!(
+        Bytes::from("[1]"),
+        registry_typed
+            .serializer::<Vec<u32>, Bytes>(MEDIA_TYPE_JSON)
+            .ok()
+            .unwrap()
+            .encode(vec![1])
+            .ok()
+            .unwrap()
+    );
+
+    // Unsupported
+    let err = registry
+        .serializer::<String, Bytes>("application/xml; charset=utf-8")
+        .err()
+        .unwrap();
+    assert_eq!("application/xml", err.media_type);
+    assert!(registry.deserializer::<String>("text/html").is_err());
+
+    // Registered later
+    registry.register_serde("application/vnd.api+json", DEFAULT_JSON_CODEC);
+    assert!(registry.contains("application/vnd.api+json"));
+    assert!(registry.unregister(MEDIA_TYPE_JSON));
+    assert!(!registry.contains("application/problem+json"));
+    assert_eq!(
+        json!({"a": 1}),
+        registry
+            .get::<serde_json::Value>("application/vnd.api+json")
+            .unwrap()
+            .decode(&Bytes::from("{\"a\":1}"))
+            .ok()
+            .unwrap()
+    );
+    let err = UnsupportedMediaTypeError::new("no codec", "text/html");
+    assert_eq!("UnsupportedMediaTypeError: no codec", err.to_string());
+}
+
+#[tokio::test]
+async fn test_codec_api() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::error::Error as StdError;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::bind_ureq;
+    use http_api_service::codec::{Codec, UnsupportedMediaTypeError, MEDIA_TYPE_JSON};
+    use http_api_service::simple_api::PathParam;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Product {
+        name: String,
+    }
+
+    // Upper case strings (a custom media type)
+    struct UpperCodec;
+    impl Codec<String> for UpperCodec {
+        fn encode(&self, value: &String) -> Result<Bytes, Box<dyn StdError>> {
+            Ok(Bytes::from(value.to_uppercase()))
+        }
+        fn decode(&self, bytes: &Bytes) -> Result<String, Box<dyn StdError>> {
+            Ok(std::str::from_utf8(bytes)?.to_uppercase())
+        }
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4010).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // "/echo" echoes the body with its Content-Type, the others respond by their paths
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let (parts, body_instance) = req.into_parts();
+            let response = Response::builder();
+            let response = match parts.uri.path() {
+                "/echo" => response.header(
+                    "Content-Type",
+                    parts.headers.get("Content-Type").unwrap().clone(),
+                ),
+                "/problem" => response.header("Content-Type", "application/problem+json"),
+                "/text" => response.header("Content-Type", "text/plain; charset=utf-8"),
+                "/html" => response.header("Content-Type", "text/html"),
+                _ => response,
+            };
+            let body = match parts.uri.path() {
+                "/echo" => Body::from(body::to_bytes(body_instance).await?),
+                "/problem" | "/none" => Body::from("{\"name\":\"json\"}"),
+                "/text" => Body::from("plain"),
+                _ => Body::from("<html></html>"),
+            };
+            Ok::<Response<Body>, hyper::Error>(response.body(body).unwrap())
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(base_url.clone());
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_setter
+        .get_codec_registry()
+        .register::<String>("application/x-upper", Arc::new(UpperCodec));
+
+    // By the content types
+    let api_post_echo = base_service_setter
+        .make_api_has_body_with_codec::<Product, Product>(
+            base_service_shared.clone(),
+            Method::POST,
+            "/echo",
+            MEDIA_TYPE_JSON,
+            &Product {
+                name: String::new(),
+            },
+        )
+        .ok()
+        .unwrap();
+    let product = api_post_echo
+        .call(
+            None::<PathParam>,
+            Product {
+                name: "Alice".to_string(),
+            },
+        )
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("Alice", product.name);
+    let api_post_upper = base_service_setter
+        .make_api_has_body_with_codec::<String, String>(
+            base_service_shared.clone(),
+            Method::POST,
+            "/echo",
+            "application/x-upper; charset=utf-8",
+            &String::new(),
+        )
+        .ok()
+        .unwrap();
+    let echoed = api_post_upper
+        .call(None::<PathParam>, "quiet".to_string())
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("QUIET", *echoed);
+    assert_eq!(
+        "text/html",
+        base_service_setter
+            .make_api_has_body_with_codec::<String, String>(
+                base_service_shared.clone(),
+                Method::POST,
+                "/echo",
+                "text/html",
+                &String::new(),
+            )
+            .err()
+            .unwrap()
+            .media_type
+    );
+
+    // The responses by their Content-Type
+    let make_api_get = |url: &str| {
+        base_service_setter
+            .make_api_no_body_with_codec::<Value>(
+                base_service_shared.clone(),
+                Method::GET,
+                url.to_string(),
+                MEDIA_TYPE_JSON,
+                &Value::Null,
+            )
+            .ok()
+            .unwrap()
+    };
+    let json = make_api_get("/problem").call(None).await.ok().unwrap();
+    assert_eq!("json", json["name"]);
+    let json = make_api_get("/none").call(None).await.ok().unwrap();
+    assert_eq!("json", json["name"]);
+    let text = make_api_get("/text").call(None).await.ok().unwrap();
+    assert_eq!(Value::String("plain".to_string()), *text);
+    let err = make_api_get("/html").call(None).await.err().unwrap();
+    assert_eq!(
+        "text/html",
+        err.downcast_ref::<UnsupportedMediaTypeError>()
+            .unwrap()
+            .media_type
+    );
+    // Mapped into the domain types
+    let product = make_api_get("/problem")
+        .map_into::<ProductName>()
+        .call(None)
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("json", product.0);
+
+    // By ureq
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    let base_service_setter_ureq = common_api_ureq.as_base_service_setter();
+    base_service_setter_ureq.set_base_url(base_url);
+    let api_get_text = base_service_setter_ureq
+        .make_api_no_body_with_codec::<Value>(
+            common_api_ureq.as_base_service_shared(),
+            Method::GET,
+            "/text",
+            MEDIA_TYPE_JSON,
+            &Value::Null,
+        )
+        .ok()
+        .unwrap();
+    let text = api_get_text.call(None).await.ok().unwrap();
+    assert_eq!(Value::String("plain".to_string()), *text);
+
+    hyper_latch.countdown();
+}
+
+struct ProductName(String);
+impl std::convert::TryFrom<serde_json::Value> for ProductName {
+    type Error = String;
+    fn try_from(value: serde_json::Value) -> Result<Self, Self::Error> {
+        match value["name"].as_str() {
+            Some(name) => Ok(ProductName(name.to_string())),
+            None => Err("no name".to_string()),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_codec_transcoding() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::error::Error as StdError;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+    use serde::Deserialize;
+    use serde_json::json;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::bind_ureq;
+    use http_api_service::codec::{Codec, MEDIA_TYPE_JSON, MEDIA_TYPE_TEXT};
+    use http_api_service::simple_api::{
+        ApiBuilder, PathParam, Versioning, DEFAULT_DUMMY_BYPASS_DESERIALIZER,
+    };
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Received {
+        content_type: String,
+        accept: String,
+        body: String,
+    }
+
+    // Upper case strings (a custom media type)
+    struct UpperCodec;
+    impl Codec<String> for UpperCodec {
+        fn encode(&self, value: &String) -> Result<Bytes, Box<dyn StdError>> {
+            Ok(Bytes::from(value.to_uppercase()))
+        }
+        fn decode(&self, bytes: &Bytes) -> Result<String, Box<dyn StdError>> {
+            Ok(std::str::from_utf8(bytes)?.to_uppercase())
+        }
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4020).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // "/received" responds what's received as JSON, "/none" responds the Accept without Content-Type
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let (parts, body_instance) = req.into_parts();
+            let header_of = |name: &str| {
+                parts
+                    .headers
+                    .get(name)
+                    .map(|v| v.to_str().unwrap().to_string())
+                    .unwrap_or_default()
+            };
+            let response = match parts.uri.path() {
+                "/received" => {
+                    let received = json!({
+                        "content_type": header_of("Content-Type"),
+                        "accept": header_of("Accept"),
+                        "body": String::from_utf8(body::to_bytes(body_instance).await?.to_vec()).unwrap(),
+                    });
+                    Response::builder()
+                        .header("Content-Type", MEDIA_TYPE_JSON)
+                        .body(Body::from(received.to_string()))
+                }
+                _ => Response::builder().body(Body::from(header_of("Accept"))),
+            };
+            Ok::<Response<Body>, hyper::Error>(response.unwrap())
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(base_url.clone());
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_setter
+        .get_codec_registry()
+        .register::<String>("application/x-upper", Arc::new(UpperCodec));
+
+    // Send the custom media type, receive JSON
+    let api_transcoding = base_service_setter
+        .make_api_transcoding::<String, Received>(
+            base_service_shared.clone(),
+            Method::POST,
+            "/received",
+            "application/x-upper",
+            MEDIA_TYPE_JSON,
+            &Received {
+                content_type: String::new(),
+                accept: String::new(),
+                body: String::new(),
+            },
+        )
+        .ok()
+        .unwrap();
+    assert_eq!(Some(MEDIA_TYPE_JSON), api_transcoding.accept.as_deref());
+    let received = api_transcoding
+        .call(None::<PathParam>, "shout".to_string())
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(
+        Received {
+            content_type: "application/x-upper".to_string(),
+            accept: MEDIA_TYPE_JSON.to_string(),
+            body: "SHOUT".to_string(),
+        },
+        *received
+    );
+
+    // Versioned by the media type parameters (for the API)
+    let registry = base_service_setter.get_codec_registry();
+    let api_versioned = ApiBuilder::new(Method::POST, "/received")
+        .content_type("application/x-upper")
+        .accept(MEDIA_TYPE_JSON)
+        .versioning(Versioning::media_type_parameter("version", "2"))
+        .serializer(registry.serializer("application/x-upper").ok().unwrap())
+        .deserializer(
+            registry
+                .deserializer::<Received>(MEDIA_TYPE_JSON)
+                .ok()
+                .unwrap(),
+        )
+        .build(base_service_shared.clone());
+    let received = api_versioned
+        .call(None::<PathParam>, "v2".to_string())
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("application/x-upper; version=2", received.content_type);
+    assert_eq!("application/json; version=2", received.accept);
+
+    // The responses without Content-Type are decoded by the codec of the Accept
+    let api_none = base_service_setter
+        .make_api_transcoding::<String, String>(
+            base_service_shared.clone(),
+            Method::POST,
+            "/none",
+            MEDIA_TYPE_JSON,
+            "application/x-upper",
+            &String::new(),
+        )
+        .ok()
+        .unwrap();
+    let accepted = api_none
+        .call(None::<PathParam>, "ignored".to_string())
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("APPLICATION/X-UPPER", *accepted);
+
+    // Unsupported
+    assert_eq!(
+        "application/xml",
+        base_service_setter
+            .make_api_transcoding::<String, String>(
+                base_service_shared.clone(),
+                Method::POST,
+                "/received",
+                MEDIA_TYPE_JSON,
+                "application/xml",
+                &String::new(),
+            )
+            .err()
+            .unwrap()
+            .media_type
+    );
+
+    // The APIs without Accept don't send it
+    let api_plain = base_service_setter.make_api_no_body(
+        base_service_shared.clone(),
+        Method::GET,
+        "/none",
+        Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &Bytes::new(),
+    );
+    assert_eq!(Bytes::new(), *api_plain.call(None).await.ok().unwrap());
+
+    // By ureq (and ApiBuilder)
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    let base_service_setter_ureq = common_api_ureq.as_base_service_setter();
+    base_service_setter_ureq.set_base_url(base_url);
+    let registry = base_service_setter_ureq.get_codec_registry();
+    let api_get_text = ApiBuilder::new(Method::GET, "/none")
+        .accept(MEDIA_TYPE_TEXT)
+        .deserializer(
+            registry
+                .deserializer::<String>(MEDIA_TYPE_TEXT)
+                .ok()
+                .unwrap(),
+        )
+        .build_no_body(common_api_ureq.as_base_service_shared());
+    let accepted = api_get_text.call(None).await.ok().unwrap();
+    assert_eq!(MEDIA_TYPE_TEXT, *accepted);
+
+    hyper_latch.countdown();
+}
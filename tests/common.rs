@@ -0,0 +1,1906 @@
+extern crate futures;
+
+extern crate http_api_service;
+
+#[test]
+fn test_async_write_for_stream_to_async_read() {
+    use futures::executor::block_on;
+    use futures::io::{AsyncReadExt, AsyncWriteExt};
+    use futures::StreamExt;
+
+    use http_api_service::common::{make_stream, stream_to_async_read, AsyncWriteForStream};
+
+    block_on(async {
+        let (tx, rx) = make_stream::<bytes::Bytes>();
+        let mut writer = AsyncWriteForStream(tx);
+
+        let write = async move {
+            for i in 0..100 {
+                writer
+                    .write_all(format!("line {}\n", i).as_bytes())
+                    .await
+                    .unwrap();
+            }
+            writer.close().await.unwrap();
+        };
+        let read = async move {
+            let mut reader = stream_to_async_read(rx.map(Ok::<_, std::io::Error>));
+            let mut result = String::new();
+            reader.read_to_string(&mut result).await.unwrap();
+            result
+        };
+
+        let (_, result) = futures::future::join(write, read).await;
+        assert_eq!(100, result.lines().count());
+        assert_eq!("line 99", result.lines().last().unwrap());
+    });
+}
+
+#[test]
+fn test_async_write_for_stream_closed_receiver() {
+    use futures::executor::block_on;
+    use futures::io::AsyncWriteExt;
+
+    use http_api_service::common::{make_stream, AsyncWriteForStream};
+
+    block_on(async {
+        let (tx, rx) = make_stream::<bytes::Bytes>();
+        drop(rx);
+        let mut writer = AsyncWriteForStream(tx);
+
+        let err = writer.write_all(b"lost").await.err().unwrap();
+        assert_eq!(std::io::ErrorKind::BrokenPipe, err.kind());
+    });
+}
+
+#[test]
+fn test_async_read_to_stream() {
+    use futures::executor::block_on;
+    use futures::io::Cursor;
+    use futures::TryStreamExt;
+
+    use http_api_service::common::{async_read_to_stream, stream_to_async_read};
+
+    block_on(async {
+        let chunks: Vec<bytes::Bytes> = async_read_to_stream(Cursor::new(b"abcdefgh".to_vec()), 3)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(vec!["abc", "def", "gh"], chunks);
+
+        // Errors are propagated
+        let failing =
+            futures::stream::iter(vec![Ok(bytes::Bytes::from("ok")), Err("broken stream")]);
+        let chunks: Result<Vec<bytes::Bytes>, std::io::Error> =
+            async_read_to_stream(stream_to_async_read(failing), 0)
+                .try_collect()
+                .await;
+        assert_eq!("broken stream", chunks.err().unwrap().to_string());
+    });
+}
+
+#[test]
+fn test_streaming_body_writer_read() {
+    use std::io::{Read, Write};
+    use std::thread;
+
+    use http_api_service::common::StreamingBodyWriter;
+
+    let (mut writer, mut reader) = StreamingBodyWriter::channel(16);
+    let producer = thread::spawn(move || {
+        for i in 0..100 {
+            writer
+                .write_all(format!("line {}\n", i).as_bytes())
+                .unwrap();
+        }
+        writer.close();
+    });
+
+    let mut result = String::new();
+    reader.read_to_string(&mut result).unwrap();
+    producer.join().unwrap();
+    assert_eq!(100, result.lines().count());
+    assert_eq!("line 99", result.lines().last().unwrap());
+}
+
+#[test]
+fn test_streaming_body_writer_abort() {
+    use std::error::Error as StdError;
+    use std::io::{self, Read, Write};
+    use std::thread;
+
+    use futures::executor::block_on;
+    use futures::StreamExt;
+
+    use http_api_service::common::{BodyProducerError, StreamingBodyWriter};
+
+    // Stream side
+    let (mut writer, mut reader) = StreamingBodyWriter::channel(0);
+    writer.write_all(b"partial").unwrap();
+    writer.abort("broken producer");
+    assert!(writer.write_all(b"more").is_err());
+    block_on(async {
+        let first = reader.next().await.unwrap();
+        assert_eq!(
+            "BodyProducerError: broken producer",
+            first.err().unwrap().to_string()
+        );
+        assert!(reader.next().await.is_none());
+    });
+
+    // Read side
+    let (mut writer, mut reader) = StreamingBodyWriter::channel(0);
+    let producer = thread::spawn(move || {
+        writer.write_all(b"partial").unwrap();
+        writer.abort("broken producer");
+    });
+    let mut result = Vec::new();
+    let err = reader.read_to_end(&mut result).err().unwrap();
+    producer.join().unwrap();
+    assert_eq!("BodyProducerError: broken producer", err.to_string());
+
+    // The typed error of the producer is the source
+    let (writer, mut reader) = StreamingBodyWriter::channel(0);
+    writer.abort_with_source(
+        "write_formdata",
+        io::Error::new(io::ErrorKind::NotFound, "missing part"),
+    );
+    let err = reader.read_to_end(&mut Vec::new()).err().unwrap();
+    let producer_error = BodyProducerError::find_in(&err).unwrap();
+    assert_eq!(
+        "BodyProducerError: write_formdata",
+        producer_error.to_string()
+    );
+    let source = producer_error.source().unwrap();
+    assert_eq!(
+        io::ErrorKind::NotFound,
+        source.downcast_ref::<io::Error>().unwrap().kind()
+    );
+
+    // Dropped reader
+    let (mut writer, reader) = StreamingBodyWriter::channel(4);
+    writer.write_all(b"full").unwrap();
+    let producer = thread::spawn(move || writer.write_all(b"blocked").err().unwrap().kind());
+    drop(reader);
+    assert_eq!(std::io::ErrorKind::BrokenPipe, producer.join().unwrap());
+}
+
+#[test]
+fn test_priority_limiter() {
+    use std::sync::{Arc, Mutex};
+
+    use futures::executor::block_on;
+    use futures::future::{join, join_all};
+    use futures::FutureExt;
+
+    use http_api_service::common::{Priority, PriorityLimiter};
+
+    let limiter = Arc::new(PriorityLimiter::new(1));
+    let order = Arc::new(Mutex::new(Vec::new()));
+
+    block_on(async {
+        let first = limiter.acquire(Priority::Normal).await;
+        assert_eq!(1, limiter.running());
+
+        // The cancelled waiting is skipped
+        let cancelled = limiter.acquire(Priority::High);
+        assert!(Box::pin(cancelled).now_or_never().is_none());
+
+        let waiters = [
+            ("low", Priority::Low),
+            ("normal 1", Priority::Normal),
+            ("high", Priority::High),
+            ("normal 2", Priority::Normal),
+        ]
+        .iter()
+        .map(|(name, priority)| {
+            let limiter = limiter.clone();
+            let order = order.clone();
+            async move {
+                let _permit = limiter.acquire(*priority).await;
+                order.lock().unwrap().push(*name);
+            }
+        })
+        .collect::<Vec<_>>();
+
+        join(join_all(waiters), async move {
+            drop(first);
+        })
+        .await;
+    });
+
+    assert_eq!(
+        vec!["high", "normal 1", "normal 2", "low"],
+        *order.lock().unwrap()
+    );
+    assert_eq!(0, limiter.running());
+    assert_eq!(0, limiter.waiting());
+}
+
+#[test]
+fn test_retry_budget() {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use http_api_service::common::{MockClock, RetryBudget, RetryBudgetUsage};
+
+    // Retries may add at most 20% extra load
+    let clock = MockClock::new();
+    let budget =
+        RetryBudget::new_with_clock(0.2, 0, Duration::from_millis(200), Arc::new(clock.clone()));
+    assert!(!budget.try_withdraw());
+    for _ in 0..10 {
+        budget.deposit();
+    }
+    assert!(budget.try_withdraw());
+    assert!(budget.try_withdraw());
+    assert!(!budget.try_withdraw());
+    assert_eq!(
+        RetryBudgetUsage {
+            requests: 10,
+            retries: 2,
+            limit: 2,
+        },
+        budget.usage()
+    );
+
+    // The consumption slides out of the window
+    clock.advance(Duration::from_millis(250));
+    assert_eq!(RetryBudgetUsage::default(), budget.usage());
+
+    // The minimum retries are always allowed
+    let budget = RetryBudget::new(0.2, 1, Duration::from_secs(10));
+    assert!(budget.try_withdraw());
+    assert!(!budget.try_withdraw());
+}
+
+#[test]
+fn test_usage_quota() {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use http_api_service::common::{MockClock, QuotaAlarm, QuotaResource, QuotaUsage, UsageQuota};
+
+    let clock = MockClock::new();
+    let alarms = Arc::new(Mutex::new(Vec::<QuotaAlarm>::new()));
+    let alarms_for_hook = alarms.clone();
+    let quota = UsageQuota::new_with_clock(Duration::from_millis(200), Arc::new(clock.clone()))
+        .max_calls(4)
+        .max_bytes(1000)
+        .thresholds(vec![0.5, 1.0])
+        .hard_stop(true)
+        .on_alarm(move |alarm| alarms_for_hook.lock().unwrap().push(alarm.clone()));
+
+    assert!(quota.try_acquire().is_ok());
+    assert!(alarms.lock().unwrap().is_empty());
+    assert!(quota.try_acquire().is_ok());
+    assert_eq!(
+        vec![QuotaAlarm {
+            resource: QuotaResource::Calls,
+            threshold: 0.5,
+            used: 2,
+            limit: 4,
+        }],
+        alarms.lock().unwrap().drain(..).collect::<Vec<_>>()
+    );
+
+    // The bytes crossing both of the thresholds at once
+    quota.record_bytes(1200);
+    assert_eq!(
+        vec![(QuotaResource::Bytes, 0.5), (QuotaResource::Bytes, 1.0)],
+        alarms
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|alarm| (alarm.resource, alarm.threshold))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        QuotaUsage {
+            calls: 2,
+            bytes: 1200,
+        },
+        quota.usage()
+    );
+    // Hard stopped
+    let err = quota.try_acquire().err().unwrap();
+    assert_eq!(QuotaResource::Bytes, err.resource);
+    assert!(err.to_string().starts_with("QuotaExceededError: "));
+
+    // The usage slides out of the window, and the alarms are raised again
+    clock.advance(Duration::from_millis(250));
+    assert_eq!(QuotaUsage::default(), quota.usage());
+    for _ in 0..4 {
+        assert!(quota.try_acquire().is_ok());
+    }
+    assert_eq!(
+        QuotaResource::Calls,
+        quota.try_acquire().err().unwrap().resource
+    );
+    assert_eq!(
+        vec![(2, 0.5), (4, 1.0)],
+        alarms
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|alarm| (alarm.used, alarm.threshold))
+            .collect::<Vec<_>>()
+    );
+
+    // Only alarmed without the hard stop
+    let quota = UsageQuota::new(Duration::from_secs(10)).max_calls(1);
+    assert!(quota.try_acquire().is_ok());
+    assert!(quota.try_acquire().is_ok());
+    assert_eq!(2, quota.usage().calls);
+}
+
+#[test]
+fn test_maintenance_policy() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use futures::executor::block_on;
+
+    use http_api_service::common::{
+        Clock, MaintenanceAction, MaintenancePolicy, MaintenanceWindow, MockClock,
+    };
+
+    let hour = Duration::from_secs(3600);
+    let day_start = UNIX_EPOCH + hour * 24 * 100;
+
+    // The daily window (02:00~03:00) and the one across the midnight (23:00~01:00)
+    let window = MaintenanceWindow::daily(hour * 2, hour);
+    assert_eq!(None, window.end_of(day_start + hour));
+    assert_eq!(
+        Some(day_start + hour * 3),
+        window.end_of(day_start + hour * 2)
+    );
+    assert_eq!(None, window.end_of(day_start + hour * 3));
+    let window = MaintenanceWindow::daily(hour * 23, hour * 2);
+    assert_eq!(
+        Some(day_start + hour),
+        window.end_of(day_start + Duration::from_secs(60))
+    );
+    let window = MaintenanceWindow::once(day_start + hour * 5, day_start + hour * 6);
+    assert_eq!(
+        Some(day_start + hour * 6),
+        window.end_of(day_start + hour * 5)
+    );
+    assert_eq!(None, window.end_of(day_start + hour * 6));
+
+    // The chained windows are merged
+    let policy = MaintenancePolicy::new(
+        vec![
+            MaintenanceWindow::once(day_start + hour * 5, day_start + hour * 6),
+            MaintenanceWindow::daily(hour * 6, hour),
+        ],
+        MaintenanceAction::Reject,
+    );
+    assert_eq!(
+        Some(day_start + hour * 7),
+        policy.window_end(day_start + hour * 5)
+    );
+    assert_eq!(None, policy.window_end(day_start + hour * 7));
+
+    // Rejected or deferred by the clock
+    let clock = MockClock::new_auto_advance();
+    let now = clock.system_now();
+    let policy = MaintenancePolicy::new(
+        vec![MaintenanceWindow::once(now, now + hour)],
+        MaintenanceAction::Reject,
+    );
+    let e = block_on(policy.enter(&clock)).unwrap_err();
+    assert_eq!(now + hour, e.until);
+    assert_eq!(Duration::default(), clock.elapsed());
+
+    let policy = MaintenancePolicy {
+        action: MaintenanceAction::Defer,
+        ..policy
+    };
+    assert!(block_on(policy.enter(&clock)).is_ok());
+    assert_eq!(hour, clock.elapsed());
+    assert!(block_on(policy.enter(&clock)).is_ok());
+    assert_eq!(hour, clock.elapsed());
+}
+
+#[test]
+fn test_keep_alive_worker() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    use url::Url;
+
+    use http_api_service::common::{
+        KeepAliveMethod, KeepAliveOptions, KeepAliveProbe, KeepAliveStats, KeepAliveWorker,
+        MockClock,
+    };
+
+    let interval = Duration::from_secs(30);
+    let url = Url::parse("http://localhost/").unwrap();
+    // Advance the clock until the stats are matched
+    fn wait_for(
+        clock: &MockClock,
+        worker: &KeepAliveWorker,
+        done: impl Fn(&KeepAliveStats) -> bool,
+    ) {
+        for _ in 0..1000 {
+            if done(&worker.stats()) {
+                return;
+            }
+            clock.advance(Duration::from_secs(30));
+            thread::sleep(Duration::from_millis(2));
+        }
+        panic!("{:?}", worker.stats());
+    }
+
+    // Alive by any status, failed by the errors
+    let calls = Arc::new(AtomicUsize::new(0));
+    let calls_for_probe = calls.clone();
+    let probe: KeepAliveProbe = Arc::new(move |method, url, _timeout| {
+        assert_eq!(KeepAliveMethod::Options, method);
+        assert_eq!("http://localhost/", url.as_str());
+        let call = calls_for_probe.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move {
+            if call == 0 {
+                Ok(404)
+            } else {
+                Err("unreachable".into())
+            }
+        })
+    });
+    let clock = Arc::new(MockClock::new());
+    let options = KeepAliveOptions::new(interval).method(KeepAliveMethod::Options);
+    let worker = KeepAliveWorker::spawn(probe, url.clone(), options, clock.clone()).unwrap();
+    // Nothing is sent before the first interval
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(KeepAliveStats::default(), worker.stats());
+    wait_for(clock.as_ref(), &worker, |stats| stats.sent >= 2);
+    let stats = worker.stats();
+    assert!(stats.failed >= 1);
+    assert_eq!(Some(404), stats.last_status);
+    worker.stop();
+    let sent = calls.load(Ordering::SeqCst);
+    clock.advance(interval * 10);
+    thread::sleep(Duration::from_millis(20));
+    assert_eq!(sent, calls.load(Ordering::SeqCst));
+
+    // Timed out, and stopped even if the probe is in flight
+    let probe: KeepAliveProbe =
+        Arc::new(|_method, _url, _timeout| Box::pin(futures::future::pending()));
+    let clock = Arc::new(MockClock::new());
+    let options = KeepAliveOptions::new(interval).timeout(interval);
+    let worker = KeepAliveWorker::spawn(probe, url, options, clock.clone()).unwrap();
+    wait_for(clock.as_ref(), &worker, |stats| stats.failed >= 1);
+    assert_eq!(None, worker.stats().last_status);
+    drop(worker);
+}
+
+#[test]
+fn test_adaptive_limiter() {
+    use std::time::Duration;
+
+    use futures::executor::block_on;
+
+    use http_api_service::common::{AdaptiveConcurrencyOptions, AdaptiveLimiter, Priority};
+
+    let limiter = AdaptiveLimiter::new(AdaptiveConcurrencyOptions {
+        initial_limit: 4,
+        min_limit: 2,
+        max_limit: 5,
+        latency_tolerance: 2.0,
+        backoff_ratio: 0.5,
+    });
+    assert_eq!(4, limiter.limit());
+
+    block_on(async {
+        // The idle traffic doesn't grow the limit
+        let permit = limiter.acquire(Priority::Normal).await;
+        limiter.on_sample(Duration::from_millis(10), false);
+        drop(permit);
+        assert_eq!(4, limiter.limit());
+        assert_eq!(Some(Duration::from_millis(10)), limiter.min_latency());
+
+        // The busy traffic within the tolerance grows the limit
+        let permits = vec![
+            limiter.acquire(Priority::Normal).await,
+            limiter.acquire(Priority::Normal).await,
+        ];
+        limiter.on_sample(Duration::from_millis(15), false);
+        assert_eq!(5, limiter.limit());
+        limiter.on_sample(Duration::from_millis(20), false);
+        assert_eq!(5, limiter.limit());
+
+        // The high latency and dropped calls shrink the limit (down to `min_limit`)
+        limiter.on_sample(Duration::from_millis(50), false);
+        assert_eq!(2, limiter.limit());
+        limiter.on_sample(Duration::from_millis(10), true);
+        assert_eq!(2, limiter.limit());
+        drop(permits);
+    });
+    assert_eq!(0, limiter.limiter.running());
+}
+
+#[test]
+fn test_priority_limiter_set_max_concurrency() {
+    use std::sync::Arc;
+
+    use futures::executor::block_on;
+    use futures::FutureExt;
+
+    use http_api_service::common::{Priority, PriorityLimiter};
+
+    let limiter = Arc::new(PriorityLimiter::new(1));
+    block_on(async {
+        let first = limiter.acquire(Priority::Normal).await;
+        let mut second = Box::pin(limiter.acquire(Priority::Normal));
+        assert!((&mut second).now_or_never().is_none());
+
+        // The waiting call is admitted by raising the limit
+        limiter.set_max_concurrency(2);
+        let second = second.await;
+        assert_eq!(2, limiter.running());
+
+        // The running calls over the lowered limit are released without handing over
+        limiter.set_max_concurrency(1);
+        let mut third = Box::pin(limiter.acquire(Priority::Normal));
+        assert!((&mut third).now_or_never().is_none());
+        drop(first);
+        assert!((&mut third).now_or_never().is_none());
+        drop(second);
+        let third = third.await;
+        assert_eq!(1, limiter.running());
+        drop(third);
+    });
+    assert_eq!(0, limiter.running());
+}
+
+#[test]
+fn test_mock_clock() {
+    use std::time::Duration;
+
+    use futures::executor::block_on;
+    use futures::FutureExt;
+
+    use http_api_service::common::{Clock, MockClock};
+
+    let clock = MockClock::new();
+    let start = clock.now();
+    let start_system = clock.system_now();
+
+    // The sleeping completes after the clock is advanced past its deadline
+    let mut sleep = clock.sleep(Duration::from_secs(60));
+    assert!((&mut sleep).now_or_never().is_none());
+    clock.advance(Duration::from_secs(30));
+    assert!((&mut sleep).now_or_never().is_none());
+    clock.advance(Duration::from_secs(30));
+    assert!((&mut sleep).now_or_never().is_some());
+    assert_eq!(Duration::from_secs(60), clock.now() - start);
+    assert_eq!(
+        Duration::from_secs(60),
+        clock.system_now().duration_since(start_system).unwrap()
+    );
+
+    // The auto advance clock sleeps instantly
+    let clock = MockClock::new_auto_advance();
+    block_on(async {
+        clock.sleep(Duration::from_secs(3600)).await;
+        clock.sleep(Duration::from_secs(1)).await;
+    });
+    assert_eq!(Duration::from_secs(3601), clock.elapsed());
+}
+
+#[test]
+fn test_id_generator() {
+    use std::sync::Arc;
+
+    use hyper::{Body, Request};
+
+    use http_api_service::common::{
+        generate_id, set_id_generator, IdGenerator, SequentialIdGenerator, UuidV7IdGenerator,
+    };
+    use http_api_service::simple_http::{Interceptor, InterceptorFunc};
+
+    // UUIDv7 by default
+    let id = UuidV7IdGenerator.generate();
+    assert_eq!(36, id.len());
+    assert_eq!(Some('7'), id.chars().nth(14));
+    assert_ne!(id, UuidV7IdGenerator.generate());
+
+    // The deterministic ids
+    set_id_generator(Some(Arc::new(SequentialIdGenerator::new("id-"))));
+    assert_eq!("id-1", generate_id());
+    let interceptor = InterceptorFunc::new_for_id_header("X-Request-Id");
+    assert_eq!("id-2", interceptor.get_id());
+
+    let mut request = Request::new(Body::empty());
+    interceptor.intercept(&mut request).ok().unwrap();
+    assert_eq!("id-3", request.headers()["x-request-id"]);
+    // The existing id is kept
+    interceptor.intercept(&mut request).ok().unwrap();
+    assert_eq!("id-3", request.headers()["x-request-id"]);
+
+    set_id_generator(None);
+    assert_eq!(36, generate_id().len());
+}
+
+#[test]
+fn test_join_url() {
+    use http_api_service::bind_hyper;
+    use http_api_service::common::{join_url, QueryParam, UrlJoinError, UrlJoinStrategy};
+    use http_api_service::path_param;
+    use url::Url;
+
+    let base_url = Url::parse("https://host/api/v2").unwrap();
+    let join = |relative_url: &str, strategy: UrlJoinStrategy| {
+        join_url(&base_url, relative_url, strategy).map(|url| url.to_string())
+    };
+
+    // Standard: Url::join()
+    assert_eq!(
+        "https://host/products",
+        join("/products", UrlJoinStrategy::Standard).ok().unwrap()
+    );
+    assert_eq!(
+        "https://host/api/products",
+        join("products", UrlJoinStrategy::Standard).ok().unwrap()
+    );
+
+    // StrictAppend
+    for relative_url in ["/products?soft=true", "products?soft=true"].iter() {
+        assert_eq!(
+            "https://host/api/v2/products?soft=true",
+            join(relative_url, UrlJoinStrategy::StrictAppend)
+                .ok()
+                .unwrap()
+        );
+    }
+    let trailing_slash = Url::parse("https://host/api/v2/").unwrap();
+    assert_eq!(
+        "https://host/api/v2/products/3",
+        join_url(
+            &trailing_slash,
+            "/products/3",
+            UrlJoinStrategy::StrictAppend
+        )
+        .ok()
+        .unwrap()
+        .to_string()
+    );
+    assert_eq!(
+        "https://host/api/v2",
+        join("", UrlJoinStrategy::StrictAppend).ok().unwrap()
+    );
+    for relative_url in [
+        "https://other/products",
+        "//other/products",
+        "../v1/products",
+        "/products/../../admin",
+        // The encoded dots are resolved by Url::join() as well
+        "%2e%2e/v1/products",
+        "/products/.%2E/%2E./admin",
+        "/products/%2E%2e/..%2f/admin",
+        "products\\..\\..\\admin",
+    ]
+    .iter()
+    {
+        let err = join(relative_url, UrlJoinStrategy::StrictAppend)
+            .err()
+            .unwrap();
+        assert!(err.downcast_ref::<UrlJoinError>().is_some());
+        if !relative_url.starts_with("https:") {
+            assert!(join(relative_url, UrlJoinStrategy::AbsoluteAllowed).is_err());
+        }
+    }
+    // The other encoded dots are kept
+    assert_eq!(
+        "https://host/api/v2/products/%2e%2e%2e",
+        join("products/%2e%2e%2e", UrlJoinStrategy::StrictAppend)
+            .ok()
+            .unwrap()
+    );
+    // The .. in the query is fine
+    assert_eq!(
+        "https://host/api/v2/products?q=..",
+        join("products?q=..", UrlJoinStrategy::StrictAppend)
+            .ok()
+            .unwrap()
+    );
+
+    // RootRelative
+    assert_eq!(
+        "https://host/products",
+        join("/products", UrlJoinStrategy::RootRelative)
+            .ok()
+            .unwrap()
+    );
+    assert!(join("products", UrlJoinStrategy::RootRelative).is_err());
+    assert!(join("https://other/products", UrlJoinStrategy::RootRelative).is_err());
+    assert!(join("//other/products", UrlJoinStrategy::RootRelative).is_err());
+
+    // AbsoluteAllowed
+    assert_eq!(
+        "https://other/products",
+        join("https://other/products", UrlJoinStrategy::AbsoluteAllowed)
+            .ok()
+            .unwrap()
+    );
+    assert_eq!(
+        "https://host/api/v2/products",
+        join("/products", UrlJoinStrategy::AbsoluteAllowed)
+            .ok()
+            .unwrap()
+    );
+    assert!(join("//other/products", UrlJoinStrategy::AbsoluteAllowed).is_err());
+
+    // Per service
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_shared.set_base_url(base_url.clone());
+    assert_eq!(
+        UrlJoinStrategy::Standard,
+        base_service_shared.get_url_join_strategy()
+    );
+    base_service_shared.set_url_join_strategy(UrlJoinStrategy::StrictAppend);
+    let req = common_api
+        .simple_api
+        .lock()
+        .unwrap()
+        .make_request(
+            hyper::Method::GET,
+            "/products/{id}",
+            "",
+            Some(path_param!["id" => "3"]),
+            None::<QueryParam>,
+            hyper::Body::empty(),
+        )
+        .ok()
+        .unwrap();
+    assert_eq!("https://host/api/v2/products/3", req.uri().to_string());
+    // The path params can't escape the base path
+    let result = common_api.simple_api.lock().unwrap().make_request(
+        hyper::Method::GET,
+        "/products/{id}",
+        "",
+        Some(path_param!["id" => "../../admin"]),
+        None::<QueryParam>,
+        hyper::Body::empty(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_url_normalization() {
+    use http_api_service::bind_hyper;
+    use http_api_service::common::{QueryParam, UrlJoinStrategy, UrlNormalization};
+    use url::Url;
+
+    let normalize = |normalization: UrlNormalization, url: &str| {
+        let mut url = Url::parse(url).unwrap();
+        normalization.normalize(&mut url);
+        url.to_string()
+    };
+
+    // The hosts & the default ports are always normalized by Url
+    assert_eq!(
+        "https://host/api//v2?b=2&a=1",
+        normalize(UrlNormalization::new(), "https://HOST:443/api//v2?b=2&a=1")
+    );
+    assert_eq!(
+        "https://host/api/v2/?b=2&a=1",
+        normalize(
+            UrlNormalization::new().with_collapsed_slashes(),
+            "https://host//api///v2//?b=2&a=1"
+        )
+    );
+    assert_eq!(
+        "https://host/api//v2?a=1&b=2&b=1",
+        normalize(
+            UrlNormalization::new().with_sorted_query(),
+            "https://host/api//v2?b=2&a=1&b=1"
+        )
+    );
+    assert_eq!(
+        "https://host/api/v2#top",
+        normalize(UrlNormalization::all(), "https://host/api//v2#top")
+    );
+
+    // Per service
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_shared.set_base_url(Url::parse("https://HOST:443/api/").unwrap());
+    base_service_shared.set_url_join_strategy(UrlJoinStrategy::StrictAppend);
+    let make_request = || {
+        common_api
+            .simple_api
+            .lock()
+            .unwrap()
+            .make_request(
+                hyper::Method::GET,
+                "/v2//products/?b=2&a=1",
+                "",
+                None::<QueryParam>,
+                None::<QueryParam>,
+                hyper::Body::empty(),
+            )
+            .ok()
+            .unwrap()
+    };
+    assert_eq!(
+        "https://host/api/v2//products/?b=2&a=1",
+        make_request().uri().to_string()
+    );
+    assert_eq!(
+        UrlNormalization::default(),
+        base_service_shared.get_url_normalization()
+    );
+    base_service_shared.set_url_normalization(UrlNormalization::all());
+    assert_eq!(
+        "https://host/api/v2/products/?a=1&b=2",
+        make_request().uri().to_string()
+    );
+}
+
+#[test]
+fn test_versioning() {
+    use futures::executor::block_on;
+
+    use http_api_service::bind_hyper;
+    use http_api_service::bind_ureq;
+    use http_api_service::common::{with_extensions, Extensions, QueryParam, Versioning};
+    use http_api_service::path_param;
+    use url::Url;
+
+    // Path prefixes
+    let versioning = Versioning::path_prefix("/v2/");
+    assert_eq!("/v2/products", versioning.apply_to_url("/products"));
+    assert_eq!("v2/products?q=1", versioning.apply_to_url("products?q=1"));
+    assert_eq!(
+        "https://other/products",
+        versioning.apply_to_url("https://other/products")
+    );
+    assert_eq!("/products", Versioning::None.apply_to_url("/products"));
+    // Media type parameters
+    let versioning = Versioning::media_type_parameter("version", "2");
+    assert_eq!(
+        "application/json; version=2",
+        versioning.apply_to_media_type("application/json")
+    );
+    assert_eq!("", versioning.apply_to_media_type(""));
+    assert_eq!(
+        "multipart/form-data; boundary=x",
+        versioning.apply_to_media_type("multipart/form-data; boundary=x")
+    );
+    assert_eq!(None, versioning.get_header());
+    assert_eq!(
+        Some(("Accept-Version", "2")),
+        Versioning::header("Accept-Version", "2").get_header()
+    );
+
+    // Per service
+    let base_url = Url::parse("https://host/api/").unwrap();
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_shared.set_base_url(base_url.clone());
+    assert_eq!(Versioning::None, base_service_shared.get_versioning());
+    base_service_shared.set_versioning(Versioning::path_prefix("v2"));
+    let make_request = || {
+        common_api
+            .simple_api
+            .lock()
+            .unwrap()
+            .make_request(
+                hyper::Method::POST,
+                "products/{id}",
+                "application/json",
+                Some(path_param!["id" => "3"]),
+                None::<QueryParam>,
+                hyper::Body::empty(),
+            )
+            .ok()
+            .unwrap()
+    };
+    let req = make_request();
+    assert_eq!("https://host/api/v2/products/3", req.uri().to_string());
+    base_service_shared.set_versioning(Versioning::header("Accept-Version", "2"));
+    let req = make_request();
+    assert_eq!("https://host/api/products/3", req.uri().to_string());
+    assert_eq!("2", req.headers()["accept-version"]);
+    base_service_shared.set_versioning(Versioning::media_type_parameter("version", "2"));
+    let req = make_request();
+    assert_eq!("application/json; version=2", req.headers()["content-type"]);
+
+    // Overridden by the APIs (by the extensions of the calls)
+    let mut extensions = Extensions::new();
+    extensions.insert(Versioning::None);
+    let req = block_on(with_extensions(extensions, async { make_request() }));
+    assert_eq!("application/json", req.headers()["content-type"]);
+
+    // By ureq
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    let base_service_ureq = common_api_ureq.as_base_service_shared();
+    base_service_ureq.set_base_url(base_url);
+    base_service_ureq.set_versioning(Versioning::path_prefix("v3"));
+    let mut extensions = Extensions::new();
+    extensions.insert(Versioning::header("Accept-Version", "1"));
+    let req = common_api_ureq
+        .simple_api
+        .lock()
+        .unwrap()
+        .make_request(
+            hyper::Method::GET,
+            "/products",
+            "",
+            None::<QueryParam>,
+            None::<QueryParam>,
+            bytes::Bytes::new(),
+        )
+        .ok()
+        .unwrap();
+    assert_eq!("https://host/v3/products", req.url.to_string());
+    let req = block_on(with_extensions(extensions, async {
+        common_api_ureq
+            .simple_api
+            .lock()
+            .unwrap()
+            .make_request(
+                hyper::Method::GET,
+                "/products",
+                "",
+                None::<QueryParam>,
+                None::<QueryParam>,
+                bytes::Bytes::new(),
+            )
+            .ok()
+            .unwrap()
+    }));
+    assert_eq!("https://host/products", req.url.to_string());
+    assert_eq!(Some("1"), req.headers.get("Accept-Version"));
+}
+
+#[test]
+fn test_template_variables() {
+    use futures::executor::block_on;
+
+    use http_api_service::bind_hyper;
+    use http_api_service::bind_ureq;
+    use http_api_service::common::{
+        with_extensions, Extensions, HasRequestOptions, QueryParam, TemplateVariableError,
+        TemplateVariables,
+    };
+    use http_api_service::path_param;
+    use url::Url;
+
+    std::env::set_var("HTTP_API_SERVICE_TEST_REGION", "eu");
+
+    // By the values set, or the environment variables
+    let variables = TemplateVariables::new().set("ctx.tenant", "acme");
+    assert_eq!(
+        "/eu/acme/products/{id}",
+        variables
+            .apply_to_url("/{env.HTTP_API_SERVICE_TEST_REGION}/{ctx.tenant}/products/{id}")
+            .ok()
+            .unwrap()
+    );
+    assert_eq!(
+        "/us/products",
+        variables
+            .merge(&TemplateVariables::new().set("env.HTTP_API_SERVICE_TEST_REGION", "us"))
+            .apply_to_url("/{env.HTTP_API_SERVICE_TEST_REGION}/products")
+            .ok()
+            .unwrap()
+    );
+    assert_eq!(
+        "/{unclosed",
+        variables.apply_to_url("/{unclosed").ok().unwrap()
+    );
+    let err = variables
+        .apply_to_url("/{env.HTTP_API_SERVICE_TEST_UNKNOWN}/products")
+        .err()
+        .unwrap();
+    assert_eq!("env.HTTP_API_SERVICE_TEST_UNKNOWN", err.name);
+    assert!(variables.apply_to_url("/{ctx.unknown}").is_err());
+
+    // Per service, the path params are applied after the variables
+    let base_url = Url::parse("https://host/api/").unwrap();
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_shared.set_base_url(base_url.clone());
+    base_service_shared.set_template_variables(variables.clone());
+    assert_eq!(variables, base_service_shared.get_template_variables());
+    let make_request = || {
+        common_api.simple_api.lock().unwrap().make_request(
+            hyper::Method::GET,
+            "{env.HTTP_API_SERVICE_TEST_REGION}/{ctx.tenant}/products/{id}",
+            "",
+            Some(path_param!["id" => "{ctx.tenant}"]),
+            None::<QueryParam>,
+            hyper::Body::empty(),
+        )
+    };
+    let req = make_request().ok().unwrap();
+    assert_eq!(
+        "https://host/api/eu/acme/products/%7Bctx.tenant%7D",
+        req.uri().to_string()
+    );
+    // The url template is kept as it is
+    assert_eq!(
+        Some("{env.HTTP_API_SERVICE_TEST_REGION}/{ctx.tenant}/products/{id}".to_string()),
+        req.get_request_options().url_template
+    );
+
+    // Overridden by the scoped ones
+    let mut extensions = Extensions::new();
+    extensions.insert(TemplateVariables::new().set("ctx.tenant", "other"));
+    let req = block_on(with_extensions(extensions, async { make_request() }))
+        .ok()
+        .unwrap();
+    assert!(req.uri().path().starts_with("/api/eu/other/"));
+
+    // By ureq, the unresolved ones fail the calls
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    let base_service_ureq = common_api_ureq.as_base_service_shared();
+    base_service_ureq.set_base_url(base_url);
+    let err = common_api_ureq
+        .simple_api
+        .lock()
+        .unwrap()
+        .make_request(
+            hyper::Method::GET,
+            "/{ctx.tenant}/products",
+            "",
+            None::<QueryParam>,
+            None::<QueryParam>,
+            bytes::Bytes::new(),
+        )
+        .err()
+        .unwrap();
+    assert!(err.downcast_ref::<TemplateVariableError>().is_some());
+}
+
+#[test]
+fn test_content_disposition_filename() {
+    use http_api_service::any_service::AnyResponse;
+    use http_api_service::common::{
+        parse_content_disposition_filename, sanitize_filename, Headers, MAX_FILENAME_LENGTH,
+    };
+
+    let parse = parse_content_disposition_filename;
+    assert_eq!(
+        Some("report.pdf".to_string()),
+        parse("attachment; filename=\"report.pdf\"")
+    );
+    assert_eq!(
+        Some("report.pdf".to_string()),
+        parse("attachment;filename=report.pdf; size=3")
+    );
+    assert_eq!(
+        Some("a _quoted_; name.txt".to_string()),
+        parse("attachment; filename=\"a \\\"quoted\\\"; name.txt\"; size=3")
+    );
+    // The ext-values take precedence
+    assert_eq!(
+        Some("naïve résumé.pdf".to_string()),
+        parse("attachment; filename=\"resume.pdf\"; filename*=UTF-8''na%C3%AFve%20r%C3%A9sum%C3%A9.pdf")
+    );
+    assert_eq!(
+        Some("£ rates.txt".to_string()),
+        parse("attachment; filename*=iso-8859-1'en'%A3%20rates.txt")
+    );
+    // The invalid ext-values fall back to the filenames
+    assert_eq!(
+        Some("resume.pdf".to_string()),
+        parse("attachment; filename*=UTF-8''%FF%; filename=resume.pdf")
+    );
+    assert_eq!(
+        Some("a.txt".to_string()),
+        parse("attachment; foo; filename=a.txt")
+    );
+    assert_eq!(None, parse("inline"));
+    assert_eq!(None, parse("attachment; filename=\"..\""));
+    assert_eq!(
+        Some("unterminated".to_string()),
+        parse("attachment; filename=\"unterminated")
+    );
+
+    // Sanitized
+    assert_eq!(
+        Some("passwd".to_string()),
+        parse("attachment; filename=\"../../etc/passwd\"")
+    );
+    assert_eq!(
+        Some("evil.exe".to_string()),
+        sanitize_filename("C:\\Windows\\..\\evil.exe")
+    );
+    assert_eq!(
+        Some("a_b_c.txt".to_string()),
+        sanitize_filename("a:b\nc.txt")
+    );
+    assert_eq!(Some("hidden".to_string()), sanitize_filename(" .hidden. "));
+    let long = "é".repeat(200) + ".tar.gz";
+    let sanitized = sanitize_filename(&long).unwrap();
+    assert!(sanitized.len() <= MAX_FILENAME_LENGTH);
+    assert!(sanitized.ends_with("é.gz"));
+
+    // By the responses
+    let mut headers = Headers::new();
+    headers.insert("content-disposition", "attachment; filename=data.csv");
+    let response = AnyResponse {
+        status: 200,
+        headers,
+        body: bytes::Bytes::new(),
+    };
+    assert_eq!(Some("data.csv".to_string()), response.file_name());
+}
+
+#[test]
+fn test_sniff_content_type() {
+    use formdata::FormData;
+
+    use http_api_service::common::sniff_content_type;
+    use http_api_service::simple_api::{FormDataExt, FormFile};
+
+    assert_eq!(
+        Some("image/png"),
+        sniff_content_type(b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR")
+    );
+    assert_eq!(Some("image/jpeg"), sniff_content_type(b"\xff\xd8\xff\xe0"));
+    assert_eq!(Some("application/pdf"), sniff_content_type(b"%PDF-1.7"));
+    assert_eq!(
+        Some("image/webp"),
+        sniff_content_type(b"RIFF\x00\x00\x00\x00WEBPVP8 ")
+    );
+    assert_eq!(
+        Some("audio/wav"),
+        sniff_content_type(b"RIFF\x00\x00\x00\x00WAVEfmt ")
+    );
+    assert_eq!(
+        Some("video/mp4"),
+        sniff_content_type(b"\x00\x00\x00\x18ftypmp42")
+    );
+    assert_eq!(None, sniff_content_type(b"RIFF\x00\x00\x00\x00AVI "));
+    assert_eq!(None, sniff_content_type(b"{\"name\": \"Baxter\"}"));
+    assert_eq!(None, sniff_content_type(b""));
+
+    // The file parts without the content types
+    let path = std::env::temp_dir().join("http_api_service_test_sniff.bin");
+    std::fs::write(&path, b"GIF89a\x01\x00\x01\x00")
+        .ok()
+        .unwrap();
+    let form_data = FormData::new()
+        .with_file("sniffed", FormFile::new(&path))
+        .with_file("specified", FormFile::new(&path).content_type("text/plain"))
+        .with_file("missing", FormFile::new(path.with_extension("missing")));
+    let content_types = form_data
+        .files
+        .iter()
+        .map(|(_, file)| file.content_type().map(|v| v.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            Some("image/gif".to_string()),
+            Some("text/plain".to_string()),
+            None
+        ],
+        content_types
+    );
+    std::fs::remove_file(&path).ok().unwrap();
+}
+
+#[test]
+fn test_header_value_builder() {
+    use std::convert::TryFrom;
+
+    use http_api_service::common::{
+        add_header_authentication_bearer, HeaderValueBuilder, Headers, InvalidHeaderError,
+    };
+    use http_api_service::scrub::{HeaderScrubber, Scrubber};
+
+    // Validation with clear errors (without the values)
+    assert_eq!(
+        "abc def\t~",
+        HeaderValueBuilder::new("abc def\t~").build().ok().unwrap()
+    );
+    let err = HeaderValueBuilder::new("tok\u{e9}n").build().err().unwrap();
+    assert_eq!(
+        "InvalidHeaderError: invalid character '\u{e9}' at 3 of the header value (percent_encode() could encode it)",
+        err.to_string()
+    );
+    assert!(HeaderValueBuilder::new("a\r\nInjected: 1").build().is_err());
+    let err = add_header_authentication_bearer(Headers::new(), "tok\u{e9}n")
+        .err()
+        .unwrap();
+    assert!(err.downcast_ref::<InvalidHeaderError>().is_some());
+    assert!(!err.to_string().contains("tok"));
+
+    // Percent-encoding
+    assert_eq!(
+        "tok%C3%A9n%2520%0D%0A",
+        HeaderValueBuilder::new("tok\u{e9}n%20\r\n")
+            .percent_encode(true)
+            .build()
+            .ok()
+            .unwrap()
+    );
+
+    // Names
+    let mut headers = Headers::new();
+    assert!(HeaderValueBuilder::new("v")
+        .insert_into(&mut headers, "Bad Name")
+        .is_err());
+    assert!(HeaderValueBuilder::new("v")
+        .insert_into(&mut headers, "")
+        .is_err());
+
+    // Sensitive headers are masked in the Debug output
+    let mut headers = add_header_authentication_bearer(Headers::new(), "MY_TOKEN")
+        .ok()
+        .unwrap();
+    HeaderValueBuilder::new("secret")
+        .sensitive(true)
+        .insert_into(&mut headers, "X-Api-Secret")
+        .ok()
+        .unwrap();
+    headers.insert("X-Trace", "visible");
+    assert!(headers.is_sensitive("authorization"));
+    assert_eq!(Some("Bearer MY_TOKEN"), headers.get("Authorization"));
+    let debug = format!("{:?}", headers);
+    assert!(!debug.contains("MY_TOKEN"));
+    assert!(!debug.contains("secret"));
+    assert!(debug.contains("visible"));
+    assert!(debug.contains("[REDACTED]"));
+
+    // The marks are kept by the hyper headers
+    let header_map = hyper::HeaderMap::try_from(&headers).ok().unwrap();
+    assert!(header_map.get("authorization").unwrap().is_sensitive());
+    assert!(!header_map.get("x-trace").unwrap().is_sensitive());
+    let converted = Headers::from(&header_map);
+    assert!(converted.is_sensitive("X-Api-Secret"));
+    assert_eq!(Some("Bearer MY_TOKEN"), converted.get("Authorization"));
+
+    // The obs-text (non UTF-8) values round-trip by their bytes
+    let mut header_map = hyper::HeaderMap::new();
+    header_map.insert(
+        "x-legacy",
+        hyper::header::HeaderValue::from_bytes(b"caf\xe9").unwrap(),
+    );
+    header_map.append("x-legacy", hyper::header::HeaderValue::from_static("plain"));
+    let converted = Headers::from(&header_map);
+    assert_eq!(Some("caf\u{fffd}"), converted.get("X-Legacy"));
+    assert_eq!(Some(&b"caf\xe9"[..]), converted.get_bytes("X-Legacy"));
+    assert_eq!(b"plain", converted.value_bytes("X-Legacy", "plain"));
+    let round_trip = hyper::HeaderMap::try_from(&converted).ok().unwrap();
+    assert_eq!(header_map, round_trip);
+
+    // The scrubber redacts the sensitive headers
+    let mut scrubbed = headers.clone();
+    HeaderScrubber::new(Vec::<String>::new()).scrub_headers(&mut scrubbed);
+    assert_eq!(Some("[REDACTED]"), scrubbed.get("X-Api-Secret"));
+    assert_eq!(Some("visible"), scrubbed.get("X-Trace"));
+}
+
+#[test]
+fn test_extensions() {
+    use http_api_service::common::{Extensions, RequestOptions, RetryAttempt};
+
+    #[derive(Debug, PartialEq)]
+    struct Tenant(String);
+
+    let mut options = RequestOptions::default();
+    assert!(options.extensions.is_empty());
+    options.extensions.insert(Tenant("tenant-a".to_string()));
+    options.extensions.insert(RetryAttempt(0));
+    assert_eq!(
+        Some(RetryAttempt(0)),
+        options.extensions.insert(RetryAttempt(1)).map(|v| *v)
+    );
+    assert_eq!(2, options.extensions.len());
+    assert_eq!(
+        Some(&Tenant("tenant-a".to_string())),
+        options.extensions.get::<Tenant>()
+    );
+    assert_eq!(
+        Some(&RetryAttempt(1)),
+        options.extensions.get::<RetryAttempt>()
+    );
+    assert!(!options.extensions.contains::<String>());
+
+    // The clones share the values
+    let cloned = options.clone();
+    assert_eq!(options, cloned);
+    options.extensions.insert(Tenant("tenant-a".to_string()));
+    assert_ne!(options, cloned);
+
+    // Extend & remove
+    let mut extensions = Extensions::new();
+    extensions.insert(RetryAttempt(2));
+    options.extensions.extend(extensions);
+    assert_eq!(
+        Some(&RetryAttempt(2)),
+        options.extensions.get::<RetryAttempt>()
+    );
+    assert_eq!(
+        Some(RetryAttempt(2)),
+        options.extensions.remove::<RetryAttempt>().map(|v| *v)
+    );
+    assert_eq!(None, options.extensions.get::<RetryAttempt>());
+    assert!(format!("{:?}", options.extensions).contains("Tenant"));
+}
+
+#[test]
+fn test_credential_rotation() {
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use http_api_service::common::{
+        Clock, CredentialRotation, Headers, MockClock, RotationStrategy, MAX_RATE_LIMIT_RESET,
+    };
+
+    // The names & values are validated
+    assert!(CredentialRotation::new("X Api Key", vec!["a"], RotationStrategy::RoundRobin).is_err());
+    assert!(
+        CredentialRotation::new("X-Api-Key", vec!["a\nb"], RotationStrategy::RoundRobin).is_err()
+    );
+
+    let clock = MockClock::new();
+    let start = clock.now();
+    let mut rotation = CredentialRotation::new(
+        "X-Api-Key",
+        vec!["a", "b", "c"],
+        RotationStrategy::RoundRobin,
+    )
+    .ok()
+    .unwrap();
+    rotation.clock = Arc::new(clock.clone());
+    let next = |rotation: &CredentialRotation| rotation.next().map(|(i, _)| i);
+
+    // Round-robin
+    assert_eq!(Some((0, "a".to_string())), rotation.next());
+    assert_eq!(Some(1), next(&rotation));
+
+    // The rejected one is skipped until the Retry-After
+    let mut headers = Headers::new();
+    headers.insert("Retry-After", "30");
+    rotation.report(1, 429, &headers);
+    assert_eq!(Some(2), next(&rotation));
+    assert_eq!(Some(0), next(&rotation));
+    assert_eq!(Some(2), next(&rotation));
+    let states = rotation.states();
+    assert_eq!(1, states[1].rejections);
+    assert_eq!(
+        Some(start + Duration::from_secs(30)),
+        states[1].limited_until
+    );
+    assert_eq!(
+        vec![2, 1, 2],
+        states.iter().map(|v| v.uses).collect::<Vec<_>>()
+    );
+
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(Some(0), next(&rotation));
+    assert_eq!(Some(1), next(&rotation));
+    assert_eq!(None, rotation.states()[1].limited_until);
+
+    // The exhausted one is skipped until the X-RateLimit-Reset (epoch seconds)
+    let reset_at = clock.system_now() + Duration::from_secs(10);
+    let mut headers = Headers::new();
+    headers.insert("X-RateLimit-Remaining", "0");
+    headers.insert(
+        "X-RateLimit-Reset",
+        reset_at
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .unwrap()
+            .as_secs()
+            .to_string(),
+    );
+    rotation.report(2, 200, &headers);
+    let state = rotation.states()[2].clone();
+    assert_eq!(Some(0), state.remaining);
+    assert_eq!(0, state.rejections);
+    let limited_for = state.limited_until.unwrap() - clock.now();
+    assert!(limited_for <= Duration::from_secs(10) && limited_for > Duration::from_secs(8));
+    assert_eq!(Some(0), next(&rotation));
+    assert_eq!(Some(1), next(&rotation));
+
+    // Keep using the current one until it's rejected
+    let mut rotation = CredentialRotation::new(
+        "Authorization",
+        vec!["Bearer a", "Bearer b"],
+        RotationStrategy::OnRejection,
+    )
+    .ok()
+    .unwrap();
+    rotation.clock = Arc::new(clock.clone());
+    assert_eq!(Some(0), next(&rotation));
+    assert_eq!(Some(0), next(&rotation));
+    rotation.report(0, 403, &Headers::new());
+    let mut headers = Headers::new();
+    assert_eq!(Some(1), rotation.apply(&mut headers));
+    assert_eq!(Some("Bearer b"), headers.get("Authorization"));
+    assert!(headers.is_sensitive("Authorization"));
+
+    // All limited: the one available soonest (not the cooldown one)
+    let mut headers = Headers::new();
+    headers.insert("Retry-After", "5");
+    rotation.report(1, 429, &headers);
+    assert_eq!(Some(1), next(&rotation));
+
+    // The huge resets are clamped (instead of overflowing)
+    for reset in ["999999999", "18446744073709551615"] {
+        let mut headers = Headers::new();
+        headers.insert("Retry-After", reset);
+        rotation.report(0, 429, &headers);
+        let limited_for = rotation.states()[0].limited_until.unwrap() - clock.now();
+        assert!(limited_for <= MAX_RATE_LIMIT_RESET);
+    }
+}
+
+#[test]
+fn test_cookie_jar() {
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use http_api_service::common::{Cookie, CookieJar, MockClock};
+    use url::Url;
+
+    let url = Url::parse("https://api.example.com/v1/login").ok().unwrap();
+    let now = UNIX_EPOCH + Duration::from_secs(1_445_412_000);
+
+    // The defaults & the attributes
+    let cookie = Cookie::parse("sid=abc; HttpOnly", &url, now).unwrap();
+    assert_eq!("api.example.com", cookie.domain);
+    assert!(cookie.host_only);
+    assert_eq!("/v1", cookie.path);
+    assert_eq!(None, cookie.expires);
+    let cookie = Cookie::parse(
+        "pref=\"dark\"; Domain=.Example.com; Path=/; Secure; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+        &url,
+        now,
+    )
+    .unwrap();
+    assert_eq!("dark", cookie.value);
+    assert_eq!("example.com", cookie.domain);
+    assert!(!cookie.host_only && cookie.secure);
+    assert_eq!(
+        Some(UNIX_EPOCH + Duration::from_secs(1_445_412_480)),
+        cookie.expires
+    );
+    let cookie = Cookie::parse(
+        "a=1; Max-Age=60; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+        &url,
+        now,
+    )
+    .unwrap();
+    assert_eq!(Some(now + Duration::from_secs(60)), cookie.expires);
+    // The overflowing/out of range ones don't panic
+    let cookie = Cookie::parse(
+        "a=1; Max-Age=9223372036854775807; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+        &url,
+        now,
+    )
+    .unwrap();
+    assert_eq!(None, cookie.expires);
+    for expires in [
+        "Wed, 21 Oct 9223372036854775807 07:28:00 GMT",
+        "Wed, 21 Oct -9223372036854775808 07:28:00 GMT",
+        "Wed, 9223372036854775807 Oct 2015 07:28:00 GMT",
+        "Wed, 21 Oct 2015 9223372036854775807:28:00 GMT",
+    ] {
+        let cookie = Cookie::parse(&format!("a=1; Expires={}", expires), &url, now).unwrap();
+        assert!(cookie.expires.is_none() || cookie.expires == Some(UNIX_EPOCH));
+    }
+    assert_eq!(None, Cookie::parse("a=1; Domain=other.com", &url, now));
+    assert_eq!(None, Cookie::parse("invalid", &url, now));
+
+    let clock = MockClock::new();
+    let mut jar = CookieJar::new();
+    jar.clock = Arc::new(clock.clone());
+    jar.store(
+        &url,
+        vec![
+            "sid=abc",
+            "pref=dark; Domain=example.com; Path=/",
+            "token=1; Path=/v1/admin; Max-Age=60",
+        ],
+    );
+    assert_eq!(3, jar.cookies().len());
+
+    // Matched by the domain, the path and Secure (the longer paths first)
+    let cookie_header = |url: &str| jar.cookie_header(&Url::parse(url).ok().unwrap());
+    assert_eq!(
+        Some("sid=abc; pref=dark".to_string()),
+        cookie_header("https://api.example.com/v1/items")
+    );
+    assert_eq!(
+        Some("token=1; sid=abc; pref=dark".to_string()),
+        cookie_header("https://api.example.com/v1/admin")
+    );
+    assert_eq!(
+        Some("pref=dark".to_string()),
+        cookie_header("https://www.example.com/v1")
+    );
+    assert_eq!(
+        Some("pref=dark".to_string()),
+        cookie_header("https://api.example.com/v10")
+    );
+    assert_eq!(None, cookie_header("https://example.org/"));
+
+    // Replaced, expired & removed
+    jar.store(&url, vec!["sid=def"]);
+    assert_eq!(
+        Some("token=1; sid=def; pref=dark".to_string()),
+        cookie_header("https://api.example.com/v1/admin")
+    );
+    clock.advance(Duration::from_secs(60));
+    assert_eq!(
+        Some("sid=def; pref=dark".to_string()),
+        cookie_header("https://api.example.com/v1/admin")
+    );
+    jar.store(&url, vec!["pref=; Domain=example.com; Path=/; Max-Age=0"]);
+    assert_eq!(1, jar.cookies().len());
+    jar.clear();
+    assert!(jar.cookies().is_empty());
+}
+
+#[test]
+fn test_scoped_extensions() {
+    use futures::executor::block_on;
+
+    use http_api_service::common::{
+        scoped_extensions, with_extensions, DelegatedIdentity, Extensions, RetryAttempt,
+    };
+
+    let mut outer = Extensions::new();
+    outer.insert(DelegatedIdentity::new("alice").with_tenant("a"));
+    outer.insert(RetryAttempt(0));
+    let mut inner = Extensions::new();
+    inner.insert(DelegatedIdentity::new("bob"));
+
+    let (outer_identity, inner_identity, inner_attempt, after_inner) =
+        block_on(with_extensions(outer, async {
+            let outer_identity = scoped_extensions().get::<DelegatedIdentity>().cloned();
+            // The nested scopes are merged (the inner values take precedence)
+            let (inner_identity, inner_attempt) = with_extensions(inner, async {
+                let extensions = scoped_extensions();
+                (
+                    extensions.get::<DelegatedIdentity>().cloned(),
+                    extensions.get::<RetryAttempt>().cloned(),
+                )
+            })
+            .await;
+            let after_inner = scoped_extensions().get::<DelegatedIdentity>().cloned();
+            (outer_identity, inner_identity, inner_attempt, after_inner)
+        }));
+    assert_eq!(
+        Some(DelegatedIdentity::new("alice").with_tenant("a")),
+        outer_identity
+    );
+    assert_eq!(Some(DelegatedIdentity::new("bob")), inner_identity);
+    assert_eq!(Some(RetryAttempt(0)), inner_attempt);
+    assert_eq!(outer_identity, after_inner);
+
+    // Out of the scopes
+    assert!(scoped_extensions().is_empty());
+    assert!(!format!(
+        "{:?}",
+        DelegatedIdentity::new("a").with_subject_token("secret")
+    )
+    .contains("secret"));
+}
+
+#[test]
+fn test_redirect_policy() {
+    use http_api_service::common::{
+        strip_credentials, DefaultRedirectPolicy, Headers, Redirect, RedirectAction, RedirectPolicy,
+    };
+    use url::Url;
+
+    let from = Url::parse("https://api.example.com/v1/items").ok().unwrap();
+
+    // The methods of the redirected requests
+    let redirect = |status, method| Redirect::new(status, method, &from, Some("/v2/items"), 1);
+    assert_eq!("GET", redirect(303, "POST").unwrap().method);
+    assert_eq!("HEAD", redirect(303, "HEAD").unwrap().method);
+    assert_eq!("GET", redirect(302, "POST").unwrap().method);
+    assert_eq!("PUT", redirect(301, "PUT").unwrap().method);
+    assert_eq!("POST", redirect(307, "POST").unwrap().method);
+    assert!(redirect(307, "POST").unwrap().keeps_body());
+    assert!(!redirect(302, "POST").unwrap().keeps_body());
+    assert!(redirect(304, "GET").is_none());
+    assert!(Redirect::new(302, "GET", &from, None, 1).is_none());
+
+    // The relative locations are resolved against the redirected urls
+    let same = redirect(302, "GET").unwrap();
+    assert_eq!("https://api.example.com/v2/items", same.to.as_str());
+    assert!(same.is_same_origin());
+    let cross = Redirect::new(302, "GET", &from, Some("https://cdn.example.com/a"), 1).unwrap();
+    assert!(!cross.is_same_origin());
+    let downgraded = Redirect::new(302, "GET", &from, Some("http://api.example.com/a"), 1).unwrap();
+    assert!(!downgraded.is_same_origin());
+
+    // The credentials (and the sensitive headers) are stripped from the cross-origin redirects only
+    let mut headers = Headers::new();
+    headers.insert("authorization", "Bearer token");
+    headers.insert("Cookie", "session=1");
+    headers.insert_sensitive("X-Api-Key", "key");
+    headers.insert("Accept", "application/json");
+    let policy = DefaultRedirectPolicy::default();
+    let mut same_headers = headers.clone();
+    assert_eq!(
+        RedirectAction::Follow,
+        policy.redirect(&same, &mut same_headers)
+    );
+    assert_eq!(4, same_headers.iter().count());
+    assert_eq!(
+        RedirectAction::Follow,
+        policy.redirect(&cross, &mut headers)
+    );
+    assert_eq!(
+        vec![("Accept", "application/json")],
+        headers.iter().collect::<Vec<_>>()
+    );
+
+    // Over max_redirects
+    let policy = DefaultRedirectPolicy { max_redirects: 1 };
+    let mut headers = Headers::new();
+    let second = Redirect::new(302, "GET", &from, Some("/a"), 2).unwrap();
+    assert_eq!(RedirectAction::Stop, policy.redirect(&second, &mut headers));
+
+    let mut headers = Headers::new();
+    headers.insert("Proxy-Authorization", "Basic a");
+    strip_credentials(&mut headers);
+    assert!(headers.iter().next().is_none());
+}
+
+#[test]
+fn test_resilience_events() {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use http_api_service::common::{MetricsSink, MockClock, ResilienceEvent};
+    use http_api_service::simple_api::MemoCache;
+
+    // The stable names and fields
+    let event = ResilienceEvent::RetryAttempt {
+        method: "GET".to_string(),
+        url: "http://localhost/a".to_string(),
+        attempt: 2,
+        backoff: Duration::from_millis(200),
+        allowed: true,
+    };
+    assert_eq!("retry.attempt", event.name());
+    assert_eq!(
+        vec![
+            ("method", "GET".to_string()),
+            ("url", "http://localhost/a".to_string()),
+            ("attempt", "2".to_string()),
+            ("backoff_ms", "200".to_string()),
+            ("allowed", "true".to_string()),
+        ],
+        event.fields()
+    );
+    assert_eq!(
+        "retry.attempt method=GET url=http://localhost/a attempt=2 backoff_ms=200 allowed=true",
+        event.to_string()
+    );
+    let event = ResilienceEvent::LimiterWait {
+        limiter: "priority",
+        method: "GET".to_string(),
+        url: "/a".to_string(),
+        waited: Duration::from_millis(15),
+    };
+    assert_eq!(
+        "limiter.wait limiter=priority method=GET url=/a waited_ms=15",
+        event.to_string()
+    );
+    assert_eq!(
+        "cache.miss key=\"GET /a [] []\"",
+        ResilienceEvent::CacheMiss {
+            key: "GET /a [] []".to_string()
+        }
+        .to_string()
+    );
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<ResilienceEvent>>);
+    impl MetricsSink for RecordingSink {
+        fn record_event(&self, event: &ResilienceEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    // The lookups of MemoCache
+    let sink = Arc::new(RecordingSink::default());
+    let clock = MockClock::new();
+    let mut cache = MemoCache::new_with_clock(Duration::from_secs(60), 16, Arc::new(clock.clone()));
+    cache.stale_while_revalidate = Duration::from_secs(60);
+    cache.metrics_sink = Some(sink.clone());
+    assert!(cache.get("flags").is_none());
+    cache.insert("flags", Arc::new(1));
+    assert!(cache.get("flags").is_some());
+    clock.advance(Duration::from_secs(61));
+    assert_eq!(
+        Some(false),
+        cache.get_stale("flags").map(|(_, fresh)| fresh)
+    );
+    assert_eq!(
+        vec!["cache.miss", "cache.hit", "cache.stale"],
+        sink.0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|event| event.name())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_deprecation_notice() {
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use http_api_service::common::{
+        DeprecationMonitor, DeprecationNotice, MetricsSink, ResilienceEvent,
+    };
+
+    // 2015-10-21T07:28:00Z
+    let date = UNIX_EPOCH + Duration::from_secs(1445412480);
+
+    assert_eq!(None, DeprecationNotice::parse("/a", None, None, &[]));
+    assert_eq!(
+        None,
+        DeprecationNotice::parse("/a", Some("false"), None, &[])
+    );
+    // Only the persistent warnings (299)
+    assert_eq!(
+        None,
+        DeprecationNotice::parse("/a", None, None, &["110 - \"Response is Stale\""])
+    );
+
+    let notice = DeprecationNotice::parse(
+        "/a?page=1",
+        Some("@1445412480"),
+        Some("Wed, 21 Oct 2015 07:28:00 GMT"),
+        &[
+            "299 - \"Deprecated API, use \\\"/v2\\\"\" \"Wed, 21 Oct 2015 07:28:00 GMT\", 110 - \"stale\"",
+            "299 gateway \"Sunset soon\"",
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        DeprecationNotice {
+            url: "/a?page=1".to_string(),
+            deprecated: true,
+            deprecated_at: Some(date),
+            sunset: Some(date),
+            warnings: vec![
+                "Deprecated API, use \"/v2\"".to_string(),
+                "Sunset soon".to_string()
+            ],
+        },
+        notice
+    );
+    // The legacy values of the drafts
+    let legacy = DeprecationNotice::parse("/b", Some("true"), None, &[]).unwrap();
+    assert!(legacy.deprecated);
+    assert_eq!(None, legacy.deprecated_at);
+    let legacy =
+        DeprecationNotice::parse("/b", Some("Wed, 21 Oct 2015 07:28:00 GMT"), None, &[]).unwrap();
+    assert_eq!(Some(date), legacy.deprecated_at);
+    // Only sunset
+    let sunset_only =
+        DeprecationNotice::parse("/c", None, Some("Wed, 21 Oct 2015 07:28:00 GMT"), &[]).unwrap();
+    assert!(!sunset_only.deprecated);
+    // The timestamps out of range are unknown (instead of overflowing)
+    let overflowed =
+        DeprecationNotice::parse("/d", Some("@18446744073709551615"), None, &[]).unwrap();
+    assert!(overflowed.deprecated);
+    assert_eq!(None, overflowed.deprecated_at);
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<ResilienceEvent>>);
+    impl MetricsSink for RecordingSink {
+        fn record_event(&self, event: &ResilienceEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    // Reported to the hook & the metrics, the notices are of the endpoints
+    let sink = Arc::new(RecordingSink::default());
+    let hooked = Arc::new(Mutex::new(Vec::new()));
+    let hooked_for_hook = hooked.clone();
+    let monitor = DeprecationMonitor::new()
+        .with_metrics_sink(sink.clone())
+        .with_hook(move |notice| hooked_for_hook.lock().unwrap().push(notice.url.clone()));
+    monitor.observe(&notice);
+    monitor.observe(&DeprecationNotice {
+        url: "/a?page=2".to_string(),
+        ..notice.clone()
+    });
+    monitor.observe(&legacy);
+    assert_eq!(
+        vec!["/a?page=1", "/a?page=2", "/b"],
+        *hooked.lock().unwrap()
+    );
+    assert_eq!(
+        vec!["/a?page=2", "/b"],
+        monitor
+            .notices()
+            .iter()
+            .map(|notice| notice.url.as_str())
+            .collect::<Vec<_>>()
+    );
+    let events = sink.0.lock().unwrap();
+    assert_eq!(3, events.len());
+    assert_eq!(
+        "api.deprecation url=/a?page=1 deprecated_at=1445412480 sunset=1445412480",
+        events[0].to_string()
+    );
+    assert_eq!(
+        "api.deprecation url=/b deprecated_at=1445412480",
+        events[2].to_string()
+    );
+}
+
+#[test]
+fn test_read_only_guard() {
+    use http_api_service::common::{ReadOnlyGuard, ReadOnlyMode};
+
+    let guard = ReadOnlyGuard::new().allow("post", "/products/search");
+    assert!(guard.is_allowed("GET", "/products"));
+    assert!(guard.is_allowed("HEAD", "/products"));
+    assert!(guard.is_allowed("POST", "/products/search"));
+    assert!(!guard.is_allowed("PUT", "/products/search"));
+    assert!(!guard.is_allowed("OPTIONS", "/products"));
+
+    let e = guard.check("DELETE", "/products/{id}").unwrap_err();
+    assert_eq!(ReadOnlyMode::new("DELETE", "/products/{id}"), e);
+    assert_eq!(
+        "ReadOnlyMode: DELETE /products/{id} isn't sent in the read-only mode",
+        e.to_string()
+    );
+}
+
+#[test]
+fn test_aggregate_error() {
+    use std::error::Error as StdError;
+    use std::io;
+    use std::sync::Arc;
+
+    use http_api_service::common::{AggregateError, ItemError};
+
+    let results: Vec<Result<u32, io::Error>> = vec![Ok(1), Ok(2)];
+    assert_eq!(vec![1, 2], AggregateError::collect(results).unwrap());
+
+    let results: Vec<Result<u32, Box<dyn StdError>>> = vec![
+        Ok(1),
+        Err("conflict".into()),
+        Ok(3),
+        Err(Box::new(io::Error::other("reset"))),
+    ];
+    let e = AggregateError::collect(results).unwrap_err();
+    assert_eq!(4, e.total);
+    assert_eq!(vec![1, 3], e.indices());
+    assert_eq!(
+        "AggregateError: 2 of 4 items failed: #1: conflict; #3: reset",
+        e.to_string()
+    );
+    assert_eq!("conflict", e.source().unwrap().to_string());
+
+    // By the keys, sorted by the indices
+    let shared: Arc<dyn StdError> = Arc::from(Box::<dyn StdError>::from("timeout"));
+    let errors = (0..5)
+        .rev()
+        .map(|index| ItemError::new(index, shared.clone()).with_key(format!("sku-{}", index)))
+        .collect();
+    let e = AggregateError::new(8, errors);
+    assert_eq!(vec![0, 1, 2, 3, 4], e.indices());
+    assert_eq!(2, e.get(2).unwrap().index);
+    assert!(e.get(5).is_none());
+    assert_eq!(3, e.get_by_key("sku-3").unwrap().index);
+    assert_eq!(
+        "AggregateError: 5 of 8 items failed: #0 (sku-0): timeout; #1 (sku-1): timeout; #2 (sku-2): timeout; and 2 more",
+        e.to_string()
+    );
+}
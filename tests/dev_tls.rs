@@ -0,0 +1,236 @@
+extern crate http_api_service;
+
+// The dev CA & the certificate of "localhost" issued by it (valid until 2126)
+const CA_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBpjCCAU2gAwIBAgIUB2Wxt6DTF9hns2UMg+CoRtOIQdUwCgYIKoZIzj0EAwIw
+IDEeMBwGA1UEAwwVaHR0cEFQSVNlcnZpY2UgRGV2IENBMCAXDTI2MTAxNzEwNDUz
+MFoYDzIxMjYwOTIzMTA0NTMwWjAgMR4wHAYDVQQDDBVodHRwQVBJU2VydmljZSBE
+ZXYgQ0EwWTATBgcqhkjOPQIBBggqhkjOPQMBBwNCAASHJKV2XqdFBzkKw05nx3E1
+abgp9aNwXSD2wFrioCoN4p7cGCccE4UN4irJAu/ANahynVqLFanzVxM8cMS8cLC6
+o2MwYTAdBgNVHQ4EFgQUtjxVkzwC+VifuQHK36Zrh1AOiuUwHwYDVR0jBBgwFoAU
+tjxVkzwC+VifuQHK36Zrh1AOiuUwDwYDVR0TAQH/BAUwAwEB/zAOBgNVHQ8BAf8E
+BAMCAQYwCgYIKoZIzj0EAwIDRwAwRAIgCXW+Oi0EmDRpTurTQupUdv5SIol1g8wA
+K2TYKAPg3/0CID7Sbwhs8M6Cq9BR1vEiTxlP80tIKtXci1X+usn0AVsj
+-----END CERTIFICATE-----
+";
+const LOCALHOST_PEM: &str = "-----BEGIN CERTIFICATE-----
+MIIBvzCCAWWgAwIBAgIUQVSK0v4cF2xyM+BGy+rO7FALBS4wCgYIKoZIzj0EAwIw
+IDEeMBwGA1UEAwwVaHR0cEFQSVNlcnZpY2UgRGV2IENBMCAXDTI2MTAxNzEwNDUz
+MFoYDzIxMjYwOTIzMTA0NTMwWjAUMRIwEAYDVQQDDAlsb2NhbGhvc3QwWTATBgcq
+hkjOPQIBBggqhkjOPQMBBwNCAAQTOHUczIrhAj3PSaRUnW6i4KFt/mbqDCrkswlf
+p099KmPB4eYeK+M5ugMP8BHLxdThfZONnFjKkAI5W15G8q09o4GGMIGDMBQGA1Ud
+EQQNMAuCCWxvY2FsaG9zdDAJBgNVHRMEAjAAMAsGA1UdDwQEAwIHgDATBgNVHSUE
+DDAKBggrBgEFBQcDATAdBgNVHQ4EFgQU1qjnN5rUoP1o6Prpcmg3L18udVgwHwYD
+VR0jBBgwFoAUtjxVkzwC+VifuQHK36Zrh1AOiuUwCgYIKoZIzj0EAwIDSAAwRQIg
+Gm7GWBlHBkiGaioIi03M4H2SoW2nwD5bvRNYQXvc58ACIQCmUFVbZZFY9VooCOYo
+kkyaydAP6PU7/qBIuD25ws/VIQ==
+-----END CERTIFICATE-----
+";
+// The PKCS#8 key of LOCALHOST_PEM
+const LOCALHOST_KEY: &str = "MIGHAgEAMBMGByqGSM49AgEGCCqGSM49AwEHBG0wawIBAQQg27csnXixVdTOmd/+cJHmuVk258nv9Mlz/y/XOzl6b9OhRANCAAQTOHUczIrhAj3PSaRUnW6i4KFt/mbqDCrkswlfp099KmPB4eYeK+M5ugMP8BHLxdThfZONnFjKkAI5W15G8q09";
+
+#[test]
+fn test_dev_tls_options() {
+    use http_api_service::dev_tls::{pem_certificates, DevTlsOptions};
+
+    assert_eq!(1, pem_certificates(CA_PEM.as_bytes()).unwrap().len());
+    let bundle = format!("{}{}", CA_PEM, LOCALHOST_PEM);
+    assert_eq!(2, pem_certificates(bundle.as_bytes()).unwrap().len());
+    assert!(pem_certificates(b"").is_err());
+    assert!(pem_certificates(b"-----BEGIN CERTIFICATE-----\nAAAA").is_err());
+
+    let options = DevTlsOptions::new()
+        .with_ca_pem(CA_PEM.as_bytes())
+        .unwrap()
+        .without_webpki_roots()
+        .danger_accept_invalid_certs_for_host("Dev.Example.");
+    assert_eq!(1, options.root_cert_store().unwrap().len());
+    assert_eq!(
+        vec!["dev.example".to_string()],
+        options.danger_insecure_hosts
+    );
+    assert!(options.client_config().is_ok());
+
+    // Not a certificate
+    assert!(DevTlsOptions::new()
+        .with_ca_der(vec![1, 2, 3])
+        .client_config()
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_dev_tls_ureq() {
+    extern crate base64;
+    extern crate rustls;
+
+    use std::convert::TryInto;
+    use std::io::{Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    use http::Method;
+    use rustls::{
+        Certificate, ClientConnection, PrivateKey, ServerConfig, ServerConnection, StreamOwned,
+    };
+
+    use http_api_service::bind_ureq;
+    use http_api_service::common::{Extensions, SniHostname};
+    use http_api_service::dev_tls::{pem_certificates, tls_info_of_rustls, DevTlsOptions};
+    use http_api_service::simple_api::{with_extensions, DEFAULT_SERDE_JSON_DESERIALIZER};
+
+    let config = Arc::new(
+        ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                pem_certificates(LOCALHOST_PEM.as_bytes())
+                    .unwrap()
+                    .into_iter()
+                    .map(Certificate)
+                    .collect(),
+                PrivateKey(base64::decode(LOCALHOST_KEY).unwrap()),
+            )
+            .unwrap(),
+    );
+    // A TLS server of the dev certificate, responding "ok" to the requests (recording the SNI)
+    let listener = TcpListener::bind("127.0.0.1:4077").unwrap();
+    let sni = Arc::new(Mutex::new(Vec::new()));
+    let sni_for_thread = sni.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let connection = ServerConnection::new(config.clone()).unwrap();
+            let mut stream = StreamOwned::new(connection, stream.unwrap());
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(n) if n > 0 => request.extend_from_slice(&buf[..n]),
+                    _ => break,
+                }
+            }
+            if let Some(hostname) = stream.conn.sni_hostname() {
+                sni_for_thread.lock().unwrap().push(hostname.to_string());
+            }
+            if request.ends_with(b"\r\n\r\n") {
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\nConnection: close\r\n\r\n\"ok\"",
+                );
+                let _ = stream.flush();
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+        }
+    });
+
+    let api_of = |common_api: bind_ureq::CommonAPI<_, _, _, _, _, _>, base_url: &str| {
+        let base_service_setter = common_api.as_base_service_setter();
+        base_service_setter.set_base_url(url::Url::parse(base_url).ok().unwrap());
+        base_service_setter.make_api_no_body(
+            common_api.as_base_service_shared(),
+            Method::GET,
+            "/status",
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &String::new(),
+        )
+    };
+
+    // The dev CA isn't trusted by default
+    assert!(api_of(
+        bind_ureq::CommonAPI::new_for_ureq(),
+        "https://localhost:4077"
+    )
+    .call(None)
+    .await
+    .is_err());
+
+    // Trust the dev CA
+    let agent = DevTlsOptions::new()
+        .with_ca_pem(CA_PEM.as_bytes())
+        .unwrap()
+        .ureq_agent()
+        .unwrap();
+    let result = api_of(
+        bind_ureq::CommonAPI::new_with_agent(agent),
+        "https://localhost:4077",
+    )
+    .call(None)
+    .await;
+    assert_eq!("ok", *result.ok().unwrap());
+
+    // Skip the verification of the host only
+    let agent = DevTlsOptions::new()
+        .danger_accept_invalid_certs_for_host("other.localhost")
+        .ureq_agent()
+        .unwrap();
+    assert!(api_of(
+        bind_ureq::CommonAPI::new_with_agent(agent),
+        "https://localhost:4077",
+    )
+    .call(None)
+    .await
+    .is_err());
+    let agent = DevTlsOptions::new()
+        .danger_accept_invalid_certs_for_host("localhost")
+        .ureq_agent()
+        .unwrap();
+    let result = api_of(
+        bind_ureq::CommonAPI::new_with_agent(agent),
+        "https://localhost:4077",
+    )
+    .call(None)
+    .await;
+    assert_eq!("ok", *result.ok().unwrap());
+
+    // The TlsInfo of the rustls connections
+    let config = DevTlsOptions::new()
+        .with_ca_pem(CA_PEM.as_bytes())
+        .unwrap()
+        .client_config()
+        .unwrap();
+    let connection = ClientConnection::new(config, "localhost".try_into().unwrap()).unwrap();
+    let mut stream = StreamOwned::new(connection, TcpStream::connect("127.0.0.1:4077").unwrap());
+    stream
+        .write_all(b"GET /status HTTP/1.1\r\nHost: localhost\r\n\r\n")
+        .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    assert!(response.ends_with(b"\"ok\""));
+    let tls_info = tls_info_of_rustls(&stream.conn);
+    assert_eq!(Some("TLSv1_3".to_string()), tls_info.protocol);
+    assert!(tls_info.cipher_suite.unwrap().starts_with("TLS13_"));
+    assert_eq!(None, tls_info.alpn_protocol);
+    assert_eq!(
+        pem_certificates(LOCALHOST_PEM.as_bytes()).unwrap(),
+        tls_info.peer_certificates
+    );
+
+    // The SNI of the url, or the SniHostname (by the default TLS config, the dev CA isn't trusted)
+    sni.lock().unwrap().clear();
+    assert!(api_of(
+        bind_ureq::CommonAPI::new_for_ureq(),
+        "https://localhost:4077"
+    )
+    .call(None)
+    .await
+    .is_err());
+    let mut extensions = Extensions::new();
+    extensions.insert(SniHostname("fronted.localhost".to_string()));
+    let result = with_extensions(
+        extensions,
+        api_of(
+            bind_ureq::CommonAPI::new_for_ureq(),
+            "https://127.0.0.1:4077",
+        )
+        .call(None),
+    )
+    .await;
+    assert!(result.is_err());
+    // The server records the SNI after the client rejects the certificate
+    for _ in 0..50 {
+        if sni.lock().unwrap().len() == 2 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(vec!["localhost", "fronted.localhost"], *sni.lock().unwrap());
+}
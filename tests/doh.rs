@@ -0,0 +1,172 @@
+extern crate http_api_service;
+
+#[test]
+fn test_dns_messages() {
+    use std::net::IpAddr;
+
+    use http_api_service::doh::{dns_query_message, parse_dns_response};
+
+    assert_eq!(
+        vec![
+            0, 0, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 3, b'a', b'p', b'i', 7, b'e', b'x', b'a', b'm',
+            b'p', b'l', b'e', 0, 0, 1, 0, 1
+        ],
+        dns_query_message("api.example.", 1).unwrap()
+    );
+    assert!(dns_query_message("api..example", 1).is_err());
+    assert!(dns_query_message(&"a".repeat(64), 1).is_err());
+
+    // A CNAME followed by the A record (by the compressed names)
+    let mut message = vec![0, 0, 0x81, 0x80, 0, 1, 0, 2, 0, 0, 0, 0];
+    message.extend_from_slice(&dns_query_message("api.example", 1).unwrap()[12..]);
+    message.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 1, 0, 0, 2, 0xc0, 12]);
+    message.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 30, 0, 4, 10, 0, 0, 7]);
+    assert_eq!(
+        (vec!["10.0.0.7".parse::<IpAddr>().unwrap()], 30),
+        parse_dns_response(&message).unwrap()
+    );
+    assert!(parse_dns_response(&message[..message.len() - 2]).is_err());
+    // NXDOMAIN
+    message[3] = 0x83;
+    assert!(parse_dns_response(&message).is_err());
+}
+
+#[tokio::test]
+async fn test_doh_resolver() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::{IpAddr, SocketAddr};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use hyper::header::{CONTENT_TYPE, HOST};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::CommonAPI;
+    use http_api_service::common::MockClock;
+    use http_api_service::doh::{DohResolver, MEDIA_TYPE_DNS_MESSAGE};
+    use http_api_service::simple_api::DEFAULT_SERDE_JSON_DESERIALIZER;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let doh_addr: SocketAddr = ([127, 0, 0, 1], 4074).into();
+    let addr: SocketAddr = ([127, 0, 0, 1], 4075).into();
+
+    // The DoH endpoint answers "api.example.test" by 127.0.0.1 (A only), and NXDOMAIN for the others
+    let queries = Arc::new(AtomicUsize::new(0));
+    let queries_for_thread = queries.clone();
+    let doh_server = Server::bind(&doh_addr).serve(make_service_fn(move |_| {
+        let queries_for_thread_2 = queries_for_thread.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let queries_for_thread_3 = queries_for_thread_2.clone();
+                async move {
+                    assert_eq!(MEDIA_TYPE_DNS_MESSAGE, req.headers()[CONTENT_TYPE]);
+                    let query = hyper::body::to_bytes(req.into_body()).await?.to_vec();
+                    queries_for_thread_3.fetch_add(1, Ordering::SeqCst);
+                    let question = &query[12..];
+                    let record_type = question[question.len() - 3];
+                    let known = question.starts_with(b"\x03api\x07example\x04test\x00");
+
+                    let mut message = vec![0, 0, 0x81, if known { 0x80 } else { 0x83 }, 0, 1];
+                    let answers = if known && record_type == 1 { 1 } else { 0 };
+                    message.extend_from_slice(&[0, answers, 0, 0, 0, 0]);
+                    message.extend_from_slice(question);
+                    if answers > 0 {
+                        message.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4]);
+                        message.extend_from_slice(&[127, 0, 0, 1]);
+                    }
+                    Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(message)))
+                }
+            }))
+        }
+    }));
+    // Echo the host
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let body = format!("\"{}\"", req.headers()[HOST].to_str().unwrap());
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(body)))
+        }))
+    }));
+    let doh_latch = hyper_latch.clone();
+    tokio::spawn(async {
+        let _ = doh_server
+            .with_graceful_shutdown(async move {
+                doh_latch.await;
+            })
+            .await;
+    });
+    let server_latch = hyper_latch.clone();
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                server_latch.await;
+            })
+            .await;
+    });
+
+    let clock = MockClock::new();
+    let resolver = DohResolver::new(
+        url::Url::parse(&format!("http://{}/dns-query", doh_addr))
+            .ok()
+            .unwrap(),
+        Arc::new(hyper::Client::new()),
+    )
+    .with_pinned("pinned.test", vec![IpAddr::from([127, 0, 0, 1])])
+    .with_clock(Arc::new(clock.clone()));
+
+    // A service resolving by the DoH resolver
+    let common_api = CommonAPI::new_with_connector(resolver.clone().connector());
+    let api_of = |base_url: &str| {
+        let base_service_setter = common_api.as_base_service_setter();
+        base_service_setter.set_base_url(url::Url::parse(base_url).ok().unwrap());
+        base_service_setter.make_api_no_body(
+            common_api.as_base_service_shared(),
+            Method::GET,
+            "/",
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &String::new(),
+        )
+    };
+    let result = api_of("http://api.example.test:4075").call(None).await;
+    assert_eq!("api.example.test:4075", *result.ok().unwrap());
+    // A & AAAA
+    assert_eq!(2, queries.load(Ordering::SeqCst));
+
+    // Cached by the TTL
+    assert_eq!(
+        vec![IpAddr::from([127, 0, 0, 1])],
+        resolver.resolve("API.example.test.").await.unwrap()
+    );
+    assert_eq!(2, queries.load(Ordering::SeqCst));
+    clock.advance(Duration::from_secs(61));
+    assert!(resolver.resolve("api.example.test").await.is_ok());
+    assert_eq!(4, queries.load(Ordering::SeqCst));
+
+    // The pinned hosts aren't queried
+    let result = api_of("http://pinned.test:4075").call(None).await;
+    assert_eq!("pinned.test:4075", *result.ok().unwrap());
+    assert_eq!(4, queries.load(Ordering::SeqCst));
+
+    // The unknown hosts fail the calls
+    let result = api_of("http://unknown.test:4075").call(None).await;
+    assert!(result.is_err());
+    assert!(resolver.resolve("unknown.test").await.is_err());
+
+    // The unreachable DoH endpoint
+    let resolver = DohResolver::new(
+        url::Url::parse("http://127.0.0.1:1/dns-query")
+            .ok()
+            .unwrap(),
+        Arc::new(hyper::Client::new()),
+    )
+    .with_ipv6(false);
+    let error = resolver.resolve("api.example.test").await.err().unwrap();
+    assert!(error.to_string().starts_with("DohError"));
+
+    hyper_latch.countdown();
+}
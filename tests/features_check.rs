@@ -0,0 +1,158 @@
+// The feature matrix of the crate: each feature alone (on top of each binding) and all of them combined.
+//
+// The matrix runs `cargo check` per combination, so only the bindings alone and all features combined
+// are checked by default, and the whole matrix is ignored:
+// `cargo test --features test_runtime --test features_check -- --ignored`
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+const BINDINGS: [&str; 2] = ["for_hyper", "for_ureq"];
+// Not the subsystems (the presets & the placeholders)
+const NON_SUBSYSTEMS: [&str; 3] = ["default", "pure", "test_runtime"];
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+// The `[features]` of Cargo.toml (feature => the enabled features/dependencies)
+fn read_features() -> BTreeMap<String, Vec<String>> {
+    let manifest = fs::read_to_string(manifest_dir().join("Cargo.toml")).unwrap();
+    let mut features = BTreeMap::new();
+    let mut section = false;
+    let mut current: Option<(String, String)> = None;
+    for line in manifest.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && !line.starts_with("[\"") {
+            section = line == "[features]";
+            continue;
+        }
+        if !section || line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, items) = match current.take() {
+            Some((name, items)) => (name, items + line),
+            None => match line.split_once('=') {
+                Some((name, items)) => (name.trim().to_string(), items.trim().to_string()),
+                None => continue,
+            },
+        };
+        if !items.ends_with(']') {
+            current = Some((name, items));
+            continue;
+        }
+        let items = items
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .split(',')
+            .map(|item| item.trim().trim_matches('"').to_string())
+            .filter(|item| !item.is_empty())
+            .collect();
+        features.insert(name, items);
+    }
+    features
+}
+
+fn subsystems(features: &BTreeMap<String, Vec<String>>) -> Vec<String> {
+    features
+        .keys()
+        .filter(|name| {
+            !NON_SUBSYSTEMS.contains(&name.as_str()) && !BINDINGS.contains(&name.as_str())
+        })
+        .cloned()
+        .collect()
+}
+
+#[test]
+fn test_feature_declarations() {
+    let features = read_features();
+    for binding in BINDINGS.iter() {
+        assert!(features.contains_key(*binding), "{} is missing", binding);
+    }
+
+    // All of them are combined by `test_runtime`
+    let test_runtime = &features["test_runtime"];
+    for name in BINDINGS
+        .iter()
+        .map(|v| v.to_string())
+        .chain(subsystems(&features))
+    {
+        assert!(
+            test_runtime.contains(&name),
+            "{} isn't enabled by test_runtime",
+            name
+        );
+    }
+}
+
+// `cargo check` the combinations, returns the failures
+fn check_combinations(combinations: &[Vec<String>]) -> Vec<String> {
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    // Not to wait for the lock of the target directory of the running tests
+    let target_dir = manifest_dir().join("target").join("features_check");
+
+    let mut failures = Vec::new();
+    for combination in combinations.iter() {
+        let features = combination.join(",");
+        let output = Command::new(&cargo)
+            .current_dir(manifest_dir())
+            .args(["check", "--lib", "--no-default-features", "--features"])
+            .arg(&features)
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .output()
+            .unwrap();
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let errors: Vec<&str> = stderr
+                .lines()
+                .filter(|line| line.starts_with("error"))
+                .collect();
+            failures.push(format!("[{}]\n{}", features, errors.join("\n")));
+        }
+    }
+    failures
+}
+
+// All the bindings & the subsystems
+fn all_features(features: &BTreeMap<String, Vec<String>>) -> Vec<String> {
+    BINDINGS
+        .iter()
+        .map(|v| v.to_string())
+        .chain(subsystems(features))
+        .collect()
+}
+
+// The cheap subset of the matrix: each binding alone and all of them combined
+#[test]
+fn test_feature_bindings() {
+    let features = read_features();
+
+    let mut combinations: Vec<Vec<String>> = BINDINGS.iter().map(|v| vec![v.to_string()]).collect();
+    combinations.push(all_features(&features));
+
+    let failures = check_combinations(&combinations);
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
+
+#[test]
+#[ignore]
+fn test_feature_matrix() {
+    let features = read_features();
+
+    let mut combinations: Vec<Vec<String>> = Vec::new();
+    for binding in BINDINGS.iter() {
+        combinations.push(vec![binding.to_string()]);
+        for name in subsystems(&features) {
+            combinations.push(vec![binding.to_string(), name]);
+        }
+    }
+    combinations.push(all_features(&features));
+
+    let failures = check_combinations(&combinations);
+    assert!(failures.is_empty(), "{}", failures.join("\n\n"));
+}
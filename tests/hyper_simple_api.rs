@@ -9,15 +9,15 @@ async fn test_simple_api_for_readme_md() {
     extern crate hyper;
 
     use std::sync::Arc;
+    use std::time::Duration;
 
     use http::method::Method;
-    use hyper::HeaderMap;
 
     use http_api_service::bind_hyper;
     use http_api_service::path_param;
     use http_api_service::simple_api;
     use http_api_service::simple_api::{
-        DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
+        Headers, DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
     };
 
     use serde::{Deserialize, Serialize};
@@ -45,13 +45,13 @@ async fn test_simple_api_for_readme_md() {
 
     // Setup base_url
     base_service_setter.set_base_url(url::Url::parse("http://localhost:3000").ok().unwrap());
-    // Setup timeout_millisecond
-    base_service_setter.set_timeout_millisecond(10 * 1000);
+    // Setup timeout
+    base_service_setter.set_timeout(Duration::from_secs(10));
 
     // Add common headers for Authentication or other usages
     let mut header_map = match base_service_setter.get_default_header() {
         Some(header) => header,
-        None => HeaderMap::new(),
+        None => Headers::new(),
     };
     header_map = bind_hyper::add_header_authentication_bearer(header_map, "MY_TOKEN")
         .ok()
@@ -140,9 +140,10 @@ async fn test_simple_api_common() {
 
     use std::net::SocketAddr;
     use std::sync::Arc;
+    use std::time::Duration;
 
     use hyper::service::{make_service_fn, service_fn};
-    use hyper::{body, Body, HeaderMap, Method, Request, Response, Server};
+    use hyper::{body, Body, Method, Request, Response, Server};
     use serde::{Deserialize, Serialize};
 
     use fp_rust::sync::CountDownLatch;
@@ -150,7 +151,7 @@ async fn test_simple_api_common() {
     use http_api_service::bind_hyper::add_header_authentication_bearer;
     use http_api_service::simple_api;
     use http_api_service::simple_api::{
-        DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
+        Headers, DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
     };
     use http_api_service::{path_param, query_param};
 
@@ -262,19 +263,23 @@ async fn test_simple_api_common() {
             .ok()
             .unwrap(),
     );
-    // Setup timeout_millisecond
-    base_service_setter.set_timeout_millisecond(10 * 1000);
+    // Setup timeout
+    base_service_setter.set_timeout(Duration::from_secs(10));
 
     let mut header_map = if let Some(header) = base_service_setter.get_default_header() {
         header
     } else {
-        HeaderMap::new()
+        Headers::new()
     };
     header_map = add_header_authentication_bearer(header_map, "MY_TOKEN")
         .ok()
         .unwrap();
     base_service_setter.set_default_header(Some(header_map));
 
+    base_service_setter.add_interceptor_headers_fn(|headers| {
+        assert_eq!(Some("Bearer MY_TOKEN"), headers.get("Authorization"));
+        Ok(())
+    });
     base_service_setter.add_interceptor_fn(|req| {
         println!("REQ_CONTENT: {:?}", req);
         Ok(())
@@ -367,6 +372,120 @@ async fn test_simple_api_common() {
             serialized
         );
     }
+    // PUT/GET ApiBuilder
+    {
+        let api_put_product = simple_api::ApiBuilder::new(Method::PUT, "/products/{id}")
+            .content_type("application/json")
+            .serializer(json_serializer.clone())
+            .deserializer(json_deserializer.clone())
+            .build(base_service_shared.clone());
+
+        let sent_body = Product {
+            name: "Alien ".to_string(),
+            age: "5 month".to_string(),
+            meta: Some("123".to_string()),
+        };
+        let resp = api_put_product
+            .call(Some(path_param!["id" => "5"]), sent_body)
+            .await;
+        let model: Box<Product> = resp.ok().unwrap();
+        assert_eq!("Alien  modified", model.name);
+        assert_eq!("3 years", model.age);
+
+        // The raw Bytes of the response by default
+        let api_get_product = simple_api::ApiBuilder::new(Method::GET, "/products/{id}")
+            .build_no_body(base_service_shared.clone());
+        assert_eq!("", api_get_product.content_type);
+        let resp = api_get_product.call(Some(path_param!["id" => "3"])).await;
+        let bytes = resp.ok().unwrap();
+        let model: Product = serde_json::from_slice(bytes.as_ref()).ok().unwrap();
+        assert_eq!("Baxter from server", model.name);
+    }
+    // GET/PUT MakeApi (without passing base_service_shared again)
+    {
+        use http_api_service::simple_api::MakeApi;
+
+        let api_get_product = base_service_shared.api_no_body(
+            Method::GET,
+            "/products/{id}",
+            json_deserializer.clone(),
+            return_type_marker,
+        );
+        let resp = api_get_product.call(Some(path_param!["id" => "3"])).await;
+        assert_eq!("Baxter from server", resp.ok().unwrap().name);
+
+        let api_put_product = base_service_shared.api_has_body(
+            Method::PUT,
+            "/products/{id}",
+            "application/json",
+            json_serializer.clone(),
+            json_deserializer.clone(),
+            return_type_marker,
+        );
+        let sent_body = Product {
+            name: "Alien ".to_string(),
+            age: "5 month".to_string(),
+            meta: None,
+        };
+        let resp = api_put_product
+            .call(Some(path_param!["id" => "5"]), sent_body)
+            .await;
+        assert_eq!("Alien  modified", resp.ok().unwrap().name);
+    }
+    // GET make_api_no_body + map_into
+    {
+        #[derive(Debug)]
+        struct Pet {
+            name: String,
+        }
+        impl std::convert::TryFrom<Product> for Pet {
+            type Error = String;
+
+            fn try_from(origin: Product) -> Result<Self, Self::Error> {
+                if origin.name.is_empty() {
+                    return Err("empty name".to_string());
+                }
+                Ok(Pet { name: origin.name })
+            }
+        }
+        struct Nameless {}
+        impl std::convert::TryFrom<Product> for Nameless {
+            type Error = String;
+
+            fn try_from(_: Product) -> Result<Self, Self::Error> {
+                Err("no name allowed".to_string())
+            }
+        }
+
+        let api_get_pet = base_service_setter
+            .make_api_no_body(
+                base_service_shared.clone(),
+                Method::GET,
+                "/pets/{id}",
+                json_deserializer.clone(),
+                return_type_marker,
+            )
+            .map_into::<Pet>();
+        let resp = api_get_pet.call(Some(path_param!["id" => "1"])).await;
+        assert_eq!("Baxter from server", resp.ok().unwrap().name);
+
+        let api_get_nameless = base_service_setter
+            .make_api_no_body(
+                base_service_shared.clone(),
+                Method::GET,
+                "/pets/{id}",
+                json_deserializer.clone(),
+                return_type_marker,
+            )
+            .map_into::<Nameless>();
+        let resp = api_get_nameless.call(Some(path_param!["id" => "1"])).await;
+        let err = resp.err().unwrap();
+        assert_eq!(
+            true,
+            err.downcast_ref::<simple_api::MapIntoError>().is_some()
+        );
+        assert_eq!("MapIntoError: no name allowed", err.to_string());
+    }
 
     started_latch.wait();
     println!("REQ",);
@@ -424,14 +543,16 @@ async fn test_simple_api_formdata() {
 
                     let (parts, body) = req.into_parts();
 
-                    let multipart = body_to_multipart(&parts.headers, body).await;
-
-                    println!("Error: {:?}", multipart.as_ref().err());
-
-                    let mut multipart = multipart.ok().unwrap();
-
-                    let hash_map =
-                        block_on(simple_http::multer_multipart_to_hash_map(&mut multipart));
+                    // NOTE: Block on another thread, the streamed body is still being received.
+                    let hash_map = tokio::task::spawn_blocking(move || {
+                        block_on(async move {
+                            let mut multipart = body_to_multipart(&parts.headers, body).await?;
+                            simple_http::multer_multipart_to_hash_map(&mut multipart).await
+                        })
+                        .map_err(|e| e.to_string())
+                    })
+                    .await
+                    .unwrap();
                     let hash_map = match hash_map {
                         Ok(v) => v,
                         Err(e) => {
@@ -526,7 +647,6 @@ async fn test_simple_api_formdata() {
         String::from_utf8(resp.to_vec()).ok().unwrap().as_str()
     );
 
-    /*
     println!("========");
     // POST make_api_multipart_for_stream
     let api_post_multipart_for_stream = base_service_setter.make_api_multipart_for_stream(
@@ -552,7 +672,108 @@ async fn test_simple_api_formdata() {
         "\"age\":\"\":b\"1 month\"\n\"name\":\"\":b\"Baxter\"\n",
         String::from_utf8(resp.to_vec()).ok().unwrap().as_str()
     );
-    */
+
+    // Written on the given thread pool
+    {
+        use futures::executor::ThreadPool;
+
+        use http_api_service::bind_hyper::MultipartSerializerForStream;
+
+        let mut api_post_multipart_for_pool = base_service_setter.make_api_multipart_for_stream(
+            base_service_shared.clone(),
+            Method::POST,
+            "/form",
+            Arc::new(simple_api::DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+            &bytes::Bytes::new(),
+        );
+        api_post_multipart_for_pool.request_serializer = Arc::new(
+            MultipartSerializerForStream::new(Some(Arc::new(ThreadPool::new().ok().unwrap()))),
+        );
+        let resp = api_post_multipart_for_pool
+            .call(Some(simple_api::PathParam::new()), form_data_origin.clone())
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(
+            "\"age\":\"\":b\"1 month\"\n\"name\":\"\":b\"Baxter\"\n",
+            String::from_utf8(resp.to_vec()).ok().unwrap().as_str()
+        );
+    }
+
+    // The errors of the serializers are returned as SerializeError
+    {
+        use std::error::Error as StdError;
+
+        use http_api_service::simple_api::{AsyncBodySerializer, BodySerializer, SerializeError};
+
+        struct FailedSerializer;
+        impl BodySerializer<FormData, (String, Body)> for FailedSerializer {
+            fn encode(&self, _: FormData) -> Result<(String, Body), Box<dyn StdError>> {
+                Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "no form",
+                )))
+            }
+        }
+
+        let err = FailedSerializer
+            .serialize(form_data_origin.clone())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!("SerializeError: no form", err.to_string());
+        assert!(err
+            .source()
+            .unwrap()
+            .downcast_ref::<std::io::Error>()
+            .is_some());
+
+        let mut api_post_multipart_failed = base_service_setter.make_api_multipart_for_stream(
+            base_service_shared.clone(),
+            Method::POST,
+            "/form",
+            Arc::new(simple_api::DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+            &bytes::Bytes::new(),
+        );
+        api_post_multipart_failed.request_serializer = Arc::new(FailedSerializer);
+        let err = api_post_multipart_failed
+            .call(Some(simple_api::PathParam::new()), form_data_origin.clone())
+            .await
+            .err()
+            .unwrap();
+        assert!(err.downcast_ref::<SerializeError>().is_some());
+    }
+
+    // The producer failed: the request is aborted
+    {
+        use std::io::Write;
+
+        use http_api_service::common::{BodyProducerError, StreamingBodyWriter};
+
+        let (mut writer, reader) = StreamingBodyWriter::channel(0);
+        let producer = std::thread::spawn(move || {
+            writer.write_all(b"--partial").unwrap();
+            writer.abort("no more parts");
+        });
+        let resp = base_service_shared
+            .do_request(
+                Method::POST,
+                None,
+                "/form",
+                "multipart/form-data; boundary=\"partial\"",
+                None::<simple_api::PathParam>,
+                None::<simple_api::QueryParam>,
+                Body::wrap_stream(reader),
+            )
+            .await;
+        producer.join().unwrap();
+        let err = resp.err().unwrap();
+        println!("Error: aborted body {:?}", err);
+        assert_eq!(
+            "BodyProducerError: no more parts",
+            err.downcast_ref::<BodyProducerError>().unwrap().to_string()
+        );
+    }
 
     started_latch.wait();
     println!("REQ",);
@@ -561,3 +782,3544 @@ async fn test_simple_api_formdata() {
 
     println!("OK");
 }
+
+#[cfg(feature = "default")]
+#[tokio::test]
+async fn test_simple_api_upload() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use hyper::header::CONTENT_LENGTH;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::path_param;
+    use http_api_service::simple_api;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3500).into();
+
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let (parts, body_instance) = req.into_parts();
+            let content_length = parts
+                .headers
+                .get(CONTENT_LENGTH)
+                .map(|v| v.to_str().unwrap().to_string())
+                .unwrap_or_default();
+            let bytes = body::to_bytes(body_instance).await?;
+
+            let response = Response::new(Body::from(format!(
+                "{} {} {} {}",
+                parts.method,
+                parts.uri,
+                content_length,
+                bytes.len()
+            )));
+            Ok::<Response<Body>, hyper::Error>(response)
+        }))
+    }));
+
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let file_path = std::env::temp_dir().join("http_api_service_test_simple_api_upload.bin");
+    std::fs::write(&file_path, vec![7u8; 200 * 1024]).unwrap();
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_setter.set_base_url(
+        url::Url::parse(&("http://".to_string() + addr.to_string().as_str()))
+            .ok()
+            .unwrap(),
+    );
+
+    // PUT make_api_upload
+    let api_put_file = base_service_setter.make_api_upload(
+        base_service_shared.clone(),
+        Method::PUT,
+        "/files/{name}",
+        "application/octet-stream",
+        Arc::new(simple_api::DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &bytes::Bytes::new(),
+    );
+    let resp = api_put_file
+        .call(Some(path_param!["name" => "a.bin"]), &file_path)
+        .await;
+    let err = resp.as_ref().err();
+    println!("{:?}", err);
+    assert_eq!(false, resp.is_err());
+    assert_eq!(
+        "PUT /files/a.bin 204800 204800",
+        String::from_utf8(resp.ok().unwrap().to_vec())
+            .ok()
+            .unwrap()
+            .as_str()
+    );
+
+    // PUT make_api_bytes_in_out
+    let api_put_bytes = base_service_setter.make_api_bytes_in_out(
+        base_service_shared.clone(),
+        Method::PUT,
+        "/bytes",
+    );
+    let resp = api_put_bytes
+        .call(
+            None::<simple_api::PathParam>,
+            bytes::Bytes::from(vec![1u8; 10]),
+        )
+        .await;
+    assert_eq!(
+        "PUT /bytes 10 10",
+        String::from_utf8(resp.ok().unwrap().to_vec())
+            .ok()
+            .unwrap()
+            .as_str()
+    );
+    // GET make_api_bytes_out
+    let api_get_bytes =
+        base_service_setter.make_api_bytes_out(base_service_shared.clone(), Method::GET, "/bytes");
+    let resp = api_get_bytes.call(None).await;
+    assert_eq!(
+        "GET /bytes  0",
+        String::from_utf8(resp.ok().unwrap().to_vec())
+            .ok()
+            .unwrap()
+            .as_str()
+    );
+
+    // Missing files
+    let resp = api_put_file
+        .call(
+            Some(path_param!["name" => "a.bin"]),
+            file_path.join("missing"),
+        )
+        .await;
+    assert_eq!(true, resp.is_err());
+
+    let _ = std::fs::remove_file(&file_path);
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_simple_api_preconnect_and_close_idle() {
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::{PathParam, QueryParam};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3700).into();
+
+    let hyper_latch_for_thread = hyper_latch.clone();
+    let connection_count = Arc::new(AtomicUsize::new(0));
+    let connection_count_for_thread = connection_count.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        connection_count_for_thread.fetch_add(1, Ordering::SeqCst);
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+                Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                    "{} {}",
+                    req.method(),
+                    req.uri()
+                ))))
+            }))
+        }
+    }));
+
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let mut base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(
+        url::Url::parse(&("http://".to_string() + addr.to_string().as_str()))
+            .ok()
+            .unwrap(),
+    );
+    // The preconnects are not application requests (no interceptors)
+    let intercepted = Arc::new(AtomicUsize::new(0));
+    let intercepted_for_interceptor = intercepted.clone();
+    base_service_setter.add_interceptor_fn(move |_req| {
+        intercepted_for_interceptor.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    });
+
+    let resp = base_service_setter.preconnect().await;
+    assert!(resp.is_ok());
+    assert_eq!(1, connection_count.load(Ordering::SeqCst));
+    assert_eq!(0, intercepted.load(Ordering::SeqCst));
+
+    // The pooled connection is reused
+    let body = base_service_setter
+        .do_request(
+            Method::GET,
+            None,
+            "/pets",
+            "",
+            None::<PathParam>,
+            None::<QueryParam>,
+            Body::empty(),
+        )
+        .await
+        .ok()
+        .unwrap();
+    let bytes = hyper::body::to_bytes(*body).await.ok().unwrap();
+    assert_eq!("GET /pets", String::from_utf8(bytes.to_vec()).unwrap());
+    assert_eq!(1, connection_count.load(Ordering::SeqCst));
+    assert_eq!(1, intercepted.load(Ordering::SeqCst));
+
+    // The idle connection is closed, so a new one is made
+    base_service_setter.close_idle();
+    let resp = base_service_setter.preconnect().await;
+    assert!(resp.is_ok());
+    assert_eq!(2, connection_count.load(Ordering::SeqCst));
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_offload_serde() {
+    use std::error::Error as StdError;
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, ThreadId};
+
+    use bytes::Bytes;
+    use futures::executor::ThreadPool;
+
+    use http_api_service::simple_api::{
+        BodyDeserializer, BodySerializer, OffloadDeserializer, OffloadSerializer,
+        DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES,
+    };
+
+    // Record the threads where the bodies are decoded
+    struct ThreadRecorder(Mutex<Vec<ThreadId>>);
+    impl BodyDeserializer<Vec<u32>> for ThreadRecorder {
+        fn decode(&self, bytes: &Bytes) -> Result<Box<Vec<u32>>, Box<dyn StdError>> {
+            self.0.lock().unwrap().push(thread::current().id());
+            DEFAULT_SERDE_JSON_DESERIALIZER.decode(bytes)
+        }
+    }
+
+    let recorder = Arc::new(ThreadRecorder(Mutex::new(Vec::new())));
+    let deserializer = OffloadDeserializer::new(recorder.clone(), 16);
+
+    // Small bodies are decoded in place
+    let small = deserializer
+        .decode_async(Bytes::from("[1,2,3]"))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(vec![1, 2, 3], *small);
+    // Large bodies are decoded on another thread
+    let large_list = (0..1000).collect::<Vec<u32>>();
+    let large = deserializer
+        .decode_async(Bytes::from(serde_json::to_vec(&large_list).unwrap()))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(large_list, *large);
+    {
+        let threads = recorder.0.lock().unwrap();
+        assert_eq!(thread::current().id(), threads[0]);
+        assert!(thread::current().id() != threads[1]);
+    }
+
+    // The errors are returned with their messages
+    let err = deserializer
+        .decode_async(Bytes::from("[1,2,3,\"not a number\"]"))
+        .await
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("invalid type"));
+    // The errors of the serde deserializers are returned as they are
+    let deserializer: OffloadDeserializer<Vec<u32>> =
+        OffloadDeserializer::new(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER), 0);
+    let err = deserializer
+        .decode_async(Bytes::from("[1,2,3,\"not a number\"]"))
+        .await
+        .err()
+        .unwrap();
+    assert!(err.downcast_ref::<serde_json::Error>().is_some());
+
+    // Encode on the thread pool
+    let serializer = OffloadSerializer::new(
+        Arc::new(DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES),
+        Some(Arc::new(ThreadPool::new().ok().unwrap())),
+    );
+    let encoded: Bytes = serializer
+        .encode_async(large_list.clone())
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(serde_json::to_vec(&large_list).unwrap(), encoded.to_vec());
+
+    // Record the threads where the bodies are encoded
+    struct EncodingThreadRecorder(Mutex<Vec<ThreadId>>);
+    impl BodySerializer<Vec<u32>, Bytes> for EncodingThreadRecorder {
+        fn encode(&self, origin: Vec<u32>) -> Result<Bytes, Box<dyn StdError>> {
+            self.0.lock().unwrap().push(thread::current().id());
+            DEFAULT_SERDE_JSON_SERIALIZER_FOR_BYTES.encode(origin)
+        }
+    }
+
+    // Only the bodies estimated larger than the threshold are encoded on the thread pool
+    let recorder = Arc::new(EncodingThreadRecorder(Mutex::new(Vec::new())));
+    let serializer = OffloadSerializer::new_with_threshold(
+        recorder.clone(),
+        16,
+        Arc::new(|list: &Vec<u32>| list.len() * 4),
+        None,
+    );
+    let encoded = serializer.encode_async(vec![1, 2]).await.ok().unwrap();
+    assert_eq!("[1,2]", encoded);
+    let encoded = serializer
+        .encode_async(large_list.clone())
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(serde_json::to_vec(&large_list).unwrap(), encoded.to_vec());
+    {
+        let threads = recorder.0.lock().unwrap();
+        assert_eq!(thread::current().id(), threads[0]);
+        assert!(thread::current().id() != threads[1]);
+    }
+}
+
+#[tokio::test]
+async fn test_simd_json() {
+    use bytes::Bytes;
+    use serde::{Deserialize, Serialize};
+
+    use http_api_service::simple_api::{
+        BodyDeserializer, BodySerializer, DEFAULT_SERDE_JSON_DESERIALIZER,
+        DEFAULT_SIMD_JSON_DESERIALIZER, DEFAULT_SIMD_JSON_SERIALIZER,
+    };
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Product {
+        name: String,
+        age: String,
+        meta: Option<String>,
+    }
+
+    let product = Product {
+        name: "Alien \"quoted\"".to_string(),
+        age: "5 month".to_string(),
+        meta: None,
+    };
+
+    // The same as serde_json
+    let encoded: Bytes = DEFAULT_SIMD_JSON_SERIALIZER.encode(&product).ok().unwrap();
+    assert_eq!(serde_json::to_vec(&product).unwrap(), encoded.to_vec());
+    let decoded: Box<Product> = DEFAULT_SIMD_JSON_DESERIALIZER
+        .decode(&encoded)
+        .ok()
+        .unwrap();
+    assert_eq!(product, *decoded);
+    let decoded: Box<Product> = DEFAULT_SERDE_JSON_DESERIALIZER
+        .decode(&encoded)
+        .ok()
+        .unwrap();
+    assert_eq!(product, *decoded);
+
+    // The original body is kept (simd-json parses in place)
+    let body = Bytes::from("{\"name\":\"a\",\"age\":\"1\",\"meta\":\"\\u0041\"}");
+    let decoded: Box<Product> = DEFAULT_SIMD_JSON_DESERIALIZER.decode(&body).ok().unwrap();
+    assert_eq!(Some("A".to_string()), decoded.meta);
+    assert_eq!("{\"name\":\"a\",\"age\":\"1\",\"meta\":\"\\u0041\"}", body);
+
+    let result: Result<Box<Product>, _> = DEFAULT_SIMD_JSON_DESERIALIZER.decode(&Bytes::from("{"));
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_memoized_api() {
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+    use serde::Deserialize;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::MockClock;
+    use http_api_service::path_param;
+    use http_api_service::simple_api::{ApiBuilder, MemoCache, DEFAULT_SERDE_JSON_DESERIALIZER};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Product {
+        name: String,
+        age: String,
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3930).into();
+
+    let hyper_latch_for_thread = hyper_latch.clone();
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits_for_thread = hits.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let hits_for_thread_2 = hits_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                hits_for_thread_2.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                        "{{\"name\":\"{}\",\"age\":\"1 month\"}}",
+                        req.uri().path()
+                    ))))
+                }
+            }))
+        }
+    }));
+
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(
+        url::Url::parse(&("http://".to_string() + addr.to_string().as_str()))
+            .ok()
+            .unwrap(),
+    );
+    let base_service_shared = common_api.as_base_service_shared();
+
+    let clock = MockClock::new();
+    let cache = Arc::new(MemoCache::new_with_clock(
+        Duration::from_secs(60),
+        2,
+        Arc::new(clock.clone()),
+    ));
+    let api_get_product = ApiBuilder::new(Method::GET, "/products/{id}")
+        .deserializer(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(base_service_shared.clone())
+        .memoized(cache.clone());
+
+    // Memoized by the params
+    let first: Arc<Product> = api_get_product
+        .call(Some(path_param!["id" => "3"]))
+        .await
+        .ok()
+        .unwrap();
+    let second = api_get_product
+        .call(Some(path_param!["id" => "3"]))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("/products/3", first.name);
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_eq!(1, hits.load(Ordering::SeqCst));
+
+    let other = api_get_product
+        .call(Some(path_param!["id" => "5"]))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("/products/5", other.name);
+    assert_eq!(2, hits.load(Ordering::SeqCst));
+
+    // Expired by the TTL
+    clock.advance(Duration::from_secs(61));
+    let third = api_get_product
+        .call(Some(path_param!["id" => "3"]))
+        .await
+        .ok()
+        .unwrap();
+    assert!(!Arc::ptr_eq(&first, &third));
+    assert_eq!(3, hits.load(Ordering::SeqCst));
+
+    // Evicted when it's full (max_entries: 2)
+    assert_eq!(2, cache.len());
+    let _ = api_get_product
+        .call(Some(path_param!["id" => "7"]))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(2, cache.len());
+
+    // Invalidated
+    cache.clear();
+    let _ = api_get_product
+        .call(Some(path_param!["id" => "3"]))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(5, hits.load(Ordering::SeqCst));
+
+    // Shared by the services of the other base urls, the values are not mixed
+    let cache = Arc::new(MemoCache::new(Duration::from_secs(60), 16));
+    let other_common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let other_base_service_setter = other_common_api.as_base_service_setter();
+    other_base_service_setter.set_base_url(
+        url::Url::parse(&format!("http://{}/v2/", addr))
+            .ok()
+            .unwrap(),
+    );
+    let api_get_product = ApiBuilder::new(Method::GET, "products/{id}")
+        .deserializer(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(base_service_shared.clone())
+        .memoized(cache.clone());
+    let other_api_get_product = ApiBuilder::new(Method::GET, "products/{id}")
+        .deserializer(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(other_common_api.as_base_service_shared())
+        .memoized(cache.clone());
+    let product: Arc<Product> = api_get_product
+        .call(Some(path_param!["id" => "3"]))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("/products/3", product.name);
+    let product = other_api_get_product
+        .call(Some(path_param!["id" => "3"]))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("/v2/products/3", product.name);
+    assert_eq!(7, hits.load(Ordering::SeqCst));
+
+    // Varied by the identity of the caller
+    let user = Arc::new(std::sync::Mutex::new("alice".to_string()));
+    let user_for_vary = user.clone();
+    let api_get_product = ApiBuilder::new(Method::GET, "/products/{id}")
+        .deserializer(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(base_service_shared.clone())
+        .memoized(Arc::new(MemoCache::<Product>::new(
+            Duration::from_secs(60),
+            16,
+        )))
+        .vary_by(Arc::new(move || user_for_vary.lock().unwrap().clone()));
+    for (name, expected_hits) in [("alice", 8), ("alice", 8), ("bob", 9), ("alice", 9)] {
+        *user.lock().unwrap() = name.to_string();
+        let _ = api_get_product
+            .call(Some(path_param!["id" => "3"]))
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(expected_hits, hits.load(Ordering::SeqCst));
+    }
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_memoized_api_stale_while_revalidate() {
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+    use serde::Deserialize;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::MockClock;
+    use http_api_service::simple_api::{ApiBuilder, MemoCache, DEFAULT_SERDE_JSON_DESERIALIZER};
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Flags {
+        version: usize,
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3940).into();
+
+    let hyper_latch_for_thread = hyper_latch.clone();
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits_for_thread = hits.clone();
+    let broken = Arc::new(AtomicBool::new(false));
+    let broken_for_thread = broken.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let hits_for_thread_2 = hits_for_thread.clone();
+        let broken_for_thread_2 = broken_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| {
+                let version = hits_for_thread_2.fetch_add(1, Ordering::SeqCst) + 1;
+                let broken = broken_for_thread_2.load(Ordering::SeqCst);
+                async move {
+                    Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(if broken {
+                        "broken".to_string()
+                    } else {
+                        format!("{{\"version\":{}}}", version)
+                    })))
+                }
+            }))
+        }
+    }));
+
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(
+        url::Url::parse(&("http://".to_string() + addr.to_string().as_str()))
+            .ok()
+            .unwrap(),
+    );
+    let base_service_shared = common_api.as_base_service_shared();
+
+    let clock = MockClock::new();
+    let refresh_errors = Arc::new(Mutex::new(Vec::new()));
+    let refresh_errors_for_hook = refresh_errors.clone();
+    let mut cache = MemoCache::new_with_clock(Duration::from_secs(60), 16, Arc::new(clock.clone()));
+    cache.stale_while_revalidate = Duration::from_secs(600);
+    cache.refresh_error_hook = Some(Arc::new(move |key, _e| {
+        refresh_errors_for_hook
+            .lock()
+            .unwrap()
+            .push(key.to_string());
+    }));
+    let api_get_flags = Arc::new(
+        ApiBuilder::new(Method::GET, "/flags")
+            .deserializer(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+            .build_no_body(base_service_shared.clone())
+            .memoized(Arc::new(cache)),
+    );
+
+    // The refreshes are spawned by `tokio::task::spawn_local()`
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async {
+            let wait_hits = |expected: usize| {
+                let hits = hits.clone();
+                async move {
+                    while hits.load(Ordering::SeqCst) < expected {
+                        tokio::task::yield_now().await;
+                    }
+                }
+            };
+
+            // Missed, then fresh
+            let (flags, spawned): (Arc<Flags>, _) =
+                api_get_flags.call_swr(None).await.ok().unwrap();
+            assert_eq!(1, flags.version);
+            assert!(!spawned);
+            let (flags, spawned) = api_get_flags.call_swr(None).await.ok().unwrap();
+            assert_eq!(1, flags.version);
+            assert!(!spawned);
+            assert_eq!(1, hits.load(Ordering::SeqCst));
+
+            // Stale: served immediately, refreshed once in the background
+            clock.advance(Duration::from_secs(61));
+            let (flags, spawned) = api_get_flags.call_swr(None).await.ok().unwrap();
+            assert_eq!(1, flags.version);
+            assert!(spawned);
+            let (_, spawned_again) = api_get_flags.call_swr(None).await.ok().unwrap();
+            assert!(!spawned_again);
+            wait_hits(2).await;
+            // The refreshed value is inserted after the response
+            let flags = loop {
+                let (flags, _) = api_get_flags.call_swr(None).await.ok().unwrap();
+                if flags.version == 2 {
+                    break flags;
+                }
+                tokio::task::yield_now().await;
+            };
+            assert_eq!(2, flags.version);
+            assert_eq!(2, hits.load(Ordering::SeqCst));
+
+            // The refresh failures are observed, and the stale value is kept
+            broken.store(true, Ordering::SeqCst);
+            clock.advance(Duration::from_secs(61));
+            let (_, spawned) = api_get_flags.call_swr(None).await.ok().unwrap();
+            assert!(spawned);
+            while refresh_errors.lock().unwrap().is_empty() {
+                tokio::task::yield_now().await;
+            }
+            assert_eq!(1, refresh_errors.lock().unwrap().len());
+            // The failed refresh doesn't block the later ones
+            let (flags, spawned) = api_get_flags.call_swr(None).await.ok().unwrap();
+            assert_eq!(2, flags.version);
+            assert!(spawned);
+            while refresh_errors.lock().unwrap().len() < 2 {
+                tokio::task::yield_now().await;
+            }
+
+            // Expired beyond the stale_while_revalidate
+            broken.store(false, Ordering::SeqCst);
+            clock.advance(Duration::from_secs(600));
+            let (flags, spawned) = api_get_flags.call_swr(None).await.ok().unwrap();
+            assert_eq!(5, flags.version);
+            assert!(!spawned);
+        })
+        .await;
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_session() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{self, Session};
+    use http_api_service::common::{HeaderAuth, Headers};
+    use http_api_service::path_param;
+    use http_api_service::simple_api::{ApiBuilder, DEFAULT_SERDE_JSON_DESERIALIZER};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3980).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // "/{version}/login/{user}" sets the cookie, and the others echo the headers
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let header = |name: &str| {
+                req.headers()
+                    .get(name)
+                    .map(|v| v.to_str().unwrap().to_string())
+                    .unwrap_or_default()
+            };
+            let path = req.uri().path().to_string();
+            let mut resp = Response::new(Body::from(format!(
+                "\"{} app={} tenant={} auth={} cookie={}\"",
+                path,
+                header("X-App"),
+                header("X-Tenant"),
+                header("Authorization"),
+                header("Cookie")
+            )));
+            if let Some(user) = path.split("/login/").nth(1) {
+                resp.headers_mut().insert(
+                    "Set-Cookie",
+                    format!("sid={}; Path=/; HttpOnly", user).parse().unwrap(),
+                );
+            }
+            Ok::<Response<Body>, hyper::Error>(resp)
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = format!("http://{}", addr);
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&(base_url.clone() + "/v1/")).ok().unwrap());
+    let mut shared_header = Headers::new();
+    shared_header.insert("X-App", "app");
+    base_service_setter.set_default_header(Some(shared_header));
+
+    // Two identities over the shared CommonAPI
+    let alice = Session::new(&common_api);
+    alice.set_auth_provider(Some(Arc::new(
+        HeaderAuth::bearer("alice-token").ok().unwrap(),
+    )));
+    let mut tenant_header = Headers::new();
+    tenant_header.insert("X-Tenant", "a");
+    alice.set_default_header(Some(tenant_header));
+    let bob = Session::new(&common_api);
+    bob.set_auth_provider(Some(Arc::new(
+        HeaderAuth::bearer("bob-token").ok().unwrap(),
+    )));
+    bob.set_base_url(Some(url::Url::parse(&(base_url + "/v2/")).ok().unwrap()));
+
+    let make_apis = |session: &Session<_, _, _, _, _>| {
+        let service = session.as_base_service_shared();
+        (
+            ApiBuilder::new(Method::POST, "login/{user}")
+                .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+                .build_no_body(service.clone()),
+            ApiBuilder::new(Method::GET, "profile")
+                .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+                .build_no_body(service),
+        )
+    };
+    let (alice_login, alice_profile) = make_apis(&alice);
+    let (bob_login, bob_profile) = make_apis(&bob);
+
+    let (alice_result, bob_result) = tokio::join!(
+        alice_login.call(Some(path_param!["user" => "alice"])),
+        bob_login.call(Some(path_param!["user" => "bob"])),
+    );
+    assert_eq!(
+        "/v1/login/alice app=app tenant=a auth=Bearer alice-token cookie=",
+        *alice_result.ok().unwrap()
+    );
+    assert_eq!(
+        "/v2/login/bob app=app tenant= auth=Bearer bob-token cookie=",
+        *bob_result.ok().unwrap()
+    );
+
+    // The cookies are kept per session
+    let (alice_result, bob_result) = tokio::join!(alice_profile.call(None), bob_profile.call(None));
+    assert_eq!(
+        "/v1/profile app=app tenant=a auth=Bearer alice-token cookie=sid=alice",
+        *alice_result.ok().unwrap()
+    );
+    assert_eq!(
+        "/v2/profile app=app tenant= auth=Bearer bob-token cookie=sid=bob",
+        *bob_result.ok().unwrap()
+    );
+    assert_eq!(1, alice.get_cookie_jar().cookies().len());
+
+    // The shared CommonAPI isn't changed by the sessions
+    let shared_profile = ApiBuilder::new(Method::GET, "profile")
+        .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(common_api.as_base_service_shared());
+    assert_eq!(
+        "/v1/profile app=app tenant= auth= cookie=",
+        *shared_profile.call(None).await.ok().unwrap()
+    );
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_call_absolute() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{self, Session};
+    use http_api_service::bind_ureq;
+    use http_api_service::common::{HeaderAuth, Headers, Versioning};
+    use http_api_service::simple_api::{
+        ApiBuilder, DEFAULT_DUMMY_BYPASS_SERIALIZER_FOR_BODY, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4040).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the url, the Authorization and the body
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let (parts, body_instance) = req.into_parts();
+            let auth = parts
+                .headers
+                .get("Authorization")
+                .map(|v| v.to_str().unwrap().to_string())
+                .unwrap_or_default();
+            let body = body::to_bytes(body_instance).await?;
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                "\"{} auth={} body={}\"",
+                parts.uri,
+                auth,
+                std::str::from_utf8(&body).unwrap()
+            ))))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = format!("http://{}", addr);
+    let signed_url = base_url.clone() + "/bucket/file?sig=a%2Fb&expires=1";
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&(base_url.clone() + "/api/")).ok().unwrap());
+    base_service_setter.set_versioning(Versioning::path_prefix("v2"));
+    let mut shared_header = Headers::new();
+    shared_header.insert("Authorization", "Bearer shared-token");
+    base_service_setter.set_default_header(Some(shared_header));
+
+    let api_get = ApiBuilder::new(Method::GET, "files/{id}")
+        .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(common_api.as_base_service_shared());
+    // Called verbatim (no base url, no versioning)
+    assert_eq!(
+        "/bucket/file?sig=a%2Fb&expires=1 auth=Bearer shared-token body=",
+        *api_get.call_absolute(&signed_url).await.ok().unwrap()
+    );
+    // Without the credentials
+    assert_eq!(
+        "/bucket/file?sig=a%2Fb&expires=1 auth= body=",
+        *api_get
+            .call_absolute_with_options(&signed_url, true, None)
+            .await
+            .ok()
+            .unwrap()
+    );
+    // The relative urls are rejected
+    assert!(api_get.call_absolute("/bucket/file").await.is_err());
+
+    // With the body, by the session (not rebased)
+    let session = Session::new(&common_api);
+    session.set_base_url(Some(
+        url::Url::parse(&(base_url.clone() + "/other/"))
+            .ok()
+            .unwrap(),
+    ));
+    session.set_auth_provider(Some(Arc::new(
+        HeaderAuth::bearer("session-token").ok().unwrap(),
+    )));
+    let api_put = ApiBuilder::new(Method::PUT, "files/{id}")
+        .serializer(Arc::new(DEFAULT_DUMMY_BYPASS_SERIALIZER_FOR_BODY))
+        .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build(session.as_base_service_shared());
+    assert_eq!(
+        "/bucket/file?sig=a%2Fb&expires=1 auth=Bearer session-token body=data",
+        *api_put
+            .call_absolute(&signed_url, Body::from("data"))
+            .await
+            .ok()
+            .unwrap()
+    );
+    assert_eq!(
+        "/bucket/file?sig=a%2Fb&expires=1 auth= body=data",
+        *api_put
+            .call_absolute_with_options(&signed_url, true, None, Body::from("data"))
+            .await
+            .ok()
+            .unwrap()
+    );
+    // The relative calls are not changed
+    assert_eq!(
+        "/other/v2/files/3 auth=Bearer session-token body=",
+        *api_put
+            .call(
+                Some(http_api_service::path_param!["id" => "3"]),
+                Body::empty()
+            )
+            .await
+            .ok()
+            .unwrap()
+    );
+
+    // By ureq
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    let base_service_setter_ureq = common_api_ureq.as_base_service_setter();
+    let mut shared_header = Headers::new();
+    shared_header.insert("Authorization", "Bearer shared-token");
+    base_service_setter_ureq.set_default_header(Some(shared_header));
+    let api_get_ureq = ApiBuilder::new(Method::GET, "files/{id}")
+        .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(common_api_ureq.as_base_service_shared());
+    assert_eq!(
+        "/bucket/file?sig=a%2Fb&expires=1 auth=Bearer shared-token body=",
+        *api_get_ureq.call_absolute(&signed_url).await.ok().unwrap()
+    );
+    assert_eq!(
+        "/bucket/file?sig=a%2Fb&expires=1 auth= body=",
+        *api_get_ureq
+            .call_absolute_with_options(&signed_url, true, None)
+            .await
+            .ok()
+            .unwrap()
+    );
+
+    hyper_latch.countdown();
+}
+
+#[test]
+fn test_check_content_type() {
+    use http_api_service::simple_api::check_content_type;
+
+    assert!(check_content_type("application/json", Some("application/json")).is_ok());
+    assert!(
+        check_content_type("application/json", Some("Application/JSON; charset=utf-8")).is_ok()
+    );
+    assert!(check_content_type("application/*", Some("application/xml")).is_ok());
+    assert!(check_content_type("*/*", Some("text/html")).is_ok());
+    assert!(
+        check_content_type("application/json; charset=utf-8", Some("application/json")).is_ok()
+    );
+    assert!(check_content_type(
+        "application/json; charset=utf-8",
+        Some("application/json; charset=\"ISO-8859-1\"")
+    )
+    .is_err());
+    assert!(check_content_type("application/json", Some("application/problem+json")).is_err());
+    assert!(check_content_type("application/*", Some("text/html")).is_err());
+
+    let err = check_content_type("application/json", None).err().unwrap();
+    assert_eq!("application/json", err.expected);
+    assert_eq!(None, err.actual);
+    let err = check_content_type("application/json", Some("text/html"))
+        .err()
+        .unwrap();
+    assert_eq!(Some("text/html".to_string()), err.actual);
+    assert_eq!(
+        "UnexpectedContentTypeError: expected application/json, got text/html",
+        err.to_string()
+    );
+}
+
+#[tokio::test]
+async fn test_strict_content_type() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::bind_ureq;
+    use http_api_service::simple_api::{
+        ApiBuilder, UnexpectedContentTypeError, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4054).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // "/json" is JSON, "/html" is an HTML error page (of a proxy), and "/none" has no Content-Type
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let response = match req.uri().path() {
+                "/json" => Response::builder()
+                    .header("Content-Type", "application/json; charset=utf-8")
+                    .body(Body::from("\"ok\"")),
+                "/html" => Response::builder()
+                    .header("Content-Type", "text/html")
+                    .body(Body::from("<html>Bad Gateway</html>")),
+                _ => Response::builder().body(Body::from("\"ok\"")),
+            };
+            Ok::<Response<Body>, hyper::Error>(response.unwrap())
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    common_api
+        .as_base_service_setter()
+        .set_base_url(base_url.clone());
+    let api_of = |relative_url: &str, strict: bool| {
+        let builder = ApiBuilder::new(Method::GET, relative_url)
+            .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER));
+        let builder = if strict {
+            builder.strict_content_type("application/json")
+        } else {
+            builder
+        };
+        builder.build_no_body(common_api.as_base_service_shared())
+    };
+
+    assert_eq!("ok", *api_of("/json", true).call(None).await.ok().unwrap());
+    assert_eq!("ok", *api_of("/none", false).call(None).await.ok().unwrap());
+    assert!(api_of("/none", true).call(None).await.is_err());
+
+    // The HTML isn't fed to the deserializer
+    let err = api_of("/html", true).call(None).await.err().unwrap();
+    let err = err.downcast_ref::<UnexpectedContentTypeError>().unwrap();
+    assert_eq!(Some("text/html".to_string()), err.actual);
+    let err = api_of("/html", false).call(None).await.err().unwrap();
+    assert!(err.downcast_ref::<UnexpectedContentTypeError>().is_none());
+
+    // By ureq
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    common_api_ureq
+        .as_base_service_setter()
+        .set_base_url(base_url);
+    let api_ureq = ApiBuilder::new(Method::GET, "/html")
+        .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .strict_content_type("application/json")
+        .build_no_body(common_api_ureq.as_base_service_shared());
+    let err = api_ureq.call(None).await.err().unwrap();
+    assert!(err.downcast_ref::<UnexpectedContentTypeError>().is_some());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_deserializer_stream() {
+    use std::error::Error as StdError;
+    use std::future::Future;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use futures::{stream, StreamExt};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{
+        collect_body_stream, ApiBuilder, BodyDeserializer, BodyStream,
+        DEFAULT_DUMMY_BYPASS_DESERIALIZER, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    // The owned bodies are bypassed without copying
+    let bytes = Bytes::from("zero-copy");
+    let decoded = DEFAULT_DUMMY_BYPASS_DESERIALIZER
+        .decode_owned(bytes.clone())
+        .ok()
+        .unwrap();
+    assert_eq!(bytes.as_ptr(), decoded.as_ptr());
+
+    // Collected from the chunks (a single one is kept as it is)
+    let chunks: BodyStream = Box::pin(stream::iter(vec![Ok(bytes.clone())]));
+    assert_eq!(
+        bytes.as_ptr(),
+        collect_body_stream(chunks).await.ok().unwrap().as_ptr()
+    );
+    let chunks: BodyStream = Box::pin(stream::iter(vec![
+        Ok(Bytes::from("[1,")),
+        Ok(Bytes::from("2,")),
+        Ok(Bytes::from("3]")),
+    ]));
+    let decoded: Box<Vec<u32>> = DEFAULT_SERDE_JSON_DESERIALIZER
+        .decode_stream(chunks)
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(vec![1, 2, 3], *decoded);
+
+    // Parse the lines incrementally, and record the chunks
+    struct LinesDeserializer(Mutex<Vec<usize>>);
+    impl BodyDeserializer<Vec<String>> for LinesDeserializer {
+        fn decode(&self, bytes: &Bytes) -> Result<Box<Vec<String>>, Box<dyn StdError>> {
+            let text = std::str::from_utf8(bytes)?;
+            Ok(Box::new(text.lines().map(|v| v.to_string()).collect()))
+        }
+        fn decode_stream<'a>(
+            &'a self,
+            mut stream: BodyStream<'a>,
+        ) -> Pin<Box<dyn Future<Output = Result<Box<Vec<String>>, Box<dyn StdError>>> + 'a>>
+        where
+            Vec<String>: 'a,
+        {
+            Box::pin(async move {
+                let mut lines = Vec::new();
+                let mut pending = String::new();
+                while let Some(chunk) = stream.next().await {
+                    let chunk = chunk?;
+                    self.0.lock().unwrap().push(chunk.len());
+                    pending.push_str(std::str::from_utf8(&chunk)?);
+                    while let Some(index) = pending.find('\n') {
+                        lines.push(pending[..index].to_string());
+                        pending.drain(..=index);
+                    }
+                }
+                if !pending.is_empty() {
+                    lines.push(pending);
+                }
+                Ok(Box::new(lines))
+            })
+        }
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4000).into();
+
+    let hyper_latch_for_thread = hyper_latch.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+            // The chunks are sent separately
+            let chunks = stream::iter(vec!["a\nb", "b\nc", "c\n"]).then(|chunk| async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok::<_, hyper::Error>(chunk)
+            });
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::wrap_stream(chunks)))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let deserializer = Arc::new(LinesDeserializer(Mutex::new(Vec::new())));
+    let api_get_lines = ApiBuilder::new(Method::GET, "/lines")
+        .deserializer(deserializer.clone())
+        .build_no_body(common_api.as_base_service_shared());
+
+    let lines = api_get_lines.call(None).await.ok().unwrap();
+    assert_eq!(vec!["a", "bb", "cc"], *lines);
+    let chunks = deserializer.0.lock().unwrap().clone();
+    assert!(chunks.len() > 1);
+    assert_eq!(8, chunks.iter().sum::<usize>());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_quick() {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::quick::{get_json, post_json, StatusError};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4058).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Count the connections, the shared client reuses the pooled one
+    let connections = Arc::new(AtomicUsize::new(0));
+    let connections_for_server = connections.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        connections_for_server.fetch_add(1, Ordering::SeqCst);
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+                let response = match req.uri().path() {
+                    "/product" => Response::new(Body::from("{\"name\":\"apple\"}")),
+                    // Echo the body
+                    "/echo" => Response::new(req.into_body()),
+                    _ => Response::builder()
+                        .status(404)
+                        .body(Body::from("not found"))
+                        .unwrap(),
+                };
+                Ok::<Response<Body>, hyper::Error>(response)
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let product = get_json::<HashMap<String, String>>(&format!("http://{}/product", addr))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("apple", product["name"]);
+
+    let echo = post_json::<_, Vec<u32>>(&format!("http://{}/echo", addr), vec![1, 2, 3])
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(vec![1, 2, 3], *echo);
+
+    let err = get_json::<String>(&format!("http://{}/missing", addr))
+        .await
+        .err()
+        .unwrap();
+    let err = err.downcast_ref::<StatusError>().unwrap();
+    assert_eq!(404, err.status);
+    assert_eq!("not found", err.body);
+
+    assert_eq!(1, connections.load(Ordering::SeqCst));
+
+    hyper_latch.countdown();
+}
+
+#[test]
+fn test_form_data_from_serialize() {
+    use std::path::PathBuf;
+
+    use formdata::FormData;
+    use serde::Serialize;
+
+    use http_api_service::simple_api::{
+        BodySerializer, FormDataExt, FormFile, DEFAULT_MULTIPART_SERIALIZER_FOR_BYTES,
+    };
+
+    #[derive(Serialize)]
+    struct Inner {
+        x: u32,
+    }
+    #[derive(Serialize)]
+    struct Upload {
+        #[serde(rename = "title")]
+        name: String,
+        age: u32,
+        adopted: bool,
+        nickname: Option<String>,
+        #[serde(skip)]
+        _ignored: String,
+        tags: Vec<String>,
+        inner: Inner,
+        photo: FormFile,
+    }
+
+    let path = std::env::temp_dir().join("http_api_service_test_form_data.txt");
+    std::fs::write(&path, "file content").ok().unwrap();
+
+    let form_data = FormData::from_serialize(&Upload {
+        name: "Baxter".to_string(),
+        age: 1,
+        adopted: true,
+        nickname: None,
+        _ignored: "ignored".to_string(),
+        tags: vec!["dog".to_string(), "puppy".to_string()],
+        inner: Inner { x: 3 },
+        photo: FormFile::new(&path).content_type("text/plain"),
+    })
+    .ok()
+    .unwrap()
+    .with_field("extra", "1");
+
+    // In the order of the names
+    assert_eq!(
+        vec![
+            ("adopted".to_string(), "true".to_string()),
+            ("age".to_string(), "1".to_string()),
+            ("inner".to_string(), "{\"x\":3}".to_string()),
+            ("tags".to_string(), "dog".to_string()),
+            ("tags".to_string(), "puppy".to_string()),
+            ("title".to_string(), "Baxter".to_string()),
+            ("extra".to_string(), "1".to_string()),
+        ],
+        form_data.fields
+    );
+    assert_eq!(1, form_data.files.len());
+    assert_eq!("photo", form_data.files[0].0);
+    assert_eq!(PathBuf::from(&path), form_data.files[0].1.path);
+
+    let (content_type, body) = DEFAULT_MULTIPART_SERIALIZER_FOR_BYTES
+        .encode(form_data)
+        .ok()
+        .unwrap();
+    assert!(content_type.starts_with("multipart/form-data"));
+    let body = String::from_utf8_lossy(&body);
+    assert!(body.contains("filename=\"http_api_service_test_form_data.txt\""));
+    assert!(body.contains("Content-Type: text/plain"));
+    assert!(body.contains("file content"));
+
+    // Not a struct
+    assert!(FormData::from_serialize(&vec![1, 2]).is_err());
+
+    std::fs::remove_file(&path).ok().unwrap();
+}
+
+#[tokio::test]
+async fn test_bytes_in_sniffed() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{MakeApi, PathParam, DEFAULT_DUMMY_BYPASS_DESERIALIZER};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4059).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the Content-Type
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let content_type = req.headers()["content-type"].clone();
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(
+                content_type.to_str().unwrap().to_string(),
+            )))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_shared.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let api = base_service_shared.api_bytes_in_sniffed(
+        Method::POST,
+        "/upload",
+        Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &Bytes::new(),
+    );
+
+    let res = api
+        .call(None::<PathParam>, Bytes::from_static(b"%PDF-1.7\n"))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("application/pdf", *res);
+    let res = api
+        .call(None::<PathParam>, Bytes::from_static(b"plain text"))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("application/octet-stream", *res);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_shutdown() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use futures::future::join;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{
+        MakeApi, ShuttingDownError, DEFAULT_DUMMY_BYPASS_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4060).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Respond slowly
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from("slow")))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_shared.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let api = base_service_shared.api_no_body(
+        Method::GET,
+        "/slow",
+        Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &Bytes::new(),
+    );
+
+    // Shut down once the call is in flight
+    let drain_gate = base_service_shared.get_drain_gate();
+    let wait_in_flight = || async {
+        while drain_gate.in_flight() == 0 {
+            tokio::task::yield_now().await;
+        }
+    };
+
+    // The in-flight call is waited
+    let (res, shutdown) = join(api.call(None), async {
+        wait_in_flight().await;
+        base_service_shared.shutdown(Duration::from_secs(5)).await
+    })
+    .await;
+    assert_eq!("slow", *res.ok().unwrap());
+    assert!(shutdown.is_ok());
+    assert_eq!(0, base_service_shared.get_drain_gate().in_flight());
+
+    // The new calls are rejected
+    let e = api.call(None).await.err().unwrap();
+    assert!(e.downcast_ref::<ShuttingDownError>().is_some());
+
+    // The in-flight call isn't finished within the grace period
+    base_service_shared.get_drain_gate().reopen();
+    let (res, shutdown) = join(api.call(None), async {
+        wait_in_flight().await;
+        base_service_shared
+            .shutdown(Duration::from_millis(10))
+            .await
+    })
+    .await;
+    assert!(res.is_ok());
+    let e = shutdown.err().unwrap();
+    assert_eq!(1, e.downcast_ref::<ShuttingDownError>().unwrap().in_flight);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_status() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::OfflineError;
+    use http_api_service::simple_api::{
+        MakeApi, MemoCache, ServiceStatus, DEFAULT_DUMMY_BYPASS_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4061).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(
+                req.uri().path().to_string(),
+            )))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_shared.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let api_get_flags = base_service_shared
+        .api_no_body(
+            Method::GET,
+            "/flags",
+            Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+            &Bytes::new(),
+        )
+        .memoized(Arc::new(MemoCache::new(Duration::from_secs(60), 16)));
+
+    assert_eq!(
+        ServiceStatus {
+            in_flight: 0,
+            shutting_down: false,
+            offline: false,
+            queue_depth: None,
+            cache_entries: 0,
+            last_error: None,
+        },
+        base_service_shared.status()
+    );
+
+    // The memoized responses are counted
+    assert_eq!("/flags", *api_get_flags.call(None).await.ok().unwrap());
+    assert_eq!(1, base_service_shared.status().cache_entries);
+
+    // The last error is reported
+    base_service_shared.set_offline(true);
+    let api_get_other = base_service_shared.api_no_body(
+        Method::GET,
+        "/other",
+        Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &Bytes::new(),
+    );
+    let e = api_get_other.call(None).await.err().unwrap();
+    assert!(e.downcast_ref::<OfflineError>().is_some());
+    let status = base_service_shared.status();
+    assert!(status.offline);
+    assert_eq!(e.to_string(), status.last_error.unwrap().message);
+
+    // The dropped caches are not counted
+    drop(api_get_flags);
+    assert_eq!(0, base_service_shared.status().cache_entries);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_request_validator() {
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+    use serde::Serialize;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{
+        FieldViolation, PathParam, QueryParam, RequestValidator, RequestValidatorFunc,
+        ValidationError, Violations, DEFAULT_DUMMY_BYPASS_DESERIALIZER,
+        DEFAULT_SERDE_JSON_SERIALIZER,
+    };
+
+    #[derive(Serialize)]
+    struct Product {
+        name: String,
+        status: String,
+    }
+    struct ProductValidator;
+    impl RequestValidator<Product> for ProductValidator {
+        fn validate(&self, value: &Product) -> Result<(), ValidationError> {
+            let mut violations = Violations::new();
+            violations
+                .check_length("name", &value.name, 1, 8)
+                .check_one_of("status", &value.status, &["draft", "published"]);
+            violations.into_result()
+        }
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4070).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let received = Arc::new(AtomicUsize::new(0));
+    let received_for_thread = received.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let received = received_for_thread.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| {
+                received.fetch_add(1, Ordering::SeqCst);
+                async move { Ok::<Response<Body>, hyper::Error>(Response::new(Body::from("ok"))) }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let base_service_shared = common_api.as_base_service_shared();
+
+    let api_post_product = base_service_setter
+        .make_api_has_body(
+            base_service_shared.clone(),
+            Method::POST,
+            "/products",
+            "application/json",
+            Arc::new(DEFAULT_SERDE_JSON_SERIALIZER),
+            Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+            &bytes::Bytes::new(),
+        )
+        .with_validator(Arc::new(ProductValidator));
+    let product = |name: &str, status: &str| Product {
+        name: name.to_string(),
+        status: status.to_string(),
+    };
+
+    assert_eq!(
+        "ok",
+        *api_post_product
+            .call(None::<PathParam>, product("Alice", "draft"))
+            .await
+            .ok()
+            .unwrap()
+    );
+    assert_eq!(1, received.load(Ordering::SeqCst));
+
+    // All the violations are reported, and nothing is sent
+    let err = api_post_product
+        .call(None::<PathParam>, product("", "deleted"))
+        .await
+        .err()
+        .unwrap();
+    let err = err.downcast_ref::<ValidationError>().unwrap();
+    assert_eq!(
+        vec!["name", "status"],
+        err.violations
+            .iter()
+            .map(|v| v.field.as_str())
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        "ValidationError: name: length 0 isn't within 1..=8, status: \"deleted\" isn't one of [\"draft\", \"published\"]",
+        err.to_string()
+    );
+    assert_eq!(1, received.load(Ordering::SeqCst));
+
+    // The query parameters (the empty ones if there's none)
+    let api_search = base_service_setter
+        .make_api_no_body(
+            base_service_shared.clone(),
+            Method::GET,
+            "/products",
+            Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+            &bytes::Bytes::new(),
+        )
+        .with_query_validator(Arc::new(RequestValidatorFunc::new(
+            |query_param: &QueryParam| {
+                let mut violations = Violations::new();
+                violations.check_required(query_param, "q");
+                if let Some(limit) = query_param.get("limit") {
+                    violations.check(
+                        matches!(limit.parse::<u32>(), Ok(v) if v <= 100),
+                        "limit",
+                        "isn't a number up to 100",
+                    );
+                }
+                violations.into_result()
+            },
+        )));
+    let query = |pairs: &[(&str, &str)]| {
+        Some(
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<QueryParam>(),
+        )
+    };
+    let valid_query = query(&[("q", "tea"), ("limit", "10")]);
+    assert!(api_search
+        .call_with_options(None, None::<PathParam>, valid_query)
+        .await
+        .is_ok());
+    assert_eq!(2, received.load(Ordering::SeqCst));
+    let err = api_search.call(None).await.err().unwrap();
+    assert_eq!(
+        vec![FieldViolation {
+            field: "q".to_string(),
+            message: "is required".to_string()
+        }],
+        err.downcast_ref::<ValidationError>().unwrap().violations
+    );
+    let invalid_query = query(&[("q", "tea"), ("limit", "1000")]);
+    let err = api_search
+        .call_with_options(None, None::<PathParam>, invalid_query)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        "limit",
+        err.downcast_ref::<ValidationError>().unwrap().violations[0].field
+    );
+    assert_eq!(2, received.load(Ordering::SeqCst));
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_locale() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, HeaderMap, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::bind_ureq;
+    use http_api_service::simple_api::{
+        localized, Locale, LocaleHeaders, PathParam, QueryParam, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4071).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the locale headers, in the language of the first tag ("/any" has no Content-Language)
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let header = |name: &str| {
+                req.headers()
+                    .get(name)
+                    .map(|v| v.to_str().unwrap().to_string())
+                    .unwrap_or_default()
+            };
+            let language = header("Accept-Language");
+            let body = format!(
+                "\"{}|{}|{}\"",
+                language,
+                header("X-Units"),
+                header("X-Timezone")
+            );
+            let mut response = Response::builder();
+            if req.uri().path() != "/any" {
+                response = response.header(
+                    "Content-Language",
+                    language.split(',').next().unwrap_or_default(),
+                );
+            }
+            Ok::<Response<Body>, hyper::Error>(response.body(Body::from(body)).unwrap())
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let default_locale = Some(Locale::new("en-US").with_units("imperial"));
+
+    // By hyper
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let mut base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(base_url.clone());
+    base_service_setter.add_interceptor_locale(default_locale.clone(), LocaleHeaders::default());
+    let base_service_shared = common_api.as_base_service_shared();
+    let api_of = |relative_url: &str| {
+        base_service_setter.make_api_no_body(
+            base_service_shared.clone(),
+            Method::GET,
+            relative_url,
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &String::new(),
+        )
+    };
+
+    // The default locale of the service
+    let result = localized(None, api_of("/products").call(None)).await;
+    assert_eq!("en-US|imperial|", *result.value.ok().unwrap());
+    assert_eq!(Some("en-US".to_string()), result.content_language);
+    // The locale of the call
+    let result = localized(
+        Some(
+            Locale::new("fr-CA, fr;q=0.8")
+                .with_units("metric")
+                .with_timezone("America/Montreal"),
+        ),
+        api_of("/products").call(None),
+    )
+    .await;
+    assert_eq!(
+        "fr-CA, fr;q=0.8|metric|America/Montreal",
+        *result.value.ok().unwrap()
+    );
+    assert_eq!(Some("fr-CA".to_string()), result.content_language);
+    let result = localized(None, api_of("/any").call(None)).await;
+    assert_eq!(None, result.content_language);
+    // The `Accept-Language` of the call is kept
+    let mut header = HeaderMap::new();
+    header.insert("Accept-Language", "ja".parse().unwrap());
+    let result = localized(
+        None,
+        api_of("/products").call_with_options(Some(header), None::<PathParam>, None::<QueryParam>),
+    )
+    .await;
+    assert_eq!("ja||", *result.value.ok().unwrap());
+    // The illegal values fail the calls
+    let result = localized(
+        Some(Locale::new("en\r\nX: y")),
+        api_of("/products").call(None),
+    )
+    .await;
+    assert!(result.value.is_err());
+
+    // By ureq
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    let mut base_service_setter_ureq = common_api_ureq.as_base_service_setter();
+    base_service_setter_ureq.set_base_url(base_url);
+    base_service_setter_ureq.add_interceptor_locale(
+        default_locale,
+        LocaleHeaders {
+            units: None,
+            timezone: Some("Time-Zone".to_string()),
+        },
+    );
+    let api_ureq = base_service_setter_ureq.make_api_no_body(
+        common_api_ureq.as_base_service_shared(),
+        Method::GET,
+        "/products",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+    let result = localized(
+        Some(Locale::new("de").with_units("metric")),
+        api_ureq.call(None),
+    )
+    .await;
+    assert_eq!("de||", *result.value.ok().unwrap());
+    assert_eq!(Some("de".to_string()), result.content_language);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_clock_skew() {
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::bind_ureq;
+    use http_api_service::common::{system_clock, Clock, MetricsSink, ResilienceEvent};
+    use http_api_service::simple_api::{
+        ClockSkew, SkewCorrectedClock, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4072).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // "/past" is in 2015, the others have the current `Date` (added by hyper)
+    const PAST_DATE: &str = "Wed, 21 Oct 2015 07:28:00 GMT";
+    const PAST_SECS: u64 = 1_445_412_480;
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let mut response = Response::builder();
+            if req.uri().path() == "/past" {
+                response = response.header("Date", PAST_DATE);
+            }
+            Ok::<Response<Body>, hyper::Error>(response.body(Body::from("\"ok\"")).unwrap())
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<ResilienceEvent>>);
+    impl MetricsSink for RecordingSink {
+        fn record_event(&self, event: &ResilienceEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let expected_past_skew = || {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        PAST_SECS as i64 * 1000 - now.as_millis() as i64
+    };
+
+    // By hyper
+    let sink = Arc::new(RecordingSink::default());
+    let skew = ClockSkew::new(Duration::from_secs(60)).with_metrics_sink(sink.clone());
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(base_url.clone());
+    base_service_setter.set_clock_skew(Some(skew.clone()));
+    let base_service_shared = common_api.as_base_service_shared();
+    let api_of = |relative_url: &str| {
+        base_service_setter.make_api_no_body(
+            base_service_shared.clone(),
+            Method::GET,
+            relative_url,
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &String::new(),
+        )
+    };
+    assert_eq!(None, skew.skew_millis());
+
+    // The current `Date` (in seconds) isn't skewed
+    assert_eq!("ok", *api_of("/now").call(None).await.ok().unwrap());
+    assert!(skew.skew_millis().unwrap().abs() < 2000);
+    assert!(!skew.is_skewed());
+    assert!(sink.0.lock().unwrap().is_empty());
+
+    // The skewed `Date` is reported
+    assert_eq!("ok", *api_of("/past").call(None).await.ok().unwrap());
+    assert!((skew.skew_millis().unwrap() - expected_past_skew()).abs() < 2000);
+    assert!(skew.is_skewed());
+    assert!(matches!(
+        sink.0.lock().unwrap().as_slice(),
+        [ResilienceEvent::ClockSkew { url, skew_millis }]
+            if url.ends_with("/past") && *skew_millis < 0
+    ));
+    // The corrected clock is in 2015
+    let corrected = SkewCorrectedClock::new(system_clock(), skew.clone()).system_now();
+    let corrected_secs = corrected.duration_since(UNIX_EPOCH).unwrap().as_secs();
+    assert!(corrected_secs.abs_diff(PAST_SECS) < 2);
+
+    // By ureq
+    let skew_ureq = ClockSkew::default();
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    let base_service_setter_ureq = common_api_ureq.as_base_service_setter();
+    base_service_setter_ureq.set_base_url(base_url);
+    base_service_setter_ureq.set_clock_skew(Some(skew_ureq.clone()));
+    let api_ureq = base_service_setter_ureq.make_api_no_body(
+        common_api_ureq.as_base_service_shared(),
+        Method::GET,
+        "/past",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+    assert_eq!("ok", *api_ureq.call(None).await.ok().unwrap());
+    assert!((skew_ureq.skew_millis().unwrap() - expected_past_skew()).abs() < 2000);
+    assert!(skew_ureq.is_skewed());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_replay_nonce() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, HeaderMap, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::bind_ureq;
+    use http_api_service::common::{Clock, HasRequestOptions, MockClock};
+    use http_api_service::simple_api::{
+        PathParam, QueryParam, ReplayNonce, ReplayNonceHeaders, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4073).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the anti-replay headers & the signature
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let header = |name: &str| {
+                req.headers()
+                    .get(name)
+                    .map(|v| v.to_str().unwrap().to_string())
+                    .unwrap_or_default()
+            };
+            let body = format!(
+                "\"{}|{}|{}|{}|{}\"",
+                header("X-Nonce"),
+                header("X-Timestamp"),
+                header("X-Signature"),
+                header("Request-Nonce"),
+                header("Request-Time")
+            );
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(body)))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let clock = MockClock::new();
+    clock.advance(Duration::from_secs(30));
+    let since_epoch = clock.system_now().duration_since(UNIX_EPOCH).unwrap();
+
+    // By hyper, signed (a fake HMAC) after the nonce
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let mut base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(base_url.clone());
+    base_service_setter
+        .add_interceptor_replay_nonce(ReplayNonceHeaders::default(), Arc::new(clock.clone()));
+    base_service_setter.add_interceptor_fn(|request: &mut Request<Body>| {
+        let options = request.get_request_options();
+        let nonce = options.extensions.get::<ReplayNonce>().unwrap();
+        let signature = format!("{}:{}:{}", request.method(), nonce.nonce, nonce.timestamp);
+        request
+            .headers_mut()
+            .insert("X-Signature", signature.parse().unwrap());
+        Ok(())
+    });
+    let api = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/orders",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+
+    let parts_of = |result: &str| result.split('|').map(str::to_string).collect::<Vec<_>>();
+    let first = parts_of(&api.call(None).await.ok().unwrap());
+    assert!(!first[0].is_empty());
+    assert_eq!(since_epoch.as_secs().to_string(), first[1]);
+    assert_eq!(format!("GET:{}:{}", first[0], first[1]), first[2]);
+    // A new nonce of each request
+    let second = parts_of(&api.call(None).await.ok().unwrap());
+    assert_ne!(first[0], second[0]);
+    assert_eq!(format!("GET:{}:{}", second[0], second[1]), second[2]);
+    // The nonce of the call is kept (and signed)
+    let mut header = HeaderMap::new();
+    header.insert("X-Nonce", "nonce-of-call".parse().unwrap());
+    let result = api
+        .call_with_options(Some(header), None::<PathParam>, None::<QueryParam>)
+        .await
+        .ok()
+        .unwrap();
+    let parts = parts_of(&result);
+    assert_eq!("nonce-of-call", parts[0]);
+    assert_eq!(format!("GET:nonce-of-call:{}", parts[1]), parts[2]);
+
+    // By ureq, by the other names & in milliseconds
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    let mut base_service_setter_ureq = common_api_ureq.as_base_service_setter();
+    base_service_setter_ureq.set_base_url(base_url);
+    base_service_setter_ureq.add_interceptor_replay_nonce(
+        ReplayNonceHeaders::new("Request-Nonce", "Request-Time").with_timestamp_millis(true),
+        Arc::new(clock),
+    );
+    let api_ureq = base_service_setter_ureq.make_api_no_body(
+        common_api_ureq.as_base_service_shared(),
+        Method::GET,
+        "/orders",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+    let parts = parts_of(&api_ureq.call(None).await.ok().unwrap());
+    assert_eq!(vec!["", "", ""], parts[..3].to_vec());
+    assert!(!parts[3].is_empty());
+    assert_eq!(since_epoch.as_millis().to_string(), parts[4]);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_connect_to() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{self, ConnectToConnector};
+    use http_api_service::bind_ureq;
+    use http_api_service::common::{ConnectTo, Extensions};
+    use http_api_service::simple_api::{with_extensions, DEFAULT_SERDE_JSON_DESERIALIZER};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4076).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the host
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let body = format!("\"{}\"", req.headers()["Host"].to_str().unwrap());
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(body)))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let connect_to = || {
+        let mut extensions = Extensions::new();
+        extensions.insert(ConnectTo(addr));
+        extensions
+    };
+    // The hosts behind the VIP (not resolvable)
+    let base_url = url::Url::parse("http://backend-1.invalid:8080")
+        .ok()
+        .unwrap();
+
+    // By hyper
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(base_url.clone());
+    let api = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+    let result = with_extensions(connect_to(), api.call(None)).await;
+    assert_eq!("backend-1.invalid:8080", *result.ok().unwrap());
+    // Resolved as usual without it
+    assert!(api.call(None).await.is_err());
+
+    // The `https` urls over the dedicated connections of `ConnectToConnector`
+    // (without TLS here, the SNI would be the host)
+    let common_api_https =
+        bind_hyper::CommonAPI::new_with_connector(ConnectToConnector::new_for_hyper());
+    let base_service_setter_https = common_api_https.as_base_service_setter();
+    base_service_setter_https
+        .set_base_url(url::Url::parse("https://backend-1.invalid").ok().unwrap());
+    let api_https = base_service_setter_https.make_api_no_body(
+        common_api_https.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+    let result = with_extensions(connect_to(), api_https.call(None)).await;
+    assert_eq!("backend-1.invalid", *result.ok().unwrap());
+
+    // By ureq
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    let base_service_setter_ureq = common_api_ureq.as_base_service_setter();
+    base_service_setter_ureq.set_base_url(base_url);
+    let api_ureq = base_service_setter_ureq.make_api_no_body(
+        common_api_ureq.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+    let result = with_extensions(connect_to(), api_ureq.call(None)).await;
+    assert_eq!("backend-1.invalid:8080", *result.ok().unwrap());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_tls_info() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::future::Future;
+    use std::io;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+
+    use http::method::Method;
+    use hyper::client::connect::{Connected, Connection};
+    use hyper::client::HttpConnector;
+    use hyper::service::{make_service_fn, service_fn, Service};
+    use hyper::{Body, Client, Request, Response, Server, Uri};
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio::net::TcpStream;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{self, TimingClient};
+    use http_api_service::common::RequestTiming;
+    use http_api_service::simple_api::{
+        with_timing, with_tls_info, TlsInfo, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+    use http_api_service::simple_http::SimpleHTTP;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4078).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from("\"ok\"")))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    // The fake TLS connector attaching the TlsInfo to the plain connections
+    fn tls_info() -> TlsInfo {
+        TlsInfo {
+            protocol: Some("TLSv1_3".to_string()),
+            cipher_suite: Some("TLS13_AES_128_GCM_SHA256".to_string()),
+            alpn_protocol: Some("http/1.1".to_string()),
+            peer_certificates: vec![vec![0x30, 0x82]],
+        }
+    }
+    struct FakeTlsStream(TcpStream);
+    impl Connection for FakeTlsStream {
+        fn connected(&self) -> Connected {
+            self.0.connected().extra(tls_info())
+        }
+    }
+    impl AsyncRead for FakeTlsStream {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+    impl AsyncWrite for FakeTlsStream {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.0).poll_write(cx, buf)
+        }
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_flush(cx)
+        }
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_shutdown(cx)
+        }
+    }
+    #[derive(Clone)]
+    struct FakeTlsConnector(HttpConnector);
+    impl Service<Uri> for FakeTlsConnector {
+        type Response = FakeTlsStream;
+        type Error = Box<dyn std::error::Error + Send + Sync>;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.0.poll_ready(cx).map_err(From::from)
+        }
+        fn call(&mut self, uri: Uri) -> Self::Future {
+            let connecting = self.0.call(uri);
+            Box::pin(async move { Ok(FakeTlsStream(connecting.await?)) })
+        }
+    }
+
+    // The typed calls
+    let common_api =
+        bind_hyper::CommonAPI::new_with_connector(FakeTlsConnector(HttpConnector::new()));
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let api = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+    let result = with_tls_info(api.call(None)).await;
+    assert_eq!("ok", *result.value.ok().unwrap());
+    assert_eq!(Some(tls_info()), result.tls_info);
+    // Also by the reused connection
+    let result = with_tls_info(api.call(None)).await;
+    assert_eq!(Some(tls_info()), result.tls_info);
+
+    // The plain connections have no TlsInfo
+    let common_api_plain = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter_plain = common_api_plain.as_base_service_setter();
+    base_service_setter_plain
+        .set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let api_plain = base_service_setter_plain.make_api_no_body(
+        common_api_plain.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+    let result = with_tls_info(api_plain.call(None)).await;
+    assert_eq!("ok", *result.value.ok().unwrap());
+    assert_eq!(None, result.tls_info);
+
+    // The timing info
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(TimingClient::new_with_options(
+            Client::builder().build(FakeTlsConnector(HttpConnector::new())),
+            None,
+        ))),
+        std::collections::VecDeque::new(),
+        http_api_service::simple_http::DEFAULT_TIMEOUT,
+    );
+    let resp = simple_http
+        .request(
+            Request::get(format!("http://{}/status", addr))
+                .body(Body::empty())
+                .ok()
+                .unwrap(),
+        )
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    let timing = resp.extensions().get::<RequestTiming>().unwrap();
+    assert_eq!(Some(tls_info()), timing.tls_info);
+
+    // The timing of the typed calls
+    let result = with_timing(api_plain.call(None)).await;
+    assert_eq!(None, result.timing);
+    common_api_plain
+        .as_base_service_shared()
+        .set_client(Arc::new(Mutex::new(TimingClient::new_with_options(
+            Client::new(),
+            None,
+        ))));
+    let result = with_timing(api_plain.call(None)).await;
+    assert_eq!("ok", *result.value.ok().unwrap());
+    let timing = result.timing.unwrap();
+    assert!(timing.ttfb.is_some());
+    assert_eq!(None, timing.total);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_sni_hostname() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::future::Future;
+    use std::net::SocketAddr;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex};
+    use std::task::{Context, Poll};
+
+    use http::method::Method;
+    use hyper::client::HttpConnector;
+    use hyper::service::{make_service_fn, service_fn, Service};
+    use hyper::{Body, Request, Response, Server, Uri};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{self, ConnectToConnector};
+    use http_api_service::common::{ConnectTo, Extensions, SniHostname};
+    use http_api_service::simple_api::{with_extensions, DEFAULT_SERDE_JSON_DESERIALIZER};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4079).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the host
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let body = format!("\"{}\"", req.headers()["Host"].to_str().unwrap());
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(body)))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    // The fake TLS connector recording the SNI (the hosts of the uris), over plain connections
+    #[derive(Clone)]
+    struct FakeTlsConnector(ConnectToConnector, Arc<Mutex<Vec<String>>>);
+    impl Service<Uri> for FakeTlsConnector {
+        type Response = <HttpConnector as Service<Uri>>::Response;
+        type Error = <HttpConnector as Service<Uri>>::Error;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            self.0.poll_ready(cx)
+        }
+        fn call(&mut self, uri: Uri) -> Self::Future {
+            self.1
+                .lock()
+                .unwrap()
+                .push(uri.host().unwrap_or_default().to_string());
+            self.0.call(uri)
+        }
+    }
+    let sni = Arc::new(Mutex::new(Vec::new()));
+
+    let common_api = bind_hyper::CommonAPI::new_with_connector(FakeTlsConnector(
+        ConnectToConnector::new_for_hyper(),
+        sni.clone(),
+    ));
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse("https://localhost:4079").ok().unwrap());
+    let api = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+
+    // The SNI of the fronted domain, connecting to the host of the url
+    let mut extensions = Extensions::new();
+    extensions.insert(SniHostname("fronted.example".to_string()));
+    let result = with_extensions(extensions, api.call(None)).await;
+    assert_eq!("localhost:4079", *result.ok().unwrap());
+    assert_eq!(vec!["fronted.example"], *sni.lock().unwrap());
+
+    // With the connect_to address
+    sni.lock().unwrap().clear();
+    base_service_setter.set_base_url(url::Url::parse("https://origin.invalid:4079").ok().unwrap());
+    let mut extensions = Extensions::new();
+    extensions.insert(SniHostname("fronted.example".to_string()));
+    extensions.insert(ConnectTo(addr));
+    let result = with_extensions(extensions, api.call(None)).await;
+    assert_eq!("origin.invalid:4079", *result.ok().unwrap());
+    assert_eq!(vec!["fronted.example"], *sni.lock().unwrap());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_make_api_form() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+
+    use formdata::FormData;
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{
+        FormDataExt, FormFile, MakeApi, PathParam, DEFAULT_DUMMY_BYPASS_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4080).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the content type & the body
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let content_type = req.headers()["Content-Type"].to_str().unwrap().to_string();
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                "{}\n{}",
+                content_type,
+                String::from_utf8_lossy(&body)
+            ))))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let api = common_api.as_base_service_shared().api_form(
+        Method::POST,
+        "/form",
+        std::sync::Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &bytes::Bytes::new(),
+    );
+
+    // Without files, urlencoded
+    let form_data = FormData::new()
+        .with_field("name", "Baxter & Co")
+        .with_field("tag", "dog");
+    let body = api.call(None::<PathParam>, form_data).await.ok().unwrap();
+    assert_eq!(
+        "application/x-www-form-urlencoded\nname=Baxter+%26+Co&tag=dog",
+        String::from_utf8_lossy(&body)
+    );
+
+    // With files, multipart
+    let path = std::env::temp_dir().join("http_api_service_test_make_api_form.txt");
+    std::fs::write(&path, "file content").unwrap();
+    let form_data = FormData::new()
+        .with_field("name", "Baxter")
+        .with_file("photo", FormFile::new(&path).content_type("text/plain"));
+    let body = api.call(None::<PathParam>, form_data).await.ok().unwrap();
+    let body = String::from_utf8_lossy(&body).to_string();
+    std::fs::remove_file(&path).unwrap();
+    assert!(body.starts_with("multipart/form-data; boundary="));
+    assert!(body.contains("name=\"name\"\r\n\r\nBaxter\r\n"));
+    assert!(body.contains("file content"));
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_body_factory_retry() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use formdata::FormData;
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{
+        self, RetryClient, DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM,
+    };
+    use http_api_service::common::RetryBudget;
+    use http_api_service::simple_api::{
+        FormDataExt, PathParam, QueryParam, DEFAULT_DUMMY_BYPASS_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4081).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // The first upload is unavailable (after reading the body), the others are echoed with the content type
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits_for_thread = hits.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let hits_for_thread_2 = hits_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let hit = hits_for_thread_2.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let content_type = req.headers()["Content-Type"].to_str().unwrap().to_string();
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    let mut resp = Response::new(Body::from(format!(
+                        "{}\n{}",
+                        content_type,
+                        String::from_utf8_lossy(&body)
+                    )));
+                    if hit == 0 {
+                        *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                    }
+                    Ok::<Response<Body>, hyper::Error>(resp)
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_shared.set_client(Arc::new(Mutex::new(RetryClient::new_with_options(
+        hyper::Client::new(),
+        2,
+        Duration::from_millis(1),
+        Arc::new(RetryBudget::new(1.0, 10, Duration::from_secs(10))),
+        None,
+    ))));
+    let api = base_service_setter.make_api_multipart_for_stream(
+        base_service_shared.clone(),
+        Method::PUT,
+        "/upload",
+        Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &bytes::Bytes::new(),
+    );
+
+    // A fresh stream (& boundary) per attempt
+    let produced = Arc::new(AtomicUsize::new(0));
+    let produced_for_factory = produced.clone();
+    let factory = DEFAULT_MULTIPART_SERIALIZER_FOR_STREAM.body_factory(move || {
+        produced_for_factory.fetch_add(1, Ordering::SeqCst);
+        FormData::new().with_field("name", "Baxter")
+    });
+    let body = api
+        .call_with_body_factory(None, None::<PathParam>, None::<QueryParam>, factory)
+        .await
+        .ok()
+        .unwrap();
+    let body = String::from_utf8_lossy(&body).to_string();
+    assert_eq!(2, hits.load(Ordering::SeqCst));
+    assert_eq!(2, produced.load(Ordering::SeqCst));
+    let (content_type, body) = body.split_once('\n').unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/form-data; boundary=")
+        .unwrap()
+        .trim_matches('"');
+    // The content type of the retried body
+    assert!(body.starts_with(&format!("--{}", boundary)));
+    assert!(body.contains("name=\"name\"\r\n\r\nBaxter\r\n"));
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_hook_panicked() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::error::Error as StdError;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{
+        BodyDeserializer, HookPanicked, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    struct PanickingDeserializer;
+    impl BodyDeserializer<String> for PanickingDeserializer {
+        fn decode(&self, _bytes: &Bytes) -> Result<Box<String>, Box<dyn StdError>> {
+            panic!("the deserializer panicked")
+        }
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4082).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from("\"ok\"")))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let mut base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    // The interceptor panics once
+    let panicking = Arc::new(AtomicBool::new(true));
+    let panicking_for_interceptor = panicking.clone();
+    base_service_setter.add_interceptor_fn(move |_req| {
+        if panicking_for_interceptor.swap(false, Ordering::SeqCst) {
+            panic!("the interceptor panicked");
+        }
+        Ok(())
+    });
+    let api = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+
+    let err = api.call(None).await.err().unwrap();
+    let err = err.downcast_ref::<HookPanicked>().unwrap();
+    assert_eq!("interceptor", err.hook());
+    assert_eq!(
+        "HookPanicked: interceptor panicked: the interceptor panicked",
+        err.to_string()
+    );
+    // The following calls aren't affected (the shared states aren't poisoned)
+    assert!(!panicking.load(Ordering::SeqCst));
+    assert_eq!("ok", *api.call(None).await.ok().unwrap());
+
+    let api = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(PanickingDeserializer),
+        &String::new(),
+    );
+    let err = api.call(None).await.err().unwrap();
+    assert_eq!(
+        "response_deserializer",
+        err.downcast_ref::<HookPanicked>().unwrap().hook()
+    );
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_multipart_request_info() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use formdata::FormData;
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{
+        MultipartRequestInfo, PathParam, DEFAULT_DUMMY_BYPASS_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4083).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the content type & the length of the received body
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let content_type = req
+                .headers()
+                .get(http::header::CONTENT_TYPE)
+                .map(|v| v.to_str().unwrap().to_string())
+                .unwrap_or_default();
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                "{}\n{}",
+                content_type,
+                body.len()
+            ))))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let form_data = FormData {
+        fields: vec![("name".to_owned(), "Baxter".to_owned())],
+        files: vec![],
+    };
+
+    let infos = Arc::new(Mutex::new(Vec::<MultipartRequestInfo>::new()));
+    let infos_for_hook = infos.clone();
+    let api = base_service_setter
+        .make_api_multipart(
+            common_api.as_base_service_shared(),
+            Method::POST,
+            "/form",
+            Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+            &bytes::Bytes::new(),
+        )
+        .with_request_info_hook(move |info| infos_for_hook.lock().unwrap().push(info.clone()));
+    let resp = api
+        .call(Some(PathParam::new()), form_data.clone())
+        .await
+        .ok()
+        .unwrap();
+    let info = infos.lock().unwrap().pop().unwrap();
+    let boundary = info.boundary.clone().unwrap();
+    assert!(info.content_type.starts_with("multipart/form-data"));
+    assert!(info.content_type.contains(&boundary));
+    // The same as the server received
+    assert_eq!(
+        format!("{}\n{}", info.content_type, info.body_size.unwrap()),
+        String::from_utf8(resp.to_vec()).unwrap()
+    );
+
+    // The streaming bodies are of unknown sizes
+    let infos_for_hook = infos.clone();
+    let api = base_service_setter
+        .make_api_multipart_for_stream(
+            common_api.as_base_service_shared(),
+            Method::POST,
+            "/form",
+            Arc::new(DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+            &bytes::Bytes::new(),
+        )
+        .with_request_info_hook(move |info| infos_for_hook.lock().unwrap().push(info.clone()));
+    let resp = api
+        .call(Some(PathParam::new()), form_data)
+        .await
+        .ok()
+        .unwrap();
+    let info = infos.lock().unwrap().pop().unwrap();
+    assert!(info.boundary.is_some());
+    assert_eq!(None, info.body_size);
+    assert!(String::from_utf8(resp.to_vec())
+        .unwrap()
+        .starts_with(&format!("{}\n", info.content_type)));
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_response_size() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use futures::stream;
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{
+        with_response_size, ResponseSize, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4084).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            Ok::<Response<Body>, hyper::Error>(match req.uri().path() {
+                // Chunked (without Content-Length)
+                "/chunked" => Response::new(Body::wrap_stream(stream::iter(vec![
+                    Ok::<_, hyper::Error>("\"hel"),
+                    Ok("lo\""),
+                ]))),
+                _ => Response::new(Body::from("\"hello\"")),
+            })
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let api_of = |relative_url: &str| {
+        base_service_setter.make_api_no_body(
+            common_api.as_base_service_shared(),
+            Method::GET,
+            relative_url,
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &String::new(),
+        )
+    };
+
+    let api = api_of("/full");
+    let result = with_response_size(api.call(None)).await;
+    assert_eq!("hello", *result.value.ok().unwrap());
+    assert_eq!(
+        Some(ResponseSize {
+            content_length: Some(7),
+            content_encoding: None,
+            decoded_length: Some(7),
+            decompressed: Some(false),
+        }),
+        result.response_size
+    );
+
+    let api = api_of("/chunked");
+    let result = with_response_size(api.call(None)).await;
+    assert_eq!("hello", *result.value.ok().unwrap());
+    let response_size = result.response_size.unwrap();
+    assert_eq!(None, response_size.content_length);
+    assert_eq!(Some(7), response_size.decoded_length);
+
+    // Not recorded out of the scope
+    assert_eq!("hello", *api.call(None).await.ok().unwrap());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_usage_quota() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{
+        QuotaExceededError, QuotaUsage, UsageQuota, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4086).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from("\"ok\"")))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let quota = Arc::new(
+        UsageQuota::new(Duration::from_secs(60))
+            .max_calls(2)
+            .hard_stop(true),
+    );
+    base_service_setter.set_usage_quota(Some(quota.clone()));
+    let api = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+
+    assert_eq!("ok", *api.call(None).await.ok().unwrap());
+    assert_eq!("ok", *api.call(None).await.ok().unwrap());
+    // The response bodies by Content-Length
+    assert_eq!(QuotaUsage { calls: 2, bytes: 8 }, quota.usage());
+    let err = api.call(None).await.err().unwrap();
+    assert!(err.downcast_ref::<QuotaExceededError>().is_some());
+    assert_eq!(2, quota.usage().calls);
+
+    base_service_setter.set_usage_quota(None);
+    assert_eq!("ok", *api.call(None).await.ok().unwrap());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_maintenance_policy() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use futures::future::join;
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::{Clock, MockClock};
+    use http_api_service::simple_api::{
+        MaintenanceAction, MaintenancePolicy, MaintenanceWindow, MaintenanceWindowError,
+        DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4087).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from("\"ok\"")))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    // The windows are passed by advancing the clock
+    let clock = MockClock::new();
+    base_service_setter.set_clock(Arc::new(clock.clone()));
+    let api = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/status",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+
+    // Rejected in the window
+    let now = clock.system_now();
+    let end = now + Duration::from_secs(3600);
+    base_service_setter.set_maintenance_policy(Some(Arc::new(MaintenancePolicy::new(
+        vec![MaintenanceWindow::once(now, end)],
+        MaintenanceAction::Reject,
+    ))));
+    let err = api.call(None).await.err().unwrap();
+    assert_eq!(
+        end,
+        err.downcast_ref::<MaintenanceWindowError>().unwrap().until
+    );
+
+    // Sent after the window
+    let now = clock.system_now();
+    let end = now + Duration::from_secs(10);
+    base_service_setter.set_maintenance_policy(Some(Arc::new(MaintenancePolicy::new(
+        vec![MaintenanceWindow::once(now, end)],
+        MaintenanceAction::Defer,
+    ))));
+    let (res, _) = join(api.call(None), async {
+        tokio::task::yield_now().await;
+        clock.advance(Duration::from_secs(10));
+    })
+    .await;
+    assert_eq!("ok", *res.ok().unwrap());
+    assert!(clock.system_now() >= end);
+
+    base_service_setter.set_maintenance_policy(None);
+    assert_eq!("ok", *api.call(None).await.ok().unwrap());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_keep_alive() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::MockClock;
+    use http_api_service::simple_api::{KeepAliveMethod, KeepAliveOptions};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4089).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+    let probes = Arc::new(AtomicUsize::new(0));
+    let probes_for_server = probes.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let probes = probes_for_server.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let probes = probes.clone();
+                async move {
+                    if req.method() == Method::HEAD {
+                        probes.fetch_add(1, Ordering::SeqCst);
+                    }
+                    // Any status is alive
+                    let mut res = Response::new(Body::empty());
+                    *res.status_mut() = StatusCode::METHOD_NOT_ALLOWED;
+                    Ok::<Response<Body>, hyper::Error>(res)
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    assert_eq!(None, base_service_setter.get_keep_alive_stats());
+
+    // The intervals are passed by advancing the clock
+    let interval = Duration::from_secs(30);
+    let clock = MockClock::new();
+    base_service_setter.set_clock(Arc::new(clock.clone()));
+    base_service_setter
+        .start_keep_alive(KeepAliveOptions::new(interval).method(KeepAliveMethod::Head))
+        .unwrap();
+    for _ in 0..1000 {
+        if base_service_setter.get_keep_alive_stats().unwrap().sent >= 2 {
+            break;
+        }
+        clock.advance(interval);
+        tokio::time::sleep(Duration::from_millis(2)).await;
+    }
+    let stats = base_service_setter.get_keep_alive_stats().unwrap();
+    assert!(stats.sent >= 2);
+    assert_eq!(0, stats.failed);
+    assert_eq!(Some(405), stats.last_status);
+    assert!(probes.load(Ordering::SeqCst) >= 2);
+
+    // Stopped
+    base_service_setter.stop_keep_alive();
+    assert_eq!(None, base_service_setter.get_keep_alive_stats());
+    let sent = probes.load(Ordering::SeqCst);
+    clock.advance(interval * 10);
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert_eq!(sent, probes.load(Ordering::SeqCst));
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_deprecation() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_api::{
+        with_deprecation, DeprecationMonitor, DEFAULT_SERDE_JSON_DESERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4090).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let mut res = Response::builder();
+            if req.uri().path() == "/v1/items" {
+                res = res
+                    .header("Deprecation", "@1445412480")
+                    .header("Sunset", "Wed, 21 Oct 2015 07:28:00 GMT")
+                    .header("Warning", "299 - \"Use /v2/items\"");
+            }
+            Ok::<Response<Body>, hyper::Error>(res.body(Body::from("\"ok\"")).unwrap())
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let hooked = Arc::new(Mutex::new(Vec::new()));
+    let hooked_for_hook = hooked.clone();
+    let monitor = DeprecationMonitor::new().with_hook(move |notice| {
+        hooked_for_hook
+            .lock()
+            .unwrap()
+            .push(notice.warnings.clone())
+    });
+    base_service_setter.set_deprecation_monitor(Some(monitor.clone()));
+    let make_api = |path: &str| {
+        base_service_setter.make_api_no_body(
+            common_api.as_base_service_shared(),
+            Method::GET,
+            path,
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &String::new(),
+        )
+    };
+    let api_v1 = make_api("/v1/items");
+    let api_v2 = make_api("/v2/items");
+
+    // On the typed response
+    let result = with_deprecation(api_v1.call(None)).await;
+    assert_eq!("ok", *result.value.ok().unwrap());
+    let notice = result.deprecation.unwrap();
+    assert!(notice.deprecated);
+    assert!(notice.sunset.is_some());
+    assert_eq!(vec!["Use /v2/items".to_string()], notice.warnings);
+
+    let result = with_deprecation(api_v2.call(None)).await;
+    assert_eq!("ok", *result.value.ok().unwrap());
+    assert_eq!(None, result.deprecation);
+
+    // By the monitor
+    assert_eq!(
+        vec![vec!["Use /v2/items".to_string()]],
+        *hooked.lock().unwrap()
+    );
+    assert_eq!(1, monitor.notices().len());
+    assert!(monitor.notices()[0].url.ends_with("/v1/items"));
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_call_bulk() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::PathParam;
+    use http_api_service::simple_api::{
+        BulkWriteOptions, DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4091).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+    let failed_once = Arc::new(AtomicBool::new(false));
+    let failed_once_for_server = failed_once.clone();
+
+    // The batch with 4 fails once, the one with 7 always fails, and the one with 9 is rejected (not retried)
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let failed_once = failed_once_for_server.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let failed_once = failed_once.clone();
+                async move {
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    let items: Vec<u32> = serde_json::from_slice(&body).unwrap();
+                    if items.contains(&9) {
+                        let mut res = Response::new(Body::from("rejected"));
+                        *res.status_mut() = StatusCode::BAD_REQUEST;
+                        return Ok::<Response<Body>, hyper::Error>(res);
+                    }
+                    if items.contains(&7)
+                        || (items.contains(&4) && !failed_once.swap(true, Ordering::SeqCst))
+                    {
+                        // Not deserialized as the count
+                        let mut res = Response::new(Body::from("unavailable"));
+                        *res.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                        return Ok::<Response<Body>, hyper::Error>(res);
+                    }
+                    Ok(Response::new(Body::from(items.len().to_string())))
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let api_bulk = base_service_setter.make_api_has_body(
+        common_api.as_base_service_shared(),
+        Method::POST,
+        "/items:bulk",
+        "application/json",
+        Arc::new(DEFAULT_SERDE_JSON_SERIALIZER),
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &0_usize,
+    );
+
+    let report = api_bulk
+        .call_bulk(
+            None::<PathParam>,
+            0..10_u32,
+            BulkWriteOptions::new(3)
+                .concurrency(2)
+                .retry(2, Duration::from_millis(10)),
+        )
+        .await;
+    assert_eq!(10, report.item_count());
+    assert!(!report.is_ok());
+    assert_eq!(
+        vec![(0..3, 1), (3..6, 2), (6..9, 2), (9..10, 1)],
+        report
+            .batches
+            .iter()
+            .map(|batch| (batch.items.clone(), batch.attempts))
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(vec![6, 7, 8, 9], report.failed_items());
+    let written: Vec<(usize, usize)> = report
+        .item_results()
+        .filter_map(|(index, result)| result.ok().map(|count| (index, *count)))
+        .collect();
+    assert_eq!(
+        vec![(0, 3), (1, 3), (2, 3), (3, 3), (4, 3), (5, 3)],
+        written
+    );
+    let e = report.into_result().err().unwrap();
+    assert_eq!(10, e.total);
+    assert_eq!(vec![6, 7, 8, 9], e.indices());
+    assert!(e
+        .to_string()
+        .starts_with("AggregateError: 4 of 10 items failed: #6: "));
+
+    // Nothing to write
+    let report = api_bulk
+        .call_bulk(None::<PathParam>, Vec::new(), BulkWriteOptions::default())
+        .await;
+    assert!(report.is_ok());
+    assert_eq!(0, report.item_count());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_read_only() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::PathParam;
+    use http_api_service::path_param;
+    use http_api_service::simple_api::{
+        ReadOnlyGuard, ReadOnlyMode, DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4092).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+    let writes = Arc::new(AtomicUsize::new(0));
+    let writes_for_server = writes.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let writes = writes_for_server.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                if req.method() != Method::GET {
+                    writes.fetch_add(1, Ordering::SeqCst);
+                }
+                let res = Response::new(Body::from("\"ok\""));
+                async move { Ok::<Response<Body>, hyper::Error>(res) }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let make_api = |method: Method, path: &str| {
+        base_service_setter.make_api_has_body(
+            common_api.as_base_service_shared(),
+            method,
+            path,
+            "application/json",
+            Arc::new(DEFAULT_SERDE_JSON_SERIALIZER),
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &String::new(),
+        )
+    };
+    let api_get = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/products/{id}",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+    let api_put = make_api(Method::PUT, "/products/{id}");
+    let api_search = make_api(Method::POST, "/products/search");
+
+    base_service_setter.set_read_only(Some(ReadOnlyGuard::new().allow("POST", "/products/search")));
+    let id = || Some(path_param!["id" => "1"]);
+    assert_eq!("ok", *api_get.call(id()).await.ok().unwrap());
+    assert_eq!(
+        "ok",
+        *api_search
+            .call(None::<PathParam>, "shoes".to_string())
+            .await
+            .ok()
+            .unwrap()
+    );
+    let err = api_put.call(id(), "x".to_string()).await.err().unwrap();
+    assert_eq!(
+        &ReadOnlyMode::new("PUT", "/products/{id}"),
+        err.downcast_ref::<ReadOnlyMode>().unwrap()
+    );
+    // Only the allowed one is sent
+    assert_eq!(1, writes.load(Ordering::SeqCst));
+
+    base_service_setter.set_read_only(None);
+    assert_eq!(
+        "ok",
+        *api_put.call(id(), "x".to_string()).await.ok().unwrap()
+    );
+    assert_eq!(2, writes.load(Ordering::SeqCst));
+
+    hyper_latch.countdown();
+}
@@ -275,3 +275,1588 @@ async fn test_formdata() {
 
     println!("OK");
 }
+
+#[tokio::test]
+async fn test_mirror_failover() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+    use url::Url;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{MirrorFailoverClient, ServedBy};
+    use http_api_service::simple_http::SimpleHTTP;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3600).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let (parts, body_instance) = req.into_parts();
+            let bytes = body::to_bytes(body_instance).await?;
+            let body_str = String::from_utf8(bytes.to_vec()).expect("request was not valid utf-8");
+
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                "{} {}",
+                parts.uri, body_str
+            ))))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    // Nothing listens on the primary one.
+    let primary = Url::parse("http://127.0.0.1:1/api/").ok().unwrap();
+    let mirror = Url::parse(&format!("http://{}/mirror/", addr))
+        .ok()
+        .unwrap();
+
+    let mut simple_http = SimpleHTTP::new_for_hyper();
+    simple_http.set_client(Arc::new(Mutex::new(MirrorFailoverClient::new_for_hyper(
+        primary.clone(),
+        vec![mirror.clone()],
+    ))));
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(primary.join("pets?id=1").ok().unwrap().as_str())
+        .body(Body::from("hello"))
+        .ok()
+        .unwrap();
+    let mut resp = simple_http
+        .request(request)
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+
+    assert_eq!(
+        Some(&ServedBy(mirror.clone())),
+        resp.extensions().get::<ServedBy>()
+    );
+    let bytes = body::to_bytes(resp.body_mut()).await.ok().unwrap();
+    assert_eq!(
+        "/mirror/pets?id=1 hello",
+        String::from_utf8(bytes.to_vec()).unwrap()
+    );
+
+    // `/api` doesn't prefix `/apiv2`: only the host is replaced.
+    let primary_without_slash = Url::parse("http://127.0.0.1:1/api").ok().unwrap();
+    simple_http.set_client(Arc::new(Mutex::new(MirrorFailoverClient::new_for_hyper(
+        primary_without_slash.clone(),
+        vec![mirror.clone()],
+    ))));
+    for (path, expected) in [
+        ("/apiv2/pets", "/apiv2/pets "),
+        ("/api/pets", "/mirror/pets "),
+    ] {
+        let request = Request::builder()
+            .uri(primary_without_slash.join(path).ok().unwrap().as_str())
+            .body(Body::empty())
+            .ok()
+            .unwrap();
+        let mut resp = simple_http
+            .request(request)
+            .await
+            .ok()
+            .unwrap()
+            .ok()
+            .unwrap();
+        let bytes = body::to_bytes(resp.body_mut()).await.ok().unwrap();
+        assert_eq!(expected, String::from_utf8(bytes.to_vec()).unwrap());
+    }
+
+    // No mirror: the connection error is returned.
+    simple_http.set_client(Arc::new(Mutex::new(MirrorFailoverClient::new_for_hyper(
+        primary.clone(),
+        vec![],
+    ))));
+    let request = Request::builder()
+        .uri(primary.join("pets").ok().unwrap().as_str())
+        .body(Body::empty())
+        .ok()
+        .unwrap();
+    let resp = simple_http.request(request).await.ok().unwrap();
+    assert!(resp.err().unwrap().is_connect());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_request_timing() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Client, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{TimingClient, TimingConnector};
+    use http_api_service::common::{MetricsSink, RequestTiming};
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<(String, String, RequestTiming)>>);
+    impl MetricsSink for RecordingSink {
+        fn record_timing(&self, method: &str, url: &str, timing: &RequestTiming) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((method.to_string(), url.to_string(), timing.clone()));
+        }
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3610).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from("timed")))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let sink = Arc::new(RecordingSink::default());
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(TimingClient::new_for_hyper(Some(sink.clone())))),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+
+    // By the host name, for the DNS lookup
+    let url = format!("http://localhost:{}/timed", addr.port());
+    for i in 0..2 {
+        let request = Request::builder()
+            .uri(url.as_str())
+            .body(Body::empty())
+            .ok()
+            .unwrap();
+        let mut resp = simple_http
+            .request(request)
+            .await
+            .ok()
+            .unwrap()
+            .ok()
+            .unwrap();
+
+        let timing = resp.extensions().get::<RequestTiming>().unwrap().clone();
+        assert!(timing.ttfb.is_some());
+        assert_eq!(None, timing.total);
+        // Only the first call made the connection.
+        assert_eq!(i == 0, timing.connect.is_some());
+        assert_eq!(i == 0, timing.dns.is_some());
+        assert_eq!(None, timing.tls);
+
+        let bytes = body::to_bytes(resp.body_mut()).await.ok().unwrap();
+        assert_eq!("timed", String::from_utf8(bytes.to_vec()).unwrap());
+    }
+
+    let recorded = sink.0.lock().unwrap().clone();
+    assert_eq!(2, recorded.len());
+    assert_eq!("GET", recorded[0].0);
+    assert_eq!(url, recorded[0].1);
+    assert!(recorded[0].2.connect.is_some());
+    assert!(recorded[0].2.total.unwrap() >= recorded[0].2.ttfb.unwrap());
+    assert_eq!(None, recorded[1].2.connect);
+
+    // The nested `TimingConnector` (as the TCP one of a TLS connector) splits `connect`/`tls`
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(TimingClient::new_with_options(
+            Client::builder().build(TimingConnector(TimingConnector::new_for_hyper())),
+            None,
+        ))),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+    let request = Request::builder()
+        .uri(url.as_str())
+        .body(Body::empty())
+        .ok()
+        .unwrap();
+    let resp = simple_http
+        .request(request)
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    let timing = resp.extensions().get::<RequestTiming>().unwrap();
+    assert!(timing.dns.is_some());
+    assert!(timing.connect.is_some());
+    assert!(timing.tls.is_some());
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_retry_client() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::RetryClient;
+    use http_api_service::common::{
+        MetricsSink, MockClock, ResilienceEvent, RetryAttempt, RetryBudget, RetryBudgetUsage,
+    };
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    #[derive(Default)]
+    struct RecordingSink(
+        Mutex<Vec<(RetryBudgetUsage, bool)>>,
+        Mutex<Vec<ResilienceEvent>>,
+    );
+    impl MetricsSink for RecordingSink {
+        fn record_retry(&self, _method: &str, _url: &str, usage: &RetryBudgetUsage, allowed: bool) {
+            self.0.lock().unwrap().push((*usage, allowed));
+        }
+        fn record_event(&self, event: &ResilienceEvent) {
+            self.1.lock().unwrap().push(event.clone());
+        }
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3630).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // The upstream is always unavailable
+    let hits = Arc::new(AtomicUsize::new(0));
+    let hits_for_thread = hits.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let hits_for_thread_2 = hits_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| {
+                hits_for_thread_2.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    let mut resp = Response::new(Body::empty());
+                    *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                    Ok::<Response<Body>, hyper::Error>(resp)
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let sink = Arc::new(RecordingSink::default());
+    let retry_budget = Arc::new(RetryBudget::new(0.2, 0, Duration::from_secs(10)));
+    let mut client = RetryClient::new_with_options(
+        hyper::Client::new(),
+        3,
+        Duration::from_millis(100),
+        retry_budget.clone(),
+        Some(sink.clone()),
+    );
+    // The backoff doesn't really sleep
+    let clock = MockClock::new_auto_advance();
+    client.clock = Arc::new(clock.clone());
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(client)),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+
+    let url = format!("http://{}/unavailable", addr);
+    let mut attempts = 0;
+    for _ in 0..10 {
+        let request = Request::builder()
+            .uri(url.as_str())
+            .body(Body::empty())
+            .ok()
+            .unwrap();
+        let resp = simple_http
+            .request(request)
+            .await
+            .ok()
+            .unwrap()
+            .ok()
+            .unwrap();
+        assert_eq!(StatusCode::SERVICE_UNAVAILABLE, resp.status());
+        attempts += resp.extensions().get::<RetryAttempt>().unwrap().0;
+    }
+
+    // 10 calls could retry only 2 times (20%)
+    assert_eq!(12, hits.load(Ordering::SeqCst));
+    assert_eq!(2, attempts);
+    assert_eq!(Duration::from_millis(200), clock.elapsed());
+    assert_eq!(
+        RetryBudgetUsage {
+            requests: 10,
+            retries: 2,
+            limit: 2,
+        },
+        retry_budget.usage()
+    );
+    let recorded = sink.0.lock().unwrap().clone();
+    assert_eq!(2, recorded.iter().filter(|(_, allowed)| *allowed).count());
+    assert_eq!(10, recorded.iter().filter(|(_, allowed)| !*allowed).count());
+    let events = sink.1.lock().unwrap().clone();
+    assert_eq!(12, events.len());
+    assert_eq!(
+        ResilienceEvent::RetryAttempt {
+            method: "GET".to_string(),
+            url: url.clone(),
+            attempt: 1,
+            backoff: Duration::from_millis(100),
+            allowed: false,
+        },
+        events[0]
+    );
+    assert_eq!(
+        2,
+        events
+            .iter()
+            .filter(|event| matches!(event, ResilienceEvent::RetryAttempt { allowed: true, .. }))
+            .count()
+    );
+
+    // The non-idempotent methods are not retried for the responses
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(url.as_str())
+        .body(Body::from("posted"))
+        .ok()
+        .unwrap();
+    let resp = simple_http
+        .request(request)
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, resp.status());
+    assert_eq!(13, hits.load(Ordering::SeqCst));
+    assert_eq!(
+        Some(&RetryAttempt(0)),
+        resp.extensions().get::<RetryAttempt>()
+    );
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_adaptive_client() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::HyperClient;
+    use http_api_service::common::AdaptiveConcurrencyOptions;
+    use http_api_service::simple_http::{AdaptiveClient, SimpleHTTP, DEFAULT_TIMEOUT};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3640).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            if req.uri().path() == "/slow" {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            }
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::empty()))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let client = AdaptiveClient::new(
+        Box::new(HyperClient::new_for_hyper()),
+        AdaptiveConcurrencyOptions {
+            initial_limit: 8,
+            backoff_ratio: 0.5,
+            ..Default::default()
+        },
+    );
+    let limiter = client.limiter.clone();
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(client)),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+
+    for path in ["/fast", "/fast", "/fast", "/slow"].iter() {
+        let request = Request::builder()
+            .uri(format!("http://{}{}", addr, path))
+            .body(Body::empty())
+            .ok()
+            .unwrap();
+        assert!(simple_http.request(request).await.ok().unwrap().is_ok());
+    }
+
+    // The latency of the slow call exceeds the tolerance of the baseline
+    assert!(limiter.min_latency().unwrap() < Duration::from_millis(100));
+    assert_eq!(4, limiter.limit());
+    assert_eq!(0, limiter.limiter.running());
+
+    hyper_latch.countdown();
+}
+
+#[test]
+fn test_lazy_client() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use http_api_service::bind_hyper::HyperClient;
+    use http_api_service::simple_http::{BaseClient, LazyClient};
+
+    let built_count = Arc::new(AtomicUsize::new(0));
+    let built_count_for_factory = built_count.clone();
+    let mut lazy_client = LazyClient::new(move || {
+        built_count_for_factory.fetch_add(1, Ordering::SeqCst);
+        Box::new(HyperClient::new_for_hyper())
+    });
+
+    // Nothing is built before the first use
+    lazy_client.close_idle();
+    assert!(!lazy_client.is_initialized());
+    assert_eq!(0, built_count.load(Ordering::SeqCst));
+
+    lazy_client.warm();
+    lazy_client.get_client();
+    assert!(lazy_client.is_initialized());
+    assert_eq!(1, built_count.load(Ordering::SeqCst));
+
+    // Shareable across threads (as the client of `SimpleHTTP`)
+    fn assert_send<T: Send>(_: &T) {}
+    assert_send(&lazy_client);
+}
+
+#[cfg(feature = "test_runtime")]
+#[tokio::test]
+async fn test_priority_client() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use futures::future::join_all;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::HyperClient;
+    use http_api_service::common::{
+        HasRequestOptions, MetricsSink, Priority, RequestOptions, ResilienceEvent,
+    };
+    use http_api_service::simple_http::{PriorityClient, SimpleHTTP, DEFAULT_TIMEOUT};
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<ResilienceEvent>>);
+    impl MetricsSink for RecordingSink {
+        fn record_event(&self, event: &ResilienceEvent) {
+            self.0.lock().unwrap().push(event.clone());
+        }
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3620).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::empty()))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let sink = Arc::new(RecordingSink::default());
+    let mut client = PriorityClient::new(Box::new(HyperClient::new_for_hyper()), 1);
+    client.metrics_sink = Some(sink.clone());
+    let limiter = client.limiter.clone();
+    let mut simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(client)),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+    // Set the priority per request
+    simple_http.add_interceptor_fn(|req| {
+        req.set_request_options(RequestOptions {
+            priority: if req.uri().path().starts_with("/interactive") {
+                Priority::High
+            } else {
+                Priority::Low
+            },
+            ..Default::default()
+        });
+        Ok(())
+    });
+
+    let completed = Arc::new(Mutex::new(Vec::<String>::new()));
+    let calls = ["/bulk/1", "/bulk/2", "/bulk/3", "/interactive"]
+        .iter()
+        .map(|path| {
+            let request = Request::builder()
+                .uri(format!("http://{}{}", addr, path))
+                .body(Body::empty())
+                .ok()
+                .unwrap();
+            let simple_http = &simple_http;
+            let completed = completed.clone();
+            async move {
+                let resp = simple_http.request(request).await;
+                assert!(resp.ok().unwrap().is_ok());
+                completed.lock().unwrap().push(path.to_string());
+            }
+        })
+        .collect::<Vec<_>>();
+    join_all(calls).await;
+
+    // The interactive call overtakes the waiting bulk calls
+    assert_eq!(
+        vec!["/bulk/1", "/interactive", "/bulk/2", "/bulk/3"],
+        *completed.lock().unwrap()
+    );
+    assert_eq!(0, limiter.running());
+
+    // The waits of the queued calls are reported
+    let waits = sink.0.lock().unwrap().clone();
+    assert_eq!(3, waits.len());
+    for event in waits {
+        match event {
+            ResilienceEvent::LimiterWait {
+                limiter, waited, ..
+            } => {
+                assert_eq!("priority", limiter);
+                assert!(waited >= Duration::from_millis(50));
+            }
+            event => panic!("unexpected event {}", event),
+        }
+    }
+
+    hyper_latch.countdown();
+}
+
+#[test]
+fn test_multipart_boundary() {
+    use http_api_service::simple_http::{
+        data_and_boundary_from_multipart, get_content_type_from_multipart_boundary,
+        parse_multipart_boundary, validate_multipart_boundary,
+    };
+
+    assert_eq!(
+        "ABC-'()+_,./:=? DEF",
+        parse_multipart_boundary("multipart/form-data; boundary=\"ABC-'()+_,./:=? DEF\"")
+            .ok()
+            .unwrap()
+    );
+    assert_eq!(
+        "------ABCDEFG",
+        parse_multipart_boundary("Multipart/Form-Data; charset=utf-8; boundary=------ABCDEFG")
+            .ok()
+            .unwrap()
+    );
+
+    // Malformed inputs are rejected (without panics)
+    for content_type in [
+        "",
+        "multipart",
+        "multipart/form-data",
+        "multipart/form-data; boundary=",
+        "multipart/form-data; boundary=\"\"",
+        "multipart/form-data; boundary=\"ABC \"",
+        "multipart/form-data; boundary=\"AB\\\"C\"",
+        "multipart/form-data; boundary=\"ABC",
+        "multipart/form-data; boundary=AB;C",
+        "multipart/mixed; boundary=ABC",
+        "application/json; boundary=ABC",
+        "multipart/form-data; boundary=\u{e9}\u{e9}",
+        "multipart/form-data; boundary=\u{0}",
+        "multipart/form-data;;;;;;;;;;;;;;;;;;;",
+    ]
+    .iter()
+    {
+        assert!(
+            parse_multipart_boundary(content_type).is_err(),
+            "{:?}",
+            content_type
+        );
+    }
+    let oversized = format!("multipart/form-data; boundary={}", "A".repeat(71));
+    assert!(parse_multipart_boundary(&oversized).is_err());
+    assert!(validate_multipart_boundary(&[b'A'; 70]).is_ok());
+    assert!(validate_multipart_boundary(&[0xff, 0xfe]).is_err());
+    assert!(get_content_type_from_multipart_boundary(b"A\"B".to_vec()).is_err());
+
+    // The generated boundaries survive the round trip
+    let (_, boundary) = data_and_boundary_from_multipart(&formdata::FormData {
+        fields: vec![("name".to_owned(), "Baxter".to_owned())],
+        files: vec![],
+    })
+    .ok()
+    .unwrap();
+    let content_type = get_content_type_from_multipart_boundary(boundary.clone())
+        .ok()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(boundary).unwrap(),
+        parse_multipart_boundary(&content_type).ok().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_credential_rotation_client() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{CredentialRotationClient, UsedCredential};
+    use http_api_service::common::{CredentialRotation, RotationStrategy};
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3970).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // The key "a" is rate limited, and the received (key, body) are recorded
+    let received = Arc::new(Mutex::new(Vec::<(String, String)>::new()));
+    let received_for_thread = received.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let received_for_thread_2 = received_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let received_for_thread_3 = received_for_thread_2.clone();
+                async move {
+                    let key = req
+                        .headers()
+                        .get("X-Api-Key")
+                        .unwrap()
+                        .to_str()
+                        .unwrap()
+                        .to_string();
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    received_for_thread_3
+                        .lock()
+                        .unwrap()
+                        .push((key.clone(), String::from_utf8(body.to_vec()).unwrap()));
+
+                    let mut resp = Response::new(Body::empty());
+                    if key == "a" {
+                        *resp.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+                        resp.headers_mut()
+                            .insert("Retry-After", "60".parse().unwrap());
+                    }
+                    Ok::<Response<Body>, hyper::Error>(resp)
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let rotation = Arc::new(
+        CredentialRotation::new("X-Api-Key", vec!["a", "b"], RotationStrategy::OnRejection)
+            .ok()
+            .unwrap(),
+    );
+    let client =
+        CredentialRotationClient::new_with_options(hyper::Client::new(), rotation.clone(), true);
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(client)),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+
+    let url = format!("http://{}/items", addr);
+    for _ in 0..2 {
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri(url.as_str())
+            .body(Body::from("item"))
+            .ok()
+            .unwrap();
+        let resp = simple_http
+            .request(request)
+            .await
+            .ok()
+            .unwrap()
+            .ok()
+            .unwrap();
+        assert_eq!(StatusCode::OK, resp.status());
+        assert_eq!(
+            Some(&UsedCredential(1)),
+            resp.extensions().get::<UsedCredential>()
+        );
+    }
+
+    // The rejected request is sent again by the other key, and "a" is skipped since then
+    assert_eq!(
+        vec![
+            ("a".to_string(), "item".to_string()),
+            ("b".to_string(), "item".to_string()),
+            ("b".to_string(), "item".to_string()),
+        ],
+        *received.lock().unwrap()
+    );
+    let states = rotation.states();
+    assert_eq!(1, states[0].rejections);
+    assert!(states[0].limited_until.is_some());
+    assert_eq!(2, states[1].uses);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_redirect_client() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use hyper::header::{AUTHORIZATION, COOKIE, LOCATION};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::RedirectClient;
+    use http_api_service::common::{
+        BodyFactory, DefaultRedirectPolicy, HasRequestOptions, Headers, Redirect, RedirectAction,
+        RedirectPolicy,
+    };
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    // The same handler on two ports (two origins):
+    // "/same" & "/cross" redirect to "/echo" of the same/other origin, "/loop" redirects to itself
+    let addr: SocketAddr = ([127, 0, 0, 1], 4050).into();
+    let other_addr: SocketAddr = ([127, 0, 0, 1], 4051).into();
+    for bind_addr in [addr, other_addr] {
+        let hyper_latch_for_thread = hyper_latch.clone();
+        let server = Server::bind(&bind_addr).serve(make_service_fn(move |_| async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+                let (status, location) = match req.uri().path() {
+                    "/same" => (StatusCode::TEMPORARY_REDIRECT, "/echo".to_string()),
+                    "/cross" => (StatusCode::FOUND, format!("http://{}/echo", other_addr)),
+                    "/loop" => (StatusCode::FOUND, "/loop".to_string()),
+                    _ => (StatusCode::OK, String::new()),
+                };
+                let header = |name| {
+                    req.headers()
+                        .get(name)
+                        .map_or("-", |v: &hyper::header::HeaderValue| v.to_str().unwrap())
+                        .to_string()
+                };
+                let echo = format!(
+                    "{} {} {}",
+                    req.method(),
+                    header(AUTHORIZATION),
+                    header(COOKIE)
+                );
+                let body = hyper::body::to_bytes(req.into_body()).await?;
+
+                let mut resp = Response::new(Body::from(format!(
+                    "{} {}",
+                    echo,
+                    String::from_utf8(body.to_vec()).unwrap()
+                )));
+                *resp.status_mut() = status;
+                if !location.is_empty() {
+                    resp.headers_mut()
+                        .insert(LOCATION, location.parse().unwrap());
+                }
+                Ok::<Response<Body>, hyper::Error>(resp)
+            }))
+        }));
+        tokio::spawn(async {
+            let _ = server
+                .with_graceful_shutdown(async move {
+                    hyper_latch_for_thread.await;
+                })
+                .await;
+        });
+    }
+
+    let simple_http_of = |policy: Arc<dyn RedirectPolicy>| {
+        SimpleHTTP::new_with_timeout(
+            Arc::new(Mutex::new(RedirectClient::new_with_options(
+                hyper::Client::new(),
+                policy,
+            ))),
+            VecDeque::new(),
+            DEFAULT_TIMEOUT,
+        )
+    };
+    let request = |path: &str| {
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://{}{}", addr, path))
+            .header(AUTHORIZATION, "Bearer token")
+            .header(COOKIE, "session=1")
+            .body(Body::from("item"))
+            .ok()
+            .unwrap()
+    };
+    let simple_http = simple_http_of(Arc::new(DefaultRedirectPolicy { max_redirects: 3 }));
+
+    // The same origin keeps the credentials, and 307 keeps the method & the body
+    let resp = simple_http
+        .request(request("/same"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("POST Bearer token session=1 item", body);
+
+    // The cross-origin one strips the credentials, and 302 changes POST to GET without the body
+    let resp = simple_http
+        .request(request("/cross"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("GET - - ", body);
+
+    // The redirects over max_redirects are returned as they are
+    let resp = simple_http
+        .request(request("/loop"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::FOUND, resp.status());
+
+    // The custom policy stops the cross-origin redirects
+    struct SameOriginOnly {}
+    impl RedirectPolicy for SameOriginOnly {
+        fn redirect(&self, redirect: &Redirect, _headers: &mut Headers) -> RedirectAction {
+            if redirect.is_same_origin() {
+                RedirectAction::Follow
+            } else {
+                RedirectAction::Stop
+            }
+        }
+    }
+    let simple_http = simple_http_of(Arc::new(SameOriginOnly {}));
+    let resp = simple_http
+        .request(request("/cross"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::FOUND, resp.status());
+    let resp = simple_http
+        .request(request("/same"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+
+    // The policies following every redirect are stopped by MAX_REDIRECTS
+    struct FollowAll {}
+    impl RedirectPolicy for FollowAll {
+        fn redirect(&self, _redirect: &Redirect, _headers: &mut Headers) -> RedirectAction {
+            RedirectAction::Follow
+        }
+    }
+    let simple_http = simple_http_of(Arc::new(FollowAll {}));
+    let resp = simple_http
+        .request(request("/loop"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::FOUND, resp.status());
+
+    // The streaming bodies are forwarded as they are (not buffered)
+    let streaming_request = |path: &str| {
+        let chunks: Vec<Result<&str, std::io::Error>> = vec![Ok("it"), Ok("em")];
+        let mut req = request(path);
+        *req.body_mut() = Body::wrap_stream(futures::stream::iter(chunks));
+        req
+    };
+    let simple_http = simple_http_of(Arc::new(DefaultRedirectPolicy::default()));
+    let resp = simple_http
+        .request(streaming_request("/echo"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("POST Bearer token session=1 item", body);
+
+    // The 307 can't send the streaming body again, unless there's a BodyFactory
+    let resp = simple_http
+        .request(streaming_request("/same"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::TEMPORARY_REDIRECT, resp.status());
+
+    let mut req = streaming_request("/same");
+    let mut options = req.get_request_options();
+    options.extensions.insert(BodyFactory::new(|| async {
+        Ok::<_, Box<dyn std::error::Error>>(("text/plain".to_string(), Body::from("item")))
+    }));
+    req.set_request_options(options);
+    let resp = simple_http.request(req).await.ok().unwrap().ok().unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("POST Bearer token session=1 item", body);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_transport_error_kind() {
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use hyper::{Body, Method, Request};
+    use tokio::net::TcpListener;
+    use url::Url;
+
+    use http_api_service::bind_hyper::RetryClient;
+    use http_api_service::bind_ureq::UreqRequest;
+    use http_api_service::common::{MetricsSink, MockClock, RetryBudget, TransportErrorKind};
+    use http_api_service::simple_api::SerializeError;
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    // The io::Error kinds, and the other errors aren't classified even by their messages
+    let broken_pipe = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "write");
+    assert_eq!(
+        Some(TransportErrorKind::BrokenPipe),
+        TransportErrorKind::of(&broken_pipe)
+    );
+    let dns = std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "failed to lookup address information",
+    );
+    assert_eq!(Some(TransportErrorKind::Dns), TransportErrorKind::of(&dns));
+    let other = std::io::Error::new(std::io::ErrorKind::Other, "unknown");
+    assert_eq!(
+        Some(TransportErrorKind::Other),
+        TransportErrorKind::of(&other)
+    );
+    let not_transport = SerializeError::new("invalid certificate field");
+    assert_eq!(None, TransportErrorKind::of(&not_transport));
+    assert_eq!(
+        "connection_refused",
+        TransportErrorKind::ConnectionRefused.to_string()
+    );
+
+    // Nothing listens on the port
+    let refused_url = "http://127.0.0.1:4057/";
+    let simple_http = SimpleHTTP::new_for_hyper();
+    let request = Request::builder()
+        .uri(refused_url)
+        .body(Body::empty())
+        .ok()
+        .unwrap();
+    // The errors of hyper are in the results
+    let err = simple_http
+        .request(request)
+        .await
+        .ok()
+        .unwrap()
+        .err()
+        .unwrap();
+    assert_eq!(
+        Some(TransportErrorKind::ConnectionRefused),
+        TransportErrorKind::of(&err)
+    );
+    let simple_http_ureq = SimpleHTTP::new_for_ureq();
+    let request = UreqRequest::new(Method::GET, Url::parse(refused_url).ok().unwrap());
+    let err = simple_http_ureq.request(request).await.err().unwrap();
+    assert_eq!(
+        Some(TransportErrorKind::ConnectionRefused),
+        TransportErrorKind::of(err.as_ref())
+    );
+
+    // The host isn't resolved
+    let request = Request::builder()
+        .uri("http://nonexistent.invalid/")
+        .body(Body::empty())
+        .ok()
+        .unwrap();
+    let err = simple_http
+        .request(request)
+        .await
+        .ok()
+        .unwrap()
+        .err()
+        .unwrap();
+    assert_eq!(Some(TransportErrorKind::Dns), TransportErrorKind::of(&err));
+    let request = UreqRequest::new(
+        Method::GET,
+        Url::parse("http://nonexistent.invalid/").ok().unwrap(),
+    );
+    let err = simple_http_ureq.request(request).await.err().unwrap();
+    assert_eq!(
+        Some(TransportErrorKind::Dns),
+        TransportErrorKind::of(err.as_ref())
+    );
+
+    // The connections closed without the responses
+    let addr: SocketAddr = ([127, 0, 0, 1], 4056).into();
+    let listener = TcpListener::bind(addr).await.ok().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            drop(stream);
+        }
+    });
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<TransportErrorKind>>);
+    impl MetricsSink for RecordingSink {
+        fn record_transport_error(&self, _method: &str, _url: &str, kind: TransportErrorKind) {
+            self.0.lock().unwrap().push(kind);
+        }
+    }
+    let sink = Arc::new(RecordingSink::default());
+    let mut client = RetryClient::new_with_options(
+        hyper::Client::new(),
+        2,
+        Duration::from_millis(100),
+        Arc::new(RetryBudget::new(1.0, 10, Duration::from_secs(10))),
+        Some(sink.clone()),
+    );
+    client.clock = Arc::new(MockClock::new_auto_advance());
+    // Only the refused connections are retried
+    client.retryable_errors = vec![TransportErrorKind::ConnectionRefused];
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(client)),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+
+    let request = Request::builder()
+        .uri(format!("http://{}/", addr))
+        .body(Body::empty())
+        .ok()
+        .unwrap();
+    let err = simple_http
+        .request(request)
+        .await
+        .ok()
+        .unwrap()
+        .err()
+        .unwrap();
+    assert_eq!(
+        Some(TransportErrorKind::ConnectionReset),
+        TransportErrorKind::of(&err)
+    );
+    let request = Request::builder()
+        .uri(refused_url)
+        .body(Body::empty())
+        .ok()
+        .unwrap();
+    assert!(simple_http.request(request).await.ok().unwrap().is_err());
+    assert_eq!(
+        vec![
+            TransportErrorKind::ConnectionReset,
+            TransportErrorKind::ConnectionRefused,
+            TransportErrorKind::ConnectionRefused,
+            TransportErrorKind::ConnectionRefused,
+        ],
+        *sink.0.lock().unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_expect_continue_client() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use hyper::header::EXPECT;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper::{ExpectContinueClient, ExpectContinueOutcome};
+    use http_api_service::simple_http::BaseClient;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    // "/upload" reads the body (hyper sends `100 Continue` then), and "/reject" rejects it without reading it
+    let addr: SocketAddr = ([127, 0, 0, 1], 4066).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async move {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let expect = req
+                .headers()
+                .get(EXPECT)
+                .map_or("-", |v| v.to_str().unwrap())
+                .to_string();
+            if req.uri().path() == "/reject" {
+                let mut resp = Response::new(Body::from(format!("{} too large", expect)));
+                *resp.status_mut() = StatusCode::PAYLOAD_TOO_LARGE;
+                return Ok::<Response<Body>, hyper::Error>(resp);
+            }
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                "{} {}",
+                expect,
+                body.len()
+            ))))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    // The server never sending `100 Continue`
+    let silent_addr: SocketAddr = ([127, 0, 0, 1], 4067).into();
+    let listener = TcpListener::bind(silent_addr).await.ok().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut received = Vec::new();
+            let mut buf = [0; 8192];
+            let head_len = loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+                if let Some(i) = received.windows(4).position(|w| w == b"\r\n\r\n") {
+                    break i + 4;
+                }
+            };
+            let head = String::from_utf8_lossy(&received[..head_len]).to_lowercase();
+            let content_length = head
+                .lines()
+                .find_map(|line| line.strip_prefix("content-length: "))
+                .unwrap()
+                .trim()
+                .parse::<usize>()
+                .unwrap();
+            while received.len() < head_len + content_length {
+                let n = stream.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+            }
+            let body = format!(
+                "{} {}",
+                head.contains("expect: 100-continue"),
+                content_length
+            );
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(resp.as_bytes()).await.unwrap();
+        }
+    });
+
+    let client = ExpectContinueClient::new_with_options(
+        hyper::client::HttpConnector::new(),
+        1024,
+        Duration::from_secs(10),
+    );
+    let request = |addr: SocketAddr, path: &str, size: usize| {
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://{}{}", addr, path))
+            .body(Body::from(vec![b'x'; size]))
+            .ok()
+            .unwrap()
+    };
+
+    // The body is sent after `100 Continue` (without waiting for the timeout)
+    let resp = client
+        .request(request(addr, "/upload", 64 * 1024))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(
+        Some(&ExpectContinueOutcome::Continued),
+        resp.extensions().get::<ExpectContinueOutcome>()
+    );
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("100-continue 65536", body);
+
+    // The rejected body isn't sent, and the response is returned as it is
+    let resp = client
+        .request(request(addr, "/reject", 64 * 1024))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::PAYLOAD_TOO_LARGE, resp.status());
+    assert_eq!(
+        Some(&ExpectContinueOutcome::Rejected),
+        resp.extensions().get::<ExpectContinueOutcome>()
+    );
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("100-continue too large", body);
+
+    // The small bodies are sent as they are
+    let resp = client
+        .request(request(addr, "/upload", 16))
+        .await
+        .ok()
+        .unwrap();
+    assert!(resp.extensions().get::<ExpectContinueOutcome>().is_none());
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("- 16", body);
+
+    // The body is sent after the timeout without `100 Continue`
+    let mut client = client;
+    client.timeout = Duration::from_millis(100);
+    let resp = client
+        .request(request(silent_addr, "/upload", 4096))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(
+        Some(&ExpectContinueOutcome::TimedOut),
+        resp.extensions().get::<ExpectContinueOutcome>()
+    );
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("true 4096", body);
+
+    hyper_latch.countdown();
+}
+
+#[test]
+fn test_interceptors_by_id() {
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_http::{Interceptor, SimpleHTTP};
+
+    let mut simple_http = SimpleHTTP::new_for_hyper();
+    let first = simple_http.add_interceptor_fn(|_req| Ok(()));
+    let second = simple_http.add_interceptor_id_header("X-Request-Id");
+    let third = simple_http.add_interceptor_fn(|_req| Ok(()));
+    assert_eq!(
+        vec![first.get_id(), second.get_id(), third.get_id()],
+        simple_http.list_interceptors()
+    );
+
+    assert!(simple_http.delete_interceptor_by_id(&second.get_id()));
+    assert!(!simple_http.delete_interceptor_by_id(&second.get_id()));
+    assert_eq!(
+        vec![first.get_id(), third.get_id()],
+        simple_http.list_interceptors()
+    );
+    simple_http.clear_interceptors();
+    assert!(simple_http.list_interceptors().is_empty());
+
+    // By the service
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let mut base_service_setter = common_api.as_base_service_setter();
+    let interceptor = base_service_setter.add_interceptor_fn(|_req| Ok(()));
+    assert_eq!(
+        vec![interceptor.get_id()],
+        base_service_setter.list_interceptors()
+    );
+    assert!(base_service_setter.delete_interceptor_by_id(&interceptor.get_id()));
+    base_service_setter.add_interceptor_fn(|_req| Ok(()));
+    base_service_setter.clear_interceptors();
+    assert!(base_service_setter.list_interceptors().is_empty());
+}
+
+#[test]
+fn test_interceptor_slots() {
+    use std::sync::Arc;
+
+    use http_api_service::simple_http::{
+        Interceptor, InterceptorFunc, InterceptorSlot, SimpleHTTP,
+    };
+    use hyper::{Body, Request};
+
+    let noop = || Arc::new(InterceptorFunc::new(|_req: &mut Request<Body>| Ok(())));
+
+    let mut simple_http = SimpleHTTP::new_for_hyper();
+    let unnamed = simple_http.add_interceptor_fn(|_req| Ok(()));
+    let mut expected = vec![unnamed.get_id()];
+    // Whatever order they're added in
+    simple_http
+        .add_interceptor_slot(InterceptorSlot::new("logging").after("auth"), noop())
+        .unwrap();
+    simple_http
+        .add_interceptor_slot(InterceptorSlot::new("cache").before("auth"), noop())
+        .unwrap();
+    simple_http
+        .add_interceptor_slot(InterceptorSlot::new("auth"), noop())
+        .unwrap();
+    expected.extend(["cache", "auth", "logging"].iter().map(|v| v.to_string()));
+    assert_eq!(expected, simple_http.list_interceptors());
+
+    // The user ones slotted in between
+    simple_http
+        .add_interceptor_slot(
+            InterceptorSlot::new("sign").after("auth").before("logging"),
+            noop(),
+        )
+        .unwrap();
+    expected.insert(3, "sign".to_string());
+    assert_eq!(expected, simple_http.list_interceptors());
+
+    // Replaced by the same name
+    simple_http
+        .add_interceptor_slot(InterceptorSlot::new("auth"), noop())
+        .unwrap();
+    assert_eq!(expected, simple_http.list_interceptors());
+
+    // Contradictory
+    let err = simple_http
+        .add_interceptor_slot(
+            InterceptorSlot::new("bad").after("logging").before("cache"),
+            noop(),
+        )
+        .err()
+        .unwrap();
+    assert!(err.to_string().starts_with("InterceptorOrderError: "));
+    assert_eq!(expected, simple_http.list_interceptors());
+
+    assert!(simple_http.delete_interceptor_by_id("sign"));
+    expected.remove(3);
+    assert_eq!(expected, simple_http.list_interceptors());
+}
+
+#[tokio::test]
+async fn test_body_to_multipart_with_constraints() {
+    use formdata::FormData;
+    use hyper::header::CONTENT_TYPE;
+    use hyper::HeaderMap;
+
+    use http_api_service::bind_hyper::{
+        body_from_multipart, body_to_multipart_with_constraints,
+        get_content_type_from_multipart_boundary, Constraints, SizeLimit,
+    };
+    use http_api_service::simple_http::multer_multipart_to_hash_map;
+
+    let form_data = FormData {
+        fields: vec![
+            ("name".to_owned(), "Baxter".to_owned()),
+            ("age".to_owned(), "1 month".to_owned()),
+        ],
+        files: vec![],
+    };
+    let parse = |constraints: Constraints| {
+        let form_data = form_data.clone();
+        async move {
+            let (body, boundary) = body_from_multipart(&form_data).unwrap();
+            let mut headers = HeaderMap::new();
+            headers.insert(
+                CONTENT_TYPE,
+                get_content_type_from_multipart_boundary(boundary)
+                    .unwrap()
+                    .parse()
+                    .unwrap(),
+            );
+            let mut multipart = body_to_multipart_with_constraints(&headers, body, constraints)
+                .await
+                .unwrap();
+            multer_multipart_to_hash_map(&mut multipart)
+                .await
+                .map(|hash_map| hash_map.len())
+                .map_err(|e| e.to_string())
+        }
+    };
+
+    assert_eq!(Ok(2), parse(Constraints::new()).await);
+    assert_eq!(
+        Ok(2),
+        parse(Constraints::new().allowed_fields(vec!["name", "age"])).await
+    );
+    assert!(parse(Constraints::new().allowed_fields(vec!["name"]))
+        .await
+        .err()
+        .unwrap()
+        .contains("age"));
+    assert!(
+        parse(Constraints::new().size_limit(SizeLimit::new().per_field(4)))
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        Ok(2),
+        parse(Constraints::new().size_limit(SizeLimit::new().for_field("name", 16).per_field(8)))
+            .await
+    );
+    assert!(
+        parse(Constraints::new().size_limit(SizeLimit::new().whole_stream(16)))
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_multipart_spooling() {
+    use formdata::FormData;
+    use hyper::header::CONTENT_TYPE;
+    use hyper::HeaderMap;
+
+    use http_api_service::bind_hyper::{
+        body_from_multipart, body_to_multipart, get_content_type_from_multipart_boundary,
+    };
+    use http_api_service::simple_http::{
+        multer_multipart_to_hash_map_spooled, MultipartFieldData, MultipartSpooling,
+    };
+
+    let large = "x".repeat(64 * 1024);
+    let form_data = FormData {
+        fields: vec![
+            ("name".to_owned(), "Baxter".to_owned()),
+            ("bio".to_owned(), large.clone()),
+        ],
+        files: vec![],
+    };
+    let (body, boundary) = body_from_multipart(&form_data).unwrap();
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        get_content_type_from_multipart_boundary(boundary)
+            .unwrap()
+            .parse()
+            .unwrap(),
+    );
+    let mut multipart = body_to_multipart(&headers, body).await.unwrap();
+
+    let dir = std::env::temp_dir().join(format!("multipart-spooling-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let spooling = MultipartSpooling::new(1024).dir(&dir);
+    let hash_map = multer_multipart_to_hash_map_spooled(&mut multipart, &spooling)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        MultipartFieldData::Memory(bytes::Bytes::from("Baxter")),
+        hash_map["name"].2
+    );
+    match &hash_map["bio"].2 {
+        MultipartFieldData::Spooled(path) => {
+            assert!(path.starts_with(&dir));
+            assert_eq!(large, std::fs::read_to_string(path).unwrap());
+        }
+        data => panic!("not spooled: {:?}", data),
+    }
+    assert_eq!(1, std::fs::read_dir(&dir).unwrap().count());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_timeout() {
+    use std::time::Duration;
+
+    use http_api_service::bind_hyper;
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    let mut simple_http = SimpleHTTP::new_for_hyper();
+    assert_eq!(DEFAULT_TIMEOUT, simple_http.get_timeout());
+    simple_http.set_timeout(Duration::from_millis(1500));
+    assert_eq!(Duration::from_millis(1500), simple_http.get_timeout());
+    // Zero for the default
+    simple_http.set_timeout(Duration::default());
+    assert_eq!(DEFAULT_TIMEOUT, simple_http.get_timeout());
+
+    // By the service
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_timeout(Duration::from_secs(10));
+    assert_eq!(Duration::from_secs(10), base_service_setter.get_timeout());
+
+    // The deprecated millisecond ones
+    #[allow(deprecated)]
+    {
+        base_service_setter.set_timeout_millisecond(2500);
+        assert_eq!(2500, base_service_setter.get_timeout_millisecond());
+    }
+    assert_eq!(
+        Duration::from_millis(2500),
+        base_service_setter.get_timeout()
+    );
+}
@@ -0,0 +1,218 @@
+extern crate http_api_service;
+
+#[test]
+fn test_json_patch_diff() {
+    use serde::Serialize;
+    use serde_json::{json, Value};
+
+    use http_api_service::json_patch::{
+        apply_merge_patch, json_patch_diff, merge_patch_diff, PatchOperation,
+    };
+
+    #[derive(Serialize)]
+    struct Product {
+        name: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        tags: Vec<String>,
+        stock: Value,
+    }
+
+    let before = Product {
+        name: "Alice".to_string(),
+        note: Some("draft".to_string()),
+        tags: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+        stock: json!({"a/b": 1, "~c": {"count": 2}}),
+    };
+    let after = Product {
+        name: "Bob".to_string(),
+        note: None,
+        tags: vec!["a".to_string()],
+        stock: json!({"a/b": 1, "~c": {"count": 3}, "d": [1]}),
+    };
+
+    let operations = json_patch_diff(&before, &after).unwrap();
+    assert_eq!(
+        vec![
+            PatchOperation::Remove {
+                path: "/note".to_string()
+            },
+            PatchOperation::Replace {
+                path: "/name".to_string(),
+                value: json!("Bob")
+            },
+            PatchOperation::Add {
+                path: "/stock/d".to_string(),
+                value: json!([1])
+            },
+            PatchOperation::Replace {
+                path: "/stock/~0c/count".to_string(),
+                value: json!(3)
+            },
+            PatchOperation::Remove {
+                path: "/tags/2".to_string()
+            },
+            PatchOperation::Remove {
+                path: "/tags/1".to_string()
+            },
+        ],
+        operations
+    );
+    assert_eq!(
+        json!([{"op": "remove", "path": "/note"}, {"op": "replace", "path": "/name", "value": "Bob"}]),
+        serde_json::to_value(&operations[..2]).unwrap()
+    );
+    assert_eq!(
+        json!({"op": "add", "path": "/a~1b/0", "value": null}),
+        serde_json::to_value(
+            &json_patch_diff(&json!({"a/b": []}), &json!({"a/b": [null]})).unwrap()[0]
+        )
+        .unwrap()
+    );
+    assert!(json_patch_diff(&before, &before).unwrap().is_empty());
+
+    // The merge patch is applied back into `after`
+    let patch = merge_patch_diff(&before, &after).unwrap();
+    assert_eq!(
+        json!({"name": "Bob", "note": null, "tags": ["a"], "stock": {"~c": {"count": 3}, "d": [1]}}),
+        patch
+    );
+    let mut target = serde_json::to_value(&before).unwrap();
+    apply_merge_patch(&mut target, &patch);
+    assert_eq!(serde_json::to_value(&after).unwrap(), target);
+    assert_eq!(json!({}), merge_patch_diff(&after, &after).unwrap());
+    assert_eq!(
+        json!([1]),
+        merge_patch_diff(&json!({"a": 1}), &json!([1])).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_json_patch_api() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+    use serde::Serialize;
+    use serde_json::{json, Value};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::json_patch::{json_patch_diff, merge_patch_diff};
+    use http_api_service::simple_api::{PathParam, DEFAULT_SERDE_JSON_DESERIALIZER};
+
+    #[derive(Serialize)]
+    struct ProductPatch {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        name: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<Option<String>>,
+    }
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4069).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the Content-Type & the body
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let (parts, body_instance) = req.into_parts();
+            let body: Value =
+                serde_json::from_slice(&body::to_bytes(body_instance).await?).unwrap();
+            let content_type = parts.headers.get("Content-Type").unwrap().to_str().unwrap();
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(
+                json!({"method": parts.method.as_str(), "content_type": content_type, "body": body})
+                    .to_string(),
+            )))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let base_service_shared = common_api.as_base_service_shared();
+
+    let api_json_patch = base_service_setter.make_api_json_patch(
+        base_service_shared.clone(),
+        Method::PATCH,
+        "/products/1",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &Value::Null,
+    );
+    let operations = json_patch_diff(&json!({"name": "Alice"}), &json!({"name": "Bob"})).unwrap();
+    let echoed = api_json_patch
+        .call(None::<PathParam>, operations)
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(
+        json!({
+            "method": "PATCH",
+            "content_type": "application/json-patch+json",
+            "body": [{"op": "replace", "path": "/name", "value": "Bob"}]
+        }),
+        *echoed
+    );
+
+    // By the struct of `Option` fields, or by the diff
+    let api_merge_patch = base_service_setter.make_api_merge_patch(
+        base_service_shared.clone(),
+        Method::PATCH,
+        "/products/1",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &Value::Null,
+    );
+    let echoed = api_merge_patch
+        .call(
+            None::<PathParam>,
+            ProductPatch {
+                name: Some("Bob".to_string()),
+                note: Some(None),
+            },
+        )
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(
+        json!({
+            "method": "PATCH",
+            "content_type": "application/merge-patch+json",
+            "body": {"name": "Bob", "note": null}
+        }),
+        *echoed
+    );
+    let api_merge_patch = base_service_setter.make_api_merge_patch(
+        base_service_shared.clone(),
+        Method::PATCH,
+        "/products/1",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &Value::Null,
+    );
+    let echoed = api_merge_patch
+        .call(
+            None::<PathParam>,
+            merge_patch_diff(
+                &json!({"name": "Alice", "note": "draft"}),
+                &json!({"name": "Alice"}),
+            )
+            .unwrap(),
+        )
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(json!({"note": null}), echoed["body"]);
+
+    hyper_latch.countdown();
+}
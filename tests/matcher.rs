@@ -0,0 +1,87 @@
+extern crate http_api_service;
+
+#[test]
+fn test_request_matcher() {
+    use bytes::Bytes;
+    use http::Method;
+    use url::Url;
+
+    use http_api_service::bind_ureq::UreqRequest;
+    use http_api_service::matcher::{fnv1a_digest, RequestMatcher};
+
+    // The well-known FNV-1a values
+    assert_eq!(0xcbf29ce484222325, fnv1a_digest(b""));
+    assert_eq!(0xaf63dc4c8601ec8c, fnv1a_digest(b"a"));
+
+    let request_of = |url: &str, headers: Vec<(&str, &str)>, body: &'static str| {
+        let mut request = UreqRequest::new(Method::POST, Url::parse(url).unwrap());
+        request.headers = headers.into_iter().collect();
+        request.body = Some(Bytes::from(body));
+        request
+    };
+    let recorded = request_of(
+        "https://API.example.com:443/items?b=2&a=1&ts=100#top",
+        vec![("Content-Type", "application/json"), ("X-Request-Id", "1")],
+        "{\"name\":\"Baxter\"}",
+    );
+    let replayed = request_of(
+        "https://api.example.com/items?a=1&ts=200&b=2",
+        vec![
+            ("X-Request-Id", "2"),
+            ("content-type", " application/json "),
+        ],
+        "{\"name\":\"Baxter\"}",
+    );
+
+    // The timestamps differ
+    let matcher = RequestMatcher::new().with_header("Content-Type");
+    assert!(!matcher.matches(&recorded, &replayed));
+
+    let matcher = matcher.ignore_query_param("ts");
+    assert!(matcher.matches(&recorded, &replayed));
+    assert_eq!(
+        matcher.fingerprint(&recorded),
+        matcher.fingerprint(&replayed)
+    );
+    assert_eq!(
+        format!(
+            "POST\nhttps://api.example.com/items?a=1&b=2\ncontent-type: application/json\nbody: {:016x}",
+            fnv1a_digest(b"{\"name\":\"Baxter\"}")
+        ),
+        matcher.canonicalize(&recorded)
+    );
+
+    // The selected headers & the bodies
+    assert!(!matcher
+        .clone()
+        .with_header("x-request-id")
+        .matches(&recorded, &replayed));
+    let other_body = request_of(
+        "https://api.example.com/items?a=1&b=2",
+        vec![("Content-Type", "application/json")],
+        "{\"name\":\"Bob\"}",
+    );
+    assert!(!matcher.matches(&recorded, &other_body));
+    assert!(matcher
+        .clone()
+        .without_body()
+        .matches(&recorded, &other_body));
+
+    // The streamed hyper bodies are matched as absent
+    let request = hyper::Request::builder()
+        .method("GET")
+        .uri("http://example.com/items?b=2&a=1")
+        .body(hyper::Body::empty())
+        .unwrap();
+    assert_eq!(
+        "GET\nhttp://example.com/items?a=1&b=2\nbody: -",
+        RequestMatcher::new().canonicalize(&request)
+    );
+    assert_eq!(
+        "http://example.com/items",
+        RequestMatcher::new()
+            .ignore_query_param("a")
+            .ignore_query_param("b")
+            .canonicalize_url("http://example.com/items?b=2&a=1")
+    );
+}
@@ -0,0 +1,204 @@
+extern crate http_api_service;
+
+#[tokio::test]
+async fn test_negotiate_client() {
+    extern crate base64;
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::error::Error as StdError;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use hyper::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::negotiate::{
+        NegotiateAuthenticated, NegotiateClient, NegotiateContext, NegotiateProvider,
+    };
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4064).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // The fake exchange of two rounds: "client-1" -> "server-1" -> "client-2" -> the final "server-2",
+    // "/basic" challenges Basic only
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async move {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let path = req.uri().path().to_string();
+            let authorization = req
+                .headers()
+                .get(AUTHORIZATION)
+                .map(|v| v.to_str().unwrap().to_string())
+                .unwrap_or_default();
+            let body = hyper::body::to_bytes(req.into_body()).await?;
+
+            let (status, challenge) = if path == "/basic" {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    "Basic realm=\"intranet\"".to_string(),
+                )
+            } else {
+                let client_token = authorization
+                    .strip_prefix("Negotiate ")
+                    .map(|token| base64::decode(token).unwrap());
+                match client_token.as_deref() {
+                    None => (StatusCode::UNAUTHORIZED, "Negotiate".to_string()),
+                    Some(b"client-1") => (
+                        StatusCode::UNAUTHORIZED,
+                        format!("Negotiate {}", base64::encode("server-1")),
+                    ),
+                    Some(b"client-2") => (
+                        StatusCode::OK,
+                        format!("Negotiate {}", base64::encode("server-2")),
+                    ),
+                    _ => (StatusCode::UNAUTHORIZED, "Negotiate".to_string()),
+                }
+            };
+
+            let mut resp = Response::new(Body::from(format!(
+                "{} {}",
+                authorization,
+                String::from_utf8(body.to_vec()).unwrap()
+            )));
+            *resp.status_mut() = status;
+            resp.headers_mut()
+                .insert(WWW_AUTHENTICATE, challenge.parse().unwrap());
+            Ok::<Response<Body>, hyper::Error>(resp)
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    struct FakeContext {
+        host: String,
+        steps: Arc<Mutex<Vec<String>>>,
+        complete: bool,
+    }
+    impl NegotiateContext for FakeContext {
+        fn step(
+            &mut self,
+            server_token: Option<&[u8]>,
+        ) -> Result<Option<Vec<u8>>, Box<dyn StdError>> {
+            let server_token = server_token.map(|v| String::from_utf8(v.to_vec()).unwrap());
+            self.steps
+                .lock()
+                .unwrap()
+                .push(format!("{} {:?}", self.host, server_token));
+            match server_token.as_deref() {
+                None => Ok(Some(b"client-1".to_vec())),
+                Some("server-1") => Ok(Some(b"client-2".to_vec())),
+                Some("server-2") => {
+                    self.complete = true;
+                    Ok(None)
+                }
+                _ => Err(From::from("unexpected token")),
+            }
+        }
+        fn is_complete(&self) -> bool {
+            self.complete
+        }
+    }
+    struct FakeProvider {
+        steps: Arc<Mutex<Vec<String>>>,
+        broken: bool,
+    }
+    impl NegotiateProvider for FakeProvider {
+        fn new_context(&self, host: &str) -> Result<Box<dyn NegotiateContext>, Box<dyn StdError>> {
+            if self.broken {
+                return Err(From::from("no credentials cache"));
+            }
+            Ok(Box::new(FakeContext {
+                host: host.to_string(),
+                steps: self.steps.clone(),
+                complete: false,
+            }))
+        }
+    }
+
+    let steps = Arc::new(Mutex::new(Vec::new()));
+    let simple_http_of = |broken: bool| {
+        SimpleHTTP::new_with_timeout(
+            Arc::new(Mutex::new(NegotiateClient::new_for_hyper(Arc::new(
+                FakeProvider {
+                    steps: steps.clone(),
+                    broken,
+                },
+            )))),
+            VecDeque::new(),
+            DEFAULT_TIMEOUT,
+        )
+    };
+    let request = |path: &str| {
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://{}{}", addr, path))
+            .body(Body::from("item"))
+            .ok()
+            .unwrap()
+    };
+    let simple_http = simple_http_of(false);
+
+    // The original request is sent again with the tokens, and the server is authenticated by the final token
+    let resp = simple_http
+        .request(request("/intranet"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(
+        Some(&NegotiateAuthenticated { mutual: true }),
+        resp.extensions().get::<NegotiateAuthenticated>()
+    );
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!(
+        format!("Negotiate {} item", base64::encode("client-2")),
+        body
+    );
+    assert_eq!(
+        vec![
+            "127.0.0.1 None",
+            "127.0.0.1 Some(\"server-1\")",
+            "127.0.0.1 Some(\"server-2\")"
+        ],
+        *steps.lock().unwrap()
+    );
+
+    // The other challenges are returned as they are
+    steps.lock().unwrap().clear();
+    let resp = simple_http
+        .request(request("/basic"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, resp.status());
+    assert!(resp.extensions().get::<NegotiateAuthenticated>().is_none());
+    assert!(steps.lock().unwrap().is_empty());
+
+    // The failures of the provider return the challenge
+    let resp = simple_http_of(true)
+        .request(request("/intranet"))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, resp.status());
+    assert_eq!("Negotiate", resp.headers()[WWW_AUTHENTICATE]);
+
+    hyper_latch.countdown();
+}
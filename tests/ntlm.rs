@@ -0,0 +1,245 @@
+extern crate http_api_service;
+
+#[test]
+fn test_ntlm_messages() {
+    use http_api_service::ntlm::{
+        authenticate_message, negotiate_message, ntowf_v2, NtlmChallenge, NtlmCredentials,
+    };
+
+    // The test vectors of MS-NLMP 4.2.4 (NTLMv2)
+    let credentials = NtlmCredentials::new("Domain", "User", "Password");
+    assert_eq!(
+        [
+            0x0c, 0x86, 0x8a, 0x40, 0x3b, 0xfd, 0x7a, 0x93, 0xa3, 0x00, 0x1e, 0xf2, 0x2e, 0xf0,
+            0x2e, 0x3f
+        ],
+        ntowf_v2(&credentials)
+    );
+    assert!(!format!("{:?}", credentials).contains("Password"));
+
+    let target_info = [
+        0x02, 0x00, 0x0c, 0x00, 0x44, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x61, 0x00, 0x69, 0x00, 0x6e,
+        0x00, 0x01, 0x00, 0x0c, 0x00, 0x53, 0x00, 0x65, 0x00, 0x72, 0x00, 0x76, 0x00, 0x65, 0x00,
+        0x72, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let mut message = b"NTLMSSP\0".to_vec();
+    message.extend_from_slice(&2u32.to_le_bytes());
+    message.extend_from_slice(&[0; 8]);
+    message.extend_from_slice(&0xe28a_8233u32.to_le_bytes());
+    message.extend_from_slice(&[0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+    message.extend_from_slice(&[0; 8]);
+    message.extend_from_slice(&(target_info.len() as u16).to_le_bytes());
+    message.extend_from_slice(&(target_info.len() as u16).to_le_bytes());
+    message.extend_from_slice(&48u32.to_le_bytes());
+    message.extend_from_slice(&target_info);
+    let challenge = NtlmChallenge::parse(&message).unwrap();
+    assert_eq!(target_info.to_vec(), challenge.target_info);
+    assert_eq!(None, challenge.timestamp());
+    assert_eq!(None, NtlmChallenge::parse(&message[..40]));
+    assert_eq!(None, NtlmChallenge::parse(&negotiate_message()));
+
+    let message = authenticate_message(&credentials, &challenge, [0xaa; 8], 0);
+    assert_eq!(b"NTLMSSP\0", &message[..8]);
+    assert_eq!(3, message[8]);
+    let field = |offset: usize| {
+        let len = u16::from_le_bytes([message[offset], message[offset + 1]]) as usize;
+        let start = u32::from_le_bytes([
+            message[offset + 4],
+            message[offset + 5],
+            message[offset + 6],
+            message[offset + 7],
+        ]) as usize;
+        message[start..start + len].to_vec()
+    };
+    // NTProofStr
+    assert_eq!(
+        vec![
+            0x68, 0xcd, 0x0a, 0xb8, 0x51, 0xe5, 0x1c, 0x96, 0xaa, 0xbc, 0x92, 0x7b, 0xeb, 0xef,
+            0x6a, 0x1c
+        ],
+        field(20)[..16].to_vec()
+    );
+    // LMv2
+    assert_eq!(
+        vec![
+            0x86, 0xc3, 0x50, 0x97, 0xac, 0x9c, 0xec, 0x10, 0x25, 0x54, 0x76, 0x4a, 0x57, 0xcc,
+            0xcc, 0x19, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa
+        ],
+        field(12)
+    );
+    assert_eq!(
+        "User"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>(),
+        field(36)
+    );
+}
+
+#[tokio::test]
+async fn test_ntlm_client() {
+    extern crate base64;
+    extern crate fp_rust;
+    extern crate hmac;
+    extern crate hyper;
+    extern crate md5;
+
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use hmac::{Hmac, Mac};
+    use hyper::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+    use hyper::server::conn::AddrStream;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::ntlm::{ntowf_v2, NtlmClient, NtlmCredentials};
+    use http_api_service::simple_http::BaseClient;
+
+    const SERVER_CHALLENGE: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+    const TIMESTAMP: u64 = 0x01d9_0000_0000_0000;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4065).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // The connections are authenticated by the handshakes over them (the account is "CORP\alice" of "secret"),
+    // and the authenticated ones echo the body
+    let connections = Arc::new(AtomicUsize::new(0));
+    let handshakes = Arc::new(AtomicUsize::new(0));
+    let connections_for_thread = connections.clone();
+    let handshakes_for_thread = handshakes.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_: &AddrStream| {
+        connections_for_thread.fetch_add(1, Ordering::SeqCst);
+        let handshakes_for_thread_2 = handshakes_for_thread.clone();
+        let authenticated = Arc::new(Mutex::new(false));
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let handshakes_for_thread_3 = handshakes_for_thread_2.clone();
+                let authenticated = authenticated.clone();
+                async move {
+                    let message = req
+                        .headers()
+                        .get(AUTHORIZATION)
+                        .and_then(|v| v.to_str().unwrap().strip_prefix("NTLM "))
+                        .map(|token| base64::decode(token).unwrap());
+                    let body = hyper::body::to_bytes(req.into_body()).await?;
+                    let challenge = |token: Option<Vec<u8>>| {
+                        let mut resp = Response::new(Body::from("unauthorized"));
+                        *resp.status_mut() = StatusCode::UNAUTHORIZED;
+                        let value = match token {
+                            Some(token) => format!("NTLM {}", base64::encode(token)),
+                            None => "NTLM".to_string(),
+                        };
+                        resp.headers_mut()
+                            .insert(WWW_AUTHENTICATE, value.parse().unwrap());
+                        Ok::<Response<Body>, hyper::Error>(resp)
+                    };
+
+                    match message {
+                        Some(message) if message[8] == 1 => {
+                            handshakes_for_thread_3.fetch_add(1, Ordering::SeqCst);
+                            // The target info of MsvAvTimestamp & MsvAvEOL
+                            let mut target_info = vec![7, 0, 8, 0];
+                            target_info.extend_from_slice(&TIMESTAMP.to_le_bytes());
+                            target_info.extend_from_slice(&[0; 4]);
+                            let mut challenge_message = b"NTLMSSP\0".to_vec();
+                            challenge_message.extend_from_slice(&2u32.to_le_bytes());
+                            challenge_message.extend_from_slice(&[0; 8]);
+                            challenge_message.extend_from_slice(&0xe28a_8235u32.to_le_bytes());
+                            challenge_message.extend_from_slice(&SERVER_CHALLENGE);
+                            challenge_message.extend_from_slice(&[0; 8]);
+                            challenge_message
+                                .extend_from_slice(&(target_info.len() as u16).to_le_bytes());
+                            challenge_message
+                                .extend_from_slice(&(target_info.len() as u16).to_le_bytes());
+                            challenge_message.extend_from_slice(&48u32.to_le_bytes());
+                            challenge_message.extend_from_slice(&target_info);
+                            challenge(Some(challenge_message))
+                        }
+                        Some(message) if message[8] == 3 => {
+                            let len = u16::from_le_bytes([message[20], message[21]]) as usize;
+                            let offset = u32::from_le_bytes([
+                                message[24],
+                                message[25],
+                                message[26],
+                                message[27],
+                            ]) as usize;
+                            let nt_response = &message[offset..offset + len];
+                            // The timestamp of the challenge is used
+                            assert_eq!(&TIMESTAMP.to_le_bytes(), &nt_response[24..32]);
+
+                            let mut mac = <Hmac<md5::Md5> as Mac>::new_from_slice(&ntowf_v2(
+                                &NtlmCredentials::new("CORP", "alice", "secret"),
+                            ))
+                            .unwrap();
+                            mac.update(&SERVER_CHALLENGE);
+                            mac.update(&nt_response[16..]);
+                            if mac.finalize().into_bytes()[..] != nt_response[..16] {
+                                return challenge(None);
+                            }
+                            *authenticated.lock().unwrap() = true;
+                            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(body)))
+                        }
+                        _ if *authenticated.lock().unwrap() => {
+                            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(body)))
+                        }
+                        _ => challenge(None),
+                    }
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let request = |item: &str| {
+        Request::builder()
+            .method(Method::POST)
+            .uri(format!("http://{}/intranet", addr))
+            .body(Body::from(item.to_string()))
+            .ok()
+            .unwrap()
+    };
+    let mut client = NtlmClient::new_for_hyper(NtlmCredentials::new("CORP", "alice", "secret"));
+
+    // The handshake over a connection, sending the original request again
+    let resp = client.request(request("item-1")).await.ok().unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("item-1", body);
+    assert_eq!(1, connections.load(Ordering::SeqCst));
+    assert_eq!(1, handshakes.load(Ordering::SeqCst));
+
+    // The authenticated connection is reused
+    let resp = client.request(request("item-2")).await.ok().unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    let body = hyper::body::to_bytes(resp.into_body()).await.ok().unwrap();
+    assert_eq!("item-2", body);
+    assert_eq!(1, connections.load(Ordering::SeqCst));
+    assert_eq!(1, handshakes.load(Ordering::SeqCst));
+
+    // A new connection after close_idle()
+    client.close_idle();
+    let resp = client.request(request("item-3")).await.ok().unwrap();
+    assert_eq!(StatusCode::OK, resp.status());
+    assert_eq!(2, connections.load(Ordering::SeqCst));
+    assert_eq!(2, handshakes.load(Ordering::SeqCst));
+
+    // The rejected credentials return the challenge
+    let client = NtlmClient::new_for_hyper(NtlmCredentials::new("CORP", "alice", "wrong"));
+    let resp = client.request(request("item-4")).await.ok().unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, resp.status());
+    assert_eq!("NTLM", resp.headers()[WWW_AUTHENTICATE]);
+    assert_eq!(3, handshakes.load(Ordering::SeqCst));
+
+    hyper_latch.countdown();
+}
@@ -0,0 +1,55 @@
+extern crate http_api_service;
+
+#[tokio::test]
+async fn test_prelude() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::prelude::*;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4088).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                "\"{}\"",
+                req.uri().path()
+            ))))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    // Everything is from the prelude
+    let common_api = CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(Url::parse(&format!("http://{}", addr)).unwrap());
+    let api = base_service_setter.make_api_no_body(
+        common_api.as_base_service_shared(),
+        Method::GET,
+        "/items/{id}",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        &String::new(),
+    );
+
+    let path_param: PathParam = path_param!["id" => "7"];
+    assert_eq!("/items/7", *api.call(Some(path_param)).await.unwrap());
+    let query_param: QueryParam = query_param!["page" => "2"];
+    assert_eq!(Some(&"2".to_string()), query_param.get("page"));
+
+    hyper_latch.countdown();
+}
@@ -0,0 +1,448 @@
+extern crate http_api_service;
+
+#[tokio::test]
+async fn test_upload_queue() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::any_service::AnyService;
+    use http_api_service::common::MockClock;
+    use http_api_service::queue::{
+        FileQueueStore, QueueOptions, QueueRunReport, QueueStore, UploadQueue,
+    };
+    use http_api_service::simple_api::Headers;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3910).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let received = Arc::new(Mutex::new(Vec::<String>::new()));
+    let received_for_thread = received.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let received_for_thread_2 = received_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let received_for_thread_3 = received_for_thread_2.clone();
+                async move {
+                    let (parts, body_instance) = req.into_parts();
+                    let bytes = body::to_bytes(body_instance).await?;
+                    received_for_thread_3.lock().unwrap().push(format!(
+                        "{} {} {} {}",
+                        parts.method,
+                        parts.uri,
+                        parts
+                            .headers
+                            .get("x-client")
+                            .map(|v| v.to_str().unwrap())
+                            .unwrap_or_default(),
+                        String::from_utf8(bytes.to_vec()).unwrap()
+                    ));
+                    Ok::<Response<Body>, hyper::Error>(Response::new(Body::from("ok")))
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let dir = std::env::temp_dir().join(format!(
+        "http_api_service_test_queue_{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let options = QueueOptions {
+        max_attempts: 3,
+        initial_backoff: Duration::from_secs(60),
+        max_backoff: Duration::from_secs(60),
+        poll_interval: Duration::from_millis(50),
+    };
+    // The backoff is waited by advancing the clock
+    let clock = MockClock::new();
+
+    // Enqueue while the host is unreachable, the items are kept for retrying
+    {
+        let service = AnyService::new_for_hyper();
+        service.set_base_url(url::Url::parse("http://127.0.0.1:1").ok().unwrap());
+        let mut queue = UploadQueue::new_with_options(
+            service,
+            Arc::new(FileQueueStore::new(&dir).ok().unwrap()),
+            options.clone(),
+        );
+        queue.clock = Arc::new(clock.clone());
+
+        let header: Headers = vec![("X-Client", "cli")].into_iter().collect();
+        queue
+            .enqueue(Method::POST, "/events", Some(header), "text/plain", "first")
+            .ok()
+            .unwrap();
+        queue
+            .enqueue(Method::PUT, "/files/1", None, "text/plain", "second")
+            .ok()
+            .unwrap();
+
+        let report = queue.run_once().await.ok().unwrap();
+        assert_eq!(
+            QueueRunReport {
+                sent: 0,
+                failed: 2,
+                dropped: 0,
+            },
+            report
+        );
+        let pending = queue.pending().ok().unwrap();
+        assert_eq!(2, pending.len());
+        assert!(pending.iter().all(|item| item.attempts == 1));
+
+        // They're not due before the backoff
+        clock.advance(Duration::from_secs(30));
+        let report = queue.run_once().await.ok().unwrap();
+        assert_eq!(QueueRunReport::default(), report);
+    }
+
+    // The items survive the "restart" (a new queue over the same directory)
+    {
+        let service = AnyService::new_for_hyper();
+        service.set_base_url(
+            url::Url::parse(&("http://".to_string() + addr.to_string().as_str()))
+                .ok()
+                .unwrap(),
+        );
+        let store = Arc::new(FileQueueStore::new(&dir).ok().unwrap());
+        let mut queue = UploadQueue::new_with_options(service, store.clone(), options.clone());
+        queue.clock = Arc::new(clock.clone());
+
+        clock.advance(Duration::from_secs(30));
+        let report = queue.run_once().await.ok().unwrap();
+        assert_eq!(2, report.sent);
+        assert!(store.load_all().ok().unwrap().is_empty());
+        assert_eq!(
+            vec![
+                "POST /events cli first".to_string(),
+                "PUT /files/1  second".to_string(),
+            ],
+            *received.lock().unwrap()
+        );
+    }
+
+    // The background worker
+    {
+        let store = Arc::new(FileQueueStore::new(&dir).ok().unwrap());
+        UploadQueue::new_with_options(AnyService::new_for_hyper(), store.clone(), options.clone())
+            .enqueue(Method::POST, "/events", None, "text/plain", "third")
+            .ok()
+            .unwrap();
+
+        let base_url = "http://".to_string() + addr.to_string().as_str();
+        let options_for_thread = options.clone();
+        let store_for_thread = store.clone();
+        let worker = UploadQueue::spawn_worker(move || {
+            let service = AnyService::new_for_hyper();
+            service.set_base_url(url::Url::parse(&base_url).ok().unwrap());
+            UploadQueue::new_with_options(service, store_for_thread, options_for_thread)
+        })
+        .ok()
+        .unwrap();
+
+        let started = Instant::now();
+        while received.lock().unwrap().len() < 3 && started.elapsed() < Duration::from_secs(5) {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        worker.stop();
+
+        assert_eq!("POST /events  third", received.lock().unwrap()[2]);
+        assert!(store.load_all().ok().unwrap().is_empty());
+    }
+
+    let _ = std::fs::remove_dir_all(&dir);
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_offline_mode() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::any_service::AnyService;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::{MockClock, OfflineError};
+    use http_api_service::queue::{MemoryQueueStore, QueueRunReport, QueueStore, UploadQueue};
+    use http_api_service::simple_api::{
+        ApiBuilder, MemoCache, DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
+    };
+    use http_api_service::{path_param, query_param};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3950).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let received = Arc::new(Mutex::new(Vec::<String>::new()));
+    let received_for_thread = received.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let received_for_thread_2 = received_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let received_for_thread_3 = received_for_thread_2.clone();
+                async move {
+                    let (parts, body_instance) = req.into_parts();
+                    let bytes = body::to_bytes(body_instance).await?;
+                    received_for_thread_3.lock().unwrap().push(format!(
+                        "{} {} {}",
+                        parts.method,
+                        parts.uri,
+                        String::from_utf8(bytes.to_vec()).unwrap()
+                    ));
+                    Ok::<Response<Body>, hyper::Error>(Response::new(Body::from("\"ok\"")))
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let store: Arc<dyn QueueStore> = Arc::new(MemoryQueueStore::new());
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(base_url.clone());
+    base_service_setter.set_offline_queue(Some(store.clone()));
+    let base_service_shared = common_api.as_base_service_shared();
+
+    // The queue shares the store & the switch
+    let service = AnyService::new_for_hyper();
+    service.set_base_url(base_url);
+    let mut queue = UploadQueue::new(service, store.clone());
+    queue.offline = Some(base_service_setter.get_offline_switch());
+
+    let clock = MockClock::new();
+    let api_get_config = ApiBuilder::new(Method::GET, "/config")
+        .deserializer(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(base_service_shared.clone())
+        .memoized(Arc::new(MemoCache::new_with_clock(
+            Duration::from_secs(60),
+            16,
+            Arc::new(clock.clone()),
+        )));
+    let api_get_item = ApiBuilder::new(Method::GET, "/items/{id}")
+        .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(base_service_shared.clone());
+    let api_post_item = ApiBuilder::new(Method::POST, "/items/{id}")
+        .serializer(Arc::new(DEFAULT_SERDE_JSON_SERIALIZER))
+        .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .content_type("application/json")
+        .build(base_service_shared.clone());
+
+    // Online: memoized
+    let config: Arc<String> = api_get_config.call(None).await.ok().unwrap();
+    assert_eq!("ok", config.as_str());
+    assert_eq!(1, received.lock().unwrap().len());
+
+    base_service_setter.set_offline(true);
+    assert!(base_service_setter.is_offline());
+
+    // Reads: the memoized value is served even if it's expired, otherwise it fails
+    clock.advance(Duration::from_secs(61));
+    let config = api_get_config.call(None).await.ok().unwrap();
+    assert_eq!("ok", config.as_str());
+    let err = api_get_item
+        .call(Some(path_param!["id" => "1"]))
+        .await
+        .err()
+        .unwrap();
+    let err = err.downcast_ref::<OfflineError>().unwrap();
+    assert_eq!(None, err.queued_id);
+
+    // Writes: queued, and not flushed while it's offline
+    let err = api_post_item
+        .call_with_options(
+            None,
+            Some(path_param!["id" => "2"]),
+            Some(query_param!["source" => "offline"]),
+            "second".to_string(),
+        )
+        .await
+        .err()
+        .unwrap();
+    let queued_id = err
+        .downcast_ref::<OfflineError>()
+        .unwrap()
+        .queued_id
+        .clone();
+    let pending = store.load_all().ok().unwrap();
+    assert_eq!(1, pending.len());
+    assert_eq!(queued_id, Some(pending[0].id.clone()));
+    assert_eq!("/items/2?source=offline", pending[0].relative_url);
+    assert_eq!(
+        QueueRunReport::default(),
+        queue.run_once().await.ok().unwrap()
+    );
+    assert_eq!(1, received.lock().unwrap().len());
+
+    // Back online: flushed
+    base_service_setter.set_offline(false);
+    let report = queue.run_once().await.ok().unwrap();
+    assert_eq!(1, report.sent);
+    assert!(store.load_all().ok().unwrap().is_empty());
+    assert_eq!(
+        "POST /items/2?source=offline \"second\"",
+        received.lock().unwrap()[1]
+    );
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_upload_queue_retry_status() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::any_service::AnyService;
+    use http_api_service::common::MockClock;
+    use http_api_service::queue::{
+        FileQueueStore, QueueOptions, QueueRunReport, QueueStore, UploadQueue,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4093).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // "/flaky" fails twice (503, then 429), "/bad" is rejected (400)
+    let flaky_count = Arc::new(AtomicUsize::new(0));
+    let flaky_count_for_thread = flaky_count.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let flaky_count_for_thread_2 = flaky_count_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let flaky_count_for_thread_3 = flaky_count_for_thread_2.clone();
+                async move {
+                    let status = match req.uri().path() {
+                        "/flaky" => match flaky_count_for_thread_3.fetch_add(1, Ordering::SeqCst) {
+                            0 => StatusCode::SERVICE_UNAVAILABLE,
+                            1 => StatusCode::TOO_MANY_REQUESTS,
+                            _ => StatusCode::OK,
+                        },
+                        _ => StatusCode::BAD_REQUEST,
+                    };
+                    let mut res = Response::new(Body::empty());
+                    *res.status_mut() = status;
+                    Ok::<Response<Body>, hyper::Error>(res)
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let dir = std::env::temp_dir().join(format!(
+        "http_api_service_test_queue_status_{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = Arc::new(FileQueueStore::new(&dir).ok().unwrap());
+
+    let service = AnyService::new_for_hyper();
+    service.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let mut queue = UploadQueue::new_with_options(
+        service,
+        store.clone(),
+        QueueOptions {
+            max_attempts: 5,
+            initial_backoff: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(60),
+            poll_interval: Duration::from_millis(50),
+        },
+    );
+    let clock = MockClock::new();
+    queue.clock = Arc::new(clock.clone());
+
+    // A corrupt item file doesn't stall the others, it's quarantined
+    std::fs::write(dir.join("0000000000000-000000.json"), "{not json").unwrap();
+
+    let flaky_id = queue
+        .enqueue(Method::POST, "/flaky", None, "text/plain", "flaky")
+        .ok()
+        .unwrap();
+    queue
+        .enqueue(Method::POST, "/bad", None, "text/plain", "bad")
+        .ok()
+        .unwrap();
+
+    // 503: kept, 400: delivered (it'd be rejected again)
+    let report = queue.run_once().await.ok().unwrap();
+    assert_eq!(
+        QueueRunReport {
+            sent: 1,
+            failed: 1,
+            dropped: 0,
+        },
+        report
+    );
+    let pending = store.load_all().ok().unwrap();
+    assert_eq!(1, pending.len());
+    assert_eq!(flaky_id, pending[0].id);
+    assert_eq!(1, pending[0].attempts);
+    assert!(!dir.join("0000000000000-000000.json").exists());
+    assert!(dir.join("0000000000000-000000.json.corrupt").exists());
+
+    // 429: kept
+    clock.advance(Duration::from_secs(60));
+    let report = queue.run_once().await.ok().unwrap();
+    assert_eq!(1, report.failed);
+    assert_eq!(2, store.load_all().ok().unwrap()[0].attempts);
+
+    // 200: delivered
+    clock.advance(Duration::from_secs(60));
+    let report = queue.run_once().await.ok().unwrap();
+    assert_eq!(1, report.sent);
+    assert!(store.load_all().ok().unwrap().is_empty());
+    assert_eq!(3, flaky_count.load(Ordering::SeqCst));
+
+    let _ = std::fs::remove_dir_all(&dir);
+    hyper_latch.countdown();
+}
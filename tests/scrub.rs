@@ -0,0 +1,204 @@
+extern crate http_api_service;
+
+#[test]
+fn test_scrubbers() {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+
+    use http_api_service::scrub::{
+        HeaderScrubber, JsonPathScrubber, RegexScrubber, Scrubber, REDACTED,
+    };
+    use http_api_service::simple_api::Headers;
+
+    // Headers by names
+    let mut headers: Headers = vec![
+        ("authorization", "Bearer MY_TOKEN"),
+        ("Accept", "text/plain"),
+    ]
+    .into_iter()
+    .collect();
+    HeaderScrubber::default().scrub_headers(&mut headers);
+    assert_eq!(Some(REDACTED), headers.get("Authorization"));
+    assert_eq!(Some("text/plain"), headers.get("Accept"));
+
+    // Regex on header values and bodies
+    let regex_scrubber = RegexScrubber::from_patterns(&[r"[\w.]+@[\w.]+", r"\d{4}-\d{4}"])
+        .ok()
+        .unwrap();
+    let mut headers: Headers = vec![("X-User", "user@example.com")].into_iter().collect();
+    regex_scrubber.scrub_headers(&mut headers);
+    assert_eq!(Some(REDACTED), headers.get("X-User"));
+    assert_eq!(
+        "card [REDACTED] of [REDACTED]",
+        regex_scrubber.scrub_text("card 1234-5678 of user@example.com")
+    );
+    let binary = Bytes::from(vec![0xff, 0xfe]);
+    assert_eq!(
+        binary,
+        regex_scrubber.scrub_body("text/plain", binary.clone())
+    );
+
+    // JSON paths
+    let json_scrubber =
+        JsonPathScrubber::new(vec!["$.user.email", "$.items[*].card", "$.tokens[0]"]);
+    let body = Bytes::from(
+        r#"{"user":{"email":"user@example.com","name":"Alien"},"items":[{"card":"1"},{"card":"2"}],"tokens":["a","b"]}"#,
+    );
+    let scrubbed: serde_json::Value =
+        serde_json::from_slice(&json_scrubber.scrub_body("application/json", body.clone()))
+            .ok()
+            .unwrap();
+    assert_eq!(
+        serde_json::json!({
+            "user": {"email": REDACTED, "name": "Alien"},
+            "items": [{"card": REDACTED}, {"card": REDACTED}],
+            "tokens": [REDACTED, "b"],
+        }),
+        scrubbed
+    );
+    // Only the JSON bodies
+    assert_eq!(body, json_scrubber.scrub_body("text/plain", body.clone()));
+
+    // Combined
+    let scrubbers: Vec<Arc<dyn Scrubber>> = vec![
+        Arc::new(HeaderScrubber::default()),
+        Arc::new(regex_scrubber),
+    ];
+    let mut headers: Headers = vec![("Cookie", "id=1"), ("X-User", "user@example.com")]
+        .into_iter()
+        .collect();
+    scrubbers.scrub_headers(&mut headers);
+    assert_eq!(
+        vec![REDACTED, REDACTED],
+        headers.iter().map(|(_, v)| v).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_audit_log_scrubber() {
+    use std::sync::Arc;
+
+    use http_api_service::audit::{AuditLog, ChannelAuditSink};
+    use http_api_service::common::RequestOptions;
+    use http_api_service::scrub::RegexScrubber;
+
+    let (sink, receiver) = ChannelAuditSink::channel();
+    let mut audit_log = AuditLog::new(Arc::new(sink));
+    audit_log.scrubber = Some(Arc::new(
+        RegexScrubber::from_patterns(&[r"[\w.]+@[\w.]+"])
+            .ok()
+            .unwrap(),
+    ));
+
+    let options = RequestOptions {
+        tags: vec![("user".to_string(), "user@example.com".to_string())],
+        ..Default::default()
+    };
+    let audit_call = audit_log.begin("GET", "/users", &options).unwrap();
+    audit_log.finish(
+        audit_call,
+        None,
+        Some("failed for user@example.com".to_string()),
+    );
+
+    let record = receiver.try_recv().ok().unwrap();
+    assert_eq!(
+        vec![("user".to_string(), "[REDACTED]".to_string())],
+        record.tags
+    );
+    assert_eq!(Some("failed for [REDACTED]".to_string()), record.error);
+}
+
+#[test]
+fn test_request_formatter() {
+    use bytes::Bytes;
+    use http::method::Method;
+    use url::Url;
+
+    use http_api_service::bind_ureq::UreqRequest;
+    use http_api_service::scrub::{RequestFormatter, REDACTED};
+
+    let mut request = UreqRequest::new(
+        Method::POST,
+        Url::parse("http://localhost:3000/products").unwrap(),
+    );
+    request.headers.insert("Authorization", "Bearer MY_TOKEN");
+    request.headers.insert("X-Tenant-Secret", "MY_SECRET");
+    request.headers.insert_sensitive("X-Session", "MY_SESSION");
+    request.headers.insert("Accept", "application/json");
+    request.body = Some(Bytes::from("{\"name\":\"Baxter\"}"));
+
+    // Configured names, marked headers & the whole body
+    let formatted =
+        RequestFormatter::new(vec!["Authorization", "x-tenant-secret"], 1024).format(&request);
+    assert!(formatted.starts_with("POST http://localhost:3000/products "));
+    assert!(!formatted.contains("MY_TOKEN"));
+    assert!(!formatted.contains("MY_SECRET"));
+    assert!(!formatted.contains("MY_SESSION"));
+    assert!(formatted.contains(REDACTED));
+    assert!(formatted.contains("application/json"));
+    assert!(formatted.ends_with("\"{\\\"name\\\":\\\"Baxter\\\"}\""));
+
+    // Truncated bodies (at the char boundary) & non UTF-8 bodies
+    let formatter = RequestFormatter::new(Vec::<String>::new(), 4);
+    assert_eq!("\"abcd\"... <6 bytes>", formatter.format_body(b"abcdef"));
+    assert_eq!(
+        "\"abc\"... <5 bytes>",
+        formatter.format_body("abc\u{e9}".as_bytes())
+    );
+    assert_eq!("<2 bytes>", formatter.format_body(&[0xff, 0xfe]));
+
+    // The query values are masked (unless it's disabled)
+    let formatter = RequestFormatter::default();
+    assert_eq!(
+        "http://localhost/products?api_key=%5BREDACTED%5D&id=%5BREDACTED%5D",
+        formatter.format_url("http://localhost/products?api_key=MY_KEY&id=1")
+    );
+    assert_eq!(
+        "http://localhost/products",
+        formatter.format_url("http://localhost/products")
+    );
+    assert_eq!(
+        "/products?[REDACTED]",
+        formatter.format_url("/products?api_key=MY_KEY")
+    );
+    assert_eq!(
+        "http://localhost/products?api_key=MY_KEY",
+        formatter
+            .mask_query(false)
+            .format_url("http://localhost/products?api_key=MY_KEY")
+    );
+}
+
+#[test]
+fn test_logging_interceptor() {
+    use std::sync::{Arc, Mutex};
+
+    use http::method::Method;
+    use url::Url;
+
+    use http_api_service::bind_ureq::UreqRequest;
+    use http_api_service::scrub::RequestFormatter;
+    use http_api_service::simple_http::{Interceptor, InterceptorFunc};
+
+    // The lines go to the hook (instead of stdout)
+    let lines = Arc::new(Mutex::new(Vec::<String>::new()));
+    let lines_for_hook = lines.clone();
+    let interceptor = InterceptorFunc::new_for_logging(
+        RequestFormatter::default(),
+        Arc::new(move |line: &str| lines_for_hook.lock().unwrap().push(line.to_string())),
+    );
+
+    let mut request = UreqRequest::new(
+        Method::GET,
+        Url::parse("http://localhost:3000/products?token=MY_TOKEN").unwrap(),
+    );
+    request.headers.insert("Authorization", "Bearer MY_TOKEN");
+    interceptor.intercept(&mut request).ok().unwrap();
+
+    let lines = lines.lock().unwrap();
+    assert_eq!(1, lines.len());
+    assert!(lines[0].starts_with("GET http://localhost:3000/products?token=%5BREDACTED%5D "));
+    assert!(!lines[0].contains("MY_TOKEN"));
+}
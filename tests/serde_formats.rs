@@ -0,0 +1,83 @@
+extern crate http_api_service;
+
+#[test]
+fn test_serde_formats_datetime() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use serde::{Deserialize, Serialize};
+
+    use http_api_service::serde_formats::{self, EpochMillis, Rfc3339, Rfc3339Fractional};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Event {
+        #[serde(with = "serde_formats::rfc3339")]
+        created_at: std::time::SystemTime,
+        updated_at: Rfc3339Fractional,
+        expired_at: EpochMillis,
+        deleted_at: Option<Rfc3339>,
+    }
+
+    let time = UNIX_EPOCH + Duration::from_millis(1_625_128_200_123);
+    let event = Event {
+        created_at: UNIX_EPOCH + Duration::from_secs(1_625_128_200),
+        updated_at: Rfc3339Fractional(time),
+        expired_at: EpochMillis(time),
+        deleted_at: None,
+    };
+
+    let serialized = serde_json::to_string(&event).unwrap();
+    assert_eq!(
+        "{\"created_at\":\"2021-07-01T08:30:00Z\",\"updated_at\":\"2021-07-01T08:30:00.123Z\",\"expired_at\":1625128200123,\"deleted_at\":null}",
+        serialized
+    );
+    let deserialized: Event = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(event, deserialized);
+
+    // Offsets & arbitrary fractions
+    assert_eq!(
+        UNIX_EPOCH + Duration::from_millis(1_625_128_200_500),
+        serde_formats::parse_rfc3339("2021-07-01T16:30:00.5+08:00").unwrap()
+    );
+    // Before UNIX_EPOCH
+    let before = serde_formats::parse_rfc3339("1969-12-31T23:59:59.250Z").unwrap();
+    assert_eq!(-750, serde_formats::to_epoch_millis(&before));
+    assert_eq!(
+        "1969-12-31T23:59:59.250Z",
+        serde_formats::format_rfc3339(&before, true)
+    );
+    assert_eq!(before, serde_formats::from_epoch_millis(-750));
+
+    // Malformed
+    assert!(serde_formats::parse_rfc3339("2021-02-29T00:00:00Z").is_err());
+    assert!(serde_formats::parse_rfc3339("2021-07-01 08:30:00").is_err());
+    assert!(serde_json::from_str::<Rfc3339>("\"yesterday\"").is_err());
+}
+
+#[test]
+fn test_serde_formats_string_decimal() {
+    use serde::{Deserialize, Serialize};
+
+    use http_api_service::serde_formats::{self, StringDecimal};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Price {
+        #[serde(with = "serde_formats::string_decimal")]
+        amount: f64,
+        quantity: StringDecimal<u64>,
+    }
+
+    let price = Price {
+        amount: 12.5,
+        quantity: StringDecimal(3),
+    };
+    let serialized = serde_json::to_string(&price).unwrap();
+    assert_eq!("{\"amount\":\"12.5\",\"quantity\":\"3\"}", serialized);
+    assert_eq!(price, serde_json::from_str(&serialized).unwrap());
+
+    // Numbers are accepted as well
+    assert_eq!(
+        price,
+        serde_json::from_str("{\"amount\":12.5,\"quantity\":3}").unwrap()
+    );
+    assert!(serde_json::from_str::<Price>("{\"amount\":\"abc\",\"quantity\":\"3\"}").is_err());
+}
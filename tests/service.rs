@@ -0,0 +1,201 @@
+#[macro_use]
+extern crate http_api_service;
+
+use std::sync::Arc;
+
+use http::Method;
+use serde::{Deserialize, Serialize};
+
+use http_api_service::any_service::{HyperService, UreqService};
+use http_api_service::simple_api::{
+    PathParam, DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
+};
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Product {
+    id: u32,
+    name: String,
+}
+
+service! {
+    // The APIs of the products
+    struct ProductService: HyperService {
+        /// Get the product by its id
+        get_product(Method::GET, "/products/{id}") -> Product = Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER);
+        list_products(Method::GET, "/products") -> Vec<Product> = Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER);
+        create_product(
+            Method::POST,
+            "/products",
+            Product => "application/json", Arc::new(DEFAULT_SERDE_JSON_SERIALIZER),
+        ) -> Product = Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER);
+    }
+}
+
+service! {
+    pub struct ProductServiceForUreq: UreqService {
+        get_product(Method::GET, "/products/{id}") -> Product = Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER);
+    }
+}
+
+#[tokio::test]
+async fn test_service() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_hyper;
+    use http_api_service::bind_ureq;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4030).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // "/products" lists the products, "/products/{id}" gets one, and POST echoes the body
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let (parts, body_instance) = req.into_parts();
+            let body = match (parts.method.as_str(), parts.uri.path()) {
+                ("POST", _) => Body::from(body::to_bytes(body_instance).await?),
+                (_, "/products") => {
+                    Body::from("[{\"id\":1,\"name\":\"a\"},{\"id\":2,\"name\":\"b\"}]")
+                }
+                (_, path) => Body::from(format!(
+                    "{{\"id\":{},\"name\":\"p\"}}",
+                    path.trim_start_matches("/products/")
+                )),
+            };
+            Ok::<Response<Body>, hyper::Error>(Response::new(body))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    common_api
+        .as_base_service_setter()
+        .set_base_url(base_url.clone());
+    let product_service = ProductService::new(common_api.as_base_service_shared());
+
+    // The typed methods
+    let product = product_service
+        .get_product(Some(path_param!["id" => "3"]))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(
+        Product {
+            id: 3,
+            name: "p".to_string()
+        },
+        *product
+    );
+    let products = product_service.list_products(None).await.ok().unwrap();
+    assert_eq!(
+        vec![1, 2],
+        products.iter().map(|v| v.id).collect::<Vec<_>>()
+    );
+    let product = product_service
+        .create_product(
+            None,
+            Product {
+                id: 5,
+                name: "new".to_string(),
+            },
+        )
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!("new", product.name);
+
+    // The API handles
+    assert_eq!("/products/{id}", product_service.get_product.relative_url);
+    assert_eq!(
+        "application/json",
+        product_service.create_product.content_type
+    );
+    let products = product_service
+        .list_products
+        .call_with_options(None, None::<PathParam>, Some(query_param!["page" => "1"]))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(2, products.len());
+
+    // By ureq
+    let common_api_ureq = bind_ureq::CommonAPI::new_for_ureq();
+    common_api_ureq
+        .as_base_service_setter()
+        .set_base_url(base_url);
+    let product_service_ureq = ProductServiceForUreq::new(common_api_ureq.as_base_service_shared());
+    let product = product_service_ureq
+        .get_product(Some(path_param!["id" => "7"]))
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(7, product.id);
+
+    hyper_latch.countdown();
+}
+
+#[test]
+fn test_service_registry() {
+    use http_api_service::bind_hyper;
+    use http_api_service::service::{
+        register_service, service, unregister_service, ServiceRegistryError,
+    };
+
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    common_api
+        .as_base_service_setter()
+        .set_base_url(url::Url::parse("http://billing.example.com").ok().unwrap());
+    assert!(!register_service(
+        "billing",
+        common_api.as_base_service_shared()
+    ));
+    assert!(!register_service(
+        "products",
+        ProductService::new(common_api.as_base_service_shared())
+    ));
+
+    // Retrieve them by the names, with their types
+    let billing = service::<Arc<HyperService>>("billing").ok().unwrap();
+    assert_eq!(
+        "http://billing.example.com/",
+        billing.get_base_url().as_str()
+    );
+    assert!(ProductService::registered("products").is_ok());
+
+    // Another type or an unknown name
+    let err = ProductService::registered("billing").err().unwrap();
+    assert!(err.to_string().contains("ProductService"));
+    assert!(service::<ProductService>("unknown").is_err());
+    assert!(ProductServiceForUreq::registered("products").is_err());
+
+    // The registry is per thread
+    std::thread::spawn(|| {
+        let err: ServiceRegistryError = ProductService::registered("products").err().unwrap();
+        assert!(err.to_string().contains("isn't registered"));
+    })
+    .join()
+    .ok()
+    .unwrap();
+
+    // Replace/Unregister
+    assert!(register_service("billing", common_api));
+    assert!(service::<Arc<HyperService>>("billing").is_err());
+    assert!(unregister_service("billing"));
+    assert!(!unregister_service("billing"));
+    assert!(service::<Arc<HyperService>>("billing").is_err());
+}
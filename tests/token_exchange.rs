@@ -0,0 +1,173 @@
+extern crate http_api_service;
+
+#[tokio::test]
+async fn test_token_exchange() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Method, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::any_service::AnyService;
+    use http_api_service::bind_hyper;
+    use http_api_service::common::{
+        with_extensions, DelegatedIdentity, DelegationHeaders, Extensions, MockClock,
+    };
+    use http_api_service::simple_api::{ApiBuilder, DEFAULT_SERDE_JSON_DESERIALIZER};
+    use http_api_service::token_exchange::{
+        TokenExchangeError, TokenExchanger, GRANT_TYPE_TOKEN_EXCHANGE,
+    };
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3990).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // "/token" exchanges the tokens (except "revoked"), and "/resource" echoes the headers
+    let exchanges = Arc::new(AtomicUsize::new(0));
+    let exchanges_for_thread = exchanges.clone();
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let exchanges_for_thread_2 = exchanges_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let exchanges_for_thread_3 = exchanges_for_thread_2.clone();
+                async move {
+                    let (parts, body_instance) = req.into_parts();
+                    if parts.method == Method::POST && parts.uri.path() == "/token" {
+                        exchanges_for_thread_3.fetch_add(1, Ordering::SeqCst);
+                        let bytes = body::to_bytes(body_instance).await?;
+                        let form: HashMap<String, String> =
+                            url::form_urlencoded::parse(&bytes).into_owned().collect();
+                        assert_eq!(GRANT_TYPE_TOKEN_EXCHANGE, form["grant_type"]);
+                        assert_eq!("backend", form["audience"]);
+                        assert_eq!("client", form["client_id"]);
+                        if form["subject_token"] == "revoked" {
+                            let mut resp = Response::new(Body::from("{\"error\":\"invalid_grant\"}"));
+                            *resp.status_mut() = StatusCode::BAD_REQUEST;
+                            return Ok::<Response<Body>, hyper::Error>(resp);
+                        }
+                        let expires_in = if form["subject_token"] == "long-lived" {
+                            u64::MAX
+                        } else {
+                            60
+                        };
+                        return Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(
+                            format!(
+                                "{{\"access_token\":\"exchanged-{}\",\"issued_token_type\":\"urn:ietf:params:oauth:token-type:access_token\",\"token_type\":\"Bearer\",\"expires_in\":{}}}",
+                                form["subject_token"], expires_in
+                            ),
+                        )));
+                    }
+
+                    let header = |name: &str| {
+                        parts
+                            .headers
+                            .get(name)
+                            .map(|v| v.to_str().unwrap().to_string())
+                            .unwrap_or_default()
+                    };
+                    Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                        "\"obo={} tenant={} auth={}\"",
+                        header("X-On-Behalf-Of"),
+                        header("X-Tenant-Id"),
+                        header("Authorization")
+                    ))))
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let common_api = bind_hyper::CommonAPI::new_for_hyper();
+    let mut base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(base_url.clone());
+    base_service_setter.add_interceptor_delegation(DelegationHeaders::default());
+    let api_get_resource = ApiBuilder::new(Method::GET, "/resource")
+        .deserializer::<String>(Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER))
+        .build_no_body(common_api.as_base_service_shared());
+
+    // Without the identity
+    assert_eq!(
+        "obo= tenant= auth=",
+        *api_get_resource.call(None).await.ok().unwrap()
+    );
+
+    // The identity scoped per call
+    let mut extensions = Extensions::new();
+    extensions.insert(DelegatedIdentity::new("alice").with_tenant("tenant-a"));
+    let result = with_extensions(extensions, api_get_resource.call(None)).await;
+    assert_eq!("obo=alice tenant=tenant-a auth=", *result.ok().unwrap());
+
+    // The token exchange
+    let service = AnyService::new_for_hyper();
+    service.set_base_url(base_url);
+    let clock = MockClock::new();
+    let mut exchanger = TokenExchanger::new(service, "/token");
+    exchanger.audience = Some("backend".to_string());
+    exchanger.client_id = Some("client".to_string());
+    exchanger.client_secret = Some("secret".to_string());
+    exchanger.clock = Arc::new(clock.clone());
+
+    let identity = DelegatedIdentity::new("bob").with_subject_token("bob-token");
+    for _ in 0..2 {
+        let result = exchanger
+            .on_behalf_of(identity.clone(), api_get_resource.call(None))
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(
+            "obo=bob tenant= auth=Bearer exchanged-bob-token",
+            *result.ok().unwrap()
+        );
+    }
+    // Cached until it expires (60s minus the 30s margin)
+    assert_eq!(1, exchanges.load(Ordering::SeqCst));
+    clock.advance(Duration::from_secs(30));
+    exchanger
+        .on_behalf_of(identity, api_get_resource.call(None))
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!(2, exchanges.load(Ordering::SeqCst));
+
+    // The huge `expires_in` is clamped by the `max_ttl`
+    assert_eq!(
+        "exchanged-long-lived",
+        exchanger.exchange("long-lived").await.ok().unwrap()
+    );
+    exchanger.exchange("long-lived").await.ok().unwrap();
+    assert_eq!(3, exchanges.load(Ordering::SeqCst));
+    clock.advance(exchanger.max_ttl);
+    exchanger.exchange("long-lived").await.ok().unwrap();
+    assert_eq!(4, exchanges.load(Ordering::SeqCst));
+
+    // The rejected exchange fails without calling
+    let identity = DelegatedIdentity::new("carol").with_subject_token("revoked");
+    let err = exchanger
+        .on_behalf_of(identity, api_get_resource.call(None))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        Some(400),
+        err.downcast_ref::<TokenExchangeError>().unwrap().status
+    );
+
+    hyper_latch.countdown();
+}
@@ -9,6 +9,7 @@ async fn test_simple_api_for_readme_md() {
     extern crate hyper;
 
     use std::sync::Arc;
+    use std::time::Duration;
 
     use http::method::Method;
     use hyper::HeaderMap;
@@ -17,7 +18,7 @@ async fn test_simple_api_for_readme_md() {
     use http_api_service::path_param;
     use http_api_service::simple_api;
     use http_api_service::simple_api::{
-        DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
+        Headers, DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
     };
 
     use serde::{Deserialize, Serialize};
@@ -45,13 +46,13 @@ async fn test_simple_api_for_readme_md() {
 
     // Setup base_url
     base_service_setter.set_base_url(url::Url::parse("http://localhost:3000").ok().unwrap());
-    // Setup timeout_millisecond
-    base_service_setter.set_timeout_millisecond(10 * 1000);
+    // Setup timeout
+    base_service_setter.set_timeout(Duration::from_secs(10));
 
     // Add common headers for Authentication or other usages
     let mut header_map = match base_service_setter.get_default_header() {
         Some(header) => header,
-        None => Vec::new(),
+        None => Headers::new(),
     };
     header_map = bind_ureq::add_header_authentication_bearer(header_map, "MY_TOKEN")
         .ok()
@@ -87,7 +88,7 @@ async fn test_simple_api_for_readme_md() {
 
     let api_post_product = base_service_shared.make_api_has_body(
         base_service_shared.clone(),
-        "POST".to_string(),
+        Method::POST,
         "/products/{id}",
         "application/json",
         json_serializer.clone(),
@@ -119,7 +120,7 @@ async fn test_simple_api_for_readme_md() {
     // POST make_api_multipart
     let api_post_multipart = base_service_shared.make_api_multipart(
         base_service_shared.clone(),
-        "POST".to_string(),
+        Method::POST,
         "/form",
         json_deserializer.clone(),
         return_type_marker,
@@ -140,6 +141,7 @@ async fn test_simple_api_common() {
 
     use std::net::SocketAddr;
     use std::sync::Arc;
+    use std::time::Duration;
 
     use hyper::service::{make_service_fn, service_fn};
     use hyper::{body, Body, Method, Request, Response, Server};
@@ -150,7 +152,7 @@ async fn test_simple_api_common() {
     use http_api_service::bind_ureq::add_header_authentication_bearer;
     use http_api_service::simple_api;
     use http_api_service::simple_api::{
-        DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
+        Headers, DEFAULT_SERDE_JSON_DESERIALIZER, DEFAULT_SERDE_JSON_SERIALIZER,
     };
     use http_api_service::{path_param, query_param};
 
@@ -262,19 +264,23 @@ async fn test_simple_api_common() {
             .ok()
             .unwrap(),
     );
-    // Setup timeout_millisecond
-    base_service_setter.set_timeout_millisecond(10 * 1000);
+    // Setup timeout
+    base_service_setter.set_timeout(Duration::from_secs(10));
 
     let mut header_map = if let Some(header) = base_service_setter.get_default_header() {
         header
     } else {
-        Vec::new()
+        Headers::new()
     };
     header_map = add_header_authentication_bearer(header_map, "MY_TOKEN")
         .ok()
         .unwrap();
     base_service_setter.set_default_header(Some(header_map));
 
+    base_service_setter.add_interceptor_headers_fn(|headers| {
+        assert_eq!(Some("Bearer MY_TOKEN"), headers.get("Authorization"));
+        Ok(())
+    });
     base_service_setter.add_interceptor_fn(|req| {
         println!("REQ_CONTENT: {:?}", req);
         Ok(())
@@ -288,7 +294,7 @@ async fn test_simple_api_common() {
     {
         let api_get_products = base_service_setter.make_api_response_only(
             base_service_shared.clone(),
-            "GET".to_string(),
+            Method::GET,
             "/products",
             json_deserializer.clone(),
             return_type_marker,
@@ -302,7 +308,7 @@ async fn test_simple_api_common() {
         let serialized = serde_json::to_string(model.as_ref()).unwrap();
         println!("serialized: {:?}", serialized);
         assert_eq!(
-            "{\"name\":\"Baxter from server\",\"age\":\"1 month from server\",\"meta\":\"Parts { method: GET, uri: /products, version: HTTP/1.1, headers: {\\\"host\\\": \\\"127.0.0.1:3400\\\", \\\"user-agent\\\": \\\"ureq/2.1.1\\\", \\\"accept\\\": \\\"*/*\\\", \\\"authorization\\\": \\\"Bearer MY_TOKEN\\\", \\\"content-length\\\": \\\"0\\\"} }\"}",
+            "{\"name\":\"Baxter from server\",\"age\":\"1 month from server\",\"meta\":\"Parts { method: GET, uri: /products, version: HTTP/1.1, headers: {\\\"host\\\": \\\"127.0.0.1:3400\\\", \\\"user-agent\\\": \\\"ureq/2.6.2\\\", \\\"accept\\\": \\\"*/*\\\", \\\"authorization\\\": \\\"Bearer MY_TOKEN\\\", \\\"accept-encoding\\\": \\\"gzip\\\", \\\"content-length\\\": \\\"0\\\"} }\"}",
             serialized
         );
     }
@@ -310,7 +316,7 @@ async fn test_simple_api_common() {
     {
         let api_delete_product = base_service_setter.make_api_no_body(
             base_service_shared.clone(),
-            "DELETE".to_string(),
+            Method::DELETE,
             "/products/{id}",
             json_deserializer.clone(),
             return_type_marker,
@@ -331,7 +337,7 @@ async fn test_simple_api_common() {
         let serialized = serde_json::to_string(model.as_ref()).unwrap();
         println!("serialized: {:?}", serialized);
         assert_eq!(
-            "{\"name\":\"Baxter from server\",\"age\":\"1 month from server\",\"meta\":\"Parts { method: DELETE, uri: /products/3?soft=true, version: HTTP/1.1, headers: {\\\"host\\\": \\\"127.0.0.1:3400\\\", \\\"user-agent\\\": \\\"ureq/2.1.1\\\", \\\"accept\\\": \\\"*/*\\\", \\\"authorization\\\": \\\"Bearer MY_TOKEN\\\", \\\"content-length\\\": \\\"0\\\"} }\"}",
+            "{\"name\":\"Baxter from server\",\"age\":\"1 month from server\",\"meta\":\"Parts { method: DELETE, uri: /products/3?soft=true, version: HTTP/1.1, headers: {\\\"host\\\": \\\"127.0.0.1:3400\\\", \\\"user-agent\\\": \\\"ureq/2.6.2\\\", \\\"accept\\\": \\\"*/*\\\", \\\"authorization\\\": \\\"Bearer MY_TOKEN\\\", \\\"accept-encoding\\\": \\\"gzip\\\", \\\"content-length\\\": \\\"0\\\"} }\"}",
             serialized
         );
     }
@@ -339,7 +345,7 @@ async fn test_simple_api_common() {
     {
         let api_put_product = base_service_setter.make_api_has_body(
             base_service_shared.clone(),
-            "PUT".to_string(),
+            Method::PUT,
             "/products/{id}",
             "application/json",
             json_serializer.clone(),
@@ -363,7 +369,7 @@ async fn test_simple_api_common() {
         let serialized = serde_json::to_string(model.as_ref()).unwrap();
         println!("serialized: {:?}", serialized);
         assert_eq!(
-            "{\"name\":\"Alien  modified\",\"age\":\"3 years\",\"meta\":\"Parts { method: PUT, uri: /products/5, version: HTTP/1.1, headers: {\\\"host\\\": \\\"127.0.0.1:3400\\\", \\\"user-agent\\\": \\\"ureq/2.1.1\\\", \\\"accept\\\": \\\"*/*\\\", \\\"authorization\\\": \\\"Bearer MY_TOKEN\\\", \\\"content-type\\\": \\\"application/json\\\", \\\"content-length\\\": \\\"46\\\"} }\"}",
+            "{\"name\":\"Alien  modified\",\"age\":\"3 years\",\"meta\":\"Parts { method: PUT, uri: /products/5, version: HTTP/1.1, headers: {\\\"host\\\": \\\"127.0.0.1:3400\\\", \\\"user-agent\\\": \\\"ureq/2.6.2\\\", \\\"accept\\\": \\\"*/*\\\", \\\"authorization\\\": \\\"Bearer MY_TOKEN\\\", \\\"content-type\\\": \\\"application/json\\\", \\\"accept-encoding\\\": \\\"gzip\\\", \\\"content-length\\\": \\\"46\\\"} }\"}",
             serialized
         );
     }
@@ -388,12 +394,13 @@ async fn test_simple_api_formdata() {
 
     use formdata::FormData;
     use futures::executor::block_on;
+    use http::method::Method;
     use hyper::service::{make_service_fn, service_fn};
     use hyper::{Body, Request, Response, Server};
 
     use fp_rust::sync::CountDownLatch;
-    use http_api_service::bind_ureq;
     use http_api_service::bind_hyper::body_to_multipart;
+    use http_api_service::bind_ureq;
     use http_api_service::simple_api;
     use http_api_service::simple_http;
 
@@ -423,14 +430,17 @@ async fn test_simple_api_formdata() {
 
                     let (parts, body) = req.into_parts();
 
-                    let multipart = body_to_multipart(&parts.headers, body).await;
-
-                    println!("Error: {:?}", multipart.as_ref().err());
-
-                    let mut multipart = multipart.ok().unwrap();
-
-                    let hash_map =
-                        block_on(simple_http::multer_multipart_to_hash_map(&mut multipart));
+                    // NOTE: Block on another thread, the body might be still being received.
+                    let hash_map = tokio::task::spawn_blocking(move || {
+                        block_on(async move {
+                            let mut multipart = body_to_multipart(&parts.headers, body).await?;
+                            simple_http::multer_multipart_to_hash_map(&mut multipart).await
+                        })
+                        .map_err(|e| e.to_string())
+                    })
+                    .await
+                    .unwrap();
+                    println!("Error: {:?}", hash_map.as_ref().err());
                     let hash_map = hash_map.ok().unwrap();
 
                     let mut body_str = String::new();
@@ -486,7 +496,7 @@ async fn test_simple_api_formdata() {
     };
 
     let common_api = bind_ureq::CommonAPI::new_for_ureq();
-    let base_service_setter = common_api.as_base_service_setter();
+    let mut base_service_setter = common_api.as_base_service_setter();
     let base_service_shared = common_api.as_base_service_shared();
 
     base_service_setter.set_base_url(
@@ -495,10 +505,23 @@ async fn test_simple_api_formdata() {
             .unwrap(),
     );
 
+    // Inspect the whole request (method/url/headers/body) before sending
+    base_service_setter.add_interceptor_fn(|req| {
+        assert_eq!(Method::POST, req.method);
+        assert_eq!("/form", req.url.path());
+        assert!(req
+            .headers
+            .get("content-type")
+            .unwrap_or_default()
+            .starts_with("multipart/form-data"));
+        assert!(!req.body.as_ref().unwrap().is_empty());
+        Ok(())
+    });
+
     // POST make_api_multipart
     let api_post_multipart = base_service_setter.make_api_multipart(
         base_service_shared.clone(),
-        "POST".to_string(),
+        Method::POST,
         "/form",
         Arc::new(simple_api::DEFAULT_DUMMY_BYPASS_DESERIALIZER),
         &bytes::Bytes::new(),
@@ -531,3 +554,281 @@ async fn test_simple_api_formdata() {
 
     println!("OK");
 }
+
+#[cfg(feature = "test_runtime")]
+#[tokio::test]
+async fn test_simple_api_body_read_timeout() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use futures::stream;
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_ureq;
+    use http_api_service::simple_api;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3720).into();
+
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |_req: Request<Body>| async move {
+            // The headers and the first chunk arrive at once, the rest of the body is slow
+            let body = stream::unfold(0, |index| async move {
+                match index {
+                    0 => Some((Ok::<_, hyper::Error>("first"), 1)),
+                    1 => {
+                        tokio::time::sleep(Duration::from_millis(2000)).await;
+                        Some((Ok("second"), 2))
+                    }
+                    _ => None,
+                }
+            });
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::wrap_stream(body)))
+        }))
+    }));
+
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_ureq::CommonAPI::new_for_ureq();
+    let mut base_service_setter = common_api.as_base_service_setter();
+    let base_service_shared = common_api.as_base_service_shared();
+
+    base_service_setter.set_base_url(
+        url::Url::parse(&("http://".to_string() + addr.to_string().as_str()))
+            .ok()
+            .unwrap(),
+    );
+    base_service_setter.set_timeout(Duration::from_secs(10));
+    // Override the timeout per request
+    base_service_setter.add_interceptor_fn(|req| {
+        req.timeout = Some(Duration::from_millis(300));
+        Ok(())
+    });
+
+    let api_get_slow = base_service_setter.make_api_response_only(
+        base_service_shared.clone(),
+        Method::GET,
+        "/slow",
+        Arc::new(simple_api::DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &bytes::Bytes::new(),
+    );
+
+    let started = Instant::now();
+    let resp = api_get_slow.call().await;
+    println!("{:?}", resp.as_ref().err());
+    assert!(resp.is_err());
+    assert!(started.elapsed() < Duration::from_millis(1500));
+
+    hyper_latch.countdown();
+}
+
+#[cfg(feature = "test_runtime")]
+#[tokio::test]
+async fn test_streaming_body() {
+    extern crate formdata;
+
+    use std::io::Cursor;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use formdata::FormData;
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{body, Body, Request, Response, Server};
+    use url::Url;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_ureq::{
+        self, StreamingBody, StreamingBodyConsumedError, UreqRequest,
+    };
+    use http_api_service::simple_api;
+    use http_api_service::simple_http::SimpleHTTP;
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4055).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // Echo the Transfer-Encoding, the Content-Type and the body
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            let (parts, body_instance) = req.into_parts();
+            let header = |name| {
+                parts
+                    .headers
+                    .get(name)
+                    .map(|v: &hyper::header::HeaderValue| v.to_str().unwrap().to_string())
+                    .unwrap_or_default()
+            };
+            let body = body::to_bytes(body_instance).await?;
+            Ok::<Response<Body>, hyper::Error>(Response::new(Body::from(format!(
+                "te={} ct={} {}",
+                header("transfer-encoding"),
+                header("content-type"),
+                std::str::from_utf8(&body).unwrap()
+            ))))
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_ureq::CommonAPI::new_for_ureq();
+    let base_service_setter = common_api.as_base_service_setter();
+    let base_service_shared = common_api.as_base_service_shared();
+    base_service_setter.set_base_url(Url::parse(&format!("http://{}", addr)).ok().unwrap());
+
+    // The reader is sent chunked
+    let resp = base_service_shared
+        .do_request_streaming(
+            Method::PUT,
+            None,
+            "/export",
+            "text/csv",
+            None::<simple_api::PathParam>,
+            None::<simple_api::QueryParam>,
+            Cursor::new(b"a,b\n1,2\n".to_vec()),
+        )
+        .await
+        .ok()
+        .unwrap();
+    assert_eq!(
+        "te=chunked ct=text/csv a,b\n1,2\n",
+        std::str::from_utf8(&resp).unwrap()
+    );
+
+    // The multipart body is streamed as it's written
+    let api_post_multipart = base_service_setter.make_api_multipart_for_stream(
+        base_service_shared.clone(),
+        Method::POST,
+        "/form",
+        Arc::new(simple_api::DEFAULT_DUMMY_BYPASS_DESERIALIZER),
+        &bytes::Bytes::new(),
+    );
+    let form_data = FormData {
+        fields: vec![("name".to_owned(), "Baxter".to_owned())],
+        files: vec![],
+    };
+    let resp = api_post_multipart
+        .call(None::<simple_api::PathParam>, form_data)
+        .await
+        .ok()
+        .unwrap();
+    let resp = std::str::from_utf8(&resp).unwrap();
+    assert!(resp.starts_with("te=chunked ct=multipart/form-data; boundary="));
+    assert!(resp.contains("name=\"name\"\r\n\r\nBaxter\r\n"));
+
+    // The streaming body is sent once
+    let simple_http = SimpleHTTP::new_for_ureq();
+    let mut request = UreqRequest::new(
+        Method::POST,
+        Url::parse(&format!("http://{}/once", addr)).ok().unwrap(),
+    );
+    request.streaming_body = Some(StreamingBody::new(Cursor::new(b"once".to_vec())));
+    let resp = simple_http
+        .request(request.clone())
+        .await
+        .ok()
+        .unwrap()
+        .ok()
+        .unwrap();
+    assert_eq!("te=chunked ct= once", resp.into_string().ok().unwrap());
+    assert!(request.streaming_body.as_ref().unwrap().is_consumed());
+    let err = simple_http.request(request).await.err().unwrap();
+    assert!(err.downcast_ref::<StreamingBodyConsumedError>().is_some());
+
+    hyper_latch.countdown();
+}
+
+#[cfg(feature = "test_runtime")]
+#[tokio::test]
+async fn test_response_size() {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+
+    use http::method::Method;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_ureq;
+    use http_api_service::simple_api::{with_response_size, DEFAULT_SERDE_JSON_DESERIALIZER};
+
+    // "\"hello\"" by gzip
+    const GZIPPED: [u8; 27] = [
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 83, 202, 72, 205, 201, 201, 87, 2, 0, 178, 206, 3, 119, 7,
+        0, 0, 0,
+    ];
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 4085).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| async {
+        Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+            Ok::<Response<Body>, hyper::Error>(match req.uri().path() {
+                "/gzip" => Response::builder()
+                    .header("Content-Encoding", "gzip")
+                    .body(Body::from(GZIPPED.to_vec()))
+                    .unwrap(),
+                _ => Response::new(Body::from("\"hello\"")),
+            })
+        }))
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let common_api = bind_ureq::CommonAPI::new_for_ureq();
+    let base_service_setter = common_api.as_base_service_setter();
+    base_service_setter.set_base_url(url::Url::parse(&format!("http://{}", addr)).ok().unwrap());
+    let api_of = |relative_url: &str| {
+        base_service_setter.make_api_no_body(
+            common_api.as_base_service_shared(),
+            Method::GET,
+            relative_url,
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+            &String::new(),
+        )
+    };
+
+    let result = with_response_size(api_of("/plain").call(None)).await;
+    assert_eq!("hello", *result.value.ok().unwrap());
+    let response_size = result.response_size.unwrap();
+    assert_eq!(Some(7), response_size.content_length);
+    assert_eq!(Some(7), response_size.decoded_length);
+    assert_eq!(Some(false), response_size.decompressed);
+
+    // Decompressed by ureq transparently
+    let result = with_response_size(api_of("/gzip").call(None)).await;
+    assert_eq!("hello", *result.value.ok().unwrap());
+    let response_size = result.response_size.unwrap();
+    assert_eq!(None, response_size.content_length);
+    assert_eq!(None, response_size.content_encoding);
+    assert_eq!(Some(7), response_size.decoded_length);
+    assert_eq!(None, response_size.decompressed);
+
+    hyper_latch.countdown();
+}
@@ -20,12 +20,14 @@ async fn test_get_header() {
     extern crate fp_rust;
     use std::net::SocketAddr;
 
+    use http::method::Method;
     use hyper::header::CONTENT_TYPE;
     use hyper::service::{make_service_fn, service_fn};
     use hyper::{body, Body, Request, Response, Server};
-    use ureq::Agent;
+    use url::Url;
 
     use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_ureq::UreqRequest;
     use http_api_service::simple_http::SimpleHTTP;
 
     let hyper_latch = CountDownLatch::new(1);
@@ -92,16 +94,19 @@ async fn test_get_header() {
     */
 
     let simple_http = SimpleHTTP::new_for_ureq();
-    let agent = Agent::new();
-    let mut request = agent.request("POST", &("http://".to_string() + &addr.to_string()));
-    request = request.set(&CONTENT_TYPE.to_string(), "application/json");
+    let mut request = UreqRequest::new(
+        Method::POST,
+        Url::parse(&("http://".to_string() + &addr.to_string()))
+            .ok()
+            .unwrap(),
+    );
+    request
+        .headers
+        .insert(CONTENT_TYPE.to_string(), "application/json");
+    request.body = Some(bytes::Bytes::from(r#"{"library":"hyper"}"#));
 
     println!("{:?}", request);
-    let resp = simple_http
-        .request((request, Some(bytes::Bytes::from(r#"{"library":"hyper"}"#))))
-        .await
-        .ok()
-        .unwrap();
+    let resp = simple_http.request(request).await.ok().unwrap();
     let err = resp.as_ref().err();
     println!("{:?}", err);
     assert_eq!(false, resp.is_err());
@@ -113,7 +118,7 @@ async fn test_get_header() {
     let resp = resp.ok().unwrap();
     let body_str = resp.into_string().ok().unwrap();
 
-    assert_eq!("{\"library\":\"hyper\"}Parts { method: POST, uri: /, version: HTTP/1.1, headers: {\"host\": \"127.0.0.1:3000\", \"user-agent\": \"ureq/2.1.1\", \"accept\": \"*/*\", \"content-type\": \"application/json\", \"content-length\": \"19\"} }", body_str);
+    assert_eq!("{\"library\":\"hyper\"}Parts { method: POST, uri: /, version: HTTP/1.1, headers: {\"host\": \"127.0.0.1:3000\", \"user-agent\": \"ureq/2.6.2\", \"accept\": \"*/*\", \"content-type\": \"application/json\", \"accept-encoding\": \"gzip\", \"content-length\": \"19\"} }", body_str);
 
     started_latch.wait();
     println!("REQ",);
@@ -123,6 +128,54 @@ async fn test_get_header() {
     println!("OK");
 }
 
+#[cfg(feature = "test_runtime")]
+#[test]
+fn test_to_request_repeated_values() {
+    extern crate ureq;
+
+    use http::method::Method;
+    use url::Url;
+
+    use http_api_service::bind_ureq::{self, UreqRequest};
+    use http_api_service::{query_param, simple_api::QueryParam};
+
+    // The values of the same name are combined (not the last one only)
+    let mut request = UreqRequest::new(
+        Method::GET,
+        Url::parse("http://localhost/pets").ok().unwrap(),
+    );
+    request.headers.append("Accept", "application/json");
+    request.headers.append("accept", "text/plain");
+    request.headers.append("Cookie", "a=1");
+    request.headers.append("Cookie", "b=2");
+    request.headers.append("X-Trace", "t");
+    let built = request.to_request(&ureq::Agent::new());
+    assert_eq!(vec!["application/json, text/plain"], built.all("Accept"));
+    assert_eq!(vec!["a=1; b=2"], built.all("Cookie"));
+    assert_eq!(Some("t"), built.header("X-Trace"));
+
+    // The query params are appended to the query of the relative url (repeated keys are kept)
+    let common_api = bind_ureq::CommonAPI::new_for_ureq();
+    common_api
+        .as_base_service_shared()
+        .set_base_url(Url::parse("http://localhost/").ok().unwrap());
+    let req = common_api
+        .simple_api
+        .lock()
+        .unwrap()
+        .make_request(
+            Method::GET,
+            "/pets?tag=a&tag=b",
+            "",
+            None::<QueryParam>,
+            Some(query_param!["limit" => "10"]),
+            bytes::Bytes::new(),
+        )
+        .ok()
+        .unwrap();
+    assert_eq!(Some("tag=a&tag=b&limit=10"), req.url.query());
+}
+
 #[cfg(feature = "test_runtime")]
 #[tokio::test]
 async fn test_formdata() {
@@ -135,15 +188,17 @@ async fn test_formdata() {
 
     use formdata::FormData;
     use futures::executor::block_on;
+    use http::method::Method;
     use hyper::header::CONTENT_TYPE;
     use hyper::service::{make_service_fn, service_fn};
     use hyper::{Body, Request, Response, Server};
-    use ureq::{Agent, Header};
+    use ureq::Header;
+    use url::Url;
 
     use fp_rust::sync::CountDownLatch;
     use http_api_service::bind_ureq::body_from_multipart;
     use http_api_service::bind_ureq::{
-        body_to_multipart, get_content_type_from_multipart_boundary,
+        body_to_multipart, get_content_type_from_multipart_boundary, UreqRequest,
     };
     use http_api_service::simple_http;
     use http_api_service::simple_http::SimpleHTTP;
@@ -255,21 +310,22 @@ async fn test_formdata() {
     );
 
     let simple_http = SimpleHTTP::new_for_ureq();
-    let agent = Agent::new();
-    let mut request = agent.request("POST", &("http://".to_string() + &addr.to_string()));
-    request = request.set(
-        &CONTENT_TYPE.to_string(),
-        &get_content_type_from_multipart_boundary(boundary)
+    let mut request = UreqRequest::new(
+        Method::POST,
+        Url::parse(&("http://".to_string() + &addr.to_string()))
+            .ok()
+            .unwrap(),
+    );
+    request.headers.insert(
+        CONTENT_TYPE.to_string(),
+        get_content_type_from_multipart_boundary(boundary)
             .ok()
             .unwrap(),
     );
+    request.body = Some(body);
 
     println!("{:?}", request);
-    let resp = simple_http
-        .request((request, Some(body)))
-        .await
-        .ok()
-        .unwrap();
+    let resp = simple_http.request(request).await.ok().unwrap();
     let err = resp.as_ref().err();
     println!("{:?}", err);
     assert_eq!(false, resp.is_err());
@@ -296,3 +352,205 @@ async fn test_formdata() {
 
     println!("OK");
 }
+
+#[cfg(feature = "test_runtime")]
+#[tokio::test]
+async fn test_redirect_client() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use http::method::Method;
+    use hyper::header::{AUTHORIZATION, COOKIE, LOCATION};
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+    use url::Url;
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::bind_ureq::{RedirectClient, UreqRequest};
+    use http_api_service::simple_http::{SimpleHTTP, DEFAULT_TIMEOUT};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    // "/cross" redirects to "/echo" of the other origin, and "/echo" echoes the credentials
+    let addr: SocketAddr = ([127, 0, 0, 1], 4052).into();
+    let other_addr: SocketAddr = ([127, 0, 0, 1], 4053).into();
+    for bind_addr in [addr, other_addr] {
+        let hyper_latch_for_thread = hyper_latch.clone();
+        let server = Server::bind(&bind_addr).serve(make_service_fn(move |_| async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| async move {
+                let header = |name| {
+                    req.headers()
+                        .get(name)
+                        .map_or("-", |v: &hyper::header::HeaderValue| v.to_str().unwrap())
+                        .to_string()
+                };
+                let mut resp = Response::new(Body::from(format!(
+                    "{} {} {}",
+                    req.method(),
+                    header(AUTHORIZATION),
+                    header(COOKIE)
+                )));
+                match req.uri().path() {
+                    "/same" => {
+                        *resp.status_mut() = StatusCode::PERMANENT_REDIRECT;
+                        resp.headers_mut()
+                            .insert(LOCATION, "/echo".parse().unwrap());
+                    }
+                    "/cross" => {
+                        *resp.status_mut() = StatusCode::SEE_OTHER;
+                        resp.headers_mut().insert(
+                            LOCATION,
+                            format!("http://{}/echo", other_addr).parse().unwrap(),
+                        );
+                    }
+                    _ => {}
+                }
+                Ok::<Response<Body>, hyper::Error>(resp)
+            }))
+        }));
+        tokio::spawn(async {
+            let _ = server
+                .with_graceful_shutdown(async move {
+                    hyper_latch_for_thread.await;
+                })
+                .await;
+        });
+    }
+
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(RedirectClient::new_for_ureq())),
+        VecDeque::new(),
+        DEFAULT_TIMEOUT,
+    );
+    let request = |path: &str| {
+        let mut request = UreqRequest::new(
+            Method::PUT,
+            Url::parse(&format!("http://{}{}", addr, path))
+                .ok()
+                .unwrap(),
+        );
+        request.headers.insert("Authorization", "Bearer token");
+        request.headers.insert("Cookie", "session=1");
+        request.body = Some(bytes::Bytes::from("item"));
+        request
+    };
+
+    // The same origin keeps the credentials (and 308 keeps the method)
+    let resp = simple_http.request(request("/same")).await.ok().unwrap();
+    let body = resp.ok().unwrap().into_string().ok().unwrap();
+    assert_eq!("PUT Bearer token session=1", body);
+
+    // The cross-origin one strips the credentials (and 303 changes the method to GET)
+    let resp = simple_http.request(request("/cross")).await.ok().unwrap();
+    let body = resp.ok().unwrap().into_string().ok().unwrap();
+    assert_eq!("GET - -", body);
+
+    hyper_latch.countdown();
+}
+
+#[tokio::test]
+async fn test_request_bytes_deadline() {
+    use std::collections::VecDeque;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use http::method::Method;
+    use url::Url;
+
+    use http_api_service::bind_ureq::{RedirectClient, UreqRequest};
+    use http_api_service::simple_http::SimpleHTTP;
+
+    // "/redirect" redirects to "/stall" slowly, and "/stall" stalls mid-body
+    let listener = TcpListener::bind("127.0.0.1:4095").ok().unwrap();
+    thread::spawn(move || {
+        for stream in listener.incoming().take(2) {
+            let mut stream = stream.ok().unwrap();
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).ok().unwrap();
+            if String::from_utf8_lossy(&buf[..n]).starts_with("GET /redirect ") {
+                thread::sleep(Duration::from_millis(600));
+                let _ = stream.write_all(
+                    b"HTTP/1.1 307 Temporary Redirect\r\nLocation: /stall\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                );
+            } else {
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\nConnection: close\r\n\r\npartial",
+                );
+                let _ = stream.flush();
+                thread::sleep(Duration::from_secs(3));
+            }
+        }
+    });
+
+    let simple_http = SimpleHTTP::new_with_timeout(
+        Arc::new(Mutex::new(RedirectClient::new_for_ureq())),
+        VecDeque::new(),
+        Duration::from_millis(1000),
+    );
+    let mut request = UreqRequest::new(
+        Method::GET,
+        Url::parse("http://127.0.0.1:4095/redirect").ok().unwrap(),
+    );
+    request.timeout = Some(Duration::from_millis(1000));
+
+    // The deadline is counted from sending the first request (not restarted by the redirect),
+    // and it's enforced while the read is blocked
+    let start = Instant::now();
+    let err = simple_http.request_bytes(request).await.err().unwrap();
+    let elapsed = start.elapsed();
+    assert_eq!(
+        std::io::ErrorKind::TimedOut,
+        err.downcast_ref::<std::io::Error>().unwrap().kind()
+    );
+    assert!(elapsed >= Duration::from_millis(1000));
+    assert!(elapsed < Duration::from_millis(1400), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn test_body_to_multipart_with_constraints() {
+    use formdata::FormData;
+    use ureq::Header;
+
+    use http_api_service::bind_ureq::{
+        body_from_multipart, body_to_multipart_with_constraints,
+        get_content_type_from_multipart_boundary, Constraints, SizeLimit,
+    };
+    use http_api_service::simple_http::multer_multipart_to_hash_map;
+
+    let form_data = FormData {
+        fields: vec![("name".to_owned(), "Baxter".to_owned())],
+        files: vec![],
+    };
+    let (body, boundary) = body_from_multipart(&form_data).unwrap();
+    let headers = vec![Header::new(
+        "content-type",
+        &get_content_type_from_multipart_boundary(boundary).unwrap(),
+    )];
+
+    let mut multipart =
+        body_to_multipart_with_constraints(&headers, body.clone(), Constraints::new())
+            .await
+            .unwrap();
+    assert_eq!(
+        1,
+        multer_multipart_to_hash_map(&mut multipart)
+            .await
+            .unwrap()
+            .len()
+    );
+    let mut multipart = body_to_multipart_with_constraints(
+        &headers,
+        body,
+        Constraints::new().size_limit(SizeLimit::new().per_field(4)),
+    )
+    .await
+    .unwrap();
+    assert!(multer_multipart_to_hash_map(&mut multipart).await.is_err());
+}
@@ -0,0 +1,167 @@
+extern crate http_api_service;
+
+#[tokio::test]
+async fn test_watcher() {
+    extern crate fp_rust;
+    extern crate hyper;
+
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use futures::StreamExt;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+
+    use fp_rust::sync::CountDownLatch;
+    use http_api_service::any_service::AnyService;
+    use http_api_service::common::OfflineError;
+    use http_api_service::simple_api::DEFAULT_SERDE_JSON_DESERIALIZER;
+    use http_api_service::watcher::{WatchStatusError, Watcher};
+
+    let hyper_latch = CountDownLatch::new(1);
+
+    let addr: SocketAddr = ([127, 0, 0, 1], 3960).into();
+    let hyper_latch_for_thread = hyper_latch.clone();
+
+    // (version, body, status), and the count of 304 responses
+    let state = Arc::new(Mutex::new((1, "\"a\"".to_string(), 200)));
+    let not_modified = Arc::new(Mutex::new(0));
+    let state_for_thread = state.clone();
+    let not_modified_for_thread = not_modified.clone();
+
+    let server = Server::bind(&addr).serve(make_service_fn(move |_| {
+        let state_for_thread_2 = state_for_thread.clone();
+        let not_modified_for_thread_2 = not_modified_for_thread.clone();
+        async {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let (version, body, status) = state_for_thread_2.lock().unwrap().clone();
+                let not_modified_for_thread_3 = not_modified_for_thread_2.clone();
+                async move {
+                    let etag = format!("\"v{}\"", version);
+                    let response = Response::builder().header("ETag", etag.clone());
+                    let if_none_match = req.headers().get("If-None-Match");
+                    if status == 200 && if_none_match.map(|v| v.to_str().unwrap()) == Some(&etag) {
+                        *not_modified_for_thread_3.lock().unwrap() += 1;
+                        return Ok::<Response<Body>, hyper::Error>(
+                            response
+                                .status(StatusCode::NOT_MODIFIED)
+                                .body(Body::empty())
+                                .unwrap(),
+                        );
+                    }
+                    Ok::<Response<Body>, hyper::Error>(
+                        response.status(status).body(Body::from(body)).unwrap(),
+                    )
+                }
+            }))
+        }
+    }));
+    tokio::spawn(async {
+        let _ = server
+            .with_graceful_shutdown(async move {
+                hyper_latch_for_thread.await;
+            })
+            .await;
+    });
+
+    let base_url = url::Url::parse(&format!("http://{}", addr)).ok().unwrap();
+    let service = AnyService::new_for_hyper();
+    service.set_base_url(base_url.clone());
+
+    let mut watcher: Watcher<String> = Watcher::new(
+        service.clone(),
+        "/config",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+    );
+
+    // The first one is emitted
+    let value = watcher.poll_once().await.ok().unwrap();
+    assert_eq!(Some("a"), value.as_deref().map(|v| v.as_str()));
+    assert_eq!(Some("\"v1\""), watcher.get_etag());
+
+    // Not modified
+    assert_eq!(None, watcher.poll_once().await.ok().unwrap());
+    assert_eq!(1, *not_modified.lock().unwrap());
+
+    // A new version of the same value is not emitted
+    *state.lock().unwrap() = (2, "\"a\"".to_string(), 200);
+    assert_eq!(None, watcher.poll_once().await.ok().unwrap());
+    assert_eq!(Some("\"v2\""), watcher.get_etag());
+
+    // A changed value is emitted
+    *state.lock().unwrap() = (3, "\"b\"".to_string(), 200);
+    let value = watcher.poll_once().await.ok().unwrap();
+    assert_eq!(Some("b"), value.as_deref().map(|v| v.as_str()));
+    assert_eq!(Some("b"), watcher.get_last().as_deref().map(|v| v.as_str()));
+
+    // By ureq
+    let service_ureq = AnyService::new_for_ureq();
+    service_ureq.set_base_url(base_url.clone());
+    let mut watcher_ureq: Watcher<String> = Watcher::new(
+        service_ureq,
+        "/config",
+        Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+    );
+    let value = watcher_ureq.poll_once().await.ok().unwrap();
+    assert_eq!(Some("b"), value.as_deref().map(|v| v.as_str()));
+    assert_eq!(None, watcher_ureq.poll_once().await.ok().unwrap());
+    assert_eq!(2, *not_modified.lock().unwrap());
+
+    // Error statuses & offline
+    *state.lock().unwrap() = (4, "\"c\"".to_string(), 500);
+    let err = watcher.poll_once().await.err().unwrap();
+    assert_eq!(500, err.downcast_ref::<WatchStatusError>().unwrap().status);
+    let err = watcher_ureq.poll_once().await.err().unwrap();
+    assert_eq!(500, err.downcast_ref::<WatchStatusError>().unwrap().status);
+    assert_eq!(Some("\"v3\""), watcher.get_etag());
+    service.set_offline(true);
+    let err = watcher.poll_once().await.err().unwrap();
+    assert!(err.downcast_ref::<OfflineError>().is_some());
+    service.set_offline(false);
+
+    // The background worker
+    *state.lock().unwrap() = (5, "\"d\"".to_string(), 200);
+    let base_url_for_thread = base_url.clone();
+    let (worker, mut receiver) = Watcher::spawn(move || {
+        let service = AnyService::new_for_hyper();
+        service.set_base_url(base_url_for_thread);
+        let mut watcher = Watcher::<String>::new(
+            service,
+            "/config",
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        );
+        watcher.interval = Duration::from_millis(10);
+        watcher
+    })
+    .ok()
+    .unwrap();
+    assert_eq!("d", receiver.next().await.unwrap().as_str());
+    *state.lock().unwrap() = (6, "\"e\"".to_string(), 200);
+    assert_eq!("e", receiver.next().await.unwrap().as_str());
+    worker.stop();
+    assert_eq!(None, receiver.next().await);
+
+    // The sleeping worker is woken up to stop
+    let base_url_for_worker = base_url.clone();
+    let (worker, mut receiver) = Watcher::spawn(move || {
+        let service = AnyService::new_for_hyper();
+        service.set_base_url(base_url_for_worker);
+        let mut watcher = Watcher::<String>::new(
+            service,
+            "/config",
+            Arc::new(DEFAULT_SERDE_JSON_DESERIALIZER),
+        );
+        watcher.interval = Duration::from_secs(3600);
+        watcher
+    })
+    .ok()
+    .unwrap();
+    assert_eq!("e", receiver.next().await.unwrap().as_str());
+    let started = Instant::now();
+    drop(worker);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(None, receiver.next().await);
+
+    hyper_latch.countdown();
+}